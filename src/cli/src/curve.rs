@@ -0,0 +1,246 @@
+//! Axis response preview and pedal curve editor
+//!
+//! Runs a sweep of raw wheel or pedal positions through the real
+//! `InputTranslator` with the loaded calibration and prints the output as a
+//! table and an ASCII plot, so a curve can be checked without a game.
+//! `edit` redraws the plot after every change to a pedal's spline control
+//! points and saves the spline to the configuration when asked.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write as _};
+use std::path::Path;
+use thrustmaster_core::config::{CurveType, InputConfig};
+use thrustmaster_core::curve::spline_problem;
+use thrustmaster_core::testing::neutral_report;
+use thrustmaster_core::{Config, InputTranslator, NormalizedAxis};
+
+const PLOT_WIDTH: usize = 41;
+const PLOT_HEIGHT: usize = 12;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewAxis {
+    Steering,
+    Throttle,
+    Brake,
+    Clutch,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PreviewCurve {
+    Linear,
+    Squared,
+    Cubed,
+}
+
+impl From<PreviewCurve> for CurveType {
+    fn from(curve: PreviewCurve) -> Self {
+        match curve {
+            PreviewCurve::Linear => CurveType::Linear,
+            PreviewCurve::Squared => CurveType::Squared,
+            PreviewCurve::Cubed => CurveType::Cubed,
+        }
+    }
+}
+
+/// Print the response of `axis`, with `curve` replacing the configured pedal curve
+pub fn preview(config: &InputConfig, axis: PreviewAxis, curve: Option<PreviewCurve>, steps: usize) -> Result<()> {
+    let mut config = config.clone();
+    let deadzone = config.axis_tuning.steering.deadzone;
+    let curve_name = match (pedal_curve(&mut config, axis), curve) {
+        (None, Some(_)) => {
+            return Err(anyhow::anyhow!("Steering has no response curve, --curve applies to pedals only"));
+        }
+        (None, None) => format!("deadzone {:.2}", deadzone),
+        (Some(configured), Some(curve)) => {
+            *configured = curve.into();
+            format!("{:?}", curve)
+        }
+        (Some(configured), None) => format!("{:?} (configured)", configured),
+    };
+
+    let points = response(&config, axis, steps.max(2));
+    println!("{:?} response, {}", axis, curve_name);
+    println!();
+    println!("{:>8} {:>8}", "input", "output");
+    for (input, output) in &points {
+        println!("{:>7.1}% {:>7.1}%", input * 100.0, output * 100.0);
+    }
+    println!();
+    print!("{}", plot(&response(&config, axis, PLOT_WIDTH), axis == PreviewAxis::Steering));
+    Ok(())
+}
+
+/// Control points a new spline starts from, the current curve at every quarter
+const START_POINTS: usize = 5;
+
+const EDIT_HELP: &str = "\
+Commands, inputs and outputs in percent:
+  move <n> <input> <output>   move control point n
+  add <input> <output>        add a control point
+  remove <n>                  remove control point n
+  reset                       back to the points you started with
+  save                        save the spline to the configuration and quit
+  quit                        quit without saving
+  help                        show this list";
+
+/// Edit the spline of a pedal interactively, saving it to `config_path` on `save`
+pub fn edit(mut config: Config, config_path: &Path, axis: PreviewAxis) -> Result<()> {
+    let Some(current) = pedal_curve(&mut config.input_config, axis) else {
+        return Err(anyhow::anyhow!("Steering has no response curve, pick a pedal with --axis"));
+    };
+    let start = match current {
+        CurveType::Spline(points) => points.clone(),
+        other => (0..START_POINTS)
+            .map(|index| {
+                let input = index as f32 / (START_POINTS - 1) as f32;
+                [input, other.sample(input)]
+            })
+            .collect(),
+    };
+    let mut points = start.clone();
+
+    println!("{}", EDIT_HELP);
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        let mut preview = config.input_config.clone();
+        if let Some(curve) = pedal_curve(&mut preview, axis) {
+            *curve = CurveType::Spline(points.clone());
+        }
+        println!();
+        print!("{}", plot(&response(&preview, axis, PLOT_WIDTH), false));
+        for (index, [input, output]) in points.iter().enumerate() {
+            println!("  {}: {:>5.1}% -> {:>5.1}%", index, input * 100.0, output * 100.0);
+        }
+        print!("{:?} curve> ", axis);
+        std::io::stdout().flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            println!();
+            return Ok(());
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["save"] => {
+                if let Some(curve) = pedal_curve(&mut config.input_config, axis) {
+                    *curve = CurveType::Spline(points);
+                }
+                config
+                    .save_to_file(&config_path.to_string_lossy())
+                    .map_err(|e| anyhow::anyhow!("Failed to save config: {}", e))?;
+                println!("Saved the {:?} curve to {}", axis, config_path.display());
+                return Ok(());
+            }
+            ["quit"] | ["q"] => return Ok(()),
+            ["help"] => println!("{}", EDIT_HELP),
+            words => match edited_points(&points, &start, words) {
+                Ok(edited) => match spline_problem(&edited) {
+                    Some(problem) => println!("Not changed, {}", problem),
+                    None => points = edited,
+                },
+                Err(e) => println!("{}, `help` lists the commands", e),
+            },
+        }
+    }
+}
+
+/// Control points after the editing command `words`
+fn edited_points(points: &[[f32; 2]], start: &[[f32; 2]], words: &[&str]) -> Result<Vec<[f32; 2]>> {
+    let mut edited = points.to_vec();
+    match words {
+        ["reset"] => edited = start.to_vec(),
+        ["move", index, input, output] => {
+            let index: usize = index.parse()?;
+            let point = edited.get_mut(index).ok_or_else(|| anyhow::anyhow!("No control point {}", index))?;
+            *point = [percent(input)?, percent(output)?];
+        }
+        ["add", input, output] => {
+            let point = [percent(input)?, percent(output)?];
+            let at = edited.partition_point(|existing| existing[0] < point[0]);
+            edited.insert(at, point);
+        }
+        ["remove", index] => {
+            let index: usize = index.parse()?;
+            if index >= edited.len() {
+                return Err(anyhow::anyhow!("No control point {}", index));
+            }
+            edited.remove(index);
+        }
+        _ => return Err(anyhow::anyhow!("Unknown command")),
+    }
+    Ok(edited)
+}
+
+fn percent(value: &str) -> Result<f32> {
+    let value: f32 = value.trim_end_matches('%').parse()?;
+    Ok(value / 100.0)
+}
+
+fn pedal_curve(config: &mut InputConfig, axis: PreviewAxis) -> Option<&mut CurveType> {
+    match axis {
+        PreviewAxis::Steering => None,
+        PreviewAxis::Throttle => Some(&mut config.pedal_curves.throttle_curve),
+        PreviewAxis::Brake => Some(&mut config.pedal_curves.brake_curve),
+        PreviewAxis::Clutch => Some(&mut config.pedal_curves.clutch_curve),
+    }
+}
+
+/// `steps` evenly spaced (input, output) pairs, -1.0..=1.0 for steering and 0.0..=1.0 for pedals
+fn response(config: &InputConfig, axis: PreviewAxis, steps: usize) -> Vec<(f32, f32)> {
+    let mut translator = InputTranslator::new(config);
+
+    (0..steps)
+        .map(|step| {
+            let position = step as f32 / (steps - 1) as f32;
+            let mut report = neutral_report();
+            match axis {
+                PreviewAxis::Steering => {
+                    let input = position * 2.0 - 1.0;
+                    report.steering = (input * i16::MAX as f32).round() as i16;
+                    let output = translator.translate(report).steering;
+                    (input, NormalizedAxis::from_g29_steering(output).value())
+                }
+                _ => {
+                    let raw = (position * u8::MAX as f32).round() as u8;
+                    match axis {
+                        PreviewAxis::Throttle => report.throttle = raw,
+                        PreviewAxis::Brake => report.brake = raw,
+                        _ => report.clutch = raw,
+                    }
+                    let translated = translator.translate(report);
+                    let output = match axis {
+                        PreviewAxis::Throttle => translated.throttle,
+                        PreviewAxis::Brake => translated.brake,
+                        _ => translated.clutch,
+                    };
+                    (position, NormalizedAxis::from_g29_pedal(output).value())
+                }
+            }
+        })
+        .collect()
+}
+
+/// Output over input, one column per point; `signed` plots -1.0..=1.0 instead of 0.0..=1.0
+fn plot(points: &[(f32, f32)], signed: bool) -> String {
+    let mut grid = vec![vec![' '; points.len()]; PLOT_HEIGHT];
+    for (x, &(_, output)) in points.iter().enumerate() {
+        let level = if signed { (output + 1.0) / 2.0 } else { output };
+        let y = (level.clamp(0.0, 1.0) * (PLOT_HEIGHT - 1) as f32).round() as usize;
+        grid[PLOT_HEIGHT - 1 - y][x] = '*';
+    }
+
+    let (low, high) = if signed { ("-100%", "100%") } else { ("0%", "100%") };
+    let mut out = String::new();
+    for (row, cells) in grid.into_iter().enumerate() {
+        let label = match row {
+            0 => high,
+            r if r == PLOT_HEIGHT - 1 => low,
+            _ => "",
+        };
+        let _ = writeln!(out, "{:>5} |{}", label, cells.into_iter().collect::<String>());
+    }
+    let _ = writeln!(out, "      +{}", "-".repeat(points.len()));
+    let _ = writeln!(out, "       {:<w$}{}", low, high, w = points.len().saturating_sub(high.len()));
+    out
+}
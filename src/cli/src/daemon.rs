@@ -0,0 +1,361 @@
+//! Background mode of `tm-g29 run`
+//!
+//! On Unix the process detaches the classic way before the async runtime
+//! starts: fork, `setsid`, fork again so it can never reacquire a terminal,
+//! stdin from `/dev/null` and stdout/stderr appended to the log file. The
+//! working directory is kept so relative configuration paths still resolve.
+//! The daemon's PID goes into a pidfile, which `run --stop` and
+//! `run --status` read; stopping sends SIGTERM, which the signal task turns
+//! into a clean shutdown. The daemon holds an exclusive `flock` on the
+//! pidfile while it runs, so a second one refuses to start. Both files live
+//! in a directory only the user can enter, and neither is opened through a
+//! symlink.
+//!
+//! On Windows background mode means running as a service. When the service
+//! control manager started the process it is handed to the service
+//! dispatcher and a stop request shuts the translator down cleanly;
+//! otherwise it runs in the foreground. `run --stop` and `run --status` go
+//! through the service control manager.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use thrustmaster_core::TranslatorControl;
+
+/// Name of the pidfile, and of the Windows service
+pub const SERVICE_NAME: &str = "tm-g29";
+
+/// `$XDG_STATE_HOME/tm-g29`, else `~/.local/state/tm-g29`, the temp directory without a home
+fn state_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))
+        .map(|dir| dir.join(SERVICE_NAME))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Platform default pidfile, next to the control socket, in the state directory without `$XDG_RUNTIME_DIR`
+pub fn default_pid_file() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(state_dir)
+        .join(format!("{}.pid", SERVICE_NAME))
+}
+
+/// Log file a daemon writes to when `--log-file` is not given
+pub fn default_log_file() -> PathBuf {
+    state_dir().join(format!("{}.log", SERVICE_NAME))
+}
+
+/// Create the directory of `path` if needed, accessible to the user only
+fn create_parent(path: &Path) -> std::io::Result<()> {
+    let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) else {
+        return Ok(());
+    };
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// `options` opening `path` owner-only when created and never through a symlink
+fn open_private(path: &Path, options: &mut std::fs::OpenOptions) -> std::io::Result<std::fs::File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+    create_parent(path)?;
+    options.open(path)
+}
+
+/// Open `path` for appending log lines
+pub fn open_log(path: &Path) -> std::io::Result<std::fs::File> {
+    open_private(path, std::fs::OpenOptions::new().create(true).append(true))
+}
+
+/// Pidfile of the running daemon, locked while it runs and removed when dropped
+pub struct PidFile {
+    path: PathBuf,
+    _lock: std::fs::File,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// PID recorded in `pid_file`, if that process is still alive
+#[cfg(unix)]
+fn running_pid(pid_file: &Path) -> Option<i32> {
+    use std::io::Read;
+
+    let mut content = String::new();
+    open_private(pid_file, std::fs::OpenOptions::new().read(true)).ok()?.read_to_string(&mut content).ok()?;
+    let pid: i32 = content.trim().parse().ok()?;
+    // SAFETY: kill with signal 0 sends nothing, it only checks that the process exists
+    (pid > 0 && unsafe { libc::kill(pid, 0) } == 0).then_some(pid)
+}
+
+/// Open `pid_file` and take its exclusive lock, failing while another daemon holds it
+#[cfg(unix)]
+fn lock_pid_file(pid_file: &Path) -> Result<std::fs::File> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = open_private(pid_file, std::fs::OpenOptions::new().create(true).read(true).write(true))?;
+    // SAFETY: the descriptor belongs to `file`, which outlives the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == -1 {
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            let pid = running_pid(pid_file).map(|pid| format!(" with PID {}", pid)).unwrap_or_default();
+            return Err(anyhow::anyhow!("Translator already running{} ({})", pid, pid_file.display()));
+        }
+        return Err(error.into());
+    }
+    Ok(file)
+}
+
+/// Detach from the terminal, returning in the daemon process only
+///
+/// Must be called before any thread is started, the async runtime included.
+#[cfg(unix)]
+pub fn daemonize(pid_file: &Path, log_file: &Path) -> Result<PidFile> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    // Taken before forking: the lock belongs to the open file, which the daemon inherits
+    let mut lock = lock_pid_file(pid_file)?;
+    let log = open_log(log_file)?;
+    let null = std::fs::File::open("/dev/null")?;
+
+    // SAFETY: no other thread exists yet (see above), so the child starts from a consistent state
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error().into()),
+        0 => {}
+        child => {
+            // The first child exits as soon as the daemon is forked
+            // SAFETY: a null status pointer is allowed, the exit status is not needed
+            unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+            report_started(pid_file, log_file);
+            std::process::exit(0);
+        }
+    }
+    // SAFETY: setsid takes no arguments and only fails for a process group leader, reported below
+    if unsafe { libc::setsid() } == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // SAFETY: still single-threaded; the intermediate child leaves with _exit, running no destructors
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error().into()),
+        0 => {}
+        // SAFETY: _exit only ends the process
+        _ => unsafe { libc::_exit(0) },
+    }
+
+    // SAFETY: both descriptors stay open for the duration of the calls, dup2 leaves them untouched
+    unsafe {
+        libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+        libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+    }
+    lock.set_len(0)?;
+    writeln!(lock, "{}", std::process::id())?;
+    Ok(PidFile { path: pid_file.to_path_buf(), _lock: lock })
+}
+
+/// Tell the user where the daemon went, once it wrote its pidfile
+#[cfg(unix)]
+fn report_started(pid_file: &Path, log_file: &Path) {
+    for _ in 0..20 {
+        if let Some(pid) = running_pid(pid_file) {
+            println!("Translator running in the background, PID {}, logging to {}", pid, log_file.display());
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    println!("Translator started in the background, see {} if it does not show up", log_file.display());
+}
+
+/// Stop the daemon recorded in `pid_file` and wait for it to exit
+#[cfg(unix)]
+pub fn stop(pid_file: &Path) -> Result<()> {
+    let pid = running_pid(pid_file).ok_or_else(|| anyhow::anyhow!("Translator is not running ({})", pid_file.display()))?;
+    // SAFETY: kill has no memory arguments; `pid` is positive, so only that process is signalled
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    for _ in 0..100 {
+        // SAFETY: signal 0 only checks that the process exists
+        if unsafe { libc::kill(pid, 0) } != 0 {
+            println!("Translator stopped (PID {})", pid);
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    Err(anyhow::anyhow!("Translator (PID {}) did not stop within 5 seconds", pid))
+}
+
+#[cfg(unix)]
+pub fn status(pid_file: &Path) -> Result<()> {
+    match running_pid(pid_file) {
+        Some(pid) => {
+            println!("Translator running, PID {}", pid);
+            Ok(())
+        }
+        None => Err(anyhow::anyhow!("Translator is not running")),
+    }
+}
+
+/// Run `body` as the Windows service, or directly when not started as one
+#[cfg(windows)]
+pub fn run_service<F>(control: TranslatorControl, body: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    service::run(control, Box::new(body))
+}
+
+#[cfg(not(windows))]
+pub fn run_service<F>(_control: TranslatorControl, body: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    body()
+}
+
+#[cfg(windows)]
+pub fn stop(_pid_file: &Path) -> Result<()> {
+    service::stop()
+}
+
+#[cfg(windows)]
+pub fn status(_pid_file: &Path) -> Result<()> {
+    service::status()
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn stop(_pid_file: &Path) -> Result<()> {
+    Err(anyhow::anyhow!("Background mode is not supported on this platform"))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn status(_pid_file: &Path) -> Result<()> {
+    Err(anyhow::anyhow!("Background mode is not supported on this platform"))
+}
+
+#[cfg(windows)]
+mod service {
+    use super::SERVICE_NAME;
+    use anyhow::Result;
+    use std::ffi::OsString;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use thrustmaster_core::TranslatorControl;
+    use windows_service::service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    type Body = Box<dyn FnOnce() -> Result<()> + Send>;
+
+    /// What the dispatcher thread runs, set before it starts
+    static PENDING: Mutex<Option<(TranslatorControl, Body)>> = Mutex::new(None);
+
+    /// The dispatcher could not reach the service control manager
+    const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    pub fn run(control: TranslatorControl, body: Body) -> Result<()> {
+        *PENDING.lock().unwrap() = Some((control, body));
+        match service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+            Ok(()) => Ok(()),
+            Err(windows_service::Error::Winapi(e)) if e.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) => {
+                eprintln!("Not started as a service, running in the foreground");
+                let (_, body) = PENDING.lock().unwrap().take().expect("service body already taken");
+                body()
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let Some((control, body)) = PENDING.lock().ok().and_then(|mut pending| pending.take()) else {
+            return;
+        };
+        let stopping = control.clone();
+        let handler = service_control_handler::register(SERVICE_NAME, move |request| match request {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                stopping.request_shutdown();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        });
+        let Ok(handler) = handler else {
+            return;
+        };
+
+        let report = |state: ServiceState, exit_code: u32| {
+            let _ = handler.set_service_status(ServiceStatus {
+                service_type: ServiceType::OWN_PROCESS,
+                current_state: state,
+                controls_accepted: if state == ServiceState::Running {
+                    ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+                } else {
+                    ServiceControlAccept::empty()
+                },
+                exit_code: ServiceExitCode::Win32(exit_code),
+                checkpoint: 0,
+                wait_hint: Duration::default(),
+                process_id: None,
+            });
+        };
+        report(ServiceState::Running, 0);
+        let result = body();
+        if let Err(e) = &result {
+            tracing::error!("Translator service failed: {}", e);
+        }
+        report(ServiceState::Stopped, result.is_err() as u32);
+    }
+
+    fn open(access: ServiceAccess) -> Result<windows_service::service::Service> {
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+        manager
+            .open_service(SERVICE_NAME, access)
+            .map_err(|e| anyhow::anyhow!("Service {} not available, is it installed? {}", SERVICE_NAME, e))
+    }
+
+    pub fn stop() -> Result<()> {
+        let service = open(ServiceAccess::STOP | ServiceAccess::QUERY_STATUS)?;
+        service.stop()?;
+        for _ in 0..100 {
+            if service.query_status()?.current_state == ServiceState::Stopped {
+                println!("Service {} stopped", SERVICE_NAME);
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Err(anyhow::anyhow!("Service {} did not stop within 5 seconds", SERVICE_NAME))
+    }
+
+    pub fn status() -> Result<()> {
+        let status = open(ServiceAccess::QUERY_STATUS)?.query_status()?;
+        match status.current_state {
+            ServiceState::Running => {
+                match status.process_id {
+                    Some(pid) => println!("Service {} running, PID {}", SERVICE_NAME, pid),
+                    None => println!("Service {} running", SERVICE_NAME),
+                }
+                Ok(())
+            }
+            state => Err(anyhow::anyhow!("Service {} is not running ({:?})", SERVICE_NAME, state)),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! `tm-g29 install-service`: start the translator with the session
+//!
+//! Each platform gets its native supervisor, running `tm-g29 run` with the
+//! configuration file given at install time:
+//!
+//! - Linux: a systemd user unit, `Type=notify` with a watchdog, see `sd_notify`
+//! - macOS: a launchd agent kept alive after failures
+//! - Windows: an auto-start service, see `daemon`
+//!
+//! `--print` only shows what would be installed.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// What to install and whether to start it right away
+pub struct InstallOptions {
+    pub config_path: PathBuf,
+    /// Log file of the launchd agent or Windows service; systemd keeps a journal
+    pub log_file: Option<PathBuf>,
+    /// Show the unit instead of installing it
+    pub print: bool,
+    /// Install without enabling and starting it
+    pub no_start: bool,
+    /// Replace an installed unit
+    pub force: bool,
+}
+
+/// Watchdog timeout set in the systemd unit
+#[cfg(target_os = "linux")]
+const WATCHDOG_SEC: u32 = 10;
+
+/// launchd label of the agent, also its file name
+#[cfg(target_os = "macos")]
+const LAUNCHD_LABEL: &str = "io.github.leisrich.tm-g29";
+
+pub fn install_service(options: &InstallOptions) -> Result<()> {
+    let exe = std::env::current_exe()?;
+    // The service manager does not start in the current directory
+    let config = std::path::absolute(&options.config_path)?;
+    install(&exe, &config, options)
+}
+
+/// systemd user unit running the translator in the foreground
+#[cfg(target_os = "linux")]
+fn systemd_unit(exe: &Path, config: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Thrustmaster to G29 protocol translator\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         NotifyAccess=main\n\
+         ExecStart=\"{}\" --config \"{}\" run --foreground\n\
+         Restart=on-failure\n\
+         RestartSec=2\n\
+         WatchdogSec={}\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exe.display(),
+        config.display(),
+        WATCHDOG_SEC
+    )
+}
+
+/// launchd agent running the translator in the foreground
+#[cfg(target_os = "macos")]
+fn launchd_plist(exe: &Path, config: &Path, log_file: &Path) -> String {
+    let string = |value: &Path| format!("<string>{}</string>", xml_escape(&value.display().to_string()));
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        {}
+        <string>--config</string>
+        {}
+        <string>run</string>
+        <string>--foreground</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    {}
+    <key>StandardErrorPath</key>
+    {}
+</dict>
+</plist>
+"#,
+        LAUNCHD_LABEL,
+        string(exe),
+        string(config),
+        string(log_file),
+        string(log_file)
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Write `contents` to `path` unless a different unit is there and `force` is off
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn write_unit(path: &Path, contents: &str, force: bool) -> Result<()> {
+    if !force && path.exists() && std::fs::read_to_string(path)? != contents {
+        return Err(anyhow::anyhow!("{} already exists, use --force to replace it", path.display()));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run_command(program: &str, args: &[&str]) -> Result<()> {
+    let status = std::process::Command::new(program).args(args).status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("`{} {}` failed with {}", program, args.join(" "), status));
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn home() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow::anyhow!("HOME is not set"))
+}
+
+#[cfg(target_os = "linux")]
+fn install(exe: &Path, config: &Path, options: &InstallOptions) -> Result<()> {
+    let unit = systemd_unit(exe, config);
+    if options.print {
+        print!("{}", unit);
+        return Ok(());
+    }
+    let directory = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) => PathBuf::from(config_home),
+        None => home()?.join(".config"),
+    };
+    let name = format!("{}.service", crate::daemon::SERVICE_NAME);
+    write_unit(&directory.join("systemd").join("user").join(&name), &unit, options.force)?;
+    run_command("systemctl", &["--user", "daemon-reload"])?;
+    if options.no_start {
+        println!("Start with: systemctl --user enable --now {}", name);
+        return Ok(());
+    }
+    run_command("systemctl", &["--user", "enable", "--now", &name])?;
+    println!("Translator service enabled, logs with: journalctl --user -u {}", name);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn install(exe: &Path, config: &Path, options: &InstallOptions) -> Result<()> {
+    let log_file = match &options.log_file {
+        Some(path) => std::path::absolute(path)?,
+        None => home()?.join("Library").join("Logs").join(format!("{}.log", crate::daemon::SERVICE_NAME)),
+    };
+    let plist = launchd_plist(exe, config, &log_file);
+    if options.print {
+        print!("{}", plist);
+        return Ok(());
+    }
+    let path = home()?.join("Library").join("LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL));
+    write_unit(&path, &plist, options.force)?;
+    let path = path.display().to_string();
+    if options.no_start {
+        println!("Start with: launchctl load -w {}", path);
+        return Ok(());
+    }
+    if options.force {
+        // Reloading picks up a replaced plist
+        let _ = run_command("launchctl", &["unload", &path]);
+    }
+    run_command("launchctl", &["load", "-w", &path])?;
+    println!("Translator agent loaded, logging to {}", log_file.display());
+    Ok(())
+}
+
+#[cfg(windows)]
+fn install(exe: &Path, config: &Path, options: &InstallOptions) -> Result<()> {
+    use std::ffi::OsString;
+    use windows_service::service::{ServiceAccess, ServiceErrorControl, ServiceInfo, ServiceStartType, ServiceType};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    let name = crate::daemon::SERVICE_NAME;
+    let log_file = match &options.log_file {
+        Some(path) => std::path::absolute(path)?,
+        None => exe.with_file_name(format!("{}.log", name)),
+    };
+    let arguments = [
+        OsString::from("--config"),
+        config.as_os_str().to_os_string(),
+        OsString::from("--log-file"),
+        log_file.as_os_str().to_os_string(),
+        OsString::from("run"),
+    ];
+    if options.print {
+        println!(
+            "Service {}: \"{}\" --config \"{}\" --log-file \"{}\" run, started automatically",
+            name,
+            exe.display(),
+            config.display(),
+            log_file.display()
+        );
+        return Ok(());
+    }
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+        .map_err(|e| anyhow::anyhow!("Cannot reach the service control manager, run as administrator: {}", e))?;
+    if options.force {
+        if let Ok(existing) = manager.open_service(name, ServiceAccess::DELETE) {
+            existing.delete()?;
+        }
+    }
+    let info = ServiceInfo {
+        name: OsString::from(name),
+        display_name: OsString::from("Thrustmaster to G29 protocol translator"),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: exe.to_path_buf(),
+        launch_arguments: arguments.to_vec(),
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::START)
+        .map_err(|e| anyhow::anyhow!("Service {} not installed, use --force to replace an existing one: {}", name, e))?;
+    println!("Installed service {}", name);
+    if options.no_start {
+        println!("Start with: sc start {}", name);
+        return Ok(());
+    }
+    service.start::<OsString>(&[])?;
+    println!("Service {} started", name);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn install(_exe: &Path, _config: &Path, _options: &InstallOptions) -> Result<()> {
+    Err(anyhow::anyhow!("Installing a service is not supported on this platform"))
+}
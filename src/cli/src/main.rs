@@ -1,11 +1,28 @@
 //! CLI for Thrustmaster to G29 protocol translator
 
 use clap::{Parser, Subcommand};
-use thrustmaster_core::{Config, ProtocolTranslator};
+use thrustmaster_core::calibration::CalibrationSession;
+use thrustmaster_core::config::{BackupConfig, InputWakeup, SerialPolicy, SteeringGeometry, TargetWheel};
+use thrustmaster_core::device::models::{self, ModelDescription, ThrustmasterModel};
+use thrustmaster_core::inventory::{self, Inventory, InventoryEntry};
+use thrustmaster_core::presentation::Presenter;
+use thrustmaster_core::{bench, capture, conformance, host, identity, ipc, metrics, persist, pipe, profile, proton, recording, remote, replay, validation, Config, ProtocolTranslator, SessionRecorder, SharedSessionRecorder, SourceWheel, SteeringAngle, ThrustmasterDevice, TranslatorControl, VirtualG29Device, VirtualWheel};
 use anyhow::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::{info, warn, error};
 
+mod curve;
+mod daemon;
+mod install;
+mod monitor;
+mod prerequisites;
+mod self_update;
+mod signals;
+mod sd_notify;
+mod supervisor;
+mod visualize;
+mod watch;
+
 #[derive(Parser)]
 #[command(name = "tm-g29")]
 #[command(about = "Thrustmaster to G29 Protocol Translator")]
@@ -34,6 +51,106 @@ enum Commands {
         /// Run in foreground (don't daemonize)
         #[arg(short, long)]
         foreground: bool,
+        /// Pidfile of the background translator (Unix)
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+        /// Stop the background translator
+        #[arg(long, conflicts_with = "status")]
+        stop: bool,
+        /// Show whether the background translator is running
+        #[arg(long)]
+        status: bool,
+        /// Record translated input and export it as an evemu file on exit
+        #[arg(long)]
+        evemu: Option<PathBuf>,
+        /// Record input and force output and save them as a trace on exit
+        #[arg(long)]
+        trace: Option<PathBuf>,
+        /// Save the session, wheel and raw FFB reports included, as a capture on exit (JSON lines for .jsonl)
+        #[arg(long)]
+        capture: Option<PathBuf>,
+        /// Restart the translator automatically after recoverable failures
+        #[arg(long)]
+        supervise: bool,
+        /// Restarts allowed in a row before giving up (with --supervise)
+        #[arg(long, default_value = "10")]
+        max_restarts: u32,
+        /// Append supervisor incidents to this file (with --supervise)
+        #[arg(long)]
+        incident_log: Option<PathBuf>,
+        /// File written with recent translator state on SIGUSR2
+        #[arg(long)]
+        state_dump: Option<PathBuf>,
+        /// Control socket (Unix) or named pipe (Windows) to listen on
+        #[arg(long)]
+        control: Option<PathBuf>,
+        /// Don't reload the configuration when its file changes
+        #[arg(long)]
+        no_watch: bool,
+        /// Wait up to this many seconds for missing setup (wheel access, uinput) instead of failing
+        #[arg(long, value_name = "SECONDS")]
+        wait_setup: Option<u64>,
+        /// Logitech wheel to emulate, overriding `target` under [g29_config]
+        #[arg(long, value_enum)]
+        target: Option<TargetArg>,
+    },
+    /// Install a systemd user unit, launchd agent or Windows service running the translator
+    InstallService {
+        /// Print the unit instead of installing it
+        #[arg(long)]
+        print: bool,
+        /// Install without enabling and starting it
+        #[arg(long)]
+        no_start: bool,
+        /// Replace an installed unit
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show live wheel state of a running translator, e.g. for stream overlays
+    Monitor {
+        /// Attach to a running translator through its control channel
+        #[arg(long)]
+        remote: bool,
+        /// Control socket (Unix) or named pipe (Windows) of the translator
+        #[arg(long)]
+        endpoint: Option<PathBuf>,
+        /// Output line format
+        #[arg(long, value_enum, default_value = "text")]
+        format: monitor::MonitorFormat,
+        /// Milliseconds between samples
+        #[arg(long, default_value = "50")]
+        interval: u16,
+        /// Serve lines to TCP clients on this address instead of stdout
+        #[arg(long)]
+        listen: Option<std::net::SocketAddr>,
+    },
+    /// Control a running translator
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+        /// Control socket (Unix) or named pipe (Windows) of the translator
+        #[arg(long)]
+        endpoint: Option<PathBuf>,
+    },
+    /// Show or change the serial number policy of the virtual G29
+    Identity {
+        /// Save this policy to the configuration file
+        #[arg(long, value_enum)]
+        policy: Option<IdentityPolicy>,
+        /// Forget the saved random serial so the next run generates a new one
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Check the system setup and fix what can be fixed automatically
+    Doctor {
+        /// Apply fixes, e.g. register the virtual wheel in Proton prefixes
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Give the devices of the rig nicknames, shown by discover, status and monitor
+    Devices {
+        #[command(subcommand)]
+        command: Option<DevicesCommand>,
     },
     /// Device discovery and information
     Discover {
@@ -41,6 +158,12 @@ enum Commands {
         #[arg(short, long)]
         detailed: bool,
     },
+    /// List the wheel models the translator knows, or describe one
+    Models {
+        /// Print the report layout, effects and quirks known for this product ID (hex)
+        #[arg(long, value_parser = parse_pid)]
+        describe: Option<u16>,
+    },
     /// Calibrate the wheel
     Calibrate {
         /// Skip steering calibration
@@ -56,11 +179,13 @@ enum Commands {
         #[arg(short, long, default_value = "30")]
         duration: u64,
     },
-    /// Generate default configuration file
+    /// Generate default configuration file, or list and restore its backups
     Config {
         /// Force overwrite existing config
         #[arg(short, long)]
         force: bool,
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
     },
     /// Show FFB test patterns
     FfbTest {
@@ -71,6 +196,303 @@ enum Commands {
         #[arg(short, long, default_value = "5")]
         duration: u64,
     },
+    /// Record a session, wheel reports and FFB included, to replay without the wheel
+    Record {
+        /// Capture to write, as JSON lines if it ends in .jsonl
+        output: PathBuf,
+        /// Stop after this many seconds instead of on Ctrl+C
+        #[arg(short, long)]
+        duration: Option<u64>,
+    },
+    /// Replay a recorded session through the translator, or dry-run its FFB with --validate
+    Replay {
+        /// Capture written by `record` or `run --capture`
+        #[arg(required_unless_present = "validate", conflicts_with = "validate")]
+        capture: Option<PathBuf>,
+        /// Only run the FFB reports of a capture through the FFB engine
+        #[arg(long)]
+        validate: Option<PathBuf>,
+        /// Also play the translated reports on a virtual G29 at the recorded pace
+        #[arg(long)]
+        live: bool,
+        /// Configuration file whose FFB settings are evaluated, defaults to --config
+        #[arg(long)]
+        profile: Option<PathBuf>,
+    },
+    /// Check the virtual G29 stack against golden captures from a real G29
+    Conformance {
+        /// Directory of golden captures, one case per file
+        suite: PathBuf,
+        /// Largest accepted force difference in percent of full scale
+        #[arg(long, default_value = "2.0")]
+        tolerance: f32,
+    },
+    /// Inspect or convert capture files
+    Capture {
+        #[command(subcommand)]
+        command: CaptureCommand,
+    },
+    /// Measure translation latency, or the full OS round trip with --loopback
+    Bench {
+        /// Inject reports into a virtual G29 and read them back from its event node (Linux)
+        #[arg(long)]
+        loopback: bool,
+        /// Reports to measure
+        #[arg(short, long, default_value = "1000")]
+        samples: usize,
+    },
+    /// Translate raw reports from stdin to stdout, without devices
+    Pipe {
+        /// Wheel input reports to G29 reports, or game FFB reports to IFORCE packets
+        #[arg(long, value_enum, default_value = "input")]
+        direction: PipeDirection,
+        /// Bytes per report read from stdin, defaults to a full report of the model
+        #[arg(long)]
+        report_len: Option<usize>,
+    },
+    /// List, create and activate configuration profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Preview axis response curves with the current calibration
+    Curve {
+        #[command(subcommand)]
+        command: CurveCommand,
+    },
+    /// Update tm-g29 to the latest signed release
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+        /// Reinstall the latest release even if it is not newer
+        #[arg(long)]
+        force: bool,
+        /// GitHub repository (owner/name) to update from
+        #[arg(long, default_value = self_update::DEFAULT_REPO)]
+        repo: String,
+    },
+    /// Analyze a recorded session trace
+    Visualize {
+        /// Trace file written by `run --trace`
+        path: PathBuf,
+        /// Also render the plots as an SVG file
+        #[arg(long)]
+        svg: Option<PathBuf>,
+        /// Plot width in columns
+        #[arg(short, long, default_value = "72")]
+        width: usize,
+    },
+    /// Serve the attached wheel to a translator on another host, see [remote_config]
+    ServeWheel {
+        /// Address to accept translators on, defaults to remote_config.listen
+        #[arg(long)]
+        listen: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum CurveCommand {
+    /// Print input vs output for an axis
+    Preview {
+        /// Axis to preview
+        #[arg(long, value_enum, default_value = "throttle")]
+        axis: curve::PreviewAxis,
+        /// Pedal curve to try instead of the configured one
+        #[arg(long, value_enum)]
+        curve: Option<curve::PreviewCurve>,
+        /// Rows in the table
+        #[arg(long, default_value = "11")]
+        steps: usize,
+    },
+    /// Shape a pedal curve from spline control points, redrawn after every change
+    Edit {
+        /// Pedal whose curve is edited
+        #[arg(long, value_enum, default_value = "throttle")]
+        axis: curve::PreviewAxis,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum DevicesCommand {
+    /// List named devices and whether they are connected
+    List,
+    /// Name a connected device, selectable in the config as `device = "<nickname>"`
+    Name {
+        nickname: String,
+        /// Serial number of the device, needed when several match
+        #[arg(long)]
+        serial: Option<String>,
+        /// Product ID of the device (hex), any Thrustmaster device when neither is given
+        #[arg(long, value_parser = parse_pid)]
+        pid: Option<u16>,
+    },
+    /// Forget the device with this nickname or serial
+    Forget {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum ConfigCommand {
+    /// List the backups kept of the configuration file
+    Backups,
+    /// Replace the configuration file with one of its backups
+    Restore {
+        /// Backup to restore, 1 = the newest
+        #[arg(default_value = "1")]
+        backup: usize,
+        /// Restore a backup that does not load as a configuration
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum ProfileCommand {
+    /// Print the effective configuration of a profile with everything it extends merged in
+    Resolve {
+        /// Profile to resolve, defaults to --config
+        path: Option<PathBuf>,
+    },
+    /// List the per-game profiles and the processes activating them
+    List,
+    /// Create a per-game profile file from the current settings
+    Create {
+        name: String,
+        /// Game executable activating the profile when auto_switch is on, repeatable
+        #[arg(long)]
+        process: Vec<String>,
+        /// Overwrite an existing profile file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Apply a profile at every start, saved to the configuration file
+    Activate {
+        /// Profile to activate, none to go back to the base configuration
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum CaptureCommand {
+    /// Show the format version, header and record counts of a capture
+    Info {
+        path: PathBuf,
+    },
+    /// Rewrite a capture in the current format version
+    Upgrade {
+        path: PathBuf,
+        /// Where to write the upgraded capture
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum CtlCommand {
+    /// Show pause state, gain, profile and pipeline counters
+    Status,
+    /// Stop forwarding input and force feedback
+    Pause,
+    /// Resume after a pause
+    Resume,
+    /// Pause if running, resume if paused
+    TogglePause,
+    /// Reload the configuration file
+    Reload,
+    /// Write recent translator state as a trace file
+    Dump {
+        /// File name, written into the translator's `--state-dump` directory
+        path: PathBuf,
+    },
+    /// Stream FFB effect lifetime events until interrupted
+    Timeline,
+    /// Set the global FFB gain until the next reload
+    Gain {
+        /// 0.0 - 1.0
+        value: f32,
+    },
+    /// Set the steering center offset and per-side scaling until the next reload
+    Geometry {
+        /// Raw position read as center, -1.0 - 1.0
+        #[arg(allow_hyphen_values = true)]
+        center: f32,
+        /// Gain applied left of center
+        left: f32,
+        /// Gain applied right of center
+        right: f32,
+    },
+    /// Switch to a profile, or back to the configured one without a name
+    Profile {
+        name: Option<String>,
+    },
+    /// Stop the translator cleanly
+    Shutdown,
+}
+
+impl CtlCommand {
+    fn to_request(&self) -> ipc::IpcRequest {
+        match self {
+            Self::Status => ipc::IpcRequest::Status,
+            Self::Pause => ipc::IpcRequest::Pause,
+            Self::Resume => ipc::IpcRequest::Resume,
+            Self::TogglePause => ipc::IpcRequest::TogglePause,
+            Self::Reload => ipc::IpcRequest::Reload,
+            Self::Dump { path } => ipc::IpcRequest::DumpState(path.clone()),
+            Self::Timeline => ipc::IpcRequest::Timeline,
+            Self::Gain { value } => ipc::IpcRequest::SetGain(*value),
+            Self::Geometry { center, left, right } => ipc::IpcRequest::SetGeometry {
+                center_offset: *center,
+                geometry: SteeringGeometry { left_scale: *left, right_scale: *right },
+            },
+            Self::Profile { name } => ipc::IpcRequest::Profile(name.clone()),
+            Self::Shutdown => ipc::IpcRequest::Shutdown,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum IdentityPolicy {
+    Fixed,
+    FromWheel,
+    RandomPerBoot,
+    RandomPersistent,
+}
+
+impl From<IdentityPolicy> for SerialPolicy {
+    fn from(policy: IdentityPolicy) -> Self {
+        match policy {
+            IdentityPolicy::Fixed => SerialPolicy::Fixed,
+            IdentityPolicy::FromWheel => SerialPolicy::FromWheel,
+            IdentityPolicy::RandomPerBoot => SerialPolicy::RandomPerBoot,
+            IdentityPolicy::RandomPersistent => SerialPolicy::RandomPersistent,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum TargetArg {
+    G27,
+    G29,
+    G920,
+    Dfgt,
+}
+
+impl From<TargetArg> for TargetWheel {
+    fn from(target: TargetArg) -> Self {
+        match target {
+            TargetArg::G27 => TargetWheel::G27,
+            TargetArg::G29 => TargetWheel::G29,
+            TargetArg::G920 => TargetWheel::G920,
+            TargetArg::Dfgt => TargetWheel::Dfgt,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PipeDirection {
+    Input,
+    Ffb,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -82,37 +504,213 @@ enum FfbTestEffect {
     Square,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    let background = match &cli.command {
+        Commands::Run { stop: true, pid_file, .. } => {
+            return daemon::stop(pid_file.as_deref().unwrap_or(&daemon::default_pid_file()));
+        }
+        Commands::Run { status: true, pid_file, .. } => {
+            return daemon::status(pid_file.as_deref().unwrap_or(&daemon::default_pid_file()));
+        }
+        Commands::Run { foreground, .. } => !foreground,
+        _ => false,
+    };
+
+    // Detach before the runtime starts any thread
+    #[cfg(unix)]
+    let _pid_file = match &cli.command {
+        Commands::Run { pid_file, .. } if background => {
+            let log_file = cli.log_file.clone().unwrap_or_else(daemon::default_log_file);
+            Some(daemon::daemonize(pid_file.as_deref().unwrap_or(&daemon::default_pid_file()), &log_file)?)
+        }
+        _ => None,
+    };
+
+    register_virtual_backend();
+    let runtime = tokio::runtime::Runtime::new()?;
+    let control = TranslatorControl::default();
+    if background {
+        return daemon::run_service(control.clone(), move || runtime.block_on(run(cli, control)));
+    }
+    runtime.block_on(run(cli, control))
+}
+
+/// Create virtual G29s through the platform crate of this target
+fn register_virtual_backend() {
+    #[cfg(target_os = "linux")]
+    thrustmaster_core::device::virtual_g29::register_backend(thrustmaster_linux::virtual_backend);
+    #[cfg(windows)]
+    thrustmaster_core::device::virtual_g29::register_backend(thrustmaster_windows::virtual_backend);
+    #[cfg(target_os = "macos")]
+    thrustmaster_core::device::virtual_g29::register_backend(thrustmaster_macos::virtual_backend);
+}
+
+async fn run(cli: Cli, control: TranslatorControl) -> Result<()> {
     // Initialize logging
     init_logging(&cli)?;
 
     info!("Thrustmaster to G29 Protocol Translator v{}", env!("CARGO_PKG_VERSION"));
 
+    // Naming works while the config selects a device that has no name yet
+    if let Commands::Devices { command } = &cli.command {
+        return manage_devices(command.clone().unwrap_or(DevicesCommand::List));
+    }
+
+    // A broken configuration must not stop its restore
+    if let Commands::Config { command: Some(command), .. } = &cli.command {
+        return manage_config_backups(&cli.config, command.clone());
+    }
+
     // Load or create configuration
     let config = load_config(&cli.config).await?;
 
     match cli.command {
-        Commands::Run { foreground } => {
-            run_translator(config, foreground).await
+        Commands::Run { foreground, evemu, trace, capture, supervise, max_restarts, incident_log, state_dump, control: endpoint, no_watch, wait_setup, target, .. } => {
+            let mut config = config;
+            if let Some(target) = target {
+                config.g29_config.target = target.into();
+            }
+            let options = RunOptions {
+                foreground,
+                evemu,
+                trace,
+                capture,
+                supervise: supervise.then(|| supervisor::SupervisorPolicy {
+                    max_restarts,
+                    incident_log,
+                    ..Default::default()
+                }),
+                config_path: cli.config.clone(),
+                state_dump: state_dump.unwrap_or_else(|| std::env::temp_dir().join("tm-g29-state.trace")),
+                control_endpoint: endpoint.unwrap_or_else(ipc::default_endpoint),
+                watch: !no_watch,
+                wait_setup: wait_setup.map(std::time::Duration::from_secs),
+                control,
+            };
+            run_translator(config, options).await
         }
+        Commands::InstallService { print, no_start, force } => {
+            install::install_service(&install::InstallOptions {
+                config_path: cli.config.clone(),
+                log_file: cli.log_file.clone(),
+                print,
+                no_start,
+                force,
+            })
+        }
+        Commands::Identity { policy, reset } => {
+            manage_identity(config, &cli.config, policy, reset)
+        }
+        Commands::Doctor { fix } => {
+            run_doctor(&config, &cli.config, fix)
+        }
+        Commands::Devices { .. } => unreachable!("handled before the configuration is loaded"),
         Commands::Discover { detailed } => {
             discover_devices(detailed).await
         }
+        Commands::Models { describe } => {
+            describe_models(describe);
+            Ok(())
+        }
         Commands::Calibrate { skip_steering, skip_pedals } => {
-            calibrate_wheel(config, skip_steering, skip_pedals).await
+            calibrate_wheel(config, &cli.config, skip_steering, skip_pedals).await
         }
         Commands::Test { duration } => {
             test_translation(config, duration).await
         }
-        Commands::Config { force } => {
+        Commands::Config { force, command: None } => {
             generate_config(&cli.config, force).await
         }
+        Commands::Config { .. } => unreachable!("handled before the configuration is loaded"),
         Commands::FfbTest { effect, duration } => {
             test_ffb_effects(config, effect, duration).await
         }
+        Commands::Record { output, duration } => {
+            if let Some(seconds) = duration {
+                let control = control.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+                    control.request_shutdown();
+                });
+            }
+            let options = RunOptions {
+                foreground: true,
+                evemu: None,
+                trace: None,
+                capture: Some(output),
+                supervise: None,
+                config_path: cli.config.clone(),
+                state_dump: std::env::temp_dir().join("tm-g29-state.trace"),
+                control_endpoint: ipc::default_endpoint(),
+                watch: false,
+                wait_setup: None,
+                control,
+            };
+            run_translator(config, options).await
+        }
+        Commands::Replay { capture, validate, live, profile } => {
+            let config = match profile {
+                Some(path) => load_config(&path).await?,
+                None => config,
+            };
+            match (validate, capture) {
+                (Some(path), _) => replay_capture(&path, &config),
+                (None, Some(path)) => replay_session(&path, &config, live).await,
+                (None, None) => Err(anyhow::anyhow!("No capture to replay")),
+            }
+        }
+        Commands::Conformance { suite, tolerance } => {
+            run_conformance(&suite, &config, tolerance)
+        }
+        Commands::Bench { loopback, samples } => {
+            run_bench(&config, loopback, samples).await
+        }
+        Commands::Pipe { direction, report_len } => {
+            run_pipe(&config, direction, report_len)
+        }
+        Commands::Capture { command } => {
+            manage_capture(command)
+        }
+        Commands::Profile { command: ProfileCommand::Resolve { path } } => {
+            resolve_profile(path.as_deref().unwrap_or(&cli.config))
+        }
+        Commands::Profile { command } => {
+            manage_profiles(config, &cli.config, command)
+        }
+        Commands::SelfUpdate { check, force, repo } => {
+            self_update::self_update(&self_update::UpdateOptions { repo, check, force })
+        }
+        Commands::Curve { command: CurveCommand::Preview { axis, curve, steps } } => {
+            curve::preview(&config.input_config, axis, curve, steps)
+        }
+        Commands::Curve { command: CurveCommand::Edit { axis } } => {
+            curve::edit(config, &cli.config, axis)
+        }
+        Commands::Visualize { path, svg, width } => {
+            visualize::visualize(&path, svg.as_deref(), width, config.input_config.output_range())
+        }
+        Commands::Monitor { remote, endpoint, format, interval, listen } => {
+            if !remote {
+                return Err(anyhow::anyhow!("Only --remote monitoring is supported, use `tm-g29 test` for local input"));
+            }
+            let endpoint = endpoint.unwrap_or_else(ipc::default_endpoint);
+            monitor::monitor_remote(&endpoint, format, interval, listen, config.input_config.output_range(), Presenter::new(&config)).await
+        }
+        Commands::Ctl { command, endpoint } => {
+            control_translator(command, endpoint.unwrap_or_else(ipc::default_endpoint), Presenter::new(&config)).await
+        }
+        Commands::ServeWheel { listen } => {
+            let mut config = config;
+            if let Some(listen) = listen {
+                config.remote_config.listen = listen;
+            }
+            tokio::select! {
+                result = remote::serve_wheel(&config) => Ok(result?),
+                _ = tokio::signal::ctrl_c() => Ok(()),
+            }
+        }
     }
 }
 
@@ -128,12 +726,12 @@ fn init_logging(cli: &Cli) -> Result<()> {
     }
 
     if let Some(log_file) = &cli.log_file {
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_file)?;
-        
+        let file = daemon::open_log(log_file)?;
+
         builder.with_writer(file).init();
+    } else if matches!(cli.command, Commands::Pipe { .. }) {
+        // stdout carries the translated reports
+        builder.with_writer(std::io::stderr).init();
     } else {
         builder.init();
     }
@@ -141,46 +739,693 @@ fn init_logging(cli: &Cli) -> Result<()> {
     Ok(())
 }
 
-async fn load_config(config_path: &PathBuf) -> Result<Config> {
-    if config_path.exists() {
+async fn load_config(config_path: &Path) -> Result<Config> {
+    let config = if config_path.exists() {
         info!("Loading configuration from: {}", config_path.display());
         Config::load_from_file(config_path.to_str().unwrap())
-            .map_err(|e| anyhow::anyhow!("Failed to load config: {}", e))
+            .map_err(|e| anyhow::anyhow!("Failed to load config: {}", e))?
     } else {
         warn!("Configuration file not found, using defaults");
-        Ok(Config::default())
+        Config { source: Some(config_path.to_path_buf()), ..Config::default() }
+    };
+
+    let report = validation::validate_profiles(&config);
+    if !report.is_empty() {
+        eprintln!("Button mapping conflict report:");
+        eprint!("{}", report);
+    }
+    if report.has_errors() {
+        return Err(anyhow::anyhow!("Button mapping has errors, fix the configuration and try again"));
+    }
+
+    Ok(config)
+}
+
+/// Warn on stderr where the active profile's assumptions do not fit the configured wheel
+fn print_model_mismatches(config: &Config) {
+    let pid = config.thrustmaster_config.pid;
+    let model = ThrustmasterModel::from_pid(pid);
+    let effective = profile::effective(config, None).unwrap_or_else(|_| config.clone());
+    let report = validation::validate_model(&effective, model, pid);
+    if report.is_empty() {
+        return;
+    }
+    let name = match &effective.profile_config.active {
+        Some(active) => format!("Profile {}", active),
+        None => "The configuration".to_string(),
+    };
+    eprintln!("{} does not fit the attached {} (PID {:04x}):", name, model, pid);
+    eprint!("{}", report);
+    if let Some(suggested) = validation::suggest_profile(config, model, pid) {
+        eprintln!("  Profile {} fits it, `tm-g29 profile activate {}` to use it", suggested, suggested);
     }
 }
 
-async fn run_translator(config: Config, foreground: bool) -> Result<()> {
+/// Options of the `run` subcommand
+struct RunOptions {
+    foreground: bool,
+    evemu: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    capture: Option<PathBuf>,
+    supervise: Option<supervisor::SupervisorPolicy>,
+    config_path: PathBuf,
+    state_dump: PathBuf,
+    control_endpoint: PathBuf,
+    watch: bool,
+    /// How long to wait for missing setup prerequisites, `None` to fail right away
+    wait_setup: Option<std::time::Duration>,
+    /// Runtime controls, shared with the service wrapper on Windows
+    control: TranslatorControl,
+}
+
+/// Translator opened and handed its runtime controls, running once awaited
+type TranslatorRun = std::pin::Pin<Box<dyn std::future::Future<Output = thrustmaster_core::Result<()>>>>;
+
+/// Open the translator, reading the wheel from `remote_config.connect` when set
+async fn open_translator(
+    config: Config,
+    control: TranslatorControl,
+    recorder: Option<SharedSessionRecorder>,
+) -> thrustmaster_core::Result<TranslatorRun> {
+    if config.remote_config.connect.is_some() {
+        Ok(Box::pin(start_translator(ProtocolTranslator::new_remote(config).await?, control, recorder)))
+    } else {
+        Ok(Box::pin(start_translator(ProtocolTranslator::new(config).await?, control, recorder)))
+    }
+}
+
+async fn start_translator<W: SourceWheel, V: VirtualWheel>(
+    mut translator: ProtocolTranslator<W, V>,
+    control: TranslatorControl,
+    recorder: Option<SharedSessionRecorder>,
+) -> thrustmaster_core::Result<()> {
+    translator.set_control(control);
+    if let Some(recorder) = recorder {
+        translator.set_session_recorder(recorder);
+    }
+    sd_notify::ready();
+    translator.run().await
+}
+
+async fn run_translator(config: Config, options: RunOptions) -> Result<()> {
+    let RunOptions { foreground, evemu, trace, capture, supervise, config_path, state_dump, control_endpoint, watch, wait_setup, control } = options;
     info!("Starting protocol translator...");
 
+    if let Some(timeout) = wait_setup {
+        tokio::select! {
+            result = prerequisites::wait_for(&config, timeout) => result?,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+
+    print_model_mismatches(&config);
+
     if !foreground {
-        info!("Running in background mode");
-        // In a real implementation, this would fork/daemonize the process
+        info!("Running in background mode, PID {}", std::process::id());
+    }
+
+    if let Some(board) = host::detect_board() {
+        if config.host_config.embedded {
+            info!("Embedded mode on {}", board);
+        } else {
+            warn!("Running on {}, consider `embedded = true` under [host_config] for steadier timing", board);
+        }
     }
 
     // Setup signal handling for graceful shutdown
-    let translator = ProtocolTranslator::new(config).await?;
+    let g29_config = config.g29_config.clone();
+
+    let recorder = (evemu.is_some() || trace.is_some() || capture.is_some())
+        .then(|| SessionRecorder::shared(recording::DEFAULT_MAX_INPUTS));
+
+    // Runtime controls survive supervised restarts
+    if config.hotkey_config.enabled {
+        match thrustmaster_core::hotkeys::spawn(&config.hotkey_config, control.clone()) {
+            Ok(_) => info!("Global hotkeys enabled"),
+            Err(e) => warn!("Global hotkeys disabled: {}", e),
+        }
+    }
+    let dump_dir = state_dump.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+    let signal_task = tokio::spawn(signals::handle_signals(control.clone(), config_path.clone(), state_dump));
+    let watch_task = watch.then(|| tokio::spawn(watch::watch_config(control.clone(), config_path.clone())));
+    let profile_task = config
+        .profile_config
+        .auto_switch
+        .then(|| tokio::spawn(profile::watch_games(control.clone(), config.clone())));
+    let control_task = {
+        let (endpoint, control) = (control_endpoint.clone(), control.clone());
+        tokio::spawn(async move {
+            if let Err(e) = ipc::serve(&endpoint, control, config_path, dump_dir).await {
+                warn!("Control channel unavailable: {}", e);
+            }
+        })
+    };
+
+    let metrics_task = config.metrics_config.enabled.then(|| {
+        let (bind, control) = (config.metrics_config.bind.clone(), control.clone());
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&bind, control).await {
+                warn!("Metrics endpoint unavailable on {}: {}", bind, e);
+            }
+        })
+    });
+    let watchdog_task = sd_notify::watchdog_timeout().map(|timeout| {
+        info!("Feeding the systemd watchdog, timeout {:?}", timeout);
+        tokio::spawn(sd_notify::watchdog(control.clone(), timeout))
+    });
 
     let ctrl_c = tokio::signal::ctrl_c();
-    
-    tokio::select! {
-        result = translator.run() => {
-            match result {
-                Ok(_) => info!("Translator stopped normally"),
-                Err(e) => error!("Translator error: {}", e),
+
+    match supervise {
+        Some(policy) => {
+            info!("Supervising translator, up to {} restarts in a row", policy.max_restarts);
+            let start = || {
+                let config = config.clone();
+                let recorder = recorder.clone();
+                let control = control.clone();
+                async move { open_translator(config, control, recorder).await?.await }
+            };
+
+            tokio::select! {
+                result = supervisor::supervise(&policy, start) => {
+                    if let Err(e) = result {
+                        error!("Supervisor stopped: {}", e);
+                    }
+                }
+                _ = ctrl_c => {
+                    info!("Received shutdown signal, stopping translator...");
+                }
+                _ = control.shutdown_requested() => {
+                    info!("Shutdown requested over the control channel, stopping translator...");
+                }
+            }
+        }
+        None => {
+            let translator = open_translator(config, control.clone(), recorder.clone()).await?;
+
+            tokio::select! {
+                result = translator => {
+                    match result {
+                        Ok(_) => info!("Translator stopped normally"),
+                        Err(e) => error!("Translator error: {}", e),
+                    }
+                }
+                _ = ctrl_c => {
+                    info!("Received shutdown signal, stopping translator...");
+                }
+                _ = control.shutdown_requested() => {
+                    info!("Shutdown requested over the control channel, stopping translator...");
+                }
             }
         }
-        _ = ctrl_c => {
-            info!("Received shutdown signal, stopping translator...");
+    }
+
+    sd_notify::stopping();
+    signal_task.abort();
+    control_task.abort();
+    for task in [watch_task, profile_task, watchdog_task, metrics_task].into_iter().flatten() {
+        task.abort();
+    }
+    // The listener must be gone before its socket counts as stale
+    let _ = control_task.await;
+    #[cfg(unix)]
+    let _ = ipc::remove_stale_endpoint(&control_endpoint);
+
+    if let Some(recorder) = recorder {
+        let mut recorder = recorder.lock().map_err(|_| anyhow::anyhow!("Session recorder poisoned"))?;
+        if recorder.dropped() > 0 {
+            let message = format!("Recording was full, {} samples were not saved", recorder.dropped());
+            warn!("{}", message);
+            recorder.record_note(message);
+        }
+        let recording = recorder.recording();
+
+        if let Some(path) = evemu {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            recording.write_evemu(&mut file, &g29_config)?;
+            info!("Wrote {} recorded reports to {}", recording.inputs.len(), path.display());
+            println!("Replay with: evemu-device {0} & evemu-play /dev/input/eventN < {0}", path.display());
+        }
+
+        if let Some(path) = trace {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            recording.write_trace(&mut file)?;
+            info!("Wrote session trace to {}", path.display());
+            println!("Analyze with: tm-g29 visualize {}", path.display());
+        }
+
+        if let Some(path) = capture {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            if path.extension().is_some_and(|ext| ext == "jsonl") {
+                recording.write_jsonl(&mut file)?;
+            } else {
+                capture::write_capture(&mut file, &capture::CaptureHeader::now(), recording)?;
+            }
+            info!(
+                "Wrote session capture with {} wheel and {} FFB reports to {}",
+                recording.wheel.len(),
+                recording.outputs.len(),
+                path.display()
+            );
+            println!("Replay with: tm-g29 replay {}", path.display());
         }
     }
 
+    if let Some(report) = control.ab_report() {
+        println!("{}", report);
+    }
+
     info!("Protocol translator stopped");
     Ok(())
 }
 
+fn load_capture(path: &Path) -> Result<capture::Capture> {
+    let file = std::fs::File::open(path)?;
+    capture::read_capture(std::io::BufReader::new(file)).map_err(|e| anyhow::anyhow!("Failed to read capture: {}", e))
+}
+
+fn replay_capture(path: &Path, config: &Config) -> Result<()> {
+    let recording = load_capture(path)?.recording;
+
+    println!("Capture: {} ({:.1}s)", path.display(), recording.duration().as_secs_f64());
+    print!("{}", replay::validate_capture(&recording, config).render(&Presenter::new(config)));
+    Ok(())
+}
+
+async fn replay_session(path: &Path, config: &Config, live: bool) -> Result<()> {
+    let recording = load_capture(path)?.recording;
+    if recording.wheel.is_empty() {
+        warn!("The capture has no wheel reports, only its FFB is replayed");
+    }
+
+    println!("Capture: {} ({:.1}s)", path.display(), recording.duration().as_secs_f64());
+    for note in &recording.notes {
+        println!("Note at {:.1}s: {}", note.timestamp.as_secs_f64(), note.text);
+    }
+    let replay = replay::replay_session(&recording, config);
+    print!("{}", replay.render(&Presenter::new(config)));
+
+    if live && !replay.inputs.is_empty() {
+        play_live(&replay.inputs, config).await?;
+    }
+    Ok(())
+}
+
+/// Send replayed reports to a virtual G29 with their recorded timing
+///
+/// FFB the game sends back is not forwarded, there is no wheel to send it to.
+async fn play_live(inputs: &[recording::RecordedInput], config: &Config) -> Result<()> {
+    let device = VirtualG29Device::create(&config.g29_config).await?;
+    info!("Playing {} reports on a virtual G29, Ctrl+C to stop", inputs.len());
+
+    let started = tokio::time::Instant::now();
+    let playback = async {
+        for input in inputs {
+            tokio::time::sleep_until(started + input.timestamp).await;
+            device.send_input(input.report).await?;
+        }
+        Ok::<_, thrustmaster_core::TranslatorError>(())
+    };
+    tokio::select! {
+        result = playback => result?,
+        _ = tokio::signal::ctrl_c() => info!("Playback stopped"),
+    }
+    Ok(())
+}
+
+fn run_conformance(suite: &Path, config: &Config, tolerance: f32) -> Result<()> {
+    let mut cases: Vec<PathBuf> = std::fs::read_dir(suite)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    cases.sort();
+    if cases.is_empty() {
+        return Err(anyhow::anyhow!("No golden captures in {}", suite.display()));
+    }
+
+    let mut report = conformance::ConformanceReport::default();
+    for path in &cases {
+        let golden = load_capture(path)?.recording;
+        let name = path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned());
+        conformance::check_case(&mut report, &name, &golden, config, tolerance / 100.0);
+    }
+
+    print!("{}", report);
+    if !report.passed() {
+        return Err(anyhow::anyhow!("{} deviations from the golden captures", report.deviations.len()));
+    }
+    println!("All cases conform");
+    Ok(())
+}
+
+/// USB product ID in hex, with or without `0x`
+fn parse_pid(value: &str) -> std::result::Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{} is not a hex product ID", value))
+}
+
+fn describe_models(pid: Option<u16>) {
+    match pid {
+        Some(pid) => print!("{}", ModelDescription::new(pid)),
+        None => {
+            println!("Known wheel bases:");
+            for &(pid, model) in models::KNOWN_BASES {
+                println!("  {:04x}  {}", pid, model);
+            }
+            println!("Other PIDs use the generic layout, `models --describe <pid>` shows the details");
+        }
+    }
+}
+
+/// Print latency figures of the input path
+async fn run_bench(config: &Config, loopback: bool, samples: usize) -> Result<()> {
+    if samples == 0 {
+        return Err(anyhow::anyhow!("--samples must be at least 1"));
+    }
+    let model = config
+        .thrustmaster_config
+        .model
+        .unwrap_or_else(|| ThrustmasterModel::from_pid(config.thrustmaster_config.pid));
+    let translation = bench::translation_latency(config, model, samples)?;
+    if let Some(summary) = bench::LatencySummary::from_samples(translation) {
+        println!("Translation ({}): {}", model, summary.render(&Presenter::new(config)));
+    }
+    if loopback {
+        loopback_bench(config, samples).await?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn loopback_bench(config: &Config, samples: usize) -> Result<()> {
+    let device = thrustmaster_linux::LinuxVirtualG29Device::new(&config.g29_config).await?;
+    // Give udev time to set up the node before opening it
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let results = device.measure_loopback(samples, std::time::Duration::from_secs(1)).await?;
+
+    let summary = |part: fn(&thrustmaster_linux::LoopbackSample) -> std::time::Duration| {
+        bench::LatencySummary::from_samples(results.iter().map(part).collect())
+    };
+    for (name, summary) in [
+        ("Injection", summary(|sample| sample.injection)),
+        ("Delivery", summary(|sample| sample.delivery)),
+        ("Loopback", summary(|sample| sample.total())),
+    ] {
+        if let Some(summary) = summary {
+            println!("{}: {}", name, summary.render(&Presenter::new(config)));
+        }
+    }
+    println!("Loopback is what the OS adds between the translator and a game reading the virtual G29");
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn loopback_bench(_config: &Config, _samples: usize) -> Result<()> {
+    Err(anyhow::anyhow!("Loopback measurement needs Linux, where the translator can read its own virtual device"))
+}
+
+/// Filter fixed-length reports from stdin into translated bytes on stdout until EOF
+fn run_pipe(config: &Config, direction: PipeDirection, report_len: Option<usize>) -> Result<()> {
+    use std::io::{Read, Write};
+
+    let model = config
+        .thrustmaster_config
+        .model
+        .unwrap_or_else(|| ThrustmasterModel::from_pid(config.thrustmaster_config.pid));
+    let mut translate: Box<dyn FnMut(&[u8]) -> thrustmaster_core::error::Result<Vec<u8>>> = match direction {
+        PipeDirection::Input => {
+            let mut pipe = pipe::InputPipe::new(config, model)?;
+            Box::new(move |frame| pipe.translate(frame))
+        }
+        PipeDirection::Ffb => {
+            let mut pipe = pipe::FfbPipe::new(config);
+            Box::new(move |frame| pipe.translate(frame))
+        }
+    };
+    let frame_len = match (report_len, direction) {
+        (Some(len), _) => len,
+        (None, PipeDirection::Input) => model.layout().full_len(),
+        (None, PipeDirection::Ffb) => pipe::FFB_FRAME_LEN,
+    };
+    if frame_len == 0 {
+        return Err(anyhow::anyhow!("--report-len must be at least 1"));
+    }
+
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    let mut frame = vec![0u8; frame_len];
+    let mut frames = 0u64;
+    loop {
+        match stdin.read_exact(&mut frame) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        frames += 1;
+        match translate(&frame) {
+            Ok(bytes) => {
+                stdout.write_all(&bytes)?;
+                // Flushed per report, the reader may be waiting for it
+                stdout.flush()?;
+            }
+            Err(e) => warn!("Report {} skipped: {}", frames, e),
+        }
+    }
+    info!("Translated {} reports of {} bytes", frames, frame_len);
+    Ok(())
+}
+
+fn manage_capture(command: CaptureCommand) -> Result<()> {
+    match command {
+        CaptureCommand::Info { path } => {
+            let capture = load_capture(&path)?;
+            println!("Capture: {}", path.display());
+            print!("{}", capture);
+        }
+        CaptureCommand::Upgrade { path, output } => {
+            let capture = load_capture(&path)?;
+            let header = capture::CaptureHeader {
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                ..capture.header
+            };
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&output)?);
+            capture::write_capture(&mut file, &header, &capture.recording)?;
+            println!(
+                "Upgraded {} from format version {} to {}: {}",
+                path.display(),
+                capture.version,
+                capture::CAPTURE_VERSION,
+                output.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn resolve_profile(path: &Path) -> Result<()> {
+    let resolved = profile::resolve(path).map_err(|e| anyhow::anyhow!("Failed to resolve profile: {}", e))?;
+    let config = Config::from_profile(&resolved).map_err(|e| anyhow::anyhow!("Invalid profile {}: {}", path.display(), e))?;
+
+    for (depth, file) in resolved.files.iter().enumerate() {
+        let role = if depth == 0 { "profile" } else { "extends" };
+        println!("# {:<8} {}", role, file.display());
+    }
+    let content = config.to_toml().map_err(|e| anyhow::anyhow!("Failed to format configuration: {}", e))?;
+    print!("{}", content);
+    Ok(())
+}
+
+fn manage_profiles(mut config: Config, config_path: &Path, command: ProfileCommand) -> Result<()> {
+    match command {
+        ProfileCommand::Resolve { .. } => unreachable!("resolved before loading profiles"),
+        ProfileCommand::List => {
+            let profiles = profile::list(&config).map_err(|e| anyhow::anyhow!("Failed to list profiles: {}", e))?;
+            if profiles.is_empty() {
+                println!("No profiles in {} or {}", config_path.display(), profile::directory(&config).display());
+            }
+            for game in &profiles {
+                let active = config.profile_config.active.as_deref() == Some(game.name.as_str());
+                let source = game.file.as_ref().map_or_else(|| config_path.display().to_string(), |file| file.display().to_string());
+                println!(
+                    "{} {:<20} {:<40} {}",
+                    if active { "*" } else { " " },
+                    game.name,
+                    game.processes.join(", "),
+                    source
+                );
+            }
+            Ok(())
+        }
+        ProfileCommand::Create { name, process, force } => {
+            let path = profile::directory(&config).join(format!("{}.toml", name));
+            if path.exists() && !force {
+                return Err(anyhow::anyhow!("Profile {} already exists, use --force to overwrite", path.display()));
+            }
+            let content = profile::template(&config, &process).map_err(|e| anyhow::anyhow!("Failed to build profile: {}", e))?;
+            persist::write_atomic(&path, content.as_bytes())?;
+            println!("Created profile {} in {}, keep only the settings it should change", name, path.display());
+            Ok(())
+        }
+        ProfileCommand::Activate { name } => {
+            if let Some(name) = &name {
+                let profiles = profile::list(&config).map_err(|e| anyhow::anyhow!("Failed to list profiles: {}", e))?;
+                if !profiles.iter().any(|game| &game.name == name) {
+                    return Err(anyhow::anyhow!("No profile named {}", name));
+                }
+            }
+            config.profile_config.active = name.clone();
+            config
+                .save_to_file(config_path.to_str().unwrap())
+                .map_err(|e| anyhow::anyhow!("Failed to save config: {}", e))?;
+            match name {
+                Some(name) => println!("Profile {} active, `tm-g29 ctl reload` applies it to a running translator", name),
+                None => println!("No profile active"),
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn control_translator(command: CtlCommand, endpoint: PathBuf, presenter: Presenter) -> Result<()> {
+    if let CtlCommand::Timeline = command {
+        return ipc::timeline(&endpoint, |entry| {
+            println!("{}", entry.to_line());
+            true
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Timeline from {} ended: {}", endpoint.display(), e));
+    }
+
+    let response = ipc::send_request(&endpoint, &command.to_request())
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to reach translator at {}: {}", endpoint.display(), e))?;
+
+    if !response.ok {
+        return Err(anyhow::anyhow!("{}", response.message));
+    }
+    match command {
+        CtlCommand::Status => println!("{}", present_status(&response.message, &presenter)),
+        _ => println!("{}", response.message),
+    }
+    Ok(())
+}
+
+/// Status line with steering and gain in the units of `presenter`
+fn present_status(message: &str, presenter: &Presenter) -> String {
+    // The wheel name may contain spaces, so it comes last
+    let (fields, wheel) = match message.split_once(" wheel=") {
+        Some((fields, wheel)) => (fields, Some(wheel)),
+        None => (message, None),
+    };
+    let range = fields
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("range="))
+        .and_then(|range| range.parse::<u16>().ok())
+        .unwrap_or(0);
+
+    let mut presented: Vec<String> = fields
+        .split_whitespace()
+        .map(|field| match field.split_once('=') {
+            Some(("steering", value)) => match value.strip_suffix("deg").and_then(|degrees| degrees.parse::<f32>().ok()) {
+                Some(degrees) if range > 0 => {
+                    format!("steering={}", presenter.steering(SteeringAngle::from_degrees(degrees).to_axis(range), range))
+                }
+                _ => field.to_string(),
+            },
+            Some(("gain", value)) => match value.parse::<f32>() {
+                Ok(gain) => format!("gain={}", presenter.percent(gain)),
+                Err(_) => field.to_string(),
+            },
+            _ => field.to_string(),
+        })
+        .collect();
+    if let Some(wheel) = wheel {
+        presented.push(format!("wheel={}", wheel));
+    }
+    presented.join(" ")
+}
+
+fn manage_identity(mut config: Config, config_path: &Path, policy: Option<IdentityPolicy>, reset: bool) -> Result<()> {
+    let state_path = identity::default_state_path();
+
+    if let Some(policy) = policy {
+        config.g29_config.serial_policy = policy.into();
+        config
+            .save_to_file(config_path.to_str().unwrap())
+            .map_err(|e| anyhow::anyhow!("Failed to save config: {}", e))?;
+        println!("Saved serial policy {:?} to {}", config.g29_config.serial_policy, config_path.display());
+    }
+    if reset {
+        identity::reset_state(&state_path)?;
+        println!("Cleared saved serial in {}", state_path.display());
+    }
+
+    println!("Serial policy: {:?}", config.g29_config.serial_policy);
+    match config.g29_config.serial_policy {
+        SerialPolicy::Fixed => println!("Serial: {}", config.g29_config.serial_number),
+        SerialPolicy::FromWheel => println!("Serial: derived from the wheel when the translator starts"),
+        SerialPolicy::RandomPerBoot | SerialPolicy::RandomPersistent => match identity::load_state(&state_path) {
+            Some(state) => println!("Serial: {} (saved in {})", state.serial, state_path.display()),
+            None => println!("Serial: generated on the next run"),
+        },
+    }
+    Ok(())
+}
+
+fn run_doctor(config: &Config, config_path: &Path, fix: bool) -> Result<()> {
+    println!("Configuration: {} loaded and valid", config_path.display());
+
+    match (host::detect_board(), config.host_config.embedded) {
+        (Some(board), true) => println!("Host: {}, embedded mode on", board),
+        (Some(board), false) => println!("Host: {}, set `embedded = true` under [host_config] to lower the loop rates", board),
+        (None, _) => {}
+    }
+
+    if !cfg!(target_os = "linux") {
+        return Ok(());
+    }
+
+    let uinput = std::fs::OpenOptions::new().write(true).open("/dev/uinput");
+    match uinput {
+        Ok(_) => println!("uinput: /dev/uinput is writable"),
+        Err(e) => println!("uinput: cannot open /dev/uinput ({}), see the Linux setup section of the README", e),
+    }
+
+    let prefixes = proton::find_prefixes();
+    if prefixes.is_empty() {
+        println!("Proton: no game prefixes found");
+        return Ok(());
+    }
+
+    let tweaks = proton::known_tweaks(&config.g29_config);
+    let mut pending = 0;
+    println!("Proton: {} game prefixes", prefixes.len());
+    for prefix in &prefixes {
+        let missing = prefix.missing(&tweaks);
+        let name = prefix.name.as_deref().unwrap_or("unknown game");
+        if missing.is_empty() {
+            println!("  {} ({}): ok", name, prefix.app_id);
+            continue;
+        }
+        if !fix {
+            pending += 1;
+            for tweak in &missing {
+                println!("  {} ({}): missing {}\\{} ({})", name, prefix.app_id, tweak.key, tweak.name, tweak.reason);
+            }
+            continue;
+        }
+        match prefix.apply(&missing) {
+            Ok(()) => println!("  {} ({}): registered the virtual wheel", name, prefix.app_id),
+            Err(e) => println!("  {} ({}): cannot update the registry: {}", name, prefix.app_id, e),
+        }
+    }
+    if pending > 0 {
+        println!("Close the affected games, then run `tm-g29 doctor --fix` to update {} prefixes", pending);
+    }
+
+    Ok(())
+}
+
 async fn discover_devices(detailed: bool) -> Result<()> {
     use hidapi::HidApi;
 
@@ -188,6 +1433,13 @@ async fn discover_devices(detailed: bool) -> Result<()> {
     
     let api = HidApi::new()?;
     let devices = api.device_list();
+    let inventory = Inventory::load(&inventory::default_path()).unwrap_or_default();
+    let nickname = |device: &hidapi::DeviceInfo| {
+        inventory
+            .nickname(device.vendor_id(), device.product_id(), device.serial_number())
+            .map(|name| format!(" \"{}\"", name))
+            .unwrap_or_default()
+    };
 
     let mut thrustmaster_devices = Vec::new();
     let mut g29_devices = Vec::new();
@@ -202,7 +1454,7 @@ async fn discover_devices(detailed: bool) -> Result<()> {
 
     println!("Found {} Thrustmaster device(s):", thrustmaster_devices.len());
     for device in &thrustmaster_devices {
-        println!("  VID:PID = {:04X}:{:04X}", device.vendor_id(), device.product_id());
+        println!("  VID:PID = {:04X}:{:04X}{}", device.vendor_id(), device.product_id(), nickname(device));
         if detailed {
             println!("    Manufacturer: {:?}", device.manufacturer_string());
             println!("    Product: {:?}", device.product_string());
@@ -229,39 +1481,166 @@ async fn discover_devices(detailed: bool) -> Result<()> {
     Ok(())
 }
 
-async fn calibrate_wheel(config: Config, skip_steering: bool, skip_pedals: bool) -> Result<()> {
+fn manage_devices(command: DevicesCommand) -> Result<()> {
+    let path = inventory::default_path();
+    let mut inventory = Inventory::load(&path)?;
+    let api = hidapi::HidApi::new()?;
+    // hidapi lists a device once per interface
+    let mut connected: Vec<&hidapi::DeviceInfo> = Vec::new();
+    for device in api.device_list() {
+        let id = |d: &hidapi::DeviceInfo| (d.vendor_id(), d.product_id(), d.serial_number().map(str::to_string));
+        if !connected.iter().any(|known| id(known) == id(device)) {
+            connected.push(device);
+        }
+    }
+
+    match command {
+        DevicesCommand::List => {
+            if inventory.devices.is_empty() {
+                println!("No named devices, name one with `tm-g29 devices name <nickname>`");
+            }
+            for entry in &inventory.devices {
+                let present = connected
+                    .iter()
+                    .any(|device| entry.matches(device.vendor_id(), device.product_id(), device.serial_number()));
+                println!(
+                    "{:<16} {:04X}:{:04X} serial {:<14} {:<10} {}",
+                    entry.nickname,
+                    entry.vid,
+                    entry.pid,
+                    entry.serial.as_deref().unwrap_or("any"),
+                    if present { "connected" } else { "absent" },
+                    entry.product
+                );
+            }
+            println!("Inventory: {}", path.display());
+        }
+        DevicesCommand::Name { nickname, serial, pid } => {
+            let candidates: Vec<_> = connected
+                .into_iter()
+                .filter(|device| match (&serial, pid) {
+                    (None, None) => device.vendor_id() == 0x044F,
+                    _ => {
+                        serial.as_deref().map_or(true, |serial| device.serial_number() == Some(serial))
+                            && pid.map_or(true, |pid| device.product_id() == pid)
+                    }
+                })
+                .collect();
+            let device = match candidates.as_slice() {
+                [device] => device,
+                [] => return Err(anyhow::anyhow!("No connected device matches, see `tm-g29 discover --detailed`")),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "{} connected devices match, pick one with --serial or --pid",
+                        candidates.len()
+                    ))
+                }
+            };
+            inventory.set(InventoryEntry {
+                nickname: nickname.clone(),
+                vid: device.vendor_id(),
+                pid: device.product_id(),
+                serial: device.serial_number().filter(|serial| !serial.is_empty()).map(str::to_string),
+                product: device.product_string().unwrap_or_default().to_string(),
+            })?;
+            inventory.save(&path)?;
+            println!(
+                "Named {:04X}:{:04X} {} \"{}\", select it with device = \"{}\"",
+                device.vendor_id(),
+                device.product_id(),
+                device.product_string().unwrap_or_default(),
+                nickname,
+                nickname
+            );
+        }
+        DevicesCommand::Forget { name } => {
+            if !inventory.remove(&name) {
+                return Err(anyhow::anyhow!("No device named {} in {}", name, path.display()));
+            }
+            inventory.save(&path)?;
+            println!("Forgot {}", name);
+        }
+    }
+    Ok(())
+}
+
+async fn calibrate_wheel(mut config: Config, config_path: &Path, skip_steering: bool, skip_pedals: bool) -> Result<()> {
     info!("Starting wheel calibration...");
+
+    // Read the raw reports directly, the event reader is not needed here
+    let mut device_config = config.thrustmaster_config.clone();
+    device_config.input_wakeup = InputWakeup::Poll;
+    let device = ThrustmasterDevice::open(&device_config).await?;
+    let mut session = CalibrationSession::new();
     
     if !skip_steering {
         println!("Steering Calibration:");
         println!("1. Turn wheel fully left and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
+        session.mark_left();
         println!("2. Turn wheel fully right and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
         println!("3. Center the wheel and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
+        session.mark_center();
         println!("Steering calibration complete!");
     }
 
     if !skip_pedals {
         println!("\nPedal Calibration:");
         println!("1. Release all pedals and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
+        session.mark_pedals_released();
         println!("2. Press throttle pedal fully and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
         println!("3. Press brake pedal fully and press Enter");
-        wait_for_enter().await;
+        record_until_enter(&device, &mut session).await?;
         if config.input_config.button_mapping.len() > 16 { // Has clutch
             println!("4. Press clutch pedal fully and press Enter");
-            wait_for_enter().await;
+            record_until_enter(&device, &mut session).await?;
         }
         println!("Pedal calibration complete!");
     }
 
-    println!("Calibration finished. Values saved to configuration file.");
+    if !session.has_input() {
+        return Err(anyhow::anyhow!("No input received from the wheel, calibration not saved"));
+    }
+
+    let calibration = session.finish(&config.input_config.calibration);
+    println!(
+        "Steering: min {} / center {} / max {}",
+        calibration.steering_min, calibration.steering_center, calibration.steering_max
+    );
+    println!(
+        "Throttle: {}-{}, brake: {}-{}, clutch: {}-{}",
+        calibration.throttle.min, calibration.throttle.max,
+        calibration.brake.min, calibration.brake.max,
+        calibration.clutch.min, calibration.clutch.max
+    );
+    config.input_config.calibration = calibration;
+    config
+        .save_to_file(config_path.to_str().unwrap())
+        .map_err(|e| anyhow::anyhow!("Failed to save config: {}", e))?;
+
+    println!("Calibration finished. Values saved to {}.", config_path.display());
     Ok(())
 }
 
+/// Feed wheel reports into `session` until Enter is pressed
+async fn record_until_enter(device: &ThrustmasterDevice, session: &mut CalibrationSession) -> Result<()> {
+    let enter = wait_for_enter();
+    tokio::pin!(enter);
+    loop {
+        tokio::select! {
+            _ = &mut enter => return Ok(()),
+            report = device.read_input() => match report? {
+                Some(report) => session.observe(&report),
+                None => tokio::time::sleep(tokio::time::Duration::from_millis(2)).await,
+            },
+        }
+    }
+}
+
 async fn wait_for_enter() {
     use tokio::io::{AsyncBufReadExt, BufReader};
     let stdin = tokio::io::stdin();
@@ -302,6 +1681,42 @@ async fn generate_config(config_path: &PathBuf, force: bool) -> Result<()> {
     Ok(())
 }
 
+fn manage_config_backups(config_path: &Path, command: ConfigCommand) -> Result<()> {
+    let load = |path: &Path| Config::load_from_file(path.to_str().unwrap());
+    let keep = load(config_path).map_or(BackupConfig::default().keep, |config| config.backup_config.keep);
+
+    match command {
+        ConfigCommand::Backups => {
+            let backups = persist::backups(config_path, keep);
+            if backups.is_empty() {
+                println!("No backups of {}", config_path.display());
+            }
+            for (index, backup) in backups {
+                let state = if load(&backup).is_ok() { "" } else { "  (does not load)" };
+                println!("{:>3}  {}{}", index, backup.display(), state);
+            }
+        }
+        ConfigCommand::Restore { backup, force } => {
+            let path = persist::backup_path(config_path, backup);
+            if !path.exists() {
+                return Err(anyhow::anyhow!("No backup {} of {}, see `tm-g29 config backups`", backup, config_path.display()));
+            }
+            if let Err(e) = load(&path) {
+                if !force {
+                    return Err(anyhow::anyhow!("Backup {} does not load ({}), use --force to restore it anyway", path.display(), e));
+                }
+            }
+            persist::restore(config_path, backup, keep)?;
+            println!(
+                "Restored {} from {}, the replaced file is now backup 1; `tm-g29 ctl reload` applies it to a running translator",
+                config_path.display(),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
 async fn test_ffb_effects(_config: Config, effect: FfbTestEffect, duration: u64) -> Result<()> {
     info!("Testing FFB effect: {:?} for {} seconds", effect, duration);
     
@@ -0,0 +1,142 @@
+//! Read-only view of a running translator for streaming overlays
+//!
+//! Subscribes to the daemon's control channel and renders each sample as a
+//! single line: a compact text gauge for terminals and OBS text sources, or
+//! JSON for browser-source overlays. Lines go to stdout, or with `--listen`
+//! to every client connected to a TCP socket.
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use thrustmaster_core::ipc::{self, IpcRequest, IpcSample};
+use thrustmaster_core::presentation::Presenter;
+use thrustmaster_core::{NormalizedAxis, SteeringAngle};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::info;
+
+const BAR_WIDTH: usize = 10;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum MonitorFormat {
+    Text,
+    Json,
+}
+
+/// Render one sample, using `steering_range` when the daemon did not report its own
+///
+/// `wheel` is the nickname or model of the translated wheel, if the daemon told it.
+/// Text lines show steering and force in the units of `presenter`, JSON keeps plain numbers.
+pub fn render(sample: &IpcSample, format: MonitorFormat, steering_range: u16, wheel: Option<&str>, presenter: &Presenter) -> String {
+    let steering = NormalizedAxis::from_g29_steering(sample.steering);
+    let range = if sample.steering_range > 0 { sample.steering_range } else { steering_range };
+    let degrees = SteeringAngle::from_axis(steering, range).degrees();
+    let throttle = NormalizedAxis::from_g29_pedal(sample.throttle).value();
+    let brake = NormalizedAxis::from_g29_pedal(sample.brake).value();
+    let clutch = NormalizedAxis::from_g29_pedal(sample.clutch).value();
+    let force = sample.force as f32 / i16::MAX as f32;
+
+    match format {
+        MonitorFormat::Text => format!(
+            "{}STR {:>8}  THR {} BRK {} CLU {} FFB {} {:>8}{}",
+            wheel.map(|name| format!("{}  ", name)).unwrap_or_default(),
+            presenter.steering(steering, range),
+            bar(throttle),
+            bar(brake),
+            bar(clutch),
+            force_bar(force),
+            presenter.force(force),
+            if sample.paused { "  PAUSED" } else { "" }
+        ),
+        MonitorFormat::Json => format!(
+            r#"{{{}"steering_deg":{:.1},"throttle":{:.3},"brake":{:.3},"clutch":{:.3},"force":{:.3},"paused":{}}}"#,
+            wheel
+                .map(|name| format!(r#""wheel":"{}","#, name.replace('\\', "\\\\").replace('"', "\\\"")))
+                .unwrap_or_default(),
+            degrees, throttle, brake, clutch, force, sample.paused
+        ),
+    }
+}
+
+fn bar(value: f32) -> String {
+    let filled = (value.clamp(0.0, 1.0) * BAR_WIDTH as f32).round() as usize;
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled))
+}
+
+/// Bipolar bar, filling left of the center mark for negative forces
+fn force_bar(value: f32) -> String {
+    let half = BAR_WIDTH / 2;
+    let filled = (value.abs().min(1.0) * half as f32).round() as usize;
+    let (left, right) = if value < 0.0 { (filled, 0) } else { (0, filled) };
+    format!(
+        "[{}{}|{}{}]",
+        " ".repeat(half - left),
+        "#".repeat(left),
+        "#".repeat(right),
+        " ".repeat(half - right)
+    )
+}
+
+/// Attach to the translator at `endpoint` and print samples until interrupted
+pub async fn monitor_remote(
+    endpoint: &Path,
+    format: MonitorFormat,
+    interval_ms: u16,
+    listen: Option<SocketAddr>,
+    steering_range: u16,
+    presenter: Presenter,
+) -> Result<()> {
+    let clients: Arc<Mutex<Vec<TcpStream>>> = Arc::default();
+
+    if let Some(addr) = listen {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("Serving overlay lines on {}", addr);
+        let clients = clients.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                info!("Overlay client connected from {}", peer);
+                if let Ok(mut clients) = clients.lock() {
+                    clients.push(stream);
+                }
+            }
+        });
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if listen.is_none() {
+                println!("{}", line);
+                continue;
+            }
+
+            let mut connected = clients.lock().map(|mut c| std::mem::take(&mut *c)).unwrap_or_default();
+            let mut alive = Vec::with_capacity(connected.len());
+            for mut stream in connected.drain(..) {
+                if stream.write_all(format!("{}\n", line).as_bytes()).await.is_ok() {
+                    alive.push(stream);
+                }
+            }
+            if let Ok(mut clients) = clients.lock() {
+                clients.extend(alive);
+            }
+        }
+    });
+
+    // Everything after `wheel=` in the status line is the name, it may contain spaces
+    let wheel = ipc::send_request(endpoint, &IpcRequest::Status)
+        .await
+        .ok()
+        .and_then(|response| response.message.split_once(" wheel=").map(|(_, name)| name.to_string()))
+        .filter(|name| name != "unknown");
+
+    let result = ipc::subscribe(endpoint, interval_ms, |sample| {
+        tx.send(render(&sample, format, steering_range, wheel.as_deref(), &presenter)).is_ok()
+    })
+    .await;
+
+    drop(tx);
+    let _ = writer.await;
+    result.map_err(|e| anyhow::anyhow!("Lost connection to translator at {}: {}", endpoint.display(), e))
+}
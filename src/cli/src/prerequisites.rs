@@ -0,0 +1,126 @@
+//! `tm-g29 run --wait-setup`: wait for setup prerequisites instead of failing
+//!
+//! Right after first-time setup the system often is not quite ready: the
+//! udev rule is installed but not applied until the wheel is replugged, or
+//! macOS shows the Input Monitoring prompt and waits for the user. Without
+//! this the translator fails to open the wheel and exits. With it the
+//! missing prerequisites are polled every second under a visible countdown,
+//! and the translator starts the moment they are all met.
+
+use anyhow::Result;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+use thrustmaster_core::Config;
+
+/// Polling period of the prerequisite checks
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Countdown lines written when stderr is not a terminal, e.g. to a log file
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A prerequisite that is not met yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Missing {
+    pub what: String,
+    pub hint: &'static str,
+}
+
+/// Prerequisites of running the translator with `config` that are not met
+pub fn missing(config: &Config) -> Vec<Missing> {
+    let mut missing = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    if let Err(e) = std::fs::OpenOptions::new().write(true).open("/dev/uinput") {
+        missing.push(Missing {
+            what: format!("write access to /dev/uinput ({})", e),
+            hint: "load the uinput module and check the udev rule from the Linux setup section of the README",
+        });
+    }
+
+    let wheel = &config.thrustmaster_config;
+    match hidapi::HidApi::new() {
+        Ok(api) => {
+            let present = api.device_list().find(|device| {
+                device.vendor_id() == wheel.vid
+                    && device.product_id() == wheel.pid
+                    && wheel.serial_number.as_deref().map_or(true, |serial| device.serial_number() == Some(serial))
+            });
+            match present {
+                None => missing.push(Missing {
+                    what: format!("wheel {:04X}:{:04X} connected", wheel.vid, wheel.pid),
+                    hint: "plug the wheel in and switch it to PC mode",
+                }),
+                Some(device) => {
+                    if let Err(e) = device.open_device(&api) {
+                        missing.push(Missing { what: format!("access to the wheel ({})", e), hint: access_hint() });
+                    }
+                }
+            }
+        }
+        Err(e) => missing.push(Missing { what: format!("HID access ({})", e), hint: access_hint() }),
+    }
+
+    missing
+}
+
+fn access_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "grant Input Monitoring in System Settings > Privacy & Security"
+    } else if cfg!(target_os = "linux") {
+        "replug the wheel so the udev rule applies, or run `sudo udevadm trigger`"
+    } else {
+        "close other programs holding the wheel"
+    }
+}
+
+/// Wait up to `timeout` for every prerequisite, showing what is missing and the time left
+///
+/// Fails with the prerequisites still missing once the time is up.
+pub async fn wait_for(config: &Config, timeout: Duration) -> Result<()> {
+    let mut pending = missing(config);
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let terminal = std::io::stderr().is_terminal();
+    let deadline = Instant::now() + timeout;
+    let mut last_log = None::<Instant>;
+    eprintln!("Waiting for setup to complete (Ctrl-C to cancel):");
+    for m in &pending {
+        eprintln!("  {}: {}", m.what, m.hint);
+    }
+    loop {
+        let now = Instant::now();
+        let left = deadline.saturating_duration_since(now);
+        let line = format!("Still missing {}, {}s left", describe(&pending), left.as_secs());
+        if terminal {
+            eprint!("\r\x1b[K{}", line);
+            let _ = std::io::stderr().flush();
+        } else if last_log.map_or(true, |logged| now.duration_since(logged) >= LOG_INTERVAL) {
+            eprintln!("{}", line);
+            last_log = Some(now);
+        }
+
+        if left.is_zero() {
+            if terminal {
+                eprintln!();
+            }
+            let hints: Vec<String> = pending.iter().map(|m| format!("{}: {}", m.what, m.hint)).collect();
+            return Err(anyhow::anyhow!("Setup incomplete after {}s\n  {}", timeout.as_secs(), hints.join("\n  ")));
+        }
+
+        tokio::time::sleep(POLL_INTERVAL.min(left)).await;
+        pending = missing(config);
+        if pending.is_empty() {
+            if terminal {
+                eprintln!();
+            }
+            eprintln!("Setup complete, starting the translator");
+            return Ok(());
+        }
+    }
+}
+
+fn describe(pending: &[Missing]) -> String {
+    pending.iter().map(|m| m.what.as_str()).collect::<Vec<_>>().join(", ")
+}
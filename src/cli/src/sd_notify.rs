@@ -0,0 +1,76 @@
+//! systemd readiness and watchdog notifications
+//!
+//! A `Type=notify` unit passes a datagram socket in `NOTIFY_SOCKET`. The
+//! translator reports `READY=1` once the wheel and virtual G29 are open and
+//! `STOPPING=1` on the way out. With `WatchdogSec=` set, systemd also passes
+//! `WATCHDOG_USEC` and expects `WATCHDOG=1` more often than that; pings are
+//! only sent while the input loop keeps ticking, so a wedged pipeline gets
+//! the unit restarted. Outside systemd every call does nothing.
+
+use std::time::Duration;
+use thrustmaster_core::TranslatorControl;
+use tracing::{debug, warn};
+
+/// Send `state` to the service manager, false when not running under one
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) -> bool {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let path = path.to_string_lossy();
+    // A leading '@' names a socket in the abstract namespace
+    let address = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path.as_ref()),
+    };
+    let sent = address.and_then(|address| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address));
+    if let Err(e) = &sent {
+        debug!("Service manager notification failed: {}", e);
+    }
+    sent.is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) -> bool {
+    false
+}
+
+/// The translator is up and translating
+pub fn ready() {
+    notify("READY=1\nSTATUS=Translating");
+}
+
+/// The translator is shutting down
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// Watchdog timeout systemd set for this process, if any
+pub fn watchdog_timeout() -> Option<Duration> {
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) {
+        if pid != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Ping the watchdog at half its timeout while the input loop is alive
+pub async fn watchdog(control: TranslatorControl, timeout: Duration) {
+    let mut interval = tokio::time::interval(timeout / 2);
+    let mut stalled = false;
+    loop {
+        interval.tick().await;
+        if control.ticked_within(timeout / 2) {
+            stalled = false;
+            notify("WATCHDOG=1");
+        } else if !stalled {
+            stalled = true;
+            warn!("Input loop stalled, no longer feeding the systemd watchdog");
+        }
+    }
+}
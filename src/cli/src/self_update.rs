@@ -0,0 +1,223 @@
+//! `tm-g29 self-update`: replace the running binary with the latest release
+//!
+//! Most users install a prebuilt binary rather than building with cargo, so
+//! the CLI can update itself from GitHub releases. A release carries one
+//! binary per platform (`tm-g29-<os>-<arch>[.exe]`), a `SHA256SUMS` file and
+//! `SHA256SUMS.sig`, an Ed25519 signature of that file. The signature is
+//! checked against the release key baked in at build time
+//! (`TM_G29_RELEASE_KEY`, hex) before the checksum is trusted. The tag name
+//! is not signed, so `SHA256SUMS` also carries a `version <x.y.z>` line and
+//! that version must match the tag and be newer than the running one; an
+//! older release's signed files cannot be passed off as an update. The new
+//! binary is written next to the old one and renamed over it, so an
+//! interrupted update never leaves a half-written executable. Only compiled
+//! with the `self-update` feature.
+
+use anyhow::Result;
+
+/// Repository releases are fetched from unless `--repo` is given
+pub const DEFAULT_REPO: &str = "your-org/thrustmaster-g29-translator";
+
+/// Options of the `self-update` subcommand
+pub struct UpdateOptions {
+    pub repo: String,
+    /// Only report whether an update is available
+    pub check: bool,
+    /// Reinstall even if the release is not newer
+    pub force: bool,
+}
+
+#[cfg(feature = "self-update")]
+pub fn self_update(options: &UpdateOptions) -> Result<()> {
+    use anyhow::{anyhow, Context};
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+    use serde::Deserialize;
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+        assets: Vec<Asset>,
+    }
+
+    #[derive(Deserialize)]
+    struct Asset {
+        name: String,
+        browser_download_url: String,
+    }
+
+    let key = release_key()?;
+    let current = env!("CARGO_PKG_VERSION");
+    let url = format!("https://api.github.com/repos/{}/releases/latest", options.repo);
+    let release: Release = ureq::get(&url)
+        .set("User-Agent", concat!("tm-g29/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .with_context(|| format!("Cannot query releases of {}", options.repo))?
+        .into_json()?;
+
+    let latest = release.tag_name.trim_start_matches('v');
+    if !is_newer(latest, current) && !options.force {
+        println!("tm-g29 {} is up to date (latest release {})", current, latest);
+        return Ok(());
+    }
+    if options.check {
+        println!("tm-g29 {} is available, {} installed. Run `tm-g29 self-update` to install it.", latest, current);
+        return Ok(());
+    }
+
+    let download = |name: &str| -> Result<Vec<u8>> {
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| anyhow!("Release {} has no {}", release.tag_name, name))?;
+        let mut bytes = Vec::new();
+        ureq::get(&asset.browser_download_url)
+            .set("User-Agent", concat!("tm-g29/", env!("CARGO_PKG_VERSION")))
+            .call()
+            .with_context(|| format!("Cannot download {}", name))?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
+        Ok(bytes)
+    };
+
+    let sums = download("SHA256SUMS")?;
+    let signature = download("SHA256SUMS.sig")?;
+    let signature = Signature::from_slice(&signature).map_err(|_| anyhow!("SHA256SUMS.sig is not an Ed25519 signature"))?;
+    VerifyingKey::from_bytes(&key)
+        .map_err(|_| anyhow!("Invalid release key in this build"))?
+        .verify(&sums, &signature)
+        .map_err(|_| anyhow!("SHA256SUMS signature does not match the release key, refusing to update"))?;
+    let sums = std::str::from_utf8(&sums)?;
+
+    // Only the signed version is trusted, the tag could name any release
+    let signed = signed_version(sums)
+        .ok_or_else(|| anyhow!("SHA256SUMS of release {} names no version, refusing to update", release.tag_name))?;
+    if signed != latest {
+        return Err(anyhow!("Release {} carries the signed files of {}, refusing to update", release.tag_name, signed));
+    }
+    if !is_newer(signed, current) && !options.force {
+        return Err(anyhow!("Signed version {} is not newer than {}, refusing to update", signed, current));
+    }
+
+    let asset_name = asset_name();
+    let expected = sums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == asset_name)
+        .map(|(sum, _)| sum.to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("No checksum for {} in release {}", asset_name, release.tag_name))?;
+
+    let binary = download(&asset_name)?;
+    let actual: String = Sha256::digest(&binary).iter().map(|byte| format!("{:02x}", byte)).collect();
+    if actual != expected {
+        return Err(anyhow!("Checksum mismatch for {}, refusing to update", asset_name));
+    }
+
+    replace_executable(&binary)?;
+    println!("Updated tm-g29 {} -> {}", current, latest);
+    Ok(())
+}
+
+#[cfg(not(feature = "self-update"))]
+pub fn self_update(_options: &UpdateOptions) -> Result<()> {
+    Err(anyhow::anyhow!("This build of tm-g29 has no self-update support, download new releases manually"))
+}
+
+/// Release asset for this platform
+#[cfg(feature = "self-update")]
+fn asset_name() -> String {
+    format!("tm-g29-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+/// Public key release checksums are signed with, set at build time
+#[cfg(feature = "self-update")]
+fn release_key() -> Result<[u8; 32]> {
+    let hex = option_env!("TM_G29_RELEASE_KEY")
+        .ok_or_else(|| anyhow::anyhow!("This build has no release signing key, updates cannot be verified"))?;
+    let bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<_>>()
+        .ok_or_else(|| anyhow::anyhow!("TM_G29_RELEASE_KEY is not valid hex"))?;
+    bytes.try_into().map_err(|_| anyhow::anyhow!("TM_G29_RELEASE_KEY must be 32 bytes"))
+}
+
+/// Version from the `version <x.y.z>` line of a `SHA256SUMS` file
+#[cfg(feature = "self-update")]
+fn signed_version(sums: &str) -> Option<&str> {
+    sums.lines()
+        .find_map(|line| line.strip_prefix("version "))
+        .map(|version| version.trim().trim_start_matches('v'))
+        .filter(|version| !version.is_empty())
+}
+
+/// Whether dotted version `candidate` is newer than `current`
+#[cfg(feature = "self-update")]
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parse(candidate) > parse(current)
+}
+
+/// Swap the running executable for `binary` with renames in its directory
+#[cfg(feature = "self-update")]
+fn replace_executable(binary: &[u8]) -> Result<()> {
+    let current = std::env::current_exe()?;
+    let staged = current.with_extension("new");
+    std::fs::write(&staged, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Windows cannot overwrite a running executable but can rename it
+    #[cfg(windows)]
+    {
+        let old = current.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&current, &old)?;
+    }
+
+    if let Err(e) = std::fs::rename(&staged, &current) {
+        #[cfg(windows)]
+        let _ = std::fs::rename(current.with_extension("old"), &current);
+        let _ = std::fs::remove_file(&staged);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "self-update"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_newer_versions_update() {
+        assert!(is_newer("0.3.0", "0.2.9"));
+        assert!(is_newer("0.10.0", "0.9.0"));
+        assert!(is_newer("1.0", "0.99.99"));
+        assert!(is_newer("0.2.1", "0.2"));
+        assert!(!is_newer("0.2.0", "0.2.0"));
+        assert!(!is_newer("0.1.9", "0.2.0"));
+        // Pre-release and build suffixes compare as their release
+        assert!(!is_newer("0.2.0-rc1", "0.2.0"));
+        assert!(!is_newer("0.2.0+build5", "0.2.0"));
+        assert!(!is_newer("garbage", "0.2.0"));
+    }
+
+    #[test]
+    fn test_version_is_read_from_the_signed_checksums() {
+        let sums = "version v0.3.0\n0123abcd  tm-g29-linux-x86_64\n4567ef01 *tm-g29-windows-x86_64.exe\n";
+        assert_eq!(signed_version(sums), Some("0.3.0"));
+        assert_eq!(signed_version("0123abcd  tm-g29-linux-x86_64\n"), None);
+        assert_eq!(signed_version("version \n"), None);
+    }
+}
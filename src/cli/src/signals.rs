@@ -0,0 +1,75 @@
+//! Unix signal controls for the run loop
+//!
+//! - SIGHUP reloads the configuration file
+//! - SIGUSR1 toggles pause
+//! - SIGUSR2 dumps the recent-state history as a trace file
+//! - SIGTERM stops the translator cleanly, as `run --stop` does
+//!
+//! On other platforms this task does nothing.
+
+use std::path::PathBuf;
+use thrustmaster_core::TranslatorControl;
+
+#[cfg(unix)]
+pub async fn handle_signals(control: TranslatorControl, config_path: PathBuf, state_dump: PathBuf) {
+    use tokio::signal::unix::{signal, SignalKind};
+    use tracing::{error, info, warn};
+
+    let (mut hangup, mut usr1, mut usr2, mut terminate) = match (
+        signal(SignalKind::hangup()),
+        signal(SignalKind::user_defined1()),
+        signal(SignalKind::user_defined2()),
+        signal(SignalKind::terminate()),
+    ) {
+        (Ok(hangup), Ok(usr1), Ok(usr2), Ok(terminate)) => (hangup, usr1, usr2, terminate),
+        _ => {
+            warn!("Failed to install signal handlers, runtime signal controls are disabled");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {
+                info!("SIGHUP received, reloading {}", config_path.display());
+                match crate::load_config(&config_path).await {
+                    Ok(config) => control.request_reload(config),
+                    Err(e) => error!("Keeping current configuration: {}", e),
+                }
+            }
+            _ = terminate.recv() => {
+                info!("SIGTERM received, shutting down");
+                control.request_shutdown();
+            }
+            _ = usr1.recv() => {
+                if control.toggle_pause() {
+                    info!("SIGUSR1 received, translation paused");
+                } else {
+                    info!("SIGUSR1 received, translation resumed");
+                }
+            }
+            _ = usr2.recv() => {
+                let snapshot = control.state_snapshot();
+                let written = std::fs::File::create(&state_dump)
+                    .map(std::io::BufWriter::new)
+                    .and_then(|mut file| {
+                        snapshot.write_trace(&mut file)?;
+                        std::io::Write::flush(&mut file)
+                    });
+                match written {
+                    Ok(()) => info!(
+                        "SIGUSR2 received, wrote {} recent reports to {}",
+                        snapshot.inputs.len(),
+                        state_dump.display()
+                    ),
+                    Err(e) => error!("Failed to write state dump {}: {}", state_dump.display(), e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn handle_signals(_control: TranslatorControl, _config_path: PathBuf, _state_dump: PathBuf) {
+    std::future::pending::<()>().await
+}
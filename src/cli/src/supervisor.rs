@@ -0,0 +1,187 @@
+//! Crash-loop supervision for headless rigs
+//!
+//! Restarts the translation pipeline after recoverable failures, waiting
+//! with exponential backoff between attempts and giving up after a bounded
+//! number of restarts. Every failure is written to an incident log so it can
+//! be inspected after the fact.
+
+use anyhow::Result;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thrustmaster_core::TranslatorError;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct SupervisorPolicy {
+    /// Restarts allowed before giving up
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// A run lasting this long resets the restart count and backoff
+    pub stable_after: Duration,
+    pub incident_log: Option<PathBuf>,
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(300),
+            incident_log: None,
+        }
+    }
+}
+
+impl SupervisorPolicy {
+    /// Delay before restart number `restart`, counting from 1
+    pub fn backoff(&self, restart: u32) -> Duration {
+        let factor = 1u32.checked_shl(restart.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Run the pipeline built by `start`, restarting it until it stops cleanly,
+/// fails unrecoverably, or exceeds the restart budget
+pub async fn supervise<F, Fut>(policy: &SupervisorPolicy, mut start: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = thrustmaster_core::Result<()>>,
+{
+    let mut restarts = 0u32;
+
+    loop {
+        let started = Instant::now();
+        let result = start().await;
+        let uptime = started.elapsed();
+
+        let err = match result {
+            Ok(()) => {
+                info!("Translator stopped normally");
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+
+        if uptime >= policy.stable_after {
+            restarts = 0;
+        }
+        log_incident(policy, &err, uptime, restarts);
+
+        if !err.is_recoverable() {
+            error!("Translator failed with an unrecoverable error: {}", err);
+            return Err(err.into());
+        }
+        if restarts >= policy.max_restarts {
+            error!("Translator failed {} times in a row, giving up", restarts + 1);
+            return Err(err.into());
+        }
+
+        restarts += 1;
+        let delay = policy.backoff(restarts);
+        warn!(
+            "Translator failed after {:.1}s: {}; restart {}/{} in {:.1}s",
+            uptime.as_secs_f64(),
+            err,
+            restarts,
+            policy.max_restarts,
+            delay.as_secs_f64()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn log_incident(policy: &SupervisorPolicy, err: &TranslatorError, uptime: Duration, restarts: u32) {
+    let Some(path) = &policy.incident_log else {
+        return;
+    };
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let line = format!(
+        "{} uptime={:.1}s restarts={} recoverable={} error=\"{}\"\n",
+        timestamp,
+        uptime.as_secs_f64(),
+        restarts,
+        err.is_recoverable(),
+        err
+    );
+
+    let written = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(e) = written {
+        warn!("Failed to write incident log {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+
+    type Run = Pin<Box<dyn Future<Output = thrustmaster_core::Result<()>>>>;
+
+    /// `start` playing back `script`, each run lasting its duration before ending with its result,
+    /// and the seconds since the test began at which each run started
+    fn scripted(
+        script: Vec<(u64, thrustmaster_core::Result<()>)>,
+    ) -> (impl FnMut() -> Run, Arc<Mutex<Vec<u64>>>) {
+        let began = Instant::now();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let mut script = VecDeque::from(script);
+        let log = starts.clone();
+        let start = move || {
+            log.lock().unwrap().push(began.elapsed().as_secs());
+            let (seconds, result) = script.pop_front().expect("supervisor started the pipeline more often than scripted");
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                result
+            }) as Run
+        };
+        (start, starts)
+    }
+
+    fn policy(max_restarts: u32) -> SupervisorPolicy {
+        SupervisorPolicy {
+            max_restarts,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+            stable_after: Duration::from_secs(5),
+            incident_log: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restarts_back_off_until_the_budget_is_spent() {
+        let script = (0..4).map(|_| (0, Err(TranslatorError::Timeout))).collect();
+        let (start, starts) = scripted(script);
+        assert!(supervise(&policy(3), start).await.is_err());
+        // 1 s, 2 s, then capped at 3 s; the fourth failure is one too many
+        assert_eq!(*starts.lock().unwrap(), [0, 1, 3, 6]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stable_run_resets_restarts_and_backoff() {
+        let script = vec![(0, Err(TranslatorError::Timeout)), (10, Err(TranslatorError::Timeout)), (0, Ok(()))];
+        let (start, starts) = scripted(script);
+        // Without the reset the second failure would exceed a budget of one restart
+        supervise(&policy(1), start).await.unwrap();
+        assert_eq!(*starts.lock().unwrap(), [0, 1, 12]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unrecoverable_error_is_not_restarted() {
+        let (start, starts) = scripted(vec![(0, Err(TranslatorError::config_error("no wheel configured")))]);
+        let err = supervise(&policy(3), start).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<TranslatorError>(), Some(TranslatorError::ConfigError { .. })));
+        assert_eq!(*starts.lock().unwrap(), [0]);
+    }
+}
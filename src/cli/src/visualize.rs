@@ -0,0 +1,208 @@
+//! Offline analysis of recorded session traces
+//!
+//! Prints per-channel statistics and ASCII plots of a trace written by
+//! `tm-g29 run --trace`, and can render the same channels as an SVG.
+
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::path::Path;
+use thrustmaster_core::convert::U10_MAX;
+use thrustmaster_core::{NormalizedAxis, Recording, SteeringAngle};
+
+const ASCII_HEIGHT: usize = 8;
+const SVG_CHANNEL_HEIGHT: usize = 120;
+
+/// One plotted signal with samples as (seconds, value)
+struct Channel {
+    name: &'static str,
+    min: f64,
+    max: f64,
+    samples: Vec<(f64, f64)>,
+}
+
+impl Channel {
+    fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|(_, v)| v).sum::<f64>() / self.samples.len() as f64
+    }
+
+    fn observed_range(&self) -> Option<(f64, f64)> {
+        let mut values = self.samples.iter().map(|(_, v)| *v);
+        let first = values.next()?;
+        Some(values.fold((first, first), |(lo, hi), v| (lo.min(v), hi.max(v))))
+    }
+
+    /// Average the samples into `width` equally long time buckets
+    fn resample(&self, duration: f64, width: usize) -> Vec<Option<f64>> {
+        let mut sums = vec![(0.0, 0usize); width];
+        for &(t, v) in &self.samples {
+            let bucket = if duration > 0.0 { ((t / duration) * width as f64) as usize } else { 0 };
+            let (sum, count) = &mut sums[bucket.min(width - 1)];
+            *sum += v;
+            *count += 1;
+        }
+        sums.into_iter()
+            .map(|(sum, count)| (count > 0).then(|| sum / count as f64))
+            .collect()
+    }
+
+    /// Position of `value` in the channel's full scale, 0.0..=1.0
+    fn normalize(&self, value: f64) -> f64 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+fn channels(recording: &Recording, steering_range: u16) -> Vec<Channel> {
+    let input = |name, f: fn(&thrustmaster_core::device::G29InputReport) -> f64, max| Channel {
+        name,
+        min: 0.0,
+        max,
+        samples: recording
+            .inputs
+            .iter()
+            .map(|i| (i.timestamp.as_secs_f64(), f(&i.report)))
+            .collect(),
+    };
+
+    let half_range = steering_range as f64 / 2.0;
+    let steering = Channel {
+        name: "steering°",
+        min: -half_range,
+        max: half_range,
+        samples: recording
+            .inputs
+            .iter()
+            .map(|i| {
+                let axis = NormalizedAxis::from_g29_steering(i.report.steering);
+                (i.timestamp.as_secs_f64(), SteeringAngle::from_axis(axis, steering_range).degrees() as f64)
+            })
+            .collect(),
+    };
+
+    vec![
+        steering,
+        input("throttle", |r| r.throttle as f64, U10_MAX as f64),
+        input("brake", |r| r.brake as f64, U10_MAX as f64),
+        input("clutch", |r| r.clutch as f64, U10_MAX as f64),
+        Channel {
+            name: "force",
+            min: i16::MIN as f64,
+            max: i16::MAX as f64,
+            samples: recording
+                .forces
+                .iter()
+                .map(|f| (f.timestamp.as_secs_f64(), f.magnitude as f64))
+                .collect(),
+        },
+    ]
+}
+
+/// Load a trace, print statistics and ASCII plots, and optionally write an SVG
+///
+/// Steering is shown in degrees for a wheel with `steering_range` degrees lock to lock.
+pub fn visualize(path: &Path, svg: Option<&Path>, width: usize, steering_range: u16) -> Result<()> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let recording = Recording::read_trace(file).map_err(|e| anyhow::anyhow!("Failed to read trace: {}", e))?;
+    let duration = recording.duration().as_secs_f64();
+    let width = width.max(10);
+    let channels = channels(&recording, steering_range);
+
+    println!("Trace: {}", path.display());
+    println!("Duration: {:.2}s", duration);
+    println!("Input reports: {}", recording.inputs.len());
+    if duration > 0.0 {
+        println!("Average input rate: {:.0} Hz", recording.inputs.len() as f64 / duration);
+    }
+    println!("Force updates: {}", recording.forces.len());
+    println!();
+
+    println!("{:<10} {:>10} {:>10} {:>10}", "channel", "min", "max", "mean");
+    for channel in &channels {
+        match channel.observed_range() {
+            Some((lo, hi)) => {
+                println!("{:<10} {:>10.0} {:>10.0} {:>10.1}", channel.name, lo, hi, channel.mean())
+            }
+            None => println!("{:<10} {:>10} {:>10} {:>10}", channel.name, "-", "-", "-"),
+        }
+    }
+
+    for channel in channels.iter().filter(|c| !c.samples.is_empty()) {
+        println!();
+        println!("{}:", channel.name);
+        print!("{}", ascii_plot(channel, duration, width));
+    }
+
+    if let Some(svg_path) = svg {
+        std::fs::write(svg_path, svg_plot(&channels, duration, width))?;
+        println!();
+        println!("Wrote SVG plot to {}", svg_path.display());
+    }
+
+    Ok(())
+}
+
+fn ascii_plot(channel: &Channel, duration: f64, width: usize) -> String {
+    let columns = channel.resample(duration, width);
+    let mut grid = vec![vec![' '; width]; ASCII_HEIGHT];
+
+    for (x, value) in columns.iter().enumerate() {
+        if let Some(value) = value {
+            let level = (channel.normalize(*value) * (ASCII_HEIGHT - 1) as f64).round() as usize;
+            grid[ASCII_HEIGHT - 1 - level][x] = '*';
+        }
+    }
+
+    let mut out = String::new();
+    for row in grid {
+        let _ = writeln!(out, "  |{}", row.into_iter().collect::<String>());
+    }
+    let _ = writeln!(out, "  +{}", "-".repeat(width));
+    let _ = writeln!(out, "   0s{:>w$}", format!("{:.1}s", duration), w = width.saturating_sub(2));
+    out
+}
+
+fn svg_plot(channels: &[Channel], duration: f64, width: usize) -> String {
+    let plot_width = width * 8;
+    let height = channels.len() * SVG_CHANNEL_HEIGHT;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"#,
+        plot_width, height
+    );
+    for (index, channel) in channels.iter().enumerate() {
+        let top = index * SVG_CHANNEL_HEIGHT;
+        let plot_height = (SVG_CHANNEL_HEIGHT - 20) as f64;
+
+        let _ = writeln!(
+            out,
+            r##"  <rect x="0" y="{}" width="{}" height="{}" fill="none" stroke="#ccc"/>"##,
+            top, plot_width, SVG_CHANNEL_HEIGHT
+        );
+        let _ = writeln!(out, r#"  <text x="4" y="{}">{}</text>"#, top + 14, channel.name);
+
+        let points: Vec<String> = channel
+            .resample(duration, plot_width)
+            .iter()
+            .enumerate()
+            .filter_map(|(x, value)| {
+                value.map(|v| {
+                    let y = top as f64 + 18.0 + (1.0 - channel.normalize(v)) * plot_height;
+                    format!("{},{:.1}", x, y)
+                })
+            })
+            .collect();
+        if !points.is_empty() {
+            let _ = writeln!(
+                out,
+                r##"  <polyline fill="none" stroke="#1f77b4" points="{}"/>"##,
+                points.join(" ")
+            );
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
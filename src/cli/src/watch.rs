@@ -0,0 +1,68 @@
+//! Configuration file watching for the run loop
+//!
+//! Editors save in bursts (truncate, write, rename a swap file over the
+//! original), so the parent directory is watched and events for the config
+//! file are collected until it has been quiet for `SETTLE`. The file is then
+//! loaded and validated like on SIGHUP; a file that fails to load leaves the
+//! running configuration as it was.
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thrustmaster_core::TranslatorControl;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// Quiet time after the last change before the file is loaded
+const SETTLE: Duration = Duration::from_millis(300);
+
+pub async fn watch_config(control: TranslatorControl, config_path: PathBuf) {
+    let directory = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let Some(file_name) = config_path.file_name().map(|name| name.to_os_string()) else {
+        warn!("Not watching {}, it names no file", config_path.display());
+        return;
+    };
+
+    let (changes, mut changed) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event.paths.iter().any(|path| path.file_name() == Some(file_name.as_os_str()));
+        if relevant {
+            let _ = changes.send(());
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Configuration file watching disabled: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&directory, RecursiveMode::NonRecursive) {
+        warn!("Configuration file watching disabled, cannot watch {}: {}", directory.display(), e);
+        return;
+    }
+    info!("Watching {} for changes", config_path.display());
+
+    while changed.recv().await.is_some() {
+        // Wait for the save to finish
+        while let Ok(Some(())) = tokio::time::timeout(SETTLE, changed.recv()).await {}
+        reload(&control, &config_path).await;
+    }
+}
+
+async fn reload(control: &TranslatorControl, config_path: &Path) {
+    if !config_path.exists() {
+        // Moved away mid-save, the rename back brings another event
+        return;
+    }
+    info!("{} changed, reloading", config_path.display());
+    match crate::load_config(config_path).await {
+        Ok(config) => control.request_reload(config),
+        Err(e) => error!("Keeping current configuration: {}", e),
+    }
+}
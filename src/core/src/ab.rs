@@ -0,0 +1,268 @@
+//! Blind A/B comparison of two FFB tunings
+//!
+//! Tuning force feedback by feel means comparing against the memory of how
+//! the last setting felt, which rarely survives a trip to the config file.
+//! With `[ab_test_config]` enabled the translator instead alternates between
+//! the FFB settings of two profiles every `interval_secs`, announcing each
+//! switch with a short haptic blip but not which side is now active. The
+//! first side is picked at random. Whenever the current feel is the better
+//! one the driver presses the mark button (or the `AbMark` hotkey), and the
+//! session report at the end reveals which profile each round used, how long
+//! it was driven and how many marks it got.
+//!
+//! Only `[ffb_config]` of the two profiles is compared; input and output
+//! settings stay those of the running configuration.
+
+use crate::config::{Config, FfbConfig};
+use crate::error::{Result, TranslatorError};
+use crate::profile;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// One of the two tunings under comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl Side {
+    fn index(self) -> usize {
+        match self {
+            Side::A => 0,
+            Side::B => 1,
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Side::A => Side::B,
+            Side::B => Side::A,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Side::A => "A",
+            Side::B => "B",
+        })
+    }
+}
+
+/// Stretch of the session driven on one side
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbRound {
+    pub side: Side,
+    /// Offset from the start of the comparison
+    pub started: Duration,
+    pub duration: Duration,
+    /// Times the driver marked this round as feeling better
+    pub marks: u32,
+}
+
+/// Rounds driven so far and the profiles behind the sides
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AbReport {
+    /// Profile names of A and B, `base` for the configuration itself
+    pub names: [String; 2],
+    pub rounds: Vec<AbRound>,
+}
+
+impl AbReport {
+    /// Total time driven and marks of `side`
+    pub fn totals(&self, side: Side) -> (Duration, u32) {
+        self.rounds
+            .iter()
+            .filter(|round| round.side == side)
+            .fold((Duration::ZERO, 0), |(time, marks), round| (time + round.duration, marks + round.marks))
+    }
+
+    /// Side with more marks, `None` on a tie
+    pub fn preferred(&self) -> Option<Side> {
+        let (a, b) = (self.totals(Side::A).1, self.totals(Side::B).1);
+        match a.cmp(&b) {
+            std::cmp::Ordering::Greater => Some(Side::A),
+            std::cmp::Ordering::Less => Some(Side::B),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+impl fmt::Display for AbReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "FFB A/B comparison, {} rounds", self.rounds.len())?;
+        for (i, round) in self.rounds.iter().enumerate() {
+            writeln!(
+                f,
+                "  round {:>2} at {:>5}s: {} ({}), {}s, {} marks",
+                i + 1,
+                round.started.as_secs(),
+                round.side,
+                self.names[round.side.index()],
+                round.duration.as_secs(),
+                round.marks
+            )?;
+        }
+        for side in [Side::A, Side::B] {
+            let (time, marks) = self.totals(side);
+            writeln!(f, "  {} = {}: {}s driven, {} marks", side, self.names[side.index()], time.as_secs(), marks)?;
+        }
+        match self.preferred() {
+            Some(side) => write!(f, "  Preferred: {} ({})", side, self.names[side.index()]),
+            None => write!(f, "  No preference marked"),
+        }
+    }
+}
+
+/// Running comparison, stepped by the input loop
+#[derive(Debug, Clone)]
+pub struct AbTest {
+    sides: [FfbConfig; 2],
+    interval: Duration,
+    mark_button: Option<u8>,
+    blip: f32,
+    started: Instant,
+    report: AbReport,
+    last_buttons: u32,
+}
+
+impl AbTest {
+    /// Comparison `config` asks for, `None` when it is off
+    pub fn from_config(config: &Config, now: Instant) -> Result<Option<Self>> {
+        let ab = &config.ab_test_config;
+        if !ab.enabled {
+            return Ok(None);
+        }
+        let side = |name: &Option<String>| -> Result<(FfbConfig, String)> {
+            match name {
+                None => Ok((config.ffb_config.clone(), "base".to_string())),
+                Some(name) => profile::effective(config, Some(name))
+                    .map(|layered| (layered.ffb_config, name.clone()))
+                    .map_err(|e| TranslatorError::config_error(format!("A/B profile {}: {}", name, e))),
+            }
+        };
+        let (a, a_name) = side(&ab.profile_a)?;
+        let (b, b_name) = side(&ab.profile_b)?;
+        if a_name == b_name {
+            return Err(TranslatorError::config_error("A/B comparison needs two different profiles"));
+        }
+
+        let mut hasher = RandomState::new().build_hasher();
+        now.hash(&mut hasher);
+        let first = if hasher.finish() & 1 == 0 { Side::A } else { Side::B };
+
+        Ok(Some(Self {
+            sides: [a, b],
+            interval: Duration::from_secs(ab.interval_secs.max(1) as u64),
+            mark_button: ab.mark_button,
+            blip: ab.blip_strength.clamp(0.0, 1.0),
+            started: now,
+            report: AbReport {
+                names: [a_name, b_name],
+                rounds: vec![AbRound { side: first, started: Duration::ZERO, duration: Duration::ZERO, marks: 0 }],
+            },
+            last_buttons: 0,
+        }))
+    }
+
+    /// Side driven right now
+    pub fn current(&self) -> Side {
+        self.current_round().side
+    }
+
+    /// FFB settings of the side driven right now
+    pub fn ffb_config(&self) -> &FfbConfig {
+        &self.sides[self.current().index()]
+    }
+
+    /// Strength of the switch blip, 0.0 - 1.0
+    pub fn blip_strength(&self) -> f32 {
+        self.blip
+    }
+
+    /// Account time up to `now`, returning the new side when it is time to switch
+    pub fn poll(&mut self, now: Instant) -> Option<Side> {
+        let elapsed = now.saturating_duration_since(self.started);
+        let round = self.current_round_mut();
+        round.duration = elapsed.saturating_sub(round.started);
+        if round.duration < self.interval {
+            return None;
+        }
+        let side = round.side.other();
+        self.report.rounds.push(AbRound { side, started: elapsed, duration: Duration::ZERO, marks: 0 });
+        Some(side)
+    }
+
+    /// Count a press of the mark button in `buttons`, returning whether one was pressed
+    pub fn observe_buttons(&mut self, buttons: u32) -> bool {
+        let Some(button) = self.mark_button.filter(|&b| b < crate::device::SOURCE_BUTTON_COUNT) else {
+            return false;
+        };
+        let mask = 1u32 << button;
+        let pressed = buttons & mask != 0 && self.last_buttons & mask == 0;
+        self.last_buttons = buttons;
+        if pressed {
+            self.mark();
+        }
+        pressed
+    }
+
+    /// The driver prefers what the current round feels like
+    pub fn mark(&mut self) {
+        self.current_round_mut().marks += 1;
+    }
+
+    pub fn report(&self) -> &AbReport {
+        &self.report
+    }
+
+    fn current_round(&self) -> &AbRound {
+        self.report.rounds.last().expect("a comparison always has a round")
+    }
+
+    fn current_round_mut(&mut self) -> &mut AbRound {
+        self.report.rounds.last_mut().expect("a comparison always has a round")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sides_alternate_and_marks_count_per_round() {
+        let mut config = Config::default();
+        config.profiles.insert("soft".to_string(), toml::from_str("[ffb_config]\nglobal_gain = 0.5\n").unwrap());
+        config.ab_test_config.enabled = true;
+        config.ab_test_config.profile_b = Some("soft".to_string());
+        config.ab_test_config.interval_secs = 10;
+        config.ab_test_config.mark_button = Some(3);
+
+        let start = Instant::now();
+        let mut ab = AbTest::from_config(&config, start).unwrap().unwrap();
+        let first = ab.current();
+        assert!(ab.poll(start + Duration::from_secs(5)).is_none());
+        assert!(ab.observe_buttons(1 << 3));
+        assert!(!ab.observe_buttons(1 << 3));
+
+        assert_eq!(ab.poll(start + Duration::from_secs(10)), Some(first.other()));
+        let soft_gain = if ab.current() == Side::B { 0.5 } else { config.ffb_config.global_gain };
+        assert_eq!(ab.ffb_config().global_gain, soft_gain);
+        ab.mark();
+        ab.mark();
+        ab.poll(start + Duration::from_secs(15));
+
+        let report = ab.report();
+        assert_eq!(report.rounds.len(), 2);
+        assert_eq!(report.totals(first), (Duration::from_secs(10), 1));
+        assert_eq!(report.totals(first.other()), (Duration::from_secs(5), 2));
+        assert_eq!(report.preferred(), Some(first.other()));
+
+        config.ab_test_config.profile_b = None;
+        assert!(AbTest::from_config(&config, start).is_err());
+    }
+}
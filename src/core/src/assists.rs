@@ -0,0 +1,144 @@
+//! Driving assists for players with limited mobility
+//!
+//! Computed by `InputTranslator` on the translated axes, so every game sees
+//! ordinary wheel input:
+//! - the steering rate limiter caps how fast the virtual wheel turns, so a
+//!   jerky or involuntary movement becomes a smooth one;
+//! - throttle hold latches the current throttle on a button press and keeps
+//!   it until the button is pressed again or the brake is applied;
+//! - one-pedal driving maps the throttle pedal's first part of travel to
+//!   braking, so the car slows down as the pedal is released.
+
+use crate::config::Assists;
+use crate::convert;
+use crate::device::SOURCE_BUTTON_COUNT;
+use crate::units::NormalizedAxis;
+use std::time::Instant;
+
+/// Brake depth that releases a held throttle
+const HOLD_RELEASE_BRAKE: f32 = 0.1;
+
+/// Running state of the assists
+#[derive(Debug, Clone)]
+pub struct AssistState {
+    config: Assists,
+    /// Last steering sent and when, for the rate limiter
+    steering: Option<(f32, Instant)>,
+    held_throttle: Option<f32>,
+    last_buttons: u32,
+}
+
+impl AssistState {
+    pub fn new(config: Assists) -> Self {
+        Self { config, steering: None, held_throttle: None, last_buttons: 0 }
+    }
+
+    /// Whether a throttle is being held for the player
+    pub fn is_holding_throttle(&self) -> bool {
+        self.held_throttle.is_some()
+    }
+
+    /// G29 steering moved towards `target` no faster than the configured rate
+    ///
+    /// The rate is in degrees per second on a wheel turning `range_degrees`
+    /// lock to lock.
+    pub fn limit_steering(&mut self, target: u16, range_degrees: u16, now: Instant) -> u16 {
+        if self.config.max_steering_rate <= 0.0 {
+            return target;
+        }
+        let target = NormalizedAxis::from_g29_steering(target).value();
+        let limited = match self.steering {
+            Some((last, at)) => {
+                let step = convert::degrees_to_normalized(
+                    self.config.max_steering_rate * now.duration_since(at).as_secs_f32(),
+                    range_degrees,
+                );
+                last + (target - last).clamp(-step, step)
+            }
+            None => target,
+        };
+        self.steering = Some((limited, now));
+        NormalizedAxis::saturating(limited).to_g29_steering()
+    }
+
+    /// G29 throttle and brake after one-pedal driving and throttle hold
+    ///
+    /// The hold button is taken out of `buttons`, it never reaches the game.
+    pub fn pedals(&mut self, throttle: u16, brake: u16, buttons: u32) -> (u16, u16, u32) {
+        let mut throttle = NormalizedAxis::from_g29_pedal(throttle).value();
+        let mut brake = NormalizedAxis::from_g29_pedal(brake).value();
+
+        if self.config.one_pedal {
+            let coast = self.config.one_pedal_coast.clamp(0.0, 0.99);
+            if throttle < coast {
+                let regen = (coast - throttle) / coast.max(f32::EPSILON) * self.config.one_pedal_brake;
+                brake = brake.max(regen);
+                throttle = 0.0;
+            } else {
+                throttle = (throttle - coast) / (1.0 - coast);
+            }
+        }
+
+        let mut buttons = buttons;
+        if let Some(button) = self.config.throttle_hold_button.filter(|&b| b < SOURCE_BUTTON_COUNT) {
+            let mask = 1u32 << button;
+            if buttons & mask != 0 && self.last_buttons & mask == 0 {
+                self.held_throttle = match self.held_throttle {
+                    Some(_) => None,
+                    None => (throttle > 0.0).then_some(throttle),
+                };
+                tracing::info!("Throttle hold {}", if self.held_throttle.is_some() { "on" } else { "off" });
+            }
+            self.last_buttons = buttons;
+            buttons &= !mask;
+        }
+        if brake > HOLD_RELEASE_BRAKE && self.held_throttle.take().is_some() {
+            tracing::info!("Throttle hold released by braking");
+        }
+        if let Some(held) = self.held_throttle {
+            throttle = throttle.max(held);
+        }
+
+        (
+            NormalizedAxis::saturating(throttle).to_g29_pedal(),
+            NormalizedAxis::saturating(brake).to_g29_pedal(),
+            buttons,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_steering_rate_is_limited() {
+        let mut assists = AssistState::new(Assists { max_steering_rate: 450.0, ..Assists::default() });
+        let start = Instant::now();
+        assert_eq!(assists.limit_steering(0x8000, 900, start), 0x8000);
+
+        // 450°/s on a 900° wheel covers half of one side in 0.5 s
+        let full_right = NormalizedAxis::MAX.to_g29_steering();
+        let steering = assists.limit_steering(full_right, 900, start + Duration::from_millis(500));
+        assert_eq!(steering, NormalizedAxis::saturating(0.5).to_g29_steering());
+    }
+
+    #[test]
+    fn test_one_pedal_and_throttle_hold() {
+        let config = Assists { one_pedal: true, one_pedal_coast: 0.2, one_pedal_brake: 0.5, throttle_hold_button: Some(3), ..Assists::default() };
+        let mut assists = AssistState::new(config);
+
+        // Released: half brake; past the coast point: rescaled throttle
+        assert_eq!(assists.pedals(0, 0, 0), (0, NormalizedAxis::saturating(0.5).to_g29_pedal(), 0));
+        assert_eq!(assists.pedals(1023, 0, 0).0, 1023);
+
+        let (_, _, buttons) = assists.pedals(1023, 0, 1 << 3);
+        assert_eq!(buttons, 0);
+        assert!(assists.is_holding_throttle());
+        // Held through lifting off, until the one-pedal braking kicks in
+        assert_eq!(assists.pedals(NormalizedAxis::saturating(0.2).to_g29_pedal(), 0, 0).0, 1023);
+        assert_eq!(assists.pedals(0, 0, 0).0, 0);
+        assert!(!assists.is_holding_throttle());
+    }
+}
@@ -0,0 +1,123 @@
+//! Buttons driving axes and axes pressing buttons
+//!
+//! Computed by `InputTranslator` on the translated axes, after curves,
+//! assists and menu mode:
+//! - `button_axes`: while a source button is held it moves a G29 axis to its
+//!   `value`. A pedal is pressed at least that deep, the steering is set to
+//!   that deflection; e.g. a handbrake button pulling the clutch fully.
+//! - `axis_buttons`: a G29 axis past its `threshold` presses a G29 button.
+//!   It is released once the axis falls back by `HYSTERESIS`, so a pedal
+//!   resting at the threshold does not chatter.
+//!
+//! A source button driving an axis still goes through `button_mapping`.
+
+use crate::config::{AxisButtonMapping, ButtonAxisMapping, G29Axis, InputConfig};
+use crate::device::SOURCE_BUTTON_COUNT;
+use crate::evdev::G29_BUTTON_COUNT;
+use crate::units::NormalizedAxis;
+
+/// How far an axis falls back past the threshold before its button releases
+const HYSTERESIS: f32 = 0.05;
+
+/// G29 axes in report units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Axes {
+    pub steering: u16,
+    pub throttle: u16,
+    pub brake: u16,
+    pub clutch: u16,
+}
+
+impl Axes {
+    /// Axis as -1.0 - 1.0 for steering, 0.0 - 1.0 for pedals
+    fn get(&self, axis: G29Axis) -> f32 {
+        match axis {
+            G29Axis::Steering => NormalizedAxis::from_g29_steering(self.steering).value(),
+            G29Axis::Throttle => NormalizedAxis::from_g29_pedal(self.throttle).value(),
+            G29Axis::Brake => NormalizedAxis::from_g29_pedal(self.brake).value(),
+            G29Axis::Clutch => NormalizedAxis::from_g29_pedal(self.clutch).value(),
+        }
+    }
+}
+
+/// Running state of the axis and button mappings
+#[derive(Debug, Clone, Default)]
+pub struct AxisMapper {
+    button_axes: Vec<ButtonAxisMapping>,
+    axis_buttons: Vec<AxisButtonMapping>,
+    /// Entries of `axis_buttons` past their threshold
+    engaged: Vec<bool>,
+}
+
+impl AxisMapper {
+    /// Mapper for `config`, skipping entries validation reports as out of range
+    pub fn new(config: &InputConfig) -> Self {
+        let button_axes: Vec<_> = config.button_axes.iter().filter(|m| m.button < SOURCE_BUTTON_COUNT).copied().collect();
+        let axis_buttons: Vec<_> = config.axis_buttons.iter().filter(|m| m.g29_button < G29_BUTTON_COUNT).copied().collect();
+        let engaged = vec![false; axis_buttons.len()];
+        Self { button_axes, axis_buttons, engaged }
+    }
+
+    /// Move the axes driven by the held `source_buttons`
+    pub fn drive_axes(&self, source_buttons: u32, axes: &mut Axes) {
+        for mapping in &self.button_axes {
+            if source_buttons & 1 << mapping.button == 0 {
+                continue;
+            }
+            let value = NormalizedAxis::saturating(mapping.value);
+            match mapping.axis {
+                G29Axis::Steering => axes.steering = value.to_g29_steering(),
+                G29Axis::Throttle => axes.throttle = axes.throttle.max(value.to_g29_pedal()),
+                G29Axis::Brake => axes.brake = axes.brake.max(value.to_g29_pedal()),
+                G29Axis::Clutch => axes.clutch = axes.clutch.max(value.to_g29_pedal()),
+            }
+        }
+    }
+
+    /// G29 buttons pressed by axes past their thresholds
+    pub fn axis_buttons(&mut self, axes: &Axes) -> u32 {
+        let mut buttons = 0u32;
+        for (mapping, engaged) in self.axis_buttons.iter().zip(&mut self.engaged) {
+            // Negative thresholds trigger below, to the left for steering
+            let (value, threshold) = if mapping.threshold < 0.0 {
+                (-axes.get(mapping.axis), -mapping.threshold)
+            } else {
+                (axes.get(mapping.axis), mapping.threshold)
+            };
+            *engaged = if *engaged { value > threshold - HYSTERESIS } else { value >= threshold };
+            if *engaged {
+                buttons |= 1 << mapping.g29_button;
+            }
+        }
+        buttons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_drives_clutch_and_brake_presses_button() {
+        let config = InputConfig {
+            button_axes: vec![ButtonAxisMapping { button: 4, axis: G29Axis::Clutch, value: 1.0 }],
+            axis_buttons: vec![AxisButtonMapping { axis: G29Axis::Brake, threshold: 0.9, g29_button: 20 }],
+            ..InputConfig::default()
+        };
+        let mut mapper = AxisMapper::new(&config);
+        let pedal = |depth: f32| NormalizedAxis::saturating(depth).to_g29_pedal();
+        let mut axes = Axes { steering: 0x8000, throttle: 0, brake: pedal(0.5), clutch: pedal(0.2) };
+
+        mapper.drive_axes(1 << 4, &mut axes);
+        assert_eq!(axes.clutch, 1023);
+
+        assert_eq!(mapper.axis_buttons(&axes), 0);
+        axes.brake = pedal(0.95);
+        assert_eq!(mapper.axis_buttons(&axes), 1 << 20);
+        // Held through small drops below the threshold
+        axes.brake = pedal(0.88);
+        assert_eq!(mapper.axis_buttons(&axes), 1 << 20);
+        axes.brake = pedal(0.8);
+        assert_eq!(mapper.axis_buttons(&axes), 0);
+    }
+}
@@ -0,0 +1,99 @@
+//! Latency figures for `tm-g29 bench`
+//!
+//! Two measurements feed the same summary. The translation figure times the
+//! input stages in-process on synthetic reports, without devices. The
+//! loopback figure, where the platform lets us open our own virtual device,
+//! injects a report and reads it back through the OS input stack, which is
+//! the delay a game actually sees on top of the wheel's own.
+
+use crate::config::Config;
+use crate::device::models::ThrustmasterModel;
+use crate::error::Result;
+use crate::pipe::InputPipe;
+use crate::presentation::Presenter;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Distribution of measured latencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Summary of `samples`, `None` if there are none
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let at = |fraction: f64| samples[((samples.len() - 1) as f64 * fraction).round() as usize];
+        Some(Self {
+            samples: samples.len(),
+            min: samples[0],
+            median: at(0.5),
+            p99: at(0.99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+impl LatencySummary {
+    /// One line in the units and number format of `presenter`
+    pub fn render(&self, presenter: &Presenter) -> String {
+        format!(
+            "{} samples, min {}, median {}, p99 {}, max {}",
+            self.samples,
+            presenter.duration(self.min),
+            presenter.duration(self.median),
+            presenter.duration(self.p99),
+            presenter.duration(self.max)
+        )
+    }
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&Presenter::default()))
+    }
+}
+
+/// Time the input stages on `samples` synthetic reports sweeping the steering range
+pub fn translation_latency(config: &Config, model: ThrustmasterModel, samples: usize) -> Result<Vec<Duration>> {
+    let mut pipe = InputPipe::new(config, model)?;
+    let mut frame = vec![0u8; pipe.frame_len()];
+    let mut times = Vec::with_capacity(samples);
+    for i in 0..samples {
+        let steering = (i as u32 * 0xFFFF / samples.max(1) as u32) as u16;
+        frame[1..3].copy_from_slice(&steering.to_le_bytes());
+        let started = Instant::now();
+        pipe.translate(&frame)?;
+        times.push(started.elapsed());
+    }
+    Ok(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_picks_percentiles_from_sorted_samples() {
+        assert_eq!(LatencySummary::from_samples(Vec::new()), None);
+
+        let samples = (1..=100).rev().map(Duration::from_micros).collect();
+        let summary = LatencySummary::from_samples(samples).unwrap();
+        assert_eq!(summary.samples, 100);
+        assert_eq!(summary.min, Duration::from_micros(1));
+        assert_eq!(summary.median, Duration::from_micros(51));
+        assert_eq!(summary.p99, Duration::from_micros(99));
+        assert_eq!(summary.max, Duration::from_micros(100));
+
+        let config = Config::default();
+        assert_eq!(translation_latency(&config, ThrustmasterModel::T300rs, 10).unwrap().len(), 10);
+    }
+}
@@ -0,0 +1,241 @@
+//! Wheel calibration: recording raw axis travel and applying it
+//!
+//! Worn potentiometers and pedals that never reach their end stops leave
+//! part of the axis unused, and a wheel that is straight may not read
+//! zero. The `calibrate` command runs a guided session that feeds every
+//! report into a `CalibrationSession`: the extremes are tracked the whole
+//! time, and the prompts mark the moments the wheel is held left, centered,
+//! or the pedals are released. The result is saved as `CalibrationData`,
+//! which `InputTranslator` applies to every report before deadzone,
+//! geometry and curves. The hand-set `AxisTuning` follows it, still ahead
+//! of the curves; the steering's is applied together with its geometry.
+
+use crate::config::{AxisCalibration, AxisTune, AxisTuning, CalibrationData, SteeringGeometry};
+use crate::device::ThrustmasterInputReport;
+use crate::units::NormalizedAxis;
+
+/// Smallest raw travel accepted for an axis; less means it was not moved
+const MIN_TRAVEL: i32 = 16;
+
+impl CalibrationData {
+    /// `report` with its axes stretched to the full range
+    pub fn apply(&self, report: ThrustmasterInputReport) -> ThrustmasterInputReport {
+        let pedal = |calibration: &AxisCalibration, raw: u8, invert: bool| {
+            let value = calibration.apply(raw);
+            if invert { u8::MAX - value } else { value }
+        };
+        ThrustmasterInputReport {
+            steering: self.apply_steering(report.steering),
+            throttle: pedal(&self.throttle, report.throttle, self.invert_throttle),
+            brake: pedal(&self.brake, report.brake, self.invert_brake),
+            clutch: pedal(&self.clutch, report.clutch, self.invert_clutch),
+            ..report
+        }
+    }
+
+    /// Raw steering scaled separately on each side of the recorded center
+    ///
+    /// A reversed wheel has `steering_min` above `steering_max`.
+    fn apply_steering(&self, raw: i16) -> i16 {
+        let center = self.steering_center as f32;
+        let offset = raw as f32 - center;
+        let span = |travel: f32| if travel.abs() < 1.0 { 1.0 } else { travel };
+        let toward_max = self.steering_max as f32 - center;
+        let value = if offset * toward_max >= 0.0 {
+            offset / span(toward_max) * i16::MAX as f32
+        } else {
+            offset / span(center - self.steering_min as f32) * -(i16::MIN as f32)
+        };
+        let value = if self.invert_steering { -value } else { value };
+        value.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+impl AxisTuning {
+    /// `report` with each pedal inverted, offset, deadzoned and saturated as configured
+    ///
+    /// Steering passes unchanged, see `AxisTune::apply_steering`.
+    pub fn apply(&self, report: ThrustmasterInputReport) -> ThrustmasterInputReport {
+        let untuned = AxisTune::default();
+        if [self.throttle, self.brake, self.clutch] == [untuned; 3] {
+            return report;
+        }
+        let pedal = |tune: &AxisTune, raw: u8| {
+            let value = tune.apply_pedal(NormalizedAxis::from_pedal_u8(raw).value());
+            (value * u8::MAX as f32).round() as u8
+        };
+        ThrustmasterInputReport {
+            throttle: pedal(&self.throttle, report.throttle),
+            brake: pedal(&self.brake, report.brake),
+            clutch: pedal(&self.clutch, report.clutch),
+            ..report
+        }
+    }
+}
+
+impl AxisTune {
+    /// Steering, -1.0 - 1.0, each side of the center scaled by `geometry` before the deadzone
+    pub fn apply_steering(&self, value: f32, geometry: &SteeringGeometry) -> f32 {
+        let value = (if self.invert { -value } else { value }) - self.center_offset;
+        let value = value * if value < 0.0 { geometry.left_scale } else { geometry.right_scale };
+        self.shape(value.abs().min(1.0)) * value.signum()
+    }
+
+    /// Pedal travel, 0.0 - 1.0
+    fn apply_pedal(&self, value: f32) -> f32 {
+        let value = (if self.invert { 1.0 - value } else { value }) - self.center_offset;
+        self.shape(value.max(0.0))
+    }
+
+    /// Travel past the deadzone rescaled so `saturation` reaches full
+    fn shape(&self, travel: f32) -> f32 {
+        let deadzone = self.deadzone.clamp(0.0, 1.0);
+        let full = self.saturation.min(1.0).max(deadzone + f32::EPSILON);
+        if travel <= deadzone {
+            0.0
+        } else {
+            ((travel - deadzone) / (full - deadzone)).min(1.0)
+        }
+    }
+}
+
+/// Raw values seen during a guided calibration
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationSession {
+    last: Option<ThrustmasterInputReport>,
+    steering: Option<(i16, i16)>,
+    /// Pedal extremes as (lowest, highest), throttle, brake and clutch
+    pedals: Option<[(u8, u8); 3]>,
+    left: Option<i16>,
+    center: Option<i16>,
+    pedals_released: Option<[u8; 3]>,
+}
+
+impl CalibrationSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account a report read during the session
+    pub fn observe(&mut self, report: &ThrustmasterInputReport) {
+        let (low, high) = self.steering.unwrap_or((report.steering, report.steering));
+        self.steering = Some((low.min(report.steering), high.max(report.steering)));
+
+        let raw = [report.throttle, report.brake, report.clutch];
+        let mut pedals = self.pedals.unwrap_or(raw.map(|value| (value, value)));
+        for ((low, high), value) in pedals.iter_mut().zip(raw) {
+            *low = (*low).min(value);
+            *high = (*high).max(value);
+        }
+        self.pedals = Some(pedals);
+        self.last = Some(*report);
+    }
+
+    /// Whether any report has been seen
+    pub fn has_input(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// The wheel is held at full left lock now
+    pub fn mark_left(&mut self) {
+        self.left = self.last.map(|report| report.steering);
+    }
+
+    /// The wheel is held straight now
+    pub fn mark_center(&mut self) {
+        self.center = self.last.map(|report| report.steering);
+    }
+
+    /// Every pedal is released now
+    pub fn mark_pedals_released(&mut self) {
+        self.pedals_released = self.last.map(|report| [report.throttle, report.brake, report.clutch]);
+    }
+
+    /// `previous` updated with every axis this session calibrated
+    ///
+    /// An axis keeps its previous values when it was skipped or not moved
+    /// far enough. Steering that read high at the left lock and pedals that
+    /// read high at rest are recorded as reversed; the inversion flags stay
+    /// as the user set them.
+    pub fn finish(&self, previous: &CalibrationData) -> CalibrationData {
+        let mut data = *previous;
+
+        if let (Some((low, high)), Some(center)) = (self.steering, self.center) {
+            if (high as i32 - low as i32) >= MIN_TRAVEL && low < center && center < high {
+                let reversed = self.left.is_some_and(|left| left > center);
+                data.steering_center = center;
+                (data.steering_min, data.steering_max) = if reversed { (high, low) } else { (low, high) };
+            }
+        }
+
+        if let (Some(pedals), Some(released)) = (self.pedals, self.pedals_released) {
+            let calibrations = [&mut data.throttle, &mut data.brake, &mut data.clutch];
+            for ((calibration, (low, high)), rest) in calibrations.into_iter().zip(pedals).zip(released) {
+                if (high as i32 - low as i32) < MIN_TRAVEL {
+                    continue;
+                }
+                // Pressed is whichever end is farther from rest
+                let pressed = if high - rest >= rest - low { high } else { low };
+                *calibration = AxisCalibration { min: rest, max: pressed };
+            }
+        }
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::HatDirection;
+
+    fn report(steering: i16, throttle: u8, brake: u8) -> ThrustmasterInputReport {
+        ThrustmasterInputReport { steering, throttle, brake, clutch: 0, buttons: 0, dpad: HatDirection::Centered, base_range: None, rim: None }
+    }
+
+    #[test]
+    fn test_session_records_and_applies_calibration() {
+        let mut session = CalibrationSession::new();
+        // Steering reaches -20000 and 30000 around a center at 1000; the brake reads reversed
+        session.observe(&report(-20000, 10, 250));
+        session.mark_left();
+        session.observe(&report(30000, 10, 250));
+        session.observe(&report(1000, 10, 250));
+        session.mark_center();
+        session.mark_pedals_released();
+        session.observe(&report(1000, 200, 50));
+
+        let data = session.finish(&CalibrationData { invert_throttle: true, ..CalibrationData::default() });
+        assert_eq!((data.steering_min, data.steering_center, data.steering_max), (-20000, 1000, 30000));
+        assert_eq!(data.throttle, AxisCalibration { min: 10, max: 200 });
+        assert_eq!(data.brake, AxisCalibration { min: 250, max: 50 });
+        // Never moved, kept as it was
+        assert_eq!(data.clutch, AxisCalibration::default());
+        assert!(data.invert_throttle);
+
+        let applied = data.apply(report(30000, 200, 50));
+        assert_eq!((applied.steering, applied.throttle, applied.brake), (i16::MAX, 0, u8::MAX));
+        assert_eq!(data.apply(report(1000, 10, 250)).steering, 0);
+        assert_eq!(data.apply(report(-20000, 10, 250)).steering, i16::MIN);
+        let passed = CalibrationData::default().apply(report(1234, 56, 78));
+        assert_eq!((passed.steering, passed.throttle, passed.brake), (1234, 56, 78));
+    }
+
+    #[test]
+    fn test_axis_tuning_inverts_and_trims_pedals() {
+        let tuning = AxisTuning {
+            brake: AxisTune { invert: true, ..AxisTune::default() },
+            throttle: AxisTune { center_offset: 0.1, deadzone: 0.05, saturation: 0.8, ..AxisTune::default() },
+            ..AxisTuning::default()
+        };
+        let tuned = tuning.apply(report(-1000, 26, 0));
+        // A throttle drifting to 10% at rest reads released, the brake reads reversed
+        assert_eq!((tuned.steering, tuned.throttle, tuned.brake), (-1000, 0, u8::MAX));
+        assert_eq!(tuning.apply(report(0, 230, 255)).throttle, u8::MAX);
+
+        let steering = AxisTune { invert: true, deadzone: 0.1, ..AxisTune::default() };
+        let geometry = SteeringGeometry::default();
+        assert_eq!(steering.apply_steering(0.05, &geometry), 0.0);
+        assert_eq!(steering.apply_steering(1.0, &geometry), -1.0);
+    }
+}
@@ -0,0 +1,415 @@
+//! Versioned binary container for recorded sessions
+//!
+//! A capture starts with `CAPTURE_MAGIC`, a `u16` format version and a TOML
+//! encoded `CaptureHeader`, followed by typed records framed as
+//! `<kind: u8> <len: u32 LE> <micros: u64 LE> <body>`. The length prefix lets
+//! readers skip record kinds added by newer versions, and header fields added
+//! later carry serde defaults, so old files keep loading as the format grows.
+//!
+//! Files from before the container (`TMG29OC1`, output reports only) are read
+//! as format version 1; `tm-g29 capture upgrade` rewrites them as the current
+//! version. Sessions saved as JSON lines (`Recording::write_jsonl`) load too.
+
+use crate::device::models::Rim;
+use crate::device::{G29InputReport, G29OutputReport, HatDirection, ThrustmasterInputReport};
+use crate::error::{Result, TranslatorError};
+use crate::recording::{
+    RecordedEvent, RecordedForce, RecordedInput, RecordedNote, RecordedOutput, RecordedWheel, Recording,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First bytes of a capture file
+pub const CAPTURE_MAGIC: &[u8; 8] = b"TMG29CAP";
+
+/// First bytes of a version 1 capture, output reports only
+pub const LEGACY_CAPTURE_MAGIC: &[u8; 8] = b"TMG29OC1";
+
+/// Format version written by this build
+pub const CAPTURE_VERSION: u16 = 2;
+
+/// Bytes of a record frame before its body
+const FRAME_HEADER_LEN: usize = 1 + 4 + 8;
+
+/// Typed records of a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RecordKind {
+    Input,   // Translated G29 input report
+    Output,  // Raw output report from the game
+    Force,   // Constant force magnitude sent to the wheel
+    Event,   // Runtime action such as pause or recenter
+    Note,    // Free text, e.g. why samples are missing
+    Wheel,   // Report as read from the wheel, before translation
+}
+
+impl RecordKind {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Input => 1,
+            Self::Output => 2,
+            Self::Force => 3,
+            Self::Event => 4,
+            Self::Note => 5,
+            Self::Wheel => 6,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(Self::Input),
+            2 => Some(Self::Output),
+            3 => Some(Self::Force),
+            4 => Some(Self::Event),
+            5 => Some(Self::Note),
+            6 => Some(Self::Wheel),
+            _ => None,
+        }
+    }
+}
+
+/// Metadata stored at the start of a capture
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    /// tm-g29 version that wrote the file
+    #[serde(default)]
+    pub tool_version: String,
+    /// Seconds since the Unix epoch when the file was written
+    #[serde(default)]
+    pub created_unix: u64,
+}
+
+impl CaptureHeader {
+    /// Header for a capture written now by this build
+    pub fn now() -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_unix: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        }
+    }
+}
+
+/// A capture file loaded into memory
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    pub version: u16,
+    pub header: CaptureHeader,
+    pub recording: Recording,
+    /// Records of kinds this build does not know, skipped while reading
+    pub skipped_records: usize,
+}
+
+/// Write `recording` as a current-version capture
+pub fn write_capture<W: Write>(writer: &mut W, header: &CaptureHeader, recording: &Recording) -> Result<()> {
+    let header = toml::to_string(header)
+        .map_err(|e| TranslatorError::config_error(format!("Cannot encode capture header: {}", e)))?;
+    writer.write_all(CAPTURE_MAGIC)?;
+    writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    for wheel in &recording.wheel {
+        let r = &wheel.report;
+        let mut body = Vec::with_capacity(14);
+        body.extend_from_slice(&r.steering.to_le_bytes());
+        body.extend_from_slice(&[r.throttle, r.brake, r.clutch]);
+        body.extend_from_slice(&r.buttons.to_le_bytes());
+        body.push(r.dpad.to_raw());
+        // 0 degrees and a clear flag stand for "not reported"
+        body.extend_from_slice(&r.base_range.unwrap_or(0).to_le_bytes());
+        body.extend_from_slice(&[r.rim.is_some() as u8, r.rim.map_or(0, Rim::id)]);
+        write_record(writer, RecordKind::Wheel, wheel.timestamp, &body)?;
+    }
+    for input in &recording.inputs {
+        let r = &input.report;
+        let mut body = Vec::with_capacity(12);
+        for axis in [r.steering, r.throttle, r.brake, r.clutch] {
+            body.extend_from_slice(&axis.to_le_bytes());
+        }
+        body.extend_from_slice(&r.buttons.to_le_bytes());
+        write_record(writer, RecordKind::Input, input.timestamp, &body)?;
+    }
+    for output in &recording.outputs {
+        let mut body = Vec::with_capacity(1 + output.report.data.len());
+        body.push(output.report.report_id);
+        body.extend_from_slice(&output.report.data);
+        write_record(writer, RecordKind::Output, output.timestamp, &body)?;
+    }
+    for force in &recording.forces {
+        write_record(writer, RecordKind::Force, force.timestamp, &force.magnitude.to_le_bytes())?;
+    }
+    for event in &recording.events {
+        write_record(writer, RecordKind::Event, event.timestamp, event.name.as_bytes())?;
+    }
+    for note in &recording.notes {
+        write_record(writer, RecordKind::Note, note.timestamp, note.text.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_record<W: Write>(writer: &mut W, kind: RecordKind, timestamp: Duration, body: &[u8]) -> Result<()> {
+    writer.write_all(&[kind.tag()])?;
+    writer.write_all(&((8 + body.len()) as u32).to_le_bytes())?;
+    writer.write_all(&(timestamp.as_micros() as u64).to_le_bytes())?;
+    writer.write_all(body)?;
+    Ok(())
+}
+
+/// Read a capture of any supported version
+pub fn read_capture<R: Read>(mut reader: R) -> Result<Capture> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if let Some(rest) = bytes.strip_prefix(LEGACY_CAPTURE_MAGIC) {
+        return read_legacy(rest);
+    }
+    if bytes.first() == Some(&b'{') {
+        let recording = Recording::read_jsonl(bytes.as_slice())?;
+        return Ok(Capture { version: CAPTURE_VERSION, recording, ..Default::default() });
+    }
+    let Some(rest) = bytes.strip_prefix(CAPTURE_MAGIC) else {
+        return Err(TranslatorError::invalid_report("Not a tm-g29 capture"));
+    };
+
+    let truncated = || TranslatorError::invalid_report("Truncated capture header");
+    let version = u16::from_le_bytes([*rest.first().ok_or_else(truncated)?, *rest.get(1).ok_or_else(truncated)?]);
+    if version > CAPTURE_VERSION {
+        return Err(TranslatorError::invalid_report(format!(
+            "Capture format version {} is newer than this tm-g29 supports ({})",
+            version, CAPTURE_VERSION
+        )));
+    }
+    let header_len = rest.get(2..6).ok_or_else(truncated)?;
+    let header_len = u32::from_le_bytes(header_len.try_into().unwrap_or_default()) as usize;
+    let header = rest.get(6..6 + header_len).ok_or_else(truncated)?;
+    let header = std::str::from_utf8(header)
+        .ok()
+        .and_then(|header| toml::from_str(header).ok())
+        .ok_or_else(|| TranslatorError::invalid_report("Malformed capture header"))?;
+
+    let mut capture = Capture { version, header, ..Default::default() };
+    let mut rest = &rest[6 + header_len..];
+    let mut index = 0;
+    while !rest.is_empty() {
+        let truncated = || TranslatorError::invalid_report(format!("Truncated capture after {} records", index));
+        if rest.len() < FRAME_HEADER_LEN {
+            return Err(truncated());
+        }
+        let len = u32::from_le_bytes(rest[1..5].try_into().unwrap_or_default()) as usize;
+        let payload = rest.get(5..5 + len).filter(|_| len >= 8).ok_or_else(truncated)?;
+        let timestamp = Duration::from_micros(u64::from_le_bytes(payload[..8].try_into().unwrap_or_default()));
+        let body = &payload[8..];
+
+        match RecordKind::from_tag(rest[0]) {
+            Some(kind) => read_record(&mut capture.recording, kind, timestamp, body)
+                .ok_or_else(|| TranslatorError::invalid_report(format!("Malformed {:?} record {}", kind, index)))?,
+            None => capture.skipped_records += 1,
+        }
+        rest = &rest[5 + len..];
+        index += 1;
+    }
+
+    Ok(capture)
+}
+
+fn read_record(recording: &mut Recording, kind: RecordKind, timestamp: Duration, body: &[u8]) -> Option<()> {
+    let u16_at = |offset: usize| Some(u16::from_le_bytes(body.get(offset..offset + 2)?.try_into().ok()?));
+
+    match kind {
+        RecordKind::Wheel => recording.wheel.push(RecordedWheel {
+            timestamp,
+            report: ThrustmasterInputReport {
+                steering: u16_at(0)? as i16,
+                throttle: *body.get(2)?,
+                brake: *body.get(3)?,
+                clutch: *body.get(4)?,
+                buttons: u32::from_le_bytes(body.get(5..9)?.try_into().ok()?),
+                dpad: HatDirection::from_raw(*body.get(9)?)?,
+                base_range: Some(u16_at(10)?).filter(|&degrees| degrees > 0),
+                rim: match body.get(12..14)? {
+                    [0, _] => None,
+                    [_, id] => Some(Rim::from_id(*id)),
+                    _ => None,
+                },
+            },
+        }),
+        RecordKind::Input => recording.inputs.push(RecordedInput {
+            timestamp,
+            report: G29InputReport {
+                report_id: 0x01,
+                steering: u16_at(0)?,
+                throttle: u16_at(2)?,
+                brake: u16_at(4)?,
+                clutch: u16_at(6)?,
+                buttons: u32::from_le_bytes(body.get(8..12)?.try_into().ok()?),
+                unused: [0; 4],
+            },
+        }),
+        RecordKind::Output => recording.outputs.push(RecordedOutput {
+            timestamp,
+            report: G29OutputReport { report_id: *body.first()?, data: body[1..].to_vec() },
+        }),
+        RecordKind::Force => recording.forces.push(RecordedForce { timestamp, magnitude: u16_at(0)? as i16 }),
+        RecordKind::Event => recording.events.push(RecordedEvent {
+            timestamp,
+            name: String::from_utf8(body.to_vec()).ok()?,
+        }),
+        RecordKind::Note => recording.notes.push(RecordedNote {
+            timestamp,
+            text: String::from_utf8(body.to_vec()).ok()?,
+        }),
+    }
+    Some(())
+}
+
+/// Version 1: `<micros: u64 LE> <report_id: u8> <len: u16 LE> <data>` per output report
+fn read_legacy(mut rest: &[u8]) -> Result<Capture> {
+    let mut recording = Recording::default();
+    while !rest.is_empty() {
+        let truncated = || TranslatorError::invalid_report(format!("Truncated capture after {} reports", recording.outputs.len()));
+        if rest.len() < 11 {
+            return Err(truncated());
+        }
+        let micros = u64::from_le_bytes(rest[..8].try_into().unwrap_or_default());
+        let report_id = rest[8];
+        let len = u16::from_le_bytes([rest[9], rest[10]]) as usize;
+        let data = rest.get(11..11 + len).ok_or_else(truncated)?;
+        recording.outputs.push(RecordedOutput {
+            timestamp: Duration::from_micros(micros),
+            report: G29OutputReport { report_id, data: data.to_vec() },
+        });
+        rest = &rest[11 + len..];
+    }
+
+    Ok(Capture { version: 1, recording, ..Default::default() })
+}
+
+impl Capture {
+    /// Number of records of each kind
+    pub fn record_counts(&self) -> BTreeMap<RecordKind, usize> {
+        let r = &self.recording;
+        [
+            (RecordKind::Wheel, r.wheel.len()),
+            (RecordKind::Input, r.inputs.len()),
+            (RecordKind::Output, r.outputs.len()),
+            (RecordKind::Force, r.forces.len()),
+            (RecordKind::Event, r.events.len()),
+            (RecordKind::Note, r.notes.len()),
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+impl fmt::Display for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let legacy = if self.version < CAPTURE_VERSION { ", upgrade with `tm-g29 capture upgrade`" } else { "" };
+        writeln!(f, "Format version: {}{}", self.version, legacy)?;
+        if !self.header.tool_version.is_empty() {
+            writeln!(f, "Written by: tm-g29 {}", self.header.tool_version)?;
+        }
+        if self.header.created_unix > 0 {
+            writeln!(f, "Created: {} (Unix time)", self.header.created_unix)?;
+        }
+        writeln!(f, "Duration: {:.1}s", self.recording.duration().as_secs_f64())?;
+        writeln!(f, "Records:")?;
+        for (kind, count) in self.record_counts() {
+            writeln!(f, "  {:<8} {}", format!("{:?}", kind), count)?;
+        }
+        if self.skipped_records > 0 {
+            writeln!(f, "  {} records of unknown kinds skipped", self.skipped_records)?;
+        }
+        for note in &self.recording.notes {
+            writeln!(f, "Note at {:.1}s: {}", note.timestamp.as_secs_f64(), note.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_recording() -> Recording {
+        let at = Duration::from_micros;
+        Recording {
+            wheel: vec![RecordedWheel {
+                timestamp: at(500),
+                report: ThrustmasterInputReport {
+                    steering: -1234,
+                    throttle: 200,
+                    brake: 0,
+                    clutch: 7,
+                    buttons: 0x8000_0001,
+                    dpad: HatDirection::SouthWest,
+                    base_range: Some(900),
+                    rim: Some(Rim::OpenWheel),
+                },
+            }],
+            inputs: vec![RecordedInput {
+                timestamp: at(1_000),
+                report: G29InputReport {
+                    report_id: 0x01,
+                    steering: 0x8123,
+                    throttle: 0x3FF,
+                    brake: 12,
+                    clutch: 0,
+                    buttons: 0x0001_0004,
+                    unused: [0; 4],
+                },
+            }],
+            outputs: vec![RecordedOutput {
+                timestamp: at(2_000),
+                report: G29OutputReport { report_id: 0x11, data: vec![0x01, 0x08, 0x80] },
+            }],
+            forces: vec![RecordedForce { timestamp: at(3_000), magnitude: -1200 }],
+            events: vec![RecordedEvent { timestamp: at(4_000), name: "Recenter".into() }],
+            notes: vec![RecordedNote { timestamp: at(5_000), text: "hello".into() }],
+        }
+    }
+
+    #[test]
+    fn test_capture_round_trips_every_record_kind() {
+        let recording = sample_recording();
+        let mut bytes = Vec::new();
+        write_capture(&mut bytes, &CaptureHeader::now(), &recording).unwrap();
+
+        let capture = read_capture(bytes.as_slice()).unwrap();
+        assert_eq!(capture.version, CAPTURE_VERSION);
+        assert_eq!(capture.header.tool_version, env!("CARGO_PKG_VERSION"));
+        let wheel = &capture.recording.wheel[0].report;
+        assert_eq!((wheel.steering, wheel.clutch, wheel.buttons), (-1234, 7, 0x8000_0001));
+        assert_eq!((wheel.dpad, wheel.base_range, wheel.rim), (HatDirection::SouthWest, Some(900), Some(Rim::OpenWheel)));
+        assert_eq!(capture.recording.inputs[0].report.buttons, 0x0001_0004);
+        assert_eq!(capture.recording.outputs[0].report.data, vec![0x01, 0x08, 0x80]);
+        assert_eq!(capture.recording.forces[0].magnitude, -1200);
+        assert_eq!(capture.recording.events[0].name, "Recenter");
+        assert_eq!(capture.recording.notes[0].text, "hello");
+        assert_eq!(capture.recording.duration(), Duration::from_micros(5_000));
+
+        let mut jsonl = Vec::new();
+        capture.recording.write_jsonl(&mut jsonl).unwrap();
+        let from_jsonl = read_capture(jsonl.as_slice()).unwrap();
+        assert_eq!(from_jsonl.record_counts(), capture.record_counts());
+        assert_eq!(from_jsonl.recording.wheel[0].report.rim, Some(Rim::OpenWheel));
+    }
+
+    #[test]
+    fn test_unknown_records_are_skipped_and_legacy_files_load() {
+        let mut bytes = Vec::new();
+        write_capture(&mut bytes, &CaptureHeader::default(), &Recording::default()).unwrap();
+        bytes.extend_from_slice(&[0x7F, 10, 0, 0, 0]);
+        bytes.extend_from_slice(&[0; 10]);
+        let capture = read_capture(bytes.as_slice()).unwrap();
+        assert_eq!(capture.skipped_records, 1);
+
+        let mut legacy = LEGACY_CAPTURE_MAGIC.to_vec();
+        legacy.extend_from_slice(&500u64.to_le_bytes());
+        legacy.extend_from_slice(&[0x11, 2, 0, 0xAA, 0xBB]);
+        let capture = read_capture(legacy.as_slice()).unwrap();
+        assert_eq!(capture.version, 1);
+        assert_eq!(capture.recording.outputs[0].report.data, vec![0xAA, 0xBB]);
+    }
+}
@@ -0,0 +1,63 @@
+//! Time source for timing-dependent logic
+//!
+//! Code that schedules by wall time (effect durations, software-rendered
+//! effects, update rates) asks a `Clock` for the current instant instead of
+//! calling `Instant::now()`, so tests can drive time by hand with a
+//! `MockClock` and check timing deterministically.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to, clones share the same time
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    base: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Move time forward by `step`
+    pub fn advance(&self, step: Duration) {
+        if let Ok(mut elapsed) = self.elapsed.lock() {
+            *elapsed += step;
+        }
+    }
+
+    /// Time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.lock().map(|elapsed| *elapsed).unwrap_or_default()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+}
@@ -0,0 +1,219 @@
+//! Button chords and long presses
+//!
+//! Computed by `InputTranslator` on the source buttons, before
+//! `button_mapping`, so wheels with few buttons can reach more G29 ones:
+//! - a chord presses its G29 button while all of its source buttons are
+//!   held. They must go down within `chord_window_ms` of each other; until
+//!   then a chord button is held back from the mapping, so the game never
+//!   sees the single buttons flicker before the chord. When the window runs
+//!   out without the chord the button goes through as usual, that much late.
+//! - a long press gives a source button a second G29 button. The button is
+//!   held back while pressed: released before `hold_ms` it is sent as a
+//!   short tap through its usual mapping, held past `hold_ms` it presses the
+//!   long press button instead until released.
+//!
+//! Buttons used up by a chord or long press stay out of the mapping until
+//! they are released.
+
+use crate::config::ButtonCombos;
+use crate::device::SOURCE_BUTTON_COUNT;
+use crate::evdev::G29_BUTTON_COUNT;
+use std::time::{Duration, Instant};
+
+/// How long the short press of a long press button is sent for
+const TAP_DURATION: Duration = Duration::from_millis(60);
+
+#[derive(Debug, Clone)]
+struct Chord {
+    mask: u32,
+    g29_button: u8,
+    active: bool,
+}
+
+#[derive(Debug, Clone)]
+struct LongPress {
+    button: u8,
+    hold: Duration,
+    g29_button: u8,
+    active: bool,
+}
+
+/// Running state of the chords and long presses
+#[derive(Debug, Clone)]
+pub struct ComboState {
+    window: Duration,
+    chords: Vec<Chord>,
+    long_presses: Vec<LongPress>,
+    /// Source buttons taking part in any chord or long press
+    combo_buttons: u32,
+    /// When each held back button went down
+    pending: [Option<Instant>; SOURCE_BUTTON_COUNT as usize],
+    /// Buttons used up by a chord or long press, until released
+    consumed: u32,
+    /// Short presses replayed to the mapping, until when
+    taps: [Option<Instant>; SOURCE_BUTTON_COUNT as usize],
+    last_buttons: u32,
+}
+
+impl ComboState {
+    /// State for `config`, skipping entries validation reports as out of range
+    pub fn new(config: &ButtonCombos) -> Self {
+        let in_range = |button: u8| button < SOURCE_BUTTON_COUNT;
+        let chords: Vec<Chord> = config
+            .chords
+            .iter()
+            .filter(|chord| {
+                chord.buttons.len() >= 2 && chord.buttons.iter().all(|&b| in_range(b)) && chord.g29_button < G29_BUTTON_COUNT
+            })
+            .map(|chord| Chord {
+                mask: chord.buttons.iter().fold(0, |mask, &b| mask | 1 << b),
+                g29_button: chord.g29_button,
+                active: false,
+            })
+            .collect();
+        let long_presses: Vec<LongPress> = config
+            .long_presses
+            .iter()
+            .filter(|long| in_range(long.button) && long.g29_button < G29_BUTTON_COUNT)
+            .map(|long| LongPress {
+                button: long.button,
+                hold: Duration::from_millis(long.hold_ms as u64),
+                g29_button: long.g29_button,
+                active: false,
+            })
+            .collect();
+        let combo_buttons = chords.iter().fold(0, |mask, chord| mask | chord.mask)
+            | long_presses.iter().fold(0, |mask, long| mask | 1 << long.button);
+
+        Self {
+            window: Duration::from_millis(config.chord_window_ms as u64),
+            chords,
+            long_presses,
+            combo_buttons,
+            pending: [None; SOURCE_BUTTON_COUNT as usize],
+            consumed: 0,
+            taps: [None; SOURCE_BUTTON_COUNT as usize],
+            last_buttons: 0,
+        }
+    }
+
+    /// Source buttons left for the mapping and G29 buttons pressed by combos at `now`
+    pub fn apply(&mut self, buttons: u32, now: Instant) -> (u32, u32) {
+        if self.combo_buttons == 0 {
+            return (buttons, 0);
+        }
+
+        let pressed = buttons & !self.last_buttons & self.combo_buttons;
+        self.last_buttons = buttons;
+        for (index, since) in self.pending.iter_mut().enumerate() {
+            if pressed & 1 << index != 0 {
+                *since = Some(now);
+            }
+        }
+
+        // A chord fires when its last button goes down while the others are still held back
+        let mut pending = self.pending_mask();
+        for chord in &mut self.chords {
+            if chord.active {
+                chord.active = chord.mask & !buttons == 0;
+            } else if chord.mask & !buttons == 0 && chord.mask & !pending == 0 {
+                chord.active = true;
+                self.consumed |= chord.mask;
+                pending &= !chord.mask;
+            }
+        }
+        for long in &mut self.long_presses {
+            let index = long.button as usize;
+            if long.active {
+                long.active = buttons & 1 << index != 0;
+            } else if let Some(since) = self.pending[index].filter(|_| pending & 1 << index != 0) {
+                if now.duration_since(since) >= long.hold {
+                    long.active = true;
+                    self.consumed |= 1 << index;
+                    pending &= !(1 << index);
+                }
+            }
+        }
+
+        let long_buttons = self.long_presses.iter().fold(0u32, |mask, long| mask | 1 << long.button);
+        for (index, (slot, tap)) in self.pending.iter_mut().zip(&mut self.taps).enumerate() {
+            let bit = 1u32 << index;
+            let Some(since) = *slot else {
+                continue;
+            };
+            if pending & bit == 0 {
+                // Used up by a combo above
+                *slot = None;
+            } else if buttons & bit == 0 {
+                // Released before any combo, replay it as a short press
+                *slot = None;
+                *tap = Some(now + TAP_DURATION);
+            } else if long_buttons & bit == 0 && now.duration_since(since) >= self.window {
+                // No chord after all, from now on an ordinary button
+                *slot = None;
+            }
+        }
+        self.consumed &= buttons;
+
+        let mut taps = 0u32;
+        for (index, tap) in self.taps.iter_mut().enumerate() {
+            match *tap {
+                Some(until) if now < until => taps |= 1 << index,
+                Some(_) => *tap = None,
+                None => {}
+            }
+        }
+
+        let source = buttons & !self.pending_mask() & !self.consumed | taps;
+        let g29 = self.chords.iter().filter(|chord| chord.active).fold(0, |mask, chord| mask | 1 << chord.g29_button)
+            | self.long_presses.iter().filter(|long| long.active).fold(0, |mask, long| mask | 1 << long.g29_button);
+        (source, g29)
+    }
+
+    fn pending_mask(&self) -> u32 {
+        self.pending
+            .iter()
+            .enumerate()
+            .filter(|(_, since)| since.is_some())
+            .fold(0, |mask, (index, _)| mask | 1 << index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChordMapping, LongPressMapping};
+
+    #[test]
+    fn test_chord_and_long_press() {
+        let config = ButtonCombos {
+            chord_window_ms: 50,
+            chords: vec![ChordMapping { buttons: vec![6, 7], g29_button: 23 }],
+            long_presses: vec![LongPressMapping { button: 3, hold_ms: 500, g29_button: 22 }],
+        };
+        let mut combos = ComboState::new(&config);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // Both chord buttons within the window: only the chord's button
+        assert_eq!(combos.apply(1 << 6, at(0)), (0, 0));
+        assert_eq!(combos.apply(1 << 6 | 1 << 7, at(20)), (0, 1 << 23));
+        assert_eq!(combos.apply(1 << 7, at(40)), (0, 0));
+        assert_eq!(combos.apply(0, at(60)), (0, 0));
+
+        // A single chord button goes through once the window has passed
+        assert_eq!(combos.apply(1 << 6 | 1, at(100)), (1, 0));
+        assert_eq!(combos.apply(1 << 6, at(160)), (1 << 6, 0));
+        assert_eq!(combos.apply(0, at(200)), (0, 0));
+
+        // Short press: a tap of the usual button on release
+        assert_eq!(combos.apply(1 << 3, at(300)), (0, 0));
+        assert_eq!(combos.apply(0, at(400)), (1 << 3, 0));
+        assert_eq!(combos.apply(0, at(500)), (0, 0));
+
+        // Long press: the alternative button until released
+        assert_eq!(combos.apply(1 << 3, at(600)), (0, 0));
+        assert_eq!(combos.apply(1 << 3, at(1100)), (0, 1 << 22));
+        assert_eq!(combos.apply(0, at(1200)), (0, 0));
+    }
+}
@@ -1,7 +1,16 @@
 //! Configuration structures for the protocol translator
 
+use crate::control::ControlAction;
+use crate::device::handbrake::HandbrakeMode;
+use crate::device::models::ThrustmasterModel;
+use crate::ffb::EffectKind;
+use crate::inventory::{self, Inventory};
+use crate::led::BlinkPattern;
+use crate::profile::{self, ResolvedProfile};
+use crate::units::ForceNewton;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -11,6 +20,47 @@ pub struct Config {
     pub output_config: OutputConfig,
     pub ffb_config: FfbConfig,
     pub logging_config: LoggingConfig,
+    #[serde(default)]
+    pub telemetry_config: TelemetryConfig,
+    #[serde(default)]
+    pub hotkey_config: HotkeyConfig,
+    #[serde(default)]
+    pub pedal_rumble_config: PedalRumbleConfig,
+    #[serde(default)]
+    pub handbrake_config: HandbrakeConfig,
+    #[serde(default)]
+    pub host_config: HostConfig,
+    #[serde(default)]
+    pub pedal_sets: Vec<PedalSetConfig>,
+    #[serde(default)]
+    pub codriver_config: CoDriverConfig,
+    #[serde(default)]
+    pub profile_config: ProfileConfig,
+    #[serde(default)]
+    pub metrics_config: MetricsConfig,
+    #[serde(default)]
+    pub ab_test_config: AbTestConfig,
+    #[serde(default)]
+    pub keyboard_config: KeyboardConfig,
+    #[serde(default)]
+    pub shifter_config: ShifterConfig,
+    #[serde(default)]
+    pub backup_config: BackupConfig,
+    #[serde(default)]
+    pub display_config: DisplayConfig,
+    #[serde(default)]
+    pub remote_config: RemoteConfig,
+    #[serde(default)]
+    pub jitter_config: JitterConfig,
+    /// Subsystems still in development, off unless listed
+    #[serde(default)]
+    pub experimental: ExperimentalConfig,
+    /// Per-game overrides kept in this file, `[profiles.<name>]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::value::Table>,
+    /// File this configuration was loaded from, locates the profile directory
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -22,6 +72,25 @@ impl Default for Config {
             output_config: OutputConfig::default(),
             ffb_config: FfbConfig::default(),
             logging_config: LoggingConfig::default(),
+            telemetry_config: TelemetryConfig::default(),
+            hotkey_config: HotkeyConfig::default(),
+            pedal_rumble_config: PedalRumbleConfig::default(),
+            handbrake_config: HandbrakeConfig::default(),
+            host_config: HostConfig::default(),
+            pedal_sets: Vec::new(),
+            codriver_config: CoDriverConfig::default(),
+            profile_config: ProfileConfig::default(),
+            metrics_config: MetricsConfig::default(),
+            ab_test_config: AbTestConfig::default(),
+            keyboard_config: KeyboardConfig::default(),
+            shifter_config: ShifterConfig::default(),
+            backup_config: BackupConfig::default(),
+            display_config: DisplayConfig::default(),
+            remote_config: RemoteConfig::default(),
+            jitter_config: JitterConfig::default(),
+            experimental: ExperimentalConfig::default(),
+            profiles: BTreeMap::new(),
+            source: None,
         }
     }
 }
@@ -31,7 +100,44 @@ pub struct ThrustmasterConfig {
     pub vid: u16,
     pub pid: u16,
     pub serial_number: Option<String>,
+    #[serde(default)]
+    pub device: Option<String>,  // Inventory nickname, sets vid, pid and serial_number
     pub exclusive_access: bool,
+    #[serde(default)]
+    pub read_strategy: ReadStrategy,
+    #[serde(default)]
+    pub input_wakeup: InputWakeup,
+    #[serde(default)]
+    pub model: Option<ThrustmasterModel>,  // Report layout, detected from the PID when unset
+}
+
+/// How input reports are read from the source wheel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadStrategy {
+    Auto,               // Benchmark the available paths at startup
+    HidapiNonBlocking,  // hidapi polled without waiting
+    HidapiBlocking,     // hidapi with a 1 ms read timeout
+    Hidraw,             // Linux: /dev/hidrawN read directly
+    Evdev,              // Linux: /dev/input/eventN of the wheel
+}
+
+impl Default for ReadStrategy {
+    fn default() -> Self {
+        ReadStrategy::Auto
+    }
+}
+
+/// What wakes the input loop for the next wheel report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputWakeup {
+    Event,  // A reader thread blocks on the wheel and hands each report over as it arrives
+    Poll,   // The loop polls the wheel on a fixed interval
+}
+
+impl Default for InputWakeup {
+    fn default() -> Self {
+        InputWakeup::Event
+    }
 }
 
 impl Default for ThrustmasterConfig {
@@ -40,7 +146,11 @@ impl Default for ThrustmasterConfig {
             vid: 0x044F,  // Guillemot/Thrustmaster VID
             pid: 0x0004,  // Common Thrustmaster wheel PID
             serial_number: None,
+            device: None,
             exclusive_access: true,
+            read_strategy: ReadStrategy::Auto,
+            input_wakeup: InputWakeup::Event,
+            model: None,
         }
     }
 }
@@ -53,17 +163,159 @@ pub struct G29Config {
     pub manufacturer_string: String,
     pub serial_number: String,
     pub use_custom_vid_pid: bool,
+    #[serde(default)]
+    pub mode: G29Mode,
+    #[serde(default)]
+    pub keep_alive_ms: u32,  // Re-send the last report after this long without input, 0 = off
+    #[serde(default)]
+    pub serial_policy: SerialPolicy,
+    #[serde(default)]
+    pub packing: G29Packing,  // Byte layout the virtual bus driver expects, RawHid for the platform HID stacks
+    #[serde(default)]
+    pub target: TargetWheel,  // Wheel to emulate, for games that only know an older or Xbox Logitech wheel
+}
+
+/// Where the virtual G29's serial number comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SerialPolicy {
+    Fixed,             // Always `serial_number`
+    FromWheel,         // Derived from the physical wheel's serial, stable per wheel
+    RandomPerBoot,     // New random serial after each reboot
+    RandomPersistent,  // Random once, then kept until reset
+}
+
+impl Default for SerialPolicy {
+    fn default() -> Self {
+        SerialPolicy::Fixed
+    }
+}
+
+/// Console compatibility mode of the emulated G29, selects descriptor and report layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum G29Mode {
+    Ps3,  // PID 0xC24F
+    Ps4,  // PID 0xC260, DualShock 4 style report with touchpad block
+}
+
+impl Default for G29Mode {
+    fn default() -> Self {
+        G29Mode::Ps3
+    }
+}
+
+/// Logitech USB vendor ID shared by every emulated wheel
+pub const LOGITECH_VID: u16 = 0x046D;
+
+/// Logitech wheel the virtual device presents itself as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetWheel {
+    G27,
+    G29,   // Layout chosen by `mode`
+    G920,  // Xbox One / PC wheel
+    Dfgt,  // Driving Force GT
+}
+
+impl Default for TargetWheel {
+    fn default() -> Self {
+        TargetWheel::G29
+    }
+}
+
+impl TargetWheel {
+    pub const ALL: [TargetWheel; 4] = [TargetWheel::G27, TargetWheel::G29, TargetWheel::G920, TargetWheel::Dfgt];
+
+    /// USB product ID of the real wheel, `mode` only matters for the G29
+    pub fn product_id(self, mode: G29Mode) -> u16 {
+        match self {
+            TargetWheel::G27 => 0xC29B,
+            TargetWheel::G29 => mode.product_id(),
+            TargetWheel::G920 => 0xC262,
+            TargetWheel::Dfgt => 0xC29A,
+        }
+    }
+
+    /// Product string of the real wheel
+    pub fn product_string(self) -> &'static str {
+        match self {
+            TargetWheel::G27 => "G27 Racing Wheel",
+            TargetWheel::G29 => "G29 Driving Force Racing Wheel",
+            TargetWheel::G920 => "Logitech G920 Driving Force Racing Wheel USB",
+            TargetWheel::Dfgt => "Driving Force GT",
+        }
+    }
+
+    /// Buttons the wheel reports, G29 buttons past these are dropped
+    pub fn button_count(self) -> u8 {
+        match self {
+            TargetWheel::G27 => 22,
+            TargetWheel::G29 => 24,
+            TargetWheel::G920 => 11,
+            TargetWheel::Dfgt => 21,
+        }
+    }
+}
+
+/// Byte layout of the reports handed to the virtual bus driver, see `device::packing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum G29Packing {
+    RawHid,      // Exactly as the descriptor lays it out, report ID first
+    NoReportId,  // Report body only, for drivers taking the report ID separately
+    BigEndian,   // Multi-byte axes most significant byte first
+}
+
+impl Default for G29Packing {
+    fn default() -> Self {
+        G29Packing::RawHid
+    }
+}
+
+impl G29Mode {
+    /// USB product ID a real G29 reports in this mode
+    pub fn product_id(self) -> u16 {
+        match self {
+            G29Mode::Ps3 => 0xC24F,
+            G29Mode::Ps4 => 0xC260,
+        }
+    }
+}
+
+impl G29Config {
+    /// USB vendor and product ID to present
+    ///
+    /// A G29 uses `vid` and `pid` as configured; other targets use their
+    /// real IDs unless `use_custom_vid_pid` is set.
+    pub fn usb_ids(&self) -> (u16, u16) {
+        if self.target == TargetWheel::G29 || self.use_custom_vid_pid {
+            (self.vid, self.pid)
+        } else {
+            (LOGITECH_VID, self.target.product_id(self.mode))
+        }
+    }
+
+    /// Product string to present, chosen like `usb_ids`
+    pub fn product_name(&self) -> &str {
+        if self.target == TargetWheel::G29 || self.use_custom_vid_pid {
+            &self.product_string
+        } else {
+            self.target.product_string()
+        }
+    }
 }
 
 impl Default for G29Config {
     fn default() -> Self {
         Self {
-            vid: 0x046D,  // Logitech VID
+            vid: LOGITECH_VID,
             pid: 0xC24F,  // G29 PID
             product_string: "G29 Driving Force Racing Wheel".to_string(),
             manufacturer_string: "Logitech".to_string(),
             serial_number: "TM2G29001".to_string(),
             use_custom_vid_pid: false,
+            mode: G29Mode::Ps3,
+            keep_alive_ms: 0,
+            serial_policy: SerialPolicy::Fixed,
+            packing: G29Packing::RawHid,
+            target: TargetWheel::G29,
         }
     }
 }
@@ -71,10 +323,46 @@ impl Default for G29Config {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InputConfig {
     pub steering_range: u16,           // Degrees of rotation (270, 540, 900, etc.)
-    pub steering_deadzone: f32,        // 0.0 - 1.0
+    #[serde(default)]
+    pub g29_range: Option<u16>,        // Degrees the game's G29 turns, e.g. 900 under a 1080° wheel; unset = steering_range
     pub pedal_curves: PedalCurves,
     pub button_mapping: HashMap<u8, u8>, // Thrustmaster button -> G29 button
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rim_button_mappings: BTreeMap<String, HashMap<u8, u8>>, // Rim key -> mapping used while it is attached
     pub axis_scaling: AxisScaling,
+    #[serde(default)]
+    pub steering_geometry: SteeringGeometry,
+    #[serde(default)]
+    pub button_timing: ButtonTiming,
+    #[serde(default)]
+    pub menu_nudge: MenuNudge,
+    #[serde(default)]
+    pub assists: Assists,
+    #[serde(default)]
+    pub combos: ButtonCombos,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub button_axes: Vec<ButtonAxisMapping>,   // Source buttons driving G29 axes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub axis_buttons: Vec<AxisButtonMapping>,  // G29 axes pressing G29 buttons
+    #[serde(default)]
+    pub calibration: CalibrationData,
+    #[serde(default)]
+    pub axis_tuning: AxisTuning,
+}
+
+impl InputConfig {
+    /// Degrees lock to lock the translated steering axis spans
+    ///
+    /// Below `steering_range` the wheel keeps its angle on screen and reaches
+    /// the G29's lock before its own.
+    pub fn output_range(&self) -> u16 {
+        self.g29_range.unwrap_or(self.steering_range)
+    }
+
+    /// Steering geometry checked as `tm-g29 ctl geometry` checks it
+    pub fn check_steering(&self) -> Result<(), String> {
+        self.steering_geometry.check(self.axis_tuning.steering.center_offset)
+    }
 }
 
 impl Default for InputConfig {
@@ -87,10 +375,20 @@ impl Default for InputConfig {
         
         Self {
             steering_range: 900,
-            steering_deadzone: 0.02,
+            g29_range: None,
             pedal_curves: PedalCurves::default(),
             button_mapping,
+            rim_button_mappings: BTreeMap::new(),
             axis_scaling: AxisScaling::default(),
+            steering_geometry: SteeringGeometry::default(),
+            button_timing: ButtonTiming::default(),
+            menu_nudge: MenuNudge::default(),
+            assists: Assists::default(),
+            combos: ButtonCombos::default(),
+            button_axes: Vec::new(),
+            axis_buttons: Vec::new(),
+            calibration: CalibrationData::default(),
+            axis_tuning: AxisTuning::default(),
         }
     }
 }
@@ -118,6 +416,7 @@ pub enum CurveType {
     Squared,
     Cubed,
     Custom(Vec<f32>), // Lookup table
+    Spline(Vec<[f32; 2]>), // Control points [input, output] a smooth curve passes through, see `curve`
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,10 +438,203 @@ impl Default for AxisScaling {
     }
 }
 
+/// Timing applied to virtual G29 button transitions, for games that miss short presses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ButtonTiming {
+    pub min_press_ms: u16,      // Shortest press sent to the game
+    pub release_delay_ms: u16,  // Extra hold after the physical release
+}
+
+/// D-pad menu navigation for games that ignore the wheel's D-pad in setup menus
+///
+/// While toggled on, D-pad left/right nudge the steering axis and up/down tap
+/// the throttle and brake, which those menus do react to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MenuNudge {
+    pub toggle_button: Option<u8>,  // Source button toggling the mode, None = off
+    pub steering_step: f32,         // Steering deflection per D-pad side, 0.0 - 1.0
+    pub pedal_tap: f32,             // Throttle/brake depth for D-pad up/down, 0.0 - 1.0
+}
+
+impl Default for MenuNudge {
+    fn default() -> Self {
+        Self {
+            toggle_button: None,
+            steering_step: 0.15,
+            pedal_tap: 0.5,
+        }
+    }
+}
+
+/// Driving assists for players with limited mobility, all off by default
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Assists {
+    pub max_steering_rate: f32,              // Degrees per second the virtual wheel turns at most, 0 = unlimited
+    pub throttle_hold_button: Option<u8>,    // Source button latching the current throttle until pressed again or braking
+    pub one_pedal: bool,                     // Releasing the throttle brakes
+    pub one_pedal_coast: f32,                // Throttle travel that neither accelerates nor brakes, 0.0 - 1.0
+    pub one_pedal_brake: f32,                // Brake with the throttle fully released, 0.0 - 1.0
+}
+
+impl Default for Assists {
+    fn default() -> Self {
+        Self {
+            max_steering_rate: 0.0,
+            throttle_hold_button: None,
+            one_pedal: false,
+            one_pedal_coast: 0.15,
+            one_pedal_brake: 0.4,
+        }
+    }
+}
+
+/// Button chords and long presses, see `combos`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ButtonCombos {
+    pub chord_window_ms: u16,               // Chord buttons pressed this close together count as one press
+    pub chords: Vec<ChordMapping>,
+    pub long_presses: Vec<LongPressMapping>,
+}
+
+impl Default for ButtonCombos {
+    fn default() -> Self {
+        Self {
+            chord_window_ms: 50,
+            chords: Vec::new(),
+            long_presses: Vec::new(),
+        }
+    }
+}
+
+/// Source buttons held together pressing one G29 button
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChordMapping {
+    pub buttons: Vec<u8>,   // Source buttons, at least two
+    pub g29_button: u8,     // G29 button pressed while they are held
+}
+
+/// G29 button pressed instead of the usual mapping while a source button is held long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LongPressMapping {
+    pub button: u8,         // Source button
+    pub hold_ms: u16,       // Hold time before the long press output
+    pub g29_button: u8,     // G29 button pressed after `hold_ms`
+}
+
+/// Axis of the virtual G29
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum G29Axis {
+    Steering,
+    Throttle,
+    Brake,
+    Clutch,
+}
+
+/// Source button moving a G29 axis while held, see `axis_mapping`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ButtonAxisMapping {
+    pub button: u8,         // Source button
+    pub axis: G29Axis,
+    pub value: f32,         // Pedal depth 0.0 - 1.0, steering -1.0 (left) - 1.0 (right)
+}
+
+/// G29 axis pressing a G29 button past a threshold, see `axis_mapping`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AxisButtonMapping {
+    pub axis: G29Axis,
+    pub threshold: f32,     // Pedal depth 0.0 - 1.0, steering -1.0 - 1.0 (negative = past it to the left)
+    pub g29_button: u8,
+}
+
+/// Raw travel of the wheel's own axes, recorded by `calibrate`
+///
+/// Applied to every report before deadzone, geometry and curves; the
+/// defaults pass the raw values through.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalibrationData {
+    pub steering_min: i16,      // Raw steering at full left lock
+    pub steering_center: i16,   // Raw steering with the wheel straight
+    pub steering_max: i16,      // Raw steering at full right lock
+    pub throttle: AxisCalibration,
+    pub brake: AxisCalibration,
+    pub clutch: AxisCalibration,
+    pub invert_steering: bool,
+    pub invert_throttle: bool,
+    pub invert_brake: bool,
+    pub invert_clutch: bool,
+}
+
+impl Default for CalibrationData {
+    fn default() -> Self {
+        Self {
+            steering_min: i16::MIN,
+            steering_center: 0,
+            steering_max: i16::MAX,
+            throttle: AxisCalibration::default(),
+            brake: AxisCalibration::default(),
+            clutch: AxisCalibration::default(),
+            invert_steering: false,
+            invert_throttle: false,
+            invert_brake: false,
+            invert_clutch: false,
+        }
+    }
+}
+
+/// Compensation for wheels whose lock-to-lock travel differs between the sides
+///
+/// The shifted center itself is `axis_tuning.steering.center_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SteeringGeometry {
+    pub left_scale: f32,     // Gain applied left of center
+    pub right_scale: f32,    // Gain applied right of center
+}
+
+impl SteeringGeometry {
+    /// Reject an offset outside -1.0 - 1.0 and scales that are not finite and above 0
+    pub fn check(&self, center_offset: f32) -> Result<(), String> {
+        if !(-1.0..=1.0).contains(&center_offset) {
+            return Err(format!("Steering center_offset {} is outside -1.0 - 1.0", center_offset));
+        }
+        for (side, scale) in [("left_scale", self.left_scale), ("right_scale", self.right_scale)] {
+            if !(scale > 0.0 && scale.is_finite()) {
+                return Err(format!("Steering {} {} must be a finite number above 0", side, scale));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for SteeringGeometry {
+    fn default() -> Self {
+        Self {
+            left_scale: 1.0,
+            right_scale: 1.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputConfig {
     pub led_support: bool,
     pub led_brightness: f32,  // 0.0 - 1.0
+    #[serde(default)]
+    pub led_night_mode: LedNightMode,
+    #[serde(default)]
+    pub led_patterns: LedPatternConfig,
+    #[serde(default = "FfbQuirks::shipped")]
+    pub ffb_quirks: Vec<FfbQuirks>,
+    #[serde(default = "OutputConfig::default_game_range")]
+    pub game_range: bool,  // Follow rotation ranges the game sets through the G29, e.g. per car
+}
+
+impl OutputConfig {
+    pub fn default_game_range() -> bool {
+        true
+    }
 }
 
 impl Default for OutputConfig {
@@ -150,6 +642,111 @@ impl Default for OutputConfig {
         Self {
             led_support: true,
             led_brightness: 1.0,
+            led_night_mode: LedNightMode::default(),
+            led_patterns: LedPatternConfig::default(),
+            ffb_quirks: FfbQuirks::shipped(),
+            game_range: OutputConfig::default_game_range(),
+        }
+    }
+}
+
+/// Workarounds for a game whose FFB output matches `signature`
+///
+/// The first entry matching the fingerprint taken at the start of a session
+/// applies, see `fingerprint::FfbFingerprinter`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FfbQuirks {
+    pub name: String,
+    #[serde(default)]
+    pub signature: FfbSignature,
+    #[serde(default)]
+    pub drop_duplicates: bool,    // Drop reports identical to the previous one, e.g. repeated stops
+    #[serde(default)]
+    pub zero_based_slots: bool,   // Effect blocks counted from 0, shifted up by one
+}
+
+impl FfbQuirks {
+    /// Quirks table used when the configuration has none
+    pub fn shipped() -> Vec<Self> {
+        vec![
+            Self {
+                name: "zero-based effect slots".to_string(),
+                signature: FfbSignature { uses_slot_zero: Some(true), ..FfbSignature::default() },
+                drop_duplicates: false,
+                zero_based_slots: true,
+            },
+            Self {
+                name: "repeated stop commands".to_string(),
+                signature: FfbSignature { min_duplicate_ratio: Some(0.3), ..FfbSignature::default() },
+                drop_duplicates: true,
+                zero_based_slots: false,
+            },
+        ]
+    }
+}
+
+/// FFB output pattern, unset fields match anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FfbSignature {
+    pub min_duplicate_ratio: Option<f32>,  // Share of reports repeating the previous one, 0.0 - 1.0
+    pub uses_slot_zero: Option<bool>,      // Whether effect block 0 is written
+    pub max_slots: Option<u8>,             // Most distinct effect blocks in use
+    pub effect_types: Vec<u8>,             // Effect type bytes that must all appear
+}
+
+/// Dimmer rev LEDs during the night hours
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LedNightMode {
+    pub enabled: bool,
+    pub start_hour: u8,           // Local hour the night brightness starts, 0 - 23
+    pub end_hour: u8,             // Local hour it ends, may be past midnight
+    pub brightness: f32,          // Brightness at night, 0.0 - 1.0
+    pub utc_offset_minutes: i32,  // Local time minus UTC, e.g. 60 for CET
+}
+
+impl Default for LedNightMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_hour: 22,
+            end_hour: 7,
+            brightness: 0.2,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedSource {
+    Game,       // Show the LEDs the game sends to the G29
+    Telemetry,  // Build the rev bar from game telemetry
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedPatternConfig {
+    pub source: LedSource,
+    pub rev_thresholds: [f32; 5],     // RPM fraction lighting each LED, left to right
+    pub limiter_threshold: f32,       // RPM fraction treated as the limiter
+    pub limiter_pattern: BlinkPattern,
+    pub gear_display_ms: u32,         // Show the new gear after a shift, 0 = off
+    pub flag_patterns: bool,          // Override the rev bar while a flag is out
+    pub blue_flag_pattern: BlinkPattern,
+    pub yellow_flag_pattern: BlinkPattern,
+}
+
+impl Default for LedPatternConfig {
+    fn default() -> Self {
+        Self {
+            source: LedSource::Game,
+            rev_thresholds: [0.75, 0.80, 0.85, 0.90, 0.94],
+            limiter_threshold: 0.97,
+            limiter_pattern: BlinkPattern { on_mask: 0b11111, off_mask: 0b00000, period_ms: 150 },
+            gear_display_ms: 0,
+            flag_patterns: true,
+            blue_flag_pattern: BlinkPattern { on_mask: 0b10001, off_mask: 0b01010, period_ms: 500 },
+            yellow_flag_pattern: BlinkPattern { on_mask: 0b11111, off_mask: 0b00000, period_ms: 1000 },
         }
     }
 }
@@ -165,8 +762,128 @@ pub struct FfbConfig {
     pub periodic_gain: f32,   // 0.0 - 1.0
     pub ramp_gain: f32,       // 0.0 - 1.0
     pub autocenter_gain: f32, // 0.0 - 1.0
-    pub max_force: f32,       // Maximum force in Newtons
+    pub max_force: ForceNewton,
     pub update_rate_hz: u32,  // FFB update frequency
+    #[serde(default)]
+    pub panic_button: Option<u8>,  // Source button that zeroes all forces until pressed again
+    #[serde(default)]
+    pub effects: EffectSwitches,
+    #[serde(default)]
+    pub thermal_limiter: ThermalLimiterConfig,
+    #[serde(default = "FfbConfig::default_soft_start_ms")]
+    pub soft_start_ms: u32,   // Ramp forces up from zero after connecting or resuming, 0 = off
+    #[serde(default)]
+    pub render_mode: FfbRenderMode,
+    #[serde(default = "FfbConfig::default_autocenter_decay_ms")]
+    pub autocenter_decay_ms: u32,  // Autocenter fade out when game effects start and back in when they stop, 0 = instant
+    #[serde(default)]
+    pub soft_lock: SoftLockConfig,
+}
+
+/// How effects reach the wheel base
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FfbRenderMode {
+    Native,  // Effects the base supports are sent as they are
+    Render,  // Every effect synthesized into one constant force stream, for constant-force-only bases
+}
+
+impl Default for FfbRenderMode {
+    fn default() -> Self {
+        FfbRenderMode::Native
+    }
+}
+
+impl FfbConfig {
+    pub fn default_soft_start_ms() -> u32 {
+        2000
+    }
+
+    pub fn default_autocenter_decay_ms() -> u32 {
+        500
+    }
+}
+
+/// Effect families passed to the wheel, disabled ones are dropped entirely
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectSwitches {
+    pub constant: bool,
+    pub periodic: bool,
+    pub ramp: bool,
+    pub spring: bool,
+    pub damper: bool,
+    pub friction: bool,
+    pub inertia: bool,
+}
+
+impl EffectSwitches {
+    pub fn is_enabled(&self, kind: EffectKind) -> bool {
+        match kind {
+            EffectKind::Constant => self.constant,
+            EffectKind::Periodic => self.periodic,
+            EffectKind::Ramp => self.ramp,
+            EffectKind::Spring => self.spring,
+            EffectKind::Damper => self.damper,
+            EffectKind::Friction => self.friction,
+            EffectKind::Inertia => self.inertia,
+        }
+    }
+}
+
+/// Gain reduction under sustained load, to keep small wheel motors from overheating
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalLimiterConfig {
+    pub enabled: bool,
+    pub window_secs: f32,     // Time constant of the load average, roughly how long the motor takes to heat up
+    pub duty_threshold: f32,  // Averaged squared load (1.0 = full force held continuously) where the gain starts to drop
+    pub min_gain: f32,        // Gain at full load, the limiter never goes below it
+}
+
+impl Default for ThermalLimiterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: 120.0,
+            duty_threshold: 0.5,
+            min_gain: 0.5,
+        }
+    }
+}
+
+/// Force wall at the G29's lock when it turns less than the wheel, see `InputConfig::g29_range`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoftLockConfig {
+    pub enabled: bool,
+    pub strength: f32,      // Push back once fully into the wall, 0.0 - 1.0 of full scale
+    pub ramp_degrees: f32,  // Wheel degrees past the lock over which the push builds up to `strength`
+    pub damping: f32,       // Resistance to motion inside the wall, 0.0 - 1.0, keeps the wheel from bouncing off it
+}
+
+impl Default for SoftLockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strength: 1.0,
+            ramp_degrees: 10.0,
+            damping: 0.3,
+        }
+    }
+}
+
+impl Default for EffectSwitches {
+    fn default() -> Self {
+        Self {
+            constant: true,
+            periodic: true,
+            ramp: true,
+            spring: true,
+            damper: true,
+            friction: true,
+            inertia: true,
+        }
+    }
 }
 
 impl Default for FfbConfig {
@@ -181,8 +898,15 @@ impl Default for FfbConfig {
             periodic_gain: 1.0,
             ramp_gain: 1.0,
             autocenter_gain: 0.2,
-            max_force: 2.5, // Typical for consumer wheels
+            max_force: ForceNewton::new(2.5), // Typical for consumer wheels
             update_rate_hz: 1000,
+            panic_button: None,
+            effects: EffectSwitches::default(),
+            thermal_limiter: ThermalLimiterConfig::default(),
+            soft_start_ms: FfbConfig::default_soft_start_ms(),
+            render_mode: FfbRenderMode::Native,
+            autocenter_decay_ms: FfbConfig::default_autocenter_decay_ms(),
+            soft_lock: SoftLockConfig::default(),
         }
     }
 }
@@ -194,6 +918,124 @@ pub struct LoggingConfig {
     pub log_file_path: Option<String>,
     pub log_hid_reports: bool,
     pub log_ffb_commands: bool,
+    #[serde(default)]
+    pub tick_budget: TickBudgetConfig,
+}
+
+/// Warning for input loop ticks that repeatedly run over their time budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickBudgetConfig {
+    pub budget_us: u32,  // Longest acceptable tick in microseconds, 0 = off
+    pub overruns: u32,   // Slow ticks per 1000 before warning
+}
+
+impl Default for TickBudgetConfig {
+    fn default() -> Self {
+        Self {
+            budget_us: 500,
+            overruns: 20,
+        }
+    }
+}
+
+/// Selection of the per-game profile layered on top of the configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub active: Option<String>,    // Profile applied at startup and on reload
+    pub directory: String,         // Profile files, relative to the configuration file
+    pub auto_switch: bool,         // Activate the profile listing a running game in its `processes`
+    pub poll_interval_ms: u32,     // How often running processes are checked
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            active: None,
+            directory: "profiles".to_string(),
+            auto_switch: false,
+            poll_interval_ms: 2000,
+        }
+    }
+}
+
+/// Prometheus metrics endpoint, off unless enabled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub bind: String,              // Address serving GET /metrics, keep it on localhost unless the rig is trusted
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:9187".to_string(),
+        }
+    }
+}
+
+/// Blind comparison of two FFB tunings, see `ab`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AbTestConfig {
+    pub enabled: bool,
+    pub profile_a: Option<String>, // Profile whose [ffb_config] is side A, this configuration when unset
+    pub profile_b: Option<String>, // Profile whose [ffb_config] is side B
+    pub interval_secs: u32,        // Time on each side before switching
+    pub mark_button: Option<u8>,   // Source button marking the current side as feeling better
+    pub blip_strength: f32,        // 0.0 - 1.0, force of the pulse announcing a switch, 0 = silent
+}
+
+impl Default for AbTestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            profile_a: None,
+            profile_b: None,
+            interval_secs: 60,
+            mark_button: None,
+            blip_strength: 0.3,
+        }
+    }
+}
+
+/// Wheel buttons typed as keyboard keys, see `keys`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    pub enabled: bool,
+    pub bindings: HashMap<u8, String>, // Source button -> key name, e.g. 5 = "F1"
+    pub exclusive: bool,               // Keep bound buttons out of the G29 report
+}
+
+/// Experimental features to switch on, see `experimental::Feature`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExperimentalConfig {
+    pub features: Vec<String>,     // Feature names, e.g. "telemetry_effects"; read at startup
+}
+
+/// Tuning for low-power hosts such as a Raspberry Pi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostConfig {
+    pub embedded: bool,            // Trade loop rates for headroom on a single board computer
+    pub input_poll_hz: u32,        // Input loop rate in embedded mode
+    pub ffb_update_rate_hz: u32,   // Cap on ffb_config.update_rate_hz in embedded mode
+    pub skip_missed_ticks: bool,   // Drop late input ticks instead of catching up in a burst
+}
+
+impl Default for HostConfig {
+    fn default() -> Self {
+        Self {
+            embedded: false,
+            input_poll_hz: 500,
+            ffb_update_rate_hz: 250,
+            skip_missed_ticks: true,
+        }
+    }
 }
 
 impl Default for LoggingConfig {
@@ -204,22 +1046,470 @@ impl Default for LoggingConfig {
             log_file_path: None,
             log_hid_reports: false,
             log_ffb_commands: false,
+            tick_budget: TickBudgetConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub assetto_corsa: AssettoCorsaConfig,
+    #[serde(default = "UdpListenerConfig::f1_default")]
+    pub f1: UdpListenerConfig,
+    #[serde(default = "UdpListenerConfig::dirt_rally_default")]
+    pub dirt_rally: UdpListenerConfig,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            assetto_corsa: AssettoCorsaConfig::default(),
+            f1: UdpListenerConfig::f1_default(),
+            dirt_rally: UdpListenerConfig::dirt_rally_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AcVariant {
+    AssettoCorsa,  // Remote telemetry protocol, port 9996
+    Competizione,  // Broadcasting protocol, port 9000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssettoCorsaConfig {
+    pub enabled: bool,
+    pub variant: AcVariant,
+    pub host: String,
+    pub port: u16,
+    pub max_rpm: Option<f32>,          // Rev limit for LEDs, neither game streams it
+    pub connection_password: String,   // ACC broadcasting.json connectionPassword
+    pub update_interval_ms: u32,       // ACC realtime update interval
+}
+
+impl Default for AssettoCorsaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            variant: AcVariant::AssettoCorsa,
+            host: "127.0.0.1".to_string(),
+            port: 9996,
+            max_rpm: None,
+            connection_password: "asd".to_string(),
+            update_interval_ms: 50,
+        }
+    }
+}
+
+/// Telemetry source that pushes packets to a local UDP port
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UdpListenerConfig {
+    pub enabled: bool,
+    pub bind_address: String,
+    pub port: u16,
+}
+
+impl UdpListenerConfig {
+    pub fn f1_default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "0.0.0.0".to_string(),
+            port: 20777,
         }
     }
+
+    pub fn dirt_rally_default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "0.0.0.0".to_string(),
+            port: 20778, // Avoid clashing with F1 when both are enabled
+        }
+    }
+}
+
+/// Global keyboard shortcuts bound to runtime actions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HotkeyConfig {
+    pub enabled: bool,
+    pub bindings: HashMap<String, ControlAction>,  // e.g. "ctrl+shift+KeyP" = "TogglePause"
 }
 
 impl Config {
-    /// Load configuration from TOML file
+    /// Load configuration from TOML file, with the profiles it `extends` merged in
     pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let resolved = profile::resolve(std::path::Path::new(path))?;
+        let mut config = Self::from_profile(&resolved)?;
+        Inventory::load(&inventory::default_path())?.resolve_selectors(&mut config)?;
+        config.source = Some(PathBuf::from(path));
+        Ok(config)
+    }
+
+    /// Configuration of a resolved profile
+    pub fn from_profile(resolved: &ResolvedProfile) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: Config = toml::Value::Table(resolved.table.clone()).try_into()?;
+        config.input_config.check_steering()?;
         Ok(config)
     }
     
-    /// Save configuration to TOML file
+    /// Save configuration to TOML file, atomically and keeping the previous versions
     pub fn save_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let content = toml::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
+        crate::persist::write_with_backups(Path::new(path), self.to_toml()?.as_bytes(), self.backup_config.keep)?;
         Ok(())
     }
-} 
\ No newline at end of file
+
+    /// Configuration as the TOML a file would hold
+    pub fn to_toml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+} 
+
+/// Vibration on pedal sets with motors, driven by game telemetry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PedalRumbleConfig {
+    pub enabled: bool,
+    pub vid: u16,
+    pub pid: Option<u16>,      // Match by product name when unset
+    pub abs_intensity: f32,    // Brake motor while ABS is active, 0.0 - 1.0
+    pub tc_intensity: f32,     // Throttle motor while TC is active, 0.0 - 1.0
+    pub slip_threshold: f32,   // Slip ratio where wheel spin / lockup starts to vibrate, 0 = off
+    pub update_interval_ms: u32,
+}
+
+impl Default for PedalRumbleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vid: 0x044F,
+            pid: None,
+            abs_intensity: 0.8,
+            tc_intensity: 0.5,
+            slip_threshold: 0.0,
+            update_interval_ms: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandbrakeTarget {
+    Clutch,  // Analog handbrake drives the G29 clutch axis
+    Button,  // Handbrake pulled past halfway presses `handbrake_button`
+}
+
+/// TSS Handbrake Sparco Mod, merged into the wheel report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandbrakeConfig {
+    pub enabled: bool,
+    pub vid: u16,
+    pub pid: Option<u16>,           // Match by product name when unset
+    pub mode: Option<HandbrakeMode>, // Detected from the descriptor when unset
+    pub target: HandbrakeTarget,
+    pub handbrake_button: u8,       // Source button index, goes through button_mapping
+    pub upshift_button: u8,         // Source button index in sequential mode
+    pub downshift_button: u8,
+}
+
+/// TH8A shifter, its gears pressing the G29 shifter buttons
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShifterConfig {
+    pub enabled: bool,
+    pub vid: u16,
+    pub pid: Option<u16>,        // Match by product name when unset
+    pub gear_buttons: [u8; 8],   // G29 buttons of gears 1-7 and reverse, 24 or more = not sent
+}
+
+impl Default for ShifterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vid: 0x044F,
+            pid: None,
+            // Driving Force Shifter: gears 1-6 on buttons 12-17, reverse on 18, no 7th
+            gear_buttons: [12, 13, 14, 15, 16, 17, u8::MAX, 18],
+        }
+    }
+}
+
+/// Backups of the configuration file kept by every save, see `persist`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub keep: usize,    // Previous versions kept as config.toml.bak.1 (newest) to .bak.N, 0 = none
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { keep: 5 }
+    }
+}
+
+/// Units and number format of CLI and monitor output, see `presentation`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub angle: AngleUnit,
+    pub force: ForceUnit,
+    pub locale: Option<String>,   // e.g. "de_DE" for decimal commas, unset = LC_ALL / LC_NUMERIC / LANG
+}
+
+/// How steering positions are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AngleUnit {
+    Degrees,      // Wheel angle, half the rotation range at full lock
+    Normalized,   // -1.0 (full left) - 1.0 (full right)
+}
+
+impl Default for AngleUnit {
+    fn default() -> Self {
+        AngleUnit::Degrees
+    }
+}
+
+/// How forces are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForceUnit {
+    Percent,   // Of full scale
+    Newton,    // Full scale taken as `ffb_config.max_force`
+}
+
+impl Default for ForceUnit {
+    fn default() -> Self {
+        ForceUnit::Percent
+    }
+}
+
+/// Wheel attached to another host running `tm-g29 serve-wheel`, see `remote`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    pub connect: Option<String>,   // Wheel host to read the wheel from, e.g. "rig-pi.local:7290", unset = local wheel
+    pub listen: String,            // Address `serve-wheel` accepts translators on
+    pub key: String,               // Shared secret both ends authenticate with, empty = remote wheels disabled
+    pub connect_timeout_ms: u32,   // Connecting and authenticating, each
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            connect: None,
+            listen: "0.0.0.0:7290".to_string(),
+            key: String::new(),
+            connect_timeout_ms: 3000,
+        }
+    }
+}
+
+/// Jitter buffers for VMs and cloud instances with erratic timing, see `jitter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JitterConfig {
+    pub enabled: bool,
+    pub input_delay_ms: u32,   // Translated reports held before the virtual G29, 0 = input unbuffered
+    pub ffb_delay_ms: u32,     // Game FFB updates held before the wheel, 0 = FFB unbuffered
+    pub max_delay_ms: u32,     // Items waiting longer are dropped instead of played late
+}
+
+impl Default for JitterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            input_delay_ms: 8,
+            ffb_delay_ms: 8,
+            max_delay_ms: 50,
+        }
+    }
+}
+
+/// Standalone USB pedal set and the calibration applied while it is connected
+///
+/// The first configured set found among the HID devices replaces the wheel's
+/// pedal axes, so swapping pedals between rigs picks up the right calibration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PedalSetConfig {
+    pub name: String,
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub serial_number: Option<String>,  // Tells identical sets apart
+    #[serde(default)]
+    pub device: Option<String>,         // Inventory nickname, sets vid, pid and serial_number
+    #[serde(default = "PedalSetConfig::default_axis_bytes")]
+    pub axis_bytes: [u8; 3],            // Report bytes of throttle, brake and clutch
+    #[serde(default)]
+    pub wide_axes: bool,                // Axes are 16-bit little-endian from their byte, e.g. a T-LCM load cell
+    #[serde(default = "PedalSetConfig::default_assign")]
+    pub assign: [PedalAxis; 3],         // Wheel axis each of the set's throttle, brake and clutch drives
+    #[serde(default)]
+    pub throttle: AxisCalibration,
+    #[serde(default)]
+    pub brake: AxisCalibration,
+    #[serde(default)]
+    pub clutch: AxisCalibration,
+}
+
+impl PedalSetConfig {
+    pub fn default_axis_bytes() -> [u8; 3] {
+        [1, 2, 3]
+    }
+
+    pub fn default_assign() -> [PedalAxis; 3] {
+        [PedalAxis::Throttle, PedalAxis::Brake, PedalAxis::Clutch]
+    }
+}
+
+/// Wheel pedal axis a pedal set axis stands in for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PedalAxis {
+    Throttle,
+    Brake,
+    Clutch,
+    Off,  // Ignored, the wheel's own pedal stays
+}
+
+/// Second input blended with the wheel, e.g. an instructor's gamepad
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoDriverConfig {
+    pub enabled: bool,
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,  // Tells identical devices apart
+    pub device: Option<String>,         // Inventory nickname, sets vid, pid and serial_number
+    pub axis_bytes: [u8; 3],            // Report bytes of steering (128 = center), throttle and brake
+    pub weight: f32,                    // Share of the co-driver in steering and throttle, 0.0 - 1.0
+    pub override_button: Option<u8>,    // Bit after the report's first byte giving the co-driver full control while held
+}
+
+impl Default for CoDriverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vid: 0,
+            pid: 0,
+            serial_number: None,
+            device: None,
+            axis_bytes: [4, 5, 6],
+            weight: 0.5,
+            override_button: None,
+        }
+    }
+}
+
+/// Per-axis fixes for drifting or reversed axes, applied after calibration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisTuning {
+    pub steering: AxisTune,
+    pub throttle: AxisTune,
+    pub brake: AxisTune,
+    pub clutch: AxisTune,
+}
+
+impl Default for AxisTuning {
+    fn default() -> Self {
+        Self {
+            steering: AxisTune { deadzone: 0.02, ..AxisTune::default() },
+            throttle: AxisTune::default(),
+            brake: AxisTune::default(),
+            clutch: AxisTune::default(),
+        }
+    }
+}
+
+/// Move steering settings older files kept apart into `axis_tuning.steering`
+///
+/// `input_config.steering_deadzone` and `steering_geometry.center_offset`
+/// used to apply on top of the steering tuning; they are folded into its
+/// `deadzone` and `center_offset` so the wheel reads as it did.
+pub fn migrate_steering_tuning(table: &mut toml::value::Table) {
+    let Some(input) = table.get_mut("input_config").and_then(toml::Value::as_table_mut) else {
+        return;
+    };
+    let number = |value: &toml::Value| value.as_float().or_else(|| value.as_integer().map(|value| value as f64));
+    let deadzone = input.remove("steering_deadzone").as_ref().and_then(number);
+    let offset = input
+        .get_mut("steering_geometry")
+        .and_then(toml::Value::as_table_mut)
+        .and_then(|geometry| geometry.remove("center_offset"))
+        .as_ref()
+        .and_then(number);
+    if deadzone.is_none() && offset.is_none() {
+        return;
+    }
+
+    let Some(steering) = input
+        .entry("axis_tuning")
+        .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+        .as_table_mut()
+        .and_then(|tuning| {
+            tuning.entry("steering").or_insert_with(|| toml::Value::Table(toml::value::Table::new())).as_table_mut()
+        })
+    else {
+        return;
+    };
+    if let Some(deadzone) = deadzone {
+        // A deadzone applied after another one widens it to the same response
+        let tuned = steering.get("deadzone").and_then(number).unwrap_or(0.0);
+        steering.insert("deadzone".to_string(), toml::Value::Float(tuned + deadzone - tuned * deadzone));
+    }
+    if let Some(offset) = offset {
+        let tuned = steering.get("center_offset").and_then(number).unwrap_or(0.0);
+        steering.insert("center_offset".to_string(), toml::Value::Float(tuned + offset));
+    }
+}
+
+/// Invert, offset, deadzone and saturation of one axis, in that order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisTune {
+    pub invert: bool,
+    pub center_offset: f32,  // Position read as center (steering) or rest (pedals), -1.0 - 1.0
+    pub deadzone: f32,       // Travel from center or rest that reads as 0, 0.0 - 1.0
+    pub saturation: f32,     // Travel that already reads as full, 0.0 - 1.0
+}
+
+impl Default for AxisTune {
+    fn default() -> Self {
+        Self {
+            invert: false,
+            center_offset: 0.0,
+            deadzone: 0.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+/// Raw travel of one pedal, stretched to the full axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisCalibration {
+    pub min: u8,  // Raw value at rest
+    pub max: u8,  // Raw value fully pressed, below min for pedals reading in reverse
+}
+
+impl Default for AxisCalibration {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: u8::MAX,
+        }
+    }
+}
+
+impl Default for HandbrakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vid: 0x044F,
+            pid: None,
+            mode: None,
+            target: HandbrakeTarget::Clutch,
+            handbrake_button: 13,
+            upshift_button: 14,
+            downshift_button: 15,
+        }
+    }
+}
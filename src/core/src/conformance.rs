@@ -0,0 +1,220 @@
+//! Conformance of the virtual G29 stack against golden captures
+//!
+//! A golden capture holds the output reports a game sent to a real G29 and,
+//! as `Force` records, the constant force that wheel rendered at those
+//! points in time. `check_case` plays the output reports through
+//! `OutputTranslator` and `FfbEngine` on a mock clock, computes the force our
+//! stack would be rendering at every golden sample and records the samples
+//! that differ by more than the tolerance. Results are grouped by feature
+//! area, the kind of the effect started last, so a regression in one effect
+//! family stands out from the rest of the battery.
+
+use crate::clock::MockClock;
+use crate::config::Config;
+use crate::device::IforceCommand;
+use crate::ffb::{EffectKind, FfbEngine};
+use crate::protocol::OutputTranslator;
+use crate::recording::Recording;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Largest accepted force difference, as a fraction of full scale
+pub const DEFAULT_TOLERANCE: f32 = 0.02;
+
+/// IFORCE command carrying a constant force
+const IFORCE_CONSTANT: u8 = 0x41;
+
+/// Deviations listed in the report, the rest are only counted
+const LISTED_DEVIATIONS: usize = 20;
+
+/// Area of output reports the real wheel accepted but we could not parse
+const PARSING_AREA: &str = "Parsing";
+
+/// Area of samples taken while no effect was playing
+const IDLE_AREA: &str = "Idle";
+
+/// Checks of one feature area over the whole battery
+#[derive(Debug, Clone, Default)]
+pub struct AreaResult {
+    pub checks: usize,
+    pub deviations: usize,
+    /// Largest force difference seen, as a fraction of full scale
+    pub max_error: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Deviation {
+    pub case: String,
+    pub area: String,
+    pub timestamp: Duration,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    pub cases: usize,
+    pub areas: BTreeMap<String, AreaResult>,
+    pub deviations: Vec<Deviation>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.deviations.is_empty()
+    }
+
+    fn check(&mut self, case: &str, area: &str, timestamp: Duration, error: f32, deviation: Option<String>) {
+        let result = self.areas.entry(area.to_string()).or_default();
+        result.checks += 1;
+        result.max_error = result.max_error.max(error);
+        if let Some(detail) = deviation {
+            result.deviations += 1;
+            self.deviations.push(Deviation { case: case.to_string(), area: area.to_string(), timestamp, detail });
+        }
+    }
+}
+
+/// Play the golden capture `golden` through the stack configured by `config`
+pub fn check_case(report: &mut ConformanceReport, case: &str, golden: &Recording, config: &Config, tolerance: f32) {
+    let clock = MockClock::new();
+    let mut translator = OutputTranslator::new(&config.output_config);
+    let mut engine = FfbEngine::new(&config.ffb_config).with_clock(Arc::new(clock.clone()));
+    // Last constant force sent per effect block
+    let mut forces = BTreeMap::new();
+    let mut area = IDLE_AREA.to_string();
+    let mut commands = Vec::new();
+    report.cases += 1;
+
+    let mut outputs = golden.outputs.iter().peekable();
+    for sample in &golden.forces {
+        while let Some(output) = outputs.next_if(|output| output.timestamp <= sample.timestamp) {
+            advance_to(&clock, output.timestamp);
+            match translator.parse_pid_report(&output.report) {
+                Ok(Some(command)) => {
+                    if let Ok(sent) = engine.handle_pid(command) {
+                        apply_constants(&mut forces, &sent);
+                    }
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report.check(case, PARSING_AREA, output.timestamp, 0.0, Some(e.to_string()));
+                    continue;
+                }
+            }
+            let effect = match translator.parse_ffb_effect(output.report.clone()) {
+                Ok(Some(effect)) => effect,
+                Ok(None) => continue,
+                Err(e) => {
+                    report.check(case, PARSING_AREA, output.timestamp, 0.0, Some(e.to_string()));
+                    continue;
+                }
+            };
+            report.check(case, PARSING_AREA, output.timestamp, 0.0, None);
+            area = format!("{:?}", EffectKind::of(&effect.effect_type));
+            if let Ok(sent) = engine.translate_effect(effect) {
+                apply_constants(&mut forces, &sent);
+            }
+        }
+
+        advance_to(&clock, sample.timestamp);
+        commands.clear();
+        if engine.update_active_effects_into(&mut commands).is_ok() {
+            apply_constants(&mut forces, &commands);
+        }
+        // Hardware-timed effects end on the wheel without a command
+        let active: Vec<u8> = engine.active_effects().iter().map(|effect| effect.id).collect();
+        forces.retain(|id, _| active.contains(id));
+        if active.is_empty() {
+            area = IDLE_AREA.to_string();
+        }
+
+        let actual = forces.values().map(|&magnitude: &i16| magnitude as i32).sum::<i32>();
+        let actual = actual.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let error = (actual as f32 - sample.magnitude as f32).abs() / i16::MAX as f32;
+        let deviation = (error > tolerance).then(|| {
+            format!("force {:+} where the G29 rendered {:+} ({:.1}% off)", actual, sample.magnitude, error * 100.0)
+        });
+        report.check(case, &area, sample.timestamp, error, deviation);
+    }
+}
+
+fn advance_to(clock: &MockClock, timestamp: Duration) {
+    if let Some(step) = timestamp.checked_sub(clock.elapsed()) {
+        clock.advance(step);
+    }
+}
+
+/// Track the constant forces in `commands` by effect block
+fn apply_constants(forces: &mut BTreeMap<u8, i16>, commands: &[IforceCommand]) {
+    for command in commands.iter().filter(|c| c.command_id == IFORCE_CONSTANT && c.data.len() >= 3) {
+        forces.insert(command.data[0], i16::from_le_bytes([command.data[1], command.data[2]]));
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Cases: {}", self.cases)?;
+        for (area, result) in &self.areas {
+            writeln!(
+                f,
+                "  {:<10} {} checks, {} deviations, max error {:.1}%",
+                area,
+                result.checks,
+                result.deviations,
+                result.max_error * 100.0
+            )?;
+        }
+        for deviation in self.deviations.iter().take(LISTED_DEVIATIONS) {
+            writeln!(
+                f,
+                "{} @ {:.3}s [{}]: {}",
+                deviation.case,
+                deviation.timestamp.as_secs_f64(),
+                deviation.area,
+                deviation.detail
+            )?;
+        }
+        if self.deviations.len() > LISTED_DEVIATIONS {
+            writeln!(f, "... and {} more", self.deviations.len() - LISTED_DEVIATIONS)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::G29OutputReport;
+    use crate::recording::{RecordedForce, RecordedOutput};
+
+    fn output(ms: u64, report: G29OutputReport) -> RecordedOutput {
+        RecordedOutput { timestamp: Duration::from_millis(ms), report }
+    }
+
+    fn force(ms: u64, magnitude: i16) -> RecordedForce {
+        RecordedForce { timestamp: Duration::from_millis(ms), magnitude }
+    }
+
+    #[test]
+    fn test_deviations_are_attributed_to_the_effect_area() {
+        let mut golden = Recording::default();
+        golden.outputs.push(output(0, G29OutputReport::constant_effect(1, 8000, 100)));
+        golden.forces.push(force(10, 8000));
+        // A real wheel ends the effect after its 100 ms duration
+        golden.forces.push(force(150, 0));
+
+        let mut report = ConformanceReport::default();
+        check_case(&mut report, "constant", &golden, &Config::default(), DEFAULT_TOLERANCE);
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.areas["Constant"].checks, 1);
+        assert_eq!(report.areas[IDLE_AREA].checks, 1);
+
+        golden.forces[0].magnitude = -8000;
+        let mut report = ConformanceReport::default();
+        check_case(&mut report, "constant", &golden, &Config::default(), DEFAULT_TOLERANCE);
+        assert_eq!(report.areas["Constant"].deviations, 1);
+        assert_eq!(report.deviations[0].area, "Constant");
+    }
+}
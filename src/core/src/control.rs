@@ -0,0 +1,429 @@
+//! Runtime control of a running translator
+//!
+//! A `TranslatorControl` is a cheap cloneable handle shared between the
+//! translation loops and whatever drives them at runtime (signal handlers,
+//! the IPC socket). It outlives individual pipeline instances, so a
+//! supervised restart keeps the pause state and the recent-state history.
+//! Observed pipeline state lives in a `StateStore`, read as consistent
+//! snapshots, and the pipeline counters in lock-free `StatsCounters`.
+
+use crate::ab::AbReport;
+use crate::config::{Config, SteeringGeometry};
+use crate::device::{G29InputReport, StrategySelection};
+use crate::device::models::Rim;
+use crate::ffb::ActiveEffectSummary;
+use crate::jitter::JitterReport;
+use crate::log_limit::LogCounts;
+use crate::recording::{RecordedInput, Recording};
+use crate::state::{StateSnapshot, StateStore};
+use crate::stats::{PipelineStats, StatsAccumulator, StatsCounters};
+use crate::timeline::{EffectEvent, TimelineEntry};
+use crate::units::{NormalizedAxis, SteeringAngle};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Translated reports kept for state dumps, about 2 seconds at 1 kHz
+pub const DEFAULT_STATE_HISTORY: usize = 2_000;
+
+/// Effect lifetime events kept for timeline viewers
+pub const TIMELINE_LEN: usize = 1_024;
+
+/// Action that can be triggered at runtime from outside the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlAction {
+    TogglePause,
+    Recenter,      // Current wheel position becomes the steering center
+    CycleProfile,
+    FfbPanic,      // Zero all forces until triggered again
+    AbMark,        // The current side of an FFB A/B comparison feels better
+}
+
+/// Latest pipeline state, as shown by read-only viewers
+#[derive(Debug, Clone, Copy)]
+pub struct StateSample {
+    pub report: G29InputReport,
+    /// Last constant force magnitude sent to the wheel
+    pub force: i16,
+    pub paused: bool,
+    /// Rotation range in degrees lock to lock, 0 if not yet known
+    pub steering_range: u16,
+}
+
+impl StateSample {
+    /// Steering position in degrees from center, if the rotation range is known
+    pub fn steering_degrees(&self) -> Option<f32> {
+        steering_degrees(self.report.steering, self.steering_range)
+    }
+}
+
+/// Degrees from center of a G29 steering value on a wheel with `range` degrees lock to lock
+pub fn steering_degrees(steering: u16, range: u16) -> Option<f32> {
+    (range > 0).then(|| SteeringAngle::from_axis(NormalizedAxis::from_g29_steering(steering), range).degrees())
+}
+
+#[derive(Clone)]
+pub struct TranslatorControl {
+    inner: Arc<ControlState>,
+}
+
+struct ControlState {
+    started: Instant,
+    paused: AtomicBool,
+    ffb_panic: AtomicBool,
+    pending_config: Mutex<Option<Config>>,
+    pending_actions: Mutex<Vec<ControlAction>>,
+    history: Mutex<VecDeque<RecordedInput>>,
+    history_len: usize,
+    state: StateStore,
+    stats: Arc<StatsCounters>,
+    read_strategy: Mutex<Option<StrategySelection>>,
+    /// Inventory nickname or model of the wheel being translated
+    wheel_name: Mutex<Option<String>>,
+    /// Rounds of the FFB A/B comparison, `None` when none runs
+    ab_report: Mutex<Option<AbReport>>,
+    /// Occupancy of the jitter buffers, `None` when they are off
+    jitter_report: Mutex<Option<JitterReport>>,
+    timeline: Mutex<VecDeque<TimelineEntry>>,
+    /// Profile of the running game, overrides the configured one
+    game_profile: Mutex<Option<String>>,
+    /// Global FFB gain waiting to be applied
+    pending_gain: Mutex<Option<f32>>,
+    /// Steering center offset and per-side scaling waiting to be applied
+    pending_geometry: Mutex<Option<(f32, SteeringGeometry)>>,
+    shutdown: tokio::sync::Notify,
+    /// Milliseconds after `started` of the last input loop tick, 0 before the first
+    last_tick: AtomicU64,
+}
+
+impl TranslatorControl {
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            inner: Arc::new(ControlState {
+                started: Instant::now(),
+                paused: AtomicBool::new(false),
+                ffb_panic: AtomicBool::new(false),
+                pending_config: Mutex::new(None),
+                pending_actions: Mutex::new(Vec::new()),
+                history: Mutex::new(VecDeque::with_capacity(history_len)),
+                history_len,
+                state: StateStore::default(),
+                stats: Arc::default(),
+                read_strategy: Mutex::new(None),
+                wheel_name: Mutex::new(None),
+                ab_report: Mutex::new(None),
+                jitter_report: Mutex::new(None),
+                timeline: Mutex::new(VecDeque::with_capacity(TIMELINE_LEN)),
+                game_profile: Mutex::new(None),
+                pending_gain: Mutex::new(None),
+                pending_geometry: Mutex::new(None),
+                shutdown: tokio::sync::Notify::new(),
+                last_tick: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.inner.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Flip the pause state, returning the new state
+    pub fn toggle_pause(&self) -> bool {
+        !self.inner.paused.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// Whether forces must be held at zero
+    pub fn is_ffb_panic(&self) -> bool {
+        self.inner.ffb_panic.load(Ordering::Relaxed)
+    }
+
+    /// Flip the FFB panic override, returning the new state
+    pub fn toggle_ffb_panic(&self) -> bool {
+        !self.inner.ffb_panic.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    /// Ask the running pipeline to switch to `config` at its next cycle
+    pub fn request_reload(&self, config: Config) {
+        if let Ok(mut pending) = self.inner.pending_config.lock() {
+            *pending = Some(config);
+        }
+    }
+
+    /// Take the configuration waiting to be applied, if any
+    pub fn take_reload(&self) -> Option<Config> {
+        self.inner.pending_config.lock().ok()?.take()
+    }
+
+    /// Profile selected for the running game, if any
+    pub fn game_profile(&self) -> Option<String> {
+        self.inner.game_profile.lock().ok()?.clone()
+    }
+
+    /// Select the profile of the running game, applied with the next configuration reload
+    pub fn set_game_profile(&self, profile: Option<String>) {
+        if let Ok(mut game_profile) = self.inner.game_profile.lock() {
+            *game_profile = profile;
+        }
+    }
+
+    /// Ask the running pipeline to switch to a global FFB gain of `gain` (0.0 - 1.0)
+    pub fn request_gain(&self, gain: f32) {
+        if let Ok(mut pending) = self.inner.pending_gain.lock() {
+            *pending = Some(gain.clamp(0.0, 1.0));
+        }
+    }
+
+    /// Take the global FFB gain waiting to be applied, if any
+    pub fn take_gain(&self) -> Option<f32> {
+        self.inner.pending_gain.lock().ok()?.take()
+    }
+
+    /// Ask the running pipeline to switch to `center_offset` and `geometry` until the next reload
+    pub fn request_steering_geometry(&self, center_offset: f32, geometry: SteeringGeometry) {
+        if let Ok(mut pending) = self.inner.pending_geometry.lock() {
+            *pending = Some((center_offset, geometry));
+        }
+    }
+
+    /// Take the steering center offset and geometry waiting to be applied, if any
+    pub fn take_steering_geometry(&self) -> Option<(f32, SteeringGeometry)> {
+        self.inner.pending_geometry.lock().ok()?.take()
+    }
+
+    /// Publish the global FFB gain the running pipeline applies
+    pub fn set_ffb_gain(&self, gain: f32) {
+        self.inner.state.update(|state| state.ffb_gain = gain);
+    }
+
+    /// Ask whoever runs the translator to stop it cleanly
+    pub fn request_shutdown(&self) {
+        self.inner.shutdown.notify_one();
+    }
+
+    /// Wait until a shutdown is requested, one waiter at a time
+    pub async fn shutdown_requested(&self) {
+        self.inner.shutdown.notified().await
+    }
+
+    /// Note that the input loop completed a tick, for watchdogs
+    pub fn mark_tick(&self) {
+        let elapsed = self.inner.started.elapsed().as_millis() as u64;
+        self.inner.last_tick.store(elapsed.max(1), Ordering::Relaxed);
+    }
+
+    /// Whether the input loop ticked within `window`, true before its first tick
+    pub fn ticked_within(&self, window: std::time::Duration) -> bool {
+        match self.inner.last_tick.load(Ordering::Relaxed) {
+            0 => true,
+            last => self.inner.started.elapsed().as_millis() as u64 <= last + window.as_millis() as u64,
+        }
+    }
+
+    /// Trigger `action`; pause and FFB panic apply at once, the rest at the next input report
+    pub fn trigger(&self, action: ControlAction) {
+        match action {
+            ControlAction::TogglePause => {
+                self.toggle_pause();
+            }
+            ControlAction::FfbPanic => {
+                self.toggle_ffb_panic();
+            }
+            _ => {
+                if let Ok(mut pending) = self.inner.pending_actions.lock() {
+                    pending.push(action);
+                }
+            }
+        }
+    }
+
+    /// Take the actions waiting for the pipeline
+    pub fn take_actions(&self) -> Vec<ControlAction> {
+        self.inner
+            .pending_actions
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default()
+    }
+
+    /// Publish the source read path the running pipeline uses
+    pub fn set_read_strategy(&self, selection: StrategySelection) {
+        if let Ok(mut current) = self.inner.read_strategy.lock() {
+            *current = Some(selection);
+        }
+    }
+
+    pub fn read_strategy(&self) -> Option<StrategySelection> {
+        self.inner.read_strategy.lock().ok()?.clone()
+    }
+
+    /// Publish the name the running pipeline's wheel is shown by
+    pub fn set_wheel_name(&self, name: String) {
+        if let Ok(mut current) = self.inner.wheel_name.lock() {
+            *current = Some(name);
+        }
+    }
+
+    pub fn wheel_name(&self) -> Option<String> {
+        self.inner.wheel_name.lock().ok()?.clone()
+    }
+
+    /// Publish the rounds of the running FFB A/B comparison
+    pub fn set_ab_report(&self, report: AbReport) {
+        if let Ok(mut current) = self.inner.ab_report.lock() {
+            *current = Some(report);
+        }
+    }
+
+    /// FFB A/B comparison so far, kept after the pipeline stops for the session report
+    pub fn ab_report(&self) -> Option<AbReport> {
+        self.inner.ab_report.lock().ok()?.clone()
+    }
+
+    /// Publish the occupancy of the jitter buffers, changed by `update`
+    pub fn update_jitter_report(&self, update: impl FnOnce(&mut JitterReport)) {
+        if let Ok(mut current) = self.inner.jitter_report.lock() {
+            update(current.get_or_insert_with(JitterReport::default));
+        }
+    }
+
+    /// Jitter buffer occupancy, `None` unless buffers are enabled
+    pub fn jitter_report(&self) -> Option<JitterReport> {
+        *self.inner.jitter_report.lock().ok()?
+    }
+
+    /// Publish the rotation range the running pipeline translates for
+    pub fn set_steering_range(&self, degrees: u16) {
+        self.inner.state.update(|state| state.steering_range = degrees);
+    }
+
+    /// Rotation range in degrees lock to lock, 0 before a pipeline has started
+    pub fn steering_range(&self) -> u16 {
+        self.inner.state.load().steering_range
+    }
+
+    /// Publish the rim attached to the base
+    pub fn set_rim(&self, rim: Rim) {
+        self.inner.state.update(|state| state.rim = Some(rim));
+    }
+
+    /// Publish a translated report, also keeping it in the history for dumps
+    pub fn record_state(&self, report: G29InputReport) {
+        let timestamp = self.inner.started.elapsed();
+        self.inner.state.update(|state| {
+            state.report = Some(report);
+            state.report_time = timestamp;
+        });
+
+        if self.inner.history_len == 0 {
+            return;
+        }
+        if let Ok(mut history) = self.inner.history.lock() {
+            if history.len() == self.inner.history_len {
+                history.pop_front();
+            }
+            history.push_back(RecordedInput { timestamp, report });
+        }
+    }
+
+    /// Remember the constant force last sent to the wheel
+    pub fn record_force(&self, magnitude: i16) {
+        self.inner.state.update(|state| state.force = magnitude);
+    }
+
+    /// Publish the effects the FFB engine holds
+    pub fn record_ffb(&self, effects: Vec<ActiveEffectSummary>) {
+        self.inner.state.update(|state| state.effects = effects.into());
+    }
+
+    /// Number and keep effect lifetime events from the FFB engine
+    pub fn record_timeline(&self, events: Vec<EffectEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let Ok(mut timeline) = self.inner.timeline.lock() else {
+            return;
+        };
+        for event in events {
+            let seq = timeline.back().map_or(0, |entry| entry.seq + 1);
+            if timeline.len() == TIMELINE_LEN {
+                timeline.pop_front();
+            }
+            timeline.push_back(TimelineEntry {
+                seq,
+                time: event.at.saturating_duration_since(self.inner.started),
+                id: event.id,
+                kind: event.kind,
+                phase: event.phase,
+                parameters: event.parameters,
+            });
+        }
+    }
+
+    /// Kept timeline events numbered `seq` or later
+    pub fn timeline_since(&self, seq: u64) -> Vec<TimelineEntry> {
+        self.inner
+            .timeline
+            .lock()
+            .map(|timeline| timeline.iter().filter(|entry| entry.seq >= seq).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Publish the warning totals of the translation loops
+    pub fn record_log_counts(&self, counts: LogCounts) {
+        self.inner.stats.set_warnings(counts.emitted, counts.suppressed);
+    }
+
+    /// Accumulator for one translation loop, feeding the shared counters
+    pub fn stats_accumulator(&self) -> StatsAccumulator {
+        StatsAccumulator::new(self.inner.stats.clone())
+    }
+
+    /// Count a pipeline start, restarts show in the stats
+    pub fn record_pipeline_start(&self) {
+        self.inner.stats.record_start();
+    }
+
+    /// Pipeline counters as of the last flush of each loop
+    pub fn stats(&self) -> PipelineStats {
+        self.inner.stats.load()
+    }
+
+    /// Consistent view of the latest pipeline state
+    pub fn snapshot(&self) -> Arc<StateSnapshot> {
+        self.inner.state.load()
+    }
+
+    /// Most recent translated report with the current force and pause state
+    pub fn latest_sample(&self) -> Option<StateSample> {
+        let state = self.snapshot();
+        Some(StateSample {
+            report: state.report?,
+            force: state.force,
+            paused: self.is_paused(),
+            steering_range: state.steering_range,
+        })
+    }
+
+    /// Snapshot of the recent-state history as a recording
+    pub fn state_snapshot(&self) -> Recording {
+        let inputs = self
+            .inner
+            .history
+            .lock()
+            .map(|history| history.iter().copied().collect())
+            .unwrap_or_default();
+        Recording { inputs, ..Default::default() }
+    }
+}
+
+impl Default for TranslatorControl {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATE_HISTORY)
+    }
+}
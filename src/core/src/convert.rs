@@ -0,0 +1,147 @@
+//! Integer conversions between the axis encodings used by wheels and games
+//!
+//! Thrustmaster bases report 8-bit pedals and signed 16-bit steering, the G29
+//! uses 10-bit pedals and offset-centered 16-bit steering, and uinput or
+//! ViGEm backends want their own ranges again. These helpers are the single
+//! place that math lives, so platform crates never shift bits by hand.
+//!
+//! Widening conversions replicate the high bits into the new low bits, so 0
+//! maps to 0 and full scale to full scale exactly; narrowing conversions
+//! round to nearest. Widening then narrowing always returns the original value.
+
+/// Largest 10-bit value, G29 pedal full scale
+pub const U10_MAX: u16 = 0x3FF;
+
+/// Offset-centered value of a signed 0
+pub const OFFSET_CENTER: u16 = 0x8000;
+
+/// 8-bit value to 10 bits, e.g. a Thrustmaster pedal to a G29 pedal
+pub const fn u8_to_u10(value: u8) -> u16 {
+    ((value as u16) << 2) | ((value as u16) >> 6)
+}
+
+/// 10-bit value to 8 bits, rounded; values above 10 bits saturate
+pub const fn u10_to_u8(value: u16) -> u8 {
+    let value = if value > U10_MAX { U10_MAX } else { value };
+    ((value as u32 * u8::MAX as u32 + U10_MAX as u32 / 2) / U10_MAX as u32) as u8
+}
+
+/// 8-bit value to 16 bits
+pub const fn u8_to_u16(value: u8) -> u16 {
+    ((value as u16) << 8) | value as u16
+}
+
+/// 16-bit value to 8 bits, rounded
+pub const fn u16_to_u8(value: u16) -> u8 {
+    ((value as u32 * u8::MAX as u32 + u16::MAX as u32 / 2) / u16::MAX as u32) as u8
+}
+
+/// 10-bit value to 16 bits; values above 10 bits saturate
+pub const fn u10_to_u16(value: u16) -> u16 {
+    let value = if value > U10_MAX { U10_MAX } else { value };
+    (value << 6) | (value >> 4)
+}
+
+/// 16-bit value to 10 bits, rounded
+pub const fn u16_to_u10(value: u16) -> u16 {
+    ((value as u32 * U10_MAX as u32 + u16::MAX as u32 / 2) / u16::MAX as u32) as u16
+}
+
+/// Signed 16-bit axis (0 = center) to offset-centered (0x8000 = center)
+pub const fn signed_to_offset(value: i16) -> u16 {
+    (value as u16) ^ OFFSET_CENTER
+}
+
+/// Offset-centered 16-bit axis (0x8000 = center) to signed (0 = center)
+pub const fn offset_to_signed(value: u16) -> i16 {
+    (value ^ OFFSET_CENTER) as i16
+}
+
+/// Rescale `value` in 0..=`from_max` onto 0..=`to_max`, rounded; out of range values saturate
+pub fn rescale(value: u32, from_max: u32, to_max: u32) -> u32 {
+    if from_max == 0 {
+        return 0;
+    }
+    let value = value.min(from_max) as u64;
+    ((value * to_max as u64 + from_max as u64 / 2) / from_max as u64) as u32
+}
+
+/// Steering angle in degrees to a -1.0..=1.0 axis on a wheel with `range_degrees` lock to lock
+pub fn degrees_to_normalized(degrees: f32, range_degrees: u16) -> f32 {
+    if range_degrees == 0 || degrees.is_nan() {
+        return 0.0;
+    }
+    (degrees / (range_degrees as f32 / 2.0)).clamp(-1.0, 1.0)
+}
+
+/// -1.0..=1.0 axis to degrees from center on a wheel with `range_degrees` lock to lock
+pub fn normalized_to_degrees(value: f32, range_degrees: u16) -> f32 {
+    value * range_degrees as f32 / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u8_widening_round_trips_and_hits_endpoints() {
+        for value in 0..=u8::MAX {
+            assert_eq!(u10_to_u8(u8_to_u10(value)), value);
+            assert_eq!(u16_to_u8(u8_to_u16(value)), value);
+        }
+        assert_eq!(u8_to_u10(u8::MAX), U10_MAX);
+        assert_eq!(u8_to_u16(u8::MAX), u16::MAX);
+        assert_eq!(u8_to_u10(0), 0);
+        assert_eq!(u8_to_u16(0), 0);
+    }
+
+    #[test]
+    fn u10_widening_round_trips_and_saturates() {
+        for value in 0..=U10_MAX {
+            assert_eq!(u16_to_u10(u10_to_u16(value)), value);
+        }
+        assert_eq!(u10_to_u16(U10_MAX), u16::MAX);
+        assert_eq!(u10_to_u16(u16::MAX), u16::MAX);
+        assert_eq!(u10_to_u8(u16::MAX), u8::MAX);
+    }
+
+    #[test]
+    fn conversions_are_monotonic() {
+        let mut previous = (0, 0, 0);
+        for value in 0..=u16::MAX {
+            let current = (u16_to_u8(value), u16_to_u10(value), u10_to_u8(value.min(U10_MAX)));
+            assert!(current.0 >= previous.0 && current.1 >= previous.1 && current.2 >= previous.2);
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn signed_and_offset_axes_are_inverse() {
+        for value in i16::MIN..=i16::MAX {
+            assert_eq!(offset_to_signed(signed_to_offset(value)), value);
+        }
+        assert_eq!(signed_to_offset(0), OFFSET_CENTER);
+        assert_eq!(signed_to_offset(i16::MIN), 0);
+        assert_eq!(signed_to_offset(i16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn rescale_rounds_and_saturates() {
+        assert_eq!(rescale(1023, 1023, 255), 255);
+        assert_eq!(rescale(512, 1023, 255), 128);
+        assert_eq!(rescale(2000, 1023, 255), 255);
+        assert_eq!(rescale(5, 0, 255), 0);
+        assert_eq!(rescale(0xFFFF, 0xFFFF, 0x3FF), 0x3FF);
+    }
+
+    #[test]
+    fn degrees_round_trip_within_range() {
+        for degrees in [-450.0, -90.0, 0.0, 45.5, 450.0] {
+            let axis = degrees_to_normalized(degrees, 900);
+            assert!((normalized_to_degrees(axis, 900) - degrees).abs() < 1e-3);
+        }
+        assert_eq!(degrees_to_normalized(900.0, 900), 1.0);
+        assert_eq!(degrees_to_normalized(10.0, 0), 0.0);
+        assert_eq!(degrees_to_normalized(f32::NAN, 900), 0.0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Pedal response curves
+//!
+//! `Linear`, `Squared` and `Cubed` are fixed shapes and `Custom` a lookup
+//! table spread evenly over the pedal travel. `Spline` is drawn through
+//! control points, `[input, output]` pairs in 0.0 - 1.0, as a monotone cubic
+//! (Fritsch-Carlson): it passes through every point and never overshoots
+//! between two of them, so rising points give a curve that never dips under
+//! the foot. Before the first point and after the last the output holds, so
+//! a first point at `[0.1, 0.0]` is also a deadzone.
+//!
+//! `InputTranslator` renders splines to a `Custom` table once when it is
+//! built, one entry per raw 8-bit pedal value, so translating stays a table
+//! lookup whatever the curve.
+
+use crate::config::{CurveType, PedalCurves};
+
+/// Entries of a rendered spline, one per raw pedal value
+pub const SPLINE_TABLE_LEN: usize = 256;
+
+impl CurveType {
+    /// Output for a pedal `input`, both 0.0 - 1.0
+    pub fn sample(&self, input: f32) -> f32 {
+        match self {
+            CurveType::Linear => input,
+            CurveType::Squared => input * input,
+            CurveType::Cubed => input * input * input,
+            CurveType::Custom(table) => lookup(table, input),
+            CurveType::Spline(points) => spline(points, input),
+        }
+    }
+
+    /// This curve with a spline rendered to a lookup table
+    pub fn rendered(&self) -> CurveType {
+        match self {
+            CurveType::Spline(points) => CurveType::Custom(
+                (0..SPLINE_TABLE_LEN)
+                    .map(|index| spline(points, index as f32 / (SPLINE_TABLE_LEN - 1) as f32))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+impl PedalCurves {
+    /// These curves with splines rendered to lookup tables
+    pub fn rendered(&self) -> Self {
+        Self {
+            throttle_curve: self.throttle_curve.rendered(),
+            brake_curve: self.brake_curve.rendered(),
+            clutch_curve: self.clutch_curve.rendered(),
+        }
+    }
+}
+
+/// Why `points` do not make a spline, `None` if they do
+pub fn spline_problem(points: &[[f32; 2]]) -> Option<&'static str> {
+    if points.len() < 2 {
+        Some("a spline needs at least two control points")
+    } else if points.iter().flatten().any(|value| !(0.0..=1.0).contains(value)) {
+        Some("control points must lie within 0.0 - 1.0")
+    } else if points.windows(2).any(|pair| pair[1][0] <= pair[0][0]) {
+        Some("control point inputs must rise from the first point to the last")
+    } else {
+        None
+    }
+}
+
+/// Linear interpolation in a table spread evenly over 0.0 - 1.0
+fn lookup(table: &[f32], input: f32) -> f32 {
+    let Some(&last) = table.last() else {
+        return input;
+    };
+    let position = input * (table.len() - 1) as f32;
+    let index = position as usize;
+    if index >= table.len() - 1 {
+        last
+    } else {
+        let frac = position - index as f32;
+        table[index] * (1.0 - frac) + table[index + 1] * frac
+    }
+}
+
+/// Monotone cubic through `points` at `input`
+fn spline(points: &[[f32; 2]], input: f32) -> f32 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return input;
+    };
+    if points.len() < 2 || input <= first[0] {
+        return first[1];
+    }
+    if input >= last[0] {
+        return last[1];
+    }
+
+    let tangents = tangents(points);
+    let segment = points.windows(2).position(|pair| input < pair[1][0]).unwrap_or(points.len() - 2);
+    let ([x0, y0], [x1, y1]) = (points[segment], points[segment + 1]);
+    let width = x1 - x0;
+    if width <= 0.0 {
+        return y1;
+    }
+    let t = (input - x0) / width;
+    let (t2, t3) = (t * t, t * t * t);
+    let value = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * width * tangents[segment]
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * width * tangents[segment + 1];
+    value.clamp(0.0, 1.0)
+}
+
+/// Slopes at each point, limited so no segment overshoots
+fn tangents(points: &[[f32; 2]]) -> Vec<f32> {
+    let slopes: Vec<f32> = points
+        .windows(2)
+        .map(|pair| {
+            let width = pair[1][0] - pair[0][0];
+            if width > 0.0 { (pair[1][1] - pair[0][1]) / width } else { 0.0 }
+        })
+        .collect();
+
+    let mut tangents = vec![0.0; points.len()];
+    tangents[0] = slopes[0];
+    tangents[points.len() - 1] = slopes[slopes.len() - 1];
+    for (index, pair) in slopes.windows(2).enumerate() {
+        // Flat at a peak or a valley, the average slope elsewhere
+        tangents[index + 1] = if pair[0] * pair[1] <= 0.0 { 0.0 } else { (pair[0] + pair[1]) / 2.0 };
+    }
+    for (index, &slope) in slopes.iter().enumerate() {
+        if slope == 0.0 {
+            tangents[index] = 0.0;
+            tangents[index + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (tangents[index] / slope, tangents[index + 1] / slope);
+        let length = (a * a + b * b).sqrt();
+        if length > 3.0 {
+            tangents[index] = 3.0 / length * a * slope;
+            tangents[index + 1] = 3.0 / length * b * slope;
+        }
+    }
+    tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spline_passes_through_points_without_overshoot() {
+        let points = vec![[0.1, 0.0], [0.5, 0.2], [0.6, 0.9], [1.0, 1.0]];
+        assert_eq!(spline_problem(&points), None);
+        let curve = CurveType::Spline(points.clone());
+        for [input, output] in &points {
+            assert!((curve.sample(*input) - output).abs() < 1e-5);
+        }
+        // Held flat before the first point, a deadzone
+        assert_eq!(curve.sample(0.05), 0.0);
+
+        let CurveType::Custom(table) = curve.rendered() else {
+            panic!("a spline renders to a table");
+        };
+        assert_eq!(table.len(), SPLINE_TABLE_LEN);
+        assert!(table.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(spline_problem(&[[0.5, 0.0], [0.2, 1.0]]).is_some());
+    }
+}
@@ -0,0 +1,385 @@
+//! Aggregation of separate input devices into the wheel report
+//!
+//! Pedals, shifters and handbrakes that enumerate as their own HID devices
+//! are each read on a dedicated thread. A reader thread publishes the latest
+//! state into a lock-free `StateCell`, and the input loop merges whatever the
+//! cells hold into the wheel report right before translation. A burst of
+//! wheel reports therefore never delays a pedal or handbrake update, and a
+//! slow auxiliary device never stalls the wheel. Configured pedal sets are
+//! looked for again every few seconds, so a set swapped in mid-session takes
+//! over the pedal axes with its own calibration. A TH8A shifter is found the
+//! same way; its gear presses G29 buttons, so `shifter_buttons` is applied
+//! after translation instead. A co-driver input, e.g. an instructor's
+//! gamepad, is blended in last. An event-driven input loop
+//! waits on `changed` to pick up auxiliary updates between wheel reports.
+
+use crate::config::{CoDriverConfig, Config, PedalSetConfig, ShifterConfig};
+use crate::device::codriver::{CoDriver, CoDriverState};
+use crate::device::handbrake::{HandbrakeMode, HandbrakeState, TssHandbrake};
+use crate::device::pedals::{PedalSet, PedalSetState};
+use crate::device::shifter::{ShifterState, Th8aShifter};
+use crate::device::ThrustmasterInputReport;
+use crate::error::Result;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// How long a reader thread blocks before checking for shutdown
+const READ_TIMEOUT_MS: i32 = 50;
+
+/// How often configured pedal sets, the shifter and the co-driver input are looked for while not connected
+const DEVICE_RESCAN: Duration = Duration::from_secs(2);
+
+/// Device state that fits into a single atomic word
+pub trait PackedState: Copy + Default {
+    fn pack(self) -> u64;
+    fn unpack(bits: u64) -> Self;
+}
+
+impl PackedState for HandbrakeState {
+    fn pack(self) -> u64 {
+        self.position as u64 | (self.upshift as u64) << 8 | (self.downshift as u64) << 9
+    }
+
+    fn unpack(bits: u64) -> Self {
+        Self {
+            position: bits as u8,
+            upshift: bits & (1 << 8) != 0,
+            downshift: bits & (1 << 9) != 0,
+        }
+    }
+}
+
+/// Latest state of one device, written by its reader thread
+///
+/// The generation counts stores, so the input loop can tell whether anything
+/// changed since it last looked without comparing states.
+pub struct StateCell<T> {
+    bits: AtomicU64,
+    generation: AtomicU64,
+    _state: PhantomData<T>,
+}
+
+impl<T: PackedState> StateCell<T> {
+    pub fn new() -> Self {
+        Self {
+            bits: AtomicU64::new(T::default().pack()),
+            generation: AtomicU64::new(0),
+            _state: PhantomData,
+        }
+    }
+
+    pub fn store(&self, state: T) {
+        self.bits.store(state.pack(), Ordering::Release);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn load(&self) -> T {
+        T::unpack(self.bits.load(Ordering::Acquire))
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+}
+
+impl<T: PackedState> Default for StateCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackedState for PedalSetState {
+    fn pack(self) -> u64 {
+        self.throttle as u64
+            | (self.brake as u64) << 8
+            | (self.clutch as u64) << 16
+            | (self.connected as u64) << 24
+            | (self.driven as u64) << 32
+    }
+
+    fn unpack(bits: u64) -> Self {
+        Self {
+            throttle: bits as u8,
+            brake: (bits >> 8) as u8,
+            clutch: (bits >> 16) as u8,
+            connected: bits & (1 << 24) != 0,
+            driven: (bits >> 32) as u8,
+        }
+    }
+}
+
+impl PackedState for ShifterState {
+    fn pack(self) -> u64 {
+        self.gear as u64
+    }
+
+    fn unpack(bits: u64) -> Self {
+        Self { gear: bits as u8 }
+    }
+}
+
+impl PackedState for CoDriverState {
+    fn pack(self) -> u64 {
+        self.steering as u64
+            | (self.throttle as u64) << 8
+            | (self.brake as u64) << 16
+            | (self.connected as u64) << 24
+            | (self.override_held as u64) << 25
+    }
+
+    fn unpack(bits: u64) -> Self {
+        Self {
+            steering: bits as u8,
+            throttle: (bits >> 8) as u8,
+            brake: (bits >> 16) as u8,
+            connected: bits & (1 << 24) != 0,
+            override_held: bits & (1 << 25) != 0,
+        }
+    }
+}
+
+/// Auxiliary input devices read on their own threads
+#[derive(Default)]
+pub struct AuxiliaryInputs {
+    handbrake: Option<(HandbrakeMode, Arc<StateCell<HandbrakeState>>)>,
+    pedal_set: Option<Arc<StateCell<PedalSetState>>>,
+    shifter: Option<Arc<StateCell<ShifterState>>>,
+    codriver: Option<Arc<StateCell<CoDriverState>>>,
+    changed: Arc<Notify>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl AuxiliaryInputs {
+    /// Open the enabled auxiliary devices and start their reader threads
+    ///
+    /// Devices that are missing or fail to open are logged and left out, the
+    /// wheel works without them.
+    pub fn spawn(config: &Config) -> Self {
+        let mut inputs = Self::default();
+
+        if config.handbrake_config.enabled {
+            match TssHandbrake::detect(&config.handbrake_config) {
+                Ok(Some(handbrake)) => inputs.spawn_handbrake(handbrake),
+                Ok(None) => tracing::info!("TSS handbrake enabled but not found"),
+                Err(e) => tracing::warn!("Cannot open the TSS handbrake: {}", e),
+            }
+        }
+
+        if !config.pedal_sets.is_empty() {
+            inputs.spawn_pedal_sets(config.pedal_sets.clone());
+        }
+
+        if config.shifter_config.enabled {
+            inputs.spawn_shifter(config.shifter_config.clone());
+        }
+
+        if config.codriver_config.enabled {
+            inputs.spawn_codriver(config.codriver_config.clone());
+        }
+
+        inputs
+    }
+
+    fn spawn_pedal_sets(&mut self, pedal_sets: Vec<PedalSetConfig>) {
+        self.pedal_set = self.spawn_reader(
+            "pedal set",
+            move || PedalSet::detect(&pedal_sets),
+            PedalSet::read,
+            PedalSetState::default(),
+        );
+    }
+
+    fn spawn_shifter(&mut self, config: ShifterConfig) {
+        self.shifter = self.spawn_reader(
+            "shifter",
+            move || Th8aShifter::detect(&config),
+            Th8aShifter::read,
+            ShifterState::default(),
+        );
+    }
+
+    fn spawn_codriver(&mut self, config: CoDriverConfig) {
+        self.codriver = self.spawn_reader(
+            "co-driver input",
+            move || CoDriver::detect(&config),
+            CoDriver::read,
+            CoDriverState::default(),
+        );
+    }
+
+    /// Start a thread publishing what `read` returns from the device found by `detect`
+    ///
+    /// While no device is connected `detect` is retried every `DEVICE_RESCAN`.
+    /// A device that fails to read publishes `default` and is looked for again.
+    fn spawn_reader<D, S>(
+        &mut self,
+        name: &'static str,
+        mut detect: impl FnMut() -> Result<Option<D>> + Send + 'static,
+        read: fn(&mut D, i32) -> Result<Option<S>>,
+        default: S,
+    ) -> Option<Arc<StateCell<S>>>
+    where
+        D: 'static,
+        S: PackedState + Send + Sync + 'static,
+    {
+        let cell = Arc::new(StateCell::new());
+        let stop = self.stop.clone();
+        let changed = self.changed.clone();
+        let writer = cell.clone();
+
+        let thread_name = format!("tm-g29-{}", name.replace(' ', "-"));
+        let thread = std::thread::Builder::new().name(thread_name).spawn(move || {
+            let mut device: Option<D> = None;
+            let mut next_scan = Instant::now();
+            while !stop.load(Ordering::Relaxed) {
+                let Some(connected) = device.as_mut() else {
+                    if Instant::now() >= next_scan {
+                        next_scan = Instant::now() + DEVICE_RESCAN;
+                        match detect() {
+                            Ok(found) => device = found,
+                            Err(e) => tracing::debug!("Cannot look for the {}: {}", name, e),
+                        }
+                    }
+                    if device.is_none() {
+                        std::thread::sleep(Duration::from_millis(READ_TIMEOUT_MS as u64));
+                    }
+                    continue;
+                };
+
+                match read(connected, READ_TIMEOUT_MS) {
+                    Ok(Some(state)) => {
+                        writer.store(state);
+                        changed.notify_one();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Lost the {}, back to its default state: {}", name, e);
+                        device = None;
+                        writer.store(default);
+                        changed.notify_one();
+                    }
+                }
+            }
+        });
+
+        match thread {
+            Ok(thread) => {
+                self.threads.push(thread);
+                Some(cell)
+            }
+            Err(e) => {
+                tracing::warn!("Cannot start the {} thread: {}", name, e);
+                None
+            }
+        }
+    }
+
+    fn spawn_handbrake(&mut self, mut handbrake: TssHandbrake) {
+        let mode = handbrake.mode();
+        let cell = Arc::new(StateCell::new());
+        let stop = self.stop.clone();
+        let changed = self.changed.clone();
+        let writer = cell.clone();
+
+        let thread = std::thread::Builder::new().name("tm-g29-tss".into()).spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match handbrake.read(READ_TIMEOUT_MS) {
+                    Ok(Some(state)) => {
+                        writer.store(state);
+                        changed.notify_one();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("TSS handbrake read failed, ignoring it from now on: {}", e);
+                        writer.store(HandbrakeState::default());
+                        changed.notify_one();
+                        break;
+                    }
+                }
+            }
+        });
+
+        match thread {
+            Ok(thread) => {
+                self.handbrake = Some((mode, cell));
+                self.threads.push(thread);
+            }
+            Err(e) => tracing::warn!("Cannot start the TSS reader thread: {}", e),
+        }
+    }
+
+    /// Changes whenever any auxiliary device reports
+    pub fn generation(&self) -> u64 {
+        let handbrake = self.handbrake.as_ref().map_or(0, |(_, cell)| cell.generation());
+        let pedal_set = self.pedal_set.as_ref().map_or(0, |cell| cell.generation());
+        let shifter = self.shifter.as_ref().map_or(0, |cell| cell.generation());
+        let codriver = self.codriver.as_ref().map_or(0, |cell| cell.generation());
+        handbrake
+            .wrapping_add(pedal_set)
+            .wrapping_add(shifter)
+            .wrapping_add(codriver)
+    }
+
+    /// Notified after any auxiliary device reports
+    pub fn changed(&self) -> Arc<Notify> {
+        self.changed.clone()
+    }
+
+    /// Fold the latest auxiliary states into a wheel report
+    pub fn merge_into(&self, report: &mut ThrustmasterInputReport, config: &Config) {
+        // Pedals first, an analog handbrake on the clutch combines with them
+        if let Some(cell) = &self.pedal_set {
+            cell.load().merge_into(report);
+        }
+        if let Some((mode, cell)) = &self.handbrake {
+            cell.load().merge_into(report, *mode, &config.handbrake_config);
+        }
+        // Last, so the co-driver blends with every pedal source
+        if let Some(cell) = &self.codriver {
+            cell.load().merge_into(report, &config.codriver_config);
+        }
+    }
+
+    /// G29 buttons pressed by the shifter's gear, OR-ed into the translated report
+    pub fn shifter_buttons(&self, config: &Config) -> u32 {
+        self.shifter
+            .as_ref()
+            .map_or(0, |cell| cell.load().g29_buttons(&config.shifter_config))
+    }
+}
+
+impl Drop for AuxiliaryInputs {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_cell_round_trips_and_counts_stores() {
+        let cell = StateCell::<HandbrakeState>::new();
+        assert_eq!(cell.load(), HandbrakeState::default());
+        assert_eq!(cell.generation(), 0);
+
+        let state = HandbrakeState {
+            position: 200,
+            upshift: false,
+            downshift: true,
+        };
+        cell.store(state);
+        cell.store(state);
+        assert_eq!(cell.load(), state);
+        assert_eq!(cell.generation(), 2);
+    }
+}
@@ -0,0 +1,128 @@
+//! Device roles the translator talks to
+//!
+//! `ProtocolTranslator` needs two things: a source wheel it reads reports
+//! from and sends IFORCE commands to, and a virtual wheel it feeds G29
+//! reports to and reads the game's output reports from. It only knows these
+//! traits, so another source wheel or virtual target is a new implementation
+//! here rather than a change to the orchestrator. `ThrustmasterDevice` and
+//! `VirtualG29Device` are the hardware backends; the scripted devices in
+//! `device::mock` run the whole pipeline in tests without hardware.
+//!
+//! Methods return `Send` futures so the translation loops can keep running
+//! on the multi-threaded runtime whatever the backend.
+
+use crate::config::{G29Config, ThrustmasterConfig};
+use crate::device::models::ThrustmasterModel;
+use crate::device::source::InputEvents;
+use crate::device::{
+    G29InputReport, G29OutputReport, IforceCommand, StrategySelection, ThrustmasterDevice, ThrustmasterInputReport,
+    VirtualG29Device,
+};
+use crate::error::Result;
+use crate::ffb::FfbCapabilities;
+use std::future::Future;
+
+/// Source wheel: reports in, FFB and wheel settings out
+pub trait SourceWheel: Send + Sync + Sized + 'static {
+    /// Open the wheel `config` selects
+    fn open(config: &ThrustmasterConfig) -> impl Future<Output = Result<Self>> + Send;
+
+    /// Bring the wheel to a known state before translating, `steering_range` in degrees
+    fn initialize(&self, steering_range: u16) -> impl Future<Output = Result<()>> + Send;
+
+    /// Model whose report layout and limits apply
+    fn model(&self) -> ThrustmasterModel;
+
+    fn serial_number(&self) -> Option<&str>;
+
+    /// Effect kinds the base renders natively
+    fn ffb_capabilities(&self) -> &FfbCapabilities;
+
+    /// Read path in use
+    fn read_strategy(&self) -> &StrategySelection;
+
+    /// Reports pushed as they arrive, `None` when the device is polled with `read_input`
+    fn take_input_events(&mut self) -> Option<InputEvents>;
+
+    /// Next report, `None` if nothing is pending
+    fn read_input(&self) -> impl Future<Output = Result<Option<ThrustmasterInputReport>>> + Send;
+
+    fn send_ffb_command(&self, command: IforceCommand) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set the rotation range, returning the degrees applied
+    fn set_range(&self, degrees: u16) -> impl Future<Output = Result<u16>> + Send;
+
+    /// Light the rev LEDs in `mask` at `brightness` (0.0 - 1.0)
+    fn set_leds(&self, mask: u8, brightness: f32) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Virtual wheel the game sees: G29 reports out, the game's output reports in
+pub trait VirtualWheel: Send + Sync + Sized + 'static {
+    /// Create the virtual wheel the game will see
+    fn create(config: &G29Config) -> impl Future<Output = Result<Self>> + Send;
+
+    fn send_input(&self, report: G29InputReport) -> impl Future<Output = Result<()>> + Send;
+
+    /// Next output report from the game, `None` if nothing is pending
+    fn read_output(&self) -> impl Future<Output = Result<Option<G29OutputReport>>> + Send;
+}
+
+impl SourceWheel for ThrustmasterDevice {
+    async fn open(config: &ThrustmasterConfig) -> Result<Self> {
+        ThrustmasterDevice::open(config).await
+    }
+
+    async fn initialize(&self, steering_range: u16) -> Result<()> {
+        ThrustmasterDevice::initialize(self, steering_range).await
+    }
+
+    fn model(&self) -> ThrustmasterModel {
+        ThrustmasterDevice::model(self)
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        ThrustmasterDevice::serial_number(self)
+    }
+
+    fn ffb_capabilities(&self) -> &FfbCapabilities {
+        ThrustmasterDevice::ffb_capabilities(self)
+    }
+
+    fn read_strategy(&self) -> &StrategySelection {
+        ThrustmasterDevice::read_strategy(self)
+    }
+
+    fn take_input_events(&mut self) -> Option<InputEvents> {
+        ThrustmasterDevice::take_input_events(self)
+    }
+
+    async fn read_input(&self) -> Result<Option<ThrustmasterInputReport>> {
+        ThrustmasterDevice::read_input(self).await
+    }
+
+    async fn send_ffb_command(&self, command: IforceCommand) -> Result<()> {
+        ThrustmasterDevice::send_ffb_command(self, command).await
+    }
+
+    async fn set_range(&self, degrees: u16) -> Result<u16> {
+        ThrustmasterDevice::set_range(self, degrees).await
+    }
+
+    async fn set_leds(&self, mask: u8, brightness: f32) -> Result<()> {
+        ThrustmasterDevice::set_leds(self, mask, brightness).await
+    }
+}
+
+impl VirtualWheel for VirtualG29Device {
+    async fn create(config: &G29Config) -> Result<Self> {
+        VirtualG29Device::create(config).await
+    }
+
+    async fn send_input(&self, report: G29InputReport) -> Result<()> {
+        VirtualG29Device::send_input(self, report).await
+    }
+
+    async fn read_output(&self) -> Result<Option<G29OutputReport>> {
+        VirtualG29Device::read_output(self).await
+    }
+}
@@ -0,0 +1,137 @@
+//! Second input blended with the wheel, e.g. an instructor's gamepad
+//!
+//! Coaching rigs and accessibility setups let a second person steer and
+//! drive alongside the wheel. The co-driver device is any HID gamepad or
+//! controller with 8-bit axes; its steering and throttle are mixed into the
+//! wheel report by a configurable weight, and either person's brake counts.
+//! While the co-driver holds the override button they take full control.
+//! The aggregation layer reads it on its own thread like the other
+//! auxiliary devices.
+
+use crate::config::CoDriverConfig;
+use crate::device::ThrustmasterInputReport;
+use crate::error::{Result, TranslatorError};
+use hidapi::{HidApi, HidDevice};
+
+/// Largest co-driver report read
+const CODRIVER_REPORT_LEN: usize = 64;
+
+/// Latest co-driver state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoDriverState {
+    /// Cleared when the device is unplugged, the wheel drives alone again
+    pub connected: bool,
+    pub steering: u8,   // 128 = center
+    pub throttle: u8,
+    pub brake: u8,
+    pub override_held: bool,
+}
+
+impl CoDriverState {
+    /// Mix this state into a wheel report as configured
+    pub fn merge_into(&self, report: &mut ThrustmasterInputReport, config: &CoDriverConfig) {
+        if !self.connected {
+            return;
+        }
+        let weight = if self.override_held { 1.0 } else { config.weight.clamp(0.0, 1.0) };
+        let blend = |wheel: f32, codriver: f32| wheel + (codriver - wheel) * weight;
+
+        let steering = (self.steering as f32 - 128.0) * 256.0;
+        report.steering = blend(report.steering as f32, steering).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        report.throttle = blend(report.throttle as f32, self.throttle as f32).round() as u8;
+        // Either driver can always brake
+        report.brake = if self.override_held { self.brake } else { report.brake.max(self.brake) };
+    }
+}
+
+pub struct CoDriver {
+    device: HidDevice,
+    config: CoDriverConfig,
+}
+
+impl CoDriver {
+    /// Find and open the co-driver device, `Ok(None)` when it is not plugged in
+    pub fn detect(config: &CoDriverConfig) -> Result<Option<Self>> {
+        let api = HidApi::new()?;
+
+        let found = api.device_list().find(|dev| {
+            dev.vendor_id() == config.vid
+                && dev.product_id() == config.pid
+                && config.serial_number.as_deref().map_or(true, |serial| dev.serial_number() == Some(serial))
+        });
+        let Some(info) = found else {
+            return Ok(None);
+        };
+
+        let device = info.open_device(&api)?;
+        tracing::info!("Found co-driver input {}", info.product_string().unwrap_or("device"));
+
+        Ok(Some(Self { device, config: config.clone() }))
+    }
+
+    /// Wait up to `timeout_ms` for the next report, `Ok(None)` on timeout
+    pub fn read(&mut self, timeout_ms: i32) -> Result<Option<CoDriverState>> {
+        let mut buf = [0u8; CODRIVER_REPORT_LEN];
+        match self.device.read_timeout(&mut buf, timeout_ms)? {
+            0 => Ok(None),
+            n => parse_codriver_report(&buf[..n], &self.config).map(Some),
+        }
+    }
+}
+
+/// Read the axes and the override button of a co-driver report
+///
+/// `axis_bytes` index the steering, throttle and brake bytes; the override
+/// button is a bit index into the report after its first byte.
+pub fn parse_codriver_report(data: &[u8], config: &CoDriverConfig) -> Result<CoDriverState> {
+    let byte = |offset: usize| {
+        data.get(offset)
+            .copied()
+            .ok_or_else(|| TranslatorError::invalid_report(format!("Co-driver report too short: {} bytes", data.len())))
+    };
+    let override_held = match config.override_button {
+        Some(bit) => byte(1 + bit as usize / 8)? & (1 << (bit % 8)) != 0,
+        None => false,
+    };
+    Ok(CoDriverState {
+        connected: true,
+        steering: byte(config.axis_bytes[0] as usize)?,
+        throttle: byte(config.axis_bytes[1] as usize)?,
+        brake: byte(config.axis_bytes[2] as usize)?,
+        override_held,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::HatDirection;
+
+    fn wheel_report() -> ThrustmasterInputReport {
+        ThrustmasterInputReport { steering: 8192, throttle: 200, brake: 0, clutch: 0, buttons: 0, dpad: HatDirection::Centered, base_range: None, rim: None }
+    }
+
+    #[test]
+    fn test_codriver_is_blended_by_weight_or_overrides() {
+        let config = CoDriverConfig { weight: 0.5, override_button: Some(9), ..CoDriverConfig::default() };
+
+        // Steering full left, no throttle, a little brake
+        let state = parse_codriver_report(&[0x01, 0x00, 0x00, 0x00, 0, 0, 40], &config).unwrap();
+        assert!(!state.override_held);
+        let mut report = wheel_report();
+        state.merge_into(&mut report, &config);
+        assert_eq!(report.steering, (8192 - 32768) / 2);
+        assert_eq!((report.throttle, report.brake), (100, 40));
+
+        let state = parse_codriver_report(&[0x01, 0x00, 0x02, 0x00, 0, 0, 40], &config).unwrap();
+        assert!(state.override_held);
+        let mut report = wheel_report();
+        state.merge_into(&mut report, &config);
+        assert_eq!((report.steering, report.throttle, report.brake), (-32768, 0, 40));
+
+        let mut report = wheel_report();
+        CoDriverState::default().merge_into(&mut report, &config);
+        assert_eq!(report.steering, 8192);
+        assert!(parse_codriver_report(&[0x01, 0x00], &config).is_err());
+    }
+}
@@ -185,25 +185,377 @@ pub const G29_HID_DESCRIPTOR: &[u8] = &[
     0xC0,              // End Collection
 ];
 
+/// Logitech G29 HID Report Descriptor for PS4 mode
+/// DualShock 4 style layout: sticks, hat, face buttons, triggers and the
+/// touchpad/motion block, followed by the 16-bit wheel and pedal axes.
+pub const G29_PS4_HID_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x05,        // Usage (Game Pad)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, 0x01,        //   Report ID (1)
+    0x09, 0x30,        //   Usage (X)
+    0x09, 0x31,        //   Usage (Y)
+    0x09, 0x32,        //   Usage (Z)
+    0x09, 0x35,        //   Usage (Rz)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x75, 0x08,        //   Report Size (8)
+    0x95, 0x04,        //   Report Count (4)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x09, 0x39,        //   Usage (Hat switch)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x07,        //   Logical Maximum (7)
+    0x35, 0x00,        //   Physical Minimum (0)
+    0x46, 0x3B, 0x01,  //   Physical Maximum (315)
+    0x65, 0x14,        //   Unit (System: English Rotation, Length: Centimeter)
+    0x75, 0x04,        //   Report Size (4)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x42,        //   Input (Data,Var,Abs,Null State)
+    0x65, 0x00,        //   Unit (None)
+    0x05, 0x09,        //   Usage Page (Button)
+    0x19, 0x01,        //   Usage Minimum (0x01)
+    0x29, 0x0E,        //   Usage Maximum (0x0E)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x75, 0x01,        //   Report Size (1)
+    0x95, 0x0E,        //   Report Count (14)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x06, 0x00, 0xFF,  //   Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x20,        //   Usage (0x20) - report counter
+    0x75, 0x06,        //   Report Size (6)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x05, 0x01,        //   Usage Page (Generic Desktop Ctrls)
+    0x09, 0x33,        //   Usage (Rx)
+    0x09, 0x34,        //   Usage (Ry)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x75, 0x08,        //   Report Size (8)
+    0x95, 0x02,        //   Report Count (2)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x06, 0x00, 0xFF,  //   Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x21,        //   Usage (0x21) - touchpad and motion data
+    0x95, 0x2E,        //   Report Count (46)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x05, 0x02,        //   Usage Page (Simulation Controls)
+    0x09, 0xC8,        //   Usage (Steering)
+    0x09, 0xC4,        //   Usage (Accelerator)
+    0x09, 0xC5,        //   Usage (Brake)
+    0x09, 0xC6,        //   Usage (Clutch)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x27, 0xFF, 0xFF, 0x00, 0x00,  //   Logical Maximum (65535)
+    0x75, 0x10,        //   Report Size (16)
+    0x95, 0x04,        //   Report Count (4)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0xC0,              // End Collection
+];
+
+/// Logitech G27 HID Report Descriptor, native mode
+/// No report ID: hat, 22 buttons, 14-bit steering, 8-bit pedals and three
+/// vendor bytes, 11 bytes in all.
+pub const G27_HID_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x04,        // Usage (Joystick)
+    0xA1, 0x01,        // Collection (Application)
+    0x09, 0x39,        //   Usage (Hat switch)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x07,        //   Logical Maximum (7)
+    0x35, 0x00,        //   Physical Minimum (0)
+    0x46, 0x3B, 0x01,  //   Physical Maximum (315)
+    0x65, 0x14,        //   Unit (System: English Rotation, Length: Centimeter)
+    0x75, 0x04,        //   Report Size (4)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x42,        //   Input (Data,Var,Abs,Null State)
+    0x65, 0x00,        //   Unit (None)
+    0x05, 0x09,        //   Usage Page (Button)
+    0x19, 0x01,        //   Usage Minimum (0x01)
+    0x29, 0x16,        //   Usage Maximum (0x16)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x75, 0x01,        //   Report Size (1)
+    0x95, 0x16,        //   Report Count (22)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x05, 0x01,        //   Usage Page (Generic Desktop Ctrls)
+    0x09, 0x30,        //   Usage (X)
+    0x26, 0xFF, 0x3F,  //   Logical Maximum (16383)
+    0x75, 0x0E,        //   Report Size (14)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x09, 0x31,        //   Usage (Y)
+    0x09, 0x32,        //   Usage (Z)
+    0x09, 0x35,        //   Usage (Rz)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x75, 0x08,        //   Report Size (8)
+    0x95, 0x03,        //   Report Count (3)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x06, 0x00, 0xFF,  //   Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01,        //   Usage (0x01) - shifter position and status
+    0x95, 0x03,        //   Report Count (3)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0xC0,              // End Collection
+];
+
+/// Logitech Driving Force GT HID Report Descriptor, native mode
+/// No report ID: hat, 21 buttons, 14-bit steering, 8-bit throttle and brake
+/// (no clutch) and a vendor byte, 8 bytes in all.
+pub const DFGT_HID_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x04,        // Usage (Joystick)
+    0xA1, 0x01,        // Collection (Application)
+    0x09, 0x39,        //   Usage (Hat switch)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x07,        //   Logical Maximum (7)
+    0x35, 0x00,        //   Physical Minimum (0)
+    0x46, 0x3B, 0x01,  //   Physical Maximum (315)
+    0x65, 0x14,        //   Unit (System: English Rotation, Length: Centimeter)
+    0x75, 0x04,        //   Report Size (4)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x42,        //   Input (Data,Var,Abs,Null State)
+    0x65, 0x00,        //   Unit (None)
+    0x05, 0x09,        //   Usage Page (Button)
+    0x19, 0x01,        //   Usage Minimum (0x01)
+    0x29, 0x15,        //   Usage Maximum (0x15)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x75, 0x01,        //   Report Size (1)
+    0x95, 0x15,        //   Report Count (21)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x03,        //   Input (Const,Var,Abs)
+    0x05, 0x01,        //   Usage Page (Generic Desktop Ctrls)
+    0x09, 0x30,        //   Usage (X)
+    0x26, 0xFF, 0x3F,  //   Logical Maximum (16383)
+    0x75, 0x0E,        //   Report Size (14)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x09, 0x31,        //   Usage (Y)
+    0x09, 0x32,        //   Usage (Z)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x75, 0x08,        //   Report Size (8)
+    0x95, 0x02,        //   Report Count (2)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x06, 0x00, 0xFF,  //   Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01,        //   Usage (0x01) - status
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0xC0,              // End Collection
+];
+
+/// Logitech G920 HID Report Descriptor, PC mode
+/// Xbox style: 11 buttons, hat, then 16-bit steering and pedals.
+pub const G920_HID_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x04,        // Usage (Joystick)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, 0x01,        //   Report ID (1)
+    0x05, 0x09,        //   Usage Page (Button)
+    0x19, 0x01,        //   Usage Minimum (0x01)
+    0x29, 0x0B,        //   Usage Maximum (0x0B)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x75, 0x01,        //   Report Size (1)
+    0x95, 0x0B,        //   Report Count (11)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x03,        //   Input (Const,Var,Abs)
+    0x05, 0x01,        //   Usage Page (Generic Desktop Ctrls)
+    0x09, 0x39,        //   Usage (Hat switch)
+    0x25, 0x07,        //   Logical Maximum (7)
+    0x35, 0x00,        //   Physical Minimum (0)
+    0x46, 0x3B, 0x01,  //   Physical Maximum (315)
+    0x65, 0x14,        //   Unit (System: English Rotation, Length: Centimeter)
+    0x75, 0x04,        //   Report Size (4)
+    0x95, 0x01,        //   Report Count (1)
+    0x81, 0x42,        //   Input (Data,Var,Abs,Null State)
+    0x65, 0x00,        //   Unit (None)
+    0x09, 0x30,        //   Usage (X)
+    0x09, 0x31,        //   Usage (Y)
+    0x09, 0x32,        //   Usage (Z)
+    0x09, 0x35,        //   Usage (Rz)
+    0x27, 0xFF, 0xFF, 0x00, 0x00,  //   Logical Maximum (65535)
+    0x75, 0x10,        //   Report Size (16)
+    0x95, 0x04,        //   Report Count (4)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0xC0,              // End Collection
+];
+
+pub const USAGE_PAGE_GENERIC_DESKTOP: u16 = 0x01;
+pub const USAGE_PAGE_SIMULATION: u16 = 0x02;
+pub const USAGE_PAGE_BUTTON: u16 = 0x09;
+pub const USAGE_PAGE_PID: u16 = 0x0F;
+
+/// A single input value described by a report descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HidField {
+    pub report_id: u8,
+    pub usage_page: u16,
+    pub usage: u16,
+    pub bit_offset: usize,   // From the start of the report, excluding the report ID byte
+    pub bit_size: usize,
+    pub logical_min: i32,
+    pub logical_max: i32,
+    pub constant: bool,
+}
+
 /// Parse HID descriptor and extract key information
+///
+/// Walks the short items of the descriptor tracking global and local state,
+/// and lays out every Input field so report sizes are derived from the
+/// descriptor itself rather than assumed.
 pub fn parse_hid_descriptor(descriptor: &[u8]) -> Result<HidDescriptorInfo, crate::error::TranslatorError> {
     // Basic validation
     if descriptor.is_empty() {
         return Err(crate::error::TranslatorError::invalid_report("Empty HID descriptor"));
     }
-    
-    // For now, return static info for G29 descriptor
-    // In a full implementation, this would parse the descriptor bytes
+
+    let mut usage_page = 0u16;
+    let mut logical_min = 0i32;
+    let mut logical_max = 0i32;
+    let mut report_size = 0usize;
+    let mut report_count = 0usize;
+    let mut report_id = 0u8;
+    let mut usages: Vec<(u16, u16)> = Vec::new();
+    let mut usage_range: (Option<u32>, Option<u32>) = (None, None);
+
+    let mut report_ids = Vec::new();
+    let mut input_bits: std::collections::BTreeMap<u8, usize> = Default::default();
+    let mut output_bits: std::collections::BTreeMap<u8, usize> = Default::default();
+    let mut fields = Vec::new();
+    let mut has_ffb = false;
+    let mut pid_usages = Vec::new();
+
+    let mut pos = 0;
+    while pos < descriptor.len() {
+        let prefix = descriptor[pos];
+
+        // Long items carry their size in the next byte and are never used by wheels
+        if prefix == 0xFE {
+            let size = *descriptor.get(pos + 1).unwrap_or(&0) as usize;
+            pos += 3 + size;
+            continue;
+        }
+
+        let size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let data = descriptor.get(pos + 1..pos + 1 + size).ok_or_else(|| {
+            crate::error::TranslatorError::invalid_report(format!("Truncated HID item at offset {}", pos))
+        })?;
+        pos += 1 + size;
+
+        let unsigned = data.iter().rev().fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+        let signed = match size {
+            1 => data[0] as i8 as i32,
+            2 => i16::from_le_bytes([data[0], data[1]]) as i32,
+            4 => unsigned as i32,
+            _ => 0,
+        };
+
+        match prefix & 0xFC {
+            // Global items
+            0x04 => {
+                usage_page = unsigned as u16;
+                has_ffb |= usage_page == USAGE_PAGE_PID;
+            }
+            0x14 => logical_min = signed,
+            // Logical maximum is signed, but 1- and 2-byte maxima are routinely
+            // written without a sign byte (0x26 0xFF 0x00), treat them as unsigned
+            0x24 => logical_max = if signed < logical_min { unsigned as i32 } else { signed },
+            0x74 => report_size = unsigned as usize,
+            0x94 => report_count = unsigned as usize,
+            0x84 => {
+                report_id = unsigned as u8;
+                if !report_ids.contains(&report_id) {
+                    report_ids.push(report_id);
+                }
+            }
+            // Local items, 4-byte usages carry their own usage page
+            0x08 => {
+                let usage = extended_usage(usage_page, unsigned, size);
+                if usage.0 == USAGE_PAGE_PID && !pid_usages.contains(&usage.1) {
+                    pid_usages.push(usage.1);
+                }
+                usages.push(usage);
+            }
+            0x18 => usage_range.0 = Some(unsigned),
+            0x28 => usage_range.1 = Some(unsigned),
+            // Main items
+            0x80 => {
+                let offset = input_bits.entry(report_id).or_insert(0);
+                let constant = unsigned & 0x01 != 0;
+                for index in 0..report_count {
+                    let (page, usage) = if let Some(&usage) = usages.get(index).or(usages.last()) {
+                        usage
+                    } else if let (Some(min), Some(_)) = usage_range {
+                        (usage_page, (min as usize + index) as u16)
+                    } else {
+                        (usage_page, 0)
+                    };
+                    fields.push(HidField {
+                        report_id,
+                        usage_page: page,
+                        usage,
+                        bit_offset: *offset,
+                        bit_size: report_size,
+                        logical_min,
+                        logical_max,
+                        constant,
+                    });
+                    *offset += report_size;
+                }
+                usages.clear();
+                usage_range = (None, None);
+            }
+            0x90 => {
+                *output_bits.entry(report_id).or_insert(0) += report_size * report_count;
+                usages.clear();
+                usage_range = (None, None);
+            }
+            0xA0 | 0xB0 | 0xC0 => {
+                usages.clear();
+                usage_range = (None, None);
+            }
+            _ => {}
+        }
+    }
+
+    let id_byte = usize::from(!report_ids.is_empty());
+    let bytes = |bits: &usize| id_byte + (bits + 7) / 8;
+
+    let button_count = fields
+        .iter()
+        .filter(|f| f.usage_page == USAGE_PAGE_BUTTON && !f.constant)
+        .count();
+    let axis_count = fields
+        .iter()
+        .filter(|f| {
+            !f.constant
+                && ((f.usage_page == USAGE_PAGE_GENERIC_DESKTOP && (0x30..=0x36).contains(&f.usage))
+                    || f.usage_page == USAGE_PAGE_SIMULATION)
+        })
+        .count();
+
     Ok(HidDescriptorInfo {
-        report_ids: vec![0x01, 0x02],
-        input_report_size: 28,   // G29 input report size
-        output_report_size: 15,  // G29 output report size
-        has_ffb: true,
-        button_count: 24,
-        axis_count: 4,
+        report_ids,
+        input_report_size: input_bits.values().map(bytes).max().unwrap_or(0),
+        output_report_size: output_bits.values().map(bytes).max().unwrap_or(0),
+        has_ffb,
+        button_count: button_count.min(u8::MAX as usize) as u8,
+        axis_count: axis_count.min(u8::MAX as usize) as u8,
+        input_fields: fields,
+        pid_usages,
     })
 }
 
+fn extended_usage(usage_page: u16, value: u32, size: usize) -> (u16, u16) {
+    if size == 4 {
+        ((value >> 16) as u16, value as u16)
+    } else {
+        (usage_page, value as u16)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HidDescriptorInfo {
     pub report_ids: Vec<u8>,
@@ -212,4 +564,21 @@ pub struct HidDescriptorInfo {
     pub has_ffb: bool,
     pub button_count: u8,
     pub axis_count: u8,
-} 
\ No newline at end of file
+    pub input_fields: Vec<HidField>,
+    /// Every usage declared on the PID page, e.g. the supported effect types
+    pub pid_usages: Vec<u16>,
+}
+
+impl HidDescriptorInfo {
+    /// Size in bytes of the input report with the given ID, including the ID byte
+    pub fn input_report_len(&self, report_id: u8) -> usize {
+        let bits = self
+            .input_fields
+            .iter()
+            .filter(|f| f.report_id == report_id)
+            .map(|f| f.bit_offset + f.bit_size)
+            .max()
+            .unwrap_or(0);
+        usize::from(!self.report_ids.is_empty()) + (bits + 7) / 8
+    }
+}
\ No newline at end of file
@@ -0,0 +1,261 @@
+//! Serialization of G29 input reports into raw HID reports
+//!
+//! The byte layout is derived from the report descriptor of the selected
+//! target mode, so the report a virtual device emits always has exactly the
+//! length and field positions its advertised descriptor promises. Drivers
+//! wanting those bytes arranged differently get a `packing::ReportPacking`.
+//! The other emulated Logitech wheels (`TargetWheel`) differ only in their
+//! descriptors, so the same encoder lays out their reports too.
+
+use crate::config::{G29Config, G29Mode, TargetWheel};
+use crate::convert;
+use crate::device::descriptors::{
+    parse_hid_descriptor, HidField, DFGT_HID_DESCRIPTOR, G27_HID_DESCRIPTOR, G29_HID_DESCRIPTOR,
+    G29_PS4_HID_DESCRIPTOR, G920_HID_DESCRIPTOR, USAGE_PAGE_BUTTON, USAGE_PAGE_GENERIC_DESKTOP,
+    USAGE_PAGE_SIMULATION,
+};
+use crate::device::packing::{self, RawHid, ReportPacking};
+use crate::device::G29InputReport;
+use crate::error::Result;
+use std::sync::Arc;
+
+/// Report ID carrying wheel input in every G29 mode and on the G920
+const INPUT_REPORT_ID: u8 = 0x01;
+
+/// Full-scale values of the `G29InputReport` fields
+const STEERING_MAX: u32 = 0xFFFF;
+const PEDAL_MAX: u32 = convert::U10_MAX as u32;
+
+/// Report descriptor advertised by the virtual device in `mode`
+pub fn descriptor_for_mode(mode: G29Mode) -> &'static [u8] {
+    match mode {
+        G29Mode::Ps3 => G29_HID_DESCRIPTOR,
+        G29Mode::Ps4 => G29_PS4_HID_DESCRIPTOR,
+    }
+}
+
+/// Report descriptor advertised when emulating `target`, `mode` only matters for the G29
+pub fn descriptor_for(target: TargetWheel, mode: G29Mode) -> &'static [u8] {
+    match target {
+        TargetWheel::G27 => G27_HID_DESCRIPTOR,
+        TargetWheel::G29 => descriptor_for_mode(mode),
+        TargetWheel::G920 => G920_HID_DESCRIPTOR,
+        TargetWheel::Dfgt => DFGT_HID_DESCRIPTOR,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct G29ReportEncoder {
+    target: TargetWheel,
+    mode: G29Mode,
+    fields: Vec<HidField>,
+    report_len: usize,
+    has_report_id: bool,
+    wheel_on_simulation_page: bool,
+    packing: Arc<dyn ReportPacking>,
+}
+
+impl G29ReportEncoder {
+    /// Encoder producing reports as the descriptor of `mode` lays them out
+    pub fn new(mode: G29Mode) -> Result<Self> {
+        Self::with_packing(mode, Arc::new(RawHid))
+    }
+
+    /// Encoder for the target, mode and packing of `config`
+    pub fn for_config(config: &G29Config) -> Result<Self> {
+        Self::for_target(config.target, config.mode, packing::packing_for(config.packing))
+    }
+
+    /// Encoder laying G29 reports out for a particular virtual bus driver
+    pub fn with_packing(mode: G29Mode, packing: Arc<dyn ReportPacking>) -> Result<Self> {
+        Self::for_target(TargetWheel::G29, mode, packing)
+    }
+
+    /// Encoder producing reports of the emulated `target` wheel
+    pub fn for_target(target: TargetWheel, mode: G29Mode, packing: Arc<dyn ReportPacking>) -> Result<Self> {
+        let info = parse_hid_descriptor(descriptor_for(target, mode))?;
+
+        let fields: Vec<HidField> = info
+            .input_fields
+            .iter()
+            .filter(|f| f.report_id == INPUT_REPORT_ID || info.report_ids.is_empty())
+            .copied()
+            .collect();
+
+        // When the wheel has dedicated simulation usages, generic X/Y/Z/Rz are gamepad sticks
+        let wheel_on_simulation_page = fields.iter().any(|f| f.usage_page == USAGE_PAGE_SIMULATION);
+
+        // Descriptors without report IDs put every field in report 0
+        let has_report_id = !info.report_ids.is_empty();
+        let mut report_len = info.input_report_len(if has_report_id { INPUT_REPORT_ID } else { 0 });
+        if has_report_id && !packing.report_id_prefix() {
+            report_len -= 1;
+        }
+
+        Ok(Self {
+            target,
+            mode,
+            report_len,
+            has_report_id,
+            fields,
+            wheel_on_simulation_page,
+            packing,
+        })
+    }
+
+    pub fn target(&self) -> TargetWheel {
+        self.target
+    }
+
+    pub fn mode(&self) -> G29Mode {
+        self.mode
+    }
+
+    /// Length of an encoded report in bytes, including the report ID unless the packing drops it
+    pub fn report_len(&self) -> usize {
+        self.report_len
+    }
+
+    /// Encode a report into a freshly allocated buffer
+    pub fn encode(&self, report: &G29InputReport) -> Vec<u8> {
+        let mut buf = vec![0u8; self.report_len];
+        self.encode_into(report, &mut buf);
+        buf
+    }
+
+    /// Encode a report into `buf`, returning the number of bytes written
+    ///
+    /// `buf` must be at least `report_len()` bytes long.
+    pub fn encode_into(&self, report: &G29InputReport, buf: &mut [u8]) -> usize {
+        let buf = &mut buf[..self.report_len];
+        buf.fill(0);
+
+        let body = if self.has_report_id && self.packing.report_id_prefix() {
+            buf[0] = INPUT_REPORT_ID;
+            &mut buf[1..]
+        } else {
+            buf
+        };
+
+        for field in &self.fields {
+            if field.constant {
+                continue;
+            }
+            let value = self.field_value(field, report);
+            self.packing.write_field(body, field, value as u32);
+        }
+
+        self.report_len
+    }
+
+    fn field_value(&self, field: &HidField, report: &G29InputReport) -> i32 {
+        let hat = report.hat().to_raw();
+
+        match (field.usage_page, field.usage) {
+            (USAGE_PAGE_SIMULATION, 0xC8) => scale(report.steering as u32, STEERING_MAX, field),
+            (USAGE_PAGE_SIMULATION, 0xC4) => scale(report.throttle as u32, PEDAL_MAX, field),
+            (USAGE_PAGE_SIMULATION, 0xC5) => scale(report.brake as u32, PEDAL_MAX, field),
+            (USAGE_PAGE_SIMULATION, 0xC6) => scale(report.clutch as u32, PEDAL_MAX, field),
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x30 | 0x31 | 0x32 | 0x35) if self.wheel_on_simulation_page => {
+                // Idle gamepad stick
+                field.logical_min + (field.logical_max - field.logical_min + 1) / 2
+            }
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x30) => scale(report.steering as u32, STEERING_MAX, field),
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x31) => scale(report.throttle as u32, PEDAL_MAX, field),
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x32) => scale(report.brake as u32, PEDAL_MAX, field),
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x35) => scale(report.clutch as u32, PEDAL_MAX, field),
+            // Out-of-range hat values are the null state meaning centered
+            (USAGE_PAGE_GENERIC_DESKTOP, 0x39) => {
+                if (hat as i32) <= field.logical_max { hat as i32 } else { field.logical_max + 1 }
+            }
+            (USAGE_PAGE_BUTTON, usage @ 1..=24) => ((report.buttons >> (usage - 1)) & 1) as i32,
+            _ => 0,
+        }
+    }
+}
+
+/// Rescale `value` in 0..=`source_max` onto the field's logical range
+fn scale(value: u32, source_max: u32, field: &HidField) -> i32 {
+    let range = (field.logical_max - field.logical_min) as i64;
+    let scaled = value.min(source_max) as i64 * range / source_max as i64;
+    field.logical_min + scaled as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::HatDirection;
+
+    fn centered_report() -> G29InputReport {
+        G29InputReport {
+            report_id: INPUT_REPORT_ID,
+            steering: 0x8000,
+            throttle: 0,
+            brake: 0,
+            clutch: 0,
+            buttons: 8 << 24,
+            unused: [0; 4],
+        }
+    }
+
+    #[test]
+    fn test_report_length_matches_descriptor() {
+        for mode in [G29Mode::Ps3, G29Mode::Ps4] {
+            let encoder = G29ReportEncoder::new(mode).unwrap();
+            let info = parse_hid_descriptor(descriptor_for_mode(mode)).unwrap();
+            let encoded = encoder.encode(&centered_report());
+
+            assert_eq!(encoded.len(), info.input_report_len(INPUT_REPORT_ID));
+            assert_eq!(encoded[0], INPUT_REPORT_ID);
+        }
+    }
+
+    #[test]
+    fn test_ps4_report_places_wheel_axes_after_touchpad_block() {
+        let encoder = G29ReportEncoder::new(G29Mode::Ps4).unwrap();
+        let mut report = centered_report();
+        report.throttle = PEDAL_MAX as u16;
+
+        let encoded = encoder.encode(&report);
+        assert_eq!(encoded.len(), 64);
+        assert_eq!(&encoded[56..58], &0x8000u16.to_le_bytes());
+        assert_eq!(&encoded[58..60], &0xFFFFu16.to_le_bytes());
+    }
+
+    #[test]
+    fn test_other_targets_encode_their_native_layouts() {
+        let mut report = centered_report();
+        report.steering = STEERING_MAX as u16;
+        report.buttons |= 1;
+
+        // G27: hat nibble, 22 buttons, then 14-bit steering at bit 26
+        let g27 = G29ReportEncoder::for_target(TargetWheel::G27, G29Mode::Ps3, Arc::new(RawHid)).unwrap();
+        let encoded = g27.encode(&report);
+        assert_eq!(encoded.len(), 11);
+        assert_eq!(encoded[0], 0x18);
+        assert_eq!(u32::from_le_bytes(encoded[3..7].try_into().unwrap()) >> 2 & 0x3FFF, 0x3FFF);
+
+        // DFGT: no clutch, a padding bit aligns steering to bit 26 as well
+        let dfgt = G29ReportEncoder::for_target(TargetWheel::Dfgt, G29Mode::Ps3, Arc::new(RawHid)).unwrap();
+        assert_eq!(dfgt.encode(&report).len(), 8);
+
+        // G920: report ID, buttons and hat, then 16-bit axes
+        let g920 = G29ReportEncoder::for_target(TargetWheel::G920, G29Mode::Ps4, Arc::new(RawHid)).unwrap();
+        let encoded = g920.encode(&report);
+        assert_eq!(encoded.len(), 11);
+        assert_eq!(&encoded[..3], &[INPUT_REPORT_ID, 0x01, 0x80]);
+        assert_eq!(&encoded[3..5], &0xFFFFu16.to_le_bytes());
+    }
+
+    #[test]
+    fn test_hat_nibble_keeps_buttons() {
+        let mut report = centered_report();
+        report.buttons |= 0x00FF_FFFF;
+        report.set_hat(HatDirection::SouthWest);
+
+        assert_eq!(report.hat(), HatDirection::SouthWest);
+        assert_eq!(report.buttons, 0x05FF_FFFF);
+        report.set_hat(HatDirection::Centered);
+        assert_eq!(report.buttons, 0x08FF_FFFF);
+    }
+}
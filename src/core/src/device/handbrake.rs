@@ -0,0 +1,191 @@
+//! Thrustmaster TSS Handbrake Sparco Mod
+//!
+//! The TSS is a separate USB device that works either as an analog handbrake
+//! or, with its mode switch flipped, as a push/pull sequential shifter. The
+//! two modes use different reports, so the active mode is read from the
+//! report descriptor when the device is opened (an analog axis means
+//! handbrake mode) unless the configuration pins it. The aggregation layer
+//! reads it on its own thread and merges its state into the wheel report
+//! before translation, so button mapping and the G29 side treat it like any
+//! other wheel input.
+
+use crate::config::{HandbrakeConfig, HandbrakeTarget};
+use crate::convert;
+use crate::device::descriptors::parse_hid_descriptor;
+use crate::device::{ThrustmasterInputReport, SOURCE_BUTTON_COUNT};
+use crate::error::{Result, TranslatorError};
+use hidapi::{HidApi, HidDevice};
+use serde::{Deserialize, Serialize};
+
+/// Product string fragment identifying the TSS
+const TSS_PRODUCT_NAME: &str = "TSS";
+
+/// Largest TSS report, handbrake mode
+const TSS_REPORT_LEN: usize = 3;
+
+/// Analog position above which the handbrake counts as pulled in button mode
+const BUTTON_THRESHOLD: u8 = u8::MAX / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandbrakeMode {
+    Analog,      // Handbrake lever, 16-bit position
+    Sequential,  // Push for upshift, pull for downshift
+}
+
+/// Latest TSS state in either mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandbrakeState {
+    pub position: u8,     // 0 = released, analog mode only
+    pub upshift: bool,    // Sequential mode only
+    pub downshift: bool,  // Sequential mode only
+}
+
+/// Parse a TSS input report for the given mode
+///
+/// Handbrake mode reports `[report_id, position_lo, position_hi]`, sequential
+/// mode `[report_id, buttons]` with bit 0 = upshift and bit 1 = downshift.
+pub fn parse_handbrake_report(data: &[u8], mode: HandbrakeMode) -> Result<HandbrakeState> {
+    match mode {
+        HandbrakeMode::Analog => {
+            if data.len() < 3 {
+                return Err(TranslatorError::invalid_report(format!("TSS handbrake report too short: {} bytes", data.len())));
+            }
+            Ok(HandbrakeState {
+                position: convert::u16_to_u8(u16::from_le_bytes([data[1], data[2]])),
+                ..Default::default()
+            })
+        }
+        HandbrakeMode::Sequential => {
+            if data.len() < 2 {
+                return Err(TranslatorError::invalid_report(format!("TSS shifter report too short: {} bytes", data.len())));
+            }
+            Ok(HandbrakeState {
+                upshift: data[1] & 0x01 != 0,
+                downshift: data[1] & 0x02 != 0,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+impl HandbrakeState {
+    /// Fold this state into a wheel report as configured
+    pub fn merge_into(&self, report: &mut ThrustmasterInputReport, mode: HandbrakeMode, config: &HandbrakeConfig) {
+        let mut set_button = |index: u8, pressed: bool| {
+            if index < SOURCE_BUTTON_COUNT && pressed {
+                report.buttons |= 1 << index;
+            }
+        };
+
+        match mode {
+            HandbrakeMode::Analog => match config.target {
+                HandbrakeTarget::Clutch => report.clutch = report.clutch.max(self.position),
+                HandbrakeTarget::Button => set_button(config.handbrake_button, self.position > BUTTON_THRESHOLD),
+            },
+            HandbrakeMode::Sequential => {
+                set_button(config.upshift_button, self.upshift);
+                set_button(config.downshift_button, self.downshift);
+            }
+        }
+    }
+}
+
+pub struct TssHandbrake {
+    device: HidDevice,
+    mode: HandbrakeMode,
+}
+
+impl TssHandbrake {
+    /// Find and open the TSS, `Ok(None)` when it is not plugged in
+    pub fn detect(config: &HandbrakeConfig) -> Result<Option<Self>> {
+        let api = HidApi::new()?;
+
+        let found = api.device_list().find(|dev| {
+            dev.vendor_id() == config.vid
+                && match config.pid {
+                    Some(pid) => dev.product_id() == pid,
+                    None => dev.product_string().map_or(false, |name| name.contains(TSS_PRODUCT_NAME)),
+                }
+        });
+        let Some(info) = found else {
+            return Ok(None);
+        };
+
+        let device = info.open_device(&api)?;
+
+        let mode = config.mode.unwrap_or_else(|| detect_mode(&device));
+        tracing::info!("Found {} in {:?} mode", info.product_string().unwrap_or("TSS"), mode);
+
+        Ok(Some(Self { device, mode }))
+    }
+
+    pub fn mode(&self) -> HandbrakeMode {
+        self.mode
+    }
+
+    /// Wait up to `timeout_ms` for the next report, `Ok(None)` on timeout
+    pub fn read(&mut self, timeout_ms: i32) -> Result<Option<HandbrakeState>> {
+        let mut buf = [0u8; TSS_REPORT_LEN];
+        match self.device.read_timeout(&mut buf, timeout_ms)? {
+            0 => Ok(None),
+            n => parse_handbrake_report(&buf[..n], self.mode).map(Some),
+        }
+    }
+}
+
+/// Handbrake mode advertises an analog axis, sequential mode only buttons
+fn detect_mode(device: &HidDevice) -> HandbrakeMode {
+    let mut descriptor = [0u8; hidapi::MAX_REPORT_DESCRIPTOR_SIZE];
+    let axis_count = device
+        .get_report_descriptor(&mut descriptor)
+        .ok()
+        .and_then(|len| parse_hid_descriptor(&descriptor[..len]).ok())
+        .map(|info| info.axis_count);
+
+    match axis_count {
+        Some(0) => HandbrakeMode::Sequential,
+        Some(_) => HandbrakeMode::Analog,
+        None => {
+            tracing::warn!("Cannot read the TSS report descriptor, assuming handbrake mode");
+            HandbrakeMode::Analog
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::HatDirection;
+
+    fn wheel_report() -> ThrustmasterInputReport {
+        ThrustmasterInputReport { steering: 0, throttle: 0, brake: 0, clutch: 40, buttons: 0, dpad: HatDirection::Centered, base_range: None, rim: None }
+    }
+
+    #[test]
+    fn test_analog_handbrake_drives_clutch_or_button() {
+        let state = parse_handbrake_report(&[0x01, 0xFF, 0xFF], HandbrakeMode::Analog).unwrap();
+        assert_eq!(state.position, u8::MAX);
+
+        let mut config = HandbrakeConfig::default();
+        let mut report = wheel_report();
+        state.merge_into(&mut report, HandbrakeMode::Analog, &config);
+        assert_eq!(report.clutch, u8::MAX);
+
+        config.target = HandbrakeTarget::Button;
+        let mut report = wheel_report();
+        state.merge_into(&mut report, HandbrakeMode::Analog, &config);
+        assert_eq!(report.clutch, 40);
+        assert_eq!(report.buttons, 1 << config.handbrake_button);
+    }
+
+    #[test]
+    fn test_sequential_mode_presses_shift_buttons() {
+        let config = HandbrakeConfig::default();
+        let state = parse_handbrake_report(&[0x01, 0x02], HandbrakeMode::Sequential).unwrap();
+        let mut report = wheel_report();
+        state.merge_into(&mut report, HandbrakeMode::Sequential, &config);
+        assert_eq!(report.buttons, 1 << config.downshift_button);
+
+        assert!(parse_handbrake_report(&[0x01], HandbrakeMode::Sequential).is_err());
+    }
+}
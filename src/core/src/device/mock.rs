@@ -0,0 +1,238 @@
+//! Scripted devices for running the translator without hardware
+//!
+//! `MockThrustmasterDevice` plays queued wheel reports and keeps every FFB
+//! command, range and LED change the translator sends it. `MockVirtualG29`
+//! keeps every G29 report it is fed and hands out output reports queued as if
+//! a game had written them. Both are cheap handles to shared state: give one
+//! clone to `ProtocolTranslator::with_devices` and drive and inspect the
+//! pipeline through the other.
+
+use crate::config::{G29Config, ReadStrategy, ThrustmasterConfig};
+use crate::device::backend::{SourceWheel, VirtualWheel};
+use crate::device::models::ThrustmasterModel;
+use crate::device::source::InputEvents;
+use crate::device::{G29InputReport, G29OutputReport, IforceCommand, StrategySelection, ThrustmasterInputReport};
+use crate::error::Result;
+use crate::ffb::FfbCapabilities;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Wheel playing scripted reports
+#[derive(Clone)]
+pub struct MockThrustmasterDevice {
+    inner: Arc<MockWheel>,
+}
+
+struct MockWheel {
+    model: ThrustmasterModel,
+    capabilities: FfbCapabilities,
+    read_strategy: StrategySelection,
+    reports: Mutex<VecDeque<ThrustmasterInputReport>>,
+    ffb_commands: Mutex<Vec<IforceCommand>>,
+    ranges: Mutex<Vec<u16>>,
+    leds: Mutex<Vec<(u8, f32)>>,
+}
+
+impl MockThrustmasterDevice {
+    /// Polled wheel of `model` rendering every effect kind
+    pub fn new(model: ThrustmasterModel) -> Self {
+        Self {
+            inner: Arc::new(MockWheel {
+                model,
+                capabilities: FfbCapabilities::all(),
+                read_strategy: StrategySelection {
+                    strategy: ReadStrategy::HidapiNonBlocking,
+                    configured: true,
+                    benchmarks: Vec::new(),
+                },
+                reports: Mutex::default(),
+                ffb_commands: Mutex::default(),
+                ranges: Mutex::default(),
+                leds: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Queue reports, read one per input loop tick
+    pub fn script(&self, reports: impl IntoIterator<Item = ThrustmasterInputReport>) {
+        if let Ok(mut queue) = self.inner.reports.lock() {
+            queue.extend(reports);
+        }
+    }
+
+    /// Scripted reports not read yet
+    pub fn pending_reports(&self) -> usize {
+        self.inner.reports.lock().map_or(0, |queue| queue.len())
+    }
+
+    pub fn ffb_commands(&self) -> Vec<IforceCommand> {
+        self.inner.ffb_commands.lock().map(|commands| commands.clone()).unwrap_or_default()
+    }
+
+    /// Rotation ranges set, in order
+    pub fn ranges(&self) -> Vec<u16> {
+        self.inner.ranges.lock().map(|ranges| ranges.clone()).unwrap_or_default()
+    }
+
+    /// LED masks and brightness set, in order
+    pub fn leds(&self) -> Vec<(u8, f32)> {
+        self.inner.leds.lock().map(|leds| leds.clone()).unwrap_or_default()
+    }
+
+    /// Wait until at least `count` FFB commands were sent, `false` on timeout
+    pub async fn wait_for_ffb_commands(&self, count: usize, timeout: Duration) -> bool {
+        wait_until(timeout, || self.ffb_commands().len() >= count).await
+    }
+}
+
+impl SourceWheel for MockThrustmasterDevice {
+    /// Fresh wheel of the model the configured PID or `model` names
+    async fn open(config: &ThrustmasterConfig) -> Result<Self> {
+        Ok(Self::new(config.model.unwrap_or_else(|| ThrustmasterModel::from_pid(config.pid))))
+    }
+
+    async fn initialize(&self, steering_range: u16) -> Result<()> {
+        self.set_range(steering_range).await.map(|_| ())
+    }
+
+    fn model(&self) -> ThrustmasterModel {
+        self.inner.model
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        Some("MOCK0001")
+    }
+
+    fn ffb_capabilities(&self) -> &FfbCapabilities {
+        &self.inner.capabilities
+    }
+
+    fn read_strategy(&self) -> &StrategySelection {
+        &self.inner.read_strategy
+    }
+
+    fn take_input_events(&mut self) -> Option<InputEvents> {
+        None
+    }
+
+    async fn read_input(&self) -> Result<Option<ThrustmasterInputReport>> {
+        Ok(self.inner.reports.lock().ok().and_then(|mut queue| queue.pop_front()))
+    }
+
+    async fn send_ffb_command(&self, command: IforceCommand) -> Result<()> {
+        if let Ok(mut commands) = self.inner.ffb_commands.lock() {
+            commands.push(command);
+        }
+        Ok(())
+    }
+
+    async fn set_range(&self, degrees: u16) -> Result<u16> {
+        let applied = self.inner.model.clamp_range(degrees).unwrap_or(degrees);
+        if let Ok(mut ranges) = self.inner.ranges.lock() {
+            ranges.push(applied);
+        }
+        Ok(applied)
+    }
+
+    async fn set_leds(&self, mask: u8, brightness: f32) -> Result<()> {
+        if let Ok(mut leds) = self.inner.leds.lock() {
+            leds.push((mask, brightness));
+        }
+        Ok(())
+    }
+}
+
+/// Virtual G29 keeping what it is sent
+#[derive(Clone, Default)]
+pub struct MockVirtualG29 {
+    inner: Arc<MockTarget>,
+}
+
+#[derive(Default)]
+struct MockTarget {
+    sent: Mutex<Vec<G29InputReport>>,
+    outputs: Mutex<VecDeque<G29OutputReport>>,
+}
+
+impl MockVirtualG29 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an output report as if the game had written it
+    pub fn game_writes(&self, report: G29OutputReport) {
+        if let Ok(mut outputs) = self.inner.outputs.lock() {
+            outputs.push_back(report);
+        }
+    }
+
+    /// G29 reports received, in order
+    pub fn sent_reports(&self) -> Vec<G29InputReport> {
+        self.inner.sent.lock().map(|sent| sent.clone()).unwrap_or_default()
+    }
+
+    /// Wait until at least `count` reports were received, `false` on timeout
+    pub async fn wait_for_reports(&self, count: usize, timeout: Duration) -> bool {
+        wait_until(timeout, || self.sent_reports().len() >= count).await
+    }
+}
+
+impl VirtualWheel for MockVirtualG29 {
+    async fn create(_config: &G29Config) -> Result<Self> {
+        Ok(Self::new())
+    }
+
+    async fn send_input(&self, report: G29InputReport) -> Result<()> {
+        if let Ok(mut sent) = self.inner.sent.lock() {
+            sent.push(report);
+        }
+        Ok(())
+    }
+
+    async fn read_output(&self) -> Result<Option<G29OutputReport>> {
+        Ok(self.inner.outputs.lock().ok().and_then(|mut outputs| outputs.pop_front()))
+    }
+}
+
+async fn wait_until(timeout: Duration, mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::neutral_report;
+    use crate::ProtocolTranslator;
+
+    #[tokio::test]
+    async fn test_pipeline_runs_end_to_end_on_mocks() {
+        let wheel = MockThrustmasterDevice::new(ThrustmasterModel::T300rs);
+        let target = MockVirtualG29::new();
+        let translator = ProtocolTranslator::with_devices(Config::default(), wheel.clone(), target.clone());
+        let running = tokio::spawn(translator.run());
+
+        // Full right lock and the throttle floored
+        let mut report = neutral_report();
+        report.steering = i16::MAX;
+        report.throttle = u8::MAX;
+        wheel.script([neutral_report(), report]);
+        assert!(target.wait_for_reports(2, Duration::from_secs(2)).await);
+        let sent = target.sent_reports();
+        assert!(sent[1].steering > sent[0].steering);
+        assert!(sent[1].throttle > sent[0].throttle);
+
+        target.game_writes(G29OutputReport::constant_effect(1, 8000, 0));
+        assert!(wheel.wait_for_ffb_commands(1, Duration::from_secs(2)).await);
+
+        running.abort();
+    }
+}
@@ -3,13 +3,32 @@
 pub mod thrustmaster;
 pub mod virtual_g29;
 pub mod descriptors;
+pub mod encoding;
+pub mod packing;
+pub mod aggregate;
+pub mod handbrake;
+pub mod codriver;
+pub mod pedals;
+pub mod shifter;
+pub mod models;
+pub mod source;
+pub mod backend;
+pub mod mock;
 
 pub use thrustmaster::ThrustmasterDevice;
 pub use virtual_g29::VirtualG29Device;
-pub use descriptors::{G29_HID_DESCRIPTOR, parse_hid_descriptor};
+pub use descriptors::{G29_HID_DESCRIPTOR, G29_PS4_HID_DESCRIPTOR, parse_hid_descriptor};
+pub use encoding::G29ReportEncoder;
+pub use source::StrategySelection;
+pub use backend::{SourceWheel, VirtualWheel};
 
+use crate::ffb::{ConditionType, Envelope, Waveform};
+use crate::pid::SET_ENVELOPE_REPORT_ID;
 use serde::{Deserialize, Serialize};
 
+/// Source buttons a `ThrustmasterInputReport` can carry, rim, base and add-ons combined
+pub const SOURCE_BUTTON_COUNT: u8 = 32;
+
 /// Input report from Thrustmaster device (8 bytes typical)
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ThrustmasterInputReport {
@@ -17,8 +36,12 @@ pub struct ThrustmasterInputReport {
     pub throttle: u8,         // 0-255
     pub brake: u8,            // 0-255  
     pub clutch: u8,           // 0-255
-    pub buttons: u16,         // Button bitfield
-    pub dpad: u8,             // D-pad state (0-7, 8=center)
+    pub buttons: u32,         // Button bitfield, bit n = source button n
+    pub dpad: HatDirection,   // D-pad state
+    #[serde(default)]
+    pub base_range: Option<u16>, // Rotation range in degrees the base reports, `None` if it does not
+    #[serde(default)]
+    pub rim: Option<models::Rim>, // Rim attached to the base, `None` if it does not say
 }
 
 /// Input report for G29 device
@@ -33,6 +56,93 @@ pub struct G29InputReport {
     pub unused: [u8; 4],      // Padding to match G29 report size
 }
 
+/// Bit position of the hat nibble in `G29InputReport::buttons`
+pub const G29_HAT_SHIFT: u32 = 24;
+const G29_HAT_MASK: u32 = 0x0F << G29_HAT_SHIFT;
+
+impl G29InputReport {
+    /// D-pad direction carried in the hat nibble
+    pub fn hat(&self) -> HatDirection {
+        HatDirection::from_raw(((self.buttons & G29_HAT_MASK) >> G29_HAT_SHIFT) as u8).unwrap_or_default()
+    }
+
+    /// Replace the hat nibble, leaving the button bits untouched
+    pub fn set_hat(&mut self, hat: HatDirection) {
+        self.buttons = (self.buttons & !G29_HAT_MASK) | ((hat.to_raw() as u32) << G29_HAT_SHIFT);
+    }
+}
+
+/// Position of a D-pad or hat switch
+///
+/// Raw values follow the HID hat convention shared by Thrustmaster and G29
+/// reports: 0 = north, counting clockwise in 45 degree steps, 8 = centered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HatDirection {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    #[default]
+    Centered,
+}
+
+impl HatDirection {
+    const DIRECTIONS: [HatDirection; 9] = [
+        HatDirection::North,
+        HatDirection::NorthEast,
+        HatDirection::East,
+        HatDirection::SouthEast,
+        HatDirection::South,
+        HatDirection::SouthWest,
+        HatDirection::West,
+        HatDirection::NorthWest,
+        HatDirection::Centered,
+    ];
+
+    /// Direction of a raw hat value, `None` for values above 8
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        Self::DIRECTIONS.get(raw as usize).copied()
+    }
+
+    pub fn to_raw(self) -> u8 {
+        self as u8
+    }
+
+    /// Horizontal and vertical components, right and up positive
+    pub fn components(self) -> (i8, i8) {
+        match self {
+            HatDirection::North => (0, 1),
+            HatDirection::NorthEast => (1, 1),
+            HatDirection::East => (1, 0),
+            HatDirection::SouthEast => (1, -1),
+            HatDirection::South => (0, -1),
+            HatDirection::SouthWest => (-1, -1),
+            HatDirection::West => (-1, 0),
+            HatDirection::NorthWest => (-1, 1),
+            HatDirection::Centered => (0, 0),
+        }
+    }
+
+    /// Direction with the given components, the sign of each is what counts
+    pub fn from_components(horizontal: i32, vertical: i32) -> Self {
+        match (horizontal.signum(), vertical.signum()) {
+            (0, 1) => HatDirection::North,
+            (1, 1) => HatDirection::NorthEast,
+            (1, 0) => HatDirection::East,
+            (1, -1) => HatDirection::SouthEast,
+            (0, -1) => HatDirection::South,
+            (-1, -1) => HatDirection::SouthWest,
+            (-1, 0) => HatDirection::West,
+            (-1, 1) => HatDirection::NorthWest,
+            _ => HatDirection::Centered,
+        }
+    }
+}
+
 /// Output report from G29 (FFB commands)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct G29OutputReport {
@@ -40,9 +150,111 @@ pub struct G29OutputReport {
     pub data: Vec<u8>,
 }
 
+/// Report ID of FFB effect blocks, see `OutputTranslator::parse_ffb_effect`
+pub const FFB_EFFECT_REPORT_ID: u8 = 0x01;
+
+/// Shortest effect block `OutputTranslator::parse_ffb_effect` accepts
+const EFFECT_REPORT_LEN: usize = 8;
+
+/// Logitech extended command, sent as report `0xF8` or as the first byte of report 0
+pub const G29_EXTENDED_COMMAND: u8 = 0xF8;
+
+/// Extended command setting the rev-strip LEDs: `0xF8 0x12 <mask> 0x00 0x00 0x00 0x01`
+pub const G29_SET_LEDS: u8 = 0x12;
+
+/// Extended command setting the rotation range: `0xF8 0x81 <degrees, little endian> 0x00 0x00 0x00`
+pub const G29_SET_RANGE: u8 = 0x81;
+
+/// Rotation ranges a G29 accepts, hid-lg4ff clamps to the same
+pub const G29_RANGE: std::ops::RangeInclusive<u16> = 40..=900;
+
+impl G29OutputReport {
+    /// Effect block `[effect_id, effect_type, parameters...]`, zero padded
+    fn effect(effect_id: u8, effect_type: u8, parameters: &[u8]) -> Self {
+        let mut data = vec![effect_id, effect_type];
+        data.extend_from_slice(parameters);
+        if data.len() < EFFECT_REPORT_LEN {
+            data.resize(EFFECT_REPORT_LEN, 0);
+        }
+        Self { report_id: FFB_EFFECT_REPORT_ID, data }
+    }
+
+    /// Constant force, `duration_ms` 0 = until stopped
+    pub fn constant_effect(effect_id: u8, magnitude: i16, duration_ms: u16) -> Self {
+        let mut parameters = [0u8; 4];
+        parameters[..2].copy_from_slice(&magnitude.to_le_bytes());
+        parameters[2..].copy_from_slice(&duration_ms.to_le_bytes());
+        Self::effect(effect_id, 0x01, &parameters)
+    }
+
+    pub fn periodic_effect(effect_id: u8, waveform: Waveform, magnitude: u16, period_ms: u16, phase: u16) -> Self {
+        let effect_type = match waveform {
+            Waveform::Square => 0x03,
+            Waveform::Sine => 0x04,
+            Waveform::Triangle => 0x05,
+            Waveform::SawtoothUp => 0x06,
+            Waveform::SawtoothDown => 0x07,
+        };
+        let mut parameters = [0u8; 6];
+        parameters[..2].copy_from_slice(&magnitude.to_le_bytes());
+        parameters[2..4].copy_from_slice(&period_ms.to_le_bytes());
+        parameters[4..].copy_from_slice(&phase.to_le_bytes());
+        Self::effect(effect_id, effect_type, &parameters)
+    }
+
+    /// Set Envelope for the next effect written to `effect_id`
+    pub fn envelope(effect_id: u8, envelope: &Envelope) -> Self {
+        let mut data = vec![effect_id];
+        for value in [envelope.attack_level, envelope.fade_level, envelope.attack_time, envelope.fade_time] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        Self { report_id: SET_ENVELOPE_REPORT_ID, data }
+    }
+
+    /// Rev-strip LED command, bit 0 is the leftmost LED
+    pub fn leds(mask: u8) -> Self {
+        Self { report_id: G29_EXTENDED_COMMAND, data: vec![G29_SET_LEDS, mask, 0x00, 0x00, 0x00, 0x01] }
+    }
+
+    /// Rotation range command, as games send it per car
+    pub fn range(degrees: u16) -> Self {
+        let [low, high] = degrees.to_le_bytes();
+        Self { report_id: G29_EXTENDED_COMMAND, data: vec![G29_SET_RANGE, low, high, 0x00, 0x00, 0x00] }
+    }
+
+    pub fn condition_effect(effect_id: u8, condition: ConditionType, positive: i16, negative: i16) -> Self {
+        let effect_type = match condition {
+            ConditionType::Spring => 0x08,
+            ConditionType::Damper => 0x09,
+            ConditionType::Inertia => 0x0A,
+            ConditionType::Friction => 0x0B,
+        };
+        let mut parameters = [0u8; 4];
+        parameters[..2].copy_from_slice(&positive.to_le_bytes());
+        parameters[2..].copy_from_slice(&negative.to_le_bytes());
+        Self::effect(effect_id, effect_type, &parameters)
+    }
+}
+
 /// IFORCE command for Thrustmaster FFB
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IforceCommand {
     pub command_id: u8,
     pub data: Vec<u8>,
+}
+
+impl IforceCommand {
+    /// Packet as sent to the base: `[length, command_id, data..., checksum]`
+    ///
+    /// The length counts the command ID and checksum, the checksum is the
+    /// XOR of every byte before it.
+    pub fn to_packet(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(self.data.len() + 3);
+        packet.push((self.data.len() + 2) as u8);
+        packet.push(self.command_id);
+        packet.extend_from_slice(&self.data);
+        let checksum = packet.iter().fold(0u8, |acc, &byte| acc ^ byte);
+        packet.push(checksum);
+        packet
+    }
 } 
\ No newline at end of file
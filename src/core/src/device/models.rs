@@ -0,0 +1,602 @@
+//! Input report layouts of the Thrustmaster wheel bases
+//!
+//! Thrustmaster bases do not share one report format. The older T150 and TMX
+//! report 10-bit pedals after an offset-centered steering axis, the T300RS,
+//! TX and T248 16-bit pedals and a wider button field for their rims and
+//! displays. Each model maps to a `ReportLayout`, picked from the PID or set
+//! in the configuration, and `ReportLayout::parse` turns any of them into a
+//! `ThrustmasterInputReport`. Pedal axes are narrowed to the report's 8 bits
+//! the same way on every model.
+//!
+//! Bases whose rotation range can be changed with their own buttons report
+//! the current range after the controls, in the units of their range
+//! command, so the translator can follow a change made on the wheel.
+//!
+//! The T300RS and TX also report which rim is attached, in the byte after
+//! the range. Rims can be swapped with the base powered, so the ID is read
+//! from every report and a change switches the button mapping.
+//!
+//! `ModelDescription` renders what this module knows about a PID, for
+//! `tm-g29 models --describe`.
+
+use crate::convert;
+use crate::device::{HatDirection, ThrustmasterInputReport, SOURCE_BUTTON_COUNT};
+use crate::error::{Result, TranslatorError};
+use crate::ffb::{EffectKind, FfbCapabilities};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Read buffer large enough for every layout
+pub const MAX_INPUT_REPORT_LEN: usize = 64;
+
+/// Report ID of the T300RS family's FFB and settings output reports
+const T300_OUTPUT_REPORT_ID: u8 = 0x60;
+
+/// Settings command setting the rotation range, on every model with one
+const SET_RANGE_COMMAND: u8 = 0x11;
+
+/// Settings command lighting the rev LEDs of the T300RS family
+const SET_LEDS_COMMAND: u8 = 0x13;
+
+/// Rotation range the T150 family's 16-bit range value spans
+const T150_FULL_RANGE: u32 = 1080;
+
+/// Product ID of the I-Force protocol bases
+const IFORCE_PID: u16 = 0x0004;
+
+/// Product IDs with a known layout
+pub const KNOWN_BASES: &[(u16, ThrustmasterModel)] = &[
+    (0xB677, ThrustmasterModel::T150),
+    (0xB67F, ThrustmasterModel::Tmx),
+    (0xB66E, ThrustmasterModel::T300rs),
+    (0xB66F, ThrustmasterModel::T300rs),
+    (0xB669, ThrustmasterModel::Tx),
+    (0xB696, ThrustmasterModel::T248),
+    (IFORCE_PID, ThrustmasterModel::Generic),
+];
+
+/// Effect kinds of bases whose descriptor does not list them
+pub fn model_capabilities(pid: u16) -> Option<FfbCapabilities> {
+    match pid {
+        // I-Force protocol bases, the Linux iforce driver exposes no ramp effect
+        IFORCE_PID => Some(FfbCapabilities::from_kinds([
+            EffectKind::Constant,
+            EffectKind::Periodic,
+            EffectKind::Spring,
+            EffectKind::Damper,
+            EffectKind::Inertia,
+            EffectKind::Friction,
+        ])),
+        _ => None,
+    }
+}
+
+/// I-Force protocol bases center themselves with the IFORCE autocenter command
+pub fn has_hardware_autocenter(pid: u16) -> bool {
+    pid == IFORCE_PID
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThrustmasterModel {
+    Generic,  // 8-byte layout, optional trailing buttons 16-31
+    T150,
+    Tmx,
+    T300rs,
+    Tx,
+    T248,
+}
+
+impl ThrustmasterModel {
+    /// Model of a base by its USB product ID, `Generic` for unknown ones
+    pub fn from_pid(pid: u16) -> Self {
+        KNOWN_BASES
+            .iter()
+            .find(|(known, _)| *known == pid)
+            .map_or(Self::Generic, |&(_, model)| model)
+    }
+
+    pub fn layout(self) -> &'static ReportLayout {
+        match self {
+            Self::Generic => &GENERIC_LAYOUT,
+            Self::T150 | Self::Tmx => &T150_LAYOUT,
+            Self::T300rs | Self::Tx => &T300_LAYOUT,
+            Self::T248 => &T248_LAYOUT,
+        }
+    }
+
+    /// Lock-to-lock rotation range in degrees the base accepts, `None` if it
+    /// cannot be set over USB
+    pub fn range_limits(self) -> Option<(u16, u16)> {
+        match self {
+            Self::Generic => None,
+            Self::T150 => Some((270, 1080)),
+            Self::Tmx => Some((270, 900)),
+            Self::T300rs => Some((40, 1080)),
+            Self::Tx | Self::T248 => Some((40, 900)),
+        }
+    }
+
+    /// Whether the base or its rims can show rev lights
+    pub fn has_leds(self) -> bool {
+        self.led_report(0, 0).is_some()
+    }
+
+    /// Whether the rev LEDs take a PWM brightness level rather than only on and off
+    ///
+    /// Rims on the T300RS and TX dim their LEDs; the T248's display rev
+    /// lights are on or off.
+    pub fn dims_leds(self) -> bool {
+        matches!(self, Self::T300rs | Self::Tx)
+    }
+
+    /// Output report lighting the rev LEDs in `mask` at `brightness`
+    ///
+    /// The T248 shows the mask on the rev lights of its display, the T300RS
+    /// and TX pass it to rims with LEDs and ignore it otherwise.
+    pub fn led_report(self, mask: u8, brightness: u8) -> Option<Vec<u8>> {
+        match self {
+            Self::T300rs | Self::Tx | Self::T248 => {
+                Some(vec![T300_OUTPUT_REPORT_ID, 0x08, SET_LEDS_COMMAND, mask, brightness])
+            }
+            Self::Generic | Self::T150 | Self::Tmx => None,
+        }
+    }
+
+    /// Behaviour of the model the translator works around
+    pub fn quirks(self) -> &'static [&'static str] {
+        match self {
+            Self::Generic => &["Layout assumed: 8 bytes, buttons 16-31 only when the report is longer"],
+            Self::T150 | Self::Tmx => &[
+                "10-bit pedals, narrowed to 8 bits",
+                "Range command value 0xFFFF is 1080 degrees, whatever the model's own limit",
+            ],
+            Self::T300rs | Self::Tx => &[
+                "Reports the attached rim; a swap switches to the rim's button mapping",
+                "Rev LEDs are on the rim, rims without them ignore the LED command",
+            ],
+            Self::T248 => &["Rev lights on the display, on or off without brightness levels"],
+        }
+    }
+
+    /// `degrees` clamped to the range the base accepts
+    pub fn clamp_range(self, degrees: u16) -> Option<u16> {
+        self.range_limits().map(|(min, max)| degrees.clamp(min, max))
+    }
+
+    /// Output report setting the rotation range to `degrees`, which must be within `range_limits`
+    pub fn range_report(self, degrees: u16) -> Option<Vec<u8>> {
+        match self {
+            Self::Generic => None,
+            Self::T150 | Self::Tmx => {
+                // 0xFFFF is the full 1080°, whatever the model's own limit
+                let value = convert::rescale(degrees as u32, T150_FULL_RANGE, u16::MAX as u32) as u16;
+                let [low, high] = value.to_le_bytes();
+                Some(vec![0x40, SET_RANGE_COMMAND, low, high])
+            }
+            Self::T300rs | Self::Tx | Self::T248 => {
+                // 60 units per degree
+                let [low, high] = (degrees * 0x3C).to_le_bytes();
+                Some(vec![T300_OUTPUT_REPORT_ID, 0x08, SET_RANGE_COMMAND, low, high])
+            }
+        }
+    }
+}
+
+impl fmt::Display for ThrustmasterModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Generic => "generic",
+            Self::T150 => "T150",
+            Self::Tmx => "TMX",
+            Self::T300rs => "T300RS",
+            Self::Tx => "TX",
+            Self::T248 => "T248",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What the translator knows about the base with a product ID
+pub struct ModelDescription {
+    pub pid: u16,
+    pub model: ThrustmasterModel,
+}
+
+impl ModelDescription {
+    pub fn new(pid: u16) -> Self {
+        Self { pid, model: ThrustmasterModel::from_pid(pid) }
+    }
+
+    /// Whether the PID is in the registry rather than given the generic layout
+    pub fn is_known(&self) -> bool {
+        KNOWN_BASES.iter().any(|(pid, _)| *pid == self.pid)
+    }
+}
+
+impl fmt::Display for ModelDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model = self.model;
+        let layout = model.layout();
+        write!(f, "PID {:04x}: {}", self.pid, model)?;
+        if !self.is_known() {
+            write!(f, " (unknown PID, generic layout)")?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Input report: {} bytes, at least {}", layout.full_len(), layout.min_len)?;
+        let steering = match layout.steering_encoding {
+            SteeringEncoding::Signed => "signed, 0 = center",
+            SteeringEncoding::Offset => "offset, 0x8000 = center",
+        };
+        writeln!(f, "  steering  bytes {}-{}, {}", layout.steering, layout.steering + 1, steering)?;
+        let [throttle, brake, clutch] = layout.pedals;
+        writeln!(
+            f,
+            "  pedals    throttle {}, brake {}, clutch {}, {}-bit",
+            throttle, brake, clutch, layout.pedal_bits
+        )?;
+        for field in layout.buttons {
+            writeln!(
+                f,
+                "  buttons   {} from byte {} (source buttons {}-{})",
+                field.count,
+                field.offset,
+                field.first,
+                field.first as u16 + field.count as u16 - 1
+            )?;
+        }
+        writeln!(f, "  hat       byte {}, low nibble", layout.hat)?;
+        if let Some(range) = layout.range {
+            let units = match range.encoding {
+                RangeEncoding::Full1080 => "0xFFFF = 1080 degrees",
+                RangeEncoding::Sixtieths => "1/60 degree",
+            };
+            writeln!(f, "  range     bytes {}-{}, {}", range.offset, range.offset + 1, units)?;
+        }
+        if let Some(rim) = layout.rim {
+            writeln!(f, "  rim       byte {}", rim)?;
+        }
+
+        match model.range_limits() {
+            Some((min, max)) => writeln!(f, "Rotation range: {}-{} degrees", min, max)?,
+            None => writeln!(f, "Rotation range: fixed by the base")?,
+        }
+        let leds = match (model.has_leds(), model.dims_leds()) {
+            (false, _) => "none",
+            (true, true) => "rev lights with brightness",
+            (true, false) => "rev lights, on or off",
+        };
+        writeln!(f, "LEDs: {}", leds)?;
+
+        match model_capabilities(self.pid) {
+            Some(capabilities) => writeln!(f, "FFB effects: {:?}", capabilities.kinds())?,
+            None => writeln!(f, "FFB effects: from the report descriptor when opened, all assumed if it lists none")?,
+        }
+        let autocenter = if has_hardware_autocenter(self.pid) { "IFORCE autocenter command" } else { "spring effect" };
+        writeln!(f, "Autocenter: {}", autocenter)?;
+
+        writeln!(f, "Quirks:")?;
+        for quirk in model.quirks() {
+            writeln!(f, "  - {}", quirk)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rim attached to a T300RS or TX base, by the attachment ID it reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rim {
+    Detached,
+    T300,              // The round rim the T300RS ships with
+    FerrariAlcantara,
+    OpenWheel,         // Open Wheel add-on, F1 style with paddles
+    SparcoR383,
+    Other(u8),
+}
+
+impl Rim {
+    pub fn from_id(id: u8) -> Self {
+        match id {
+            0x00 => Self::Detached,
+            0x04 => Self::FerrariAlcantara,
+            0x06 => Self::T300,
+            0x09 => Self::OpenWheel,
+            0x0A => Self::SparcoR383,
+            _ => Self::Other(id),
+        }
+    }
+
+    /// Attachment ID the base reports for this rim
+    pub fn id(self) -> u8 {
+        match self {
+            Self::Detached => 0x00,
+            Self::FerrariAlcantara => 0x04,
+            Self::T300 => 0x06,
+            Self::OpenWheel => 0x09,
+            Self::SparcoR383 => 0x0A,
+            Self::Other(id) => id,
+        }
+    }
+
+    /// Name of the rim's table under `[input_config.rim_button_mappings]`
+    pub fn key(self) -> String {
+        match self {
+            Self::Detached => "detached".to_string(),
+            Self::T300 => "t300".to_string(),
+            Self::FerrariAlcantara => "ferrari_alcantara".to_string(),
+            Self::OpenWheel => "open_wheel".to_string(),
+            Self::SparcoR383 => "sparco_r383".to_string(),
+            Self::Other(id) => format!("rim_{:02x}", id),
+        }
+    }
+}
+
+impl fmt::Display for Rim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Detached => f.write_str("no rim"),
+            Self::T300 => f.write_str("T300 rim"),
+            Self::FerrariAlcantara => f.write_str("Ferrari Alcantara rim"),
+            Self::OpenWheel => f.write_str("Open Wheel add-on"),
+            Self::SparcoR383 => f.write_str("Sparco R383 rim"),
+            Self::Other(id) => write!(f, "unknown rim 0x{:02X}", id),
+        }
+    }
+}
+
+/// Encoding of the steering axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteeringEncoding {
+    Signed,  // i16, 0 = center
+    Offset,  // u16, 0x8000 = center
+}
+
+/// Units of the rotation range a base reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeEncoding {
+    /// 0xFFFF = 1080°, as the T150 family's range command
+    Full1080,
+    /// 60 units per degree, as the T300RS family's range command
+    Sixtieths,
+}
+
+/// Little-endian word holding the base's current rotation range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeField {
+    pub offset: usize,
+    pub encoding: RangeEncoding,
+}
+
+impl RangeField {
+    /// Degrees of a raw range value, `None` for values no base sets
+    pub fn degrees(&self, raw: u16) -> Option<u16> {
+        let degrees = match self.encoding {
+            RangeEncoding::Full1080 => convert::rescale(raw as u32, u16::MAX as u32, T150_FULL_RANGE) as u16,
+            RangeEncoding::Sixtieths => raw / 0x3C,
+        };
+        (degrees > 0 && degrees as u32 <= T150_FULL_RANGE).then_some(degrees)
+    }
+}
+
+/// Bits of `count` consecutive source buttons starting at `first`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonField {
+    pub offset: usize,
+    pub count: u8,
+    pub first: u8,
+}
+
+/// Where a model puts its controls, offsets in bytes from the report start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportLayout {
+    /// Shortest report accepted, fields past it are optional
+    pub min_len: usize,
+    pub steering: usize,
+    pub steering_encoding: SteeringEncoding,
+    /// Throttle, brake and clutch
+    pub pedals: [usize; 3],
+    /// Pedal resolution, 8 bits are one byte, wider ones a little-endian word
+    pub pedal_bits: u8,
+    pub buttons: &'static [ButtonField],
+    /// Hat switch in the lower nibble
+    pub hat: usize,
+    /// Current rotation range, on bases that report it
+    pub range: Option<RangeField>,
+    /// Attachment ID of the rim, on bases that report it
+    pub rim: Option<usize>,
+}
+
+const GENERIC_LAYOUT: ReportLayout = ReportLayout {
+    min_len: 8,
+    steering: 0,
+    steering_encoding: SteeringEncoding::Signed,
+    pedals: [2, 3, 4],
+    pedal_bits: 8,
+    buttons: &[ButtonField { offset: 5, count: 16, first: 0 }, ButtonField { offset: 8, count: 16, first: 16 }],
+    hat: 7,
+    range: None,
+    rim: None,
+};
+
+/// T150 and TMX: report ID, steering, 10-bit pedals, 13 buttons
+const T150_LAYOUT: ReportLayout = ReportLayout {
+    min_len: 12,
+    steering: 1,
+    steering_encoding: SteeringEncoding::Offset,
+    pedals: [3, 5, 7],
+    pedal_bits: 10,
+    buttons: &[ButtonField { offset: 9, count: 13, first: 0 }],
+    hat: 11,
+    range: Some(RangeField { offset: 12, encoding: RangeEncoding::Full1080 }),
+    rim: None,
+};
+
+/// T300RS and TX: 16-bit pedals, base and rim buttons
+const T300_LAYOUT: ReportLayout = ReportLayout {
+    min_len: 14,
+    steering: 1,
+    steering_encoding: SteeringEncoding::Offset,
+    pedals: [3, 5, 7],
+    pedal_bits: 16,
+    buttons: &[ButtonField { offset: 9, count: 26, first: 0 }],
+    hat: 13,
+    range: Some(RangeField { offset: 14, encoding: RangeEncoding::Sixtieths }),
+    rim: Some(16),
+};
+
+/// T248: as the T300RS, plus the display and rotary encoder buttons
+const T248_LAYOUT: ReportLayout = ReportLayout {
+    min_len: 14,
+    steering: 1,
+    steering_encoding: SteeringEncoding::Offset,
+    pedals: [3, 5, 7],
+    pedal_bits: 16,
+    buttons: &[ButtonField { offset: 9, count: 32, first: 0 }],
+    hat: 13,
+    range: Some(RangeField { offset: 14, encoding: RangeEncoding::Sixtieths }),
+    rim: None,
+};
+
+impl ReportLayout {
+    /// Steering axis as a signed value, 0 = center
+    pub fn steering(&self, raw: u16) -> i16 {
+        match self.steering_encoding {
+            SteeringEncoding::Signed => raw as i16,
+            SteeringEncoding::Offset => convert::offset_to_signed(raw),
+        }
+    }
+
+    /// Pedal axis at this layout's resolution, narrowed to 8 bits
+    pub fn pedal(&self, raw: u32) -> u8 {
+        convert::rescale(raw, (1u32 << self.pedal_bits) - 1, u8::MAX as u32) as u8
+    }
+
+    /// Length of a report carrying every field, optional ones included
+    pub fn full_len(&self) -> usize {
+        let buttons = self.buttons.iter().map(|field| field.offset + (field.count as usize).div_ceil(8));
+        let range = self.range.map(|field| field.offset + 2);
+        let rim = self.rim.map(|offset| offset + 1);
+        buttons.chain(range).chain(rim).fold(self.min_len, usize::max)
+    }
+
+    pub fn parse(&self, data: &[u8]) -> Result<ThrustmasterInputReport> {
+        if data.len() < self.min_len {
+            return Err(TranslatorError::invalid_report(format!("Input report too short: {} bytes", data.len())));
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let pedal = |offset: usize| match self.pedal_bits {
+            8 => data[offset],
+            _ => self.pedal(word(offset) as u32),
+        };
+
+        let mut buttons = 0u32;
+        for field in self.buttons {
+            let bytes = (field.count as usize).div_ceil(8);
+            let Some(raw) = data.get(field.offset..field.offset + bytes) else {
+                continue;
+            };
+            let bits = raw.iter().rev().fold(0u32, |bits, &byte| bits << 8 | byte as u32);
+            let mask = if field.count >= 32 { u32::MAX } else { (1 << field.count) - 1 };
+            if field.first < SOURCE_BUTTON_COUNT {
+                buttons |= (bits & mask) << field.first;
+            }
+        }
+
+        Ok(ThrustmasterInputReport {
+            steering: self.steering(word(self.steering)),
+            throttle: pedal(self.pedals[0]),
+            brake: pedal(self.pedals[1]),
+            clutch: pedal(self.pedals[2]),
+            buttons,
+            // Values past 8 are the HID null state
+            dpad: HatDirection::from_raw(data[self.hat] & 0x0F).unwrap_or_default(),
+            base_range: self.range.and_then(|field| {
+                let raw = data.get(field.offset..field.offset + 2)?;
+                field.degrees(u16::from_le_bytes([raw[0], raw[1]]))
+            }),
+            rim: self.rim.and_then(|offset| data.get(offset)).map(|&id| Rim::from_id(id)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_models_parse_their_own_layouts() {
+        let generic = ThrustmasterModel::from_pid(0x0004).layout();
+        let report = generic.parse(&[0x00, 0x80, 10, 20, 30, 0x01, 0x80, 0x02, 0x01, 0x00]).unwrap();
+        assert_eq!(report.steering, i16::MIN);
+        assert_eq!((report.throttle, report.brake, report.clutch), (10, 20, 30));
+        assert_eq!(report.buttons, 0x0001_8001);
+        assert_eq!(report.dpad, HatDirection::East);
+
+        let t300 = ThrustmasterModel::from_pid(0xB66E).layout();
+        let mut data = [0u8; 14];
+        data[1..3].copy_from_slice(&0x8000u16.to_le_bytes());
+        data[3..5].copy_from_slice(&u16::MAX.to_le_bytes());
+        data[5..7].copy_from_slice(&0x8000u16.to_le_bytes());
+        data[9..13].copy_from_slice(&(1u32 << 25 | 1 << 30).to_le_bytes());
+        data[13] = 0x0F;
+        let report = t300.parse(&data).unwrap();
+        assert_eq!(report.steering, 0);
+        assert_eq!((report.throttle, report.brake, report.clutch), (255, 128, 0));
+        // The T300RS has 26 buttons, bit 30 is not one of them
+        assert_eq!(report.buttons, 1 << 25);
+        assert_eq!(report.dpad, HatDirection::Centered);
+
+        let t150 = ThrustmasterModel::T150.layout();
+        let mut data = [0u8; 12];
+        data[3..5].copy_from_slice(&0x3FFu16.to_le_bytes());
+        assert_eq!(t150.parse(&data).unwrap().throttle, 255);
+        assert!(t150.parse(&data[..11]).is_err());
+    }
+
+    #[test]
+    fn test_reported_range_is_decoded_per_model() {
+        let mut data = [0u8; 16];
+        data[14..16].copy_from_slice(&(540u16 * 0x3C).to_le_bytes());
+        assert_eq!(ThrustmasterModel::T300rs.layout().parse(&data).unwrap().base_range, Some(540));
+        // Reports ending before the range carry none
+        assert_eq!(ThrustmasterModel::T300rs.layout().parse(&data[..14]).unwrap().base_range, None);
+
+        let mut data = [0u8; 14];
+        data[12..14].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_eq!(ThrustmasterModel::T150.layout().parse(&data).unwrap().base_range, Some(1080));
+        assert_eq!(ThrustmasterModel::Generic.layout().parse(&[0u8; 16]).unwrap().base_range, None);
+
+        let mut data = [0u8; 17];
+        data[16] = 0x09;
+        assert_eq!(ThrustmasterModel::Tx.layout().parse(&data).unwrap().rim, Some(Rim::OpenWheel));
+        assert_eq!(ThrustmasterModel::T300rs.layout().parse(&data[..16]).unwrap().rim, None);
+        assert_eq!(Rim::from_id(0x0C).key(), "rim_0c");
+    }
+
+    #[test]
+    fn test_descriptions_come_from_the_registry() {
+        let t300 = ModelDescription::new(0xB66F).to_string();
+        assert!(t300.starts_with("PID b66f: T300RS\n"));
+        assert!(t300.contains("  rim       byte 16\n"));
+        assert!(t300.contains("Rotation range: 40-1080 degrees\n"));
+
+        let iforce = ModelDescription::new(0x0004).to_string();
+        assert!(iforce.contains("Autocenter: IFORCE autocenter command"));
+        assert!(!iforce.contains("Ramp"));
+        assert!(!ModelDescription::new(0x1234).is_known());
+    }
+
+    #[test]
+    fn test_range_reports_are_clamped_and_scaled_per_model() {
+        assert_eq!(ThrustmasterModel::T300rs.clamp_range(1080), Some(1080));
+        assert_eq!(ThrustmasterModel::T248.clamp_range(1080), Some(900));
+        assert_eq!(ThrustmasterModel::T150.clamp_range(180), Some(270));
+        assert_eq!(ThrustmasterModel::Generic.clamp_range(900), None);
+
+        // 900° × 60 = 0xD2F0
+        assert_eq!(ThrustmasterModel::T300rs.range_report(900), Some(vec![0x60, 0x08, 0x11, 0xF0, 0xD2]));
+        assert_eq!(ThrustmasterModel::T150.range_report(1080), Some(vec![0x40, 0x11, 0xFF, 0xFF]));
+        assert_eq!(ThrustmasterModel::Generic.range_report(900), None);
+    }
+}
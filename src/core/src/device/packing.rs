@@ -0,0 +1,112 @@
+//! Final byte layout of encoded G29 reports
+//!
+//! `G29ReportEncoder` decides which value every descriptor field gets; a
+//! `ReportPacking` decides how those values end up in bytes. The platform HID
+//! stacks take reports exactly as the descriptor lays them out, but some
+//! third-party virtual bus drivers want the report ID passed separately or
+//! axes in a different byte order. Supporting another driver means adding a
+//! `ReportPacking` for it, the encoder stays as it is.
+
+use crate::config::G29Packing;
+use crate::device::descriptors::HidField;
+use std::fmt;
+use std::sync::Arc;
+
+/// How encoded reports are laid out for one virtual bus driver
+pub trait ReportPacking: fmt::Debug + Send + Sync {
+    /// Whether reports start with their report ID byte
+    fn report_id_prefix(&self) -> bool {
+        true
+    }
+
+    /// Write `value` for `field` into the report body, which excludes the report ID
+    fn write_field(&self, body: &mut [u8], field: &HidField, value: u32) {
+        write_bits(body, field.bit_offset, field.bit_size, value);
+    }
+}
+
+/// Reports as the descriptor lays them out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawHid;
+
+impl ReportPacking for RawHid {}
+
+/// Report body without the report ID byte
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoReportId;
+
+impl ReportPacking for NoReportId {
+    fn report_id_prefix(&self) -> bool {
+        false
+    }
+}
+
+/// Byte-aligned multi-byte fields most significant byte first
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BigEndian;
+
+impl ReportPacking for BigEndian {
+    fn write_field(&self, body: &mut [u8], field: &HidField, value: u32) {
+        let bytes = field.bit_size / 8;
+        if field.bit_offset % 8 != 0 || field.bit_size % 8 != 0 || !(2..=4).contains(&bytes) {
+            // Bit fields (buttons, hat) have no byte order
+            write_bits(body, field.bit_offset, field.bit_size, value);
+            return;
+        }
+        let start = field.bit_offset / 8;
+        if let Some(target) = body.get_mut(start..start + bytes) {
+            target.copy_from_slice(&value.to_be_bytes()[4 - bytes..]);
+        }
+    }
+}
+
+/// Packing selected in the configuration
+pub fn packing_for(packing: G29Packing) -> Arc<dyn ReportPacking> {
+    match packing {
+        G29Packing::RawHid => Arc::new(RawHid),
+        G29Packing::NoReportId => Arc::new(NoReportId),
+        G29Packing::BigEndian => Arc::new(BigEndian),
+    }
+}
+
+/// Write the low `bit_size` bits of `value` little-endian at `bit_offset`
+pub fn write_bits(buf: &mut [u8], bit_offset: usize, bit_size: usize, value: u32) {
+    for bit in 0..bit_size.min(32) {
+        if value & (1 << bit) != 0 {
+            let position = bit_offset + bit;
+            if let Some(byte) = buf.get_mut(position / 8) {
+                *byte |= 1 << (position % 8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::G29Mode;
+    use crate::device::{G29InputReport, G29ReportEncoder};
+
+    #[test]
+    fn test_packings_rearrange_the_raw_report() {
+        let report = G29InputReport {
+            report_id: 0x01,
+            steering: 0x1234,
+            throttle: 0,
+            brake: 0,
+            clutch: 0,
+            buttons: 8 << 24,
+            unused: [0; 4],
+        };
+        let raw = G29ReportEncoder::new(G29Mode::Ps4).unwrap().encode(&report);
+
+        let bare = G29ReportEncoder::with_packing(G29Mode::Ps4, Arc::new(NoReportId)).unwrap();
+        assert_eq!(bare.report_len(), raw.len() - 1);
+        assert_eq!(bare.encode(&report), raw[1..]);
+
+        let big = G29ReportEncoder::with_packing(G29Mode::Ps4, Arc::new(BigEndian)).unwrap().encode(&report);
+        assert_eq!(&raw[56..58], &[0x34, 0x12]);
+        assert_eq!(&big[56..58], &[0x12, 0x34]);
+        assert_eq!(big[..56], raw[..56]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Thrustmaster pedal sets with vibration motors, and standalone pedal sets
+//!
+//! Newer Thrustmaster pedals (T3PM and the vibration kit for the T-LCM) expose
+//! one motor per pedal through a vendor output report. They enumerate as their
+//! own HID device next to the wheel base, so they are found by vendor ID and
+//! product string rather than through the wheel's configuration.
+//!
+//! A pedal set on its own USB connection can also stand in for the pedals of
+//! the wheel. Each configured `PedalSetConfig` carries the calibration of
+//! one physical set, keyed by its USB identity, so the aggregation layer
+//! applies the right travel whichever set is plugged into the rig. `assign`
+//! routes each of the set's axes to a wheel pedal or leaves it out, so e.g.
+//! a T-LCM can supply only the brake while the wheel's own pedals keep
+//! throttle and clutch. Load cell sets reporting 16-bit axes are read with
+//! `wide_axes`.
+
+use crate::config::{AxisCalibration, PedalAxis, PedalRumbleConfig, PedalSetConfig};
+use crate::device::ThrustmasterInputReport;
+use crate::error::{Result, TranslatorError};
+use hidapi::{HidApi, HidDevice};
+
+/// Vendor output report carrying the motor intensities
+///
+/// Thrustmaster publishes no protocol for the pedal motors and no capture of
+/// the report is on hand. The ID and the one-byte-per-pedal layout after it
+/// are unverified, so the motors only run with the `pedal_vibration`
+/// experimental feature.
+const VIBRATION_REPORT_ID: u8 = 0x60;
+
+/// Product strings of pedal sets known to have vibration motors
+const VIBRATION_PEDAL_NAMES: &[&str] = &["T3PM", "T-LCM"];
+
+/// Motor intensity for each pedal, 0 = off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PedalVibration {
+    pub throttle: u8,
+    pub brake: u8,
+    pub clutch: u8,
+}
+
+pub struct VibrationPedals {
+    device: HidDevice,
+    name: String,
+}
+
+impl VibrationPedals {
+    /// Find and open a vibration-capable pedal set, `Ok(None)` when none is plugged in
+    pub fn detect(config: &PedalRumbleConfig) -> Result<Option<Self>> {
+        let api = HidApi::new()?;
+
+        let found = api.device_list().find(|dev| {
+            if dev.vendor_id() != config.vid {
+                return false;
+            }
+            match config.pid {
+                Some(pid) => dev.product_id() == pid,
+                None => dev
+                    .product_string()
+                    .map(|name| VIBRATION_PEDAL_NAMES.iter().any(|known| name.contains(known)))
+                    .unwrap_or(false),
+            }
+        });
+
+        let Some(info) = found else {
+            return Ok(None);
+        };
+
+        let name = info.product_string().unwrap_or("pedals").to_string();
+        let device = info.open_device(&api)?;
+        tracing::info!("Found vibration pedals: {}", name);
+
+        Ok(Some(Self { device, name }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the motor intensities, replacing the previous state
+    pub fn send(&self, vibration: PedalVibration) -> Result<()> {
+        let report = [VIBRATION_REPORT_ID, vibration.throttle, vibration.brake, vibration.clutch];
+        self.device
+            .write(&report)
+            .map(|_| ())
+            .map_err(|e| TranslatorError::protocol_error(format!("Pedal vibration write failed: {}", e)))
+    }
+}
+
+impl Drop for VibrationPedals {
+    fn drop(&mut self) {
+        // An aborted rumble task must not leave the motors running
+        let _ = self.send(PedalVibration::default());
+    }
+}
+
+/// Largest pedal set report read
+const PEDAL_REPORT_LEN: usize = 64;
+
+impl AxisCalibration {
+    /// Stretch a raw reading between `min` and `max` over the full axis
+    pub fn apply(&self, raw: u8) -> u8 {
+        let (low, high) = (self.min.min(self.max), self.min.max(self.max));
+        let span = (high - low).max(1) as u32;
+        let value = ((raw.clamp(low, high) - low) as u32 * u8::MAX as u32 / span) as u8;
+        if self.min > self.max {
+            u8::MAX - value
+        } else {
+            value
+        }
+    }
+}
+
+/// Calibrated pedal positions of a standalone pedal set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PedalSetState {
+    /// Cleared when the set is unplugged, the wheel's own pedals apply again
+    pub connected: bool,
+    pub throttle: u8,
+    pub brake: u8,
+    pub clutch: u8,
+    /// Wheel axes the set drives, bit 0 throttle, 1 brake, 2 clutch
+    pub driven: u8,
+}
+
+impl PedalSetState {
+    /// Replace the wheel's pedal axes the set drives while it is connected
+    pub fn merge_into(&self, report: &mut ThrustmasterInputReport) {
+        if !self.connected {
+            return;
+        }
+        if self.driven & 0b001 != 0 {
+            report.throttle = self.throttle;
+        }
+        if self.driven & 0b010 != 0 {
+            report.brake = self.brake;
+        }
+        if self.driven & 0b100 != 0 {
+            report.clutch = self.clutch;
+        }
+    }
+}
+
+pub struct PedalSet {
+    device: HidDevice,
+    config: PedalSetConfig,
+}
+
+impl PedalSet {
+    /// Open the first of `pedal_sets` that is plugged in, `Ok(None)` when none is
+    pub fn detect(pedal_sets: &[PedalSetConfig]) -> Result<Option<Self>> {
+        let api = HidApi::new()?;
+
+        for set in pedal_sets {
+            let found = api.device_list().find(|dev| {
+                dev.vendor_id() == set.vid
+                    && dev.product_id() == set.pid
+                    && set.serial_number.as_deref().map_or(true, |serial| dev.serial_number() == Some(serial))
+            });
+            if let Some(info) = found {
+                let device = info.open_device(&api)?;
+                tracing::info!("Found pedal set {}, applying its calibration", set.name);
+                return Ok(Some(Self { device, config: set.clone() }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Wait up to `timeout_ms` for the next report, `Ok(None)` on timeout
+    pub fn read(&mut self, timeout_ms: i32) -> Result<Option<PedalSetState>> {
+        let mut buf = [0u8; PEDAL_REPORT_LEN];
+        match self.device.read_timeout(&mut buf, timeout_ms)? {
+            0 => Ok(None),
+            n => parse_pedal_report(&buf[..n], &self.config).map(Some),
+        }
+    }
+}
+
+/// Read, calibrate and assign the axes of a pedal set report
+pub fn parse_pedal_report(data: &[u8], config: &PedalSetConfig) -> Result<PedalSetState> {
+    let axis = |index: usize| {
+        let offset = config.axis_bytes[index] as usize;
+        // The high byte carries a 16-bit axis at the pedal report's resolution
+        let offset = if config.wide_axes { offset + 1 } else { offset };
+        data.get(offset).copied().ok_or_else(|| {
+            TranslatorError::invalid_report(format!("{} report too short: {} bytes", config.name, data.len()))
+        })
+    };
+
+    let mut state = PedalSetState { connected: true, ..Default::default() };
+    let calibrations = [&config.throttle, &config.brake, &config.clutch];
+    for (index, target) in config.assign.iter().enumerate() {
+        let (slot, bit) = match target {
+            PedalAxis::Throttle => (&mut state.throttle, 0b001),
+            PedalAxis::Brake => (&mut state.brake, 0b010),
+            PedalAxis::Clutch => (&mut state.clutch, 0b100),
+            PedalAxis::Off => continue,
+        };
+        *slot = calibrations[index].apply(axis(index)?);
+        state.driven |= bit;
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pedal_report_is_calibrated_per_set() {
+        let config = PedalSetConfig {
+            name: "T3PA".to_string(),
+            vid: 0x044F,
+            pid: 0xB678,
+            serial_number: None,
+            device: None,
+            axis_bytes: PedalSetConfig::default_axis_bytes(),
+            wide_axes: false,
+            assign: PedalSetConfig::default_assign(),
+            throttle: AxisCalibration { min: 20, max: 220 },
+            brake: AxisCalibration::default(),
+            clutch: AxisCalibration { min: 250, max: 10 },
+        };
+
+        let state = parse_pedal_report(&[0x01, 220, 128, 250], &config).unwrap();
+        assert_eq!(state, PedalSetState { connected: true, throttle: 255, brake: 128, clutch: 0, driven: 0b111 });
+        assert_eq!(parse_pedal_report(&[0x01, 10, 0, 10], &config).unwrap().throttle, 0);
+        assert_eq!(parse_pedal_report(&[0x01, 0, 0, 10], &config).unwrap().clutch, 255);
+        assert!(parse_pedal_report(&[0x01, 0], &config).is_err());
+    }
+
+    #[test]
+    fn test_assigned_axes_replace_only_their_wheel_pedals() {
+        // Load cell brake only, 16-bit axis at bytes 3-4
+        let config = PedalSetConfig {
+            name: "T-LCM".to_string(),
+            vid: 0x044F,
+            pid: 0xB000,
+            serial_number: None,
+            device: None,
+            axis_bytes: [1, 3, 5],
+            wide_axes: true,
+            assign: [PedalAxis::Off, PedalAxis::Brake, PedalAxis::Off],
+            throttle: AxisCalibration::default(),
+            brake: AxisCalibration::default(),
+            clutch: AxisCalibration::default(),
+        };
+
+        let state = parse_pedal_report(&[0x01, 0, 0, 0x34, 0xC0], &config).unwrap();
+        assert_eq!(state.driven, 0b010);
+        let mut report = crate::testing::neutral_report();
+        report.throttle = 77;
+        state.merge_into(&mut report);
+        assert_eq!((report.throttle, report.brake), (77, 0xC0));
+    }
+}
@@ -0,0 +1,118 @@
+//! Thrustmaster TH8A shifter
+//!
+//! The TH8A enumerates as its own HID device. In H-pattern mode it reports
+//! the engaged gear as one button per slot, 1 - 7 and reverse, and nothing in
+//! neutral. The aggregation layer reads it on its own thread, like the pedal
+//! sets looking for it again while it is unplugged, and the engaged gear
+//! presses the G29 shifter button `[shifter_config]` assigns to it. Those go
+//! straight into the G29 report, after `button_mapping`, so the wheel's own
+//! buttons keep their mapping. The G29's Driving Force Shifter has no 7th
+//! gear, so by default the TH8A's 7th is not sent.
+
+use crate::config::ShifterConfig;
+use crate::error::{Result, TranslatorError};
+use hidapi::{HidApi, HidDevice};
+
+/// Product string fragment identifying the TH8A
+const TH8A_PRODUCT_NAME: &str = "TH8A";
+
+/// Largest shifter report read
+const SHIFTER_REPORT_LEN: usize = 64;
+
+/// Buttons on the G29, shifter buttons outside them are not sent
+const G29_BUTTONS: u8 = 24;
+
+/// Slot of the reverse gear in `ShifterConfig::gear_buttons`
+pub const REVERSE: u8 = 8;
+
+/// Gear engaged on the shifter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShifterState {
+    /// 0 = neutral, 1 - 7, `REVERSE`
+    pub gear: u8,
+}
+
+/// Parse a TH8A report, `[report_id, gears]` with bit n = gear n + 1 and bit 7 = reverse
+pub fn parse_shifter_report(data: &[u8]) -> Result<ShifterState> {
+    let Some(&gears) = data.get(1) else {
+        return Err(TranslatorError::invalid_report(format!("TH8A report too short: {} bytes", data.len())));
+    };
+    // Between gates two slots can close at once, the lower one wins
+    let gear = if gears == 0 { 0 } else { gears.trailing_zeros() as u8 + 1 };
+    Ok(ShifterState { gear })
+}
+
+impl ShifterState {
+    /// G29 buttons this gear presses as configured
+    pub fn g29_buttons(&self, config: &ShifterConfig) -> u32 {
+        match self.gear {
+            1..=REVERSE => match config.gear_buttons[self.gear as usize - 1] {
+                button if button < G29_BUTTONS => 1 << button,
+                _ => 0,
+            },
+            _ => 0,
+        }
+    }
+}
+
+pub struct Th8aShifter {
+    device: HidDevice,
+    name: String,
+}
+
+impl Th8aShifter {
+    /// Find and open the shifter, `Ok(None)` when it is not plugged in
+    pub fn detect(config: &ShifterConfig) -> Result<Option<Self>> {
+        let api = HidApi::new()?;
+
+        let found = api.device_list().find(|dev| {
+            dev.vendor_id() == config.vid
+                && match config.pid {
+                    Some(pid) => dev.product_id() == pid,
+                    None => dev.product_string().map_or(false, |name| name.contains(TH8A_PRODUCT_NAME)),
+                }
+        });
+        let Some(info) = found else {
+            return Ok(None);
+        };
+
+        let name = info.product_string().unwrap_or(TH8A_PRODUCT_NAME).to_string();
+        let device = info.open_device(&api)?;
+        tracing::info!("Found shifter {}", name);
+
+        Ok(Some(Self { device, name }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait up to `timeout_ms` for the next report, `Ok(None)` on timeout
+    pub fn read(&mut self, timeout_ms: i32) -> Result<Option<ShifterState>> {
+        let mut buf = [0u8; SHIFTER_REPORT_LEN];
+        match self.device.read_timeout(&mut buf, timeout_ms)? {
+            0 => Ok(None),
+            n => parse_shifter_report(&buf[..n]).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gears_press_g29_shifter_buttons() {
+        let config = ShifterConfig::default();
+        let gear = |gears: u8| parse_shifter_report(&[0x01, gears]).unwrap();
+
+        assert_eq!(gear(0x00).g29_buttons(&config), 0);
+        assert_eq!(gear(0x01).g29_buttons(&config), 1 << 12);
+        assert_eq!(gear(0x20).g29_buttons(&config), 1 << 17);
+        assert_eq!(gear(0x80), ShifterState { gear: REVERSE });
+        assert_eq!(gear(0x80).g29_buttons(&config), 1 << 18);
+        // No 7th gear on the G29 shifter
+        assert_eq!(gear(0x40).g29_buttons(&config), 0);
+        assert!(parse_shifter_report(&[0x01]).is_err());
+    }
+}
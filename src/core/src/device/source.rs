@@ -0,0 +1,523 @@
+//! Read paths for Thrustmaster input reports
+//!
+//! The same wheel can be read through hidapi (polled or with a short
+//! timeout) and, on Linux, directly from its hidraw node or from the evdev
+//! node the kernel driver creates. Which one has the lowest latency depends
+//! on the platform, the kernel and the hidapi backend, so with
+//! `ReadStrategy::Auto` every available path is benchmarked at startup and
+//! the one delivering reports most promptly is kept.
+//!
+//! With `InputWakeup::Event` the chosen path is read on an `EventReader`
+//! thread that blocks until the wheel reports and hands each report to the
+//! input loop through a channel, instead of the loop polling every tick.
+
+use crate::config::ReadStrategy;
+use crate::device::models::{ReportLayout, MAX_INPUT_REPORT_LEN};
+use crate::device::ThrustmasterInputReport;
+use crate::error::Result;
+use hidapi::{DeviceInfo, HidApi, HidDevice};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How long each candidate is sampled during auto-detection
+const BENCHMARK_WINDOW: Duration = Duration::from_millis(250);
+
+/// Timeout of a hidapi blocking read
+const BLOCKING_READ_TIMEOUT_MS: i32 = 1;
+
+/// How long the event reader blocks on hidapi before checking for shutdown
+const EVENT_READ_TIMEOUT_MS: i32 = 50;
+
+/// Reports the event reader may queue ahead of the input loop
+const EVENT_QUEUE_LEN: usize = 64;
+
+impl fmt::Display for ReadStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReadStrategy::Auto => "auto",
+            ReadStrategy::HidapiNonBlocking => "hidapi-nonblocking",
+            ReadStrategy::HidapiBlocking => "hidapi-blocking",
+            ReadStrategy::Hidraw => "hidraw",
+            ReadStrategy::Evdev => "evdev",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Result of sampling one read path
+#[derive(Debug, Clone)]
+pub struct StrategyBenchmark {
+    pub strategy: ReadStrategy,
+    pub reports: usize,
+    /// Mean time between two reports arriving, `None` below two reports
+    pub mean_interval: Option<Duration>,
+    /// Time until the first report arrived
+    pub first_report: Option<Duration>,
+    pub error: Option<String>,
+}
+
+/// Resolution the inter-arrival times are compared at, finer differences are noise
+const INTERVAL_RESOLUTION_US: u128 = 100;
+
+/// Read path in use and why it was chosen
+#[derive(Debug, Clone)]
+pub struct StrategySelection {
+    pub strategy: ReadStrategy,
+    /// The strategy was set in the configuration instead of benchmarked
+    pub configured: bool,
+    pub benchmarks: Vec<StrategyBenchmark>,
+}
+
+impl fmt::Display for StrategySelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let how = if self.configured { "configured" } else { "auto-detected" };
+        write!(f, "{} ({})", self.strategy, how)
+    }
+}
+
+/// Reader for one of the direct paths that bypass hidapi
+pub enum DirectReader {
+    #[cfg(target_os = "linux")]
+    Hidraw(std::fs::File, &'static ReportLayout),
+    #[cfg(target_os = "linux")]
+    Evdev(linux::EvdevReader),
+}
+
+impl DirectReader {
+    /// Read one report without blocking, `None` if nothing is pending
+    pub fn read(&mut self) -> Result<Option<ThrustmasterInputReport>> {
+        match self {
+            #[cfg(target_os = "linux")]
+            DirectReader::Hidraw(file, layout) => linux::read_hidraw(file, layout),
+            #[cfg(target_os = "linux")]
+            DirectReader::Evdev(reader) => reader.read(),
+            #[allow(unreachable_patterns)]
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Read one report in `layout` through hidapi, `None` if nothing arrived
+pub fn read_hidapi(device: &HidDevice, blocking: bool, layout: &ReportLayout) -> Result<Option<ThrustmasterInputReport>> {
+    read_hidapi_timeout(device, if blocking { BLOCKING_READ_TIMEOUT_MS } else { 0 }, layout)
+}
+
+/// Wait up to `timeout_ms` for one report through hidapi
+fn read_hidapi_timeout(
+    device: &HidDevice,
+    timeout_ms: i32,
+    layout: &ReportLayout,
+) -> Result<Option<ThrustmasterInputReport>> {
+    let mut buf = [0u8; MAX_INPUT_REPORT_LEN];
+
+    match device.read_timeout(&mut buf, timeout_ms)? {
+        0 => Ok(None), // No data available
+        n => layout.parse(&buf[..n]).map(Some),
+    }
+}
+
+/// Open the direct reader for `strategy`, `None` for the hidapi strategies
+///
+/// A `blocking` reader waits in `read` until the wheel reports.
+pub fn open_direct(
+    strategy: ReadStrategy,
+    info: &DeviceInfo,
+    blocking: bool,
+    layout: &'static ReportLayout,
+) -> Result<Option<DirectReader>> {
+    match strategy {
+        ReadStrategy::Auto | ReadStrategy::HidapiNonBlocking | ReadStrategy::HidapiBlocking => Ok(None),
+        #[cfg(target_os = "linux")]
+        ReadStrategy::Hidraw => {
+            linux::open_hidraw(info, blocking).map(|file| Some(DirectReader::Hidraw(file, layout)))
+        }
+        #[cfg(target_os = "linux")]
+        ReadStrategy::Evdev => {
+            linux::EvdevReader::open(info, blocking, layout).map(|reader| Some(DirectReader::Evdev(reader)))
+        }
+        #[cfg(not(target_os = "linux"))]
+        ReadStrategy::Hidraw | ReadStrategy::Evdev => {
+            let _ = (info, blocking, layout);
+            Err(crate::error::TranslatorError::UnsupportedPlatform)
+        }
+    }
+}
+
+/// Reports delivered by an `EventReader`, an error ends the stream
+pub type InputEvents = mpsc::Receiver<Result<ThrustmasterInputReport>>;
+
+/// Handle the event reader thread waits on
+enum EventSource {
+    /// A second hidapi handle, read with a timeout so shutdown is prompt
+    Hidapi(HidDevice, &'static ReportLayout),
+    /// A direct reader opened in blocking mode
+    Direct(DirectReader),
+}
+
+impl EventSource {
+    fn read(&mut self) -> Result<Option<ThrustmasterInputReport>> {
+        match self {
+            EventSource::Hidapi(device, layout) => read_hidapi_timeout(device, EVENT_READ_TIMEOUT_MS, layout),
+            EventSource::Direct(reader) => reader.read(),
+        }
+    }
+}
+
+/// Thread that reads the wheel as reports arrive
+pub struct EventReader {
+    stop: Arc<AtomicBool>,
+    /// Only joined for hidapi, a blocked direct read has no timeout
+    thread: Option<JoinHandle<()>>,
+    events: Option<InputEvents>,
+}
+
+impl EventReader {
+    /// Open the read path for `strategy` again and start reading it on its own thread
+    pub fn spawn(
+        strategy: ReadStrategy,
+        info: &DeviceInfo,
+        api: &HidApi,
+        layout: &'static ReportLayout,
+    ) -> Result<Self> {
+        let (source, joinable) = match open_direct(strategy, info, true, layout)? {
+            Some(reader) => (EventSource::Direct(reader), false),
+            None => {
+                let device = info.open_device(api)?;
+                device.set_blocking_mode(true)?;
+                (EventSource::Hidapi(device, layout), true)
+            }
+        };
+
+        let (sender, events) = mpsc::channel(EVENT_QUEUE_LEN);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            let mut source = source;
+            std::thread::Builder::new().name("tm-g29-wheel".into()).spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(event) = source.read().transpose() else {
+                        continue;
+                    };
+                    let failed = event.is_err();
+                    match sender.try_send(event) {
+                        // The input loop is behind, it catches up on the next report
+                        Err(mpsc::error::TrySendError::Full(_)) => {}
+                        Err(mpsc::error::TrySendError::Closed(_)) => break,
+                        Ok(()) => {}
+                    }
+                    if failed {
+                        break;
+                    }
+                }
+            })?
+        };
+
+        Ok(Self { stop, thread: joinable.then_some(thread), events: Some(events) })
+    }
+
+    /// Receiver for the reports, `None` once taken
+    pub fn take_events(&mut self) -> Option<InputEvents> {
+        self.events.take()
+    }
+}
+
+impl Drop for EventReader {
+    fn drop(&mut self) {
+        // A thread blocked in a direct read ends with the next report or the unplug
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Strategies worth trying on this platform, in order of preference on ties
+pub fn candidates() -> Vec<ReadStrategy> {
+    let mut candidates = vec![ReadStrategy::HidapiNonBlocking, ReadStrategy::HidapiBlocking];
+    if cfg!(target_os = "linux") {
+        candidates.push(ReadStrategy::Hidraw);
+        candidates.push(ReadStrategy::Evdev);
+    }
+    candidates
+}
+
+/// Pick the read path for the wheel described by `info`
+///
+/// A configured strategy is used as-is. `Auto` samples every candidate for
+/// a short window and keeps the one `rank` prefers. Sampling blocks for
+/// about a second, call it from a blocking task.
+pub fn select_strategy(
+    configured: ReadStrategy,
+    info: &DeviceInfo,
+    device: &HidDevice,
+    layout: &'static ReportLayout,
+) -> StrategySelection {
+    if configured != ReadStrategy::Auto {
+        return StrategySelection { strategy: configured, configured: true, benchmarks: Vec::new() };
+    }
+
+    let benchmarks: Vec<_> = candidates()
+        .into_iter()
+        .map(|strategy| benchmark(strategy, info, device, layout))
+        .collect();
+
+    let strategy = rank(&benchmarks);
+    StrategySelection { strategy, configured: false, benchmarks }
+}
+
+/// Strategy whose reports arrived most often, then soonest, falling back to polled hidapi
+///
+/// How long a read call takes says nothing about latency, a non-blocking
+/// read returns at once whether or not the report is late. The gap between
+/// reports shows a path that merges or drops them, the delay to the first
+/// one a path that buffers. Earlier candidates win ties.
+pub fn rank(benchmarks: &[StrategyBenchmark]) -> ReadStrategy {
+    benchmarks
+        .iter()
+        .filter(|b| b.error.is_none())
+        .filter_map(|b| {
+            let interval = b.mean_interval?.as_micros() / INTERVAL_RESOLUTION_US;
+            Some((b.strategy, (interval, b.first_report?)))
+        })
+        .min_by_key(|(_, key)| *key)
+        .map(|(strategy, _)| strategy)
+        .unwrap_or(ReadStrategy::HidapiNonBlocking)
+}
+
+fn benchmark(
+    strategy: ReadStrategy,
+    info: &DeviceInfo,
+    device: &HidDevice,
+    layout: &'static ReportLayout,
+) -> StrategyBenchmark {
+    let mut result = StrategyBenchmark { strategy, reports: 0, mean_interval: None, first_report: None, error: None };
+
+    let mut direct = match open_direct(strategy, info, false, layout) {
+        Ok(direct) => direct,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let started = Instant::now();
+    let mut last_report = None;
+    while started.elapsed() < BENCHMARK_WINDOW {
+        let report = match (&mut direct, strategy) {
+            (Some(reader), _) => reader.read(),
+            (None, ReadStrategy::HidapiBlocking) => read_hidapi(device, true, layout),
+            (None, _) => read_hidapi(device, false, layout),
+        };
+        match report {
+            Ok(Some(_)) => {
+                let now = Instant::now();
+                result.first_report.get_or_insert(now - started);
+                last_report = Some(now);
+                result.reports += 1;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_micros(100)),
+            Err(e) => {
+                result.error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    if let (Some(first), Some(last)) = (result.first_report, last_report) {
+        if result.reports > 1 {
+            result.mean_interval = Some((last - started - first) / (result.reports - 1) as u32);
+        }
+    }
+    result
+}
+
+#[cfg(target_os = "linux")]
+pub mod linux {
+    use crate::device::models::{ReportLayout, MAX_INPUT_REPORT_LEN};
+    use crate::device::{HatDirection, ThrustmasterInputReport, SOURCE_BUTTON_COUNT};
+    use crate::error::{Result, TranslatorError};
+    use crate::evdev::{self, ABS_HAT0X, ABS_HAT0Y, ABS_RZ, ABS_X, ABS_Y, ABS_Z, EV_ABS, EV_KEY, EV_SYN, SYN_REPORT};
+    use hidapi::DeviceInfo;
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::{Path, PathBuf};
+
+    /// O_NONBLOCK on the architectures we ship for (x86, x86_64, arm, aarch64)
+    const O_NONBLOCK: i32 = 0o4000;
+
+    /// `struct input_event`: a timeval followed by type, code and value
+    const INPUT_EVENT_LEN: usize = 2 * std::mem::size_of::<usize>() + 8;
+
+    fn open_node(path: &Path, blocking: bool) -> Result<File> {
+        let flags = if blocking { 0 } else { O_NONBLOCK };
+        Ok(std::fs::OpenOptions::new().read(true).custom_flags(flags).open(path)?)
+    }
+
+    /// hidraw node hidapi opened the wheel through, e.g. `/dev/hidraw3`
+    fn hidraw_path(info: &DeviceInfo) -> Result<PathBuf> {
+        let path = info.path().to_string_lossy().into_owned();
+        if path.starts_with("/dev/hidraw") {
+            Ok(PathBuf::from(path))
+        } else {
+            Err(TranslatorError::invalid_report(format!("Device path {} is not a hidraw node", path)))
+        }
+    }
+
+    pub fn open_hidraw(info: &DeviceInfo, blocking: bool) -> Result<File> {
+        open_node(&hidraw_path(info)?, blocking)
+    }
+
+    pub fn read_hidraw(file: &mut File, layout: &ReportLayout) -> Result<Option<ThrustmasterInputReport>> {
+        let mut buf = [0u8; MAX_INPUT_REPORT_LEN];
+        match file.read(&mut buf) {
+            Ok(n) => layout.parse(&buf[..n]).map(Some),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// evdev node of the input device created for the wheel's hidraw node
+    fn event_path(info: &DeviceInfo) -> Result<PathBuf> {
+        let hidraw = hidraw_path(info)?;
+        let name = hidraw.file_name().unwrap_or_default();
+        let inputs = Path::new("/sys/class/hidraw").join(name).join("device/input");
+
+        for input in std::fs::read_dir(&inputs)? {
+            for entry in std::fs::read_dir(input?.path())? {
+                let entry = entry?;
+                if entry.file_name().to_string_lossy().starts_with("event") {
+                    return Ok(Path::new("/dev/input").join(entry.file_name()));
+                }
+            }
+        }
+
+        Err(TranslatorError::invalid_report(format!("No evdev node found under {}", inputs.display())))
+    }
+
+    /// Rebuilds Thrustmaster reports from evdev events
+    ///
+    /// hid-generic forwards the HID logical values unchanged, so axis values
+    /// are in the units of the model's report layout.
+    pub struct EvdevReader {
+        file: File,
+        layout: &'static ReportLayout,
+        state: ThrustmasterInputReport,
+        hat: (i32, i32),
+        buf: Vec<u8>,
+    }
+
+    impl EvdevReader {
+        pub fn open(info: &DeviceInfo, blocking: bool, layout: &'static ReportLayout) -> Result<Self> {
+            Ok(Self {
+                file: open_node(&event_path(info)?, blocking)?,
+                layout,
+                state: ThrustmasterInputReport { steering: 0, throttle: 0, brake: 0, clutch: 0, buttons: 0, dpad: HatDirection::Centered, base_range: None, rim: None },
+                hat: (0, 0),
+                buf: Vec::with_capacity(INPUT_EVENT_LEN * 16),
+            })
+        }
+
+        /// Consume pending events, returning the state at the first SYN_REPORT
+        pub fn read(&mut self) -> Result<Option<ThrustmasterInputReport>> {
+            loop {
+                while self.buf.len() >= INPUT_EVENT_LEN {
+                    let event: Vec<u8> = self.buf.drain(..INPUT_EVENT_LEN).collect();
+                    let header = INPUT_EVENT_LEN - 8;
+                    let event_type = u16::from_ne_bytes([event[header], event[header + 1]]);
+                    let code = u16::from_ne_bytes([event[header + 2], event[header + 3]]);
+                    let value = i32::from_ne_bytes([event[header + 4], event[header + 5], event[header + 6], event[header + 7]]);
+
+                    if event_type == EV_SYN && code == SYN_REPORT {
+                        return Ok(Some(self.state));
+                    }
+                    self.apply(event_type, code, value);
+                }
+
+                let mut chunk = [0u8; INPUT_EVENT_LEN * 16];
+                match self.file.read(&mut chunk) {
+                    Ok(0) => return Ok(None),
+                    Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        fn apply(&mut self, event_type: u16, code: u16, value: i32) {
+            let pedal = self.layout.pedal(value.max(0) as u32);
+            match (event_type, code) {
+                (EV_ABS, ABS_X) => {
+                    // Signed layouts report -32768..32767, offset ones 0..65535
+                    self.state.steering = self.layout.steering(value.clamp(i16::MIN as i32, u16::MAX as i32) as u16)
+                }
+                (EV_ABS, ABS_Y) => self.state.throttle = pedal,
+                (EV_ABS, ABS_Z) => self.state.brake = pedal,
+                (EV_ABS, ABS_RZ) => self.state.clutch = pedal,
+                (EV_ABS, ABS_HAT0X) | (EV_ABS, ABS_HAT0Y) => {
+                    if code == ABS_HAT0X {
+                        self.hat.0 = value;
+                    } else {
+                        self.hat.1 = value;
+                    }
+                    self.state.dpad = evdev::hat_from_axes(self.hat.0, self.hat.1);
+                }
+                (EV_KEY, code) => {
+                    if let Some(index) = evdev::button_index(code).filter(|&i| i < SOURCE_BUTTON_COUNT) {
+                        if value != 0 {
+                            self.state.buttons |= 1 << index;
+                        } else {
+                            self.state.buttons &= !(1 << index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampled(strategy: ReadStrategy, interval_us: u64, first_us: u64) -> StrategyBenchmark {
+        StrategyBenchmark {
+            strategy,
+            reports: 200,
+            mean_interval: Some(Duration::from_micros(interval_us)),
+            first_report: Some(Duration::from_micros(first_us)),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_strategies_rank_by_report_arrival() {
+        // hidraw gets every report, polled hidapi merges every other one
+        let benchmarks = [
+            sampled(ReadStrategy::HidapiNonBlocking, 2000, 300),
+            sampled(ReadStrategy::HidapiBlocking, 1010, 900),
+            sampled(ReadStrategy::Hidraw, 1000, 150),
+        ];
+        assert_eq!(rank(&benchmarks), ReadStrategy::Hidraw);
+
+        // Arrival gaps within the resolution tie, the sooner first report wins
+        let benchmarks = [
+            sampled(ReadStrategy::HidapiBlocking, 1040, 900),
+            sampled(ReadStrategy::Evdev, 1020, 200),
+        ];
+        assert_eq!(rank(&benchmarks), ReadStrategy::Evdev);
+
+        // Failed or silent paths are never picked
+        let mut failed = sampled(ReadStrategy::Hidraw, 500, 100);
+        failed.error = Some("permission denied".to_string());
+        let silent = StrategyBenchmark { reports: 0, mean_interval: None, first_report: None, ..sampled(ReadStrategy::Evdev, 0, 0) };
+        let working = sampled(ReadStrategy::HidapiBlocking, 1000, 400);
+        assert_eq!(rank(&[failed.clone(), silent.clone(), working]), ReadStrategy::HidapiBlocking);
+        assert_eq!(rank(&[failed, silent]), ReadStrategy::HidapiNonBlocking);
+    }
+}
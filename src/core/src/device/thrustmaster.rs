@@ -1,15 +1,55 @@
 //! Thrustmaster device communication
 
 use crate::device::{ThrustmasterInputReport, IforceCommand};
-use crate::config::ThrustmasterConfig;
+use crate::device::descriptors::parse_hid_descriptor;
+use crate::device::models::{self, ThrustmasterModel};
+use crate::device::source::{self, DirectReader, EventReader, InputEvents, StrategySelection};
+use crate::config::{InputWakeup, ReadStrategy, ThrustmasterConfig};
 use crate::error::{TranslatorError, Result};
+use crate::ffb::FfbCapabilities;
 use hidapi::{HidApi, HidDevice};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 pub struct ThrustmasterDevice {
     device: Arc<Mutex<HidDevice>>,
-    config: ThrustmasterConfig,
+    /// Second hidapi handle for polled reads, so a blocking read never holds up FFB writes
+    input_device: Option<Mutex<HidDevice>>,
+    direct_reader: Option<Arc<Mutex<DirectReader>>>,
+    /// Set when reports are read on their own thread instead of polled
+    event_reader: Option<EventReader>,
+    read_strategy: StrategySelection,
+    model: ThrustmasterModel,
+    ffb_capabilities: FfbCapabilities,
+    serial_number: Option<String>,
+}
+
+/// Effect kinds the base supports, from its PID descriptor, the model table, or assumed complete
+fn detect_ffb_capabilities(device: &HidDevice, pid: u16) -> FfbCapabilities {
+    let mut descriptor = [0u8; hidapi::MAX_REPORT_DESCRIPTOR_SIZE];
+    let from_descriptor = device
+        .get_report_descriptor(&mut descriptor)
+        .ok()
+        .and_then(|len| parse_hid_descriptor(&descriptor[..len]).ok())
+        .and_then(|info| FfbCapabilities::from_descriptor(&info));
+
+    if let Some(capabilities) = from_descriptor {
+        tracing::info!("FFB effects from report descriptor: {:?}", capabilities.kinds());
+        return capabilities;
+    }
+    if let Some(capabilities) = models::model_capabilities(pid) {
+        tracing::info!("FFB effects from model table: {:?}", capabilities.kinds());
+        return capabilities;
+    }
+    tracing::debug!("Unknown FFB capabilities for PID {:04x}, assuming every effect", pid);
+    FfbCapabilities::all()
+}
+
+/// Open the wheel again for polled input reads, non-blocking like the first handle
+fn open_input_handle(info: &hidapi::DeviceInfo, api: &HidApi) -> Result<HidDevice> {
+    let device = info.open_device(api)?;
+    device.set_blocking_mode(false)?;
+    Ok(device)
 }
 
 impl ThrustmasterDevice {
@@ -32,35 +72,124 @@ impl ThrustmasterDevice {
             device_info.product_string()
         );
 
+        let serial_number = device_info.serial_number().map(str::to_string);
         let device = device_info.open_device(&api)?;
         
         // Set non-blocking mode for input reads
         device.set_blocking_mode(false)?;
 
+        let ffb_capabilities =
+            detect_ffb_capabilities(&device, config.pid).with_hardware_autocenter(models::has_hardware_autocenter(config.pid));
+
+        let model = config.model.unwrap_or_else(|| ThrustmasterModel::from_pid(config.pid));
+        let how = if config.model.is_some() { "configured" } else { "from PID" };
+        tracing::info!("Parsing input reports as {} ({})", model, how);
+        let layout = model.layout();
+
+        // Sampling the read paths blocks for about a second, keep it off the runtime's threads
+        let (configured, info) = (config.read_strategy, device_info.clone());
+        let (mut read_strategy, device) = tokio::task::spawn_blocking(move || {
+            (source::select_strategy(configured, &info, &device, layout), device)
+        })
+        .await
+        .map_err(|e| TranslatorError::protocol_error(format!("Read strategy benchmark failed: {}", e)))?;
+        for benchmark in &read_strategy.benchmarks {
+            tracing::debug!(
+                "Read strategy {}: {} reports, mean interval {:?}, first after {:?}, error {:?}",
+                benchmark.strategy, benchmark.reports, benchmark.mean_interval, benchmark.first_report, benchmark.error
+            );
+        }
+
+        let event_reader = match config.input_wakeup {
+            InputWakeup::Poll => None,
+            InputWakeup::Event => match EventReader::spawn(read_strategy.strategy, device_info, &api, layout) {
+                Ok(reader) => Some(reader),
+                Err(e) => {
+                    tracing::warn!("Cannot read the wheel on its own thread ({}), polling it instead", e);
+                    None
+                }
+            },
+        };
+
+        let direct_reader = match event_reader {
+            Some(_) => None,
+            None => match source::open_direct(read_strategy.strategy, device_info, false, layout) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    tracing::warn!("Cannot use {} read strategy ({}), falling back to hidapi", read_strategy.strategy, e);
+                    read_strategy.strategy = ReadStrategy::HidapiNonBlocking;
+                    None
+                }
+            },
+        };
+        let input_device = match (&event_reader, &direct_reader) {
+            (None, None) => match open_input_handle(device_info, &api) {
+                Ok(handle) => Some(Mutex::new(handle)),
+                Err(e) => {
+                    tracing::warn!("Cannot open a second handle for input ({}), sharing the FFB one", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        let wakeup = if event_reader.is_some() { "event-driven" } else { "polled" };
+        tracing::info!("Reading input with strategy {}, {}", read_strategy, wakeup);
+
         Ok(Self {
             device: Arc::new(Mutex::new(device)),
-            config: config.clone(),
+            input_device,
+            direct_reader: direct_reader.map(|reader| Arc::new(Mutex::new(reader))),
+            event_reader,
+            read_strategy,
+            model,
+            ffb_capabilities,
+            serial_number,
         })
     }
 
+    /// USB serial number of the wheel, if it reports one
+    pub fn serial_number(&self) -> Option<&str> {
+        self.serial_number.as_deref()
+    }
+
+    /// Model whose report layout the input is parsed with
+    pub fn model(&self) -> ThrustmasterModel {
+        self.model
+    }
+
+    /// Effect kinds the base renders natively
+    pub fn ffb_capabilities(&self) -> &FfbCapabilities {
+        &self.ffb_capabilities
+    }
+
+    /// Read path chosen when the device was opened
+    pub fn read_strategy(&self) -> &StrategySelection {
+        &self.read_strategy
+    }
+
+    /// Reports from the event reader thread, `None` when the wheel is polled
+    ///
+    /// The receiver can be taken once; `read_input` keeps returning `None`
+    /// for an event-driven device.
+    pub fn take_input_events(&mut self) -> Option<InputEvents> {
+        self.event_reader.as_mut().and_then(EventReader::take_events)
+    }
+
     /// Read input report from Thrustmaster device
     pub async fn read_input(&self) -> Result<Option<ThrustmasterInputReport>> {
-        let device = self.device.lock().await;
-        let mut buf = [0u8; 8]; // Typical Thrustmaster input report size
-
-        match device.read(&mut buf) {
-            Ok(0) => Ok(None), // No data available
-            Ok(bytes_read) => {
-                if bytes_read >= 8 {
-                    Ok(Some(self.parse_input_report(&buf)))
-                } else {
-                    Err(TranslatorError::invalid_report(format!(
-                        "Input report too short: {} bytes", bytes_read
-                    )))
-                }
-            }
-            Err(e) => Err(TranslatorError::HidError(e)),
+        if self.event_reader.is_some() {
+            return Ok(None);
         }
+        if let Some(reader) = &self.direct_reader {
+            return reader.lock().await.read();
+        }
+
+        let device = match &self.input_device {
+            Some(device) => device.lock().await,
+            None => self.device.lock().await,
+        };
+        let blocking = self.read_strategy.strategy == ReadStrategy::HidapiBlocking;
+        source::read_hidapi(&device, blocking, self.model.layout())
     }
 
     /// Send FFB command to Thrustmaster device
@@ -68,79 +197,62 @@ impl ThrustmasterDevice {
         let device = self.device.lock().await;
         
         // Construct IFORCE packet
-        let packet = self.build_iforce_packet(command)?;
+        let packet = command.to_packet();
         
         tracing::debug!("Sending IFORCE command: {:02x?}", packet);
         
         // Send via USB control transfer or feature report
-        match device.send_feature_report(&packet) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                tracing::warn!("Failed to send FFB command: {:?}", e);
-                Err(TranslatorError::HidError(e))
-            }
-        }
+        // Failures are logged by the caller, which rate-limits them
+        device.send_feature_report(&packet).map_err(TranslatorError::HidError)
     }
 
-    fn parse_input_report(&self, data: &[u8]) -> ThrustmasterInputReport {
-        // Parse Thrustmaster input report format
-        // This is a simplified implementation - real format depends on specific wheel model
-        
-        let steering = i16::from_le_bytes([data[0], data[1]]);
-        let throttle = data[2];
-        let brake = data[3];
-        let clutch = data[4];
-        let buttons = u16::from_le_bytes([data[5], data[6]]);
-        let dpad = data[7] & 0x0F; // Lower 4 bits
-
-        ThrustmasterInputReport {
-            steering,
-            throttle,
-            brake,
-            clutch,
-            buttons,
-            dpad,
+    /// Set the lock-to-lock rotation range, returning the degrees applied
+    ///
+    /// Ranges the base does not support are clamped to its limits.
+    pub async fn set_range(&self, degrees: u16) -> Result<u16> {
+        let applied = self.model.clamp_range(degrees).ok_or_else(|| {
+            TranslatorError::protocol_error(format!("The {} base has no rotation range command", self.model))
+        })?;
+        if applied != degrees {
+            tracing::warn!("The {} base supports no {}° rotation range, using {}°", self.model, degrees, applied);
         }
-    }
+        let report = self.model.range_report(applied).unwrap_or_default();
 
-    fn build_iforce_packet(&self, command: IforceCommand) -> Result<Vec<u8>> {
-        // Build IFORCE packet format
-        // IFORCE packets typically have: [length, command_id, data..., checksum]
-        
-        let mut packet = Vec::new();
-        packet.push((command.data.len() + 2) as u8); // Length including command_id and checksum
-        packet.push(command.command_id);
-        packet.extend_from_slice(&command.data);
-        
-        // Calculate checksum (XOR of all bytes except checksum itself)
-        let checksum = packet.iter().fold(0u8, |acc, &byte| acc ^ byte);
-        packet.push(checksum);
-        
-        Ok(packet)
+        let device = self.device.lock().await;
+        device
+            .write(&report)
+            .map_err(|e| TranslatorError::protocol_error(format!("Rotation range write failed: {}", e)))?;
+        tracing::info!("Rotation range set to {}°", applied);
+        Ok(applied)
     }
 
-    /// Initialize wheel (set range, autocenter, etc.)
-    pub async fn initialize(&self) -> Result<()> {
-        // Send initialization commands
-        let commands = vec![
-            // Set wheel range to configured value
-            IforceCommand {
-                command_id: 0x01, // Set range command
-                data: vec![
-                    (self.config.vid & 0xFF) as u8, // Placeholder for range setting
-                    (self.config.vid >> 8) as u8,
-                ],
-            },
-            // Enable autocenter
-            IforceCommand {
-                command_id: 0x02, // Autocenter command
-                data: vec![0x01], // Enable
-            },
-        ];
+    /// Light the rev LEDs in `mask` at `brightness` (0.0 - 1.0)
+    ///
+    /// LEDs without brightness levels are lit fully, or not at all at zero brightness.
+    pub async fn set_leds(&self, mask: u8, brightness: f32) -> Result<()> {
+        let brightness = (brightness.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+        let (mask, brightness) = match (self.model.dims_leds(), brightness) {
+            (true, _) => (mask, brightness),
+            (false, 0) => (0, u8::MAX),
+            (false, _) => (mask, u8::MAX),
+        };
+        let report = self.model.led_report(mask, brightness).ok_or_else(|| {
+            TranslatorError::protocol_error(format!("The {} base has no rev LEDs", self.model))
+        })?;
+
+        let device = self.device.lock().await;
+        device
+            .write(&report)
+            .map(|_| ())
+            .map_err(|e| TranslatorError::protocol_error(format!("LED write failed: {}", e)))
+    }
 
-        for command in commands {
-            self.send_ffb_command(command).await?;
-            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    /// Initialize wheel (rotation range)
+    ///
+    /// Autocenter is left to the FFB engine, which fades it with game effects.
+    pub async fn initialize(&self, steering_range: u16) -> Result<()> {
+        if let Err(e) = self.set_range(steering_range).await {
+            tracing::warn!("Rotation range not set, the wheel keeps its own: {}", e);
         }
 
         tracing::info!("Thrustmaster device initialized");
@@ -1,68 +1,167 @@
 //! Virtual G29 device implementation
+//!
+//! The device games see is created by a platform crate: uinput on Linux,
+//! ViGEm on Windows, IOHIDUserDevice on macOS. Those crates build on this
+//! one, so the binary registers the backend of its target with
+//! `register_backend` and `VirtualG29Device` presents its reports through it.
 
-use crate::device::{G29InputReport, G29OutputReport};
+use crate::clock::{Clock, SystemClock};
+use crate::device::{G29InputReport, G29OutputReport, G29ReportEncoder};
 use crate::config::G29Config;
 use crate::error::{TranslatorError, Result};
 use tokio::sync::mpsc;
-use std::sync::Arc;
+use tokio::task::JoinHandle;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Channel platform backends push host output reports (FFB) into
+pub type OutputSender = mpsc::UnboundedSender<G29OutputReport>;
+
+/// Future returned by `PlatformBackend` and `BackendFactory`
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Virtual device of a platform crate that games see as the G29
+pub trait PlatformBackend: Send + Sync + 'static {
+    /// Present `report` to games
+    fn send_input(&self, report: G29InputReport) -> BoxFuture<'_, Result<()>>;
+
+    /// Whether games can see the device
+    fn is_connected(&self) -> bool;
+}
+
+/// Create the backend for a configuration, handing every output report games write to the sender
+pub type BackendFactory = fn(G29Config, OutputSender) -> BoxFuture<'static, Result<Box<dyn PlatformBackend>>>;
+
+static BACKEND: OnceLock<BackendFactory> = OnceLock::new();
+
+/// Create every `VirtualG29Device` on the backend `factory` makes
+///
+/// Called once by the binary before a translator is opened; later calls are
+/// ignored.
+pub fn register_backend(factory: BackendFactory) {
+    let _ = BACKEND.set(factory);
+}
 
 pub struct VirtualG29Device {
     config: G29Config,
+    encoder: G29ReportEncoder,
+    platform: Arc<dyn PlatformBackend>,
+    /// `None` with `keep_alive_ms` off
+    keep_alive: Option<Arc<KeepAlive>>,
+    keep_alive_task: Option<JoinHandle<()>>,
     input_sender: mpsc::UnboundedSender<G29InputReport>,
+    /// Keeps the output channel open for backends that never write to it
+    _output_sender: OutputSender,
     output_receiver: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<G29OutputReport>>>,
-    #[cfg(target_os = "windows")]
-    vigem_device: Option<VigEmDevice>,
-    #[cfg(target_os = "linux")]
-    uinput_device: Option<UInputDevice>,
-    #[cfg(target_os = "macos")]
-    virtual_hid_device: Option<VirtualHIDDevice>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SentReport {
+    report: G29InputReport,
+    at: Instant,
+}
+
+/// Last report sent, shared by the input path and the keep-alive timer
+///
+/// Both send while holding the lock, so a repeat already under way finishes
+/// before a newer report goes out and never lands on top of it.
+struct KeepAlive {
+    last_sent: tokio::sync::Mutex<Option<SentReport>>,
+    period: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl KeepAlive {
+    fn new(period: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self { last_sent: tokio::sync::Mutex::new(None), period, clock }
+    }
+
+    /// Send `report` with `send` and remember it
+    async fn send<F, Fut>(&self, report: G29InputReport, send: F) -> Result<()>
+    where
+        F: FnOnce(G29InputReport) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut last_sent = self.last_sent.lock().await;
+        send(report).await?;
+        *last_sent = Some(SentReport { report, at: self.clock.now() });
+        Ok(())
+    }
+
+    /// Repeat the last report with `send` if nothing was sent for the period, returning whether it was
+    async fn resend_if_stale<F, Fut>(&self, send: F) -> Result<bool>
+    where
+        F: FnOnce(G29InputReport) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut last_sent = self.last_sent.lock().await;
+        let now = self.clock.now();
+        let Some(sent) = last_sent.filter(|sent| now.saturating_duration_since(sent.at) >= self.period) else {
+            return Ok(false);
+        };
+        send(sent.report).await?;
+        *last_sent = Some(SentReport { report: sent.report, at: self.clock.now() });
+        Ok(true)
+    }
 }
 
 impl VirtualG29Device {
-    /// Create and initialize virtual G29 device
+    /// Create and initialize virtual G29 device on the registered platform backend
     pub async fn create(config: &G29Config) -> Result<Self> {
+        let factory = *BACKEND.get().ok_or(TranslatorError::UnsupportedPlatform)?;
+        Self::with_backend(config, factory).await
+    }
+
+    /// Create and initialize virtual G29 device on the backend `factory` makes
+    pub async fn with_backend(config: &G29Config, factory: BackendFactory) -> Result<Self> {
         let (input_sender, _input_receiver) = mpsc::unbounded_channel();
-        let (_output_sender, output_receiver) = mpsc::unbounded_channel();
+        let (output_sender, output_receiver) = mpsc::unbounded_channel();
 
-        let mut device = Self {
+        let encoder = G29ReportEncoder::for_config(config)?;
+        tracing::debug!("G29 {:?} mode input report is {} bytes", config.mode, encoder.report_len());
+
+        let platform: Arc<dyn PlatformBackend> = Arc::from(factory(config.clone(), output_sender.clone()).await?);
+        if !platform.is_connected() {
+            tracing::warn!("Virtual {} created but not connected yet", config.product_name());
+        }
+        let keep_alive = (config.keep_alive_ms > 0).then(|| {
+            let period = Duration::from_millis(config.keep_alive_ms as u64);
+            tracing::info!("Re-sending unchanged input every {:?}", period);
+            Arc::new(KeepAlive::new(period, Arc::new(SystemClock)))
+        });
+        let keep_alive_task = keep_alive
+            .clone()
+            .map(|keep_alive| tokio::spawn(Self::keep_alive(platform.clone(), keep_alive)));
+
+        Ok(Self {
             config: config.clone(),
+            encoder,
+            platform,
+            keep_alive,
+            keep_alive_task,
             input_sender,
+            _output_sender: output_sender,
             output_receiver: Arc::new(tokio::sync::Mutex::new(output_receiver)),
-            #[cfg(target_os = "windows")]
-            vigem_device: None,
-            #[cfg(target_os = "linux")]
-            uinput_device: None,
-            #[cfg(target_os = "macos")]
-            virtual_hid_device: None,
-        };
+        })
+    }
 
-        device.initialize_platform_device().await?;
-        
-        Ok(device)
+    /// Encoder producing raw reports matching this device's descriptor
+    pub fn encoder(&self) -> &G29ReportEncoder {
+        &self.encoder
+    }
+
+    /// Whether games can see the device
+    pub fn is_connected(&self) -> bool {
+        self.platform.is_connected()
     }
 
     /// Send input report to the virtual G29 device
     pub async fn send_input(&self, report: G29InputReport) -> Result<()> {
-        // Send to platform-specific device
-        #[cfg(target_os = "windows")]
-        {
-            if let Some(ref vigem) = self.vigem_device {
-                vigem.send_input(report).await?;
-            }
-        }
-
-        #[cfg(target_os = "linux")]
-        {
-            if let Some(ref uinput) = self.uinput_device {
-                uinput.send_input(report).await?;
-            }
-        }
-
-        #[cfg(target_os = "macos")]
-        {
-            if let Some(ref vhid) = self.virtual_hid_device {
-                vhid.send_input(report).await?;
-            }
+        match &self.keep_alive {
+            Some(keep_alive) => keep_alive.send(report, |report| self.platform.send_input(report)).await?,
+            None => self.platform.send_input(report).await?,
         }
 
         // Also send through internal channel for testing/monitoring
@@ -72,7 +171,23 @@ impl VirtualG29Device {
         Ok(())
     }
 
+    /// Repeat the last report whenever nothing was sent for `period`
+    async fn keep_alive(platform: Arc<dyn PlatformBackend>, keep_alive: Arc<KeepAlive>) {
+        let mut ticker = tokio::time::interval(keep_alive.period / 2);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = keep_alive.resend_if_stale(|report| platform.send_input(report)).await {
+                tracing::debug!("Keep-alive report failed: {}", e);
+            }
+        }
+    }
+
     /// Read output report from the virtual G29 device (FFB commands from games)
+    ///
+    /// The backend hands every FFB upload or playback it receives to the
+    /// sender it was created with, this picks them up.
     pub async fn read_output(&self) -> Result<Option<G29OutputReport>> {
         let mut receiver = self.output_receiver.lock().await;
         
@@ -85,110 +200,100 @@ impl VirtualG29Device {
         }
     }
 
-    async fn initialize_platform_device(&mut self) -> Result<()> {
-        cfg_if::cfg_if! {
-            if #[cfg(target_os = "windows")] {
-                self.initialize_windows().await
-            } else if #[cfg(target_os = "linux")] {
-                self.initialize_linux().await
-            } else if #[cfg(target_os = "macos")] {
-                self.initialize_macos().await
-            } else {
-                Err(TranslatorError::UnsupportedPlatform)
-            }
-        }
+    /// Configuration the device was created with
+    pub fn config(&self) -> &G29Config {
+        &self.config
     }
+}
 
-    #[cfg(target_os = "windows")]
-    async fn initialize_windows(&mut self) -> Result<()> {
-        // Initialize ViGEm client and create G29 device
-        let vigem = VigEmDevice::new(&self.config).await?;
-        self.vigem_device = Some(vigem);
-        
-        tracing::info!("Virtual G29 device created on Windows using ViGEm");
-        Ok(())
+impl Drop for VirtualG29Device {
+    fn drop(&mut self) {
+        if let Some(task) = self.keep_alive_task.take() {
+            task.abort();
+        }
     }
+}
 
-    #[cfg(target_os = "linux")]
-    async fn initialize_linux(&mut self) -> Result<()> {
-        // Create uinput device with G29 descriptor
-        let uinput = UInputDevice::new(&self.config).await?;
-        self.uinput_device = Some(uinput);
-        
-        tracing::info!("Virtual G29 device created on Linux using uinput");
-        Ok(())
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
 
-    #[cfg(target_os = "macos")]
-    async fn initialize_macos(&mut self) -> Result<()> {
-        // Create virtual HID device using DriverKit
-        let vhid = VirtualHIDDevice::new(&self.config).await?;
-        self.virtual_hid_device = Some(vhid);
-        
-        tracing::info!("Virtual G29 device created on macOS using VirtualHIDDevice");
-        Ok(())
+    fn report(steering: u16) -> G29InputReport {
+        G29InputReport { report_id: 0x01, steering, throttle: 0, brake: 0, clutch: 0, buttons: 0, unused: [0; 4] }
     }
-}
 
-// Platform-specific implementations
+    #[tokio::test]
+    async fn test_keep_alive_never_resends_over_a_newer_report() {
+        let clock = MockClock::new();
+        let keep_alive = KeepAlive::new(Duration::from_millis(50), Arc::new(clock.clone()));
+        let sent = std::sync::Mutex::new(Vec::new());
+        let record = |report: G29InputReport| {
+            sent.lock().unwrap().push(report.steering);
+            async { Ok::<_, TranslatorError>(()) }
+        };
 
-#[cfg(target_os = "windows")]
-struct VigEmDevice {
-    // ViGEm implementation would go here
-    // This is a stub for the actual ViGEm integration
-}
+        // Only repeated once the period passed without a send
+        keep_alive.send(report(1), record).await.unwrap();
+        clock.advance(Duration::from_millis(40));
+        assert!(!keep_alive.resend_if_stale(record).await.unwrap());
+        clock.advance(Duration::from_millis(10));
+        assert!(keep_alive.resend_if_stale(record).await.unwrap());
 
-#[cfg(target_os = "windows")]
-impl VigEmDevice {
-    async fn new(_config: &G29Config) -> Result<Self> {
-        // Initialize ViGEm bus and create G29 device
-        // This would use the vigem-sys crate or similar
-        Ok(Self {})
+        // A newer report waits for a repeat under way instead of being overtaken
+        clock.advance(Duration::from_millis(50));
+        let gate = tokio::sync::Notify::new();
+        let slow = |report: G29InputReport| {
+            let (gate, sent) = (&gate, &sent);
+            async move {
+                gate.notified().await;
+                sent.lock().unwrap().push(report.steering);
+                Ok::<_, TranslatorError>(())
+            }
+        };
+        let (resent, newer) = tokio::join!(keep_alive.resend_if_stale(slow), async {
+            tokio::task::yield_now().await;
+            gate.notify_one();
+            keep_alive.send(report(2), record).await
+        });
+        assert!(resent.unwrap());
+        newer.unwrap();
+        assert_eq!(*sent.lock().unwrap(), [1, 1, 1, 2]);
+
+        // The repeat after that is the newer report
+        clock.advance(Duration::from_millis(50));
+        assert!(keep_alive.resend_if_stale(record).await.unwrap());
+        assert_eq!(sent.lock().unwrap().last(), Some(&2));
     }
 
-    async fn send_input(&self, report: G29InputReport) -> Result<()> {
-        // Send input report to ViGEm device
-        tracing::debug!("Sending input to ViGEm G29 device: {:?}", report);
-        Ok(())
+    /// Backend answering every input report with a constant force of its steering value
+    struct Echo {
+        output: OutputSender,
     }
-}
 
-#[cfg(target_os = "linux")]
-struct UInputDevice {
-    // uinput implementation would go here
-}
+    impl PlatformBackend for Echo {
+        fn send_input(&self, report: G29InputReport) -> BoxFuture<'_, Result<()>> {
+            let _ = self.output.send(G29OutputReport::constant_effect(1, report.steering as i16, 0));
+            Box::pin(async { Ok(()) })
+        }
 
-#[cfg(target_os = "linux")]
-impl UInputDevice {
-    async fn new(_config: &G29Config) -> Result<Self> {
-        // Create uinput device with G29 HID descriptor
-        // This would use the uinput crate or direct file operations
-        Ok(Self {})
+        fn is_connected(&self) -> bool {
+            true
+        }
     }
 
-    async fn send_input(&self, report: G29InputReport) -> Result<()> {
-        // Send input event to uinput device
-        tracing::debug!("Sending input to uinput G29 device: {:?}", report);
-        Ok(())
+    fn echo(_config: G29Config, output: OutputSender) -> BoxFuture<'static, Result<Box<dyn PlatformBackend>>> {
+        Box::pin(async move { Ok(Box::new(Echo { output }) as Box<dyn PlatformBackend>) })
     }
-}
 
-#[cfg(target_os = "macos")]
-struct VirtualHIDDevice {
-    // VirtualHIDDevice implementation would go here
-}
+    #[tokio::test]
+    async fn test_backend_output_reaches_read_output() {
+        let device = VirtualG29Device::with_backend(&G29Config::default(), echo).await.unwrap();
+        assert!(device.is_connected());
+        assert!(device.read_output().await.unwrap().is_none());
 
-#[cfg(target_os = "macos")]
-impl VirtualHIDDevice {
-    async fn new(_config: &G29Config) -> Result<Self> {
-        // Create virtual HID device using DriverKit
-        // This would use IOKit bindings
-        Ok(Self {})
+        device.send_input(report(1200)).await.unwrap();
+        let output = device.read_output().await.unwrap().expect("output report");
+        assert_eq!(output.data, G29OutputReport::constant_effect(1, 1200, 0).data);
     }
-
-    async fn send_input(&self, report: G29InputReport) -> Result<()> {
-        // Send input report to virtual HID device
-        tracing::debug!("Sending input to virtual G29 device: {:?}", report);
-        Ok(())
-    }
-} 
\ No newline at end of file
+}
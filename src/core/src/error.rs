@@ -36,6 +36,9 @@ pub enum TranslatorError {
     #[error("Protocol error: {reason}")]
     ProtocolError { reason: String },
     
+    #[error("Telemetry error: {reason}")]
+    TelemetryError { reason: String },
+    
     #[error("Timeout waiting for device response")]
     Timeout,
     
@@ -70,4 +73,20 @@ impl TranslatorError {
     pub fn protocol_error(reason: impl Into<String>) -> Self {
         Self::ProtocolError { reason: reason.into() }
     }
+    
+    pub fn telemetry_error(reason: impl Into<String>) -> Self {
+        Self::TelemetryError { reason: reason.into() }
+    }
+    
+    /// Whether restarting the pipeline may clear the error
+    ///
+    /// Device and I/O failures usually come from a wheel being unplugged or
+    /// briefly busy; configuration and platform errors will fail the same way
+    /// on every attempt.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self,
+            Self::ConfigError { .. } | Self::Cancelled | Self::UnsupportedPlatform
+        )
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,171 @@
+//! Linux input event model for the virtual G29
+//!
+//! Describes how a `G29InputReport` maps onto evdev axes and keys. Shared by
+//! the uinput backend and by tooling that exports recordings in evdev-based
+//! formats, so every consumer agrees on codes and ranges.
+
+use crate::device::{G29InputReport, HatDirection};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+pub const EV_FF: u16 = 0x15;
+
+pub const SYN_REPORT: u16 = 0x00;
+
+pub const ABS_X: u16 = 0x00;      // Steering
+pub const ABS_Y: u16 = 0x01;      // Throttle
+pub const ABS_Z: u16 = 0x02;      // Brake
+pub const ABS_RZ: u16 = 0x05;     // Clutch
+pub const ABS_HAT0X: u16 = 0x10;
+pub const ABS_HAT0Y: u16 = 0x11;
+
+pub const BTN_JOYSTICK: u16 = 0x120;
+pub const BTN_TRIGGER_HAPPY: u16 = 0x2c0;
+
+pub const FF_PERIODIC: u16 = 0x51;
+pub const FF_CONSTANT: u16 = 0x52;
+pub const FF_SPRING: u16 = 0x53;
+pub const FF_FRICTION: u16 = 0x54;
+pub const FF_DAMPER: u16 = 0x55;
+pub const FF_INERTIA: u16 = 0x56;
+pub const FF_RAMP: u16 = 0x57;
+pub const FF_SQUARE: u16 = 0x58;
+pub const FF_TRIANGLE: u16 = 0x59;
+pub const FF_SINE: u16 = 0x5a;
+pub const FF_SAW_UP: u16 = 0x5b;
+pub const FF_SAW_DOWN: u16 = 0x5c;
+pub const FF_GAIN: u16 = 0x60;
+pub const FF_AUTOCENTER: u16 = 0x61;
+
+/// Force feedback capabilities advertised by the virtual G29
+pub const G29_FF_BITS: [u16; 14] = [
+    FF_PERIODIC, FF_CONSTANT, FF_SPRING, FF_FRICTION, FF_DAMPER, FF_INERTIA, FF_RAMP,
+    FF_SQUARE, FF_TRIANGLE, FF_SINE, FF_SAW_UP, FF_SAW_DOWN, FF_GAIN, FF_AUTOCENTER,
+];
+
+/// Effect slots the virtual G29 accepts
+pub const G29_FF_EFFECTS_MAX: u32 = 40;
+
+/// Number of buttons exposed by the G29
+pub const G29_BUTTON_COUNT: u8 = 24;
+
+/// Bits of `G29InputReport::buttons` holding buttons, the rest is the hat
+const BUTTON_MASK: u32 = 0x00FF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    pub fn new(event_type: u16, code: u16, value: i32) -> Self {
+        Self { event_type, code, value }
+    }
+
+    pub fn syn() -> Self {
+        Self::new(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+/// Range description of an absolute axis, as passed to UI_ABS_SETUP
+#[derive(Debug, Clone, Copy)]
+pub struct AbsAxisInfo {
+    pub code: u16,
+    pub minimum: i32,
+    pub maximum: i32,
+    pub fuzz: i32,
+    pub flat: i32,
+}
+
+pub const G29_ABS_AXES: [AbsAxisInfo; 6] = [
+    AbsAxisInfo { code: ABS_X, minimum: 0, maximum: 65535, fuzz: 0, flat: 0 },
+    AbsAxisInfo { code: ABS_Y, minimum: 0, maximum: 1023, fuzz: 0, flat: 0 },
+    AbsAxisInfo { code: ABS_Z, minimum: 0, maximum: 1023, fuzz: 0, flat: 0 },
+    AbsAxisInfo { code: ABS_RZ, minimum: 0, maximum: 1023, fuzz: 0, flat: 0 },
+    AbsAxisInfo { code: ABS_HAT0X, minimum: -1, maximum: 1, fuzz: 0, flat: 0 },
+    AbsAxisInfo { code: ABS_HAT0Y, minimum: -1, maximum: 1, fuzz: 0, flat: 0 },
+];
+
+/// Key code for a zero-based G29 button index
+///
+/// Matches the kernel's joystick mapping: the first 16 buttons use the
+/// BTN_JOYSTICK range, the rest continue in BTN_TRIGGER_HAPPY.
+pub fn button_code(index: u8) -> u16 {
+    if index < 16 {
+        BTN_JOYSTICK + index as u16
+    } else {
+        BTN_TRIGGER_HAPPY + (index - 16) as u16
+    }
+}
+
+/// Hat X/Y axis values, evdev counts Y downwards
+pub fn hat_axes(hat: HatDirection) -> (i32, i32) {
+    let (x, y) = hat.components();
+    (x as i32, -(y as i32))
+}
+
+/// Hat direction of X/Y axis values, inverse of `hat_axes`
+pub fn hat_from_axes(x: i32, y: i32) -> HatDirection {
+    HatDirection::from_components(x, -y)
+}
+
+/// Zero-based button index of a key code, inverse of `button_code`
+pub fn button_index(code: u16) -> Option<u8> {
+    match code {
+        BTN_JOYSTICK..=0x12f => Some((code - BTN_JOYSTICK) as u8),
+        BTN_TRIGGER_HAPPY..=0x2cf => Some((code - BTN_TRIGGER_HAPPY) as u8 + 16),
+        _ => None,
+    }
+}
+
+/// Events needed to move the device from `previous` to `report`, ending in SYN_REPORT
+///
+/// With no previous report every axis and button is emitted.
+pub fn report_events(previous: Option<&G29InputReport>, report: &G29InputReport) -> Vec<InputEvent> {
+    let mut events = Vec::with_capacity(8);
+
+    let axes = [
+        (ABS_X, report.steering, previous.map(|p| p.steering)),
+        (ABS_Y, report.throttle, previous.map(|p| p.throttle)),
+        (ABS_Z, report.brake, previous.map(|p| p.brake)),
+        (ABS_RZ, report.clutch, previous.map(|p| p.clutch)),
+    ];
+    for (code, value, old) in axes {
+        if old != Some(value) {
+            events.push(InputEvent::new(EV_ABS, code, value as i32));
+        }
+    }
+
+    let (hat_x, hat_y) = hat_axes(report.hat());
+    let (old_x, old_y) = match previous {
+        Some(p) => {
+            let (x, y) = hat_axes(p.hat());
+            (Some(x), Some(y))
+        }
+        None => (None, None),
+    };
+    if old_x != Some(hat_x) {
+        events.push(InputEvent::new(EV_ABS, ABS_HAT0X, hat_x));
+    }
+    if old_y != Some(hat_y) {
+        events.push(InputEvent::new(EV_ABS, ABS_HAT0Y, hat_y));
+    }
+
+    let buttons = report.buttons & BUTTON_MASK;
+    let changed = match previous {
+        Some(p) => buttons ^ (p.buttons & BUTTON_MASK),
+        None => BUTTON_MASK,
+    };
+    for index in 0..G29_BUTTON_COUNT {
+        if changed & (1 << index) != 0 {
+            let pressed = buttons & (1 << index) != 0;
+            events.push(InputEvent::new(EV_KEY, button_code(index), pressed as i32));
+        }
+    }
+
+    events.push(InputEvent::syn());
+    events
+}
@@ -0,0 +1,99 @@
+//! Gate for subsystems still in development
+//!
+//! New subsystems land behind a named flag in `[experimental]` and stay off
+//! unless the user lists them, so they can ship piece by piece without
+//! touching the default path. Every enabled feature is logged as a warning
+//! when the translator starts, so a log attached to a bug report always says
+//! whether unfinished code was running. Flags are read once at startup;
+//! names this build does not know, e.g. of features that have since become
+//! regular options, are reported and otherwise ignored.
+
+use crate::config::ExperimentalConfig;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Subsystems that can be switched on in `[experimental]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    GadgetMode,        // Present as a USB G29 through the Linux USB gadget framework
+    TelemetryEffects,  // FFB effects synthesized from game telemetry
+    PedalVibration,    // Pedal motors driven through an unverified vendor report
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::GadgetMode, Feature::TelemetryEffects, Feature::PedalVibration];
+
+    /// Name of the flag in `[experimental] features`
+    pub fn key(self) -> &'static str {
+        match self {
+            Feature::GadgetMode => "gadget_mode",
+            Feature::TelemetryEffects => "telemetry_effects",
+            Feature::PedalVibration => "pedal_vibration",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.key() == key)
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+/// Experimental features switched on for this run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Experimental {
+    enabled: BTreeSet<Feature>,
+}
+
+impl Experimental {
+    /// Features listed in `config`, warning about each one and about unknown names
+    pub fn from_config(config: &ExperimentalConfig) -> Self {
+        let mut enabled = BTreeSet::new();
+        for key in &config.features {
+            match Feature::from_key(key) {
+                Some(feature) => {
+                    enabled.insert(feature);
+                }
+                None => tracing::warn!("Unknown experimental feature '{}' ignored", key),
+            }
+        }
+        for feature in &enabled {
+            tracing::warn!(
+                "EXPERIMENTAL feature {} is enabled. It is unfinished, may misbehave and may change without notice",
+                feature
+            );
+        }
+        Self { enabled }
+    }
+
+    /// Whether code behind `feature` may run
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = Feature> + '_ {
+        self.enabled.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_listed_known_features_are_enabled() {
+        assert!(!Experimental::default().is_enabled(Feature::GadgetMode));
+
+        let config = ExperimentalConfig {
+            features: vec!["telemetry_effects".to_string(), "warp_drive".to_string()],
+        };
+        let experimental = Experimental::from_config(&config);
+        assert!(experimental.is_enabled(Feature::TelemetryEffects));
+        assert!(!experimental.is_enabled(Feature::GadgetMode));
+        assert_eq!(experimental.enabled().collect::<Vec<_>>(), vec![Feature::TelemetryEffects]);
+    }
+}
@@ -1,117 +1,1037 @@
 //! Force Feedback translation engine
 
+use crate::clock::{Clock, SystemClock};
 use crate::device::IforceCommand;
-use crate::config::FfbConfig;
-use crate::error::Result;
+use crate::device::descriptors::HidDescriptorInfo;
+use crate::config::{FfbConfig, FfbRenderMode};
+use crate::error::{Result, TranslatorError};
+use crate::pid::{BlockLoadStatus, DeviceControl, EffectPool, PidBlockLoad, PidCommand, LOOP_FOREVER};
+use crate::render::{WheelMotion, FULL_SCALE_VELOCITY};
+use crate::thermal::ThermalLimiter;
+use crate::timeline::{EffectEvent, EffectPhase, ENGINE_TIMELINE_LEN};
+use crate::units::{ForceNewton, Milliseconds};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Force the IFORCE magnitude range is calibrated against
+const BASELINE_FORCE: ForceNewton = ForceNewton::new(2.5);
+
+/// IFORCE command stopping every running effect
+const IFORCE_STOP_ALL: u8 = 0x4F;
+
+/// IFORCE hardware autocenter: `[enabled, strength]`, strength 255 = full
+const IFORCE_AUTOCENTER: u8 = 0x02;
+
+/// Effect slot of the autocenter spring on bases without hardware autocenter, games use 1 - 40
+const AUTOCENTER_SLOT: u8 = 0;
+
+/// How often native effects are replayed while the soft start ramps up
+const SOFT_START_STEP: Duration = Duration::from_millis(50);
+
+/// Effect slot of the constant force stream in `FfbRenderMode::Render`
+const RENDER_SLOT: u8 = 1;
+
+/// Effect slot of the pulse announcing an FFB A/B switch, just past the slots games use
+const BLIP_SLOT: u8 = crate::evdev::G29_FF_EFFECTS_MAX as u8 + 1;
+
+/// Effect slot of the soft lock's push back, past the A/B switch pulse
+const SOFT_LOCK_SLOT: u8 = BLIP_SLOT + 1;
+
+/// Length of the A/B switch pulse
+const BLIP_DURATION: Milliseconds = Milliseconds::new(80);
+
+/// Effects a game can have uploaded at once, sizes the pre-allocated buffers
+const EFFECT_CAPACITY: usize = crate::evdev::G29_FF_EFFECTS_MAX as usize;
+
+/// Effect family as far as wheel base support is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectKind {
+    Constant,
+    Periodic,
+    Ramp,
+    Spring,
+    Damper,
+    Inertia,
+    Friction,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 7] = [
+        EffectKind::Constant,
+        EffectKind::Periodic,
+        EffectKind::Ramp,
+        EffectKind::Spring,
+        EffectKind::Damper,
+        EffectKind::Inertia,
+        EffectKind::Friction,
+    ];
+
+    pub fn of(effect_type: &EffectType) -> Self {
+        match effect_type {
+            EffectType::Constant(_) => EffectKind::Constant,
+            EffectType::Periodic(_) => EffectKind::Periodic,
+            EffectType::Ramp(_) => EffectKind::Ramp,
+            EffectType::Condition(condition) => match condition.condition_type {
+                ConditionType::Spring => EffectKind::Spring,
+                ConditionType::Damper => EffectKind::Damper,
+                ConditionType::Inertia => EffectKind::Inertia,
+                ConditionType::Friction => EffectKind::Friction,
+            },
+        }
+    }
+}
+
+/// Effect kinds the attached wheel base renders natively
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfbCapabilities {
+    supported: HashSet<EffectKind>,
+    /// The base centers itself with `IFORCE_AUTOCENTER` instead of a spring effect
+    hardware_autocenter: bool,
+}
+
+impl FfbCapabilities {
+    /// Every effect kind, assumed when nothing better is known
+    pub fn all() -> Self {
+        Self::from_kinds(EffectKind::ALL)
+    }
+
+    pub fn from_kinds(kinds: impl IntoIterator<Item = EffectKind>) -> Self {
+        Self { supported: kinds.into_iter().collect(), hardware_autocenter: false }
+    }
+
+    /// Mark the base as accepting the IFORCE autocenter command
+    pub fn with_hardware_autocenter(mut self, hardware_autocenter: bool) -> Self {
+        self.hardware_autocenter = hardware_autocenter;
+        self
+    }
+
+    pub fn has_hardware_autocenter(&self) -> bool {
+        self.hardware_autocenter
+    }
+
+    /// Effect types declared in a PID report descriptor, `None` if it has no PID effect types
+    pub fn from_descriptor(info: &HidDescriptorInfo) -> Option<Self> {
+        let kinds: HashSet<EffectKind> = info
+            .pid_usages
+            .iter()
+            .filter_map(|&usage| match usage {
+                0x26 => Some(EffectKind::Constant),
+                0x27 => Some(EffectKind::Ramp),
+                0x30..=0x34 => Some(EffectKind::Periodic), // Square, sine, triangle, sawtooth up/down
+                0x40 => Some(EffectKind::Spring),
+                0x41 => Some(EffectKind::Damper),
+                0x42 => Some(EffectKind::Inertia),
+                0x43 => Some(EffectKind::Friction),
+                _ => None,
+            })
+            .collect();
+        (!kinds.is_empty()).then_some(Self { supported: kinds, hardware_autocenter: false })
+    }
+
+    pub fn supports(&self, kind: EffectKind) -> bool {
+        self.supported.contains(&kind)
+    }
+
+    /// Supported kinds in a stable order, for logging
+    pub fn kinds(&self) -> Vec<EffectKind> {
+        EffectKind::ALL.into_iter().filter(|kind| self.supports(*kind)).collect()
+    }
+}
+
+impl Default for FfbCapabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// How an effect the base cannot play natively is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Degradation {
+    Native,
+    Software,                    // Stepped as constant force updates
+    Substitute(ConditionType),   // Closest condition the base does support
+    Drop,
+}
+
+/// An effect currently playing, as shown to monitors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveEffectSummary {
+    pub id: u8,
+    pub kind: EffectKind,
+    pub software: bool,   // Rendered as constant force updates
+}
+
 /// Main FFB engine for translating effects
 pub struct FfbEngine {
     config: FfbConfig,
+    capabilities: FfbCapabilities,
     active_effects: HashMap<u8, ActiveEffect>,
     last_update: Instant,
+    panic: bool,
+    degraded: HashSet<EffectKind>,
+    disabled: HashMap<EffectKind, u64>,
+    clipped: AtomicU64,
+    clock: Arc<dyn Clock>,
+    /// Polynomial waveforms instead of libm calls, see `HostConfig::embedded`
+    fast_math: bool,
+    /// Reused between updates so the output loop does not allocate per tick
+    expired: Vec<u8>,
+    thermal: ThermalLimiter,
+    /// Start of the soft-start ramp, `None` once at full gain
+    soft_start: Option<Instant>,
+    soft_start_gain: f32,
+    /// Last replay of the native effects during the ramp
+    soft_start_sent: Instant,
+    pool: EffectPool,
+    /// Parameters of created blocks, played once Effect Operation starts them
+    loaded: HashMap<u8, FfbEffect>,
+    block_load: Option<PidBlockLoad>,
+    /// Device Gain report, 1.0 = full strength
+    device_gain: f32,
+    device_paused: bool,
+    actuators_enabled: bool,
+    /// Steering motion rendered condition effects push against
+    motion: WheelMotion,
+    /// Share of `autocenter_gain` applied, 1.0 while the game plays nothing
+    autocenter_level: f32,
+    /// Strength last sent to the wheel, `None` to resend
+    autocenter_sent: Option<f32>,
+    autocenter_sent_at: Instant,
+    /// Lifetime events not yet collected with `take_timeline`
+    timeline: VecDeque<EffectEvent>,
+    /// Degrees the wheel and the G29 turn, the soft lock holds the wheel at the G29's lock
+    soft_lock_ranges: Option<(u16, u16)>,
+    /// Push back last sent in `SOFT_LOCK_SLOT`, `None` to resend
+    soft_lock_sent: Option<i16>,
 }
 
 impl FfbEngine {
     pub fn new(config: &FfbConfig) -> Self {
+        Self::with_capabilities(config, FfbCapabilities::all())
+    }
+
+    /// Engine for a base that only renders the effect kinds in `capabilities`
+    pub fn with_capabilities(config: &FfbConfig, capabilities: FfbCapabilities) -> Self {
         Self {
             config: config.clone(),
+            capabilities,
             active_effects: HashMap::new(),
-            last_update: Instant::now(),
+            last_update: SystemClock.now(),
+            panic: false,
+            degraded: HashSet::new(),
+            disabled: HashMap::new(),
+            clipped: AtomicU64::new(0),
+            clock: Arc::new(SystemClock),
+            fast_math: false,
+            expired: Vec::new(),
+            thermal: ThermalLimiter::new(&config.thermal_limiter),
+            soft_start: None,
+            soft_start_gain: 1.0,
+            soft_start_sent: SystemClock.now(),
+            pool: EffectPool::default(),
+            loaded: HashMap::new(),
+            block_load: None,
+            device_gain: 1.0,
+            device_paused: false,
+            actuators_enabled: true,
+            motion: WheelMotion::default(),
+            autocenter_level: 1.0,
+            autocenter_sent: None,
+            autocenter_sent_at: SystemClock.now(),
+            timeline: VecDeque::new(),
+            soft_lock_ranges: None,
+            soft_lock_sent: None,
+        }
+    }
+
+    /// Switch to `config` while the game's effects keep playing
+    ///
+    /// Native effects are replayed with the new gains. The render mode is
+    /// kept, it decides how effects already playing were split between the
+    /// base and software rendering.
+    pub fn set_config(&mut self, config: &FfbConfig) -> Result<Vec<IforceCommand>> {
+        let render_mode = self.config.render_mode;
+        if config.render_mode != render_mode {
+            tracing::warn!("FFB render mode changes take effect after a restart");
+        }
+        if config.thermal_limiter != self.config.thermal_limiter {
+            self.thermal = ThermalLimiter::new(&config.thermal_limiter);
+        }
+        let was_enabled = self.config.enabled;
+        self.config = FfbConfig { render_mode, ..config.clone() };
+        // Resent at the new strength with the next update
+        self.autocenter_sent = None;
+
+        if self.panic {
+            return Ok(vec![]);
+        }
+        if !self.config.enabled {
+            return Ok(if was_enabled { self.stop_all() } else { vec![] });
+        }
+        self.replay_playing()
+    }
+
+    /// Take time from `clock` instead of the system clock, e.g. a `MockClock` in tests
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.last_update = clock.now();
+        self.autocenter_sent_at = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Tune for a low-power host: cheaper waveform math and buffers sized up front
+    pub fn with_embedded(mut self, embedded: bool) -> Self {
+        self.fast_math = embedded;
+        if embedded {
+            self.active_effects.reserve(EFFECT_CAPACITY);
+            self.expired.reserve(EFFECT_CAPACITY);
+        }
+        self
+    }
+
+    pub fn capabilities(&self) -> &FfbCapabilities {
+        &self.capabilities
+    }
+
+    /// Effects dropped per kind because they are switched off in `FfbConfig::effects`
+    pub fn disabled_counts(&self) -> &HashMap<EffectKind, u64> {
+        &self.disabled
+    }
+
+    /// Number of magnitudes clamped to full scale by gains or the force limit
+    pub fn clipped_count(&self) -> u64 {
+        self.clipped.load(Ordering::Relaxed)
+    }
+
+    /// Load average and gain reduction of the thermal limiter
+    pub fn thermal_limiter(&self) -> &ThermalLimiter {
+        &self.thermal
+    }
+
+    /// Effects currently playing, ordered by id
+    pub fn active_effects(&self) -> Vec<ActiveEffectSummary> {
+        let mut effects: Vec<_> = self
+            .active_effects
+            .iter()
+            .map(|(&id, active)| ActiveEffectSummary {
+                id,
+                kind: EffectKind::of(&active.effect.effect_type),
+                software: active.software,
+            })
+            .collect();
+        effects.sort_unstable_by_key(|effect| effect.id);
+        effects
+    }
+
+    /// Effect lifetime events since the last call, oldest first
+    pub fn take_timeline(&mut self) -> Vec<EffectEvent> {
+        self.timeline.drain(..).collect()
+    }
+
+    fn log_event(&mut self, event: EffectEvent) {
+        push_event(&mut self.timeline, event);
+    }
+
+    /// Log `block` as started if it is playing now
+    fn log_started(&mut self, block: u8) {
+        let now = self.clock.now();
+        if let Some(event) = self.active_effects.get(&block).map(|active| EffectEvent::new(now, &active.effect, EffectPhase::Started)) {
+            self.log_event(event);
         }
     }
 
+    /// Number of effects currently playing
+    pub fn active_count(&self) -> usize {
+        self.active_effects.len()
+    }
+
+    /// Feed a raw steering sample to the conditions rendered in software
+    pub fn observe_wheel(&mut self, steering: i16) {
+        self.motion.observe(steering, self.clock.now());
+    }
+
+    /// Like `observe_wheel`, for a sample taken at `at` and handed over since
+    pub fn observe_wheel_at(&mut self, steering: i16, at: Instant) {
+        self.motion.observe(steering, at);
+    }
+
+    /// Hold the wheel at the lock of a G29 turning `g29_degrees` on a wheel turning `wheel_degrees`
+    ///
+    /// Past that lock the wheel meets a force wall, `[ffb_config.soft_lock]`,
+    /// instead of turning on while the game's wheel stands still. There is
+    /// no wall when the G29 turns as far as the wheel.
+    pub fn set_soft_lock(&mut self, wheel_degrees: u16, g29_degrees: u16) {
+        self.soft_lock_ranges = Some((wheel_degrees, g29_degrees));
+    }
+
+    /// Whether every effect is synthesized into one constant force stream
+    fn rendering(&self) -> bool {
+        self.config.render_mode == FfbRenderMode::Render
+    }
+
+    /// Whether the panic override is holding all forces at zero
+    pub fn is_panicked(&self) -> bool {
+        self.panic
+    }
+
+    /// Stop every effect and drop all new ones until `release_panic`
+    ///
+    /// This is the highest-priority override: while engaged nothing the game
+    /// sends reaches the wheel, whatever the gains or effect state.
+    pub fn engage_panic(&mut self) -> Vec<IforceCommand> {
+        self.panic = true;
+        let mut commands = self.stop_all();
+        if self.capabilities.has_hardware_autocenter() {
+            commands.extend(self.autocenter_command(0.0));
+        }
+        commands
+    }
+
+    /// Short pulse at `strength` (0.0 - 1.0) in a slot of its own, `None` while forces are off
+    pub fn blip(&self, strength: f32) -> Option<IforceCommand> {
+        if self.panic || !self.output_enabled() || strength <= 0.0 {
+            return None;
+        }
+        let magnitude = self.clamp_full_scale(strength.min(1.0) * i16::MAX as f32);
+        Some(self.constant_command(BLIP_SLOT, magnitude, BLIP_DURATION))
+    }
+
+    /// Let game effects through again, starting from an empty effect set
+    pub fn release_panic(&mut self) {
+        self.panic = false;
+        self.begin_soft_start();
+    }
+
+    /// Ramp every force up from zero over `FfbConfig::soft_start_ms`
+    ///
+    /// Called when the translator connects or resumes, so forces the game
+    /// is already sending do not jerk the wheel at full strength.
+    pub fn begin_soft_start(&mut self) {
+        if self.config.soft_start_ms == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        self.soft_start = Some(now);
+        self.soft_start_gain = 0.0;
+        self.soft_start_sent = now;
+        tracing::debug!("FFB soft start over {} ms", self.config.soft_start_ms);
+    }
+
     /// Translate a G29 FFB effect to IFORCE commands
+    ///
+    /// Parameters of a block created with Create New Effect are only loaded
+    /// until Effect Operation starts it; other blocks play right away.
     pub fn translate_effect(&mut self, effect: FfbEffect) -> Result<Vec<IforceCommand>> {
-        if !self.config.enabled {
+        let id = effect.id;
+        let playing = self.active_effects.contains_key(&id);
+        let phase = if playing || self.loaded.contains_key(&id) { EffectPhase::Updated } else { EffectPhase::Created };
+        self.log_event(EffectEvent::new(self.clock.now(), &effect, phase));
+
+        let loops = self.active_effects.get(&id).map_or(1, |active| active.loops);
+        if self.pool.is_managed(id) {
+            self.loaded.insert(id, effect.clone());
+            if !playing {
+                return Ok(vec![]);
+            }
+        } else {
+            self.pool.claim(id);
+        }
+        let commands = self.play(effect, loops)?;
+        if !playing {
+            self.log_started(id);
+        }
+        Ok(commands)
+    }
+
+    /// Run a PID lifecycle command, returning the IFORCE commands it needs
+    pub fn handle_pid(&mut self, command: PidCommand) -> Result<Vec<IforceCommand>> {
+        let commands = match command {
+            PidCommand::Create { effect_type } => {
+                let known = matches!(effect_type, 0x01 | 0x03..=0x0B);
+                let block = if known { self.pool.allocate() } else { None };
+                let status = match block {
+                    Some(_) => BlockLoadStatus::Success,
+                    None if known => BlockLoadStatus::Full,
+                    None => BlockLoadStatus::Error,
+                };
+                if status != BlockLoadStatus::Success {
+                    tracing::warn!("Create New Effect of type {:#04x} failed: {:?}", effect_type, status);
+                }
+                self.block_load = Some(PidBlockLoad { block: block.unwrap_or(0), status, available: self.pool.available() });
+                Vec::new()
+            }
+            PidCommand::Start { block, solo, loop_count } => {
+                let effect = self
+                    .loaded
+                    .get(&block)
+                    .or_else(|| self.active_effects.get(&block).map(|active| &active.effect))
+                    .cloned()
+                    .ok_or_else(|| TranslatorError::ffb_error(format!("Start of effect block {} without parameters", block)))?;
+                let mut commands = if solo { self.stop_all() } else { Vec::new() };
+                commands.extend(self.play(effect, loop_count.max(1))?);
+                self.log_started(block);
+                commands
+            }
+            PidCommand::Stop { block } => self.stop(block),
+            PidCommand::Free { block } => {
+                self.loaded.remove(&block);
+                if !self.pool.free(block) {
+                    tracing::debug!("Block Free of effect block {} that was not allocated", block);
+                }
+                self.stop(block)
+            }
+            PidCommand::Control(DeviceControl::StopAll) => self.stop_all(),
+            PidCommand::Control(DeviceControl::Reset) => {
+                self.pool.reset();
+                self.loaded.clear();
+                self.device_gain = 1.0;
+                self.device_paused = false;
+                self.actuators_enabled = true;
+                self.stop_all()
+            }
+            PidCommand::Control(DeviceControl::Pause) => {
+                self.device_paused = true;
+                vec![IforceCommand { command_id: IFORCE_STOP_ALL, data: vec![] }]
+            }
+            PidCommand::Control(DeviceControl::DisableActuators) => {
+                self.actuators_enabled = false;
+                vec![IforceCommand { command_id: IFORCE_STOP_ALL, data: vec![] }]
+            }
+            PidCommand::Control(DeviceControl::Continue) => {
+                self.device_paused = false;
+                self.replay_playing()?
+            }
+            PidCommand::Control(DeviceControl::EnableActuators) => {
+                self.actuators_enabled = true;
+                self.replay_playing()?
+            }
+            PidCommand::Gain(gain) => {
+                self.device_gain = gain as f32 / u8::MAX as f32;
+                self.replay_playing()?
+            }
+        };
+
+        if self.panic || !self.config.enabled {
             return Ok(vec![]);
         }
+        Ok(commands)
+    }
+
+    /// Answer to the last Create New Effect, for the platform's Block Load feature report
+    pub fn block_load(&self) -> Option<PidBlockLoad> {
+        self.block_load
+    }
+
+    /// Allocation state of the PID effect blocks
+    pub fn effect_pool(&self) -> &EffectPool {
+        &self.pool
+    }
+
+    /// Whether forces reach the wheel, false while the game paused the device or disabled its actuators
+    fn output_enabled(&self) -> bool {
+        !self.device_paused && self.actuators_enabled
+    }
+
+    /// Stop the effect in `block`, as a zero constant force in its slot
+    fn stop(&mut self, block: u8) -> Vec<IforceCommand> {
+        let Some(active) = self.active_effects.remove(&block) else {
+            return Vec::new();
+        };
+        let now = self.clock.now();
+        self.log_event(EffectEvent::new(now, &active.effect, EffectPhase::Stopped));
+        if !self.output_enabled() {
+            Vec::new()
+        } else if self.rendering() {
+            vec![self.rendered_command(now)]
+        } else {
+            vec![self.constant_command(block, 0, Milliseconds::INFINITE)]
+        }
+    }
+
+    fn stop_all(&mut self) -> Vec<IforceCommand> {
+        let now = self.clock.now();
+        let mut stopped: Vec<_> = self
+            .active_effects
+            .drain()
+            .map(|(_, active)| EffectEvent::new(now, &active.effect, EffectPhase::Stopped))
+            .collect();
+        stopped.sort_unstable_by_key(|event| event.id);
+        for event in stopped {
+            self.log_event(event);
+        }
+        // Stops the autocenter spring and the soft lock too, the next update brings them back
+        self.autocenter_sent = None;
+        self.soft_lock_sent = None;
+        vec![IforceCommand { command_id: IFORCE_STOP_ALL, data: vec![] }]
+    }
 
+    /// Commands re-sending every playing native effect with the current gains
+    fn replay_playing(&self) -> Result<Vec<IforceCommand>> {
         let mut commands = Vec::new();
+        if self.output_enabled() {
+            let now = self.clock.now();
+            for active in self.active_effects.values().filter(|active| !active.software) {
+                commands.extend(self.native_commands(&active.effect, now.duration_since(active.start_time))?);
+            }
+        }
+        Ok(commands)
+    }
+
+    /// Start `effect` `loops` times, replacing whatever plays in its block
+    fn play(&mut self, effect: FfbEffect, loops: u8) -> Result<Vec<IforceCommand>> {
+        if self.panic || !self.config.enabled {
+            return Ok(vec![]);
+        }
+
+        let kind = EffectKind::of(&effect.effect_type);
+        if !self.config.effects.is_enabled(kind) {
+            let count = self.disabled.entry(kind).or_insert(0);
+            if *count == 0 {
+                tracing::info!("Ignoring {:?} effects, disabled in ffb_config.effects", kind);
+            }
+            *count += 1;
+            self.active_effects.remove(&effect.id);
+            return Ok(vec![]);
+        }
+
+        let mut effect = effect;
+        let degradation = self.degradation_for(&effect.effect_type);
+        match degradation {
+            Degradation::Native => {}
+            Degradation::Drop => {
+                self.active_effects.remove(&effect.id);
+                return Ok(vec![]);
+            }
+            Degradation::Substitute(condition_type) => {
+                if let EffectType::Condition(condition) = &mut effect.effect_type {
+                    condition.condition_type = condition_type;
+                }
+            }
+            Degradation::Software => {
+                let now = self.clock.now();
+                let active_effect = ActiveEffect {
+                    effect: effect.clone(),
+                    start_time: now,
+                    software: true,
+                    level: 0.0,
+                    loops,
+                    shaped: false,
+                };
+                let magnitude = self.software_magnitude(&active_effect, now);
+                self.active_effects.insert(effect.id, active_effect);
+                if !self.output_enabled() {
+                    return Ok(vec![]);
+                }
+                if self.rendering() {
+                    return Ok(vec![self.rendered_command(now)]);
+                }
+                return Ok(vec![self.constant_command(effect.id, magnitude, Milliseconds::INFINITE)]);
+            }
+        }
 
         // Store effect as active
         let active_effect = ActiveEffect {
             effect: effect.clone(),
-            start_time: Instant::now(),
-            enabled: true,
+            start_time: self.clock.now(),
+            software: false,
+            level: self.native_level(&effect.effect_type),
+            loops,
+            shaped: envelope_of(&effect.effect_type)
+                .is_some_and(|(envelope, duration)| envelope.is_shaping(Duration::ZERO, duration)),
         };
         self.active_effects.insert(effect.id, active_effect);
 
-        // Generate IFORCE commands based on effect type
-        match &effect.effect_type {
-            EffectType::Constant(constant) => {
-                commands.extend(self.translate_constant_effect(effect.id, constant)?);
-            }
-            EffectType::Periodic(periodic) => {
-                commands.extend(self.translate_periodic_effect(effect.id, periodic)?);
-            }
-            EffectType::Condition(condition) => {
-                commands.extend(self.translate_condition_effect(effect.id, condition)?);
-            }
-            EffectType::Ramp(ramp) => {
-                commands.extend(self.translate_ramp_effect(effect.id, ramp)?);
-            }
+        if !self.output_enabled() {
+            return Ok(vec![]);
         }
+        self.native_commands(&effect, Duration::ZERO)
+    }
 
-        Ok(commands)
+    /// IFORCE commands playing `effect` natively from `elapsed` into its play
+    fn native_commands(&self, effect: &FfbEffect, elapsed: Duration) -> Result<Vec<IforceCommand>> {
+        match &effect.effect_type {
+            EffectType::Constant(constant) => self.translate_constant_effect(effect.id, constant, elapsed),
+            EffectType::Periodic(periodic) => self.translate_periodic_effect(effect.id, periodic, elapsed),
+            EffectType::Condition(condition) => self.translate_condition_effect(effect.id, condition),
+            EffectType::Ramp(ramp) => self.translate_ramp_effect(effect.id, ramp),
+        }
     }
 
     /// Generate periodic update commands for active effects
     pub fn update_active_effects(&mut self) -> Result<Vec<IforceCommand>> {
-        let now = Instant::now();
-        if now.duration_since(self.last_update) < Duration::from_millis(1000 / self.config.update_rate_hz as u64) {
-            return Ok(vec![]);
+        let mut commands = Vec::new();
+        self.update_active_effects_into(&mut commands)?;
+        Ok(commands)
+    }
+
+    /// Append periodic update commands for active effects to `commands`
+    ///
+    /// Lets the caller keep one buffer for the lifetime of the output loop.
+    pub fn update_active_effects_into(&mut self, commands: &mut Vec<IforceCommand>) -> Result<()> {
+        if self.panic || !self.output_enabled() {
+            return Ok(());
         }
 
-        let mut commands = Vec::new();
+        let now = self.clock.now();
+        if now.duration_since(self.last_update) < Duration::from_millis(1000 / self.config.update_rate_hz.max(1) as u64) {
+            return Ok(());
+        }
 
-        // Remove expired effects
-        self.active_effects.retain(|_, effect| {
-            if let EffectType::Constant(constant) = &effect.effect.effect_type {
-                if constant.duration > 0 {
-                    let elapsed = now.duration_since(effect.start_time);
-                    return elapsed < Duration::from_millis(constant.duration as u64);
+        // Remove expired effects, stopping software-rendered ones explicitly
+        let mut expired = std::mem::take(&mut self.expired);
+        expired.clear();
+        let mut restarted = Vec::new();
+        let timeline = &mut self.timeline;
+        self.active_effects.retain(|&effect_id, effect| {
+            let duration = match &effect.effect.effect_type {
+                EffectType::Constant(constant) => constant.duration,
+                EffectType::Ramp(ramp) if effect.software => ramp.duration,
+                _ => Milliseconds::INFINITE,
+            };
+            let alive = duration
+                .as_duration()
+                .map_or(true, |duration| now.duration_since(effect.start_time) < duration);
+            if !alive && effect.loops > 1 {
+                // Effect Operation asked for more than one play
+                if effect.loops != LOOP_FOREVER {
+                    effect.loops -= 1;
+                }
+                effect.start_time = now;
+                if !effect.software {
+                    restarted.push(effect_id);
+                }
+                push_event(timeline, EffectEvent::new(now, &effect.effect, EffectPhase::Started));
+                return true;
+            }
+            if !alive {
+                push_event(timeline, EffectEvent::new(now, &effect.effect, EffectPhase::Expired));
+                if effect.software {
+                    expired.push(effect_id);
                 }
             }
-            true
+            alive
         });
+        if !self.rendering() {
+            for &effect_id in &expired {
+                commands.push(self.constant_command(effect_id, 0, Milliseconds::INFINITE));
+            }
+        }
+        self.expired = expired;
+        for &effect_id in &restarted {
+            if let Some(active) = self.active_effects.get(&effect_id) {
+                commands.extend(self.native_commands(&active.effect, Duration::ZERO)?);
+            }
+        }
 
         // Update periodic effects
+        let mut load = 0.0;
+        let mut shaped = Vec::new();
         for (effect_id, active_effect) in &self.active_effects {
+            if active_effect.software {
+                let magnitude = self.software_magnitude(active_effect, now);
+                load += magnitude.unsigned_abs() as f32 / i16::MAX as f32;
+                if !self.rendering() {
+                    commands.push(self.constant_command(*effect_id, magnitude, Milliseconds::INFINITE));
+                }
+                continue;
+            }
+            load += active_effect.level;
             if let EffectType::Periodic(periodic) = &active_effect.effect.effect_type {
                 if let Some(cmd) = self.update_periodic_effect(*effect_id, periodic, now)? {
                     commands.push(cmd);
                 }
             }
+
+            // Native effects keep the level they were sent with, so envelopes
+            // are stepped by resending them, once more when a phase ends
+            let elapsed = now.duration_since(active_effect.start_time);
+            let shaping = envelope_of(&active_effect.effect.effect_type)
+                .is_some_and(|(envelope, duration)| envelope.is_shaping(elapsed, duration));
+            if (shaping || active_effect.shaped) && !restarted.contains(effect_id) {
+                commands.extend(self.native_commands(&active_effect.effect, elapsed)?);
+            }
+            if shaping != active_effect.shaped {
+                shaped.push((*effect_id, shaping));
+            }
+        }
+        for (effect_id, shaping) in shaped {
+            if let Some(active) = self.active_effects.get_mut(&effect_id) {
+                active.shaped = shaping;
+            }
         }
 
+        if self.rendering() {
+            commands.push(self.rendered_command(now));
+        }
+
+        self.thermal.accumulate(load, now.duration_since(self.last_update));
+        self.step_soft_start(now, commands)?;
+        self.step_autocenter(now, commands);
+        self.step_soft_lock(commands);
         self.last_update = now;
-        Ok(commands)
+        Ok(())
+    }
+
+    /// Advance the soft-start ramp, replaying native effects at the new gain
+    ///
+    /// Native effects keep the gain they were sent with, so without a replay
+    /// a spring set once during the ramp would stay weak.
+    fn step_soft_start(&mut self, now: Instant, commands: &mut Vec<IforceCommand>) -> Result<()> {
+        let Some(start) = self.soft_start else {
+            return Ok(());
+        };
+        let ramp = Duration::from_millis(self.config.soft_start_ms.max(1) as u64);
+        self.soft_start_gain = (now.duration_since(start).as_secs_f32() / ramp.as_secs_f32()).min(1.0);
+        let done = self.soft_start_gain >= 1.0;
+        if !done && now.duration_since(self.soft_start_sent) < SOFT_START_STEP {
+            return Ok(());
+        }
+
+        self.soft_start_sent = now;
+        for active in self.active_effects.values().filter(|active| !active.software) {
+            // Timed effects end before the ramp matters
+            let timed = match &active.effect.effect_type {
+                EffectType::Constant(constant) => !constant.duration.is_infinite(),
+                EffectType::Ramp(_) => true,
+                _ => false,
+            };
+            if !timed {
+                commands.extend(self.native_commands(&active.effect, now.duration_since(active.start_time))?);
+            }
+        }
+        if done {
+            self.soft_start = None;
+        }
+        Ok(())
+    }
+
+    /// Fade the autocenter spring out while the game plays effects and back in once it stops
+    ///
+    /// Bases with hardware autocenter get its strength, others a spring in
+    /// `AUTOCENTER_SLOT`; in render mode it is part of the rendered stream.
+    fn step_autocenter(&mut self, now: Instant, commands: &mut Vec<IforceCommand>) {
+        if self.config.autocenter_gain <= 0.0 || !self.config.enabled {
+            return;
+        }
+        let target = if self.active_effects.is_empty() { 1.0 } else { 0.0 };
+        self.autocenter_level = match self.config.autocenter_decay_ms {
+            0 => target,
+            decay_ms => {
+                let step = now.duration_since(self.last_update).as_secs_f32() * 1000.0 / decay_ms as f32;
+                self.autocenter_level + (target - self.autocenter_level).clamp(-step, step)
+            }
+        };
+        if self.rendering() {
+            return;
+        }
+
+        let strength = self.autocenter_strength();
+        let settled = self.autocenter_level == target;
+        if self.autocenter_sent == Some(strength)
+            || (!settled && now.duration_since(self.autocenter_sent_at) < SOFT_START_STEP)
+        {
+            return;
+        }
+        if let Some(command) = self.autocenter_command(strength) {
+            commands.push(command);
+        }
+        self.autocenter_sent = Some(strength);
+        self.autocenter_sent_at = now;
+    }
+
+    /// Send the soft lock's push back when it changed; in render mode it is part of the rendered stream
+    fn step_soft_lock(&mut self, commands: &mut Vec<IforceCommand>) {
+        if self.rendering() {
+            return;
+        }
+        let magnitude = self.clamp_full_scale(self.soft_lock_force());
+        if self.soft_lock_sent == Some(magnitude) || (self.soft_lock_sent.is_none() && magnitude == 0) {
+            return;
+        }
+        commands.push(self.constant_command(SOFT_LOCK_SLOT, magnitude, Milliseconds::INFINITE));
+        self.soft_lock_sent = Some(magnitude);
+    }
+
+    /// Push back of the soft lock at the wheel's position, in magnitude units
+    ///
+    /// Builds up linearly over `ramp_degrees` past the lock, with damping
+    /// against the wheel's motion while inside the wall.
+    fn soft_lock_force(&self) -> f32 {
+        let soft_lock = &self.config.soft_lock;
+        let Some((wheel_degrees, g29_degrees)) = self.soft_lock_ranges else {
+            return 0.0;
+        };
+        if !soft_lock.enabled || !self.config.enabled || g29_degrees == 0 || g29_degrees >= wheel_degrees {
+            return 0.0;
+        }
+        let lock = g29_degrees as f32 / wheel_degrees as f32;
+        let past = self.motion.position.abs() - lock;
+        if past <= 0.0 {
+            return 0.0;
+        }
+        // The axis spans 2.0 lock to lock
+        let ramp = (soft_lock.ramp_degrees * 2.0 / wheel_degrees as f32).max(f32::EPSILON);
+        let wall = (past / ramp).min(1.0) * soft_lock.strength;
+        let damping = (self.motion.velocity / FULL_SCALE_VELOCITY).clamp(-1.0, 1.0) * soft_lock.damping;
+        let force = (-self.motion.position.signum() * wall - damping).clamp(-1.0, 1.0);
+        force * self.config.global_gain * self.soft_start_gain * i16::MAX as f32
+    }
+
+    /// Autocenter spring strength, 0.0 - 1.0 of full scale
+    fn autocenter_strength(&self) -> f32 {
+        (self.config.autocenter_gain * self.autocenter_level * self.config.global_gain * self.soft_start_gain).clamp(0.0, 1.0)
+    }
+
+    /// Command setting the autocenter to `strength`, `None` if the base can neither center itself nor play springs
+    fn autocenter_command(&self, strength: f32) -> Option<IforceCommand> {
+        if self.capabilities.has_hardware_autocenter() {
+            let level = (strength * u8::MAX as f32).round() as u8;
+            return Some(IforceCommand { command_id: IFORCE_AUTOCENTER, data: vec![(level > 0) as u8, level] });
+        }
+        if !self.capabilities.supports(EffectKind::Spring) {
+            return None;
+        }
+        let [low, high] = self.clamp_full_scale(strength * i16::MAX as f32).to_le_bytes();
+        Some(IforceCommand {
+            command_id: 0x43, // Condition effect
+            data: vec![AUTOCENTER_SLOT, 0x01, low, high, low, high],
+        })
+    }
+
+    /// Pick how to play `effect_type` on this base, logging the first degradation of each kind
+    fn degradation_for(&mut self, effect_type: &EffectType) -> Degradation {
+        let kind = EffectKind::of(effect_type);
+        if self.rendering() {
+            return Degradation::Software;
+        }
+        if self.capabilities.supports(kind) {
+            return Degradation::Native;
+        }
+
+        let substitutes: &[(EffectKind, ConditionType)] = match kind {
+            EffectKind::Inertia => &[(EffectKind::Damper, ConditionType::Damper), (EffectKind::Friction, ConditionType::Friction)],
+            EffectKind::Damper => &[(EffectKind::Friction, ConditionType::Friction)],
+            EffectKind::Friction => &[(EffectKind::Damper, ConditionType::Damper)],
+            _ => &[],
+        };
+
+        let degradation = if matches!(kind, EffectKind::Periodic | EffectKind::Ramp)
+            && self.capabilities.supports(EffectKind::Constant)
+        {
+            Degradation::Software
+        } else {
+            substitutes
+                .iter()
+                .find(|(substitute, _)| self.capabilities.supports(*substitute))
+                .map_or(Degradation::Drop, |(_, condition_type)| Degradation::Substitute(*condition_type))
+        };
+
+        if self.degraded.insert(kind) {
+            match &degradation {
+                Degradation::Software => tracing::warn!("Wheel base lacks {:?} effects, rendering them in software", kind),
+                Degradation::Substitute(with) => tracing::warn!("Wheel base lacks {:?} effects, substituting {:?}", kind, with),
+                _ => tracing::warn!("Wheel base lacks {:?} effects, dropping them", kind),
+            }
+        }
+        degradation
+    }
+
+    /// Gain-adjusted magnitude of a software-rendered effect at `now`
+    fn software_magnitude(&self, active: &ActiveEffect, now: Instant) -> i16 {
+        let elapsed = now.duration_since(active.start_time);
+        let elapsed_ms = elapsed.as_secs_f32() * 1000.0;
+        match &active.effect.effect_type {
+            EffectType::Periodic(periodic) => {
+                let period = periodic.period.max(1) as f32;
+                let cycle = (elapsed_ms / period + periodic.phase as f32 / 360.0).fract();
+                let sample = match periodic.waveform {
+                    Waveform::Sine if self.fast_math => fast_sine(cycle),
+                    Waveform::Sine => (cycle * std::f32::consts::TAU).sin(),
+                    Waveform::Square => if cycle < 0.5 { 1.0 } else { -1.0 },
+                    Waveform::Triangle => 1.0 - 4.0 * (cycle - 0.5).abs(),
+                    Waveform::SawtoothUp => 2.0 * cycle - 1.0,
+                    Waveform::SawtoothDown => 1.0 - 2.0 * cycle,
+                };
+                let magnitude = shape(&periodic.envelope, periodic.magnitude.min(i16::MAX as u16) as f32, elapsed, None);
+                self.apply_gain((magnitude * sample) as i16, self.config.periodic_gain)
+            }
+            EffectType::Ramp(ramp) => {
+                let progress = ramp
+                    .duration
+                    .as_duration()
+                    .map_or(0.0, |duration| (elapsed_ms / duration.as_millis().max(1) as f32).min(1.0));
+                let magnitude = ramp.start_magnitude as f32 + (ramp.end_magnitude as f32 - ramp.start_magnitude as f32) * progress;
+                let magnitude = shape(&ramp.envelope, magnitude, elapsed, ramp.duration.as_duration());
+                self.apply_gain(magnitude as i16, self.config.ramp_gain)
+            }
+            EffectType::Condition(condition) => {
+                let force = self.motion.condition_force(condition);
+                self.clamp_full_scale(self.gained(force, self.condition_gain(condition.condition_type)))
+            }
+            EffectType::Constant(constant) => {
+                let magnitude = shape(&constant.envelope, constant.magnitude as f32, elapsed, constant.duration.as_duration());
+                self.clamp_full_scale(self.gained(magnitude, self.config.constant_gain))
+            }
+        }
+    }
+
+    /// Sum of every software-rendered effect at `now` as one constant force
+    fn rendered_command(&self, now: Instant) -> IforceCommand {
+        let total: f32 = self
+            .active_effects
+            .values()
+            .filter(|active| active.software)
+            .map(|active| self.software_magnitude(active, now) as f32)
+            .sum();
+        let autocenter = if self.config.autocenter_gain > 0.0 && self.config.enabled {
+            -self.motion.position * self.autocenter_strength() * i16::MAX as f32
+        } else {
+            0.0
+        };
+        let total = total + autocenter + self.soft_lock_force();
+        self.constant_command(RENDER_SLOT, self.clamp_full_scale(total), Milliseconds::INFINITE)
+    }
+
+    fn translate_constant_effect(&self, effect_id: u8, effect: &ConstantEffect, elapsed: Duration) -> Result<Vec<IforceCommand>> {
+        let magnitude = shape(&effect.envelope, effect.magnitude as f32, elapsed, effect.duration.as_duration());
+        let magnitude = self.clamp_full_scale(self.gained(magnitude, self.config.constant_gain));
+        // Resent effects only play what is left of their duration
+        let duration = match effect.duration.as_duration() {
+            Some(duration) if !elapsed.is_zero() => {
+                let remaining = duration.saturating_sub(elapsed).as_millis().max(1);
+                Milliseconds::new(remaining as u16)
+            }
+            _ => effect.duration,
+        };
+        Ok(vec![self.constant_command(effect_id, magnitude, duration)])
     }
 
-    fn translate_constant_effect(&self, effect_id: u8, effect: &ConstantEffect) -> Result<Vec<IforceCommand>> {
-        let magnitude = self.apply_gain(effect.magnitude, self.config.constant_gain);
+    /// IFORCE constant force command for an already gain-adjusted magnitude
+    fn constant_command(&self, effect_id: u8, magnitude: i16, duration: Milliseconds) -> IforceCommand {
         let scaled_magnitude = self.scale_magnitude(magnitude);
 
+        let magnitude_bytes = scaled_magnitude.to_le_bytes();
+        let duration_bytes = duration.to_le_bytes();
+
         // IFORCE constant force command (simplified)
-        let cmd = IforceCommand {
+        IforceCommand {
             command_id: 0x41, // Constant force
             data: vec![
                 effect_id,
-                scaled_magnitude as u8,
-                (scaled_magnitude >> 8) as u8,
-                (effect.duration & 0xFF) as u8,
-                (effect.duration >> 8) as u8,
+                magnitude_bytes[0],
+                magnitude_bytes[1],
+                duration_bytes[0],
+                duration_bytes[1],
             ],
-        };
-
-        Ok(vec![cmd])
+        }
     }
 
-    fn translate_periodic_effect(&self, effect_id: u8, effect: &PeriodicEffect) -> Result<Vec<IforceCommand>> {
-        let magnitude = self.apply_gain(effect.magnitude as i16, self.config.periodic_gain);
+    fn translate_periodic_effect(&self, effect_id: u8, effect: &PeriodicEffect, elapsed: Duration) -> Result<Vec<IforceCommand>> {
+        let magnitude = shape(&effect.envelope, effect.magnitude as i16 as f32, elapsed, None);
+        let magnitude = self.clamp_full_scale(self.gained(magnitude, self.config.periodic_gain));
         let scaled_magnitude = self.scale_magnitude(magnitude);
 
         // IFORCE periodic effect command
@@ -140,13 +1060,17 @@ impl FfbEngine {
         Ok(vec![cmd])
     }
 
-    fn translate_condition_effect(&self, effect_id: u8, effect: &ConditionEffect) -> Result<Vec<IforceCommand>> {
-        let gain = match effect.condition_type {
+    fn condition_gain(&self, condition_type: ConditionType) -> f32 {
+        match condition_type {
             ConditionType::Spring => self.config.spring_gain,
             ConditionType::Damper => self.config.damper_gain,
             ConditionType::Inertia => 1.0, // Not specifically configurable
             ConditionType::Friction => self.config.friction_gain,
-        };
+        }
+    }
+
+    fn translate_condition_effect(&self, effect_id: u8, effect: &ConditionEffect) -> Result<Vec<IforceCommand>> {
+        let gain = self.condition_gain(effect.condition_type);
 
         let pos_coeff = self.apply_gain(effect.positive_coefficient, gain);
         let neg_coeff = self.apply_gain(effect.negative_coefficient, gain);
@@ -174,8 +1098,13 @@ impl FfbEngine {
     }
 
     fn translate_ramp_effect(&self, effect_id: u8, effect: &RampEffect) -> Result<Vec<IforceCommand>> {
-        let start_magnitude = self.apply_gain(effect.start_magnitude, self.config.ramp_gain);
-        let end_magnitude = self.apply_gain(effect.end_magnitude, self.config.ramp_gain);
+        // The base interpolates on its own, the envelope only shapes the end points
+        let duration = effect.duration.as_duration();
+        let start_magnitude = shape(&effect.envelope, effect.start_magnitude as f32, Duration::ZERO, duration);
+        let end_magnitude = shape(&effect.envelope, effect.end_magnitude as f32, duration.unwrap_or_default(), duration);
+        let start_magnitude = self.clamp_full_scale(self.gained(start_magnitude, self.config.ramp_gain));
+        let end_magnitude = self.clamp_full_scale(self.gained(end_magnitude, self.config.ramp_gain));
+        let duration_bytes = effect.duration.to_le_bytes();
 
         let cmd = IforceCommand {
             command_id: 0x44, // Ramp effect
@@ -185,8 +1114,8 @@ impl FfbEngine {
                 (start_magnitude >> 8) as u8,
                 (end_magnitude & 0xFF) as u8,
                 (end_magnitude >> 8) as u8,
-                (effect.duration & 0xFF) as u8,
-                (effect.duration >> 8) as u8,
+                duration_bytes[0],
+                duration_bytes[1],
             ],
         };
 
@@ -204,15 +1133,42 @@ impl FfbEngine {
     }
 
     fn apply_gain(&self, value: i16, gain: f32) -> i16 {
-        let adjusted = (value as f32 * gain * self.config.global_gain).clamp(-32767.0, 32767.0);
-        adjusted as i16
+        self.clamp_full_scale(self.gained(value as f32, gain))
+    }
+
+    /// `value` with `gain`, the global and device gains, the thermal limit and the soft start applied, unclamped
+    fn gained(&self, value: f32, gain: f32) -> f32 {
+        value * gain * self.config.global_gain * self.device_gain * self.thermal.gain() * self.soft_start_gain
+    }
+
+    /// Force a natively played effect holds, as a fraction of full scale
+    ///
+    /// Condition effects depend on the wheel position, which the engine does
+    /// not see, so they are not counted.
+    fn native_level(&self, effect_type: &EffectType) -> f32 {
+        let magnitude = match effect_type {
+            EffectType::Constant(constant) => self.gained(constant.magnitude as f32, self.config.constant_gain).abs(),
+            EffectType::Periodic(periodic) => self.gained(periodic.magnitude as f32, self.config.periodic_gain).abs(),
+            EffectType::Ramp(ramp) => self
+                .gained(ramp.start_magnitude as f32, self.config.ramp_gain)
+                .abs()
+                .max(self.gained(ramp.end_magnitude as f32, self.config.ramp_gain).abs()),
+            EffectType::Condition(_) => 0.0,
+        };
+        (magnitude / i16::MAX as f32).min(1.0)
     }
 
     fn scale_magnitude(&self, magnitude: i16) -> i16 {
         // Scale to IFORCE range and apply max force limit
-        let force_ratio = self.config.max_force / 2.5; // Assuming 2.5N baseline
-        let scaled = (magnitude as f32 * force_ratio).clamp(-32767.0, 32767.0);
-        scaled as i16
+        let force_ratio = self.config.max_force.ratio_to(BASELINE_FORCE);
+        self.clamp_full_scale(magnitude as f32 * force_ratio)
+    }
+
+    fn clamp_full_scale(&self, value: f32) -> i16 {
+        if value.abs() > 32767.0 {
+            self.clipped.fetch_add(1, Ordering::Relaxed);
+        }
+        value.clamp(-32767.0, 32767.0) as i16
     }
 }
 
@@ -234,7 +1190,9 @@ pub enum EffectType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConstantEffect {
     pub magnitude: i16,
-    pub duration: u16, // milliseconds, 0 = infinite
+    pub duration: Milliseconds,
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -243,6 +1201,8 @@ pub struct PeriodicEffect {
     pub period: u16,    // milliseconds
     pub phase: u16,     // degrees (0-359)
     pub waveform: Waveform,
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -261,7 +1221,7 @@ pub struct ConditionEffect {
     pub condition_type: ConditionType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConditionType {
     Spring,
     Damper,
@@ -273,12 +1233,383 @@ pub enum ConditionType {
 pub struct RampEffect {
     pub start_magnitude: i16,
     pub end_magnitude: i16,
-    pub duration: u16,
+    pub duration: Milliseconds,
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
+}
+
+/// Attack and fade of a constant, periodic or ramp effect
+///
+/// The force starts at `attack_level` and reaches the effect's own magnitude
+/// after `attack_time`, then goes to `fade_level` over the last `fade_time`
+/// of a timed play. Levels are magnitudes in the effect's units, the sign
+/// follows the effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub attack_level: u16,
+    pub attack_time: u16, // milliseconds
+    pub fade_level: u16,
+    pub fade_time: u16,   // milliseconds
+}
+
+impl Envelope {
+    /// `magnitude` shaped for `elapsed` into a play lasting `duration`, `None` if untimed
+    pub fn apply(&self, magnitude: f32, elapsed: Duration, duration: Option<Duration>) -> f32 {
+        let sustain = magnitude.abs();
+        let elapsed_ms = elapsed.as_secs_f32() * 1000.0;
+        let level = if elapsed_ms < self.attack_time as f32 {
+            let attack = self.attack_level as f32;
+            attack + (sustain - attack) * elapsed_ms / self.attack_time as f32
+        } else if let Some(remaining_ms) = self.fade_remaining(elapsed, duration) {
+            let fade = self.fade_level as f32;
+            fade + (sustain - fade) * remaining_ms / self.fade_time as f32
+        } else {
+            sustain
+        };
+        level.copysign(magnitude)
+    }
+
+    /// Whether the force is still in its attack or already in its fade
+    pub fn is_shaping(&self, elapsed: Duration, duration: Option<Duration>) -> bool {
+        elapsed < Duration::from_millis(self.attack_time as u64) || self.fade_remaining(elapsed, duration).is_some()
+    }
+
+    /// Milliseconds left of a play in its fade
+    fn fade_remaining(&self, elapsed: Duration, duration: Option<Duration>) -> Option<f32> {
+        let remaining = duration?.saturating_sub(elapsed).as_secs_f32() * 1000.0;
+        (self.fade_time > 0 && remaining < self.fade_time as f32).then_some(remaining)
+    }
+}
+
+/// Append to the engine's timeline, dropping the oldest event when nobody collects them
+fn push_event(timeline: &mut VecDeque<EffectEvent>, event: EffectEvent) {
+    if timeline.len() == ENGINE_TIMELINE_LEN {
+        timeline.pop_front();
+    }
+    timeline.push_back(event);
+}
+
+/// Envelope of an effect and how long it plays, `None` if it has no envelope
+fn envelope_of(effect_type: &EffectType) -> Option<(&Envelope, Option<Duration>)> {
+    match effect_type {
+        EffectType::Constant(constant) => constant.envelope.as_ref().map(|envelope| (envelope, constant.duration.as_duration())),
+        EffectType::Periodic(periodic) => periodic.envelope.as_ref().map(|envelope| (envelope, None)),
+        EffectType::Ramp(ramp) => ramp.envelope.as_ref().map(|envelope| (envelope, ramp.duration.as_duration())),
+        EffectType::Condition(_) => None,
+    }
+}
+
+/// `magnitude` with `envelope` applied if there is one
+fn shape(envelope: &Option<Envelope>, magnitude: f32, elapsed: Duration, duration: Option<Duration>) -> f32 {
+    envelope.map_or(magnitude, |envelope| envelope.apply(magnitude, elapsed, duration))
 }
 
 #[derive(Debug, Clone)]
 struct ActiveEffect {
     effect: FfbEffect,
     start_time: Instant,
-    enabled: bool,
-} 
\ No newline at end of file
+    software: bool,   // Rendered as constant force updates, the base lacks the effect
+    level: f32,       // Force held when played natively, 0.0 - 1.0 of full scale
+    loops: u8,        // Plays left including the current one, `LOOP_FOREVER` until stopped
+    shaped: bool,     // Last sent below its own magnitude by an envelope
+} 
+/// `sin(2π·cycle)` for `cycle` in [0, 1), within 0.002 of the libm result
+///
+/// Parabolic approximation with one refinement step: only multiplies, adds and
+/// an `abs`, which the compiler keeps in NEON registers on ARM instead of
+/// calling out to libm for every software-rendered sample.
+fn fast_sine(cycle: f32) -> f32 {
+    let t = 2.0 * cycle - 1.0;
+    let y = 4.0 * t * (1.0 - t.abs());
+    -(0.225 * (y * y.abs() - y) + y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn engine(capabilities: FfbCapabilities, clock: &MockClock) -> FfbEngine {
+        // Autocenter commands would interleave with the effects under test, see its own test
+        let config = FfbConfig { autocenter_gain: 0.0, ..FfbConfig::default() };
+        FfbEngine::with_capabilities(&config, capabilities).with_clock(Arc::new(clock.clone()))
+    }
+
+    fn magnitude(command: &IforceCommand) -> i16 {
+        i16::from_le_bytes([command.data[1], command.data[2]])
+    }
+
+    #[test]
+    fn test_timed_constant_effect_expires_after_its_duration() {
+        let clock = MockClock::new();
+        let mut engine = engine(FfbCapabilities::all(), &clock);
+        let effect = FfbEffect {
+            id: 1,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 1000, duration: Milliseconds::new(100), envelope: None }),
+            gain: 255,
+        };
+        engine.translate_effect(effect).unwrap();
+
+        clock.advance(Duration::from_millis(60));
+        engine.update_active_effects().unwrap();
+        assert_eq!(engine.active_count(), 1);
+
+        clock.advance(Duration::from_millis(60));
+        engine.update_active_effects().unwrap();
+        assert_eq!(engine.active_count(), 0);
+
+        let phases: Vec<_> = engine.take_timeline().iter().map(|event| event.phase).collect();
+        assert_eq!(phases, [EffectPhase::Created, EffectPhase::Started, EffectPhase::Expired]);
+    }
+
+    #[test]
+    fn test_created_effects_wait_for_start_and_loop() {
+        let clock = MockClock::new();
+        let mut engine = engine(FfbCapabilities::all(), &clock);
+        engine.handle_pid(PidCommand::Create { effect_type: 0x01 }).unwrap();
+        let load = engine.block_load().unwrap();
+        assert_eq!((load.block, load.status), (1, BlockLoadStatus::Success));
+
+        let effect = FfbEffect {
+            id: 1,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 1000, duration: Milliseconds::new(100), envelope: None }),
+            gain: 255,
+        };
+        assert!(engine.translate_effect(effect).unwrap().is_empty());
+        assert_eq!(engine.active_count(), 0);
+
+        let start = engine.handle_pid(PidCommand::Start { block: 1, solo: false, loop_count: 2 }).unwrap();
+        assert_eq!(magnitude(&start[0]), 1000);
+
+        // The second play is sent again, then the effect ends
+        clock.advance(Duration::from_millis(110));
+        assert_eq!(magnitude(&engine.update_active_effects().unwrap()[0]), 1000);
+        clock.advance(Duration::from_millis(110));
+        assert!(engine.update_active_effects().unwrap().is_empty());
+        assert_eq!(engine.active_count(), 0);
+
+        // Paused devices hold their effects without playing them
+        engine.handle_pid(PidCommand::Control(DeviceControl::Pause)).unwrap();
+        assert!(engine.handle_pid(PidCommand::Start { block: 1, solo: false, loop_count: 1 }).unwrap().is_empty());
+        let resumed = engine.handle_pid(PidCommand::Control(DeviceControl::Continue)).unwrap();
+        assert_eq!(magnitude(&resumed[0]), 1000);
+
+        engine.handle_pid(PidCommand::Free { block: 1 }).unwrap();
+        assert_eq!(engine.effect_pool().available(), crate::pid::PID_EFFECT_BLOCKS);
+        assert!(engine.handle_pid(PidCommand::Start { block: 1, solo: false, loop_count: 1 }).is_err());
+    }
+
+    #[test]
+    fn test_soft_start_replays_native_effects_up_to_full_gain() {
+        let clock = MockClock::new();
+        let mut engine = engine(FfbCapabilities::all(), &clock);
+        engine.begin_soft_start();
+        let effect = FfbEffect {
+            id: 1,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 10000, duration: Milliseconds::INFINITE, envelope: None }),
+            gain: 255,
+        };
+        assert_eq!(magnitude(&engine.translate_effect(effect).unwrap()[0]), 0);
+
+        clock.advance(Duration::from_millis(1000));
+        assert_eq!(magnitude(&engine.update_active_effects().unwrap()[0]), 5000);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(magnitude(&engine.update_active_effects().unwrap()[0]), 10000);
+
+        // At full gain the game's own updates take over again
+        clock.advance(Duration::from_millis(100));
+        assert!(engine.update_active_effects().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_software_ramp_steps_with_the_clock_and_stops_at_the_end() {
+        let clock = MockClock::new();
+        let kinds = EffectKind::ALL.into_iter().filter(|&kind| kind != EffectKind::Ramp);
+        let mut engine = engine(FfbCapabilities::from_kinds(kinds), &clock);
+        let ramp = FfbEffect {
+            id: 2,
+            effect_type: EffectType::Ramp(RampEffect { start_magnitude: 0, end_magnitude: 20000, duration: Milliseconds::new(1000), envelope: None }),
+            gain: 255,
+        };
+        assert_eq!(magnitude(&engine.translate_effect(ramp).unwrap()[0]), 0);
+
+        // Nothing is due before the next update period
+        assert!(engine.update_active_effects().unwrap().is_empty());
+
+        clock.advance(Duration::from_millis(250));
+        let quarter = magnitude(&engine.update_active_effects().unwrap()[0]);
+        clock.advance(Duration::from_millis(250));
+        let half = magnitude(&engine.update_active_effects().unwrap()[0]);
+        assert!(0 < quarter && quarter < half);
+        assert!((half as i32 - 2 * quarter as i32).abs() <= 2);
+
+        clock.advance(Duration::from_millis(600));
+        let stop = engine.update_active_effects().unwrap();
+        assert_eq!(stop.len(), 1);
+        assert_eq!(magnitude(&stop[0]), 0);
+        assert_eq!(engine.active_count(), 0);
+    }
+
+    #[test]
+    fn test_envelope_shapes_native_constant() {
+        let clock = MockClock::new();
+        let mut engine = engine(FfbCapabilities::all(), &clock);
+        let envelope = Envelope { attack_level: 0, attack_time: 100, fade_level: 0, fade_time: 100 };
+        let effect = FfbEffect {
+            id: 1,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 10000, duration: Milliseconds::new(1000), envelope: Some(envelope) }),
+            gain: 255,
+        };
+        assert_eq!(magnitude(&engine.translate_effect(effect).unwrap()[0]), 0);
+
+        clock.advance(Duration::from_millis(50));
+        let attack = engine.update_active_effects().unwrap();
+        assert_eq!(magnitude(&attack[0]), 5000);
+
+        // Resent once at full strength when the attack ends, then left alone
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(magnitude(&engine.update_active_effects().unwrap()[0]), 10000);
+        clock.advance(Duration::from_millis(100));
+        assert!(engine.update_active_effects().unwrap().is_empty());
+
+        clock.advance(Duration::from_millis(700));
+        let fade = engine.update_active_effects().unwrap();
+        assert_eq!(magnitude(&fade[0]), 5000);
+    }
+
+    #[test]
+    fn test_render_mode_sums_effects_into_one_constant_force() {
+        let clock = MockClock::new();
+        let config = FfbConfig { render_mode: FfbRenderMode::Render, autocenter_gain: 0.0, ..FfbConfig::default() };
+        let mut engine = FfbEngine::with_capabilities(&config, FfbCapabilities::from_kinds([EffectKind::Constant]))
+            .with_clock(Arc::new(clock.clone()));
+        let spring = FfbEffect {
+            id: 3,
+            effect_type: EffectType::Condition(ConditionEffect {
+                positive_coefficient: 8000,
+                negative_coefficient: 8000,
+                condition_type: ConditionType::Spring,
+            }),
+            gain: 255,
+        };
+        let constant = FfbEffect {
+            id: 4,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 1000, duration: Milliseconds::INFINITE, envelope: None }),
+            gain: 255,
+        };
+        engine.translate_effect(spring).unwrap();
+        let started = engine.translate_effect(constant).unwrap();
+        assert_eq!((started[0].data[0], magnitude(&started[0])), (RENDER_SLOT, 1000));
+
+        // Half a turn right, the spring pulls back with half its coefficient
+        engine.observe_wheel(i16::MAX / 2);
+        clock.advance(Duration::from_millis(10));
+        let update = engine.update_active_effects().unwrap();
+        assert_eq!(update.len(), 1);
+        assert_eq!(update[0].data[0], RENDER_SLOT);
+        assert!((magnitude(&update[0]) - (1000 - 4000)).abs() <= 1);
+    }
+
+    #[test]
+    fn test_autocenter_fades_out_while_effects_play() {
+        let clock = MockClock::new();
+        let config = FfbConfig { autocenter_gain: 0.5, autocenter_decay_ms: 500, ..FfbConfig::default() };
+        let mut engine = FfbEngine::new(&config).with_clock(Arc::new(clock.clone()));
+        let coefficient = |commands: &[IforceCommand]| {
+            let spring = commands.iter().find(|command| command.command_id == 0x43 && command.data[0] == AUTOCENTER_SLOT);
+            spring.map(|spring| i16::from_le_bytes([spring.data[2], spring.data[3]]))
+        };
+
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(coefficient(&engine.update_active_effects().unwrap()), Some(16383));
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(coefficient(&engine.update_active_effects().unwrap()), None);
+
+        let effect = FfbEffect {
+            id: 1,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: 1000, duration: Milliseconds::INFINITE, envelope: None }),
+            gain: 255,
+        };
+        engine.translate_effect(effect).unwrap();
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(coefficient(&engine.update_active_effects().unwrap()), Some(8191));
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(coefficient(&engine.update_active_effects().unwrap()), Some(0));
+
+        let hardware = FfbEngine::with_capabilities(&config, FfbCapabilities::all().with_hardware_autocenter(true))
+            .with_clock(Arc::new(clock.clone()));
+        assert_eq!(hardware.autocenter_command(1.0).unwrap().data, vec![1, 255]);
+    }
+
+    #[test]
+    fn test_soft_lock_pushes_back_past_the_g29_lock() {
+        let clock = MockClock::new();
+        let mut engine = engine(FfbCapabilities::all(), &clock);
+        let soft_lock = |commands: &[IforceCommand]| {
+            commands.iter().find(|command| command.data.first() == Some(&SOFT_LOCK_SLOT)).map(magnitude)
+        };
+        // A 900° G29 on a 1080° wheel locks at 450°, the wall is 10° deep
+        engine.set_soft_lock(1080, 900);
+        engine.observe_wheel((i16::MAX as f32 * 440.0 / 540.0) as i16);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(soft_lock(&engine.update_active_effects().unwrap()), None);
+
+        // Halfway into the wall on the right, pushed left with half the strength
+        engine.observe_wheel((i16::MAX as f32 * 455.0 / 540.0) as i16);
+        engine.motion.velocity = 0.0;
+        clock.advance(Duration::from_millis(10));
+        let push = soft_lock(&engine.update_active_effects().unwrap()).unwrap();
+        assert!((push + i16::MAX / 2).abs() <= 20, "{}", push);
+
+        // Back inside the lock the push is released
+        engine.observe_wheel(0);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(soft_lock(&engine.update_active_effects().unwrap()), Some(0));
+        engine.set_soft_lock(900, 900);
+        engine.observe_wheel(i16::MAX);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(soft_lock(&engine.update_active_effects().unwrap()), None);
+    }
+
+    #[test]
+    fn test_fast_sine_tracks_libm() {
+        for step in 0..1000 {
+            let cycle = step as f32 / 1000.0;
+            let exact = (cycle * std::f32::consts::TAU).sin();
+            assert!((fast_sine(cycle) - exact).abs() < 0.002, "cycle {}", cycle);
+        }
+    }
+
+    #[test]
+    fn test_built_output_reports_parse_back() {
+        use crate::config::OutputConfig;
+        use crate::device::G29OutputReport;
+        use crate::protocol::OutputTranslator;
+
+        let mut translator = OutputTranslator::new(&OutputConfig::default());
+        let constant = translator.parse_ffb_effect(G29OutputReport::constant_effect(3, -1200, 250)).unwrap().unwrap();
+        assert_eq!(constant.id, 3);
+        match constant.effect_type {
+            EffectType::Constant(effect) => {
+                assert_eq!(effect.magnitude, -1200);
+                assert_eq!(effect.duration.get(), 250);
+            }
+            other => panic!("expected a constant effect, got {:?}", other),
+        }
+
+        let envelope = Envelope { attack_level: 100, attack_time: 200, fade_level: 50, fade_time: 300 };
+        assert!(translator.parse_ffb_effect(G29OutputReport::envelope(5, &envelope)).unwrap().is_none());
+        let sine = G29OutputReport::periodic_effect(5, Waveform::Sine, 4000, 100, 0);
+        match translator.parse_ffb_effect(sine).unwrap().unwrap().effect_type {
+            EffectType::Periodic(effect) => assert_eq!(effect.envelope, Some(envelope)),
+            other => panic!("expected a periodic effect, got {:?}", other),
+        }
+
+        let spring = G29OutputReport::condition_effect(4, ConditionType::Spring, 900, -900);
+        match translator.parse_ffb_effect(spring).unwrap().unwrap().effect_type {
+            EffectType::Condition(effect) => assert!(matches!(effect.condition_type, ConditionType::Spring)),
+            other => panic!("expected a condition effect, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Fingerprinting of the FFB command pattern a game sends
+//!
+//! Games drive the G29 in recognisably different ways: some repeat every
+//! stop command, some count effect blocks from 0 instead of 1. The first
+//! `FINGERPRINT_WINDOW` output reports of a session are summarised into a
+//! `Fingerprint`, which picks the first matching entry of the configured
+//! quirks table. The output translator applies that entry for the rest of
+//! the session.
+
+use crate::config::{FfbQuirks, FfbSignature};
+use crate::device::{G29OutputReport, FFB_EFFECT_REPORT_ID};
+
+/// Output reports observed before the pattern is identified
+pub const FINGERPRINT_WINDOW: usize = 100;
+
+/// Summary of the first output reports of a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub reports: usize,
+    /// Share of reports identical to the one before them
+    pub duplicate_ratio: f32,
+    pub uses_slot_zero: bool,
+    /// Distinct effect blocks written
+    pub slots: u8,
+    /// Bit `n` set when effect type byte `n` appeared, types past 63 are not tracked
+    pub effect_types: u64,
+}
+
+impl Fingerprint {
+    pub fn matches(&self, signature: &FfbSignature) -> bool {
+        signature.min_duplicate_ratio.map_or(true, |ratio| self.duplicate_ratio >= ratio)
+            && signature.uses_slot_zero.map_or(true, |zero| self.uses_slot_zero == zero)
+            && signature.max_slots.map_or(true, |max| self.slots <= max)
+            && signature
+                .effect_types
+                .iter()
+                .all(|&effect_type| effect_type < 64 && self.effect_types & (1 << effect_type) != 0)
+    }
+
+    /// First entry of `table` matching this fingerprint
+    pub fn identify<'a>(&self, table: &'a [FfbQuirks]) -> Option<&'a FfbQuirks> {
+        table.iter().find(|quirks| self.matches(&quirks.signature))
+    }
+}
+
+/// Collects the fingerprint of one session
+#[derive(Debug, Default)]
+pub struct FfbFingerprinter {
+    reports: usize,
+    duplicates: usize,
+    last: Option<Vec<u8>>,
+    /// Bit `n` set when effect block `n` was written, blocks past 63 are not tracked
+    slots: u64,
+    effect_types: u64,
+    done: bool,
+}
+
+impl FfbFingerprinter {
+    /// Account one output report, returning the fingerprint once the window fills
+    pub fn observe(&mut self, report: &G29OutputReport) -> Option<Fingerprint> {
+        if self.done || report.report_id != FFB_EFFECT_REPORT_ID {
+            return None;
+        }
+
+        self.reports += 1;
+        if self.last.as_deref() == Some(report.data.as_slice()) {
+            self.duplicates += 1;
+        }
+        self.last = Some(report.data.clone());
+        if let [slot, effect_type, ..] = report.data[..] {
+            if slot < 64 {
+                self.slots |= 1 << slot;
+            }
+            if effect_type < 64 {
+                self.effect_types |= 1 << effect_type;
+            }
+        }
+
+        if self.reports < FINGERPRINT_WINDOW {
+            return None;
+        }
+        self.done = true;
+        Some(Fingerprint {
+            reports: self.reports,
+            duplicate_ratio: self.duplicates as f32 / self.reports as f32,
+            uses_slot_zero: self.slots & 1 != 0,
+            slots: self.slots.count_ones() as u8,
+            effect_types: self.effect_types,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_stops_pick_the_dedupe_quirks() {
+        let mut fingerprinter = FfbFingerprinter::default();
+        let mut fingerprint = None;
+        for i in 0..FINGERPRINT_WINDOW {
+            // Every force update is followed by the same stop, sent three times
+            let report = match i % 4 {
+                0 => G29OutputReport::constant_effect(1, i as i16 * 100, 0),
+                _ => G29OutputReport::constant_effect(1, 0, 0),
+            };
+            fingerprint = fingerprinter.observe(&report).or(fingerprint);
+        }
+
+        let fingerprint = fingerprint.expect("window filled");
+        assert!(!fingerprint.uses_slot_zero);
+        assert_eq!(fingerprint.slots, 1);
+        assert!(fingerprint.duplicate_ratio > 0.3);
+
+        let quirks = fingerprint.identify(&FfbQuirks::shipped()).expect("shipped entry matches");
+        assert!(quirks.drop_duplicates);
+        assert!(fingerprinter.observe(&G29OutputReport::constant_effect(1, 0, 0)).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+//! Host detection and tuning for low-power single board computers
+//!
+//! A Raspberry Pi can run the translator, but the desktop defaults (a 1 kHz
+//! input loop, 1 kHz FFB updates) leave it little headroom once a recorder or
+//! telemetry provider is running too. `HostConfig::embedded` trades rate for
+//! steadiness, and `detect_board` lets the CLI suggest it when the device tree
+//! names a known board.
+
+use crate::config::{FfbConfig, HostConfig};
+use std::time::Duration;
+
+/// Device tree model files, the first readable one names the board
+const MODEL_PATHS: [&str; 2] = ["/proc/device-tree/model", "/sys/firmware/devicetree/base/model"];
+
+/// Model prefixes of boards the embedded mode is meant for
+const EMBEDDED_BOARDS: [&str; 5] = ["Raspberry Pi", "Radxa ROCK", "Orange Pi", "Libre Computer", "Pine64"];
+
+/// Model of the single board computer this runs on, if it is a known one
+pub fn detect_board() -> Option<String> {
+    MODEL_PATHS
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .and_then(|model| embedded_board(&model))
+}
+
+/// `model` as read from the device tree, if it names a board that wants embedded mode
+fn embedded_board(model: &str) -> Option<String> {
+    // Device tree strings are NUL terminated
+    let model = model.trim_end_matches('\0').trim();
+    EMBEDDED_BOARDS
+        .iter()
+        .any(|board| model.starts_with(board))
+        .then(|| model.to_string())
+}
+
+impl HostConfig {
+    /// Period of the input translation loop
+    pub fn input_interval(&self) -> Duration {
+        if !self.embedded {
+            return Duration::from_millis(1);
+        }
+        Duration::from_micros(1_000_000 / self.input_poll_hz.max(1) as u64)
+    }
+
+    /// `ffb` with its update rate capped for this host
+    pub fn tune_ffb(&self, ffb: &FfbConfig) -> FfbConfig {
+        let mut ffb = ffb.clone();
+        if self.embedded {
+            ffb.update_rate_hz = ffb.update_rate_hz.min(self.ffb_update_rate_hz.max(1));
+        }
+        ffb
+    }
+
+    /// What the input loop does when a tick is late
+    pub fn missed_tick_behavior(&self) -> tokio::time::MissedTickBehavior {
+        if self.embedded && self.skip_missed_ticks {
+            // Catching up in a burst would keep a slow core spinning
+            tokio::time::MissedTickBehavior::Skip
+        } else {
+            tokio::time::MissedTickBehavior::Burst
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_board_from_model() {
+        assert_eq!(
+            embedded_board("Raspberry Pi 4 Model B Rev 1.4\0").as_deref(),
+            Some("Raspberry Pi 4 Model B Rev 1.4")
+        );
+        assert_eq!(embedded_board("QEMU Virtual Machine\0"), None);
+
+        let host = HostConfig { embedded: true, ..HostConfig::default() };
+        assert_eq!(host.input_interval(), Duration::from_millis(2));
+        assert_eq!(host.tune_ffb(&FfbConfig::default()).update_rate_hz, 250);
+        assert_eq!(HostConfig::default().input_interval(), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,73 @@
+//! Global keyboard shortcuts for runtime actions
+//!
+//! For wheels without spare buttons, actions like pause and recenter can be
+//! bound to system-wide hotkeys. Registration goes through the platform
+//! hotkey API (X11 on Linux, RegisterHotKey on Windows, Carbon on macOS) and
+//! is only compiled with the `hotkeys` feature. Wayland sessions do not allow
+//! global grabs, so there registration fails and the translator runs without
+//! hotkeys.
+
+use crate::config::HotkeyConfig;
+use crate::control::TranslatorControl;
+use crate::error::{Result, TranslatorError};
+
+#[cfg(feature = "hotkeys")]
+pub fn spawn(config: &HotkeyConfig, control: TranslatorControl) -> Result<std::thread::JoinHandle<()>> {
+    use global_hotkey::hotkey::HotKey;
+    use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+    use std::collections::HashMap;
+
+    let mut parsed = Vec::with_capacity(config.bindings.len());
+    for (binding, &action) in &config.bindings {
+        let hotkey: HotKey = binding
+            .parse()
+            .map_err(|e| TranslatorError::config_error(format!("Invalid hotkey {:?}: {}", binding, e)))?;
+        parsed.push((binding.clone(), hotkey, action));
+    }
+
+    // The manager must live on the thread that receives its events
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    let handle = std::thread::Builder::new()
+        .name("tm-g29-hotkeys".to_string())
+        .spawn(move || {
+            let manager = match GlobalHotKeyManager::new() {
+                Ok(manager) => manager,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e.to_string()));
+                    return;
+                }
+            };
+
+            let mut actions = HashMap::new();
+            for (binding, hotkey, action) in parsed {
+                if let Err(e) = manager.register(hotkey) {
+                    let _ = ready_tx.send(Err(format!("cannot register {}: {}", binding, e)));
+                    return;
+                }
+                tracing::info!("Hotkey {} bound to {:?}", binding, action);
+                actions.insert(hotkey.id(), action);
+            }
+            let _ = ready_tx.send(Ok(()));
+
+            while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+                if event.state != HotKeyState::Pressed {
+                    continue;
+                }
+                if let Some(&action) = actions.get(&event.id) {
+                    tracing::debug!("Hotkey triggered {:?}", action);
+                    control.trigger(action);
+                }
+            }
+        })?;
+
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(handle),
+        Ok(Err(reason)) => Err(TranslatorError::config_error(format!("Hotkeys unavailable: {}", reason))),
+        Err(_) => Err(TranslatorError::config_error("Hotkey thread exited during setup")),
+    }
+}
+
+#[cfg(not(feature = "hotkeys"))]
+pub fn spawn(_config: &HotkeyConfig, _control: TranslatorControl) -> Result<std::thread::JoinHandle<()>> {
+    Err(TranslatorError::UnsupportedPlatform)
+}
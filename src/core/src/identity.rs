@@ -0,0 +1,206 @@
+//! Serial number the virtual G29 presents to games
+//!
+//! Some launchers and anti-cheat systems key per-device settings or bans on
+//! the controller's identity, so users need to choose whether the virtual
+//! wheel keeps a fixed serial, follows the physical wheel, or changes. Random
+//! serials are written to a small state file so that "per boot" really means
+//! per boot and not per translator restart.
+
+use crate::config::{G29Config, SerialPolicy};
+use crate::error::{Result, TranslatorError};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Prefix of generated serials, same length as the default "TM2G29001"
+const SERIAL_PREFIX: &str = "TM";
+
+/// Random serial remembered between translator runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityState {
+    pub serial: String,
+    /// Boot the serial was generated in, for `SerialPolicy::RandomPerBoot`
+    pub boot_id: Option<String>,
+}
+
+/// Default location of the identity state file
+///
+/// `$XDG_STATE_HOME/tm-g29/identity.toml` (or `~/.local/state`) on Unix,
+/// `%LOCALAPPDATA%\tm-g29\identity.toml` on Windows.
+pub fn default_state_path() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))
+    };
+    base.unwrap_or_else(std::env::temp_dir).join("tm-g29").join("identity.toml")
+}
+
+/// Identifier of the running boot, if the platform exposes one
+#[cfg(target_os = "linux")]
+pub fn current_boot_id() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+/// Identifier of the running boot, its boot time as a Windows file time
+#[cfg(windows)]
+pub fn current_boot_id() -> Option<String> {
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_OperatingSystem).LastBootUpTime.ToFileTimeUtc()"])
+        .output()
+        .ok()?;
+    let boot_time: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(format!("boot-{}", boot_time))
+}
+
+/// Identifier of the running boot, its boot time from `kern.boottime`
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn current_boot_id() -> Option<String> {
+    let output = std::process::Command::new("sysctl").args(["-n", "kern.boottime"]).output().ok()?;
+    parse_boottime(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Boot id from `{ sec = 1697400000, usec = 123456 } Mon Oct 16 ...`
+#[cfg(not(any(target_os = "linux", windows)))]
+fn parse_boottime(boottime: &str) -> Option<String> {
+    let field = |name: &str| -> Option<u64> {
+        let value = boottime.split(name).nth(1)?.trim_start().strip_prefix('=')?;
+        value.trim_start().split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+    };
+    Some(format!("boot-{}.{:06}", field("sec")?, field("usec").unwrap_or(0)))
+}
+
+/// Serial for the virtual device under `config.serial_policy`
+///
+/// `wheel_serial` is the physical wheel's serial, if it reports one. Random
+/// serials are reused from, and saved to, `state_path`.
+pub fn resolve_serial(config: &G29Config, wheel_serial: Option<&str>, state_path: &Path) -> Result<String> {
+    match config.serial_policy {
+        SerialPolicy::Fixed => Ok(config.serial_number.clone()),
+        SerialPolicy::FromWheel => match wheel_serial.filter(|serial| !serial.is_empty()) {
+            Some(serial) => Ok(derived_serial(serial)),
+            None => {
+                tracing::warn!("Wheel reports no serial number, using the configured serial");
+                Ok(config.serial_number.clone())
+            }
+        },
+        SerialPolicy::RandomPerBoot => {
+            let boot_id = current_boot_id();
+            if boot_id.is_none() {
+                tracing::warn!("Cannot tell this boot from the last one, the random serial is kept across reboots");
+            }
+            persisted_serial(state_path, boot_id)
+        }
+        SerialPolicy::RandomPersistent => persisted_serial(state_path, None),
+    }
+}
+
+/// Stable serial derived from the wheel's, so the real serial is never exposed
+fn derived_serial(wheel_serial: &str) -> String {
+    // FNV-1a, stable across builds unlike the std hasher
+    let hash = wheel_serial
+        .bytes()
+        .fold(0x811c_9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193));
+    format!("{}{:07X}", SERIAL_PREFIX, hash & 0x0FFF_FFFF)
+}
+
+fn random_serial() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    std::time::SystemTime::now().hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    format!("{}{:07X}", SERIAL_PREFIX, hasher.finish() & 0x0FFF_FFFF)
+}
+
+/// Reuse the saved serial while `boot_id` matches, otherwise generate and save a new one
+fn persisted_serial(state_path: &Path, boot_id: Option<String>) -> Result<String> {
+    let saved = load_state(state_path);
+    if let Some(state) = saved.filter(|state| state.boot_id == boot_id) {
+        return Ok(state.serial);
+    }
+
+    let state = IdentityState { serial: random_serial(), boot_id };
+    save_state(state_path, &state)?;
+    tracing::info!("Generated virtual device serial {}", state.serial);
+    Ok(state.serial)
+}
+
+pub fn load_state(path: &Path) -> Option<IdentityState> {
+    let content = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&content).ok()
+}
+
+fn save_state(path: &Path, state: &IdentityState) -> Result<()> {
+    let content = toml::to_string(state)
+        .map_err(|e| TranslatorError::config_error(format!("Cannot encode identity state: {}", e)))?;
+    crate::persist::write_atomic(path, content.as_bytes())
+}
+
+/// Forget the saved random serial, the next run generates a new one
+pub fn reset_state(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_serials_last_as_long_as_their_boot() {
+        let path = std::env::temp_dir().join(format!("tm-g29-identity-{}", std::process::id())).join("identity.toml");
+        let first = persisted_serial(&path, Some("boot-a".to_string())).unwrap();
+        assert_eq!(first.len(), "TM2G29001".len());
+        assert!(first.starts_with(SERIAL_PREFIX));
+        assert_eq!(persisted_serial(&path, Some("boot-a".to_string())).unwrap(), first);
+        assert_eq!(load_state(&path).unwrap().boot_id.as_deref(), Some("boot-a"));
+
+        // Another boot gets another serial, which is saved in turn
+        let second = persisted_serial(&path, Some("boot-b".to_string())).unwrap();
+        assert_ne!(second, first);
+        assert_eq!(persisted_serial(&path, Some("boot-b".to_string())).unwrap(), second);
+
+        reset_state(&path).unwrap();
+        assert!(load_state(&path).is_none());
+        reset_state(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_serial_policies() {
+        let path = Path::new("/nonexistent/identity.toml");
+        let mut config = G29Config { serial_policy: SerialPolicy::Fixed, ..G29Config::default() };
+        assert_eq!(resolve_serial(&config, Some("WHEEL123"), path).unwrap(), config.serial_number);
+
+        // Derived serials are stable per wheel and never the wheel's own
+        config.serial_policy = SerialPolicy::FromWheel;
+        let derived = resolve_serial(&config, Some("WHEEL123"), path).unwrap();
+        assert_eq!(derived, resolve_serial(&config, Some("WHEEL123"), path).unwrap());
+        assert_ne!(derived, resolve_serial(&config, Some("WHEEL124"), path).unwrap());
+        assert!(!derived.contains("WHEEL"));
+        assert_eq!(resolve_serial(&config, Some(""), path).unwrap(), config.serial_number);
+        assert_eq!(resolve_serial(&config, None, path).unwrap(), config.serial_number);
+    }
+
+    #[test]
+    fn test_boot_id_identifies_this_boot() {
+        if let Some(boot_id) = current_boot_id() {
+            assert!(!boot_id.is_empty());
+            assert_eq!(current_boot_id(), Some(boot_id));
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    #[test]
+    fn test_boottime_parses_to_a_boot_id() {
+        let boottime = "{ sec = 1697400000, usec = 123456 } Sun Oct 15 20:00:00 2023";
+        assert_eq!(parse_boottime(boottime).as_deref(), Some("boot-1697400000.123456"));
+        assert!(parse_boottime("").is_none());
+    }
+}
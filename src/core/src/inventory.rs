@@ -0,0 +1,181 @@
+//! Nicknames for the physical devices of a rig
+//!
+//! Once a rig has a base, pedals, a shifter and maybe a second base, VID:PID
+//! pairs and serials stop being something users can keep apart. The
+//! inventory is a small TOML file giving each device, by serial, a nickname
+//! the user picked. `discover`, `ctl status` and `monitor` show nicknames,
+//! and the config can select a device with `device = "<nickname>"` instead
+//! of spelling out its VID, PID and serial.
+
+use crate::config::Config;
+use crate::error::{Result, TranslatorError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One named device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub nickname: String,
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub serial: Option<String>,  // Any device with this VID:PID when unset
+    #[serde(default)]
+    pub product: String,         // Product string when it was named, for listings
+}
+
+impl InventoryEntry {
+    /// Whether this entry names the device with these identifiers
+    pub fn matches(&self, vid: u16, pid: u16, serial: Option<&str>) -> bool {
+        self.vid == vid && self.pid == pid && self.serial.as_deref().map_or(true, |own| serial == Some(own))
+    }
+}
+
+/// Devices of the rig, as stored in the inventory file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    #[serde(default, rename = "device")]
+    pub devices: Vec<InventoryEntry>,
+}
+
+/// Default location of the inventory file
+///
+/// `$XDG_CONFIG_HOME/tm-g29/devices.toml` (or `~/.config`) on Unix,
+/// `%APPDATA%\tm-g29\devices.toml` on Windows.
+pub fn default_path() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    base.unwrap_or_else(std::env::temp_dir).join("tm-g29").join("devices.toml")
+}
+
+impl Inventory {
+    /// Inventory stored at `path`, empty if there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| {
+                TranslatorError::config_error(format!("Invalid device inventory {}: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string(self)
+            .map_err(|e| TranslatorError::config_error(format!("Cannot encode device inventory: {}", e)))?;
+        crate::persist::write_atomic(path, content.as_bytes())
+    }
+
+    /// Name a device, replacing the nickname it had
+    ///
+    /// Fails if another device already has the nickname.
+    pub fn set(&mut self, entry: InventoryEntry) -> Result<()> {
+        let same_device = |other: &InventoryEntry| {
+            (other.vid, other.pid, &other.serial) == (entry.vid, entry.pid, &entry.serial)
+        };
+        if self.devices.iter().any(|other| other.nickname == entry.nickname && !same_device(other)) {
+            return Err(TranslatorError::config_error(format!(
+                "Nickname '{}' is already used by another device",
+                entry.nickname
+            )));
+        }
+        self.devices.retain(|other| !same_device(other));
+        self.devices.push(entry);
+        Ok(())
+    }
+
+    /// Forget the device with this nickname or serial, `false` if there is none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.devices.len();
+        self.devices.retain(|entry| entry.nickname != name && entry.serial.as_deref() != Some(name));
+        self.devices.len() != before
+    }
+
+    pub fn find(&self, nickname: &str) -> Option<&InventoryEntry> {
+        self.devices.iter().find(|entry| entry.nickname == nickname)
+    }
+
+    /// Nickname of a device, preferring an entry for its serial over one for its whole VID:PID
+    pub fn nickname(&self, vid: u16, pid: u16, serial: Option<&str>) -> Option<&str> {
+        self.devices
+            .iter()
+            .filter(|entry| entry.matches(vid, pid, serial))
+            .max_by_key(|entry| entry.serial.is_some())
+            .map(|entry| entry.nickname.as_str())
+    }
+
+    /// Fill in the VID, PID and serial of every device the config selects by nickname
+    pub fn resolve_selectors(&self, config: &mut Config) -> Result<()> {
+        let lookup = |nickname: &str| {
+            self.find(nickname).ok_or_else(|| {
+                TranslatorError::config_error(format!(
+                    "Unknown device '{}', name it with `tm-g29 devices name {}`",
+                    nickname, nickname
+                ))
+            })
+        };
+
+        let wheel = &mut config.thrustmaster_config;
+        if let Some(nickname) = &wheel.device {
+            let entry = lookup(nickname)?;
+            (wheel.vid, wheel.pid, wheel.serial_number) = (entry.vid, entry.pid, entry.serial.clone());
+        }
+        for set in &mut config.pedal_sets {
+            if let Some(nickname) = &set.device {
+                let entry = lookup(nickname)?;
+                (set.vid, set.pid, set.serial_number) = (entry.vid, entry.pid, entry.serial.clone());
+            }
+        }
+        let codriver = &mut config.codriver_config;
+        if let Some(nickname) = &codriver.device {
+            let entry = lookup(nickname)?;
+            (codriver.vid, codriver.pid, codriver.serial_number) = (entry.vid, entry.pid, entry.serial.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(nickname: &str, pid: u16, serial: Option<&str>) -> InventoryEntry {
+        InventoryEntry {
+            nickname: nickname.to_string(),
+            vid: 0x044F,
+            pid,
+            serial: serial.map(str::to_string),
+            product: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_nicknames_select_devices_in_config() {
+        let mut inventory = Inventory::default();
+        inventory.set(entry("main base", 0xB66E, Some("A1"))).unwrap();
+        inventory.set(entry("spare base", 0xB66E, Some("B2"))).unwrap();
+        inventory.set(entry("pedals", 0xB678, None)).unwrap();
+        assert!(inventory.set(entry("pedals", 0xB679, None)).is_err());
+        inventory.set(entry("old base", 0xB66E, Some("A1"))).unwrap();
+
+        assert_eq!(inventory.nickname(0x044F, 0xB66E, Some("A1")), Some("old base"));
+        assert_eq!(inventory.nickname(0x044F, 0xB66E, None), None);
+        assert_eq!(inventory.nickname(0x044F, 0xB678, Some("any")), Some("pedals"));
+
+        let mut config = Config::default();
+        config.thrustmaster_config.device = Some("spare base".to_string());
+        inventory.resolve_selectors(&mut config).unwrap();
+        assert_eq!(config.thrustmaster_config.pid, 0xB66E);
+        assert_eq!(config.thrustmaster_config.serial_number.as_deref(), Some("B2"));
+
+        config.thrustmaster_config.device = Some("main base".to_string());
+        assert!(inventory.resolve_selectors(&mut config).is_err());
+        assert!(inventory.remove("B2"));
+        assert_eq!(inventory.devices.len(), 2);
+    }
+}
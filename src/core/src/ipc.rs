@@ -0,0 +1,834 @@
+//! Local control channel of a running translator
+//!
+//! The daemon listens on a Unix domain socket, or a named pipe on Windows,
+//! and speaks the same line protocol on both: the client sends one command
+//! line, the server answers with one line starting with `ok` or `error`.
+//! A plain text protocol keeps `tm-g29 ctl` trivial and lets scripts talk to
+//! the daemon with `socat` or PowerShell's `NamedPipeClientStream`.
+//!
+//! Commands: `status`, `pause`, `resume`, `toggle-pause`, `reload`,
+//! `gain <0.0-1.0>`, `profile [name]`, `shutdown`, `dump <path>`, and
+//! `subscribe <interval_ms>`, which is answered with
+//! `ok streaming` followed by one `sample` line per interval for read-only
+//! viewers such as streaming overlays. `timeline` is answered the same way
+//! and streams FFB effect lifetime events as `effect` lines, starting with
+//! the ones still kept; see `timeline` for their format.
+//!
+//! A line starting with `{` is the same request as a JSON object, e.g.
+//! `{"command":"gain","value":0.8}`, with its argument as `value`, `path`
+//! or `name`. It is answered as `{"ok":true,"message":"..."}`, for clients
+//! that would rather not split lines; streamed lines stay as they are.
+
+use crate::config::{Config, SteeringGeometry};
+use crate::control::{self, StateSample, TranslatorControl};
+use crate::error::{Result, TranslatorError};
+use crate::profile;
+use crate::timeline::TimelineEntry;
+use crate::validation;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpcRequest {
+    Status,
+    Pause,
+    Resume,
+    TogglePause,
+    /// Reload the configuration file the daemon was started with
+    Reload,
+    /// Write the recent-state history as a trace file, named inside the state-dump directory
+    DumpState(PathBuf),
+    /// Stream the latest state every `interval_ms`
+    Subscribe { interval_ms: u16 },
+    /// Stream FFB effect lifetime events as they happen
+    Timeline,
+    /// Set the global FFB gain until the next reload
+    SetGain(f32),
+    /// Set the steering center offset and per-side scaling until the next reload
+    SetGeometry { center_offset: f32, geometry: SteeringGeometry },
+    /// Switch to a profile, or back to the configured one with `None`
+    Profile(Option<String>),
+    /// Stop the translator cleanly
+    Shutdown,
+}
+
+/// Request in its JSON form
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JsonRequest {
+    command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    values: Option<Vec<f64>>,
+}
+
+/// Fastest sample stream a subscriber can request
+const MIN_SUBSCRIBE_INTERVAL_MS: u16 = 10;
+
+/// Default sample interval of `subscribe` without argument
+pub const DEFAULT_SUBSCRIBE_INTERVAL_MS: u16 = 50;
+
+impl IpcRequest {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (line, None),
+        };
+
+        let request = match (command, argument) {
+            ("status", None) => Some(Self::Status),
+            ("pause", None) => Some(Self::Pause),
+            ("resume", None) => Some(Self::Resume),
+            ("toggle-pause", None) => Some(Self::TogglePause),
+            ("reload", None) => Some(Self::Reload),
+            ("dump", Some(path)) if !path.is_empty() => Some(Self::DumpState(PathBuf::from(path))),
+            ("timeline", None) => Some(Self::Timeline),
+            ("subscribe", None) => Some(Self::Subscribe { interval_ms: DEFAULT_SUBSCRIBE_INTERVAL_MS }),
+            ("subscribe", Some(interval)) => interval.parse().ok().map(|interval_ms| Self::Subscribe { interval_ms }),
+            ("gain", Some(gain)) => gain.parse().ok().filter(|gain| (0.0..=1.0).contains(gain)).map(Self::SetGain),
+            ("geometry", Some(values)) => {
+                parse_geometry(values).map(|(center_offset, geometry)| Self::SetGeometry { center_offset, geometry })
+            }
+            ("profile", None) => Some(Self::Profile(None)),
+            ("profile", Some(name)) if !name.is_empty() => Some(Self::Profile(Some(name.to_string()))),
+            ("shutdown", None) => Some(Self::Shutdown),
+            _ => None,
+        };
+        request.ok_or_else(|| TranslatorError::protocol_error(format!("Unknown control command: {}", line)))
+    }
+
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Status => "status".to_string(),
+            Self::Pause => "pause".to_string(),
+            Self::Resume => "resume".to_string(),
+            Self::TogglePause => "toggle-pause".to_string(),
+            Self::Reload => "reload".to_string(),
+            Self::DumpState(path) => format!("dump {}", path.display()),
+            Self::Subscribe { interval_ms } => format!("subscribe {}", interval_ms),
+            Self::Timeline => "timeline".to_string(),
+            Self::SetGain(gain) => format!("gain {}", gain),
+            Self::SetGeometry { center_offset, geometry } => {
+                format!("geometry {} {} {}", center_offset, geometry.left_scale, geometry.right_scale)
+            }
+            Self::Profile(Some(name)) => format!("profile {}", name),
+            Self::Profile(None) => "profile".to_string(),
+            Self::Shutdown => "shutdown".to_string(),
+        }
+    }
+
+    /// Parse a request line in either form
+    pub fn parse_any(line: &str) -> Result<Self> {
+        if line.trim_start().starts_with('{') {
+            Self::parse_json(line)
+        } else {
+            Self::parse(line)
+        }
+    }
+
+    pub fn parse_json(line: &str) -> Result<Self> {
+        let request: JsonRequest = serde_json::from_str(line)
+            .map_err(|e| TranslatorError::protocol_error(format!("Malformed JSON control request: {}", e)))?;
+        let argument = request
+            .path
+            .map(|path| path.display().to_string())
+            .or(request.name)
+            .or(request.value.map(|value| value.to_string()))
+            .or(request.values.map(|values| values.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")));
+        match argument {
+            Some(argument) => Self::parse(&format!("{} {}", request.command, argument)),
+            None => Self::parse(&request.command),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let line = self.to_line();
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        let mut request = JsonRequest { command: command.to_string(), ..JsonRequest::default() };
+        match self {
+            Self::DumpState(path) => request.path = Some(path.clone()),
+            Self::Profile(name) => request.name = name.clone(),
+            Self::Subscribe { .. } | Self::SetGain(_) => request.value = argument.parse().ok(),
+            Self::SetGeometry { .. } => {
+                request.values = argument.split(' ').map(str::parse).collect::<std::result::Result<_, _>>().ok()
+            }
+            _ => {}
+        }
+        serde_json::to_string(&request).unwrap_or_default()
+    }
+}
+
+/// `<center offset> <left scale> <right scale>`, `None` when out of range
+fn parse_geometry(values: &str) -> Option<(f32, SteeringGeometry)> {
+    let values: Vec<f32> = values.split_whitespace().map(str::parse).collect::<std::result::Result<_, _>>().ok()?;
+    let [center_offset, left_scale, right_scale] = values[..] else {
+        return None;
+    };
+    let geometry = SteeringGeometry { left_scale, right_scale };
+    geometry.check(center_offset).is_ok().then_some((center_offset, geometry))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpcResponse {
+    pub ok: bool,
+    pub message: String,
+}
+
+impl IpcResponse {
+    fn ok(message: impl Into<String>) -> Self {
+        Self { ok: true, message: message.into() }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, message: message.into() }
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim_end();
+        match line.split_once(' ').unwrap_or((line, "")) {
+            ("ok", message) => Ok(Self::ok(message)),
+            ("error", message) => Ok(Self::error(message)),
+            _ => Err(TranslatorError::protocol_error(format!("Malformed control response: {}", line))),
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{} {}", if self.ok { "ok" } else { "error" }, self.message)
+    }
+
+    pub fn parse_json(line: &str) -> Result<Self> {
+        serde_json::from_str(line.trim_end())
+            .map_err(|e| TranslatorError::protocol_error(format!("Malformed JSON control response: {}", e)))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Pipeline state streamed to subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpcSample {
+    pub steering: u16,
+    pub throttle: u16,
+    pub brake: u16,
+    pub clutch: u16,
+    pub buttons: u32,
+    pub force: i16,
+    pub paused: bool,
+    pub steering_range: u16,
+}
+
+impl From<StateSample> for IpcSample {
+    fn from(sample: StateSample) -> Self {
+        Self {
+            steering: sample.report.steering,
+            throttle: sample.report.throttle,
+            brake: sample.report.brake,
+            clutch: sample.report.clutch,
+            buttons: sample.report.buttons,
+            force: sample.force,
+            paused: sample.paused,
+            steering_range: sample.steering_range,
+        }
+    }
+}
+
+impl IpcSample {
+    /// `sample <steering> <throttle> <brake> <clutch> <buttons> <force> <paused> <steering_range>`
+    pub fn to_line(&self) -> String {
+        format!(
+            "sample {} {} {} {} {} {} {} {}",
+            self.steering,
+            self.throttle,
+            self.brake,
+            self.clutch,
+            self.buttons,
+            self.force,
+            self.paused as u8,
+            self.steering_range
+        )
+    }
+
+    /// Steering position in degrees from center, if the daemon reported its rotation range
+    pub fn steering_degrees(&self) -> Option<f32> {
+        control::steering_degrees(self.steering, self.steering_range)
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let malformed = || TranslatorError::protocol_error(format!("Malformed sample: {}", line.trim_end()));
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 9 || fields[0] != "sample" {
+            return Err(malformed());
+        }
+
+        Ok(Self {
+            steering: fields[1].parse().map_err(|_| malformed())?,
+            throttle: fields[2].parse().map_err(|_| malformed())?,
+            brake: fields[3].parse().map_err(|_| malformed())?,
+            clutch: fields[4].parse().map_err(|_| malformed())?,
+            buttons: fields[5].parse().map_err(|_| malformed())?,
+            force: fields[6].parse().map_err(|_| malformed())?,
+            paused: fields[7] == "1",
+            steering_range: fields[8].parse().map_err(|_| malformed())?,
+        })
+    }
+}
+
+/// Platform default control endpoint
+///
+/// `$XDG_RUNTIME_DIR/tm-g29.sock` (or the temp dir) on Unix, `\\.\pipe\tm-g29` on Windows.
+pub fn default_endpoint() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"\\.\pipe\tm-g29")
+    } else {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("tm-g29.sock")
+    }
+}
+
+/// Apply a request to the running translator
+///
+/// State dumps are only written into `dump_dir`, the directory of `--state-dump`.
+pub fn handle_request(control: &TranslatorControl, config_path: &Path, dump_dir: &Path, request: &IpcRequest) -> IpcResponse {
+    match request {
+        IpcRequest::Status => {
+            let read_strategy = control
+                .read_strategy()
+                .map(|selection| selection.strategy.to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            let state = control.snapshot();
+            let stats = control.stats();
+            let steering = state
+                .report
+                .and_then(|report| control::steering_degrees(report.steering, state.steering_range))
+                .map(|degrees| format!("{:+.1}deg", degrees))
+                .unwrap_or_else(|| "unknown".to_string());
+            // The wheel name may contain spaces, so it comes last
+            IpcResponse::ok(format!(
+                "paused={} history={} read_strategy={} steering={} range={} rim={} gain={:.2} profile={} effects={} reports={} warnings={} suppressed={} wheel={}",
+                control.is_paused(),
+                control.state_snapshot().inputs.len(),
+                read_strategy,
+                steering,
+                state.steering_range,
+                state.rim.map(|rim| rim.key()).unwrap_or_else(|| "unknown".to_string()),
+                state.ffb_gain,
+                control.game_profile().unwrap_or_else(|| "configured".to_string()),
+                state.effects.len(),
+                stats.input_reports,
+                stats.warnings,
+                stats.suppressed_warnings,
+                control.wheel_name().unwrap_or_else(|| "unknown".to_string())
+            ))
+        }
+        IpcRequest::Pause => {
+            control.set_paused(true);
+            IpcResponse::ok("paused")
+        }
+        IpcRequest::Resume => {
+            control.set_paused(false);
+            IpcResponse::ok("resumed")
+        }
+        IpcRequest::TogglePause => {
+            IpcResponse::ok(if control.toggle_pause() { "paused" } else { "resumed" })
+        }
+        IpcRequest::Reload => match reload_config(config_path) {
+            Ok(config) => {
+                control.request_reload(config);
+                IpcResponse::ok(format!("reloading {}", config_path.display()))
+            }
+            Err(e) => IpcResponse::error(e),
+        },
+        IpcRequest::SetGain(gain) => {
+            control.request_gain(*gain);
+            IpcResponse::ok(format!("gain {:.2}", gain))
+        }
+        IpcRequest::SetGeometry { center_offset, geometry } => {
+            control.request_steering_geometry(*center_offset, *geometry);
+            IpcResponse::ok(format!(
+                "geometry center {:+.3} left {:.3} right {:.3}",
+                center_offset, geometry.left_scale, geometry.right_scale
+            ))
+        }
+        IpcRequest::Profile(name) => {
+            let config = match reload_config(config_path) {
+                Ok(config) => config,
+                Err(e) => return IpcResponse::error(e),
+            };
+            if let Some(name) = name {
+                match profile::list(&config) {
+                    Ok(profiles) if profiles.iter().any(|profile| &profile.name == name) => {}
+                    Ok(_) => return IpcResponse::error(format!("no profile named {}", name)),
+                    Err(e) => return IpcResponse::error(format!("failed to list profiles: {}", e)),
+                }
+            }
+            control.set_game_profile(name.clone());
+            control.request_reload(config);
+            IpcResponse::ok(match name {
+                Some(name) => format!("switching to profile {}", name),
+                None => "switching to the configured profile".to_string(),
+            })
+        }
+        IpcRequest::Shutdown => {
+            control.request_shutdown();
+            IpcResponse::ok("shutting down")
+        }
+        IpcRequest::Subscribe { .. } | IpcRequest::Timeline => {
+            IpcResponse::error("streaming is only available on a control connection")
+        }
+        IpcRequest::DumpState(name) => {
+            let Some(path) = dump_path(dump_dir, name) else {
+                return IpcResponse::error(format!("{} is not a file name in {}", name.display(), dump_dir.display()));
+            };
+            // Never follow a link planted in a shared directory
+            if std::fs::symlink_metadata(&path).is_ok_and(|meta| !meta.is_file()) {
+                return IpcResponse::error(format!("{} exists and is not a regular file", path.display()));
+            }
+            let snapshot = control.state_snapshot();
+            let written = std::fs::File::create(&path)
+                .map(std::io::BufWriter::new)
+                .and_then(|mut file| {
+                    snapshot.write_trace(&mut file)?;
+                    std::io::Write::flush(&mut file)
+                });
+            match written {
+                Ok(()) => IpcResponse::ok(format!("wrote {} reports to {}", snapshot.inputs.len(), path.display())),
+                Err(e) => IpcResponse::error(format!("failed to write {}: {}", path.display(), e)),
+            }
+        }
+    }
+}
+
+/// `name` inside `dump_dir`, `None` unless it is a plain file name
+fn dump_path(dump_dir: &Path, name: &Path) -> Option<PathBuf> {
+    let mut components = name.components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(file)), None) => Some(dump_dir.join(file)),
+        _ => None,
+    }
+}
+
+fn reload_config(path: &Path) -> std::result::Result<Config, String> {
+    let config = Config::load_from_file(&path.to_string_lossy()).map_err(|e| format!("failed to load config: {}", e))?;
+    let report = validation::validate_profiles(&config);
+    if report.has_errors() {
+        let errors: Vec<_> = report.errors().map(|issue| issue.message.as_str()).collect();
+        return Err(format!("button mapping has errors: {}", errors.join("; ")));
+    }
+    Ok(config)
+}
+
+/// Serve one client connection: read a command line, write the response line
+///
+/// A `subscribe` or `timeline` request keeps the connection open and streams
+/// until the client goes away.
+async fn serve_connection<S>(stream: S, control: TranslatorControl, config_path: PathBuf, dump_dir: PathBuf) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+
+    let json = line.trim_start().starts_with('{');
+    let request = IpcRequest::parse_any(&line);
+    let response = match &request {
+        Ok(IpcRequest::Subscribe { .. } | IpcRequest::Timeline) => IpcResponse::ok("streaming"),
+        Ok(request) => handle_request(&control, &config_path, &dump_dir, request),
+        Err(e) => IpcResponse::error(e.to_string()),
+    };
+
+    let mut out = if json { response.to_json() } else { response.to_line() };
+    out.push('\n');
+    stream.get_mut().write_all(out.as_bytes()).await?;
+
+    if let Ok(IpcRequest::Subscribe { interval_ms }) = request {
+        let mut interval = tokio::time::interval(Duration::from_millis(interval_ms.max(MIN_SUBSCRIBE_INTERVAL_MS) as u64));
+        loop {
+            interval.tick().await;
+            if let Some(sample) = control.latest_sample() {
+                let mut out = IpcSample::from(sample).to_line();
+                out.push('\n');
+                // A write error means the subscriber disconnected
+                if stream.get_mut().write_all(out.as_bytes()).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    if let Ok(IpcRequest::Timeline) = request {
+        let mut interval = tokio::time::interval(Duration::from_millis(MIN_SUBSCRIBE_INTERVAL_MS as u64));
+        let mut next = 0;
+        loop {
+            interval.tick().await;
+            let entries = control.timeline_since(next);
+            let Some(last) = entries.last() else {
+                continue;
+            };
+            next = last.seq + 1;
+            let mut out = String::new();
+            for entry in &entries {
+                out.push_str(&entry.to_line());
+                out.push('\n');
+            }
+            if stream.get_mut().write_all(out.as_bytes()).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    stream.get_mut().shutdown().await?;
+    Ok(())
+}
+
+async fn exchange<S>(stream: &mut BufReader<S>, request: &IpcRequest) -> Result<IpcResponse>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut line = request.to_json();
+    line.push('\n');
+    stream.get_mut().write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_line(&mut response).await?;
+    IpcResponse::parse_json(&response)
+}
+
+/// Remove the socket at `endpoint` unless a translator still listens on it
+///
+/// Anything but a socket is left alone, the endpoint may have been
+/// mistyped onto a real file.
+#[cfg(unix)]
+pub fn remove_stale_endpoint(endpoint: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(meta) = std::fs::symlink_metadata(endpoint) else {
+        return Ok(());
+    };
+    if !meta.file_type().is_socket() {
+        return Err(TranslatorError::protocol_error(format!("{} exists and is not a socket", endpoint.display())));
+    }
+    if std::os::unix::net::UnixStream::connect(endpoint).is_ok() {
+        return Err(TranslatorError::protocol_error(format!("Another translator listens on {}", endpoint.display())));
+    }
+    Ok(std::fs::remove_file(endpoint)?)
+}
+
+/// Bind a socket at `endpoint` that only its owner can ever connect to
+///
+/// The socket is bound inside a fresh 0700 directory, made 0600 and only
+/// then renamed into place, so it is never reachable with the permissions
+/// the umask gave it, even in the shared temp directory.
+#[cfg(unix)]
+fn bind_private(endpoint: &Path) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let dir = match endpoint.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let staging = dir.join(format!(".tm-g29-bind-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let bound = staging.join("tm-g29.sock");
+    let listener = std::os::unix::net::UnixListener::bind(&bound).and_then(|listener| {
+        std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound, endpoint)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    Ok(listener?)
+}
+
+/// Accept control connections on `endpoint` until the task is dropped
+///
+/// The socket is only accessible to the user running the translator.
+#[cfg(unix)]
+pub async fn serve(endpoint: &Path, control: TranslatorControl, config_path: PathBuf, dump_dir: PathBuf) -> Result<()> {
+    use tokio::net::UnixListener;
+
+    // A socket left behind by a crashed daemon would make bind fail
+    remove_stale_endpoint(endpoint)?;
+    let listener = bind_private(endpoint)?;
+    listener.set_nonblocking(true)?;
+    let listener = UnixListener::from_std(listener)?;
+    tracing::info!("Control socket listening on {}", endpoint.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (control, config_path, dump_dir) = (control.clone(), config_path.clone(), dump_dir.clone());
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, control, config_path, dump_dir).await {
+                tracing::warn!("Control connection failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Accept control connections on `endpoint` until the task is dropped
+#[cfg(windows)]
+pub async fn serve(endpoint: &Path, control: TranslatorControl, config_path: PathBuf, dump_dir: PathBuf) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = endpoint.as_os_str();
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(name)?;
+    tracing::info!("Control pipe listening on {}", endpoint.display());
+
+    loop {
+        server.connect().await?;
+        let connected = server;
+        // Create the next instance before serving so clients never see the pipe missing
+        server = ServerOptions::new().create(name)?;
+        let (control, config_path, dump_dir) = (control.clone(), config_path.clone(), dump_dir.clone());
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(connected, control, config_path, dump_dir).await {
+                tracing::warn!("Control connection failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn serve(_endpoint: &Path, _control: TranslatorControl, _config_path: PathBuf, _dump_dir: PathBuf) -> Result<()> {
+    Err(TranslatorError::UnsupportedPlatform)
+}
+
+#[cfg(unix)]
+async fn connect(endpoint: &Path) -> Result<tokio::net::UnixStream> {
+    Ok(tokio::net::UnixStream::connect(endpoint).await?)
+}
+
+#[cfg(windows)]
+async fn connect(endpoint: &Path) -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    loop {
+        match ClientOptions::new().open(endpoint.as_os_str()) {
+            Ok(client) => return Ok(client),
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Send one request to the daemon listening on `endpoint`
+#[cfg(any(unix, windows))]
+pub async fn send_request(endpoint: &Path, request: &IpcRequest) -> Result<IpcResponse> {
+    let mut stream = BufReader::new(connect(endpoint).await?);
+    exchange(&mut stream, request).await
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn send_request(_endpoint: &Path, _request: &IpcRequest) -> Result<IpcResponse> {
+    Err(TranslatorError::UnsupportedPlatform)
+}
+
+/// Stream samples from the daemon, calling `on_sample` until it returns false
+#[cfg(any(unix, windows))]
+pub async fn subscribe<F>(endpoint: &Path, interval_ms: u16, mut on_sample: F) -> Result<()>
+where
+    F: FnMut(IpcSample) -> bool,
+{
+    let mut stream = BufReader::new(connect(endpoint).await?);
+    let response = exchange(&mut stream, &IpcRequest::Subscribe { interval_ms }).await?;
+    if !response.ok {
+        return Err(TranslatorError::protocol_error(response.message));
+    }
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(TranslatorError::protocol_error("Translator closed the connection"));
+        }
+        if !on_sample(IpcSample::parse(&line)?) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn subscribe<F>(_endpoint: &Path, _interval_ms: u16, _on_sample: F) -> Result<()>
+where
+    F: FnMut(IpcSample) -> bool,
+{
+    Err(TranslatorError::UnsupportedPlatform)
+}
+
+/// Stream effect lifetime events from the daemon, calling `on_event` until it returns false
+#[cfg(any(unix, windows))]
+pub async fn timeline<F>(endpoint: &Path, mut on_event: F) -> Result<()>
+where
+    F: FnMut(TimelineEntry) -> bool,
+{
+    let mut stream = BufReader::new(connect(endpoint).await?);
+    let response = exchange(&mut stream, &IpcRequest::Timeline).await?;
+    if !response.ok {
+        return Err(TranslatorError::protocol_error(response.message));
+    }
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(TranslatorError::protocol_error("Translator closed the connection"));
+        }
+        if !on_event(TimelineEntry::parse(&line)?) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn timeline<F>(_endpoint: &Path, _on_event: F) -> Result<()>
+where
+    F: FnMut(TimelineEntry) -> bool,
+{
+    Err(TranslatorError::UnsupportedPlatform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_lines_round_trip() {
+        let requests = [
+            IpcRequest::Status,
+            IpcRequest::Pause,
+            IpcRequest::Resume,
+            IpcRequest::TogglePause,
+            IpcRequest::Reload,
+            IpcRequest::DumpState(PathBuf::from("/tmp/state.trace")),
+            IpcRequest::Subscribe { interval_ms: 20 },
+            IpcRequest::Timeline,
+            IpcRequest::SetGain(0.75),
+            IpcRequest::SetGeometry { center_offset: -0.02, geometry: SteeringGeometry { left_scale: 1.05, right_scale: 0.95 } },
+            IpcRequest::Profile(Some("acc".to_string())),
+            IpcRequest::Profile(None),
+            IpcRequest::Shutdown,
+        ];
+        for request in requests {
+            assert_eq!(IpcRequest::parse(&request.to_line()).unwrap(), request);
+            assert_eq!(IpcRequest::parse_any(&request.to_json()).unwrap(), request);
+        }
+        assert_eq!(
+            IpcRequest::parse_any(r#"{"command":"gain","value":0.5}"#).unwrap(),
+            IpcRequest::SetGain(0.5)
+        );
+        assert!(IpcRequest::parse("gain 1.5").is_err());
+        assert!(IpcRequest::parse("geometry 0 1").is_err());
+        assert!(IpcRequest::parse("geometry 0 1 -1").is_err());
+        assert!(IpcRequest::parse_any(r#"{"value":0.5}"#).is_err());
+        assert!(IpcRequest::parse("dump").is_err());
+        assert!(IpcRequest::parse("explode").is_err());
+        assert!(IpcRequest::parse("subscribe fast").is_err());
+    }
+
+    #[test]
+    fn test_sample_line_round_trip() {
+        let sample = IpcSample {
+            steering: 0x8000,
+            throttle: 1023,
+            brake: 0,
+            clutch: 12,
+            buttons: 8 << 24,
+            force: -1200,
+            paused: true,
+            steering_range: 900,
+        };
+        assert_eq!(sample.steering_degrees(), Some(0.0));
+        assert_eq!(IpcSample::parse(&sample.to_line()).unwrap(), sample);
+    }
+
+    #[test]
+    fn test_pause_commands_drive_control() {
+        let control = TranslatorControl::default();
+        let (config_path, dump_dir) = (Path::new("config.toml"), Path::new("."));
+
+        handle_request(&control, config_path, dump_dir, &IpcRequest::Pause);
+        assert!(control.is_paused());
+        let response = handle_request(&control, config_path, dump_dir, &IpcRequest::TogglePause);
+        assert_eq!(response, IpcResponse::ok("resumed"));
+        assert!(!control.is_paused());
+
+        handle_request(&control, config_path, dump_dir, &IpcRequest::SetGain(0.4));
+        assert_eq!(control.take_gain(), Some(0.4));
+        let geometry = SteeringGeometry::default();
+        handle_request(&control, config_path, dump_dir, &IpcRequest::SetGeometry { center_offset: 0.01, geometry });
+        assert_eq!(control.take_steering_geometry(), Some((0.01, geometry)));
+        assert_eq!(IpcResponse::parse_json(&response.to_json()).unwrap(), response);
+    }
+
+    #[test]
+    fn test_state_dumps_stay_in_their_directory() {
+        let dir = Path::new("/var/lib/tm-g29");
+        assert_eq!(dump_path(dir, Path::new("state.trace")), Some(dir.join("state.trace")));
+        assert!(dump_path(dir, Path::new("../state.trace")).is_none());
+        assert!(dump_path(dir, Path::new("/home/user/.bashrc")).is_none());
+        assert!(dump_path(dir, Path::new("nested/state.trace")).is_none());
+
+        let request = IpcRequest::DumpState(PathBuf::from("/etc/passwd"));
+        let response = handle_request(&TranslatorControl::default(), Path::new("config.toml"), dir, &request);
+        assert!(!response.ok);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_stale_sockets_are_removed() {
+        let dir = std::env::temp_dir().join(format!("tm-g29-ipc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A regular file at the endpoint is never deleted
+        let file = dir.join("config.toml");
+        std::fs::write(&file, "").unwrap();
+        assert!(remove_stale_endpoint(&file).is_err());
+        assert!(file.exists());
+
+        // A live socket belongs to another translator, a dead one is cleaned up
+        let socket = dir.join("tm-g29.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert!(remove_stale_endpoint(&socket).is_err());
+        drop(listener);
+        remove_stale_endpoint(&socket).unwrap();
+        assert!(!socket.exists());
+        remove_stale_endpoint(&socket).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_endpoint_is_bound_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("tm-g29-ipc-bind-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let socket = dir.join("tm-g29.sock");
+        let _listener = bind_private(&socket).unwrap();
+        assert_eq!(std::fs::metadata(&socket).unwrap().permissions().mode() & 0o777, 0o600);
+        std::os::unix::net::UnixStream::connect(&socket).unwrap();
+        // Only the socket is left behind, not the staging directory
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
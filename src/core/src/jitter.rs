@@ -0,0 +1,201 @@
+//! Jitter buffers for hosts with erratic timing
+//!
+//! In a VM or on a cloud gaming instance the wheel's reports and the game's
+//! FFB updates arrive in bursts: a few milliseconds of nothing, then several
+//! at once. With `[jitter_config]` enabled, translated reports on their way
+//! to the virtual G29 and the game's FFB updates on their way to the wheel
+//! each wait in a `JitterBuffer` for a set delay and leave at the steady
+//! pace they were sent at, trading that delay for evenly spaced updates.
+//!
+//! The pace is the running average of the arrival intervals. The sender's
+//! clock and ours never agree exactly, so the pace is also corrected for
+//! drift by how far the buffer is off the occupancy its delay calls for: a
+//! filling buffer plays out a little faster, one running dry a little
+//! slower. Items that waited longer than the maximum delay are dropped
+//! rather than played late, and a buffer that ran dry waits the full delay
+//! again before playing, to rebuild its cushion.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Weight of the newest arrival interval in the running pace
+const PACE_SMOOTHING: f32 = 0.05;
+
+/// Share of the occupancy error corrected per released item
+const DRIFT_GAIN: f32 = 0.1;
+
+/// Bounds of the corrected pace, relative to the arrival pace
+const MIN_PACE: f32 = 0.5;
+const MAX_PACE: f32 = 1.5;
+
+/// Pace assumed until two items have arrived
+const DEFAULT_PACE: Duration = Duration::from_millis(1);
+
+/// Occupancy of a buffer, as published to metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct JitterStats {
+    /// Items waiting
+    pub occupancy: usize,
+    /// Time the waiting items last at the current pace, in milliseconds
+    pub buffered_ms: f32,
+    /// Items dropped for waiting longer than the maximum delay
+    pub dropped: u64,
+    /// Times the buffer ran dry while playing out
+    pub underruns: u64,
+}
+
+/// Occupancy of the input and FFB buffers, those enabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct JitterReport {
+    pub input: Option<JitterStats>,
+    pub ffb: Option<JitterStats>,
+}
+
+/// Holds items for a delay and releases them at their arrival pace
+#[derive(Debug, Clone)]
+pub struct JitterBuffer<T> {
+    queue: VecDeque<(Instant, T)>,
+    delay: Duration,
+    max_delay: Duration,
+    /// Running average of the arrival intervals
+    pace: Option<Duration>,
+    last_arrival: Option<Instant>,
+    /// When the next item leaves, `None` until the first one waited the delay
+    next_release: Option<Instant>,
+    dropped: u64,
+    underruns: u64,
+}
+
+impl<T> JitterBuffer<T> {
+    /// Buffer holding items `delay`, dropping those still waiting after `max_delay`
+    pub fn new(delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            delay,
+            max_delay: max_delay.max(delay),
+            pace: None,
+            last_arrival: None,
+            next_release: None,
+            dropped: 0,
+            underruns: 0,
+        }
+    }
+
+    /// Queue `item`, arrived at `now`
+    pub fn push(&mut self, item: T, now: Instant) {
+        if let Some(last) = self.last_arrival {
+            // Gaps longer than the buffer can bridge are pauses, not the pace
+            let interval = now.duration_since(last);
+            if interval <= self.max_delay {
+                self.pace = Some(match self.pace {
+                    Some(pace) => pace.mul_f32(1.0 - PACE_SMOOTHING) + interval.mul_f32(PACE_SMOOTHING),
+                    None => interval,
+                });
+            }
+        }
+        self.last_arrival = Some(now);
+        self.queue.push_back((now, item));
+    }
+
+    /// Next item due at `now`, if any
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        while self.queue.len() > 1 && self.queue.front().is_some_and(|(arrival, _)| now.duration_since(*arrival) > self.max_delay) {
+            self.queue.pop_front();
+            self.dropped += 1;
+        }
+
+        let Some(&(arrival, _)) = self.queue.front() else {
+            if self.next_release.is_some_and(|at| now >= at) {
+                self.next_release = None;
+                self.underruns += 1;
+            }
+            return None;
+        };
+        let release = self.next_release.unwrap_or(arrival + self.delay);
+        if now < release {
+            return None;
+        }
+        let (_, item) = self.queue.pop_front()?;
+
+        let pace = self.pace();
+        let target = (self.delay.as_secs_f32() / pace.as_secs_f32().max(f32::EPSILON)).max(1.0);
+        let error = (self.queue.len() as f32 - target) / target;
+        let corrected = pace.mul_f32((1.0 - DRIFT_GAIN * error).clamp(MIN_PACE, MAX_PACE));
+        // After a stall the backlog drains at the corrected pace, not in one burst
+        self.next_release = Some((release + corrected).max(now));
+        Some(item)
+    }
+
+    /// When the next item is due, `None` while empty
+    pub fn next_release(&self) -> Option<Instant> {
+        let (arrival, _) = self.queue.front()?;
+        Some(self.next_release.unwrap_or(*arrival + self.delay))
+    }
+
+    /// Drop everything waiting, playing starts over after the delay
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.next_release = None;
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        JitterStats {
+            occupancy: self.queue.len(),
+            buffered_ms: self.pace().as_secs_f32() * 1000.0 * self.queue.len() as f32,
+            dropped: self.dropped,
+            underruns: self.underruns,
+        }
+    }
+
+    fn pace(&self) -> Duration {
+        self.pace.unwrap_or(DEFAULT_PACE)
+    }
+}
+
+/// Sleep until a scheduled release, e.g. a buffer's next one, forever when nothing waits
+pub async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bursts_leave_evenly_after_the_delay() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut buffer = JitterBuffer::new(Duration::from_millis(8), Duration::from_millis(50));
+
+        // A steady 2 ms pace establishes itself, then arrives in bursts of four
+        let mut released = Vec::new();
+        let mut next = 0;
+        for ms in 0..200u64 {
+            if ms < 40 && ms % 2 == 0 || ms >= 40 && ms % 8 == 0 {
+                let count = if ms < 40 { 1 } else { 4 };
+                for _ in 0..count {
+                    buffer.push(next, at(ms));
+                    next += 1;
+                }
+            }
+            while let Some(item) = buffer.pop(at(ms)) {
+                released.push((ms, item));
+            }
+        }
+
+        assert_eq!(released.first(), Some(&(8, 0)));
+        // In order, nothing lost, and never more than one per millisecond
+        assert!(released.iter().enumerate().all(|(index, &(_, item))| item == index));
+        assert!(released.windows(2).all(|pair| pair[1].0 > pair[0].0));
+        // The bursts leave about 2 ms apart, not four at once every 8 ms
+        let during_bursts = released.iter().filter(|(ms, _)| (60..150).contains(ms));
+        assert!(during_bursts.clone().zip(during_bursts.skip(1)).all(|(a, b)| b.0 - a.0 <= 3));
+        let stats = buffer.stats();
+        assert_eq!(stats.dropped, 0);
+        assert!(stats.occupancy <= 8, "{:?}", stats);
+    }
+}
@@ -0,0 +1,269 @@
+//! Wheel buttons typed as keyboard keys
+//!
+//! Some games refuse to bind wheel buttons to functions like the pit limiter
+//! or the replay camera but accept any keyboard key. `[keyboard_config]`
+//! binds source buttons to key names, and while a bound button is held its
+//! key is held on a virtual keyboard. The keys do not travel through the
+//! virtual G29: they are injected with the platform input API (XTest or
+//! libei on Linux, SendInput on Windows, Quartz events on macOS) from a
+//! thread of their own, only compiled with the `virtual-keys` feature. By
+//! default a bound button still reaches the G29 report as well, `exclusive`
+//! keeps it out. Bindings can differ per profile; keys held when a profile
+//! switch or reload changes them are released.
+
+use crate::config::KeyboardConfig;
+use crate::device::SOURCE_BUTTON_COUNT;
+use crate::error::{Result, TranslatorError};
+use std::fmt;
+use std::str::FromStr;
+
+/// Key a button can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// Letter or digit, lower case
+    Char(char),
+    /// Function key F1 - F12
+    F(u8),
+    Enter,
+    Escape,
+    Space,
+    Tab,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Shift,
+    Control,
+    Alt,
+}
+
+impl FromStr for Key {
+    type Err = TranslatorError;
+
+    /// Parse a key name such as `F1`, `Enter`, `PageUp` or `k`, ignoring case
+    fn from_str(name: &str) -> Result<Self> {
+        let lower = name.trim().to_ascii_lowercase();
+        if let [c] = lower.as_bytes() {
+            if c.is_ascii_alphanumeric() {
+                return Ok(Key::Char(*c as char));
+            }
+        }
+        if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            if (1..=12).contains(&n) {
+                return Ok(Key::F(n));
+            }
+        }
+        Ok(match lower.as_str() {
+            "enter" | "return" => Key::Enter,
+            "escape" | "esc" => Key::Escape,
+            "space" => Key::Space,
+            "tab" => Key::Tab,
+            "backspace" => Key::Backspace,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "shift" => Key::Shift,
+            "ctrl" | "control" => Key::Control,
+            "alt" => Key::Alt,
+            _ => return Err(TranslatorError::config_error(format!("Unknown key {:?}", name))),
+        })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            Key::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Key press or release to inject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub pressed: bool,
+}
+
+/// Turns source button changes into key events
+#[derive(Debug, Clone, Default)]
+pub struct KeyMapper {
+    bindings: Vec<(u8, Key)>,
+    exclusive: bool,
+    held: u32,
+}
+
+impl KeyMapper {
+    /// Mapper for the bindings of `config`, none when it is off
+    pub fn from_config(config: &KeyboardConfig) -> Result<Self> {
+        if !config.enabled {
+            return Ok(Self::default());
+        }
+        let mut bindings = Vec::with_capacity(config.bindings.len());
+        for (&button, name) in &config.bindings {
+            if button >= SOURCE_BUTTON_COUNT {
+                return Err(TranslatorError::config_error(format!("Key binding for missing button {}", button)));
+            }
+            bindings.push((button, name.parse()?));
+        }
+        bindings.sort_unstable_by_key(|&(button, _)| button);
+        Ok(Self { bindings, exclusive: config.exclusive, held: 0 })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// Source buttons to clear from the G29 report, 0 unless `exclusive`
+    pub fn exclusive_mask(&self) -> u32 {
+        if !self.exclusive {
+            return 0;
+        }
+        self.bindings.iter().fold(0, |mask, &(button, _)| mask | 1 << button)
+    }
+
+    /// Key events for the bound buttons that changed since the last call
+    pub fn update(&mut self, buttons: u32) -> Vec<KeyEvent> {
+        let mut events = Vec::new();
+        for &(button, key) in &self.bindings {
+            let mask = 1u32 << button;
+            if (buttons ^ self.held) & mask != 0 {
+                events.push(KeyEvent { key, pressed: buttons & mask != 0 });
+            }
+        }
+        self.held = buttons;
+        events
+    }
+
+    /// Release events for every key still held
+    pub fn release_all(&mut self) -> Vec<KeyEvent> {
+        self.update(0)
+    }
+}
+
+/// Handle to the thread typing keys on the virtual keyboard
+pub struct VirtualKeyboard {
+    events: std::sync::mpsc::Sender<KeyEvent>,
+}
+
+impl VirtualKeyboard {
+    /// Queue `event`, never blocking the input loop
+    pub fn send(&self, event: KeyEvent) -> Result<()> {
+        self.events
+            .send(event)
+            .map_err(|_| TranslatorError::virtual_device_error("Virtual keyboard thread stopped"))
+    }
+}
+
+#[cfg(feature = "virtual-keys")]
+pub fn spawn() -> Result<VirtualKeyboard> {
+    use enigo::{Direction, Enigo, Keyboard, Settings};
+
+    fn platform_key(key: Key) -> enigo::Key {
+        match key {
+            Key::Char(c) => enigo::Key::Unicode(c),
+            Key::F(n) => [
+                enigo::Key::F1,
+                enigo::Key::F2,
+                enigo::Key::F3,
+                enigo::Key::F4,
+                enigo::Key::F5,
+                enigo::Key::F6,
+                enigo::Key::F7,
+                enigo::Key::F8,
+                enigo::Key::F9,
+                enigo::Key::F10,
+                enigo::Key::F11,
+                enigo::Key::F12,
+            ][(n.clamp(1, 12) - 1) as usize],
+            Key::Enter => enigo::Key::Return,
+            Key::Escape => enigo::Key::Escape,
+            Key::Space => enigo::Key::Space,
+            Key::Tab => enigo::Key::Tab,
+            Key::Backspace => enigo::Key::Backspace,
+            Key::Up => enigo::Key::UpArrow,
+            Key::Down => enigo::Key::DownArrow,
+            Key::Left => enigo::Key::LeftArrow,
+            Key::Right => enigo::Key::RightArrow,
+            Key::PageUp => enigo::Key::PageUp,
+            Key::PageDown => enigo::Key::PageDown,
+            Key::Home => enigo::Key::Home,
+            Key::End => enigo::Key::End,
+            Key::Shift => enigo::Key::Shift,
+            Key::Control => enigo::Key::Control,
+            Key::Alt => enigo::Key::Alt,
+        }
+    }
+
+    // The connection to the input API must live on the thread that uses it
+    let (events_tx, events_rx) = std::sync::mpsc::channel::<KeyEvent>();
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("tm-g29-keyboard".to_string())
+        .spawn(move || {
+            let mut enigo = match Enigo::new(&Settings::default()) {
+                Ok(enigo) => enigo,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e.to_string()));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(()));
+
+            while let Ok(event) = events_rx.recv() {
+                let direction = if event.pressed { Direction::Press } else { Direction::Release };
+                if let Err(e) = enigo.key(platform_key(event.key), direction) {
+                    tracing::warn!("Key {} not typed: {}", event.key, e);
+                }
+            }
+        })?;
+
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(VirtualKeyboard { events: events_tx }),
+        Ok(Err(reason)) => Err(TranslatorError::virtual_device_error(format!("Virtual keyboard unavailable: {}", reason))),
+        Err(_) => Err(TranslatorError::virtual_device_error("Virtual keyboard thread exited during setup")),
+    }
+}
+
+#[cfg(not(feature = "virtual-keys"))]
+pub fn spawn() -> Result<VirtualKeyboard> {
+    Err(TranslatorError::UnsupportedPlatform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bound_buttons_press_and_release_keys() {
+        let mut config = KeyboardConfig { enabled: true, exclusive: true, ..Default::default() };
+        config.bindings.insert(3, "F1".to_string());
+        config.bindings.insert(7, "pageup".to_string());
+        let mut mapper = KeyMapper::from_config(&config).unwrap();
+        assert_eq!(mapper.exclusive_mask(), 1 << 3 | 1 << 7);
+
+        assert_eq!(mapper.update(1 << 3 | 1), vec![KeyEvent { key: Key::F(1), pressed: true }]);
+        assert!(mapper.update(1 << 3).is_empty());
+        assert_eq!(
+            mapper.update(1 << 7),
+            vec![KeyEvent { key: Key::F(1), pressed: false }, KeyEvent { key: Key::PageUp, pressed: true }]
+        );
+        assert_eq!(mapper.release_all(), vec![KeyEvent { key: Key::PageUp, pressed: false }]);
+
+        config.bindings.insert(1, "hyper".to_string());
+        assert!(KeyMapper::from_config(&config).is_err());
+        assert_eq!("k".parse::<Key>().unwrap(), Key::Char('k'));
+    }
+}
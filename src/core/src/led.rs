@@ -0,0 +1,240 @@
+//! Rev-strip LED pattern engine
+//!
+//! Produces the 5-LED G29 rev strip state from either game telemetry or the
+//! LED commands a game sends to the virtual wheel, layering limiter blinking,
+//! gear indication and race flag patterns on top according to the configured
+//! `LedPatternConfig`. How bright the LEDs are is decided separately by
+//! `scheduled_brightness`, so the night mode dims every pattern alike.
+
+use crate::config::{LedNightMode, LedPatternConfig, LedSource, OutputConfig};
+use crate::telemetry::{RaceFlag, TelemetryFrame};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Number of LEDs on the G29 rev strip
+pub const G29_LED_COUNT: usize = 5;
+
+/// Bitmask with every rev LED lit
+pub const ALL_LEDS: u8 = (1 << G29_LED_COUNT) - 1;
+
+/// Two-phase blinking pattern, alternating between masks every half period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlinkPattern {
+    pub on_mask: u8,
+    pub off_mask: u8,
+    pub period_ms: u32,
+}
+
+impl BlinkPattern {
+    fn mask_at(&self, elapsed: Duration) -> u8 {
+        if self.period_ms == 0 {
+            return self.on_mask;
+        }
+        let phase = elapsed.as_millis() % self.period_ms as u128;
+        if phase < (self.period_ms / 2) as u128 {
+            self.on_mask
+        } else {
+            self.off_mask
+        }
+    }
+}
+
+/// Brightness of the rev LEDs at `now`, 0.0 - 1.0, with the night mode applied
+pub fn scheduled_brightness(config: &OutputConfig, now: SystemTime) -> f32 {
+    let brightness = config.led_brightness.clamp(0.0, 1.0);
+    if is_night(&config.led_night_mode, now) {
+        brightness.min(config.led_night_mode.brightness.clamp(0.0, 1.0))
+    } else {
+        brightness
+    }
+}
+
+fn is_night(night: &LedNightMode, now: SystemTime) -> bool {
+    if !night.enabled || night.start_hour == night.end_hour {
+        return false;
+    }
+    let seconds = now.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() as i64);
+    let hour = ((seconds / 60 + night.utc_offset_minutes as i64).rem_euclid(24 * 60) / 60) as u8;
+    if night.start_hour < night.end_hour {
+        (night.start_hour..night.end_hour).contains(&hour)
+    } else {
+        hour >= night.start_hour || hour < night.end_hour
+    }
+}
+
+pub struct LedEngine {
+    config: LedPatternConfig,
+    epoch: Instant,
+    rpm_fraction: f32,
+    frame_limiter: bool,
+    flag: RaceFlag,
+    gear: Option<i8>,
+    gear_shown_until: Option<Instant>,
+    game_mask: u8,
+}
+
+impl LedEngine {
+    pub fn new(config: &LedPatternConfig) -> Self {
+        Self {
+            config: config.clone(),
+            epoch: Instant::now(),
+            rpm_fraction: 0.0,
+            frame_limiter: false,
+            flag: RaceFlag::None,
+            gear: None,
+            gear_shown_until: None,
+            game_mask: 0,
+        }
+    }
+
+    /// Switch to the patterns of `config`, e.g. those of a newly active profile
+    ///
+    /// The telemetry and game state seen so far is kept, so the strip does not
+    /// go dark until the next frame arrives.
+    pub fn set_config(&mut self, config: &LedPatternConfig) {
+        self.config = config.clone();
+        if self.config.gear_display_ms == 0 {
+            self.gear_shown_until = None;
+        }
+    }
+
+    /// Feed the latest telemetry frame
+    pub fn update_telemetry(&mut self, frame: &TelemetryFrame) {
+        self.rpm_fraction = frame.rpm_fraction().unwrap_or(0.0);
+        self.frame_limiter = frame.limiter_active;
+        self.flag = frame.flag;
+
+        if self.config.gear_display_ms > 0 && self.gear.map_or(false, |gear| gear != frame.gear) {
+            self.gear_shown_until = Some(Instant::now() + Duration::from_millis(self.config.gear_display_ms as u64));
+        }
+        self.gear = Some(frame.gear);
+    }
+
+    /// Feed the LED mask requested by the game through the virtual G29
+    pub fn set_game_leds(&mut self, mask: u8) {
+        self.game_mask = mask & ALL_LEDS;
+    }
+
+    /// Compute the LED mask to display at `now`
+    pub fn render(&self, now: Instant) -> u8 {
+        let elapsed = now.duration_since(self.epoch);
+
+        if let Some(pattern) = self.flag_pattern() {
+            return pattern.mask_at(elapsed);
+        }
+
+        if let (Some(gear), Some(until)) = (self.gear, self.gear_shown_until) {
+            if now < until {
+                return Self::gear_mask(gear);
+            }
+        }
+
+        match self.config.source {
+            LedSource::Game => self.game_mask,
+            LedSource::Telemetry => {
+                if self.frame_limiter || self.rpm_fraction >= self.config.limiter_threshold {
+                    self.config.limiter_pattern.mask_at(elapsed)
+                } else {
+                    self.rev_bar_mask()
+                }
+            }
+        }
+    }
+
+    fn flag_pattern(&self) -> Option<&BlinkPattern> {
+        if !self.config.flag_patterns {
+            return None;
+        }
+        match self.flag {
+            RaceFlag::Blue => Some(&self.config.blue_flag_pattern),
+            RaceFlag::Yellow => Some(&self.config.yellow_flag_pattern),
+            _ => None,
+        }
+    }
+
+    fn rev_bar_mask(&self) -> u8 {
+        self.config
+            .rev_thresholds
+            .iter()
+            .enumerate()
+            .filter(|(_, threshold)| self.rpm_fraction >= **threshold)
+            .fold(0u8, |mask, (led, _)| mask | (1 << led))
+    }
+
+    fn gear_mask(gear: i8) -> u8 {
+        match gear {
+            // Reverse lights both outer LEDs, neutral only the center one
+            g if g < 0 => 0b10001,
+            0 => 0b00100,
+            g => ALL_LEDS >> (G29_LED_COUNT - (g as usize).min(G29_LED_COUNT)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutputConfig;
+    use crate::device::models::ThrustmasterModel;
+    use crate::device::G29OutputReport;
+    use crate::protocol::OutputTranslator;
+
+    #[test]
+    fn test_game_rev_lights_reach_the_thrustmaster_report() {
+        let translator = OutputTranslator::new(&OutputConfig::default());
+        let mut engine = LedEngine::new(&LedPatternConfig::default());
+
+        let mask = translator.parse_leds(&G29OutputReport::leds(0b00111)).expect("LED command");
+        engine.set_game_leds(mask);
+        let shown = engine.render(Instant::now());
+        assert_eq!(shown, 0b00111);
+        assert_eq!(ThrustmasterModel::T248.led_report(shown, 128), Some(vec![0x60, 0x08, 0x13, 0b00111, 128]));
+
+        // Written without a report ID the command byte leads the data
+        let raw = G29OutputReport { report_id: 0x00, data: vec![0xF8, 0x12, 0xFF, 0x00, 0x00, 0x00, 0x01] };
+        assert_eq!(translator.parse_leds(&raw), Some(ALL_LEDS));
+        assert_eq!(translator.parse_leds(&G29OutputReport::constant_effect(1, 100, 0)), None);
+        assert!(!ThrustmasterModel::T150.has_leds());
+    }
+
+    #[test]
+    fn test_night_mode_dims_between_its_hours() {
+        let mut config = OutputConfig { led_brightness: 0.8, ..OutputConfig::default() };
+        config.led_night_mode = LedNightMode { enabled: true, start_hour: 22, end_hour: 7, brightness: 0.2, utc_offset_minutes: 60 };
+        let at = |hour: u64, minute: u64| UNIX_EPOCH + Duration::from_secs(hour * 3600 + minute * 60);
+
+        // 21:30 UTC is 22:30 local
+        assert_eq!(scheduled_brightness(&config, at(21, 30)), 0.2);
+        assert_eq!(scheduled_brightness(&config, at(5, 59)), 0.2);
+        assert_eq!(scheduled_brightness(&config, at(6, 0)), 0.8);
+        assert_eq!(scheduled_brightness(&config, at(20, 59)), 0.8);
+        config.led_night_mode.enabled = false;
+        assert_eq!(scheduled_brightness(&config, at(23, 0)), 0.8);
+    }
+
+    #[test]
+    fn test_patterns_follow_the_active_profile() {
+        let mut config = crate::config::Config {
+            source: Some(std::env::temp_dir().join("tm-g29-led-profiles/config.toml")),
+            ..crate::config::Config::default()
+        };
+        config.output_config.led_patterns.source = LedSource::Telemetry;
+        config.profiles.insert(
+            "rally".to_string(),
+            toml::from_str("[output_config.led_patterns]\nrev_thresholds = [0.2, 0.4, 0.6, 0.8, 0.9]\n").unwrap(),
+        );
+        let mut engine = LedEngine::new(&config.output_config.led_patterns);
+        let frame = TelemetryFrame { rpm: 5000.0, max_rpm: Some(10000.0), ..TelemetryFrame::default() };
+        engine.update_telemetry(&frame);
+        let now = Instant::now();
+        assert_eq!(engine.render(now), 0);
+
+        // Switching profiles keeps the last frame, only the thresholds change
+        let rally = crate::profile::effective(&config, Some("rally")).unwrap();
+        assert_eq!(rally.output_config.led_patterns.source, LedSource::Telemetry);
+        engine.set_config(&rally.output_config.led_patterns);
+        assert_eq!(engine.render(now), 0b00011);
+        engine.set_config(&config.output_config.led_patterns);
+        assert_eq!(engine.render(now), 0);
+    }
+}
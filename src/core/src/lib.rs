@@ -4,115 +4,429 @@
 //! wheel protocols and Logitech G29 protocols, including input mapping and
 //! force feedback translation.
 
+pub mod ab;
 pub mod device;
 pub mod protocol;
+pub mod profile;
+pub mod proton;
 pub mod ffb;
+pub mod led;
+pub mod log_limit;
+pub mod metrics;
+pub mod persist;
+pub mod pid;
+pub mod pipe;
+pub mod pipeline;
+pub mod presentation;
+pub mod assists;
+pub mod axis_mapping;
+pub mod bench;
+pub mod calibration;
+pub mod capture;
+pub mod clock;
+pub mod combos;
 pub mod config;
+pub mod conformance;
+pub mod control;
+pub mod convert;
+pub mod curve;
 pub mod error;
+pub mod evdev;
+pub mod experimental;
+pub mod fingerprint;
+pub mod host;
+pub mod hotkeys;
+pub mod identity;
+pub mod inventory;
+pub mod ipc;
+pub mod jitter;
+pub mod keys;
+pub mod recording;
+pub mod remote;
+pub mod render;
+pub mod replay;
+pub mod rumble;
+pub mod state;
+pub mod stats;
+pub mod telemetry;
+pub mod testing;
+pub mod thermal;
+pub mod tick_budget;
+pub mod timeline;
+pub mod units;
+pub mod validation;
 
-pub use device::{ThrustmasterDevice, VirtualG29Device};
+pub use device::{SourceWheel, ThrustmasterDevice, VirtualG29Device, VirtualWheel};
 pub use protocol::{InputTranslator, OutputTranslator};
-pub use ffb::{FfbEngine, FfbEffect};
+pub use ffb::{FfbCapabilities, FfbEngine, FfbEffect};
 pub use config::Config;
+pub use control::{ControlAction, TranslatorControl};
 pub use error::{TranslatorError, Result};
+pub use telemetry::{TelemetryBus, TelemetryFrame};
+pub use recording::{Recording, SessionRecorder, SharedSessionRecorder};
+pub use state::{StateSnapshot, StateStore};
+pub use units::{ForceNewton, Milliseconds, NormalizedAxis, SteeringAngle};
 
 /// Main translator struct that orchestrates the protocol translation
-pub struct ProtocolTranslator {
-    thrustmaster: ThrustmasterDevice,
-    virtual_g29: VirtualG29Device,
+///
+/// Generic over the `SourceWheel` it reads and the `VirtualWheel` it feeds,
+/// so other backends, or the scripted devices in `device::mock`, plug in
+/// without changes here.
+pub struct ProtocolTranslator<W = ThrustmasterDevice, V = VirtualG29Device> {
+    thrustmaster: W,
+    aux_inputs: device::aggregate::AuxiliaryInputs,
+    /// Last raw wheel report, re-translated when only an auxiliary device changed
+    last_wheel_report: Option<device::ThrustmasterInputReport>,
+    aux_generation: u64,
+    virtual_g29: V,
     input_translator: InputTranslator,
     output_translator: OutputTranslator,
     ffb_engine: FfbEngine,
+    telemetry_bus: TelemetryBus,
+    /// Frames for the LED engine, drained every output tick
+    telemetry_frames: tokio::sync::broadcast::Receiver<TelemetryFrame>,
+    led_engine: led::LedEngine,
+    /// Mask and brightness level last shown on the wheel, `None` to resend
+    leds_shown: Option<(u8, u8)>,
+    session_recorder: Option<SharedSessionRecorder>,
+    control: TranslatorControl,
+    /// Keeps failures repeating every tick from flooding the log
+    log_limiter: log_limit::LogLimiter,
+    /// Counters of the input loop, flushed to `control` every so often
+    input_stats: stats::StatsAccumulator,
+    /// Counters of FFB updates, flushed to `control` every so often
+    ffb_stats: stats::StatsAccumulator,
+    /// `None` when tick budget warnings are off
+    tick_budget: Option<tick_budget::TickBudgetMonitor>,
+    last_source_buttons: u32,
+    /// Rotation range the base last reported for itself, or was set to for the game
+    base_range: Option<u16>,
+    /// Rotation range the game last set on its G29
+    game_range: Option<u16>,
+    /// Rim the base last reported as attached
+    rim: Option<device::models::Rim>,
+    /// Features switched on in `[experimental]`, fixed until a restart
+    experimental: experimental::Experimental,
+    /// Running FFB A/B comparison, its profiles fixed until a restart
+    ab_test: Option<ab::AbTest>,
+    /// Whole seconds into the comparison when its report was last published
+    ab_published_secs: u64,
+    /// Buttons typed as keys, bindings follow the active profile
+    key_mapper: keys::KeyMapper,
+    /// Opened on the first bound button press
+    keyboard: Option<keys::VirtualKeyboard>,
+    /// Translated reports waiting for the virtual G29, with `[jitter_config]` enabled
+    input_buffer: Option<jitter::JitterBuffer<device::G29InputReport>>,
+    /// Game FFB updates waiting for the wheel, with `[jitter_config]` enabled
+    ffb_buffer: Option<jitter::JitterBuffer<Vec<device::IforceCommand>>>,
+    /// Configuration as loaded, before a profile is layered on top
+    base_config: Config,
     config: Config,
 }
 
 impl ProtocolTranslator {
     /// Create a new protocol translator instance
     pub async fn new(config: Config) -> Result<Self> {
-        let thrustmaster = ThrustmasterDevice::open(&config.thrustmaster_config).await?;
-        let virtual_g29 = VirtualG29Device::create(&config.g29_config).await?;
+        Self::open(config).await
+    }
+}
+
+impl ProtocolTranslator<remote::RemoteWheel, VirtualG29Device> {
+    /// Translator reading the wheel `tm-g29 serve-wheel` serves on `remote_config.connect`
+    pub async fn new_remote(config: Config) -> Result<Self> {
+        let wheel = remote::RemoteWheel::connect(&config.remote_config).await?;
+        Self::open_with(config, wheel).await
+    }
+}
+
+impl<W: device::SourceWheel, V: device::VirtualWheel> ProtocolTranslator<W, V> {
+    /// Open the source wheel and create the virtual wheel `config` describes
+    pub async fn open(config: Config) -> Result<Self> {
+        let base_config = config.clone();
+        let config = with_profile(config, None);
+        let thrustmaster = W::open(&config.thrustmaster_config).await?;
+        Self::start(base_config, config, thrustmaster).await
+    }
+
+    /// Create the virtual wheel for a source wheel opened elsewhere, e.g. a `remote::RemoteWheel`
+    pub async fn open_with(config: Config, thrustmaster: W) -> Result<Self> {
+        let base_config = config.clone();
+        let config = with_profile(config, None);
+        Self::start(base_config, config, thrustmaster).await
+    }
+
+    async fn start(base_config: Config, config: Config, thrustmaster: W) -> Result<Self> {
+        if let Err(e) = thrustmaster.initialize(config.input_config.steering_range).await {
+            tracing::warn!("Wheel initialization failed: {}", e);
+        }
+        let mut g29_config = config.g29_config.clone();
+        g29_config.serial_number = identity::resolve_serial(
+            &g29_config,
+            thrustmaster.serial_number(),
+            &identity::default_state_path(),
+        )?;
+        let virtual_g29 = V::create(&g29_config).await?;
+        Ok(Self::from_parts(base_config, config, thrustmaster, virtual_g29))
+    }
+
+    /// Translator between already opened devices, e.g. the mocks of `device::mock`
+    pub fn with_devices(config: Config, thrustmaster: W, virtual_g29: V) -> Self {
+        let base_config = config.clone();
+        let config = with_profile(config, None);
+        Self::from_parts(base_config, config, thrustmaster, virtual_g29)
+    }
+
+    fn from_parts(base_config: Config, config: Config, thrustmaster: W, virtual_g29: V) -> Self {
+        let aux_inputs = device::aggregate::AuxiliaryInputs::spawn(&config);
         let input_translator = InputTranslator::new(&config.input_config);
         let output_translator = OutputTranslator::new(&config.output_config);
-        let ffb_engine = FfbEngine::new(&config.ffb_config);
+        let ab_test = ab_test(&config);
+        let ffb_config = ab_test.as_ref().map_or(&config.ffb_config, |ab| ab.ffb_config());
+        let mut ffb_engine = ffb_engine(&config, ffb_config, thrustmaster.ffb_capabilities());
+        ffb_engine.set_soft_lock(input_translator.steering_range(), config.input_config.output_range());
+        let telemetry_bus = TelemetryBus::default();
+        let telemetry_frames = telemetry_bus.subscribe();
+        let led_engine = led::LedEngine::new(&config.output_config.led_patterns);
+        let control = TranslatorControl::default();
+        let experimental = experimental::Experimental::from_config(&config.experimental);
+        let key_mapper = key_mapper(&config);
 
-        Ok(Self {
+        Self {
             thrustmaster,
+            aux_inputs,
+            last_wheel_report: None,
+            aux_generation: 0,
             virtual_g29,
             input_translator,
             output_translator,
             ffb_engine,
+            telemetry_bus,
+            telemetry_frames,
+            led_engine,
+            leds_shown: None,
+            session_recorder: None,
+            input_stats: control.stats_accumulator(),
+            ffb_stats: control.stats_accumulator(),
+            control,
+            log_limiter: log_limit::LogLimiter::default(),
+            tick_budget: tick_budget_monitor(&config),
+            last_source_buttons: 0,
+            base_range: None,
+            game_range: None,
+            rim: None,
+            experimental,
+            ab_test,
+            ab_published_secs: 0,
+            key_mapper,
+            keyboard: None,
+            input_buffer: jitter_buffer(&config.jitter_config, config.jitter_config.input_delay_ms),
+            ffb_buffer: jitter_buffer(&config.jitter_config, config.jitter_config.ffb_delay_ms),
+            base_config,
             config,
-        })
+        }
+    }
+
+    /// Telemetry bus fed by the enabled game telemetry providers
+    pub fn telemetry_bus(&self) -> &TelemetryBus {
+        &self.telemetry_bus
+    }
+
+    /// Record every wheel report, its G29 translation and constant force into `recorder`
+    pub fn set_session_recorder(&mut self, recorder: SharedSessionRecorder) {
+        if let Ok(mut recorder) = recorder.lock() {
+            recorder.record_note(format!("Wheel: {}", self.thrustmaster.model()));
+            if let Some(ab) = &self.ab_test {
+                let [a, b] = &ab.report().names;
+                recorder.record_note(format!("FFB A/B: A = {}, B = {}, starting on {}", a, b, ab.current()));
+            }
+        }
+        self.session_recorder = Some(recorder);
+    }
+
+    /// Experimental features code paths in development must check before running
+    pub fn experimental(&self) -> &experimental::Experimental {
+        &self.experimental
+    }
+
+    /// Runtime control handle of this translator
+    pub fn control(&self) -> TranslatorControl {
+        self.control.clone()
+    }
+
+    /// Use an existing control handle, e.g. one kept across restarts
+    pub fn set_control(&mut self, control: TranslatorControl) {
+        self.control = control;
+        self.input_stats = self.control.stats_accumulator();
+        self.ffb_stats = self.control.stats_accumulator();
+        self.control.set_ffb_gain(self.config.ffb_config.global_gain);
+        // After a supervised restart the running game's profile applies again
+        if self.control.game_profile().is_some() {
+            // The input loop applies it on its first tick
+            self.control.request_reload(self.base_config.clone());
+        }
+    }
+
+    /// Inventory nickname of the wheel, or its model when it has none
+    fn wheel_name(&self) -> String {
+        let wheel = &self.config.thrustmaster_config;
+        inventory::Inventory::load(&inventory::default_path())
+            .ok()
+            .and_then(|inventory| {
+                inventory
+                    .nickname(wheel.vid, wheel.pid, self.thrustmaster.serial_number())
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| self.thrustmaster.model().to_string())
     }
 
     /// Start the translation loop
     pub async fn run(mut self) -> Result<()> {
         tracing::info!("Starting protocol translator");
+        self.control.record_pipeline_start();
         
-        // Use Arc and Mutex to share state between tasks
-        use std::sync::Arc;
-        use tokio::sync::Mutex;
-        
-        let translator = Arc::new(Mutex::new(self));
-        let translator_input = translator.clone();
-        let translator_output = translator.clone();
-        
-        // Spawn input translation task
-        let input_task = tokio::spawn(async move {
-            Self::run_input_translation_task(translator_input).await
-        });
-        
-        // Spawn output translation task  
-        let output_task = tokio::spawn(async move {
-            Self::run_output_translation_task(translator_output).await
-        });
+        self.control.set_read_strategy(self.thrustmaster.read_strategy().clone());
+        let wheel_name = self.wheel_name();
+        tracing::info!("Translating {}", wheel_name);
+        self.control.set_wheel_name(wheel_name);
+        self.control.set_steering_range(self.config.input_config.output_range());
+        // Games already running send full forces the moment the wheel appears
+        self.ffb_engine.begin_soft_start();
         
-        // Run both tasks concurrently
-        let (input_result, output_result) = tokio::join!(input_task, output_task);
-        input_result.map_err(|e| TranslatorError::protocol_error(format!("Input task failed: {}", e)))??;
-        output_result.map_err(|e| TranslatorError::protocol_error(format!("Output task failed: {}", e)))??;
+        // Game telemetry providers run independently of the translation loops
+        let telemetry_tasks = telemetry::spawn_providers(&self.config.telemetry_config, &self.telemetry_bus);
+        let mut side_tasks = SideTasks(telemetry_tasks.iter().map(|task| task.abort_handle()).collect());
+        match rumble::spawn(&self.config.pedal_rumble_config, &self.experimental, &self.telemetry_bus) {
+            Ok(task) => side_tasks.0.extend(task.map(|task| task.abort_handle())),
+            Err(e) => tracing::warn!("Pedal rumble unavailable: {}", e),
+        }
         
-        Ok(())
-    }
+        // Each loop owns its half of the translator, neither waits on the other
+        let (input, ffb) = pipeline::split(self);
+        let mut input_task = tokio::spawn(input.run());
+        let mut ffb_task = tokio::spawn(ffb.run());
 
-    /// Handle input translation (Thrustmaster -> G29)
-    async fn run_input_translation_task(translator: std::sync::Arc<tokio::sync::Mutex<Self>>) -> Result<()> {
-        let mut interval = tokio::time::interval(std::time::Duration::from_millis(1));
-        
-        loop {
-            interval.tick().await;
-            
-            let mut t = translator.lock().await;
-            
-            // Read from Thrustmaster device
-            if let Some(input_report) = t.thrustmaster.read_input().await? {
-                // Translate to G29 format
-                let g29_report = t.input_translator.translate(input_report);
-                
-                // Send to virtual G29 device
-                t.virtual_g29.send_input(g29_report).await?;
+        // When either loop stops, the session ends with its result
+        let stopped = tokio::select! {
+            result = &mut input_task => {
+                result.map_err(|e| TranslatorError::protocol_error(format!("Input task failed: {}", e)))
             }
+            result = &mut ffb_task => {
+                result.map_err(|e| TranslatorError::protocol_error(format!("FFB task failed: {}", e)))
+            }
+        };
+        input_task.abort();
+        ffb_task.abort();
+        stopped?
+    }
+}
+
+/// Tasks running beside the translation loops, aborted once `run` returns or is dropped
+///
+/// A supervised restart starts them afresh, the old ones must not keep the
+/// telemetry ports or subscribe to the bus of a pipeline that is gone.
+struct SideTasks(Vec<tokio::task::AbortHandle>);
+
+impl Drop for SideTasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
         }
     }
+}
+
+/// Sections of `new` whose changes need the devices reopened
+fn device_sections_changed(old: &Config, new: &Config) -> Vec<&'static str> {
+    let differs = |old: Result<toml::Value, _>, new: Result<toml::Value, _>| old.ok() != new.ok();
+    let mut sections = Vec::new();
+    if differs(toml::Value::try_from(&old.thrustmaster_config), toml::Value::try_from(&new.thrustmaster_config)) {
+        sections.push("thrustmaster_config");
+    }
+    if differs(toml::Value::try_from(&old.g29_config), toml::Value::try_from(&new.g29_config)) {
+        sections.push("g29_config");
+    }
+    if old.host_config != new.host_config {
+        sections.push("host_config");
+    }
+    if old.experimental != new.experimental {
+        sections.push("experimental");
+    }
+    if old.ab_test_config != new.ab_test_config {
+        sections.push("ab_test_config");
+    }
+    if old.jitter_config != new.jitter_config {
+        sections.push("jitter_config");
+    }
+    // Gear buttons apply at once, the device is only opened at startup
+    let shifter = |config: &Config| (config.shifter_config.enabled, config.shifter_config.vid, config.shifter_config.pid);
+    if shifter(old) != shifter(new) {
+        sections.push("shifter_config");
+    }
+    sections
+}
 
-    /// Handle output translation (G29 -> Thrustmaster)
-    async fn run_output_translation_task(translator: std::sync::Arc<tokio::sync::Mutex<Self>>) -> Result<()> {
-        loop {
-            let mut t = translator.lock().await;
-            
-            // Read output reports from virtual G29 device
-            if let Some(output_report) = t.virtual_g29.read_output().await? {
-                // Handle FFB effects
-                if let Some(ffb_effect) = t.output_translator.parse_ffb_effect(output_report)? {
-                    // Translate to Thrustmaster IFORCE format
-                    let iforce_commands = t.ffb_engine.translate_effect(ffb_effect)?;
-                    
-                    // Send to Thrustmaster device
-                    for command in iforce_commands {
-                        t.thrustmaster.send_ffb_command(command).await?;
-                    }
-                }
+/// `config` with the running game's profile, or else its active one, layered on top
+fn with_profile(config: Config, game: Option<&str>) -> Config {
+    match profile::effective(&config, game) {
+        Ok(effective) => {
+            if let Some(name) = &effective.profile_config.active {
+                tracing::info!("Using profile {}", name);
             }
+            effective
+        }
+        Err(e) => {
+            tracing::warn!("Profile not applied, using the base configuration: {}", e);
+            config
         }
     }
-} 
\ No newline at end of file
+}
+
+/// Tick budget monitor for `config`, `None` when the budget is off
+fn tick_budget_monitor(config: &Config) -> Option<tick_budget::TickBudgetMonitor> {
+    let budget = config.logging_config.tick_budget;
+    (budget.budget_us > 0).then(|| {
+        tick_budget::TickBudgetMonitor::new(std::time::Duration::from_micros(budget.budget_us as u64), budget.overruns)
+    })
+}
+
+/// FFB engine for `config`, tuned for the host
+fn ffb_engine(config: &Config, ffb_config: &config::FfbConfig, capabilities: &FfbCapabilities) -> FfbEngine {
+    if ffb_config.render_mode == config::FfbRenderMode::Render {
+        tracing::info!("Rendering every FFB effect as one constant force stream");
+    }
+    FfbEngine::with_capabilities(&config.host_config.tune_ffb(ffb_config), capabilities.clone())
+        .with_embedded(config.host_config.embedded)
+}
+
+/// Jitter buffer holding items `delay_ms`, `None` when buffering is off
+fn jitter_buffer<T>(config: &config::JitterConfig, delay_ms: u32) -> Option<jitter::JitterBuffer<T>> {
+    (config.enabled && delay_ms > 0).then(|| {
+        jitter::JitterBuffer::new(
+            std::time::Duration::from_millis(delay_ms as u64),
+            std::time::Duration::from_millis(config.max_delay_ms as u64),
+        )
+    })
+}
+
+/// Key bindings of `config`, none when they are invalid
+fn key_mapper(config: &Config) -> keys::KeyMapper {
+    keys::KeyMapper::from_config(&config.keyboard_config).unwrap_or_else(|e| {
+        tracing::warn!("Button key bindings disabled: {}", e);
+        keys::KeyMapper::default()
+    })
+}
+
+/// FFB A/B comparison `config` asks for, `None` when it is off or cannot run
+fn ab_test(config: &Config) -> Option<ab::AbTest> {
+    match ab::AbTest::from_config(config, std::time::Instant::now()) {
+        Ok(Some(ab)) => {
+            let [a, b] = &ab.report().names;
+            tracing::info!("FFB A/B comparison of {} and {} running, sides are revealed in the session report", a, b);
+            Some(ab)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("FFB A/B comparison disabled: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Rate limiting for warnings raised in the translation loops
+//!
+//! The input and output loops run at up to 1 kHz, so a failure that repeats
+//! every tick (a flaky USB link, a stalled virtual device) would otherwise log
+//! a thousand lines a second. A `LogLimiter` lets the first occurrence of a
+//! warning through, counts repeats from the same call site, and logs them as
+//! one "repeated N times" summary once per interval. The emitted and
+//! suppressed totals feed `stats::PipelineStats`.
+
+use crate::clock::{Clock, SystemClock};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Shortest time between two log lines from the same site
+pub const DEFAULT_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Warn,
+    Error,
+}
+
+/// Totals since the limiter was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogCounts {
+    /// Lines actually logged, summaries included
+    pub emitted: u64,
+    /// Repeats folded into summaries
+    pub suppressed: u64,
+}
+
+/// Repeats of one call site within the current interval
+struct Site {
+    level: LogLevel,
+    window_start: Instant,
+    suppressed: u64,
+    last_message: String,
+}
+
+pub struct LogLimiter {
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    sites: Mutex<HashMap<&'static str, Site>>,
+    emitted: AtomicU64,
+    suppressed: AtomicU64,
+}
+
+impl Default for LogLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_INTERVAL)
+    }
+}
+
+impl LogLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            clock: Arc::new(SystemClock),
+            sites: Mutex::new(HashMap::new()),
+            emitted: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Use `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn warn(&self, site: &'static str, message: impl Display) {
+        self.log(LogLevel::Warn, site, message);
+    }
+
+    pub fn error(&self, site: &'static str, message: impl Display) {
+        self.log(LogLevel::Error, site, message);
+    }
+
+    /// Log `message` unless `site` already logged within the interval
+    pub fn log(&self, level: LogLevel, site: &'static str, message: impl Display) {
+        let now = self.clock.now();
+        let Ok(mut sites) = self.sites.lock() else {
+            return;
+        };
+
+        match sites.get_mut(site) {
+            Some(entry) if now.duration_since(entry.window_start) < self.interval => {
+                entry.suppressed += 1;
+                entry.last_message = message.to_string();
+                self.suppressed.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(entry) => {
+                let repeated = std::mem::take(&mut entry.suppressed);
+                let elapsed = now.duration_since(entry.window_start);
+                entry.window_start = now;
+                entry.level = level;
+                emit(level, site, &message.to_string(), repeated, elapsed);
+                self.emitted.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                sites.insert(
+                    site,
+                    Site { level, window_start: now, suppressed: 0, last_message: String::new() },
+                );
+                emit(level, site, &message.to_string(), 0, Duration::ZERO);
+                self.emitted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Log summaries of sites whose repeats stopped before the interval ended
+    ///
+    /// Call periodically; without it the last burst of a site that went quiet
+    /// is only reported when the site fires again.
+    pub fn flush(&self) {
+        let now = self.clock.now();
+        let Ok(mut sites) = self.sites.lock() else {
+            return;
+        };
+
+        for (site, entry) in sites.iter_mut() {
+            let elapsed = now.duration_since(entry.window_start);
+            if entry.suppressed > 0 && elapsed >= self.interval {
+                let repeated = std::mem::take(&mut entry.suppressed);
+                emit(entry.level, site, &entry.last_message, repeated, elapsed);
+                entry.window_start = now;
+                self.emitted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn counts(&self) -> LogCounts {
+        LogCounts {
+            emitted: self.emitted.load(Ordering::Relaxed),
+            suppressed: self.suppressed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn emit(level: LogLevel, site: &str, message: &str, repeated: u64, elapsed: Duration) {
+    match (level, repeated) {
+        (LogLevel::Warn, 0) => tracing::warn!(site, "{}", message),
+        (LogLevel::Error, 0) => tracing::error!(site, "{}", message),
+        (LogLevel::Warn, _) => {
+            tracing::warn!(site, repeated, "{} (repeated {} times in {:.1?})", message, repeated, elapsed)
+        }
+        (LogLevel::Error, _) => {
+            tracing::error!(site, repeated, "{} (repeated {} times in {:.1?})", message, repeated, elapsed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_repeats_are_folded_into_summaries() {
+        let clock = MockClock::new();
+        let limiter = LogLimiter::new(Duration::from_secs(1)).with_clock(Arc::new(clock.clone()));
+
+        for _ in 0..1000 {
+            limiter.warn("read", "USB read failed");
+            clock.advance(Duration::from_micros(500));
+        }
+        assert_eq!(limiter.counts(), LogCounts { emitted: 1, suppressed: 999 });
+
+        clock.advance(Duration::from_secs(1));
+        limiter.flush();
+        assert_eq!(limiter.counts().emitted, 2);
+        limiter.flush();
+        assert_eq!(limiter.counts().emitted, 2);
+
+        limiter.warn("other", "different site");
+        assert_eq!(limiter.counts(), LogCounts { emitted: 3, suppressed: 999 });
+    }
+}
@@ -0,0 +1,182 @@
+//! Prometheus metrics endpoint
+//!
+//! With `[metrics_config]` enabled the translator answers `GET /metrics` on
+//! the configured address in the Prometheus text format, so a rig's health
+//! can be graphed in Grafana. Everything is read from the lock-free pipeline
+//! counters and the state snapshot, a scrape never touches the translation
+//! loops. Rates (reports or FFB commands per second) are left to PromQL's
+//! `rate()`, loop latency percentiles to `histogram_quantile()` over the
+//! input tick histogram.
+
+use crate::control::TranslatorControl;
+use crate::error::{Result, TranslatorError};
+use crate::jitter::{JitterReport, JitterStats};
+use crate::stats::{PipelineStats, TICK_BUCKETS_US};
+use std::fmt::Write as _;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most bytes of request line and headers read, a scrape sends a few hundred
+const MAX_REQUEST_LEN: u64 = 8192;
+
+/// Metrics in the Prometheus text exposition format
+pub fn render(control: &TranslatorControl) -> String {
+    let state = control.snapshot();
+    let mut out = render_stats(&control.stats());
+    let gauges = [
+        ("tm_g29_paused", "1 while input and force feedback are paused", control.is_paused() as u8 as f64),
+        ("tm_g29_ffb_gain", "Global FFB gain applied", state.ffb_gain as f64),
+        ("tm_g29_active_effects", "FFB effects held by the engine", state.effects.len() as f64),
+        ("tm_g29_force", "Last constant force magnitude sent to the wheel", state.force as f64),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+    }
+    if let Some(report) = control.jitter_report() {
+        out.push_str(&render_jitter(&report));
+    }
+    out
+}
+
+/// Jitter buffer occupancy, one series per buffered path
+fn render_jitter(report: &JitterReport) -> String {
+    let mut out = String::new();
+    let paths: Vec<_> = [("input", report.input), ("ffb", report.ffb)]
+        .into_iter()
+        .filter_map(|(path, stats)| Some((path, stats?)))
+        .collect();
+    let series: [(&str, &str, &str, fn(&JitterStats) -> f64); 4] = [
+        ("tm_g29_jitter_buffered_items", "gauge", "Items waiting in the jitter buffer", |s| s.occupancy as f64),
+        ("tm_g29_jitter_buffered_seconds", "gauge", "Time the waiting items last at the current pace", |s| s.buffered_ms as f64 / 1e3),
+        ("tm_g29_jitter_dropped_total", "counter", "Items dropped for waiting longer than max_delay_ms", |s| s.dropped as f64),
+        ("tm_g29_jitter_underruns_total", "counter", "Times the jitter buffer ran dry while playing out", |s| s.underruns as f64),
+    ];
+    for (name, kind, help, value) in series {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+        for (path, stats) in &paths {
+            let _ = writeln!(out, "{}{{path=\"{}\"}} {}", name, path, value(stats));
+        }
+    }
+    out
+}
+
+fn render_stats(stats: &PipelineStats) -> String {
+    let mut out = String::new();
+    let counters = [
+        ("tm_g29_input_reports_total", "Wheel reports translated", stats.input_reports),
+        ("tm_g29_ffb_effects_total", "FFB updates sent by the game", stats.ffb_effects),
+        ("tm_g29_ffb_commands_total", "IFORCE commands sent to the wheel", stats.ffb_commands),
+        ("tm_g29_clipped_forces_total", "Forces clipped at full scale", stats.clipped_forces),
+        ("tm_g29_dropped_reports_total", "Translated reports the virtual G29 did not accept", stats.dropped_reports),
+        ("tm_g29_dropped_ffb_commands_total", "FFB commands the wheel did not accept", stats.dropped_ffb_commands),
+        ("tm_g29_restarts_total", "Pipeline restarts, e.g. after the wheel reconnected", stats.restarts),
+        ("tm_g29_warnings_total", "Warnings logged", stats.warnings),
+        ("tm_g29_suppressed_warnings_total", "Repeated warnings not logged", stats.suppressed_warnings),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+    }
+
+    let name = "tm_g29_input_tick_seconds";
+    let _ = writeln!(out, "# HELP {} Input loop tick duration, read to send\n# TYPE {} histogram", name, name);
+    let mut cumulative = 0;
+    for (i, count) in stats.tick_buckets.iter().enumerate() {
+        cumulative += count;
+        let bound = match TICK_BUCKETS_US.get(i) {
+            Some(&micros) => format!("{}", micros as f64 / 1e6),
+            None => "+Inf".to_string(),
+        };
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+    }
+    let _ = writeln!(out, "{}_sum {}", name, stats.tick_total.as_secs_f64());
+    let _ = writeln!(out, "{}_count {}", name, cumulative);
+    out
+}
+
+/// Serve metrics on `bind` until the task is dropped
+pub async fn serve(bind: &str, control: TranslatorControl) -> Result<()> {
+    let listener = TcpListener::bind(bind).await?;
+    tracing::info!("Metrics on http://{}/metrics", listener.local_addr()?);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, &control).await {
+                tracing::debug!("Metrics request failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Request line of an HTTP request, reading no more than `MAX_REQUEST_LEN` bytes
+async fn read_request<R: AsyncRead + Unpin>(stream: R) -> Result<String> {
+    let mut stream = BufReader::new(stream.take(MAX_REQUEST_LEN));
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    // Headers are not needed, but must be read before answering
+    let mut header = String::new();
+    while stream.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+    Ok(request_line)
+}
+
+async fn serve_connection(mut stream: TcpStream, control: &TranslatorControl) -> Result<()> {
+    let request_line = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .map_err(|_| TranslatorError::Timeout)??;
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = render(control);
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_requests_are_read_up_to_the_cap() {
+        let request = b"GET /metrics HTTP/1.1\r\nHost: rig\r\n\r\n";
+        assert_eq!(read_request(&request[..]).await.unwrap(), "GET /metrics HTTP/1.1\r\n");
+
+        // A line without end stops at the cap instead of growing
+        let endless = vec![b'a'; 4 * MAX_REQUEST_LEN as usize];
+        assert_eq!(read_request(endless.as_slice()).await.unwrap().len(), MAX_REQUEST_LEN as usize);
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut stats = PipelineStats { input_reports: 3, restarts: 1, ..Default::default() };
+        stats.tick_buckets[0] = 2;
+        stats.tick_buckets[TICK_BUCKETS_US.len()] = 1;
+        stats.tick_total = Duration::from_millis(20);
+
+        let text = render_stats(&stats);
+        assert!(text.contains("tm_g29_input_reports_total 3\n"));
+        assert!(text.contains("tm_g29_restarts_total 1\n"));
+        assert!(text.contains("tm_g29_input_tick_seconds_bucket{le=\"0.00005\"} 2\n"));
+        assert!(text.contains("tm_g29_input_tick_seconds_bucket{le=\"0.01\"} 2\n"));
+        assert!(text.contains("tm_g29_input_tick_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("tm_g29_input_tick_seconds_count 3\n"));
+        assert!(text.contains("tm_g29_input_tick_seconds_sum 0.02\n"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Crash-safe writes of the files the translator keeps
+//!
+//! Files are written to a temporary file next to their destination, flushed
+//! to disk and renamed over it, so a crash or power loss mid-write leaves
+//! either the old or the new file, never a truncated one. The replacement
+//! keeps the permissions of the file it replaces; a new file is readable by
+//! its owner only, it may hold the remote wheel key. The configuration
+//! file additionally keeps rotating backups, `config.toml.bak.1` being the
+//! newest: every save that changes the file first copies the previous
+//! version aside, and `tm-g29 config restore` puts one back.
+
+use crate::error::{Result, TranslatorError};
+use std::path::{Path, PathBuf};
+
+/// Write `content` to `path` atomically, creating its directory
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| TranslatorError::config_error(format!("{} is not a file path", path.display())))?;
+    let temp = dir.join(format!(".{}.tmp", name.to_string_lossy()));
+    // A leftover temp file would keep its own mode, start from a fresh one
+    let _ = std::fs::remove_file(&temp);
+
+    let permissions = match std::fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp).and_then(|mut file| {
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| std::fs::rename(&temp, path)) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+
+    // The rename itself is durable once the directory is synced
+    #[cfg(unix)]
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Path of backup `index` of `path`, 1 = the newest
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".bak.{}", index));
+    PathBuf::from(name)
+}
+
+/// Backups of `path` that exist with their index, newest first
+pub fn backups(path: &Path, keep: usize) -> Vec<(usize, PathBuf)> {
+    (1..=keep).map(|index| (index, backup_path(path, index))).filter(|(_, backup)| backup.exists()).collect()
+}
+
+/// Write `content` to `path` atomically, keeping up to `keep` previous versions
+///
+/// Nothing is rotated when the file already holds `content`.
+pub fn write_with_backups(path: &Path, content: &[u8], keep: usize) -> Result<()> {
+    match std::fs::read(path) {
+        Ok(previous) if previous == content => return Ok(()),
+        Ok(_) if keep > 0 => {
+            let _ = std::fs::remove_file(backup_path(path, keep));
+            for index in (1..keep).rev() {
+                let backup = backup_path(path, index);
+                if backup.exists() {
+                    std::fs::rename(&backup, backup_path(path, index + 1))?;
+                }
+            }
+            // Copied rather than moved, the file stays in place until replaced
+            std::fs::copy(path, backup_path(path, 1))?;
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    write_atomic(path, content)
+}
+
+/// Put backup `index` of `path` back in place, the replaced file becoming the newest backup
+pub fn restore(path: &Path, index: usize, keep: usize) -> Result<()> {
+    let backup = backup_path(path, index);
+    let content = std::fs::read(&backup)
+        .map_err(|e| TranslatorError::config_error(format!("Cannot read backup {}: {}", backup.display(), e)))?;
+    write_with_backups(path, &content, keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let dir = std::env::temp_dir().join(format!("tm-g29-persist-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+
+        for version in ["a", "b", "b", "c", "d"] {
+            write_with_backups(&path, version.as_bytes(), 2).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "d");
+        // The unchanged save of "b" rotated nothing, "a" fell off the end
+        assert_eq!(backups(&path, 2), vec![(1, backup_path(&path, 1)), (2, backup_path(&path, 2))]);
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 2)).unwrap(), "b");
+        assert!(!dir.join(".config.toml.tmp").exists());
+
+        restore(&path, 2, 2).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 1)).unwrap(), "d");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions_and_creates_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("tm-g29-persist-mode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_atomic(&path, b"a").unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"b").unwrap();
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
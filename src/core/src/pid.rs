@@ -0,0 +1,182 @@
+//! USB PID effect lifecycle of the virtual G29
+//!
+//! Besides the Set Effect parameter blocks, PID games manage effects with
+//! their own reports: Create New Effect asks the device for a free effect
+//! block, Effect Operation starts and stops it, Block Free releases it and
+//! Device Control resets, pauses or continues the whole device. The device
+//! owns the 40-block `EffectPool`; `OutputTranslator::parse_pid_report`
+//! decodes the reports and `FfbEngine::handle_pid` runs the state machine.
+//!
+//! Blocks written with Set Effect without being created first are allocated
+//! implicitly and play as soon as their parameters arrive, which is how the
+//! backends that only forward parameter blocks (e.g. the Linux uinput device)
+//! drive the engine.
+
+use crate::evdev::G29_FF_EFFECTS_MAX;
+
+/// Effect blocks the device manages, numbered 1 to 40
+pub const PID_EFFECT_BLOCKS: u8 = G29_FF_EFFECTS_MAX as u8;
+
+/// `[effect_block, attack_level: u16 LE, fade_level: u16 LE, attack_time: u16 LE, fade_time: u16 LE]`,
+/// applied to the next Set Effect of the block
+pub const SET_ENVELOPE_REPORT_ID: u8 = 0x02;
+
+/// Feature report asking for a new effect block: `[effect_type, byte_count: u16 LE]`
+pub const CREATE_NEW_EFFECT_REPORT_ID: u8 = 0x05;
+
+/// Feature report answering Create New Effect, see `PidBlockLoad`
+pub const BLOCK_LOAD_REPORT_ID: u8 = 0x06;
+
+/// `[effect_block, operation, loop_count]`
+pub const EFFECT_OPERATION_REPORT_ID: u8 = 0x0A;
+
+/// `[effect_block]`
+pub const BLOCK_FREE_REPORT_ID: u8 = 0x0B;
+
+/// `[control]`
+pub const DEVICE_CONTROL_REPORT_ID: u8 = 0x0C;
+
+/// `[gain]`, 255 = full strength
+pub const DEVICE_GAIN_REPORT_ID: u8 = 0x0D;
+
+/// Loop count playing an effect until it is stopped
+pub const LOOP_FOREVER: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceControl {
+    EnableActuators,
+    DisableActuators,
+    StopAll,
+    Reset,
+    Pause,
+    Continue,
+}
+
+impl DeviceControl {
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            1 => Some(Self::EnableActuators),
+            2 => Some(Self::DisableActuators),
+            3 => Some(Self::StopAll),
+            4 => Some(Self::Reset),
+            5 => Some(Self::Pause),
+            6 => Some(Self::Continue),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded PID lifecycle report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidCommand {
+    Create { effect_type: u8 },
+    /// Play `loop_count` times, `LOOP_FOREVER` until stopped; `solo` stops every other effect first
+    Start { block: u8, solo: bool, loop_count: u8 },
+    Stop { block: u8 },
+    Free { block: u8 },
+    Control(DeviceControl),
+    Gain(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockLoadStatus {
+    Success = 1,
+    Full = 2,
+    Error = 3,
+}
+
+/// Answer to the last Create New Effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PidBlockLoad {
+    /// 0 when no block was allocated
+    pub block: u8,
+    pub status: BlockLoadStatus,
+    /// Blocks still free
+    pub available: u8,
+}
+
+impl PidBlockLoad {
+    /// Feature report body, without the report ID
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let available = (self.available as u16).to_le_bytes();
+        [self.block, self.status as u8, available[0], available[1]]
+    }
+}
+
+/// Allocation state of the effect blocks
+#[derive(Debug, Clone, Default)]
+pub struct EffectPool {
+    /// Bit `n` set when block `n` is allocated
+    allocated: u64,
+    /// Bit `n` set when block `n` came from Create New Effect and waits for Start
+    managed: u64,
+}
+
+impl EffectPool {
+    /// Lowest free block, marked as created by the game
+    pub fn allocate(&mut self) -> Option<u8> {
+        let block = (1..=PID_EFFECT_BLOCKS).find(|&block| !self.is_allocated(block))?;
+        self.allocated |= 1 << block;
+        self.managed |= 1 << block;
+        Some(block)
+    }
+
+    /// Mark `block` as in use by a game that writes parameters without creating it
+    pub fn claim(&mut self, block: u8) {
+        if (1..=PID_EFFECT_BLOCKS).contains(&block) {
+            self.allocated |= 1 << block;
+        }
+    }
+
+    /// Release `block`, `false` if it was not allocated
+    pub fn free(&mut self, block: u8) -> bool {
+        let was_allocated = self.is_allocated(block);
+        if block < 64 {
+            self.allocated &= !(1 << block);
+            self.managed &= !(1 << block);
+        }
+        was_allocated
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_allocated(&self, block: u8) -> bool {
+        block < 64 && self.allocated & (1 << block) != 0
+    }
+
+    /// Whether `block` was created explicitly, so Set Effect only loads its parameters
+    pub fn is_managed(&self, block: u8) -> bool {
+        block < 64 && self.managed & (1 << block) != 0
+    }
+
+    pub fn available(&self) -> u8 {
+        PID_EFFECT_BLOCKS - self.allocated.count_ones() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_allocates_every_block_once() {
+        let mut pool = EffectPool::default();
+        pool.claim(1);
+        assert!(!pool.is_managed(1));
+
+        let blocks: Vec<u8> = std::iter::from_fn(|| pool.allocate()).collect();
+        assert_eq!(blocks.len(), PID_EFFECT_BLOCKS as usize - 1);
+        assert_eq!(blocks[0], 2);
+        assert_eq!(pool.available(), 0);
+        assert!(pool.is_managed(PID_EFFECT_BLOCKS));
+
+        assert!(pool.free(7));
+        assert!(!pool.free(7));
+        assert_eq!(pool.allocate(), Some(7));
+
+        pool.reset();
+        assert_eq!(pool.available(), PID_EFFECT_BLOCKS);
+    }
+}
@@ -0,0 +1,113 @@
+//! One-shot translation of raw report streams, for `tm-g29 pipe`
+//!
+//! Runs the translation stages on bytes instead of devices, so they can be
+//! used as a Unix filter in scripts and tests. Streams are framed by fixed
+//! lengths, since raw HID reports carry no length of their own:
+//!
+//! - input: frames of the wheel model's report length in, encoded G29 input
+//!   reports out
+//! - FFB: frames of `FFB_FRAME_LEN` bytes (report ID first, zero padded) in,
+//!   IFORCE packets out, which are self-delimiting
+//!
+//! Only what a report produces at once is written. Effects the engine would
+//! render or update over time need the running translator.
+
+use crate::config::Config;
+use crate::device::models::ThrustmasterModel;
+use crate::device::{G29OutputReport, G29ReportEncoder};
+use crate::error::{Result, TranslatorError};
+use crate::ffb::FfbEngine;
+use crate::protocol::{InputTranslator, OutputTranslator};
+
+/// Default length of a game output report frame
+pub const FFB_FRAME_LEN: usize = 8;
+
+/// Thrustmaster input reports to G29 input reports
+pub struct InputPipe {
+    model: ThrustmasterModel,
+    translator: InputTranslator,
+    encoder: G29ReportEncoder,
+}
+
+impl InputPipe {
+    pub fn new(config: &Config, model: ThrustmasterModel) -> Result<Self> {
+        Ok(Self {
+            model,
+            translator: InputTranslator::new(&config.input_config),
+            encoder: G29ReportEncoder::for_config(&config.g29_config)?,
+        })
+    }
+
+    /// Default frame length, a full report of the model
+    pub fn frame_len(&self) -> usize {
+        self.model.layout().full_len()
+    }
+
+    /// Encoded G29 report for one Thrustmaster report
+    pub fn translate(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        let report = self.model.layout().parse(frame)?;
+        if let Some(range) = report.base_range {
+            self.translator.set_base_range(range);
+        }
+        if let Some(rim) = report.rim {
+            self.translator.set_rim(rim);
+        }
+        Ok(self.encoder.encode(&self.translator.translate(report)))
+    }
+}
+
+/// Game output reports to IFORCE packets
+pub struct FfbPipe {
+    translator: OutputTranslator,
+    engine: FfbEngine,
+}
+
+impl FfbPipe {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            translator: OutputTranslator::new(&config.output_config),
+            engine: FfbEngine::new(&config.ffb_config),
+        }
+    }
+
+    /// IFORCE packets for one output report, back to back
+    pub fn translate(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        let (&report_id, data) = frame
+            .split_first()
+            .ok_or_else(|| TranslatorError::invalid_report("Empty output report"))?;
+        let report = G29OutputReport { report_id, data: data.to_vec() };
+
+        let commands = match self.translator.parse_pid_report(&report)? {
+            Some(command) => self.engine.handle_pid(command)?,
+            None => match self.translator.parse_ffb_effect(report)? {
+                Some(effect) => self.engine.translate_effect(effect)?,
+                None => Vec::new(),
+            },
+        };
+        Ok(commands.iter().flat_map(|command| command.to_packet()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_frames_become_g29_reports() {
+        let config = Config::default();
+        let mut pipe = InputPipe::new(&config, ThrustmasterModel::T300rs).unwrap();
+        assert_eq!(pipe.frame_len(), 17);
+
+        let mut frame = vec![0u8; pipe.frame_len()];
+        frame[1..3].copy_from_slice(&0x8000u16.to_le_bytes());
+        let encoded = pipe.translate(&frame).unwrap();
+        let encoder = G29ReportEncoder::for_config(&config.g29_config).unwrap();
+        assert_eq!(encoded.len(), encoder.report_len());
+        assert!(pipe.translate(&frame[..4]).is_err());
+
+        let mut ffb = FfbPipe::new(&config);
+        assert!(ffb.translate(&[]).is_err());
+        // Unknown report IDs produce nothing
+        assert!(ffb.translate(&[0x7F; FFB_FRAME_LEN]).unwrap().is_empty());
+    }
+}
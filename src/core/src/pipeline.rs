@@ -0,0 +1,1028 @@
+//! The two translation loops, each owning its half of the translator
+//!
+//! `ProtocolTranslator::run` splits the translator in two so neither 1 kHz
+//! loop waits for the other. The input path owns what turns wheel reports
+//! into G29 reports: the input translator, the auxiliary devices, the key
+//! bindings and the input jitter buffer. The FFB path owns what turns the
+//! game's output reports into IFORCE commands: the output translator, the FFB
+//! engine, the LED engine, the A/B comparison and the FFB jitter buffer. Both
+//! hold the devices behind an `Arc`, their methods take `&self`.
+//!
+//! What one half needs from the other crosses over bounded channels:
+//!
+//! - input to FFB: steering samples for effects rendered against the wheel's
+//!   motion, reloaded configurations, rotation ranges for the soft lock and
+//!   A/B marks. Steering samples are dropped while the FFB path is behind,
+//!   the motion is smoothed anyway; everything else waits for room.
+//! - FFB to input: rotation ranges the game set.
+//!
+//! The FFB path ticks at the FFB update rate and handles every output report
+//! the game wrote since the last tick. When either loop stops, the other
+//! stops with it.
+
+use crate::config::{self, Config};
+use crate::control::{ControlAction, TranslatorControl};
+use crate::device::{self, G29InputReport, G29OutputReport, IforceCommand, SourceWheel, ThrustmasterInputReport, VirtualWheel};
+use crate::device::source::InputEvents;
+use crate::error::{Result, TranslatorError};
+use crate::ffb::{self, FfbEngine};
+use crate::protocol::{InputTranslator, OutputTranslator};
+use crate::recording::SharedSessionRecorder;
+use crate::telemetry::TelemetryFrame;
+use crate::units::{NormalizedAxis, SteeringAngle};
+use crate::{ab, jitter, keys, led, log_limit, profile, stats, tick_budget, ProtocolTranslator};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Messages either channel holds before the sender waits, a quarter second of samples at 1 kHz
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Wakeup period of an event-driven input loop when no input arrives
+const EVENT_IDLE_TICK: Duration = Duration::from_millis(100);
+
+/// What the input path tells the FFB path
+enum FfbMessage {
+    /// Raw steering and when it was read
+    Steering(i16, Instant),
+    /// Reloaded configuration, the profile already layered on top
+    Config(Box<Config>),
+    /// Degrees the wheel and the game's G29 turn, for the soft lock
+    Ranges { wheel: u16, g29: u16 },
+    /// Source buttons changed, for the A/B mark button
+    Buttons(u32),
+    /// The A/B mark action was requested
+    AbMark,
+}
+
+/// What the FFB path tells the input path
+enum InputMessage {
+    /// The game set its G29 to `g29` degrees, the wheel applied `wheel` if it has a range command
+    GameRange { g29: u16, wheel: Option<u16> },
+}
+
+/// Owner of the input loop, wheel reports in and G29 reports out
+pub struct InputPath<W, V> {
+    thrustmaster: Arc<W>,
+    virtual_g29: Arc<V>,
+    /// Reports pushed by the wheel, `None` when it is polled
+    events: Option<InputEvents>,
+    aux_inputs: device::aggregate::AuxiliaryInputs,
+    /// Last raw wheel report, re-translated when only an auxiliary device changed
+    last_wheel_report: Option<ThrustmasterInputReport>,
+    aux_generation: u64,
+    input_translator: InputTranslator,
+    session_recorder: Option<SharedSessionRecorder>,
+    control: TranslatorControl,
+    log_limiter: Arc<log_limit::LogLimiter>,
+    input_stats: stats::StatsAccumulator,
+    /// `None` when tick budget warnings are off
+    tick_budget: Option<tick_budget::TickBudgetMonitor>,
+    last_source_buttons: u32,
+    /// Rotation range the base last reported for itself, or was set to for the game
+    base_range: Option<u16>,
+    /// Rotation range the game last set on its G29
+    game_range: Option<u16>,
+    /// Rim the base last reported as attached
+    rim: Option<device::models::Rim>,
+    /// Buttons typed as keys, bindings follow the active profile
+    key_mapper: keys::KeyMapper,
+    /// Opened on the first bound button press
+    keyboard: Option<keys::VirtualKeyboard>,
+    /// Translated reports waiting for the virtual G29, with `[jitter_config]` enabled
+    input_buffer: Option<jitter::JitterBuffer<G29InputReport>>,
+    /// Configuration as loaded, before a profile is layered on top
+    base_config: Config,
+    config: Config,
+    ffb: mpsc::Sender<FfbMessage>,
+    messages: mpsc::Receiver<InputMessage>,
+}
+
+/// Owner of the FFB loop, the game's output reports in and IFORCE commands out
+pub struct FfbPath<W, V> {
+    thrustmaster: Arc<W>,
+    virtual_g29: Arc<V>,
+    output_translator: OutputTranslator,
+    ffb_engine: FfbEngine,
+    /// Frames for the LED engine, drained every tick
+    telemetry_frames: tokio::sync::broadcast::Receiver<TelemetryFrame>,
+    led_engine: led::LedEngine,
+    /// Mask and brightness level last shown on the wheel, `None` to resend
+    leds_shown: Option<(u8, u8)>,
+    session_recorder: Option<SharedSessionRecorder>,
+    control: TranslatorControl,
+    log_limiter: Arc<log_limit::LogLimiter>,
+    ffb_stats: stats::StatsAccumulator,
+    /// Running FFB A/B comparison, its profiles fixed until a restart
+    ab_test: Option<ab::AbTest>,
+    /// Whole seconds into the comparison when its report was last published
+    ab_published_secs: u64,
+    /// Game FFB updates waiting for the wheel, with `[jitter_config]` enabled
+    ffb_buffer: Option<jitter::JitterBuffer<Vec<IforceCommand>>>,
+    /// Rotation range the game last set on its G29
+    game_range: Option<u16>,
+    config: Config,
+    messages: mpsc::Receiver<FfbMessage>,
+    input: mpsc::Sender<InputMessage>,
+}
+
+/// The halves running `translator`'s input and FFB loops, connected to each other
+pub fn split<W: SourceWheel, V: VirtualWheel>(translator: ProtocolTranslator<W, V>) -> (InputPath<W, V>, FfbPath<W, V>) {
+    let ProtocolTranslator {
+        mut thrustmaster,
+        aux_inputs,
+        last_wheel_report,
+        aux_generation,
+        virtual_g29,
+        input_translator,
+        output_translator,
+        ffb_engine,
+        telemetry_frames,
+        led_engine,
+        leds_shown,
+        session_recorder,
+        control,
+        log_limiter,
+        input_stats,
+        ffb_stats,
+        tick_budget,
+        last_source_buttons,
+        base_range,
+        game_range,
+        rim,
+        ab_test,
+        ab_published_secs,
+        key_mapper,
+        keyboard,
+        input_buffer,
+        ffb_buffer,
+        base_config,
+        config,
+        ..
+    } = translator;
+    let events = thrustmaster.take_input_events();
+    let (thrustmaster, virtual_g29, log_limiter) = (Arc::new(thrustmaster), Arc::new(virtual_g29), Arc::new(log_limiter));
+    let (ffb_sender, ffb_messages) = mpsc::channel(CHANNEL_CAPACITY);
+    let (input_sender, input_messages) = mpsc::channel(CHANNEL_CAPACITY);
+
+    let ffb = FfbPath {
+        thrustmaster: thrustmaster.clone(),
+        virtual_g29: virtual_g29.clone(),
+        output_translator,
+        ffb_engine,
+        telemetry_frames,
+        led_engine,
+        leds_shown,
+        session_recorder: session_recorder.clone(),
+        control: control.clone(),
+        log_limiter: log_limiter.clone(),
+        ffb_stats,
+        ab_test,
+        ab_published_secs,
+        ffb_buffer,
+        game_range,
+        config: config.clone(),
+        messages: ffb_messages,
+        input: input_sender,
+    };
+    let input = InputPath {
+        thrustmaster,
+        virtual_g29,
+        events,
+        aux_inputs,
+        last_wheel_report,
+        aux_generation,
+        input_translator,
+        session_recorder,
+        control,
+        log_limiter,
+        input_stats,
+        tick_budget,
+        last_source_buttons,
+        base_range,
+        game_range,
+        rim,
+        key_mapper,
+        keyboard,
+        input_buffer,
+        base_config,
+        config,
+        ffb: ffb_sender,
+        messages: input_messages,
+    };
+    (input, ffb)
+}
+
+impl<W: SourceWheel, V: VirtualWheel> InputPath<W, V> {
+    /// Translate wheel reports until the wheel or the FFB path stops
+    pub async fn run(mut self) -> Result<()> {
+        let host = self.config.host_config;
+        let mut events = self.events.take();
+        let aux_changed = self.aux_inputs.changed();
+        let polled = events.is_none();
+        // An event-driven loop still ticks now and then for reloads and counters
+        let period = if polled { host.input_interval() } else { EVENT_IDLE_TICK };
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(host.missed_tick_behavior());
+        // When the input jitter buffer releases its next report
+        let mut release_at = None;
+        // When a button held over for the game is due for release
+        let mut button_release_at = None;
+
+        loop {
+            let wheel_event = match &mut events {
+                Some(events) => tokio::select! {
+                    event = events.recv() => match event {
+                        Some(report) => Some(report?),
+                        None => return Err(TranslatorError::protocol_error("Wheel reader thread stopped")),
+                    },
+                    _ = aux_changed.notified() => None,
+                    _ = interval.tick() => None,
+                    _ = jitter::sleep_until(release_at) => {
+                        release_at = None;
+                        None
+                    }
+                    _ = jitter::sleep_until(button_release_at) => {
+                        button_release_at = None;
+                        None
+                    }
+                },
+                None => {
+                    interval.tick().await;
+                    None
+                }
+            };
+
+            self.control.mark_tick();
+            self.take_messages().await?;
+
+            if let Some(config) = self.control.take_reload() {
+                let previous_range = self.config.input_config.steering_range;
+                self.apply_config(config).await?;
+                let range = self.config.input_config.steering_range;
+                if range != previous_range {
+                    if let Err(e) = self.thrustmaster.set_range(range).await {
+                        tracing::warn!("Rotation range not changed: {}", e);
+                    }
+                }
+            }
+            if let Some((center_offset, geometry)) = self.control.take_steering_geometry() {
+                self.input_translator.set_steering_geometry(center_offset, geometry);
+            }
+
+            // Read from Thrustmaster device and the auxiliary inputs
+            let mut timer = tick_budget::TickTimer::start();
+            let wheel_report = if polled { self.thrustmaster.read_input().await? } else { wheel_event };
+            if let Some(input_report) = self.next_input_report(wheel_report) {
+                if !polled || self.thrustmaster.read_strategy().strategy == config::ReadStrategy::HidapiBlocking {
+                    // Waiting for the wheel is not work
+                    timer.skip();
+                } else {
+                    timer.mark(tick_budget::Stage::Read);
+                }
+                for action in self.control.take_actions() {
+                    self.apply_action(action, &input_report).await?;
+                }
+                self.check_buttons(input_report.buttons).await?;
+                self.check_base_range(input_report.base_range).await?;
+                self.check_rim(input_report.rim);
+                // Dropped while the FFB path is behind, its motion is smoothed anyway
+                let _ = self.ffb.try_send(FfbMessage::Steering(input_report.steering, Instant::now()));
+
+                // Translate to G29 format, without the buttons only typed as keys
+                let mut hid_report = input_report;
+                hid_report.buttons &= !self.key_mapper.exclusive_mask();
+                let mut g29_report = self.input_translator.translate(hid_report);
+                button_release_at = self.input_translator.next_button_release();
+                g29_report.buttons |= self.aux_inputs.shifter_buttons(&self.config);
+                timer.mark(tick_budget::Stage::Translate);
+                self.control.record_state(g29_report);
+                self.input_stats.add_input_report();
+                let key_buttons = if self.control.is_paused() { 0 } else { input_report.buttons };
+                self.type_keys(key_buttons);
+
+                if self.control.is_paused() {
+                    continue;
+                }
+
+                if let Some(recorder) = &self.session_recorder {
+                    if let Ok(mut recorder) = recorder.lock() {
+                        recorder.record_wheel(input_report);
+                        recorder.record(g29_report);
+                    }
+                }
+                timer.mark(tick_budget::Stage::Publish);
+
+                // Send to virtual G29 device, through the jitter buffer when enabled
+                match &mut self.input_buffer {
+                    Some(buffer) => buffer.push(g29_report, Instant::now()),
+                    None => self.send_g29_report(g29_report).await,
+                }
+                release_at = self.release_input_reports().await;
+                timer.mark(tick_budget::Stage::Send);
+                let tick = timer.finish();
+                self.input_stats.add_tick(tick.total());
+                self.check_tick_budget(tick);
+            } else {
+                // Idle ticks bring the counters up to date
+                self.input_stats.flush();
+                release_at = self.release_input_reports().await;
+            }
+            self.publish_log_counts();
+        }
+    }
+
+    async fn tell_ffb(&self, message: FfbMessage) -> Result<()> {
+        self.ffb
+            .send(message)
+            .await
+            .map_err(|_| TranslatorError::protocol_error("FFB loop stopped"))
+    }
+
+    /// Apply what the FFB path sent since the last tick
+    async fn take_messages(&mut self) -> Result<()> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(InputMessage::GameRange { g29, wheel }) => self.apply_game_range(g29, wheel).await?,
+                Err(mpsc::error::TryRecvError::Empty) => return Ok(()),
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    return Err(TranslatorError::protocol_error("FFB loop stopped"))
+                }
+            }
+        }
+    }
+
+    /// Switch the input stages to `config` and hand it on to the FFB path
+    ///
+    /// The virtual G29 and the game's effects stay as they are. Device
+    /// settings only take effect when the devices are reopened.
+    async fn apply_config(&mut self, config: Config) -> Result<()> {
+        tracing::info!("Applying reloaded configuration");
+        self.base_config = config.clone();
+        let config = crate::with_profile(config, self.control.game_profile().as_deref());
+        for section in crate::device_sections_changed(&self.config, &config) {
+            tracing::warn!("Changes to [{}] take effect after a restart", section);
+        }
+
+        self.input_translator = InputTranslator::new(&config.input_config);
+        if let Some(range) = self.base_range {
+            self.input_translator.set_base_range(range);
+        }
+        if let Some(degrees) = self.game_range {
+            self.input_translator.set_g29_range(degrees);
+        }
+        if let Some(rim) = self.rim {
+            self.input_translator.set_rim(rim);
+        }
+        self.control.set_steering_range(self.input_translator.output_range());
+        self.tick_budget = crate::tick_budget_monitor(&config);
+        let released = self.key_mapper.release_all();
+        self.send_keys(released);
+        self.key_mapper = crate::key_mapper(&config);
+        self.config = config.clone();
+
+        self.tell_ffb(FfbMessage::Config(Box::new(config))).await?;
+        self.tell_ranges().await
+    }
+
+    /// Tell the FFB path how far the wheel and the game's G29 turn
+    async fn tell_ranges(&self) -> Result<()> {
+        let (wheel, g29) = (self.input_translator.steering_range(), self.input_translator.output_range());
+        self.tell_ffb(FfbMessage::Ranges { wheel, g29 }).await
+    }
+
+    /// Apply a queued runtime action, `input` is the report being translated
+    async fn apply_action(&mut self, action: ControlAction, input: &ThrustmasterInputReport) -> Result<()> {
+        if let Some(recorder) = &self.session_recorder {
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.record_event(format!("{:?}", action));
+            }
+        }
+        match action {
+            ControlAction::TogglePause => {
+                self.control.toggle_pause();
+            }
+            ControlAction::Recenter => {
+                self.input_translator.recenter(input.steering);
+                let angle = SteeringAngle::from_axis(
+                    NormalizedAxis::from_signed_steering(input.steering),
+                    self.input_translator.steering_range(),
+                );
+                tracing::info!("Steering recentered at {:+.1}° from the hardware center", angle.degrees());
+            }
+            ControlAction::CycleProfile => self.cycle_profile(),
+            ControlAction::FfbPanic => {
+                self.control.toggle_ffb_panic();
+            }
+            ControlAction::AbMark => self.tell_ffb(FfbMessage::AbMark).await?,
+        }
+        Ok(())
+    }
+
+    /// Switch to the profile after the active one, applied with the next reload
+    fn cycle_profile(&mut self) {
+        let profiles = match profile::list(&self.base_config) {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                tracing::warn!("Cannot cycle profiles: {}", e);
+                return;
+            }
+        };
+        let Some(next) = profile::next(&profiles, self.config.profile_config.active.as_deref()) else {
+            tracing::warn!("Profile cycling requested but no profiles are configured");
+            return;
+        };
+        tracing::info!("Switching to profile {}", next.name);
+        self.control.set_game_profile(Some(next.name.clone()));
+        self.control.request_reload(self.base_config.clone());
+    }
+
+    /// Next wheel report to translate, with the auxiliary devices merged in
+    ///
+    /// When the wheel has nothing new but an auxiliary device does, or a
+    /// held-over button is due for release, the last wheel report is reused
+    /// so the change goes out at once.
+    fn next_input_report(&mut self, wheel: Option<ThrustmasterInputReport>) -> Option<ThrustmasterInputReport> {
+        let generation = self.aux_inputs.generation();
+        let release_due = self.input_translator.next_button_release().is_some_and(|at| at <= Instant::now());
+        let mut report = match wheel {
+            Some(report) => {
+                self.last_wheel_report = Some(report);
+                report
+            }
+            None if generation != self.aux_generation || release_due => self.last_wheel_report?,
+            None => return None,
+        };
+        self.aux_generation = generation;
+
+        self.aux_inputs.merge_into(&mut report, &self.config);
+        Some(report)
+    }
+
+    /// Toggle the FFB panic override on a press of the configured panic button,
+    /// and hand button changes to the FFB path for the A/B mark button
+    async fn check_buttons(&mut self, buttons: u32) -> Result<()> {
+        if let Some(button) = self.config.ffb_config.panic_button.filter(|&b| b < device::SOURCE_BUTTON_COUNT) {
+            let mask = 1u32 << button;
+            if buttons & mask != 0 && self.last_source_buttons & mask == 0 {
+                self.control.toggle_ffb_panic();
+            }
+        }
+        let changed = buttons != self.last_source_buttons;
+        self.last_source_buttons = buttons;
+        if changed {
+            self.tell_ffb(FfbMessage::Buttons(buttons)).await?;
+        }
+        Ok(())
+    }
+
+    /// Type the keys bound to buttons that changed, releasing all of them for `buttons` 0
+    fn type_keys(&mut self, buttons: u32) {
+        let events = self.key_mapper.update(buttons);
+        self.send_keys(events);
+    }
+
+    fn send_keys(&mut self, events: Vec<keys::KeyEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        if self.keyboard.is_none() {
+            match keys::spawn() {
+                Ok(keyboard) => {
+                    tracing::info!("Virtual keyboard opened for button key bindings");
+                    self.keyboard = Some(keyboard);
+                }
+                Err(e) => {
+                    self.log_limiter.warn("virtual-keys", format!("Bound keys not typed: {}", e));
+                    return;
+                }
+            }
+        }
+        if let Some(keyboard) = &self.keyboard {
+            for event in events {
+                if let Err(e) = keyboard.send(event) {
+                    self.log_limiter.warn("virtual-keys", format!("Key {} not typed: {}", event.key, e));
+                    self.keyboard = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Resync steering scaling when the rotation range was changed on the base itself
+    async fn check_base_range(&mut self, reported: Option<u16>) -> Result<()> {
+        let Some(range) = reported.filter(|&range| Some(range) != self.base_range) else {
+            return Ok(());
+        };
+        if self.base_range.is_some() {
+            tracing::info!("Rotation range changed on the wheel to {}°", range);
+            if let Some(recorder) = &self.session_recorder {
+                if let Ok(mut recorder) = recorder.lock() {
+                    recorder.record_event(format!("BaseRangeChanged({})", range));
+                }
+            }
+        } else if range != self.config.input_config.steering_range {
+            tracing::info!(
+                "The wheel reports a {}° rotation range instead of the configured {}°, scaling steering to match",
+                range,
+                self.config.input_config.steering_range
+            );
+        }
+        self.base_range = Some(range);
+        self.input_translator.set_base_range(range);
+        self.tell_ranges().await
+    }
+
+    /// Scale steering for a rotation range the game set, and the range the wheel applied for it
+    async fn apply_game_range(&mut self, g29: u16, wheel: Option<u16>) -> Result<()> {
+        self.game_range = Some(g29);
+        self.input_translator.set_g29_range(g29);
+        if let Some(wheel) = wheel {
+            self.base_range = Some(wheel);
+            self.input_translator.set_base_range(wheel);
+        }
+        self.control.set_steering_range(g29);
+        self.tell_ranges().await
+    }
+
+    /// Switch button mappings when a rim was swapped on the running base
+    fn check_rim(&mut self, reported: Option<device::models::Rim>) {
+        let Some(rim) = reported.filter(|&rim| Some(rim) != self.rim) else {
+            return;
+        };
+        let mapping = if self.config.input_config.rim_button_mappings.contains_key(&rim.key()) {
+            format!("[input_config.rim_button_mappings.{}]", rim.key())
+        } else {
+            "the default button mapping".to_string()
+        };
+        if self.rim.is_some() {
+            tracing::info!("Rim changed to {}, using {}", rim, mapping);
+            if let Some(recorder) = &self.session_recorder {
+                if let Ok(mut recorder) = recorder.lock() {
+                    recorder.record_event(format!("RimChanged({})", rim.key()));
+                }
+            }
+        } else {
+            tracing::info!("{} attached, using {}", rim, mapping);
+        }
+        self.rim = Some(rim);
+        self.input_translator.set_rim(rim);
+        self.control.set_rim(rim);
+    }
+
+    /// Send a translated report to the virtual G29, a stalled one must not end the session
+    async fn send_g29_report(&mut self, report: G29InputReport) {
+        if let Err(e) = self.virtual_g29.send_input(report).await {
+            self.input_stats.add_dropped_report();
+            self.log_limiter.warn("virtual-send", format!("Failed to send input to the virtual G29: {}", e));
+        }
+    }
+
+    /// Send the reports the input jitter buffer releases by now, returning when the next one is due
+    async fn release_input_reports(&mut self) -> Option<Instant> {
+        let buffer = self.input_buffer.as_mut()?;
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while let Some(report) = buffer.pop(now) {
+            due.push(report);
+        }
+        let (stats, next) = (buffer.stats(), buffer.next_release());
+        self.control.update_jitter_report(|report| report.input = Some(stats));
+        for report in due {
+            self.send_g29_report(report).await;
+        }
+        next
+    }
+
+    /// Account a finished input tick, warning when ticks keep running over budget
+    fn check_tick_budget(&mut self, tick: tick_budget::TickBreakdown) {
+        let Some(report) = self.tick_budget.as_mut().and_then(|monitor| monitor.observe(tick)) else {
+            return;
+        };
+        tracing::warn!(
+            budget_us = report.budget.as_micros() as u64,
+            overruns = report.overruns,
+            ticks = report.ticks,
+            read_us = report.mean.stage(tick_budget::Stage::Read).as_micros() as u64,
+            translate_us = report.mean.stage(tick_budget::Stage::Translate).as_micros() as u64,
+            publish_us = report.mean.stage(tick_budget::Stage::Publish).as_micros() as u64,
+            send_us = report.mean.stage(tick_budget::Stage::Send).as_micros() as u64,
+            "Input loop over its time budget: {}",
+            report
+        );
+    }
+
+    /// Log summaries of quieted warnings and publish the warning counters
+    fn publish_log_counts(&self) {
+        self.log_limiter.flush();
+        self.control.record_log_counts(self.log_limiter.counts());
+    }
+}
+
+impl<W: SourceWheel, V: VirtualWheel> FfbPath<W, V> {
+    /// Translate the game's output reports until the virtual G29 or the input path stops
+    pub async fn run(mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(ffb_interval(&self.config));
+        interval.set_missed_tick_behavior(self.config.host_config.missed_tick_behavior());
+        let mut commands = Vec::new();
+        let mut was_paused = false;
+        loop {
+            interval.tick().await;
+            self.take_messages().await?;
+
+            if let Some(gain) = self.control.take_gain() {
+                let commands = self.set_ffb_gain(gain);
+                self.send_commands(commands).await;
+            }
+            let ab_commands = self.step_ab_test();
+            self.send_commands(ab_commands).await;
+
+            // Forces come back gradually after a pause
+            let paused = self.control.is_paused();
+            if was_paused && !paused {
+                self.ffb_engine.begin_soft_start();
+            }
+            was_paused = paused;
+
+            // The panic override takes priority over anything the game sends
+            let panic_commands = self.sync_ffb_panic();
+            self.send_commands(panic_commands).await;
+
+            // Step software-rendered effects for bases lacking them natively
+            if !self.control.is_paused() {
+                self.ffb_engine.update_active_effects_into(&mut commands)?;
+                if !commands.is_empty() || self.ffb_engine.active_count() != self.control.snapshot().effects.len() {
+                    self.publish_ffb(false, commands.len());
+                }
+                for command in commands.drain(..) {
+                    self.send_ffb_command(command).await;
+                }
+            }
+
+            self.update_leds().await;
+            self.release_ffb_updates().await;
+
+            // Everything the game wrote to the virtual G29 since the last tick
+            let mut idle = true;
+            while let Some(output_report) = self.virtual_g29.read_output().await? {
+                idle = false;
+                self.handle_output_report(output_report).await?;
+            }
+            if idle {
+                self.ffb_stats.flush();
+            }
+        }
+    }
+
+    /// Apply what the input path sent since the last tick
+    async fn take_messages(&mut self) -> Result<()> {
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(message) => message,
+                Err(mpsc::error::TryRecvError::Empty) => return Ok(()),
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    return Err(TranslatorError::protocol_error("Input loop stopped"))
+                }
+            };
+            match message {
+                FfbMessage::Steering(steering, at) => self.ffb_engine.observe_wheel_at(steering, at),
+                FfbMessage::Config(config) => {
+                    let commands = self.apply_config(*config);
+                    self.send_commands(commands).await;
+                }
+                FfbMessage::Ranges { wheel, g29 } => self.ffb_engine.set_soft_lock(wheel, g29),
+                FfbMessage::Buttons(buttons) => self.check_ab_mark_button(buttons),
+                FfbMessage::AbMark => self.mark_ab_round(),
+            }
+        }
+    }
+
+    /// Handle one output report from the game
+    async fn handle_output_report(&mut self, output_report: G29OutputReport) -> Result<()> {
+        if let Some(recorder) = &self.session_recorder {
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.record_output(&output_report);
+            }
+        }
+
+        // Rev-strip commands from the game
+        if let Some(mask) = self.output_translator.parse_leds(&output_report) {
+            self.led_engine.set_game_leds(mask);
+            return Ok(());
+        }
+
+        // Rotation range commands from the game, e.g. per car
+        if let Some(degrees) = self.output_translator.parse_range(&output_report) {
+            return self.apply_game_range(degrees).await;
+        }
+
+        // Effect lifecycle reports, the pool is kept up to date even while paused
+        if let Some(pid_command) = self.output_translator.parse_pid_report(&output_report)? {
+            let iforce_commands = self.ffb_engine.handle_pid(pid_command)?;
+            if self.control.is_paused() {
+                return Ok(());
+            }
+            self.publish_ffb(true, iforce_commands.len());
+            self.send_game_ffb(iforce_commands).await;
+            return Ok(());
+        }
+
+        // Handle FFB effects
+        if let Some(ffb_effect) = self.output_translator.parse_ffb_effect(output_report)? {
+            // Effects are drained but not played while paused or panicked
+            if self.control.is_paused() || self.ffb_engine.is_panicked() {
+                return Ok(());
+            }
+
+            if let ffb::EffectType::Constant(constant) = &ffb_effect.effect_type {
+                self.control.record_force(constant.magnitude);
+                if let Some(recorder) = &self.session_recorder {
+                    if let Ok(mut recorder) = recorder.lock() {
+                        recorder.record_force(constant.magnitude);
+                    }
+                }
+            }
+
+            // Translate to Thrustmaster IFORCE format
+            let iforce_commands = self.ffb_engine.translate_effect(ffb_effect)?;
+            self.publish_ffb(true, iforce_commands.len());
+
+            // Send to Thrustmaster device
+            self.send_game_ffb(iforce_commands).await;
+        }
+        Ok(())
+    }
+
+    /// Switch the FFB stages to `config`, returning the FFB commands it needs
+    ///
+    /// Playing effects are resent with the new gains.
+    fn apply_config(&mut self, config: Config) -> Vec<IforceCommand> {
+        self.output_translator.set_config(&config.output_config);
+        self.led_engine.set_config(&config.output_config.led_patterns);
+        self.leds_shown = None;
+        let ffb_config = self.ab_test.as_ref().map_or(&config.ffb_config, |ab| ab.ffb_config());
+        let commands = self
+            .ffb_engine
+            .set_config(&config.host_config.tune_ffb(ffb_config))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Playing effects not updated to the new FFB settings: {}", e);
+                Vec::new()
+            });
+        self.control.set_ffb_gain(config.ffb_config.global_gain);
+        self.config = config;
+        commands
+    }
+
+    /// Follow a rotation range the game set on its G29, e.g. for the car it loaded
+    ///
+    /// The wheel is set to the same range as far as it turns. The input path
+    /// scales steering for the range the wheel applied, and where the wheel
+    /// still turns further the soft lock holds it at the game's lock.
+    async fn apply_game_range(&mut self, degrees: u16) -> Result<()> {
+        debug_assert!(device::G29_RANGE.contains(&degrees), "parse_range clamps game ranges");
+        if self.game_range == Some(degrees) {
+            return Ok(());
+        }
+        tracing::info!("The game set a {}° rotation range", degrees);
+        if let Some(recorder) = &self.session_recorder {
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.record_event(format!("GameRangeChanged({})", degrees));
+            }
+        }
+        self.game_range = Some(degrees);
+        let wheel = match self.thrustmaster.set_range(degrees).await {
+            Ok(applied) => Some(applied),
+            Err(e) => {
+                tracing::warn!("Rotation range not changed on the wheel: {}", e);
+                None
+            }
+        };
+        self.input
+            .send(InputMessage::GameRange { g29: degrees, wheel })
+            .await
+            .map_err(|_| TranslatorError::protocol_error("Input loop stopped"))
+    }
+
+    /// Change the global FFB gain until the next reload, returning the commands resending playing effects
+    fn set_ffb_gain(&mut self, gain: f32) -> Vec<IforceCommand> {
+        if self.ab_test.is_some() {
+            tracing::warn!("FFB gain not changed, the A/B comparison sets it");
+            return Vec::new();
+        }
+        tracing::info!("FFB gain set to {:.0}%", gain * 100.0);
+        self.config.ffb_config.global_gain = gain;
+        self.control.set_ffb_gain(gain);
+        self.ffb_engine
+            .set_config(&self.config.host_config.tune_ffb(&self.config.ffb_config))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Playing effects not updated to the new FFB gain: {}", e);
+                Vec::new()
+            })
+    }
+
+    /// Count a mark for the current round of the FFB A/B comparison
+    fn mark_ab_round(&mut self) {
+        let Some(ab) = &mut self.ab_test else {
+            tracing::warn!("A/B mark ignored, no FFB comparison is running");
+            return;
+        };
+        ab.mark();
+        tracing::info!("FFB A/B comparison: round marked as better");
+        self.control.set_ab_report(ab.report().clone());
+    }
+
+    /// Count a press of the A/B mark button
+    fn check_ab_mark_button(&mut self, buttons: u32) {
+        if self.ab_test.as_mut().is_some_and(|ab| ab.observe_buttons(buttons)) {
+            tracing::info!("FFB A/B comparison: round marked as better");
+        }
+    }
+
+    /// Switch sides of the FFB A/B comparison once a round is up, returning the commands it needs
+    fn step_ab_test(&mut self) -> Vec<IforceCommand> {
+        let Some(ab) = &mut self.ab_test else {
+            return Vec::new();
+        };
+        let switched = ab.poll(Instant::now());
+        let secs = ab.report().rounds.last().map_or(0, |round| (round.started + round.duration).as_secs());
+        if switched.is_some() || secs != self.ab_published_secs {
+            self.ab_published_secs = secs;
+            self.control.set_ab_report(ab.report().clone());
+        }
+        let Some(side) = switched else {
+            return Vec::new();
+        };
+
+        // The driver is not told which side is next, only the report says
+        tracing::info!("FFB A/B comparison: next round");
+        if let Some(recorder) = &self.session_recorder {
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.record_event(format!("AbSwitch({})", side));
+            }
+        }
+        let (ffb_config, blip) = (ab.ffb_config().clone(), ab.blip_strength());
+        let mut commands = self
+            .ffb_engine
+            .set_config(&self.config.host_config.tune_ffb(&ffb_config))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Playing effects not updated to the next A/B side: {}", e);
+                Vec::new()
+            });
+        commands.extend(self.ffb_engine.blip(blip));
+        commands
+    }
+
+    /// Bring the FFB engine in line with the panic override, returning commands to send
+    fn sync_ffb_panic(&mut self) -> Vec<IforceCommand> {
+        match (self.control.is_ffb_panic(), self.ffb_engine.is_panicked()) {
+            (true, false) => {
+                tracing::warn!("FFB panic engaged, all forces zeroed");
+                self.control.record_force(0);
+                self.ffb_engine.engage_panic()
+            }
+            (false, true) => {
+                tracing::info!("FFB panic released");
+                self.ffb_engine.release_panic();
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Publish the FFB engine state after sending `commands` IFORCE commands
+    fn publish_ffb(&mut self, translated: bool, commands: usize) {
+        self.control.record_timeline(self.ffb_engine.take_timeline());
+        self.control.record_ffb(self.ffb_engine.active_effects());
+        self.ffb_stats.add_ffb(translated, commands, self.ffb_engine.clipped_count());
+    }
+
+    /// Publish and send commands the translator issued itself, not the game
+    async fn send_commands(&mut self, commands: Vec<IforceCommand>) {
+        if commands.is_empty() {
+            return;
+        }
+        self.publish_ffb(false, commands.len());
+        for command in commands {
+            self.send_ffb_command(command).await;
+        }
+    }
+
+    /// Show this tick's rev-strip state on the wheel if it changed
+    async fn update_leds(&mut self) {
+        if !self.config.output_config.led_support || !self.thrustmaster.model().has_leds() {
+            return;
+        }
+        loop {
+            match self.telemetry_frames.try_recv() {
+                Ok(frame) => self.led_engine.update_telemetry(&frame),
+                Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+
+        let mask = self.led_engine.render(Instant::now());
+        let brightness = led::scheduled_brightness(&self.config.output_config, std::time::SystemTime::now());
+        // The night mode changes the brightness without a new mask
+        let shown = (mask, (brightness * u8::MAX as f32).round() as u8);
+        if self.leds_shown == Some(shown) {
+            return;
+        }
+        match self.thrustmaster.set_leds(mask, brightness).await {
+            Ok(()) => self.leds_shown = Some(shown),
+            Err(e) => self.log_limiter.warn("led-send", format!("Failed to set wheel LEDs: {}", e)),
+        }
+    }
+
+    /// Send an IFORCE command, failures are logged and the effect dropped
+    async fn send_ffb_command(&mut self, command: IforceCommand) {
+        if let Err(e) = self.thrustmaster.send_ffb_command(command).await {
+            self.ffb_stats.add_dropped_ffb_command();
+            self.log_limiter.warn("ffb-send", format!("Failed to send FFB command: {}", e));
+        }
+    }
+
+    /// Send the FFB commands of one game update, through the FFB jitter buffer when enabled
+    async fn send_game_ffb(&mut self, commands: Vec<IforceCommand>) {
+        match &mut self.ffb_buffer {
+            Some(buffer) if !commands.is_empty() => buffer.push(commands, Instant::now()),
+            _ => {
+                for command in commands {
+                    self.send_ffb_command(command).await;
+                }
+            }
+        }
+        self.release_ffb_updates().await;
+    }
+
+    /// Send the game updates the FFB jitter buffer releases by now
+    async fn release_ffb_updates(&mut self) {
+        let Some(buffer) = &mut self.ffb_buffer else {
+            return;
+        };
+        // Updates held back during a pause or panic are stale once it ends
+        if self.control.is_paused() || self.ffb_engine.is_panicked() {
+            buffer.clear();
+        }
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while let Some(commands) = buffer.pop(now) {
+            due.extend(commands);
+        }
+        let stats = buffer.stats();
+        self.control.update_jitter_report(|report| report.ffb = Some(stats));
+        for command in due {
+            self.send_ffb_command(command).await;
+        }
+    }
+}
+
+/// Tick of the FFB loop, the FFB update rate as tuned for the host
+fn ffb_interval(config: &Config) -> Duration {
+    let rate = config.host_config.tune_ffb(&config.ffb_config).update_rate_hz.max(1);
+    Duration::from_micros(1_000_000 / rate as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{MockThrustmasterDevice, MockVirtualG29};
+    use crate::device::models::ThrustmasterModel;
+
+    #[test]
+    fn test_ffb_ticks_at_the_tuned_update_rate() {
+        let mut config = Config::default();
+        config.ffb_config.update_rate_hz = 500;
+        assert_eq!(ffb_interval(&config), Duration::from_millis(2));
+
+        // Embedded hosts cap the rate, and a zero rate must not divide by zero
+        config.host_config.embedded = true;
+        assert!(ffb_interval(&config) >= Duration::from_millis(2));
+        config.ffb_config.update_rate_hz = 0;
+        assert_eq!(ffb_interval(&config), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_game_range_crosses_to_the_input_path() {
+        let wheel = MockThrustmasterDevice::new(ThrustmasterModel::T300rs);
+        let target = MockVirtualG29::new();
+        let translator = ProtocolTranslator::with_devices(Config::default(), wheel.clone(), target.clone());
+        let control = translator.control();
+        let (input, ffb) = split(translator);
+        let input_task = tokio::spawn(input.run());
+        let ffb_task = tokio::spawn(ffb.run());
+
+        // The FFB path sets the wheel, the input path rescales steering for it
+        target.game_writes(G29OutputReport::range(540));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while control.steering_range() != 540 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(control.steering_range(), 540);
+        assert_eq!(wheel.ranges(), vec![540]);
+
+        // Without the input path the FFB path stops too
+        input_task.abort();
+        let stopped = tokio::time::timeout(Duration::from_secs(2), ffb_task).await;
+        assert!(matches!(stopped, Ok(Ok(Err(_)))));
+    }
+}
@@ -0,0 +1,127 @@
+//! Units and number format of values shown to people
+//!
+//! `[display_config]` picks how the CLI and the monitor show steering
+//! (wheel degrees or the normalized -1.0 - 1.0 axis) and force (percent of
+//! full scale or Newtons, full scale being `ffb_config.max_force`), and the
+//! decimal separator of the locale. Machine-readable output, the JSON
+//! monitor lines and the control protocol, always uses plain numbers.
+
+use crate::config::{AngleUnit, Config, DisplayConfig, ForceUnit};
+use crate::units::{ForceNewton, NormalizedAxis, SteeringAngle};
+use std::time::Duration;
+
+/// Languages writing a decimal comma
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "it", "lt", "lv", "nb", "nl",
+    "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk", "vi",
+];
+
+/// Formats values as `[display_config]` asks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Presenter {
+    angle: AngleUnit,
+    force: ForceUnit,
+    max_force: ForceNewton,
+    decimal_comma: bool,
+}
+
+impl Default for Presenter {
+    /// Degrees, percent and a decimal point
+    fn default() -> Self {
+        Self {
+            angle: AngleUnit::Degrees,
+            force: ForceUnit::Percent,
+            max_force: ForceNewton::new(2.5),
+            decimal_comma: false,
+        }
+    }
+}
+
+impl Presenter {
+    pub fn new(config: &Config) -> Self {
+        Self::with_locale(&config.display_config, config.ffb_config.max_force, system_locale().as_deref())
+    }
+
+    /// Presenter for `display`, `fallback_locale` applying when it names none
+    pub fn with_locale(display: &DisplayConfig, max_force: ForceNewton, fallback_locale: Option<&str>) -> Self {
+        let locale = display.locale.as_deref().or(fallback_locale).unwrap_or("C");
+        Self { angle: display.angle, force: display.force, max_force, decimal_comma: uses_decimal_comma(locale) }
+    }
+
+    /// `value` with `decimals` places and the locale's separator
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        self.localize(format!("{:.*}", decimals, value))
+    }
+
+    /// Like `number`, always with a sign
+    pub fn signed(&self, value: f64, decimals: usize) -> String {
+        self.localize(format!("{:+.*}", decimals, value))
+    }
+
+    /// Steering position on a wheel turning `range_degrees` lock to lock
+    pub fn steering(&self, axis: NormalizedAxis, range_degrees: u16) -> String {
+        match self.angle {
+            AngleUnit::Degrees => format!("{}°", self.signed(SteeringAngle::from_axis(axis, range_degrees).degrees() as f64, 1)),
+            AngleUnit::Normalized => self.signed(axis.value() as f64, 3),
+        }
+    }
+
+    /// Signed force as a share of full scale, -1.0 - 1.0
+    pub fn force(&self, fraction: f32) -> String {
+        match self.force {
+            ForceUnit::Percent => format!("{}%", self.signed(fraction as f64 * 100.0, 1)),
+            ForceUnit::Newton => format!("{} N", self.signed((fraction * self.max_force.newtons()) as f64, 2)),
+        }
+    }
+
+    /// Share such as a gain or a ratio, 0.0 - 1.0, as a percentage
+    pub fn percent(&self, fraction: f32) -> String {
+        format!("{}%", self.number(fraction as f64 * 100.0, 1))
+    }
+
+    /// Latency in milliseconds
+    pub fn duration(&self, duration: Duration) -> String {
+        format!("{} ms", self.number(duration.as_secs_f64() * 1000.0, 3))
+    }
+
+    fn localize(&self, number: String) -> String {
+        if self.decimal_comma {
+            number.replace('.', ",")
+        } else {
+            number
+        }
+    }
+}
+
+/// Whether `locale`, e.g. `de_DE.UTF-8`, writes a decimal comma
+pub fn uses_decimal_comma(locale: &str) -> bool {
+    let language = locale.split(['_', '-', '.', '@']).next().unwrap_or_default().to_ascii_lowercase();
+    DECIMAL_COMMA_LANGUAGES.contains(&language.as_str())
+}
+
+/// Locale numbers are formatted in, from the environment as POSIX orders it
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_and_decimal_comma() {
+        let display = DisplayConfig { angle: AngleUnit::Normalized, force: ForceUnit::Newton, locale: Some("de_DE.UTF-8".to_string()) };
+        let presenter = Presenter::with_locale(&display, ForceNewton::new(4.0), Some("en_US"));
+        assert_eq!(presenter.steering(NormalizedAxis::saturating(-0.25), 900), "-0,250");
+        assert_eq!(presenter.force(0.5), "+2,00 N");
+        assert_eq!(presenter.duration(Duration::from_micros(1500)), "1,500 ms");
+
+        let presenter = Presenter::with_locale(&DisplayConfig::default(), ForceNewton::new(4.0), Some("en_US"));
+        assert_eq!(presenter.steering(NormalizedAxis::saturating(-0.25), 900), "-112.5°");
+        assert_eq!(presenter.force(0.5), "+50.0%");
+        assert!(!uses_decimal_comma("C"));
+    }
+}
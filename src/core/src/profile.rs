@@ -0,0 +1,403 @@
+//! Configuration profiles building on each other
+//!
+//! A configuration file can start with `extends = "base_t300"` to take every
+//! setting from `base_t300.toml` next to it and only override what differs,
+//! so wheel-specific settings live in one base profile and per-game profiles
+//! stay short. Tables merge key by key, any other value (including arrays)
+//! replaces the inherited one. Bases can extend further bases; a profile
+//! reached twice is a cycle and fails to load.
+//!
+//! Per-game profiles work the same way in the other direction: a named
+//! overlay, either a `[profiles.<name>]` table of the configuration or a
+//! `<name>.toml` file in the profile directory, is layered on top of the
+//! configuration when it is active. `profile_config.active` selects one for
+//! every run; with `auto_switch` the profile listing a running game in its
+//! `processes` takes over while the game runs. Switching goes through the
+//! same reload path as `tm-g29 ctl reload`.
+
+use crate::config::Config;
+use crate::control::TranslatorControl;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml::value::Table;
+
+/// Top-level key naming the profile a file builds on
+pub const EXTENDS_KEY: &str = "extends";
+
+/// A profile with everything it inherits merged in
+#[derive(Debug, Clone)]
+pub struct ResolvedProfile {
+    pub table: Table,
+    /// Files that contributed, the loaded one first and the root base last
+    pub files: Vec<PathBuf>,
+}
+
+/// Load `path` and the chain of profiles it extends
+pub fn resolve(path: &Path) -> Result<ResolvedProfile, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut seen = Vec::new();
+    let mut layers = Vec::new();
+    let mut next = Some(path.to_path_buf());
+
+    while let Some(path) = next.take() {
+        let canonical = path.canonicalize().map_err(|e| format!("{}: {}", path.display(), e))?;
+        if seen.contains(&canonical) {
+            let chain: Vec<String> = files.iter().chain([&path]).map(|file| file.display().to_string()).collect();
+            return Err(format!("Profile inheritance cycle: {}", chain.join(" -> ")).into());
+        }
+        seen.push(canonical);
+
+        let content = std::fs::read_to_string(&path)?;
+        let mut table: Table = toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        crate::config::migrate_steering_tuning(&mut table);
+        if let Some(parent) = table.remove(EXTENDS_KEY) {
+            let name = parent
+                .as_str()
+                .ok_or_else(|| format!("{}: `{}` must be a profile name", path.display(), EXTENDS_KEY))?;
+            next = Some(profile_path(&path, name));
+        }
+        files.push(path);
+        layers.push(table);
+    }
+
+    let mut table = Table::new();
+    for layer in layers.into_iter().rev() {
+        merge(&mut table, layer);
+    }
+    Ok(ResolvedProfile { table, files })
+}
+
+/// File of profile `name` extended by the file at `from`, relative to its directory
+fn profile_path(from: &Path, name: &str) -> PathBuf {
+    let mut path = from.parent().unwrap_or(Path::new(".")).join(name);
+    if path.extension().is_none() {
+        path.set_extension("toml");
+    }
+    path
+}
+
+/// Apply `overlay` on top of `base`
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(inherited)), toml::Value::Table(overrides)) => merge(inherited, overrides),
+            (Some(inherited), value) => *inherited = value,
+            (None, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Key of a game profile listing the process names that activate it
+pub const PROCESSES_KEY: &str = "processes";
+
+/// A named overlay of the configuration
+#[derive(Debug, Clone)]
+pub struct GameProfile {
+    pub name: String,
+    /// Executable names, e.g. `acc.exe`, compared without case
+    pub processes: Vec<String>,
+    pub overlay: Table,
+    /// `None` for a profile kept in the configuration file itself
+    pub file: Option<PathBuf>,
+}
+
+impl GameProfile {
+    fn new(name: String, mut overlay: Table, file: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        crate::config::migrate_steering_tuning(&mut overlay);
+        let processes = match overlay.remove(PROCESSES_KEY) {
+            Some(toml::Value::Array(names)) => names
+                .into_iter()
+                .map(|name| name.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("Profile {}: `{}` must list names", name, PROCESSES_KEY))?,
+            Some(_) => return Err(format!("Profile {}: `{}` must be an array", name, PROCESSES_KEY).into()),
+            None => Vec::new(),
+        };
+        Ok(Self { name, processes, overlay, file })
+    }
+
+    /// Whether one of `running` is a process of this profile
+    pub fn matches(&self, running: &[String]) -> bool {
+        self.processes
+            .iter()
+            .any(|process| running.iter().any(|name| name.eq_ignore_ascii_case(process)))
+    }
+}
+
+/// Directory holding the profile files of `config`
+pub fn directory(config: &Config) -> PathBuf {
+    let base = config.source.as_deref().and_then(Path::parent).unwrap_or(Path::new("."));
+    base.join(&config.profile_config.directory)
+}
+
+/// Every profile of `config`: the inline ones, then the profile files, each by name
+pub fn list(config: &Config) -> Result<Vec<GameProfile>, Box<dyn Error>> {
+    let mut profiles = Vec::new();
+    for (name, overlay) in &config.profiles {
+        profiles.push(GameProfile::new(name.clone(), overlay.clone(), None)?);
+    }
+
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(directory(config)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    for file in files {
+        let Some(name) = file.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+            continue;
+        };
+        if config.profiles.contains_key(&name) {
+            tracing::warn!("Profile {} is defined inline and in {}, using the inline one", name, file.display());
+            continue;
+        }
+        let resolved = resolve(&file)?;
+        profiles.push(GameProfile::new(name, resolved.table, Some(file))?);
+    }
+    Ok(profiles)
+}
+
+/// File content of a new profile: the settings games usually need changed, copied from `config`
+pub fn template(config: &Config, processes: &[String]) -> Result<String, Box<dyn Error>> {
+    let toml::Value::Table(mut current) = toml::Value::try_from(config)? else {
+        return Err("Configuration is not a table".into());
+    };
+    let mut table = Table::new();
+    table.insert(PROCESSES_KEY.to_string(), toml::Value::Array(processes.iter().cloned().map(toml::Value::String).collect()));
+
+    let mut input = Table::new();
+    if let Some(toml::Value::Table(mut current_input)) = current.remove("input_config") {
+        for key in ["steering_range", "pedal_curves", "button_mapping"] {
+            if let Some(value) = current_input.remove(key) {
+                input.insert(key.to_string(), value);
+            }
+        }
+    }
+    table.insert("input_config".to_string(), toml::Value::Table(input));
+    if let Some(ffb) = current.remove("ffb_config") {
+        table.insert("ffb_config".to_string(), ffb);
+    }
+    Ok(toml::to_string_pretty(&table)?)
+}
+
+/// `config` with `profile` layered on top
+pub fn apply(config: &Config, profile: &GameProfile) -> Result<Config, Box<dyn Error>> {
+    let mut table = match toml::Value::try_from(config)? {
+        toml::Value::Table(table) => table,
+        _ => return Err("Configuration is not a table".into()),
+    };
+    merge(&mut table, profile.overlay.clone());
+    let mut layered: Config = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| format!("Profile {}: {}", profile.name, e))?;
+    layered.input_config.check_steering().map_err(|e| format!("Profile {}: {}", profile.name, e))?;
+    layered.source = config.source.clone();
+    layered.profile_config.active = Some(profile.name.clone());
+    Ok(layered)
+}
+
+/// `config` with the running game's profile, or else its active one, layered on top
+pub fn effective(config: &Config, game: Option<&str>) -> Result<Config, Box<dyn Error>> {
+    let Some(name) = game.or(config.profile_config.active.as_deref()) else {
+        return Ok(config.clone());
+    };
+    let profiles = list(config)?;
+    let profile = profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("No profile named {}", name))?;
+    apply(config, profile)
+}
+
+/// Profile after `current` in `profiles`, wrapping around, the first one when none is selected
+pub fn next<'a>(profiles: &'a [GameProfile], current: Option<&str>) -> Option<&'a GameProfile> {
+    let position = current.and_then(|name| profiles.iter().position(|profile| profile.name == name));
+    match position {
+        Some(index) => profiles.get((index + 1) % profiles.len()),
+        None => profiles.first(),
+    }
+}
+
+/// Switch to the profile of whichever listed game is running, until the task is dropped
+///
+/// `config` is the configuration as loaded from its file. The first
+/// matching profile in `list` order wins; when no game runs, the configured
+/// `active` profile applies again. A profile selected by hand, e.g. with the
+/// cycle button, is kept until the running game changes.
+pub async fn watch_games(control: TranslatorControl, config: Config) {
+    let profiles = match list(&config) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            tracing::warn!("Automatic profile switching disabled: {}", e);
+            return;
+        }
+    };
+    if profiles.iter().all(|profile| profile.processes.is_empty()) {
+        tracing::warn!("Automatic profile switching is on but no profile lists `{}`", PROCESSES_KEY);
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_millis(config.profile_config.poll_interval_ms.max(100) as u64));
+    let mut detected = control.game_profile();
+    loop {
+        interval.tick().await;
+        let running = running_processes();
+        let game = profiles.iter().find(|profile| profile.matches(&running)).map(|profile| profile.name.clone());
+        if game == detected {
+            continue;
+        }
+        detected = game.clone();
+        match &game {
+            Some(name) => tracing::info!("Game detected, switching to profile {}", name),
+            None => tracing::info!("Game closed, leaving its profile"),
+        }
+        control.set_game_profile(game);
+        // Reread the file so edits made since startup are not lost
+        let base = config
+            .source
+            .as_ref()
+            .and_then(|path| Config::load_from_file(&path.to_string_lossy()).ok())
+            .unwrap_or_else(|| config.clone());
+        control.request_reload(base);
+    }
+}
+
+/// Executable names of the running processes
+#[cfg(target_os = "linux")]
+pub fn running_processes() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let cmdline = std::fs::read(entry.ok()?.path().join("cmdline")).ok()?;
+            let program = cmdline.split(|&byte| byte == 0).next()?;
+            // Wine and Proton games show their Windows path
+            let program = String::from_utf8_lossy(program);
+            let name = program.rsplit(['/', '\\']).next()?;
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Executable names of the running processes
+#[cfg(windows)]
+pub fn running_processes() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("tasklist").args(["/FO", "CSV", "/NH"]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+/// Executable names of the running processes
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn running_processes() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("ps").args(["-A", "-o", "comm="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().rsplit('/').next())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_override_their_base_and_reject_cycles() {
+        let dir = std::env::temp_dir().join(format!("tm-g29-profile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base_t300.toml"), "[ffb_config]\nglobal_gain = 0.8\nupdate_rate_hz = 500\n").unwrap();
+        std::fs::write(dir.join("game.toml"), "extends = \"base_t300\"\n[ffb_config]\nglobal_gain = 0.6\n").unwrap();
+
+        let resolved = resolve(&dir.join("game.toml")).unwrap();
+        assert_eq!(resolved.files.len(), 2);
+        let ffb = resolved.table["ffb_config"].as_table().unwrap();
+        assert_eq!(ffb["global_gain"].as_float(), Some(0.6));
+        assert_eq!(ffb["update_rate_hz"].as_integer(), Some(500));
+        assert!(!resolved.table.contains_key(EXTENDS_KEY));
+
+        std::fs::write(dir.join("a.toml"), "extends = \"b\"\n").unwrap();
+        std::fs::write(dir.join("b.toml"), "extends = \"a.toml\"\n").unwrap();
+        let error = resolve(&dir.join("a.toml")).unwrap_err().to_string();
+        assert!(error.contains("cycle"), "{}", error);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_steering_settings_move_into_the_tuning() {
+        let mut table: Table = toml::from_str(
+            "[input_config]\nsteering_deadzone = 0.1\n[input_config.steering_geometry]\ncenter_offset = 0.05\nleft_scale = 1.1\n\
+             [input_config.axis_tuning.steering]\ncenter_offset = 0.01\ndeadzone = 0.5\n",
+        )
+        .unwrap();
+        crate::config::migrate_steering_tuning(&mut table);
+        let input = table["input_config"].as_table().unwrap();
+        assert!(!input.contains_key("steering_deadzone"));
+        let geometry = input["steering_geometry"].as_table().unwrap();
+        assert!(!geometry.contains_key("center_offset"));
+        assert_eq!(geometry["left_scale"].as_float(), Some(1.1));
+
+        let steering = input["axis_tuning"]["steering"].as_table().unwrap();
+        assert!((steering["center_offset"].as_float().unwrap() - 0.06).abs() < 1e-9);
+        // 10% of the travel left past a 50% deadzone is 55% in all
+        assert!((steering["deadzone"].as_float().unwrap() - 0.55).abs() < 1e-9);
+
+        // Only the legacy keys: they become the tuning
+        let mut table: Table = toml::from_str("[input_config]\nsteering_deadzone = 0\n").unwrap();
+        crate::config::migrate_steering_tuning(&mut table);
+        assert_eq!(table["input_config"]["axis_tuning"]["steering"]["deadzone"].as_float(), Some(0.0));
+    }
+
+    #[test]
+    fn test_game_profiles_layer_on_the_configuration() {
+        let dir = std::env::temp_dir().join(format!("tm-g29-games-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("profiles")).unwrap();
+        std::fs::write(
+            dir.join("profiles/acc.toml"),
+            "processes = [\"AC2-Win64-Shipping.exe\"]\n[ffb_config]\nglobal_gain = 0.5\n",
+        )
+        .unwrap();
+
+        let mut config = Config { source: Some(dir.join("config.toml")), ..Config::default() };
+        config.profiles.insert("rally".to_string(), toml::from_str("[input_config]\nsteering_range = 540\n").unwrap());
+
+        let profiles = list(&config).unwrap();
+        let names: Vec<_> = profiles.iter().map(|profile| profile.name.as_str()).collect();
+        assert_eq!(names, ["rally", "acc"]);
+        assert!(profiles[1].matches(&["ac2-win64-shipping.exe".to_string()]));
+        assert!(!profiles[0].matches(&["ac2-win64-shipping.exe".to_string()]));
+
+        let layered = effective(&config, Some("acc")).unwrap();
+        assert_eq!(layered.ffb_config.global_gain, 0.5);
+        assert_eq!(layered.profile_config.active.as_deref(), Some("acc"));
+        config.profile_config.active = Some("rally".to_string());
+        let layered = effective(&config, None).unwrap();
+        assert_eq!(layered.input_config.steering_range, 540);
+        assert_eq!(layered.ffb_config.global_gain, config.ffb_config.global_gain);
+        assert!(effective(&config, Some("missing")).is_err());
+
+        // The cycle button steps through every profile and wraps around
+        let name = |profile: Option<&GameProfile>| profile.map(|profile| profile.name.clone());
+        assert_eq!(name(next(&profiles, None)), Some("rally".to_string()));
+        assert_eq!(name(next(&profiles, Some("rally"))), Some("acc".to_string()));
+        assert_eq!(name(next(&profiles, Some("acc"))), Some("rally".to_string()));
+        assert_eq!(name(next(&profiles, Some("missing"))), Some("rally".to_string()));
+        assert!(next(&[], None).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,105 +1,231 @@
 //! Protocol translation between Thrustmaster and G29 formats
 
-use crate::device::{ThrustmasterInputReport, G29InputReport, G29OutputReport};
-use crate::config::{InputConfig, OutputConfig, CurveType};
-use crate::ffb::FfbEffect;
+use crate::device::{ThrustmasterInputReport, G29InputReport, G29OutputReport, HatDirection, SOURCE_BUTTON_COUNT};
+use crate::device::{G29_EXTENDED_COMMAND, G29_RANGE, G29_SET_LEDS, G29_SET_RANGE};
+use crate::device::models::Rim;
+use crate::led::ALL_LEDS;
+use crate::evdev::G29_BUTTON_COUNT;
+use crate::assists::AssistState;
+use crate::axis_mapping::{AxisMapper, Axes};
+use crate::combos::ComboState;
+use crate::config::{ButtonTiming, FfbQuirks, InputConfig, OutputConfig, CurveType, SteeringGeometry};
+use crate::ffb::{Envelope, FfbEffect};
+use crate::fingerprint::FfbFingerprinter;
+use crate::pid::{
+    DeviceControl, PidCommand, BLOCK_FREE_REPORT_ID, CREATE_NEW_EFFECT_REPORT_ID, DEVICE_CONTROL_REPORT_ID,
+    DEVICE_GAIN_REPORT_ID, EFFECT_OPERATION_REPORT_ID, SET_ENVELOPE_REPORT_ID,
+};
 use crate::error::{TranslatorError, Result};
-// use std::collections::HashMap;
+use crate::units::{Milliseconds, NormalizedAxis};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// Handles input translation from Thrustmaster to G29 format
 pub struct InputTranslator {
     config: InputConfig,
-    last_steering: i16,
+    last_steering: u16,
+    button_timer: ButtonTimer,
+    menu_mode: bool,
+    last_source_buttons: u32,
+    /// Range the base reports when it differs from the configured one
+    base_range: Option<u16>,
+    /// Rim the base reports, picks the button mapping
+    rim: Option<Rim>,
+    assists: AssistState,
+    combos: ComboState,
+    axis_mapper: AxisMapper,
 }
 
 impl InputTranslator {
     pub fn new(config: &InputConfig) -> Self {
+        let mut config = config.clone();
+        config.pedal_curves = config.pedal_curves.rendered();
         Self {
-            config: config.clone(),
-            last_steering: 0,
+            last_steering: 0x8000,
+            button_timer: ButtonTimer::new(config.button_timing),
+            menu_mode: false,
+            last_source_buttons: 0,
+            base_range: None,
+            rim: None,
+            assists: AssistState::new(config.assists),
+            combos: ComboState::new(&config.combos),
+            axis_mapper: AxisMapper::new(&config),
+            config,
         }
     }
 
     /// Translate Thrustmaster input report to G29 format
     pub fn translate(&mut self, input: ThrustmasterInputReport) -> G29InputReport {
+        self.translate_at(input, Instant::now())
+    }
+
+    /// Translate a report read at `now`, for replaying recorded sessions on their own clock
+    pub fn translate_at(&mut self, input: ThrustmasterInputReport, now: Instant) -> G29InputReport {
+        // Stretch the recorded raw travel to the full axes first, then apply the per-axis fixes
+        let input = self.config.calibration.apply(input);
+        let input = self.config.axis_tuning.apply(input);
+        
         // Apply steering deadzone and scaling
-        let steering = self.process_steering(input.steering);
+        let mut steering = self.process_steering(input.steering);
         
         // Apply pedal curves and scaling
         let throttle = self.apply_pedal_curve(input.throttle, &self.config.pedal_curves.throttle_curve);
         let brake = self.apply_pedal_curve(input.brake, &self.config.pedal_curves.brake_curve);
         let clutch = self.apply_pedal_curve(input.clutch, &self.config.pedal_curves.clutch_curve);
+        let source_buttons = self.update_menu_mode(input.buttons);
         
-        // Map buttons
-        let buttons = self.map_buttons(input.buttons);
+        // Accessibility assists act on the player's input
+        steering = self.assists.limit_steering(steering, self.config.output_range(), now);
+        let (mut throttle, mut brake, source_buttons) = self.assists.pedals(throttle, brake, source_buttons);
         
-        // Include D-pad in button field (G29 style)
-        let buttons_with_dpad = self.include_dpad(buttons, input.dpad);
-
-        G29InputReport {
+        // In menu mode the D-pad drives steering and pedals instead of the hat
+        let mut dpad = input.dpad;
+        if self.menu_mode {
+            let nudge = self.config.menu_nudge;
+            let (horizontal, vertical) = input.dpad.components();
+            if horizontal != 0 {
+                steering = NormalizedAxis::saturating(horizontal as f32 * nudge.steering_step).to_g29_steering();
+            }
+            let tap = NormalizedAxis::saturating(nudge.pedal_tap).to_g29_pedal();
+            if vertical > 0 {
+                throttle = throttle.max(tap);
+            } else if vertical < 0 {
+                brake = brake.max(tap);
+            }
+            dpad = HatDirection::Centered;
+        }
+        
+        // Buttons driving axes, then axes pressing buttons
+        let mut axes = Axes { steering, throttle, brake, clutch };
+        self.axis_mapper.drive_axes(source_buttons, &mut axes);
+        let axis_buttons = self.axis_mapper.axis_buttons(&axes);
+        let Axes { steering, throttle, brake, clutch } = axes;
+        
+        // Resolve chords and long presses, map the rest and stretch transitions too short for the game to notice
+        let (source_buttons, combo_buttons) = self.combos.apply(source_buttons, now);
+        let buttons = self.map_buttons(source_buttons) | combo_buttons | axis_buttons;
+        let buttons = self.button_timer.apply(buttons, now);
+        
+        let mut report = G29InputReport {
             report_id: 0x01,
-            steering: steering as u16,
-            throttle: throttle as u16,
-            brake: brake as u16,
-            clutch: clutch as u16,
-            buttons: buttons_with_dpad,
+            steering,
+            throttle,
+            brake,
+            clutch,
+            buttons,
             unused: [0; 4],
-        }
+        };
+        // D-pad goes into the hat nibble of the button field (G29 style)
+        report.set_hat(dpad);
+        report
     }
 
-    fn process_steering(&mut self, raw_steering: i16) -> i16 {
-        // Apply deadzone
-        let normalized = raw_steering as f32 / 32767.0;
-        
-        let processed = if normalized.abs() < self.config.steering_deadzone {
-            0.0
-        } else {
-            // Remove deadzone and rescale
-            if normalized > 0.0 {
-                (normalized - self.config.steering_deadzone) / (1.0 - self.config.steering_deadzone)
-            } else {
-                (normalized + self.config.steering_deadzone) / (1.0 - self.config.steering_deadzone)
-            }
+    /// Whether D-pad menu navigation is active
+    pub fn is_menu_mode(&self) -> bool {
+        self.menu_mode
+    }
+
+    /// Flip menu mode on a press of the toggle button, which never reaches the game
+    fn update_menu_mode(&mut self, buttons: u32) -> u32 {
+        let Some(button) = self.config.menu_nudge.toggle_button.filter(|&b| b < SOURCE_BUTTON_COUNT) else {
+            return buttons;
         };
+        let mask = 1u32 << button;
+        if buttons & mask != 0 && self.last_source_buttons & mask == 0 {
+            self.menu_mode = !self.menu_mode;
+            tracing::info!("Menu navigation mode {}", if self.menu_mode { "on" } else { "off" });
+        }
+        self.last_source_buttons = buttons;
+        buttons & !mask
+    }
+
+    /// When a button held over by `[input_config.button_timing]` is due for release
+    pub fn next_button_release(&self) -> Option<Instant> {
+        self.button_timer.next_release()
+    }
+
+    /// Change steering center offset and per-side scaling while running
+    pub fn set_steering_geometry(&mut self, center_offset: f32, geometry: SteeringGeometry) {
+        self.config.axis_tuning.steering.center_offset = center_offset;
+        self.config.steering_geometry = geometry;
+    }
+
+    /// Follow a rotation range changed on the base itself
+    ///
+    /// The steering axis then spans `degrees` instead of the configured
+    /// range, so it is rescaled to keep the game's wheel turning as far as
+    /// the physical one.
+    pub fn set_base_range(&mut self, degrees: u16) {
+        self.base_range = (degrees != self.config.steering_range).then_some(degrees);
+    }
+
+    /// Follow a rim swapped on the base, switching to its button mapping if it has one
+    pub fn set_rim(&mut self, rim: Rim) {
+        self.rim = Some(rim);
+    }
+
+    /// Button mapping for the attached rim, the general one unless the rim has its own
+    pub fn button_mapping(&self) -> &HashMap<u8, u8> {
+        self.rim
+            .and_then(|rim| self.config.rim_button_mappings.get(&rim.key()))
+            .unwrap_or(&self.config.button_mapping)
+    }
+
+    /// Rotation range the steering axis is scaled for
+    pub fn steering_range(&self) -> u16 {
+        self.base_range.unwrap_or(self.config.steering_range)
+    }
+
+    /// Follow a rotation range the game set on its G29, as `g29_range` would
+    pub fn set_g29_range(&mut self, degrees: u16) {
+        self.config.g29_range = Some(degrees);
+    }
+
+    /// Rotation range of the game's G29 the steering axis spans
+    pub fn output_range(&self) -> u16 {
+        self.config.output_range()
+    }
+
+    /// Treat the current raw wheel position as the steering center
+    pub fn recenter(&mut self, raw_steering: i16) {
+        let tune = &mut self.config.axis_tuning.steering;
+        let position = NormalizedAxis::from_signed_steering(raw_steering).value();
+        tune.center_offset = if tune.invert { -position } else { position };
+    }
+
+    fn process_steering(&mut self, raw_steering: i16) -> u16 {
+        // Invert, re-center, scale each side, then deadzone and saturation
+        let processed = self
+            .config
+            .axis_tuning
+            .steering
+            .apply_steering(NormalizedAxis::from_signed_steering(raw_steering).value(), &self.config.steering_geometry);
+
+        // Apply scaling and convert to G29 format (center = 0x8000); the axis
+        // spans the base's range in, the G29's out, keeping the angle on screen
+        let range_scale = self.steering_range() as f32 / self.config.output_range().max(1) as f32;
+        let scaled = NormalizedAxis::saturating(processed * self.config.axis_scaling.steering_multiplier * range_scale);
+        let result = scaled.to_g29_steering();
 
-        // Apply scaling and convert to G29 format (center = 0x8000)
-        let scaled = processed * self.config.axis_scaling.steering_multiplier;
-        let g29_value = (scaled * 32767.0) as i16;
-        
-        // G29 uses 0x8000 as center, so offset by 32768
-        let result = (g29_value as i32 + 32768).clamp(0, 65535) as i16;
-        
         self.last_steering = result;
         result
     }
 
-    fn apply_pedal_curve(&self, raw_value: u8, curve: &CurveType) -> u32 {
-        let normalized = raw_value as f32 / 255.0;
-        
-        let curved = match curve {
-            CurveType::Linear => normalized,
-            CurveType::Squared => normalized * normalized,
-            CurveType::Cubed => normalized * normalized * normalized,
-            CurveType::Custom(table) => {
-                // Linear interpolation in lookup table
-                let index = (normalized * (table.len() - 1) as f32) as usize;
-                if index >= table.len() - 1 {
-                    table[table.len() - 1]
-                } else {
-                    let frac = normalized * (table.len() - 1) as f32 - index as f32;
-                    table[index] * (1.0 - frac) + table[index + 1] * frac
-                }
-            }
-        };
+    fn apply_pedal_curve(&self, raw_value: u8, curve: &CurveType) -> u16 {
+        let normalized = NormalizedAxis::from_pedal_u8(raw_value).value();
 
         // G29 uses 10-bit resolution for pedals (0-1023)
-        (curved * 1023.0) as u32
+        NormalizedAxis::saturating(curve.sample(normalized)).to_g29_pedal()
     }
 
-    fn map_buttons(&self, buttons: u16) -> u32 {
+    fn map_buttons(&self, buttons: u32) -> u32 {
         let mut mapped = 0u32;
         
-        for (&thrustmaster_btn, &g29_btn) in &self.config.button_mapping {
+        for (&thrustmaster_btn, &g29_btn) in self.button_mapping() {
+            // Out of range entries are reported by validation, never shifted into the hat bits
+            if thrustmaster_btn >= SOURCE_BUTTON_COUNT || g29_btn >= G29_BUTTON_COUNT {
+                continue;
+            }
             if buttons & (1 << thrustmaster_btn) != 0 {
                 mapped |= 1 << g29_btn;
             }
@@ -107,32 +233,207 @@ impl InputTranslator {
         
         mapped
     }
+}
+
+/// Enforces minimum press and release delay on G29 button transitions
+pub struct ButtonTimer {
+    min_press: Duration,
+    release_delay: Duration,
+    pressed_since: [Option<Instant>; 24],
+    release_at: [Option<Instant>; 24],
+}
 
-    fn include_dpad(&self, buttons: u32, dpad: u8) -> u32 {
-        // G29 D-pad is encoded in the upper bits of the button field
-        let dpad_value = if dpad < 8 { dpad } else { 8 }; // 8 = center
-        buttons | ((dpad_value as u32) << 24)
+impl ButtonTimer {
+    pub fn new(timing: ButtonTiming) -> Self {
+        Self {
+            min_press: Duration::from_millis(timing.min_press_ms as u64),
+            release_delay: Duration::from_millis(timing.release_delay_ms as u64),
+            pressed_since: [None; 24],
+            release_at: [None; 24],
+        }
+    }
+
+    /// Button bits the game should see at `now` for the physical state `buttons`
+    ///
+    /// A released button stays pressed until it has been held for the minimum
+    /// press duration and the release delay has passed.
+    pub fn apply(&mut self, buttons: u32, now: Instant) -> u32 {
+        if self.min_press.is_zero() && self.release_delay.is_zero() {
+            return buttons;
+        }
+
+        let mut output = 0u32;
+        for index in 0..24 {
+            let bit = 1u32 << index;
+            if buttons & bit != 0 {
+                self.pressed_since[index].get_or_insert(now);
+                self.release_at[index] = None;
+                output |= bit;
+            } else if let Some(since) = self.pressed_since[index] {
+                let deadline = *self.release_at[index]
+                    .get_or_insert_with(|| (since + self.min_press).max(now + self.release_delay));
+                if now < deadline {
+                    output |= bit;
+                } else {
+                    self.pressed_since[index] = None;
+                    self.release_at[index] = None;
+                }
+            }
+        }
+        output
+    }
+
+    /// When the next held-over button is due for release, `None` when none is held
+    ///
+    /// Nothing changes on the wheel at that moment, so the caller has to run
+    /// `apply` again then for the release to reach the game.
+    pub fn next_release(&self) -> Option<Instant> {
+        self.release_at.iter().flatten().min().copied()
     }
 }
 
 /// Handles output translation from G29 to Thrustmaster IFORCE format
 pub struct OutputTranslator {
     config: OutputConfig,
+    fingerprinter: FfbFingerprinter,
+    /// Quirks table entry identified for this session
+    quirks: Option<FfbQuirks>,
+    last_report: Option<Vec<u8>>,
+    /// Set Envelope parameters waiting for their block's Set Effect
+    envelopes: HashMap<u8, Envelope>,
 }
 
 impl OutputTranslator {
     pub fn new(config: &OutputConfig) -> Self {
         Self {
             config: config.clone(),
+            fingerprinter: FfbFingerprinter::default(),
+            quirks: None,
+            last_report: None,
+            envelopes: HashMap::new(),
+        }
+    }
+
+    /// Switch to `config`, keeping the session's quirks and pending envelopes
+    pub fn set_config(&mut self, config: &OutputConfig) {
+        self.config = config.clone();
+    }
+
+    /// Quirks applied to this session's FFB reports, once identified
+    pub fn quirks(&self) -> Option<&FfbQuirks> {
+        self.quirks.as_ref()
+    }
+
+    /// LED mask of a G29 rev-strip command, `None` for other reports or with `led_support` off
+    pub fn parse_leds(&self, output: &G29OutputReport) -> Option<u8> {
+        if !self.config.led_support {
+            return None;
+        }
+        match extended_command(output)? {
+            [G29_SET_LEDS, mask, ..] => Some(mask & ALL_LEDS),
+            _ => None,
+        }
+    }
+
+    /// Degrees of a G29 rotation range command, `None` for other reports or with `game_range` off
+    ///
+    /// Like hid-lg4ff, 0 asks for the full range and anything else is
+    /// clamped to what a G29 turns.
+    pub fn parse_range(&self, output: &G29OutputReport) -> Option<u16> {
+        if !self.config.game_range {
+            return None;
+        }
+        match extended_command(output)? {
+            [G29_SET_RANGE, low, high, ..] => match u16::from_le_bytes([*low, *high]) {
+                0 => Some(*G29_RANGE.end()),
+                degrees => Some(degrees.clamp(*G29_RANGE.start(), *G29_RANGE.end())),
+            },
+            _ => None,
+        }
+    }
+
+    /// Parse a PID lifecycle report (Create New Effect, Effect Operation,
+    /// Block Free, Device Control or Device Gain), `None` for other reports
+    pub fn parse_pid_report(&mut self, output: &G29OutputReport) -> Result<Option<PidCommand>> {
+        let id = output.report_id;
+        if !matches!(
+            id,
+            CREATE_NEW_EFFECT_REPORT_ID
+                | EFFECT_OPERATION_REPORT_ID
+                | BLOCK_FREE_REPORT_ID
+                | DEVICE_CONTROL_REPORT_ID
+                | DEVICE_GAIN_REPORT_ID
+        ) {
+            return Ok(None);
+        }
+
+        let needed = if id == EFFECT_OPERATION_REPORT_ID { 3 } else { 1 };
+        if output.data.len() < needed {
+            return Err(TranslatorError::invalid_report(format!(
+                "PID report {:#04x} too short: {} bytes",
+                id,
+                output.data.len()
+            )));
         }
+
+        let data = &output.data;
+        let zero_based = self.quirks.as_ref().is_some_and(|quirks| quirks.zero_based_slots);
+        let block = if zero_based { data[0].saturating_add(1) } else { data[0] };
+        let command = match id {
+            CREATE_NEW_EFFECT_REPORT_ID => PidCommand::Create { effect_type: data[0] },
+            EFFECT_OPERATION_REPORT_ID => match data[1] {
+                1 => PidCommand::Start { block, solo: false, loop_count: data[2] },
+                2 => PidCommand::Start { block, solo: true, loop_count: data[2] },
+                3 => PidCommand::Stop { block },
+                op => return Err(TranslatorError::ffb_error(format!("Unknown effect operation {}", op))),
+            },
+            BLOCK_FREE_REPORT_ID => {
+                self.envelopes.remove(&block);
+                PidCommand::Free { block }
+            }
+            DEVICE_CONTROL_REPORT_ID => match DeviceControl::from_raw(data[0]) {
+                Some(control) => {
+                    if control == DeviceControl::Reset {
+                        self.envelopes.clear();
+                    }
+                    PidCommand::Control(control)
+                }
+                None => return Err(TranslatorError::ffb_error(format!("Unknown device control {}", data[0]))),
+            },
+            _ => PidCommand::Gain(data[0]),
+        };
+        Ok(Some(command))
     }
 
     /// Parse G29 output report and extract FFB effect if present
-    pub fn parse_ffb_effect(&self, output: G29OutputReport) -> Result<Option<FfbEffect>> {
+    pub fn parse_ffb_effect(&mut self, mut output: G29OutputReport) -> Result<Option<FfbEffect>> {
+        if output.report_id == SET_ENVELOPE_REPORT_ID {
+            self.parse_envelope(&output)?;
+            return Ok(None);
+        }
         if output.report_id != 0x01 || output.data.is_empty() {
             return Ok(None);
         }
 
+        if let Some(fingerprint) = self.fingerprinter.observe(&output) {
+            self.quirks = fingerprint.identify(&self.config.ffb_quirks).cloned();
+            match &self.quirks {
+                Some(quirks) => tracing::info!("FFB pattern matches \"{}\", applying its quirks", quirks.name),
+                None => tracing::debug!("FFB pattern {:?} needs no quirks", fingerprint),
+            }
+        }
+        if let Some(quirks) = &self.quirks {
+            if quirks.drop_duplicates {
+                if self.last_report.as_deref() == Some(output.data.as_slice()) {
+                    return Ok(None);
+                }
+                self.last_report = Some(output.data.clone());
+            }
+            if quirks.zero_based_slots {
+                output.data[0] = output.data[0].saturating_add(1);
+            }
+        }
+
         // Parse PID Device Control report (simplified)
         match output.data[0] {
             // Effect Block Index
@@ -149,6 +450,25 @@ impl OutputTranslator {
         }
     }
 
+    /// Keep a Set Envelope report for the next Set Effect of its block
+    fn parse_envelope(&mut self, output: &G29OutputReport) -> Result<()> {
+        let data = &output.data;
+        if data.len() < 9 {
+            return Err(TranslatorError::invalid_report(format!("Set Envelope report too short: {} bytes", data.len())));
+        }
+
+        let zero_based = self.quirks.as_ref().is_some_and(|quirks| quirks.zero_based_slots);
+        let block = if zero_based { data[0].saturating_add(1) } else { data[0] };
+        let envelope = Envelope {
+            attack_level: u16::from_le_bytes([data[1], data[2]]),
+            fade_level: u16::from_le_bytes([data[3], data[4]]),
+            attack_time: u16::from_le_bytes([data[5], data[6]]),
+            fade_time: u16::from_le_bytes([data[7], data[8]]),
+        };
+        self.envelopes.insert(block, envelope);
+        Ok(())
+    }
+
     fn parse_effect_by_type(&self, effect_id: u8, effect_type: u8, data: &[u8]) -> Result<FfbEffect> {
         use crate::ffb::{FfbEffect, EffectType, ConstantEffect, PeriodicEffect, ConditionEffect};
 
@@ -159,13 +479,14 @@ impl OutputTranslator {
                 }
                 
                 let magnitude = i16::from_le_bytes([data[0], data[1]]);
-                let duration = u16::from_le_bytes([data[2], data[3]]);
+                let duration = Milliseconds::from_report(u16::from_le_bytes([data[2], data[3]]));
                 
                 Ok(FfbEffect {
                     id: effect_id,
                     effect_type: EffectType::Constant(ConstantEffect {
                         magnitude,
                         duration,
+                        envelope: self.envelopes.get(&effect_id).copied(),
                     }),
                     gain: 255, // Will be adjusted by FFB engine
                 })
@@ -193,6 +514,7 @@ impl OutputTranslator {
                             0x07 => crate::ffb::Waveform::SawtoothDown,
                             _ => crate::ffb::Waveform::Sine,
                         },
+                        envelope: self.envelopes.get(&effect_id).copied(),
                     }),
                     gain: 255,
                 })
@@ -226,4 +548,111 @@ impl OutputTranslator {
             }
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// Bytes of a Logitech extended command after `0xF8`, `None` for other reports
+fn extended_command(output: &G29OutputReport) -> Option<&[u8]> {
+    // Games writing without a report ID put the command byte first
+    match (output.report_id, output.data.as_slice()) {
+        (G29_EXTENDED_COMMAND, command) => Some(command),
+        (0x00, [G29_EXTENDED_COMMAND, command @ ..]) => Some(command),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steering_spans_full_range_and_remaps_rotation() {
+        let mut config = InputConfig::default();
+        config.axis_tuning.steering.deadzone = 0.0;
+        let mut translator = InputTranslator::new(&config);
+        assert_eq!(translator.process_steering(i16::MIN), 0);
+        assert_eq!(translator.process_steering(-i16::MAX), 0);
+        assert_eq!(translator.process_steering(0), 0x8000);
+        assert_eq!(translator.process_steering(i16::MAX), u16::MAX);
+
+        // A 1080° wheel under a 900° G29 locks at 450° either way
+        config.steering_range = 1080;
+        config.g29_range = Some(900);
+        let mut translator = InputTranslator::new(&config);
+        let at_450 = (i16::MAX as f32 * 450.0 / 540.0).round() as i16;
+        assert_eq!(translator.process_steering(at_450), u16::MAX);
+        assert_eq!(translator.process_steering(-at_450), 0);
+        assert_eq!(translator.process_steering(i16::MAX), u16::MAX);
+        let half = translator.process_steering(at_450 / 2);
+        assert!(half.abs_diff(0xC000) <= 2, "{:#x}", half);
+    }
+
+    #[test]
+    fn test_button_timer_stretches_short_presses() {
+        let start = Instant::now();
+        let ms = |ms: u64| start + Duration::from_millis(ms);
+        let mut timer = ButtonTimer::new(ButtonTiming { min_press_ms: 50, release_delay_ms: 20 });
+
+        // A 10 ms tap is held until the minimum press has passed
+        assert_eq!(timer.apply(0b1, ms(0)), 0b1);
+        assert_eq!(timer.next_release(), None);
+        assert_eq!(timer.apply(0, ms(10)), 0b1);
+        assert_eq!(timer.next_release(), Some(ms(50)));
+        assert_eq!(timer.apply(0, ms(49)), 0b1);
+        assert_eq!(timer.apply(0, ms(50)), 0);
+        assert_eq!(timer.next_release(), None);
+
+        // A long press is released after the release delay
+        timer.apply(0b10, ms(100));
+        assert_eq!(timer.apply(0, ms(200)), 0b10);
+        assert_eq!(timer.next_release(), Some(ms(220)));
+        // Pressed again before then, it stays down without a deadline
+        assert_eq!(timer.apply(0b10, ms(210)), 0b10);
+        assert_eq!(timer.next_release(), None);
+
+        let mut off = ButtonTimer::new(ButtonTiming { min_press_ms: 0, release_delay_ms: 0 });
+        assert_eq!(off.apply(0b1, ms(0)), 0b1);
+        assert_eq!(off.apply(0, ms(1)), 0);
+    }
+
+    #[test]
+    fn test_steering_geometry_offsets_and_scales_each_side() {
+        let mut config = InputConfig::default();
+        config.axis_tuning.steering.deadzone = 0.0;
+        config.axis_tuning.steering.center_offset = 0.1;
+        config.steering_geometry = SteeringGeometry { left_scale: 2.0, right_scale: 0.5 };
+        let mut translator = InputTranslator::new(&config);
+        let raw = |value: f32| (value * i16::MAX as f32).round() as i16;
+        let g29 = |value: f32| NormalizedAxis::saturating(value).to_g29_steering();
+
+        // The offset position reads as center, each side keeps its own gain
+        assert!(translator.process_steering(raw(0.1)).abs_diff(0x8000) <= 2);
+        assert!(translator.process_steering(raw(-0.15)).abs_diff(g29(-0.5)) <= 2);
+        assert!(translator.process_steering(raw(0.9)).abs_diff(g29(0.4)) <= 2);
+        // The doubled left side saturates before the lock
+        assert_eq!(translator.process_steering(raw(-0.8)), 0);
+
+        assert!(config.check_steering().is_ok());
+        for (offset, left, right) in [(0.0, 0.0, 1.0), (0.0, 1.0, -1.0), (0.0, f32::NAN, 1.0), (1.5, 1.0, 1.0)] {
+            config.axis_tuning.steering.center_offset = offset;
+            config.steering_geometry = SteeringGeometry { left_scale: left, right_scale: right };
+            assert!(config.check_steering().is_err(), "{} {} {}", offset, left, right);
+        }
+    }
+
+    #[test]
+    fn test_game_range_commands_are_parsed() {
+        let mut translator = OutputTranslator::new(&OutputConfig::default());
+        assert_eq!(translator.parse_range(&G29OutputReport::range(540)), Some(540));
+        // Without a report ID the command byte comes first
+        let raw = G29OutputReport { report_id: 0x00, data: vec![0xF8, 0x81, 0x84, 0x03, 0x00, 0x00, 0x00] };
+        assert_eq!(translator.parse_range(&raw), Some(900));
+        assert_eq!(translator.parse_range(&G29OutputReport::leds(0x1F)), None);
+        // Out of range requests are clamped as hid-lg4ff does, 0 is the full range
+        assert_eq!(translator.parse_range(&G29OutputReport::range(0)), Some(900));
+        assert_eq!(translator.parse_range(&G29OutputReport::range(1080)), Some(900));
+        assert_eq!(translator.parse_range(&G29OutputReport::range(10)), Some(40));
+
+        translator.set_config(&OutputConfig { game_range: false, ..OutputConfig::default() });
+        assert_eq!(translator.parse_range(&raw), None);
+    }
+}
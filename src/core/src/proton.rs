@@ -0,0 +1,247 @@
+//! Proton prefix tweaks for the virtual wheel
+//!
+//! Games running under Proton see input devices through Wine, which keeps its
+//! own registry per game prefix. Some titles only list the wheel once Wine's
+//! DirectInput has it explicitly enabled there. This module finds the Proton
+//! prefixes of installed Steam games and adds the missing entries directly to
+//! the prefix registry files (`user.reg`, `system.reg`), keeping a backup of
+//! each file it changes. Wine must not be running in a prefix while it is
+//! edited, or it overwrites the change on exit.
+
+use crate::config::G29Config;
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+
+/// Registry file of a Wine prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hive {
+    User,    // HKEY_CURRENT_USER, user.reg
+    System,  // HKEY_LOCAL_MACHINE, system.reg
+}
+
+impl Hive {
+    fn file_name(self) -> &'static str {
+        match self {
+            Hive::User => "user.reg",
+            Hive::System => "system.reg",
+        }
+    }
+}
+
+/// A string value the virtual wheel needs in a prefix registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryTweak {
+    pub hive: Hive,
+    /// Key path below the hive, single backslashes
+    pub key: String,
+    pub name: String,
+    pub value: String,
+    /// Why the tweak is needed, shown to the user
+    pub reason: &'static str,
+}
+
+/// Tweaks known to be needed for the virtual G29 described by `config`
+pub fn known_tweaks(config: &G29Config) -> Vec<RegistryTweak> {
+    vec![RegistryTweak {
+        hive: Hive::User,
+        key: r"Software\Wine\DirectInput\Joysticks".to_string(),
+        name: config.product_name().to_string(),
+        value: "enabled".to_string(),
+        reason: "lets DirectInput games list the virtual wheel",
+    }]
+}
+
+/// Wine prefix Proton created for one Steam game
+#[derive(Debug, Clone)]
+pub struct ProtonPrefix {
+    pub app_id: String,
+    /// Game name from the Steam app manifest, if installed in the same library
+    pub name: Option<String>,
+    /// The `pfx` directory holding the registry files
+    pub path: PathBuf,
+}
+
+impl ProtonPrefix {
+    /// Tweaks from `tweaks` that are not yet in this prefix
+    pub fn missing<'a>(&self, tweaks: &'a [RegistryTweak]) -> Vec<&'a RegistryTweak> {
+        tweaks
+            .iter()
+            .filter(|tweak| {
+                let content = std::fs::read_to_string(self.path.join(tweak.hive.file_name())).unwrap_or_default();
+                reg_value(&content, &tweak.key, &tweak.name).as_deref() != Some(tweak.value.as_str())
+            })
+            .collect()
+    }
+
+    /// Write `tweaks` into the prefix registry, backing up each changed file as `<file>.tm-g29.bak`
+    pub fn apply(&self, tweaks: &[&RegistryTweak]) -> Result<()> {
+        for hive in [Hive::User, Hive::System] {
+            let hive_tweaks: Vec<_> = tweaks.iter().filter(|tweak| tweak.hive == hive).collect();
+            if hive_tweaks.is_empty() {
+                continue;
+            }
+
+            let path = self.path.join(hive.file_name());
+            let original = std::fs::read_to_string(&path)?;
+            let mut content = original.clone();
+            for tweak in hive_tweaks {
+                content = set_reg_value(&content, &tweak.key, &tweak.name, &tweak.value);
+            }
+            if content != original {
+                std::fs::write(path.with_extension("reg.tm-g29.bak"), &original)?;
+                crate::persist::write_atomic(&path, content.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Steam installation directories of the current user
+pub fn steam_roots() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+        return Vec::new();
+    };
+    [
+        home.join(".steam").join("steam"),
+        home.join(".local").join("share").join("Steam"),
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+    ]
+    .into_iter()
+    .filter(|root| root.join("steamapps").is_dir())
+    .collect()
+}
+
+/// Library folders of a Steam installation, the root itself included
+fn library_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![root.to_path_buf()];
+    let vdf = std::fs::read_to_string(root.join("steamapps").join("libraryfolders.vdf")).unwrap_or_default();
+    for line in vdf.lines() {
+        if let Some(path) = vdf_field(line, "path") {
+            libraries.push(PathBuf::from(path));
+        }
+    }
+    libraries
+}
+
+/// Value of a `"key"  "value"` line in a Valve KeyValues file
+fn vdf_field(line: &str, key: &str) -> Option<String> {
+    let mut parts = line.trim().split('"').filter(|part| !part.trim().is_empty());
+    if parts.next()? != key {
+        return None;
+    }
+    parts.next().map(|value| value.replace(r"\\", r"\"))
+}
+
+/// Every Proton prefix in every Steam library, each listed once
+pub fn find_prefixes() -> Vec<ProtonPrefix> {
+    let mut prefixes: Vec<ProtonPrefix> = Vec::new();
+
+    for library in steam_roots().iter().flat_map(|root| library_dirs(root)) {
+        let steamapps = library.join("steamapps");
+        let Ok(entries) = std::fs::read_dir(steamapps.join("compatdata")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path().join("pfx");
+            if !path.join(Hive::User.file_name()).is_file() {
+                continue;
+            }
+            let Ok(path) = path.canonicalize() else {
+                continue;
+            };
+            if prefixes.iter().any(|prefix| prefix.path == path) {
+                continue;
+            }
+            let app_id = entry.file_name().to_string_lossy().into_owned();
+            let name = std::fs::read_to_string(steamapps.join(format!("appmanifest_{}.acf", app_id)))
+                .ok()
+                .and_then(|manifest| manifest.lines().find_map(|line| vdf_field(line, "name")));
+            prefixes.push(ProtonPrefix { app_id, name, path });
+        }
+    }
+
+    prefixes.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.app_id.cmp(&b.app_id)));
+    prefixes
+}
+
+/// Section header of `key` in a Wine registry file, without the timestamp
+fn reg_section(key: &str) -> String {
+    format!("[{}]", key.replace('\\', r"\\"))
+}
+
+/// Name or string data escaped the way Wine writes it
+fn reg_escape(text: &str) -> String {
+    text.replace('\\', r"\\").replace('"', "\\\"")
+}
+
+/// String value `name` under `key` in Wine registry file `content`
+fn reg_value(content: &str, key: &str, name: &str) -> Option<String> {
+    let header = reg_section(key);
+    let prefix = format!("\"{}\"=\"", reg_escape(name));
+    content
+        .lines()
+        .skip_while(|line| !line.starts_with(&header))
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| line.strip_prefix(&prefix)?.strip_suffix('"').map(str::to_string))
+}
+
+/// `content` with string value `name` under `key` set to `value`, adding the key if needed
+fn set_reg_value(content: &str, key: &str, name: &str, value: &str) -> String {
+    let header = reg_section(key);
+    let name_prefix = format!("\"{}\"=", reg_escape(name));
+    let line = format!("{}\"{}\"", name_prefix, reg_escape(value));
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    match lines.iter().position(|l| l.starts_with(&header)) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| l.starts_with('['))
+                .map_or(lines.len(), |offset| start + 1 + offset);
+            match lines[start + 1..end].iter().position(|l| l.starts_with(&name_prefix)) {
+                Some(offset) => lines[start + 1 + offset] = line,
+                None => lines.insert(start + 1, line),
+            }
+        }
+        None => {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            if lines.last().map_or(false, |l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("{} {}", header, timestamp));
+            lines.push(line);
+        }
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = r"Software\Wine\DirectInput\Joysticks";
+
+    #[test]
+    fn test_reg_values_are_added_and_replaced() {
+        let content = "WINE REGISTRY Version 2\n\n[Software\\\\Wine] 1700000000\n\"Version\"=\"win10\"\n";
+        let added = set_reg_value(content, KEY, "G29 Driving Force Racing Wheel", "enabled");
+        assert_eq!(reg_value(&added, KEY, "G29 Driving Force Racing Wheel").as_deref(), Some("enabled"));
+        assert_eq!(reg_value(&added, r"Software\Wine", "Version").as_deref(), Some("win10"));
+
+        let replaced = set_reg_value(&added.replace("enabled", "disabled"), KEY, "G29 Driving Force Racing Wheel", "enabled");
+        assert_eq!(replaced.matches("G29 Driving Force Racing Wheel").count(), 1);
+        assert_eq!(reg_value(&replaced, KEY, "G29 Driving Force Racing Wheel").as_deref(), Some("enabled"));
+    }
+
+    #[test]
+    fn test_vdf_fields_are_unescaped() {
+        assert_eq!(vdf_field("\t\t\"path\"\t\t\"/mnt/games/Steam\"", "path").as_deref(), Some("/mnt/games/Steam"));
+        assert_eq!(vdf_field("\t\"name\"\t\"Assetto Corsa\"", "path"), None);
+    }
+}
@@ -0,0 +1,544 @@
+//! Recording of translation sessions for debugging and bug reports
+//!
+//! `SessionRecorder` keeps the G29 reports and force outputs produced during a
+//! session with their timestamps. A `Recording` can be saved as a plain-text
+//! trace for offline analysis, or exported as an evemu file, which
+//! `evemu-device` + `evemu-play` replay on any Linux machine as a virtual
+//! wheel so a game developer can reproduce a report without the hardware.
+//! Everything, including the wheel reports as read and the raw FFB output
+//! reports a game sent, can be saved as a binary capture (see `capture`) or
+//! as JSON lines. `tm-g29 replay` feeds a capture back through the
+//! translation stages, so a user's session can be debugged without their
+//! wheel.
+
+use crate::config::G29Config;
+use crate::device::{G29InputReport, G29OutputReport, ThrustmasterInputReport};
+use crate::error::{TranslatorError, Result};
+use crate::evdev::{self, EV_ABS, EV_KEY, EV_SYN, G29_ABS_AXES};
+use serde_json::json;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default cap on recorded reports, about 10 minutes at 1 kHz
+pub const DEFAULT_MAX_INPUTS: usize = 600_000;
+
+/// First line of a trace file
+pub const TRACE_HEADER: &str = "# tm-g29 trace v1";
+
+const KEY_MAX: usize = 0x2ff;
+const ABS_MAX: usize = 0x3f;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedInput {
+    pub timestamp: Duration,
+    pub report: G29InputReport,
+}
+
+/// Report as read from the wheel, before translation
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedWheel {
+    pub timestamp: Duration,
+    pub report: ThrustmasterInputReport,
+}
+
+/// Constant force magnitude requested by the game at a point in time
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedForce {
+    pub timestamp: Duration,
+    pub magnitude: i16,
+}
+
+/// Raw output report a game sent to the virtual G29
+#[derive(Debug, Clone)]
+pub struct RecordedOutput {
+    pub timestamp: Duration,
+    pub report: G29OutputReport,
+}
+
+/// Runtime action applied during the session, e.g. "Recenter"
+#[derive(Debug, Clone)]
+pub struct RecordedEvent {
+    pub timestamp: Duration,
+    pub name: String,
+}
+
+/// Free-text annotation of the session
+#[derive(Debug, Clone)]
+pub struct RecordedNote {
+    pub timestamp: Duration,
+    pub text: String,
+}
+
+/// Timestamped inputs and force outputs of a session
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub wheel: Vec<RecordedWheel>,
+    pub inputs: Vec<RecordedInput>,
+    pub forces: Vec<RecordedForce>,
+    pub outputs: Vec<RecordedOutput>,
+    pub events: Vec<RecordedEvent>,
+    pub notes: Vec<RecordedNote>,
+}
+
+pub struct SessionRecorder {
+    started: Instant,
+    recording: Recording,
+    max_inputs: usize,
+    dropped: usize,
+}
+
+/// Recorder shared between the translation loops and its owner
+pub type SharedSessionRecorder = Arc<Mutex<SessionRecorder>>;
+
+impl SessionRecorder {
+    pub fn new(max_inputs: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            recording: Recording::default(),
+            max_inputs,
+            dropped: 0,
+        }
+    }
+
+    pub fn shared(max_inputs: usize) -> SharedSessionRecorder {
+        Arc::new(Mutex::new(Self::new(max_inputs)))
+    }
+
+    /// Record a translated report, dropping it once the recorder is full
+    pub fn record(&mut self, report: G29InputReport) {
+        if self.recording.inputs.len() >= self.max_inputs {
+            self.dropped += 1;
+            return;
+        }
+        self.recording.inputs.push(RecordedInput {
+            timestamp: self.started.elapsed(),
+            report,
+        });
+    }
+
+    /// Record a report read from the wheel, dropping it once the recorder is full
+    pub fn record_wheel(&mut self, report: ThrustmasterInputReport) {
+        if self.recording.wheel.len() >= self.max_inputs {
+            self.dropped += 1;
+            return;
+        }
+        self.recording.wheel.push(RecordedWheel {
+            timestamp: self.started.elapsed(),
+            report,
+        });
+    }
+
+    /// Record a force sent to the wheel, dropping it once the recorder is full
+    pub fn record_force(&mut self, magnitude: i16) {
+        if self.recording.forces.len() >= self.max_inputs {
+            self.dropped += 1;
+            return;
+        }
+        self.recording.forces.push(RecordedForce {
+            timestamp: self.started.elapsed(),
+            magnitude,
+        });
+    }
+
+    /// Record a raw output report from the game, dropping it once the recorder is full
+    pub fn record_output(&mut self, report: &G29OutputReport) {
+        if self.recording.outputs.len() >= self.max_inputs {
+            self.dropped += 1;
+            return;
+        }
+        self.recording.outputs.push(RecordedOutput {
+            timestamp: self.started.elapsed(),
+            report: report.clone(),
+        });
+    }
+
+    /// Record a runtime action, events are rare so they are never dropped
+    pub fn record_event(&mut self, name: impl Into<String>) {
+        self.recording.events.push(RecordedEvent {
+            timestamp: self.started.elapsed(),
+            name: name.into(),
+        });
+    }
+
+    /// Annotate the session at the current time
+    pub fn record_note(&mut self, text: impl Into<String>) {
+        self.recording.notes.push(RecordedNote {
+            timestamp: self.started.elapsed(),
+            text: text.into(),
+        });
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Number of samples discarded because the recorder was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl Recording {
+    /// Duration from the start of recording to the last sample
+    pub fn duration(&self) -> Duration {
+        let last_wheel = self.wheel.last().map(|w| w.timestamp);
+        let last_input = self.inputs.last().map(|i| i.timestamp);
+        let last_force = self.forces.last().map(|f| f.timestamp);
+        let last_output = self.outputs.last().map(|o| o.timestamp);
+        let last_event = self.events.last().map(|e| e.timestamp);
+        let last_note = self.notes.last().map(|n| n.timestamp);
+        last_wheel.max(last_input).max(last_force).max(last_output).max(last_event).max(last_note).unwrap_or_default()
+    }
+
+    /// Write the recording as a line-based text trace
+    ///
+    /// Input lines are `I,<micros>,<steering>,<throttle>,<brake>,<clutch>,<buttons>`
+    /// and force lines `F,<micros>,<magnitude>`, in timestamp order per kind.
+    pub fn write_trace<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "{}", TRACE_HEADER)?;
+        for input in &self.inputs {
+            let r = &input.report;
+            writeln!(
+                writer,
+                "I,{},{},{},{},{},{}",
+                input.timestamp.as_micros(), r.steering, r.throttle, r.brake, r.clutch, r.buttons
+            )?;
+        }
+        for force in &self.forces {
+            writeln!(writer, "F,{},{}", force.timestamp.as_micros(), force.magnitude)?;
+        }
+        Ok(())
+    }
+
+    /// Read a trace written by `write_trace`
+    pub fn read_trace<R: BufRead>(reader: R) -> Result<Self> {
+        let mut recording = Recording::default();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad_line = || TranslatorError::invalid_report(format!("Malformed trace line {}: {}", number + 1, line));
+            let fields: Vec<&str> = line.split(',').collect();
+            let micros: u64 = fields.get(1).and_then(|v| v.parse().ok()).ok_or_else(bad_line)?;
+            let timestamp = Duration::from_micros(micros);
+
+            match (fields[0], fields.len()) {
+                ("I", 7) => {
+                    let values: Vec<u32> = fields[2..]
+                        .iter()
+                        .map(|v| v.parse())
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| bad_line())?;
+                    recording.inputs.push(RecordedInput {
+                        timestamp,
+                        report: G29InputReport {
+                            report_id: 0x01,
+                            steering: values[0] as u16,
+                            throttle: values[1] as u16,
+                            brake: values[2] as u16,
+                            clutch: values[3] as u16,
+                            buttons: values[4],
+                            unused: [0; 4],
+                        },
+                    });
+                }
+                ("F", 3) => {
+                    let magnitude = fields[2].parse().map_err(|_| bad_line())?;
+                    recording.forces.push(RecordedForce { timestamp, magnitude });
+                }
+                _ => return Err(bad_line()),
+            }
+        }
+
+        Ok(recording)
+    }
+
+    /// Write every record as one JSON object per line, in timestamp order
+    ///
+    /// Each line has `micros`, `kind` and the record itself, e.g.
+    /// `{"kind":"wheel","micros":1000,"report":{...}}`, so a session can be
+    /// read with `jq` or a few lines of Python.
+    pub fn write_jsonl<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut lines: Vec<(Duration, serde_json::Value)> = Vec::new();
+        for wheel in &self.wheel {
+            lines.push((wheel.timestamp, json!({ "kind": "wheel", "report": wheel.report })));
+        }
+        for input in &self.inputs {
+            lines.push((input.timestamp, json!({ "kind": "input", "report": input.report })));
+        }
+        for output in &self.outputs {
+            lines.push((output.timestamp, json!({ "kind": "output", "report": output.report })));
+        }
+        for force in &self.forces {
+            lines.push((force.timestamp, json!({ "kind": "force", "magnitude": force.magnitude })));
+        }
+        for event in &self.events {
+            lines.push((event.timestamp, json!({ "kind": "event", "name": event.name })));
+        }
+        for note in &self.notes {
+            lines.push((note.timestamp, json!({ "kind": "note", "text": note.text })));
+        }
+        // Stable, so records of one timestamp keep the order above
+        lines.sort_by_key(|(timestamp, _)| *timestamp);
+
+        for (timestamp, mut line) in lines {
+            line["micros"] = json!(timestamp.as_micros() as u64);
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Read records written by `write_jsonl`, skipping kinds this build does not know
+    pub fn read_jsonl<R: BufRead>(reader: R) -> Result<Self> {
+        let mut recording = Recording::default();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad_line = || TranslatorError::invalid_report(format!("Malformed JSON line {}", number + 1));
+            let mut record: serde_json::Value = serde_json::from_str(&line).map_err(|_| bad_line())?;
+            let timestamp = Duration::from_micros(record["micros"].as_u64().ok_or_else(bad_line)?);
+            let field = |record: &mut serde_json::Value, name: &str| record[name].take();
+
+            match record["kind"].as_str().unwrap_or_default() {
+                "wheel" => recording.wheel.push(RecordedWheel {
+                    timestamp,
+                    report: serde_json::from_value(field(&mut record, "report")).map_err(|_| bad_line())?,
+                }),
+                "input" => recording.inputs.push(RecordedInput {
+                    timestamp,
+                    report: serde_json::from_value(field(&mut record, "report")).map_err(|_| bad_line())?,
+                }),
+                "output" => recording.outputs.push(RecordedOutput {
+                    timestamp,
+                    report: serde_json::from_value(field(&mut record, "report")).map_err(|_| bad_line())?,
+                }),
+                "force" => recording.forces.push(RecordedForce {
+                    timestamp,
+                    magnitude: serde_json::from_value(field(&mut record, "magnitude")).map_err(|_| bad_line())?,
+                }),
+                "event" => recording.events.push(RecordedEvent {
+                    timestamp,
+                    name: record["name"].as_str().ok_or_else(bad_line)?.to_string(),
+                }),
+                "note" => recording.notes.push(RecordedNote {
+                    timestamp,
+                    text: record["text"].as_str().ok_or_else(bad_line)?.to_string(),
+                }),
+                _ => {}
+            }
+        }
+
+        Ok(recording)
+    }
+
+    /// Write the recording in evemu 1.3 format
+    ///
+    /// Only changes between consecutive reports are emitted, like a real
+    /// evdev node would deliver them.
+    pub fn write_evemu<W: Write>(&self, writer: &mut W, config: &G29Config) -> std::io::Result<()> {
+        writeln!(writer, "# EVEMU 1.3")?;
+        writeln!(writer, "# Recorded by tm-g29 {}", env!("CARGO_PKG_VERSION"))?;
+        let (vid, pid) = config.usb_ids();
+        writeln!(writer, "N: {}", config.product_name())?;
+        writeln!(writer, "I: 0003 {:04x} {:04x} 0111", vid, pid)?;
+        writeln!(writer, "P: 00 00 00 00 00 00 00 00")?;
+
+        let mut ev_bits = vec![0u8; 4];
+        for event_type in [EV_SYN, EV_KEY, EV_ABS] {
+            set_bit(&mut ev_bits, event_type as usize);
+        }
+        write_bitmask(writer, 0x00, &ev_bits)?;
+
+        let mut key_bits = vec![0u8; KEY_MAX / 8 + 1];
+        for index in 0..config.target.button_count() {
+            set_bit(&mut key_bits, evdev::button_code(index) as usize);
+        }
+        write_bitmask(writer, EV_KEY, &key_bits)?;
+
+        let mut abs_bits = vec![0u8; ABS_MAX / 8 + 1];
+        for axis in &G29_ABS_AXES {
+            set_bit(&mut abs_bits, axis.code as usize);
+        }
+        write_bitmask(writer, EV_ABS, &abs_bits)?;
+
+        for axis in &G29_ABS_AXES {
+            writeln!(writer, "A: {:02x} {} {} {} {} 0", axis.code, axis.minimum, axis.maximum, axis.fuzz, axis.flat)?;
+        }
+
+        let mut previous: Option<&G29InputReport> = None;
+        for input in &self.inputs {
+            let seconds = input.timestamp.as_secs();
+            let micros = input.timestamp.subsec_micros();
+            for event in evdev::report_events(previous, &input.report) {
+                writeln!(
+                    writer,
+                    "E: {}.{:06} {:04x} {:04x} {:04}",
+                    seconds, micros, event.event_type, event.code, event.value
+                )?;
+            }
+            previous = Some(&input.report);
+        }
+
+        Ok(())
+    }
+}
+
+fn set_bit(bits: &mut [u8], bit: usize) {
+    bits[bit / 8] |= 1 << (bit % 8);
+}
+
+fn write_bitmask<W: Write>(writer: &mut W, event_type: u16, bits: &[u8]) -> std::io::Result<()> {
+    for chunk in bits.chunks(8) {
+        write!(writer, "B: {:02x}", event_type)?;
+        for byte in chunk {
+            write!(writer, " {:02x}", byte)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::HatDirection;
+
+    fn input(micros: u64, steering: u16, buttons: u32) -> RecordedInput {
+        let mut report =
+            G29InputReport { report_id: 0x01, steering, throttle: 512, brake: 0, clutch: 1023, buttons, unused: [0; 4] };
+        report.set_hat(HatDirection::Centered);
+        RecordedInput { timestamp: Duration::from_micros(micros), report }
+    }
+
+    fn fields(input: &RecordedInput) -> (Duration, u16, u16, u16, u16, u32) {
+        let r = &input.report;
+        (input.timestamp, r.steering, r.throttle, r.brake, r.clutch, r.buttons)
+    }
+
+    fn sample() -> Recording {
+        Recording {
+            wheel: vec![RecordedWheel {
+                timestamp: Duration::from_micros(500),
+                report: ThrustmasterInputReport {
+                    steering: -1200,
+                    throttle: 40,
+                    brake: 0,
+                    clutch: 255,
+                    buttons: 0b101,
+                    dpad: HatDirection::East,
+                    base_range: Some(900),
+                    rim: None,
+                },
+            }],
+            inputs: vec![input(1000, 0x8000, 0), input(2500, 0x9000, 0b1)],
+            forces: vec![RecordedForce { timestamp: Duration::from_micros(1200), magnitude: -3000 }],
+            outputs: vec![RecordedOutput { timestamp: Duration::from_micros(1100), report: G29OutputReport::leds(0x1F) }],
+            events: vec![RecordedEvent { timestamp: Duration::from_micros(2000), name: "Recenter".to_string() }],
+            notes: vec![RecordedNote { timestamp: Duration::from_micros(3000), text: "oversteer, \"lap 2\"".to_string() }],
+        }
+    }
+
+    #[test]
+    fn test_trace_round_trips_inputs_and_forces() {
+        let recording = sample();
+        let mut trace = Vec::new();
+        recording.write_trace(&mut trace).unwrap();
+        assert!(String::from_utf8_lossy(&trace).starts_with(TRACE_HEADER));
+
+        let read = Recording::read_trace(trace.as_slice()).unwrap();
+        assert_eq!(read.inputs.iter().map(fields).collect::<Vec<_>>(), recording.inputs.iter().map(fields).collect::<Vec<_>>());
+        assert_eq!(read.forces.len(), 1);
+        assert_eq!((read.forces[0].timestamp, read.forces[0].magnitude), (Duration::from_micros(1200), -3000));
+        assert!(Recording::read_trace("I,1,2,3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_jsonl_round_trips_every_kind() {
+        let recording = sample();
+        let mut jsonl = Vec::new();
+        recording.write_jsonl(&mut jsonl).unwrap();
+        // Lines are in timestamp order across kinds
+        let kinds: Vec<String> = String::from_utf8(jsonl.clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["kind"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(kinds, ["wheel", "input", "output", "force", "event", "input", "note"]);
+
+        jsonl.extend_from_slice(b"{\"kind\":\"from_the_future\",\"micros\":4000}\n");
+        let read = Recording::read_jsonl(jsonl.as_slice()).unwrap();
+        assert_eq!(read.inputs.iter().map(fields).collect::<Vec<_>>(), recording.inputs.iter().map(fields).collect::<Vec<_>>());
+        let wheel = &read.wheel[0];
+        assert_eq!(
+            (wheel.timestamp, wheel.report.steering, wheel.report.clutch, wheel.report.buttons, wheel.report.dpad),
+            (Duration::from_micros(500), -1200, 255, 0b101, HatDirection::East)
+        );
+        assert_eq!(wheel.report.base_range, Some(900));
+        assert_eq!((read.outputs[0].report.report_id, &read.outputs[0].report.data), (0xF8, &G29OutputReport::leds(0x1F).data));
+        assert_eq!(read.forces[0].magnitude, -3000);
+        assert_eq!(read.events[0].name, "Recenter");
+        assert_eq!((read.notes[0].timestamp, read.notes[0].text.as_str()), (Duration::from_micros(3000), "oversteer, \"lap 2\""));
+        assert_eq!(read.duration(), recording.duration());
+    }
+
+    #[test]
+    fn test_evemu_export_matches_golden_file() {
+        let mut out = Vec::new();
+        sample().write_evemu(&mut out, &G29Config::default()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        let zeros = "B: 01 00 00 00 00 00 00 00 00";
+        let recorded_by = format!("# Recorded by tm-g29 {}", env!("CARGO_PKG_VERSION"));
+        let header = [
+            "# EVEMU 1.3",
+            recorded_by.as_str(),
+            "N: G29 Driving Force Racing Wheel",
+            "I: 0003 046d c24f 0111",
+            "P: 00 00 00 00 00 00 00 00",
+            // EV_SYN, EV_KEY and EV_ABS
+            "B: 00 0b 00 00 00",
+            zeros, zeros, zeros, zeros,
+            // BTN_JOYSTICK to BTN_BASE6 (0x120 - 0x12f)
+            "B: 01 00 00 00 00 ff ff 00 00",
+            zeros, zeros, zeros, zeros, zeros, zeros,
+            // BTN_TRIGGER_HAPPY1 - 8 (0x2c0 - 0x2c7)
+            "B: 01 ff 00 00 00 00 00 00 00",
+            // ABS_X, Y, Z, RZ, HAT0X and HAT0Y
+            "B: 03 27 00 03 00 00 00 00 00",
+            "A: 00 0 65535 0 0 0",
+            "A: 01 0 1023 0 0 0",
+            "A: 02 0 1023 0 0 0",
+            "A: 05 0 1023 0 0 0",
+            "A: 10 -1 1 0 0 0",
+            "A: 11 -1 1 0 0 0",
+        ];
+        assert_eq!(lines[..header.len()], header);
+
+        // The first report sets every axis, hat and button, the next only what changed
+        let events = &lines[header.len()..];
+        assert_eq!(
+            events[..7],
+            [
+                "E: 0.001000 0003 0000 32768",
+                "E: 0.001000 0003 0001 0512",
+                "E: 0.001000 0003 0002 0000",
+                "E: 0.001000 0003 0005 1023",
+                "E: 0.001000 0003 0010 0000",
+                "E: 0.001000 0003 0011 0000",
+                "E: 0.001000 0001 0120 0000",
+            ]
+        );
+        assert_eq!(events.len(), 6 + 24 + 1 + 3);
+        assert_eq!(events[30], "E: 0.001000 0000 0000 0000");
+        assert_eq!(events[31..], ["E: 0.002500 0003 0000 36864", "E: 0.002500 0001 0120 0001", "E: 0.002500 0000 0000 0000"]);
+    }
+}
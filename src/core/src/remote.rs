@@ -0,0 +1,726 @@
+//! Wheel attached to another host
+//!
+//! `tm-g29 serve-wheel` opens the wheel on a small board next to the rig
+//! and serves it over TCP; the translator on the gaming PC, with
+//! `remote_config.connect` set, reads it as a `RemoteWheel` and creates the
+//! virtual G29 there. Everything after the source wheel, calibration,
+//! mapping and the FFB engine, runs on the gaming PC as usual; only reports,
+//! IFORCE commands and wheel settings cross the network.
+//!
+//! Frames are a little-endian u32 length followed by one JSON
+//! `RemoteMessage`. Both ends prove they hold `remote_config.key` before
+//! anything else is sent: the host opens with a challenge nonce, the
+//! translator answers with its own nonce and an HMAC-SHA256 over both, and
+//! the host's welcome carries an HMAC over them the other way round, so
+//! neither end talks to a peer without the key and a recorded session cannot
+//! be replayed. The key is never sent. Every frame after the handshake ends
+//! in an HMAC-SHA256 over its sequence number and payload, keyed from the
+//! key and both nonces, so frames cannot be injected, replayed or reordered
+//! mid-session. Frames are not encrypted, keep the link on the rig's own
+//! network.
+//!
+//! The host serves one translator at a time; the next one is accepted once
+//! it disconnects. Reports queued while nobody was connected are dropped.
+
+use crate::config::{Config, ReadStrategy, RemoteConfig, ThrustmasterConfig};
+use crate::device::models::ThrustmasterModel;
+use crate::device::source::InputEvents;
+use crate::device::{IforceCommand, SourceWheel, StrategySelection, ThrustmasterDevice, ThrustmasterInputReport};
+use crate::error::{Result, TranslatorError};
+use crate::ffb::{EffectKind, FfbCapabilities};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// Bumped whenever `RemoteMessage` changes incompatibly
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Largest frame either end accepts, reports and IFORCE commands are far smaller
+const MAX_FRAME_LEN: usize = 4096;
+
+/// Reports and messages queued between the socket and the translator
+const QUEUE_LEN: usize = 64;
+
+/// How often a wheel without input events is polled
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+const NONCE_LEN: usize = 16;
+const TRANSLATOR_ROLE: &[u8] = b"tm-g29 translator";
+const HOST_ROLE: &[u8] = b"tm-g29 wheel host";
+const SESSION_ROLE: &[u8] = b"tm-g29 session";
+
+/// Length of the HMAC-SHA256 ending every frame after the handshake
+const FRAME_MAC_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What the wheel host tells the translator about its wheel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WheelInfo {
+    pub model: ThrustmasterModel,
+    pub serial_number: Option<String>,
+    pub ffb_kinds: Vec<EffectKind>,
+    pub hardware_autocenter: bool,
+    pub read_strategy: ReadStrategy,
+}
+
+impl WheelInfo {
+    fn of(wheel: &ThrustmasterDevice) -> Self {
+        Self {
+            model: wheel.model(),
+            serial_number: wheel.serial_number().map(str::to_string),
+            ffb_kinds: wheel.ffb_capabilities().kinds(),
+            hardware_autocenter: wheel.ffb_capabilities().has_hardware_autocenter(),
+            read_strategy: wheel.read_strategy().strategy,
+        }
+    }
+}
+
+/// One frame of the remote wheel protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemoteMessage {
+    /// Host to translator on connect
+    Challenge { version: u32, nonce: Vec<u8> },
+    /// Translator's answer, `mac` over the host's nonce then its own
+    Auth { nonce: Vec<u8>, mac: Vec<u8> },
+    /// Host accepted the translator, `mac` over the translator's nonce then its own
+    Welcome { mac: Vec<u8>, wheel: WheelInfo },
+    Rejected { reason: String },
+    Input { report: ThrustmasterInputReport },
+    Ffb { command_id: u8, data: Vec<u8> },
+    SetRange { degrees: u16 },
+    /// Answer to `SetRange`, the degrees applied or why not
+    RangeSet { result: std::result::Result<u16, String> },
+    SetLeds { mask: u8, brightness: f32 },
+    Initialize { steering_range: u16 },
+}
+
+/// Write `message` as one frame
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &RemoteMessage) -> Result<()> {
+    write_frame(writer, &encode(message)?).await
+}
+
+/// Read one frame, `None` when the peer closed the connection between frames
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<RemoteMessage>> {
+    match read_frame(reader).await? {
+        Some(payload) => decode(&payload).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn encode(message: &RemoteMessage) -> Result<Vec<u8>> {
+    serde_json::to_vec(message)
+        .map_err(|e| TranslatorError::protocol_error(format!("Cannot encode {:?}: {}", message, e)))
+}
+
+fn decode(payload: &[u8]) -> Result<RemoteMessage> {
+    serde_json::from_slice(payload).map_err(|e| TranslatorError::protocol_error(format!("Malformed frame: {}", e)))
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> Result<()> {
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(TranslatorError::protocol_error(format!("Frame of {} bytes is too large", payload.len())));
+    }
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME_LEN {
+        return Err(TranslatorError::protocol_error(format!("Peer sent a frame of {} bytes", length)));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(payload))
+}
+
+/// MAC over the frames one end sends during a session, counting them
+#[derive(Clone)]
+struct FrameMac {
+    key: Vec<u8>,
+    role: &'static [u8],
+    sequence: u64,
+}
+
+impl FrameMac {
+    /// Keyed from `key` and both nonces, so no two sessions share a key
+    fn new(key: &str, role: &'static [u8], host_nonce: &[u8], translator_nonce: &[u8]) -> Self {
+        Self { key: sign(key, SESSION_ROLE, host_nonce, translator_nonce), role, sequence: 0 }
+    }
+
+    fn next(&mut self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(self.role);
+        mac.update(&self.sequence.to_le_bytes());
+        mac.update(payload);
+        self.sequence += 1;
+        mac
+    }
+
+    /// Append the MAC of the next frame to `payload`
+    fn seal(&mut self, mut payload: Vec<u8>) -> Vec<u8> {
+        let tag = self.next(&payload).finalize().into_bytes();
+        payload.extend_from_slice(&tag);
+        payload
+    }
+
+    /// Payload of `frame` if it is the next frame the peer sent
+    fn open<'a>(&mut self, frame: &'a [u8]) -> Result<&'a [u8]> {
+        let split = frame
+            .len()
+            .checked_sub(FRAME_MAC_LEN)
+            .ok_or_else(|| TranslatorError::protocol_error("Frame is too short to carry a MAC"))?;
+        let (payload, tag) = frame.split_at(split);
+        self.next(payload)
+            .verify_slice(tag)
+            .map_err(|_| TranslatorError::protocol_error("Frame MAC mismatch, injected or out of order"))?;
+        Ok(payload)
+    }
+}
+
+/// Write half of an authenticated session
+struct SessionWriter {
+    writer: OwnedWriteHalf,
+    mac: FrameMac,
+}
+
+impl SessionWriter {
+    async fn send(&mut self, message: &RemoteMessage) -> Result<()> {
+        let frame = self.mac.seal(encode(message)?);
+        write_frame(&mut self.writer, &frame).await
+    }
+}
+
+/// Read half of an authenticated session
+struct SessionReader {
+    reader: OwnedReadHalf,
+    mac: FrameMac,
+}
+
+impl SessionReader {
+    /// Next frame, `None` when the peer closed the connection between frames
+    async fn recv(&mut self) -> Result<Option<RemoteMessage>> {
+        match read_frame(&mut self.reader).await? {
+            Some(frame) => decode(self.mac.open(&frame)?).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// `remote_config.key`, refusing to run without one
+fn require_key(config: &RemoteConfig) -> Result<&str> {
+    if config.key.is_empty() {
+        return Err(TranslatorError::config_error("Set remote_config.key on both hosts to use a remote wheel"));
+    }
+    Ok(&config.key)
+}
+
+/// Challenge nonce from the operating system's CSPRNG
+fn new_nonce() -> Result<Vec<u8>> {
+    let mut nonce = vec![0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce)
+        .map_err(|e| TranslatorError::protocol_error(format!("Cannot generate a nonce: {}", e)))?;
+    Ok(nonce)
+}
+
+fn keyed(key: &str, role: &[u8], first: &[u8], second: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(role);
+    mac.update(first);
+    mac.update(second);
+    mac
+}
+
+/// MAC proving `role` holds `key`, over both nonces
+fn sign(key: &str, role: &[u8], first: &[u8], second: &[u8]) -> Vec<u8> {
+    keyed(key, role, first, second).finalize().into_bytes().to_vec()
+}
+
+/// Whether `mac` came from a peer holding `key`, compared in constant time
+fn verify(key: &str, role: &[u8], first: &[u8], second: &[u8], mac: &[u8]) -> bool {
+    keyed(key, role, first, second).verify_slice(mac).is_ok()
+}
+
+/// Authenticate to the wheel host and learn its wheel
+async fn client_handshake(
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    key: &str,
+) -> Result<(WheelInfo, SessionReader, SessionWriter)> {
+    let host_nonce = match read_message(&mut reader).await? {
+        Some(RemoteMessage::Challenge { version, nonce }) if version == PROTOCOL_VERSION => nonce,
+        Some(RemoteMessage::Challenge { version, .. }) => {
+            return Err(TranslatorError::protocol_error(format!(
+                "Wheel host speaks protocol version {}, this translator {}",
+                version, PROTOCOL_VERSION
+            )))
+        }
+        Some(RemoteMessage::Rejected { reason }) => {
+            return Err(TranslatorError::protocol_error(format!("Wheel host refused the connection: {}", reason)))
+        }
+        _ => return Err(TranslatorError::protocol_error("Wheel host did not send a challenge")),
+    };
+
+    let nonce = new_nonce()?;
+    let mac = sign(key, TRANSLATOR_ROLE, &host_nonce, &nonce);
+    write_message(&mut writer, &RemoteMessage::Auth { nonce: nonce.clone(), mac }).await?;
+
+    match read_message(&mut reader).await? {
+        Some(RemoteMessage::Welcome { mac, wheel }) if verify(key, HOST_ROLE, &nonce, &host_nonce, &mac) => Ok((
+            wheel,
+            SessionReader { reader, mac: FrameMac::new(key, HOST_ROLE, &host_nonce, &nonce) },
+            SessionWriter { writer, mac: FrameMac::new(key, TRANSLATOR_ROLE, &host_nonce, &nonce) },
+        )),
+        Some(RemoteMessage::Welcome { .. }) => {
+            Err(TranslatorError::protocol_error("Wheel host does not hold remote_config.key"))
+        }
+        Some(RemoteMessage::Rejected { reason }) => {
+            Err(TranslatorError::protocol_error(format!("Wheel host refused the connection: {}", reason)))
+        }
+        _ => Err(TranslatorError::protocol_error("Wheel host closed the connection during authentication")),
+    }
+}
+
+/// Challenge a translator, welcoming it if it holds the key
+async fn host_handshake(
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    key: &str,
+    wheel: &WheelInfo,
+) -> Result<(SessionReader, SessionWriter)> {
+    let nonce = new_nonce()?;
+    write_message(&mut writer, &RemoteMessage::Challenge { version: PROTOCOL_VERSION, nonce: nonce.clone() }).await?;
+
+    let translator_nonce = match read_message(&mut reader).await? {
+        Some(RemoteMessage::Auth { nonce: translator_nonce, mac })
+            if translator_nonce.len() == NONCE_LEN && verify(key, TRANSLATOR_ROLE, &nonce, &translator_nonce, &mac) =>
+        {
+            translator_nonce
+        }
+        Some(RemoteMessage::Auth { .. }) => {
+            let reason = "authentication failed".to_string();
+            write_message(&mut writer, &RemoteMessage::Rejected { reason: reason.clone() }).await?;
+            return Err(TranslatorError::protocol_error(reason));
+        }
+        _ => return Err(TranslatorError::protocol_error("Translator did not authenticate")),
+    };
+
+    let mac = sign(key, HOST_ROLE, &translator_nonce, &nonce);
+    write_message(&mut writer, &RemoteMessage::Welcome { mac, wheel: wheel.clone() }).await?;
+    Ok((
+        SessionReader { reader, mac: FrameMac::new(key, TRANSLATOR_ROLE, &nonce, &translator_nonce) },
+        SessionWriter { writer, mac: FrameMac::new(key, HOST_ROLE, &nonce, &translator_nonce) },
+    ))
+}
+
+/// Replies to `set_range` still on their way back, oldest first
+type PendingRanges = Arc<Mutex<VecDeque<oneshot::Sender<std::result::Result<u16, String>>>>>;
+
+/// Source wheel served by `tm-g29 serve-wheel` on another host
+///
+/// Reports arrive as input events; IFORCE commands and LED updates are sent
+/// without waiting, `set_range` waits for the host's answer.
+pub struct RemoteWheel {
+    writer: tokio::sync::Mutex<SessionWriter>,
+    events: Option<InputEvents>,
+    pending_ranges: PendingRanges,
+    reply_timeout: Duration,
+    model: ThrustmasterModel,
+    serial_number: Option<String>,
+    ffb_capabilities: FfbCapabilities,
+    read_strategy: StrategySelection,
+    reader: tokio::task::JoinHandle<()>,
+}
+
+impl RemoteWheel {
+    /// Connect to the wheel host `config` names and authenticate
+    pub async fn connect(config: &RemoteConfig) -> Result<Self> {
+        let address = config
+            .connect
+            .as_deref()
+            .ok_or_else(|| TranslatorError::config_error("remote_config.connect names no wheel host"))?;
+        let key = require_key(config)?;
+        let timeout = Duration::from_millis(config.connect_timeout_ms as u64);
+
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(address))
+            .await
+            .map_err(|_| TranslatorError::Timeout)??;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        let (wheel, reader, writer) = tokio::time::timeout(timeout, client_handshake(reader, writer, key))
+            .await
+            .map_err(|_| TranslatorError::Timeout)??;
+        tracing::info!("Reading {:?} from wheel host {}", wheel.model, address);
+
+        let (sender, events) = mpsc::channel(QUEUE_LEN);
+        let pending_ranges = PendingRanges::default();
+        let reader = tokio::spawn(read_host(reader, sender, pending_ranges.clone()));
+
+        Ok(Self {
+            writer: tokio::sync::Mutex::new(writer),
+            events: Some(events),
+            pending_ranges,
+            reply_timeout: timeout,
+            model: wheel.model,
+            serial_number: wheel.serial_number,
+            ffb_capabilities: FfbCapabilities::from_kinds(wheel.ffb_kinds)
+                .with_hardware_autocenter(wheel.hardware_autocenter),
+            read_strategy: StrategySelection { strategy: wheel.read_strategy, configured: true, benchmarks: Vec::new() },
+            reader,
+        })
+    }
+
+    async fn send(&self, message: &RemoteMessage) -> Result<()> {
+        self.writer.lock().await.send(message).await
+    }
+}
+
+impl Drop for RemoteWheel {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Pass the host's reports on as input events, and range answers to their callers
+async fn read_host(
+    mut reader: SessionReader,
+    events: mpsc::Sender<Result<ThrustmasterInputReport>>,
+    pending_ranges: PendingRanges,
+) {
+    loop {
+        let message = match reader.recv().await {
+            Ok(Some(message)) => message,
+            Ok(None) => {
+                let _ = events.send(Err(TranslatorError::protocol_error("Wheel host closed the connection"))).await;
+                return;
+            }
+            Err(e) => {
+                let _ = events.send(Err(e)).await;
+                return;
+            }
+        };
+        match message {
+            RemoteMessage::Input { report } => {
+                if events.send(Ok(report)).await.is_err() {
+                    return;
+                }
+            }
+            RemoteMessage::RangeSet { result } => {
+                let caller = pending_ranges.lock().ok().and_then(|mut pending| pending.pop_front());
+                if let Some(caller) = caller {
+                    let _ = caller.send(result);
+                }
+            }
+            other => tracing::debug!("Ignoring unexpected message from the wheel host: {:?}", other),
+        }
+    }
+}
+
+impl SourceWheel for RemoteWheel {
+    async fn open(_config: &ThrustmasterConfig) -> Result<Self> {
+        Err(TranslatorError::config_error(
+            "A remote wheel is opened from [remote_config], see ProtocolTranslator::new_remote",
+        ))
+    }
+
+    async fn initialize(&self, steering_range: u16) -> Result<()> {
+        self.send(&RemoteMessage::Initialize { steering_range }).await
+    }
+
+    fn model(&self) -> ThrustmasterModel {
+        self.model
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        self.serial_number.as_deref()
+    }
+
+    fn ffb_capabilities(&self) -> &FfbCapabilities {
+        &self.ffb_capabilities
+    }
+
+    fn read_strategy(&self) -> &StrategySelection {
+        &self.read_strategy
+    }
+
+    fn take_input_events(&mut self) -> Option<InputEvents> {
+        self.events.take()
+    }
+
+    async fn read_input(&self) -> Result<Option<ThrustmasterInputReport>> {
+        // Reports only arrive as input events
+        Ok(None)
+    }
+
+    async fn send_ffb_command(&self, command: IforceCommand) -> Result<()> {
+        self.send(&RemoteMessage::Ffb { command_id: command.command_id, data: command.data }).await
+    }
+
+    async fn set_range(&self, degrees: u16) -> Result<u16> {
+        let (reply, answer) = oneshot::channel();
+        {
+            // Queued and sent under the writer lock, so answers come back in this order
+            let mut writer = self.writer.lock().await;
+            self.pending_ranges
+                .lock()
+                .map_err(|_| TranslatorError::protocol_error("Remote wheel state poisoned"))?
+                .push_back(reply);
+            writer.send(&RemoteMessage::SetRange { degrees }).await?;
+        }
+        match tokio::time::timeout(self.reply_timeout, answer).await {
+            Ok(Ok(Ok(applied))) => Ok(applied),
+            Ok(Ok(Err(reason))) => Err(TranslatorError::protocol_error(reason)),
+            Ok(Err(_)) => Err(TranslatorError::protocol_error("Wheel host closed the connection")),
+            Err(_) => Err(TranslatorError::Timeout),
+        }
+    }
+
+    async fn set_leds(&self, mask: u8, brightness: f32) -> Result<()> {
+        self.send(&RemoteMessage::SetLeds { mask, brightness }).await
+    }
+}
+
+/// How a translator's session ended
+enum SessionEnd {
+    Disconnected,
+    WheelLost(TranslatorError),
+}
+
+/// Serve the wheel `config` selects to translators on other hosts until the wheel is lost
+pub async fn serve_wheel(config: &Config) -> Result<()> {
+    let key = require_key(&config.remote_config)?.to_string();
+    let mut wheel = ThrustmasterDevice::open(&config.thrustmaster_config).await?;
+    let events = wheel.take_input_events();
+    let wheel = Arc::new(wheel);
+    let info = WheelInfo::of(&wheel);
+
+    let (sender, mut reports) = mpsc::channel(QUEUE_LEN);
+    let forwarder = tokio::spawn(forward_reports(wheel.clone(), events, sender));
+
+    let listen = &config.remote_config.listen;
+    let listener = TcpListener::bind(listen).await?;
+    tracing::info!("Serving {:?} to translators on {}", info.model, listen);
+
+    let timeout = Duration::from_millis(config.remote_config.connect_timeout_ms as u64);
+    let result = loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => break Err(e.into()),
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            tracing::warn!("Cannot disable Nagle's algorithm for {}: {}", peer, e);
+        }
+        let (reader, writer) = stream.into_split();
+        let (reader, writer) = match tokio::time::timeout(timeout, host_handshake(reader, writer, &key, &info)).await {
+            Ok(Ok(session)) => {
+                tracing::info!("Translator at {} connected", peer);
+                session
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("Refused translator at {}: {}", peer, e);
+                continue;
+            }
+            Err(_) => {
+                tracing::warn!("Translator at {} did not authenticate in time", peer);
+                continue;
+            }
+        };
+
+        // Whatever the wheel reported meanwhile is stale
+        while reports.try_recv().is_ok() {}
+        match serve_translator(reader, writer, &wheel, &mut reports).await {
+            Ok(SessionEnd::Disconnected) => tracing::info!("Translator at {} disconnected", peer),
+            Ok(SessionEnd::WheelLost(e)) => break Err(e),
+            Err(e) => tracing::warn!("Translator at {} dropped: {}", peer, e),
+        }
+    };
+    forwarder.abort();
+    result
+}
+
+/// Move wheel reports to the session, dropping them while nobody is connected
+async fn forward_reports(
+    wheel: Arc<ThrustmasterDevice>,
+    events: Option<InputEvents>,
+    reports: mpsc::Sender<Result<ThrustmasterInputReport>>,
+) {
+    match events {
+        Some(mut events) => {
+            while let Some(event) = events.recv().await {
+                match event {
+                    Ok(report) => {
+                        let _ = reports.try_send(Ok(report));
+                    }
+                    Err(e) => {
+                        let _ = reports.send(Err(e)).await;
+                        return;
+                    }
+                }
+            }
+            let _ = reports.send(Err(TranslatorError::protocol_error("Wheel reader thread stopped"))).await;
+        }
+        None => loop {
+            match wheel.read_input().await {
+                Ok(Some(report)) => {
+                    let _ = reports.try_send(Ok(report));
+                }
+                Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(e) => {
+                    let _ = reports.send(Err(e)).await;
+                    return;
+                }
+            }
+        },
+    }
+}
+
+/// Read frames on their own task, a frame read cannot be cancelled midway
+fn spawn_frame_reader(mut reader: SessionReader) -> (mpsc::Receiver<Result<Option<RemoteMessage>>>, tokio::task::JoinHandle<()>) {
+    let (sender, frames) = mpsc::channel(QUEUE_LEN);
+    let task = tokio::spawn(async move {
+        loop {
+            let frame = reader.recv().await;
+            let done = !matches!(frame, Ok(Some(_)));
+            if sender.send(frame).await.is_err() || done {
+                return;
+            }
+        }
+    });
+    (frames, task)
+}
+
+/// Forward reports to one authenticated translator and carry out its requests
+async fn serve_translator(
+    reader: SessionReader,
+    mut writer: SessionWriter,
+    wheel: &ThrustmasterDevice,
+    reports: &mut mpsc::Receiver<Result<ThrustmasterInputReport>>,
+) -> Result<SessionEnd> {
+    let (mut frames, frame_reader) = spawn_frame_reader(reader);
+    let result = loop {
+        tokio::select! {
+            report = reports.recv() => match report {
+                Some(Ok(report)) => {
+                    if let Err(e) = writer.send(&RemoteMessage::Input { report }).await {
+                        break Err(e);
+                    }
+                }
+                Some(Err(e)) => break Ok(SessionEnd::WheelLost(e)),
+                None => break Ok(SessionEnd::WheelLost(TranslatorError::protocol_error("Wheel reader stopped"))),
+            },
+            frame = frames.recv() => match frame {
+                Some(Ok(Some(message))) => {
+                    if let Err(e) = handle_request(wheel, &mut writer, message).await {
+                        break Err(e);
+                    }
+                }
+                Some(Ok(None)) | None => break Ok(SessionEnd::Disconnected),
+                Some(Err(e)) => break Err(e),
+            },
+        }
+    };
+    frame_reader.abort();
+    result
+}
+
+/// Carry out one translator request on the wheel
+async fn handle_request(wheel: &ThrustmasterDevice, writer: &mut SessionWriter, message: RemoteMessage) -> Result<()> {
+    match message {
+        RemoteMessage::Ffb { command_id, data } => {
+            if let Err(e) = wheel.send_ffb_command(IforceCommand { command_id, data }).await {
+                tracing::warn!("FFB command {:#04x} failed: {}", command_id, e);
+            }
+        }
+        RemoteMessage::SetRange { degrees } => {
+            let result = wheel.set_range(degrees).await.map_err(|e| e.to_string());
+            writer.send(&RemoteMessage::RangeSet { result }).await?;
+        }
+        RemoteMessage::SetLeds { mask, brightness } => {
+            if let Err(e) = wheel.set_leds(mask, brightness).await {
+                tracing::debug!("LEDs not set: {}", e);
+            }
+        }
+        RemoteMessage::Initialize { steering_range } => {
+            if let Err(e) = wheel.initialize(steering_range).await {
+                tracing::warn!("Wheel initialization failed: {}", e);
+            }
+        }
+        other => return Err(TranslatorError::protocol_error(format!("Unexpected request {:?}", other))),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_round_trip_and_wrong_key_is_refused() {
+        let message = RemoteMessage::Ffb { command_id: 0x41, data: vec![1, 2, 3] };
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &message).await.unwrap();
+        assert_eq!(u32::from_le_bytes(buffer[..4].try_into().unwrap()) as usize, buffer.len() - 4);
+        let mut reader = buffer.as_slice();
+        assert!(matches!(
+            read_message(&mut reader).await.unwrap(),
+            Some(RemoteMessage::Ffb { command_id: 0x41, data }) if data == [1, 2, 3]
+        ));
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+
+        let (host, translator) = (new_nonce().unwrap(), new_nonce().unwrap());
+        assert_eq!(host.len(), NONCE_LEN);
+        assert_ne!(host, translator);
+        let mac = sign("secret", TRANSLATOR_ROLE, &host, &translator);
+        assert!(verify("secret", TRANSLATOR_ROLE, &host, &translator, &mac));
+        assert!(!verify("guess", TRANSLATOR_ROLE, &host, &translator, &mac));
+        // A translator's answer does not pass for the host's welcome
+        assert!(!verify("secret", HOST_ROLE, &translator, &host, &mac));
+    }
+
+    #[test]
+    fn test_frame_mac_rejects_injected_and_replayed_frames() {
+        let (host, translator) = (new_nonce().unwrap(), new_nonce().unwrap());
+        let mut sender = FrameMac::new("secret", TRANSLATOR_ROLE, &host, &translator);
+        let mut receiver = FrameMac::new("secret", TRANSLATOR_ROLE, &host, &translator);
+        let payload = encode(&RemoteMessage::SetRange { degrees: 900 }).unwrap();
+        let first = sender.seal(payload.clone());
+        let second = sender.seal(payload.clone());
+        assert_eq!(receiver.open(&first).unwrap(), payload.as_slice());
+
+        // Replaying the first frame does not pass for the second
+        assert!(receiver.clone().open(&first).is_err());
+        // Nor does a frame sealed without the session key
+        let mut forger = FrameMac::new("guess", TRANSLATOR_ROLE, &host, &translator);
+        forger.sequence = 1;
+        assert!(receiver.clone().open(&forger.seal(payload.clone())).is_err());
+        // Nor a tampered one
+        let mut tampered = second.clone();
+        tampered[0] ^= 1;
+        assert!(receiver.clone().open(&tampered).is_err());
+        // Nor the host's own frames reflected back
+        let mut reflected = FrameMac::new("secret", HOST_ROLE, &host, &translator);
+        reflected.sequence = 1;
+        assert!(receiver.clone().open(&reflected.seal(payload.clone())).is_err());
+
+        assert_eq!(receiver.open(&second).unwrap(), payload.as_slice());
+        assert!(receiver.open(&[0u8; 8]).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Software rendering of condition effects from the wheel's motion
+//!
+//! Older Thrustmaster bases only accept constant force. With
+//! `FfbRenderMode::Render` the engine plays every effect in software and
+//! sends their sum as one constant force stream. Periodic and ramp effects
+//! only depend on time, but springs, dampers, inertia and friction push
+//! against the wheel's position, velocity and acceleration, so the input
+//! loop feeds every steering sample into a `WheelMotion` and the conditions
+//! are evaluated against it on each update.
+
+use crate::ffb::{ConditionEffect, ConditionType};
+use std::time::Instant;
+
+/// Velocity in axis units per second (2.0 = lock to lock) at which a damper reaches its coefficient
+pub const FULL_SCALE_VELOCITY: f32 = 4.0;
+
+/// Acceleration in axis units per second² at which inertia reaches its coefficient
+const FULL_SCALE_ACCELERATION: f32 = 80.0;
+
+/// Velocity below which friction fades out instead of flipping direction
+const FRICTION_DEADBAND: f32 = 0.05;
+
+/// Weight of a new sample in the smoothed velocity and acceleration
+const SMOOTHING: f32 = 0.3;
+
+/// Steering position and its derivatives, from the input loop's samples
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WheelMotion {
+    /// -1.0 (full left) to 1.0 (full right)
+    pub position: f32,
+    /// Axis units per second
+    pub velocity: f32,
+    /// Axis units per second²
+    pub acceleration: f32,
+    sampled: Option<Instant>,
+}
+
+impl WheelMotion {
+    /// Account a steering sample taken at `now`
+    pub fn observe(&mut self, steering: i16, now: Instant) {
+        let position = steering as f32 / i16::MAX as f32;
+        if let Some(last) = self.sampled {
+            let dt = now.duration_since(last).as_secs_f32();
+            if dt <= 0.0 {
+                return;
+            }
+            let velocity = (position - self.position) / dt;
+            let acceleration = (velocity - self.velocity) / dt;
+            self.velocity += (velocity - self.velocity) * SMOOTHING;
+            self.acceleration += (acceleration - self.acceleration) * SMOOTHING;
+        }
+        self.position = position.clamp(-1.0, 1.0);
+        self.sampled = Some(now);
+    }
+
+    /// Force of `condition` against this motion, in effect magnitude units before gains
+    ///
+    /// The positive coefficient applies on the right of center (or moving
+    /// right), the negative one on the left.
+    pub fn condition_force(&self, condition: &ConditionEffect) -> f32 {
+        let input = match condition.condition_type {
+            ConditionType::Spring => self.position,
+            ConditionType::Damper => (self.velocity / FULL_SCALE_VELOCITY).clamp(-1.0, 1.0),
+            ConditionType::Inertia => (self.acceleration / FULL_SCALE_ACCELERATION).clamp(-1.0, 1.0),
+            ConditionType::Friction => (self.velocity / FRICTION_DEADBAND).clamp(-1.0, 1.0),
+        };
+        let coefficient = if input >= 0.0 { condition.positive_coefficient } else { condition.negative_coefficient };
+        // Conditions resist the motion
+        -(coefficient as f32) * input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn condition(condition_type: ConditionType) -> ConditionEffect {
+        ConditionEffect { positive_coefficient: 10000, negative_coefficient: 5000, condition_type }
+    }
+
+    #[test]
+    fn test_conditions_resist_position_and_motion() {
+        let start = Instant::now();
+        let mut motion = WheelMotion::default();
+        motion.observe(i16::MAX / 2, start);
+        assert!((motion.condition_force(&condition(ConditionType::Spring)) + 5000.0).abs() < 1.0);
+        assert_eq!(motion.condition_force(&condition(ConditionType::Damper)), 0.0);
+
+        // Turning left: the damper and friction push right with the negative coefficient
+        motion.observe(0, start + Duration::from_millis(100));
+        assert!(motion.velocity < 0.0);
+        assert!(motion.condition_force(&condition(ConditionType::Damper)) > 0.0);
+        assert_eq!(motion.condition_force(&condition(ConditionType::Friction)), 5000.0);
+        assert_eq!(motion.condition_force(&condition(ConditionType::Spring)), 0.0);
+    }
+}
@@ -0,0 +1,279 @@
+//! Offline dry runs of recorded sessions
+//!
+//! Runs the output reports of a capture through `OutputTranslator` and
+//! `FfbEngine` with a given configuration, without any device attached, and
+//! summarizes what would have been sent to the wheel. This lets gains and
+//! the force limit be tuned against a real game session, spotting clipping
+//! before it is felt.
+//!
+//! `replay_session` also runs the recorded wheel reports through
+//! `InputTranslator`, on the session's own clock so button timing and assists
+//! behave as they did live. Comparing the result with the G29 reports that
+//! were recorded shows whether a user's issue reproduces with a given
+//! configuration, without their wheel.
+
+use crate::config::Config;
+use crate::ffb::{EffectKind, FfbEngine};
+use crate::device::G29InputReport;
+use crate::presentation::Presenter;
+use crate::protocol::{InputTranslator, OutputTranslator};
+use crate::recording::{RecordedInput, Recording};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+
+/// IFORCE command carrying a constant force
+const IFORCE_CONSTANT: u8 = 0x41;
+
+/// Magnitude the engine clamps to
+const FULL_SCALE: i16 = i16::MAX;
+
+/// What a capture would have produced with one configuration
+#[derive(Debug, Clone, Default)]
+pub struct ReplaySummary {
+    pub output_reports: usize,
+    pub effects: usize,
+    pub malformed_reports: usize,
+    pub effects_by_kind: BTreeMap<String, usize>,
+    /// Effects dropped because their kind is switched off
+    pub disabled_by_kind: BTreeMap<String, u64>,
+    pub commands: usize,
+    pub constant_forces: usize,
+    pub min_force: i16,
+    pub max_force: i16,
+    pub mean_abs_force: f32,
+    /// Constant forces sent at full scale
+    pub saturated_forces: usize,
+    /// Magnitudes the gains or force limit pushed past full scale
+    pub clipped: u64,
+}
+
+impl ReplaySummary {
+    /// Share of constant forces sent at full scale, 0.0..=1.0
+    pub fn saturation_ratio(&self) -> f32 {
+        if self.constant_forces == 0 {
+            0.0
+        } else {
+            self.saturated_forces as f32 / self.constant_forces as f32
+        }
+    }
+}
+
+/// Replay the output reports of `recording` with the FFB settings of `config`
+pub fn validate_capture(recording: &Recording, config: &Config) -> ReplaySummary {
+    let mut translator = OutputTranslator::new(&config.output_config);
+    let mut engine = FfbEngine::new(&config.ffb_config);
+    let mut summary = ReplaySummary::default();
+    let mut abs_sum = 0u64;
+
+    for output in &recording.outputs {
+        summary.output_reports += 1;
+
+        let commands = match translator.parse_pid_report(&output.report) {
+            Ok(Some(command)) => engine.handle_pid(command),
+            Ok(None) => {
+                let effect = match translator.parse_ffb_effect(output.report.clone()) {
+                    Ok(Some(effect)) => effect,
+                    Ok(None) => continue,
+                    Err(_) => {
+                        summary.malformed_reports += 1;
+                        continue;
+                    }
+                };
+                summary.effects += 1;
+                *summary.effects_by_kind.entry(format!("{:?}", EffectKind::of(&effect.effect_type))).or_default() += 1;
+                engine.translate_effect(effect)
+            }
+            Err(_) => {
+                summary.malformed_reports += 1;
+                continue;
+            }
+        };
+        let Ok(commands) = commands else {
+            summary.malformed_reports += 1;
+            continue;
+        };
+        summary.commands += commands.len();
+
+        for command in commands.iter().filter(|c| c.command_id == IFORCE_CONSTANT && c.data.len() >= 3) {
+            let magnitude = i16::from_le_bytes([command.data[1], command.data[2]]);
+            if summary.constant_forces == 0 {
+                summary.min_force = magnitude;
+                summary.max_force = magnitude;
+            }
+            summary.constant_forces += 1;
+            summary.min_force = summary.min_force.min(magnitude);
+            summary.max_force = summary.max_force.max(magnitude);
+            abs_sum += magnitude.unsigned_abs() as u64;
+            if magnitude.unsigned_abs() >= FULL_SCALE as u16 {
+                summary.saturated_forces += 1;
+            }
+        }
+    }
+
+    if summary.constant_forces > 0 {
+        summary.mean_abs_force = abs_sum as f32 / summary.constant_forces as f32;
+    }
+    summary.clipped = engine.clipped_count();
+    summary.disabled_by_kind = engine
+        .disabled_counts()
+        .iter()
+        .map(|(kind, count)| (format!("{:?}", kind), *count))
+        .collect();
+    summary
+}
+
+/// A capture run back through the input and FFB stages
+#[derive(Debug, Clone, Default)]
+pub struct SessionReplay {
+    /// G29 reports the recorded wheel reports translate to
+    pub inputs: Vec<RecordedInput>,
+    /// Recorded G29 reports the replay compared against
+    pub compared_inputs: usize,
+    /// Replayed G29 reports differing from the recorded ones
+    pub changed_inputs: usize,
+    /// Timestamp of the first differing report, in microseconds
+    pub first_change_micros: Option<u64>,
+    pub ffb: ReplaySummary,
+}
+
+/// Replay the wheel and output reports of `recording` with `config`
+pub fn replay_session(recording: &Recording, config: &Config) -> SessionReplay {
+    let mut translator = InputTranslator::new(&config.input_config);
+    let started = Instant::now();
+    let inputs: Vec<RecordedInput> = recording
+        .wheel
+        .iter()
+        .map(|wheel| {
+            if let Some(range) = wheel.report.base_range {
+                translator.set_base_range(range);
+            }
+            if let Some(rim) = wheel.report.rim {
+                translator.set_rim(rim);
+            }
+            RecordedInput {
+                timestamp: wheel.timestamp,
+                report: translator.translate_at(wheel.report, started + wheel.timestamp),
+            }
+        })
+        .collect();
+
+    // Wheel and G29 reports are recorded in pairs, so they line up by index
+    let mut replay = SessionReplay { ffb: validate_capture(recording, config), ..Default::default() };
+    for (replayed, recorded) in inputs.iter().zip(&recording.inputs) {
+        replay.compared_inputs += 1;
+        if !same_report(&replayed.report, &recorded.report) {
+            replay.changed_inputs += 1;
+            replay.first_change_micros.get_or_insert(replayed.timestamp.as_micros() as u64);
+        }
+    }
+    replay.inputs = inputs;
+    replay
+}
+
+fn same_report(a: &G29InputReport, b: &G29InputReport) -> bool {
+    (a.steering, a.throttle, a.brake, a.clutch, a.buttons) == (b.steering, b.throttle, b.brake, b.clutch, b.buttons)
+}
+
+impl SessionReplay {
+    /// Summary lines in the units and number format of `presenter`
+    pub fn render(&self, presenter: &Presenter) -> String {
+        let mut text = format!("Wheel reports: {}\n", self.inputs.len());
+        if self.compared_inputs > 0 {
+            text += &format!("  {} of {} translate differently than recorded", self.changed_inputs, self.compared_inputs);
+            match self.first_change_micros {
+                Some(micros) => text += &format!(", first at {}s\n", presenter.number(micros as f64 / 1e6, 3)),
+                None => text.push('\n'),
+            }
+        }
+        text + &self.ffb.render(presenter)
+    }
+}
+
+impl fmt::Display for SessionReplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&Presenter::default()))
+    }
+}
+
+impl ReplaySummary {
+    /// Summary lines in the units and number format of `presenter`
+    pub fn render(&self, presenter: &Presenter) -> String {
+        let force = |value: f32| presenter.force(value / FULL_SCALE as f32);
+
+        let mut lines = vec![
+            format!("Output reports: {}", self.output_reports),
+            format!("FFB effects: {} ({} malformed reports)", self.effects, self.malformed_reports),
+        ];
+        for (kind, count) in &self.effects_by_kind {
+            lines.push(format!("  {:<10} {}", kind, count));
+        }
+        for (kind, count) in &self.disabled_by_kind {
+            lines.push(format!("  {:<10} {} dropped, disabled in config", kind, count));
+        }
+        lines.push(format!("IFORCE commands: {}", self.commands));
+        if self.constant_forces == 0 {
+            lines.push("Constant forces: none".to_string());
+        } else {
+            lines.push(format!("Constant forces: {}", self.constant_forces));
+            lines.push(format!(
+                "  range {} .. {}, mean |force| {}",
+                force(self.min_force as f32),
+                force(self.max_force as f32),
+                force(self.mean_abs_force).trim_start_matches('+')
+            ));
+            lines.push(format!(
+                "  at full scale: {} ({})",
+                self.saturated_forces,
+                presenter.percent(self.saturation_ratio())
+            ));
+            lines.push(format!("Clipped magnitudes: {}", self.clipped));
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+impl fmt::Display for ReplaySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&Presenter::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{HatDirection, ThrustmasterInputReport};
+    use crate::recording::RecordedWheel;
+    use std::time::Duration;
+
+    #[test]
+    fn test_session_replay_matches_its_own_recording() {
+        let config = Config::default();
+        let mut translator = InputTranslator::new(&config.input_config);
+        let started = Instant::now();
+        let mut recording = Recording::default();
+        for i in 0..20u8 {
+            let timestamp = Duration::from_millis(i as u64);
+            let report = ThrustmasterInputReport {
+                steering: i as i16 * 1000,
+                throttle: i * 10,
+                brake: 0,
+                clutch: 0,
+                buttons: (i % 3 == 0) as u32,
+                dpad: HatDirection::Centered,
+                base_range: None,
+                rim: None,
+            };
+            recording.wheel.push(RecordedWheel { timestamp, report });
+            recording.inputs.push(RecordedInput { timestamp, report: translator.translate_at(report, started + timestamp) });
+        }
+
+        let replay = replay_session(&recording, &config);
+        assert_eq!((replay.inputs.len(), replay.compared_inputs, replay.changed_inputs), (20, 20, 0));
+
+        recording.inputs[5].report.throttle ^= 1;
+        let replay = replay_session(&recording, &config);
+        assert_eq!(replay.changed_inputs, 1);
+        assert_eq!(replay.first_change_micros, Some(5_000));
+    }
+}
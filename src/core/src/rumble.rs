@@ -0,0 +1,135 @@
+//! Pedal vibration driven by game telemetry
+//!
+//! Maps driving aid cues from the `TelemetryBus` onto the motors of
+//! vibration-capable Thrustmaster pedals: ABS shakes the brake, traction
+//! control the throttle, and wheel slip beyond the configured threshold either
+//! pedal depending on whether the driver is braking or accelerating.
+//!
+//! The vibration report is unverified on hardware, so the motors stay off
+//! unless the `pedal_vibration` experimental feature is enabled.
+
+use crate::config::PedalRumbleConfig;
+use crate::device::pedals::{PedalVibration, VibrationPedals};
+use crate::error::Result;
+use crate::experimental::{Experimental, Feature};
+use crate::telemetry::{TelemetryBus, TelemetryFrame};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+/// Motors are stopped when telemetry goes quiet for this long (game paused or closed)
+const TELEMETRY_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct PedalRumble {
+    config: PedalRumbleConfig,
+}
+
+impl PedalRumble {
+    pub fn new(config: &PedalRumbleConfig) -> Self {
+        Self { config: config.clone() }
+    }
+
+    /// Motor intensities for the car state in `frame`
+    pub fn vibration(&self, frame: &TelemetryFrame) -> PedalVibration {
+        let mut brake = if frame.abs_active { self.config.abs_intensity } else { 0.0 };
+        let mut throttle = if frame.tc_active { self.config.tc_intensity } else { 0.0 };
+
+        if self.config.slip_threshold > 0.0 {
+            let slip = frame.wheel_slip.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+            if slip > self.config.slip_threshold {
+                let excess = ((slip - self.config.slip_threshold) / self.config.slip_threshold).min(1.0);
+                if frame.brake > frame.throttle {
+                    brake = brake.max(excess * self.config.abs_intensity);
+                } else {
+                    throttle = throttle.max(excess * self.config.tc_intensity);
+                }
+            }
+        }
+
+        PedalVibration {
+            throttle: to_motor(throttle),
+            brake: to_motor(brake),
+            clutch: 0,
+        }
+    }
+}
+
+fn to_motor(intensity: f32) -> u8 {
+    (intensity.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+}
+
+/// Start driving the pedal motors from `bus` if vibration pedals are connected
+pub fn spawn(config: &PedalRumbleConfig, experimental: &Experimental, bus: &TelemetryBus) -> Result<Option<JoinHandle<Result<()>>>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    if !experimental.is_enabled(Feature::PedalVibration) {
+        tracing::warn!("Pedal rumble needs the {} experimental feature, its vibration report is unverified", Feature::PedalVibration);
+        return Ok(None);
+    }
+
+    let Some(pedals) = VibrationPedals::detect(config)? else {
+        tracing::info!("Pedal rumble enabled but no vibration pedals found");
+        return Ok(None);
+    };
+
+    let rumble = PedalRumble::new(config);
+    let interval = Duration::from_millis(config.update_interval_ms as u64);
+    let mut frames = bus.subscribe();
+
+    Ok(Some(tokio::spawn(async move {
+        let mut current = PedalVibration::default();
+        let mut last_write = Instant::now() - interval;
+
+        loop {
+            let target = match tokio::time::timeout(TELEMETRY_TIMEOUT, frames.recv()).await {
+                Ok(Ok(frame)) => rumble.vibration(&frame),
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) => break,
+                Err(_) => PedalVibration::default(),
+            };
+
+            // Stopping the motors is never rate limited
+            let stopping = target == PedalVibration::default();
+            if target != current && (stopping || last_write.elapsed() >= interval) {
+                pedals.send(target)?;
+                current = target;
+                last_write = Instant::now();
+            }
+        }
+
+        pedals.send(PedalVibration::default())?;
+        tracing::info!("Stopped vibration on {}", pedals.name());
+        Ok(())
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_driving_aids_and_slip_shake_the_pedal_in_use() {
+        let rumble = PedalRumble::new(&PedalRumbleConfig { slip_threshold: 0.25, ..PedalRumbleConfig::default() });
+        let quiet = TelemetryFrame::default();
+        assert_eq!(rumble.vibration(&quiet), PedalVibration::default());
+
+        // ABS shakes the brake, traction control the throttle, at their intensities
+        let aids = TelemetryFrame { abs_active: true, tc_active: true, ..TelemetryFrame::default() };
+        assert_eq!(rumble.vibration(&aids), PedalVibration { throttle: 128, brake: 204, clutch: 0 });
+
+        // Slip halfway past the threshold shakes the pressed pedal at half intensity
+        let lockup = TelemetryFrame { brake: 1.0, wheel_slip: [-0.375, 0.0, 0.0, 0.0], ..TelemetryFrame::default() };
+        assert_eq!(rumble.vibration(&lockup), PedalVibration { throttle: 0, brake: 102, clutch: 0 });
+        let wheelspin = TelemetryFrame { throttle: 1.0, wheel_slip: [0.0, 0.0, 0.375, 0.2], ..TelemetryFrame::default() };
+        assert_eq!(rumble.vibration(&wheelspin), PedalVibration { throttle: 64, brake: 0, clutch: 0 });
+        let spin = TelemetryFrame { throttle: 1.0, wheel_slip: [0.0, 0.0, 2.0, 0.0], ..TelemetryFrame::default() };
+        assert_eq!(rumble.vibration(&spin).throttle, 128);
+
+        // An active aid is never weakened by slip, and a zero threshold ignores slip
+        let abs_lockup = TelemetryFrame { abs_active: true, ..lockup.clone() };
+        assert_eq!(rumble.vibration(&abs_lockup).brake, 204);
+        let off = PedalRumble::new(&PedalRumbleConfig::default());
+        assert_eq!(off.vibration(&lockup), PedalVibration::default());
+    }
+}
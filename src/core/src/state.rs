@@ -0,0 +1,81 @@
+//! Consistent snapshots of the running pipeline for read-only consumers
+//!
+//! The translation loops publish what they produce (latest report, force,
+//! active effects) into a `StateStore`; their counters are kept apart in
+//! `stats`, as updating them here would take a lock per report. Monitors, the IPC status
+//! command and exporters load the current `StateSnapshot` as one `Arc`, so
+//! every field they read belongs to the same moment and they never hold a
+//! lock the hot path waits on. Publishing builds the next snapshot off to the
+//! side and swaps it in through an `ArcSwap`; the effect list is shared
+//! between snapshots, so that copy stays cheap.
+
+use crate::device::G29InputReport;
+use crate::device::models::Rim;
+use crate::ffb::ActiveEffectSummary;
+use arc_swap::ArcSwap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Pipeline state at one point in time
+#[derive(Debug, Clone, Default)]
+pub struct StateSnapshot {
+    /// Latest translated report, `None` before the first one
+    pub report: Option<G29InputReport>,
+    /// Time since start when `report` was translated
+    pub report_time: Duration,
+    /// Last constant force magnitude sent to the wheel
+    pub force: i16,
+    /// Rotation range in degrees lock to lock, 0 if not yet known
+    pub steering_range: u16,
+    /// Rim attached to the base, `None` if it does not report one
+    pub rim: Option<Rim>,
+    /// Global FFB gain applied, 0.0 - 1.0
+    pub ffb_gain: f32,
+    pub effects: Arc<[ActiveEffectSummary]>,
+}
+
+#[derive(Default)]
+pub struct StateStore {
+    current: ArcSwap<StateSnapshot>,
+    /// Serializes publishers so no update is lost, readers never take it
+    publish: Mutex<()>,
+}
+
+impl StateStore {
+    /// Current snapshot, lock-free from any thread
+    pub fn load(&self) -> Arc<StateSnapshot> {
+        self.current.load_full()
+    }
+
+    /// Publish a new snapshot derived from the current one
+    ///
+    /// Readers keep seeing the previous snapshot until the swap, never a
+    /// partly updated one.
+    pub fn update(&self, change: impl FnOnce(&mut StateSnapshot)) {
+        let _publish = self.publish.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut next = StateSnapshot::clone(&self.current.load());
+        change(&mut next);
+        self.current.store(Arc::new(next));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loaded_snapshot_is_unaffected_by_later_updates() {
+        let store = StateStore::default();
+        store.update(|state| state.force = 100);
+        let before = store.load();
+
+        store.update(|state| {
+            state.force = -100;
+            state.steering_range = 900;
+        });
+        assert_eq!(before.force, 100);
+        assert_eq!(before.steering_range, 0);
+        assert_eq!(store.load().force, -100);
+        assert_eq!(store.load().steering_range, 900);
+    }
+}
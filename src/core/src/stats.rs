@@ -0,0 +1,222 @@
+//! Pipeline counters that stay off the hot path
+//!
+//! The input and output loops run at up to 1 kHz. Counting every report
+//! straight into shared state would take a lock per tick, so each loop keeps
+//! a `StatsAccumulator` of plain integers and adds it to the shared
+//! `StatsCounters` atomics every `FLUSH_EVERY` events, or when the loop has
+//! nothing else to do. Readers such as the status command load the atomics
+//! without ever blocking a loop; while the loops are busy the totals lag by
+//! less than one flush.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Events a loop accumulates before adding them to the shared counters
+pub const FLUSH_EVERY: u32 = 100;
+
+/// Upper bounds of the input tick duration buckets in microseconds, a last bucket takes the rest
+pub const TICK_BUCKETS_US: [u64; 8] = [50, 100, 250, 500, 1_000, 2_000, 5_000, 10_000];
+
+const TICK_BUCKET_COUNT: usize = TICK_BUCKETS_US.len() + 1;
+
+fn tick_bucket(duration: Duration) -> usize {
+    let micros = duration.as_micros() as u64;
+    TICK_BUCKETS_US.iter().position(|&bound| micros <= bound).unwrap_or(TICK_BUCKETS_US.len())
+}
+
+/// Counters accumulated since the pipeline started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub input_reports: u64,
+    pub ffb_effects: u64,
+    pub ffb_commands: u64,
+    /// Force magnitudes the gains or force limit pushed past full scale
+    pub clipped_forces: u64,
+    /// Warnings logged by the translation loops
+    pub warnings: u64,
+    /// Repeated warnings folded into summaries instead of logged
+    pub suppressed_warnings: u64,
+    /// Translated reports the virtual G29 did not accept
+    pub dropped_reports: u64,
+    /// FFB commands the wheel did not accept
+    pub dropped_ffb_commands: u64,
+    /// Times the pipeline was started again, e.g. by the supervisor after the wheel went away
+    pub restarts: u64,
+    /// Input ticks per duration bucket, see `TICK_BUCKETS_US`
+    pub tick_buckets: [u64; TICK_BUCKET_COUNT],
+    /// Time spent in all counted input ticks
+    pub tick_total: Duration,
+}
+
+/// Shared totals, written by flushes and read by anyone
+#[derive(Debug, Default)]
+pub struct StatsCounters {
+    input_reports: AtomicU64,
+    ffb_effects: AtomicU64,
+    ffb_commands: AtomicU64,
+    clipped_forces: AtomicU64,
+    warnings: AtomicU64,
+    suppressed_warnings: AtomicU64,
+    dropped_reports: AtomicU64,
+    dropped_ffb_commands: AtomicU64,
+    pipeline_starts: AtomicU64,
+    tick_buckets: [AtomicU64; TICK_BUCKET_COUNT],
+    tick_total_ns: AtomicU64,
+}
+
+impl StatsCounters {
+    /// Current totals; each counter is exact, they may be from different flushes
+    pub fn load(&self) -> PipelineStats {
+        PipelineStats {
+            input_reports: self.input_reports.load(Ordering::Relaxed),
+            ffb_effects: self.ffb_effects.load(Ordering::Relaxed),
+            ffb_commands: self.ffb_commands.load(Ordering::Relaxed),
+            clipped_forces: self.clipped_forces.load(Ordering::Relaxed),
+            warnings: self.warnings.load(Ordering::Relaxed),
+            suppressed_warnings: self.suppressed_warnings.load(Ordering::Relaxed),
+            dropped_reports: self.dropped_reports.load(Ordering::Relaxed),
+            dropped_ffb_commands: self.dropped_ffb_commands.load(Ordering::Relaxed),
+            restarts: self.pipeline_starts.load(Ordering::Relaxed).saturating_sub(1),
+            tick_buckets: std::array::from_fn(|i| self.tick_buckets[i].load(Ordering::Relaxed)),
+            tick_total: Duration::from_nanos(self.tick_total_ns.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Count a start of the pipeline, every one after the first is a restart
+    pub fn record_start(&self) {
+        self.pipeline_starts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Replace the warning totals, which the log limiter already keeps
+    pub fn set_warnings(&self, warnings: u64, suppressed: u64) {
+        self.warnings.store(warnings, Ordering::Relaxed);
+        self.suppressed_warnings.store(suppressed, Ordering::Relaxed);
+    }
+}
+
+/// Counts of one loop since its last flush
+#[derive(Debug)]
+pub struct StatsAccumulator {
+    counters: Arc<StatsCounters>,
+    input_reports: u64,
+    ffb_effects: u64,
+    ffb_commands: u64,
+    /// Latest total of the FFB engine, not a delta
+    clipped_forces: Option<u64>,
+    dropped_reports: u64,
+    dropped_ffb_commands: u64,
+    tick_buckets: [u64; TICK_BUCKET_COUNT],
+    tick_total: Duration,
+    pending: u32,
+}
+
+impl StatsAccumulator {
+    pub fn new(counters: Arc<StatsCounters>) -> Self {
+        Self {
+            counters,
+            input_reports: 0,
+            ffb_effects: 0,
+            ffb_commands: 0,
+            clipped_forces: None,
+            dropped_reports: 0,
+            dropped_ffb_commands: 0,
+            tick_buckets: [0; TICK_BUCKET_COUNT],
+            tick_total: Duration::ZERO,
+            pending: 0,
+        }
+    }
+
+    pub fn add_input_report(&mut self) {
+        self.input_reports += 1;
+        self.event();
+    }
+
+    /// Account an FFB update that sent `commands` commands, `translated` if the game caused it
+    pub fn add_ffb(&mut self, translated: bool, commands: usize, clipped_total: u64) {
+        self.ffb_effects += translated as u64;
+        self.ffb_commands += commands as u64;
+        self.clipped_forces = Some(clipped_total);
+        self.event();
+    }
+
+    pub fn add_dropped_report(&mut self) {
+        self.dropped_reports += 1;
+        self.event();
+    }
+
+    pub fn add_dropped_ffb_command(&mut self) {
+        self.dropped_ffb_commands += 1;
+        self.event();
+    }
+
+    /// Account the duration of an input tick, flushed with the report it carried
+    pub fn add_tick(&mut self, duration: Duration) {
+        self.tick_buckets[tick_bucket(duration)] += 1;
+        self.tick_total += duration;
+    }
+
+    fn event(&mut self) {
+        self.pending += 1;
+        if self.pending >= FLUSH_EVERY {
+            self.flush();
+        }
+    }
+
+    /// Add everything counted so far to the shared counters
+    pub fn flush(&mut self) {
+        if self.pending == 0 && self.tick_buckets.iter().all(|&count| count == 0) {
+            return;
+        }
+        let counters = &self.counters;
+        counters.input_reports.fetch_add(std::mem::take(&mut self.input_reports), Ordering::Relaxed);
+        counters.ffb_effects.fetch_add(std::mem::take(&mut self.ffb_effects), Ordering::Relaxed);
+        counters.ffb_commands.fetch_add(std::mem::take(&mut self.ffb_commands), Ordering::Relaxed);
+        if let Some(clipped) = self.clipped_forces.take() {
+            counters.clipped_forces.store(clipped, Ordering::Relaxed);
+        }
+        counters.dropped_reports.fetch_add(std::mem::take(&mut self.dropped_reports), Ordering::Relaxed);
+        counters.dropped_ffb_commands.fetch_add(std::mem::take(&mut self.dropped_ffb_commands), Ordering::Relaxed);
+        for (counter, count) in counters.tick_buckets.iter().zip(&mut self.tick_buckets) {
+            if *count > 0 {
+                counter.fetch_add(std::mem::take(count), Ordering::Relaxed);
+            }
+        }
+        counters.tick_total_ns.fetch_add(std::mem::take(&mut self.tick_total).as_nanos() as u64, Ordering::Relaxed);
+        self.pending = 0;
+    }
+}
+
+impl Drop for StatsAccumulator {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulated_counts_reach_the_counters_on_flush() {
+        let counters = Arc::new(StatsCounters::default());
+        let mut input = StatsAccumulator::new(counters.clone());
+        let mut output = StatsAccumulator::new(counters.clone());
+
+        for _ in 0..FLUSH_EVERY - 1 {
+            input.add_input_report();
+        }
+        output.add_ffb(true, 3, 7);
+        assert_eq!(counters.load(), PipelineStats::default());
+
+        input.add_input_report();
+        assert_eq!(counters.load().input_reports, FLUSH_EVERY as u64);
+        output.flush();
+        let stats = counters.load();
+        assert_eq!((stats.ffb_effects, stats.ffb_commands, stats.clipped_forces), (1, 3, 7));
+
+        input.add_input_report();
+        drop(input);
+        assert_eq!(counters.load().input_reports, FLUSH_EVERY as u64 + 1);
+    }
+}
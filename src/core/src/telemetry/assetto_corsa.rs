@@ -0,0 +1,315 @@
+//! Assetto Corsa and Assetto Corsa Competizione UDP telemetry
+//!
+//! AC exposes the "remote telemetry" protocol on port 9996: the client sends a
+//! handshake, then subscribes to per-physics-step `RTCarInfo` updates.
+//! ACC only exposes the broadcasting protocol on port 9000, which requires an
+//! application registration and reports gear and speed for every car.
+
+use crate::config::{AcVariant, AssettoCorsaConfig};
+use crate::error::{TranslatorError, Result};
+use crate::telemetry::{TelemetryBus, TelemetryFrame, TelemetrySource};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+// AC remote telemetry operations
+const AC_HANDSHAKE: i32 = 0;
+const AC_SUBSCRIBE_UPDATE: i32 = 1;
+const AC_DISMISS: i32 = 3;
+const AC_HANDSHAKE_RESPONSE_SIZE: usize = 408;
+const AC_CAR_INFO_SIZE: usize = 328;
+
+// ACC broadcasting protocol
+const ACC_PROTOCOL_VERSION: u8 = 4;
+const ACC_REGISTER_COMMAND_APPLICATION: u8 = 1;
+const ACC_REGISTRATION_RESULT: u8 = 1;
+const ACC_REALTIME_UPDATE: u8 = 2;
+const ACC_REALTIME_CAR_UPDATE: u8 = 3;
+
+/// Give up on a session after this long without any packet and handshake again
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct AssettoCorsaProvider {
+    config: AssettoCorsaConfig,
+    focused_car: Option<u16>,
+}
+
+impl AssettoCorsaProvider {
+    pub fn new(config: &AssettoCorsaConfig) -> Self {
+        Self {
+            config: config.clone(),
+            focused_car: None,
+        }
+    }
+
+    /// Connect to the game and publish frames until the provider is dropped
+    pub async fn run(&mut self, bus: &TelemetryBus) -> Result<()> {
+        loop {
+            let result = match self.config.variant {
+                AcVariant::AssettoCorsa => self.run_ac_session(bus).await,
+                AcVariant::Competizione => self.run_acc_session(bus).await,
+            };
+
+            match result {
+                Err(TranslatorError::Timeout) => {
+                    tracing::debug!("No telemetry from {:?}, retrying handshake", self.config.variant);
+                }
+                Err(e) => {
+                    tracing::warn!("{:?} telemetry session ended: {}", self.config.variant, e);
+                    tokio::time::sleep(RECEIVE_TIMEOUT).await;
+                }
+                Ok(()) => return Ok(()),
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket
+            .connect((self.config.host.as_str(), self.config.port))
+            .await?;
+        Ok(socket)
+    }
+
+    async fn run_ac_session(&mut self, bus: &TelemetryBus) -> Result<()> {
+        let socket = self.connect().await?;
+        let mut buf = [0u8; 512];
+
+        socket.send(&ac_handshaker(AC_HANDSHAKE)).await?;
+        let len = recv_with_timeout(&socket, &mut buf).await?;
+        let car_name = parse_ac_handshake(&buf[..len])?;
+        tracing::info!("Connected to Assetto Corsa telemetry (car: {})", car_name);
+
+        socket.send(&ac_handshaker(AC_SUBSCRIBE_UPDATE)).await?;
+
+        let result = loop {
+            let len = match recv_with_timeout(&socket, &mut buf).await {
+                Ok(len) => len,
+                Err(e) => break Err(e),
+            };
+
+            if let Some(frame) = self.parse_ac_car_info(&buf[..len]) {
+                bus.publish(frame);
+            }
+        };
+
+        // Tell the game to stop streaming to this port
+        let _ = socket.send(&ac_handshaker(AC_DISMISS)).await;
+        result
+    }
+
+    fn parse_ac_car_info(&self, data: &[u8]) -> Option<TelemetryFrame> {
+        if data.len() < AC_CAR_INFO_SIZE || data[0] != b'a' {
+            return None;
+        }
+
+        let f32_at = |offset: usize| {
+            f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+        };
+        let i32_at = |offset: usize| {
+            i32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+        };
+
+        let mut wheel_slip = [0.0; 4];
+        for (i, slip) in wheel_slip.iter_mut().enumerate() {
+            *slip = f32_at(132 + i * 4); // slipRatio[4]
+        }
+
+        Some(TelemetryFrame {
+            source: TelemetrySource::AssettoCorsa,
+            rpm: f32_at(68),
+            max_rpm: self.config.max_rpm,
+            // AC reports 0 = reverse, 1 = neutral, 2 = first
+            gear: (i32_at(76) - 1).clamp(-1, i8::MAX as i32) as i8,
+            speed_kmh: f32_at(8),
+            throttle: f32_at(56),
+            brake: f32_at(60),
+            abs_active: data[21] != 0,
+            tc_active: data[22] != 0,
+            limiter_active: data[25] != 0,
+            wheel_slip,
+            ..TelemetryFrame::default()
+        })
+    }
+
+    async fn run_acc_session(&mut self, bus: &TelemetryBus) -> Result<()> {
+        let socket = self.connect().await?;
+        let mut buf = [0u8; 2048];
+
+        socket.send(&self.acc_registration()).await?;
+
+        loop {
+            let len = recv_with_timeout(&socket, &mut buf).await?;
+            if let Some(frame) = self.handle_acc_packet(&buf[..len])? {
+                bus.publish(frame);
+            }
+        }
+    }
+
+    /// Handle one broadcasting packet, returning the frame of the focused car if it carried one
+    fn handle_acc_packet(&mut self, packet: &[u8]) -> Result<Option<TelemetryFrame>> {
+        let Some((&kind, payload)) = packet.split_first() else {
+            return Ok(None);
+        };
+        match kind {
+            ACC_REGISTRATION_RESULT => {
+                if payload.len() < 6 || payload[4] == 0 {
+                    return Err(TranslatorError::telemetry_error(
+                        "ACC rejected broadcasting registration (check connection password)",
+                    ));
+                }
+                tracing::info!("Registered with Assetto Corsa Competizione broadcasting");
+            }
+            ACC_REALTIME_UPDATE if payload.len() >= 18 => {
+                let focused = i32::from_le_bytes([payload[14], payload[15], payload[16], payload[17]]);
+                self.focused_car = u16::try_from(focused).ok();
+            }
+            ACC_REALTIME_CAR_UPDATE => return Ok(self.parse_acc_car_update(payload)),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn acc_registration(&self) -> Vec<u8> {
+        let mut packet = vec![ACC_REGISTER_COMMAND_APPLICATION, ACC_PROTOCOL_VERSION];
+        write_acc_string(&mut packet, "tm-g29");
+        write_acc_string(&mut packet, &self.config.connection_password);
+        packet.extend_from_slice(&(self.config.update_interval_ms as i32).to_le_bytes());
+        write_acc_string(&mut packet, ""); // Command password, we never send commands
+        packet
+    }
+
+    fn parse_acc_car_update(&self, data: &[u8]) -> Option<TelemetryFrame> {
+        if data.len() < 21 {
+            return None;
+        }
+
+        let car_index = u16::from_le_bytes([data[0], data[1]]);
+        if self.focused_car != Some(car_index) {
+            return None;
+        }
+
+        Some(TelemetryFrame {
+            source: TelemetrySource::AssettoCorsaCompetizione,
+            max_rpm: self.config.max_rpm,
+            // Offset by 2 so that reverse is -1 and neutral is 0
+            gear: (data[5] as i16 - 2) as i8,
+            speed_kmh: u16::from_le_bytes([data[19], data[20]]) as f32,
+            ..TelemetryFrame::default()
+        })
+    }
+}
+
+/// Car name from the AC handshake response
+fn parse_ac_handshake(data: &[u8]) -> Result<String> {
+    if data.len() < AC_HANDSHAKE_RESPONSE_SIZE {
+        return Err(TranslatorError::telemetry_error(format!(
+            "AC handshake response too short: {} bytes", data.len()
+        )));
+    }
+    Ok(read_utf16(&data[0..100]))
+}
+
+fn ac_handshaker(operation: i32) -> [u8; 12] {
+    let mut packet = [0u8; 12];
+    packet[0..4].copy_from_slice(&1i32.to_le_bytes()); // identifier: 1 = desktop client
+    packet[4..8].copy_from_slice(&1i32.to_le_bytes()); // version
+    packet[8..12].copy_from_slice(&operation.to_le_bytes());
+    packet
+}
+
+fn write_acc_string(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    packet.extend_from_slice(value.as_bytes());
+}
+
+fn read_utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0 && unit != u16::from(b'%'))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+async fn recv_with_timeout(socket: &UdpSocket, buf: &mut [u8]) -> Result<usize> {
+    match tokio::time::timeout(RECEIVE_TIMEOUT, socket.recv(buf)).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(TranslatorError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(variant: AcVariant) -> AssettoCorsaProvider {
+        AssettoCorsaProvider::new(&AssettoCorsaConfig { variant, max_rpm: Some(8000.0), ..AssettoCorsaConfig::default() })
+    }
+
+    #[test]
+    fn test_ac_handshake_response_names_the_car() {
+        let mut response = vec![0u8; AC_HANDSHAKE_RESPONSE_SIZE];
+        for (i, unit) in "ks_bmw_m3_e30%%%".encode_utf16().enumerate() {
+            response[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(parse_ac_handshake(&response).unwrap(), "ks_bmw_m3_e30");
+        assert!(parse_ac_handshake(&response[..200]).is_err());
+    }
+
+    #[test]
+    fn test_ac_car_info_fixture_parses() {
+        let mut packet = vec![0u8; AC_CAR_INFO_SIZE];
+        packet[0] = b'a';
+        let mut put = |offset: usize, bytes: [u8; 4]| packet[offset..offset + 4].copy_from_slice(&bytes);
+        put(8, 142.5f32.to_le_bytes());
+        put(56, 0.75f32.to_le_bytes());
+        put(60, 0.25f32.to_le_bytes());
+        put(68, 6500.0f32.to_le_bytes());
+        put(76, 4i32.to_le_bytes());
+        put(140, (-0.3f32).to_le_bytes());
+        packet[21] = 1;
+        packet[25] = 1;
+
+        let frame = provider(AcVariant::AssettoCorsa).parse_ac_car_info(&packet).expect("car info");
+        assert_eq!(frame.source, TelemetrySource::AssettoCorsa);
+        assert_eq!((frame.speed_kmh, frame.rpm, frame.max_rpm), (142.5, 6500.0, Some(8000.0)));
+        assert_eq!((frame.throttle, frame.brake), (0.75, 0.25));
+        assert_eq!(frame.gear, 3);
+        assert!(frame.abs_active && !frame.tc_active && frame.limiter_active);
+        assert_eq!(frame.wheel_slip, [0.0, 0.0, -0.3, 0.0]);
+
+        assert!(provider(AcVariant::AssettoCorsa).parse_ac_car_info(&packet[..AC_CAR_INFO_SIZE - 1]).is_none());
+        packet[0] = b'x';
+        assert!(provider(AcVariant::AssettoCorsa).parse_ac_car_info(&packet).is_none());
+    }
+
+    #[test]
+    fn test_acc_broadcasting_fixtures_follow_the_focused_car() {
+        let mut provider = provider(AcVariant::Competizione);
+
+        // Registration result: connection ID, success, read-only, error message
+        let mut rejected = vec![ACC_REGISTRATION_RESULT, 7, 0, 0, 0, 0, 0, 0, 0];
+        assert!(provider.handle_acc_packet(&rejected).is_err());
+        rejected[5] = 1;
+        assert!(provider.handle_acc_packet(&rejected).unwrap().is_none());
+
+        // Car update: car index, driver index, driver count, gear, position, yaw, location, km/h
+        let mut car_update = vec![ACC_REALTIME_CAR_UPDATE, 12, 0, 0, 0, 1, 5];
+        car_update.extend_from_slice(&[0; 13]);
+        car_update.extend_from_slice(&212u16.to_le_bytes());
+        assert!(provider.handle_acc_packet(&car_update).unwrap().is_none());
+
+        // Realtime update: event, session, type, phase, times, then the focused car
+        let mut realtime = vec![ACC_REALTIME_UPDATE];
+        realtime.extend_from_slice(&[0; 14]);
+        realtime.extend_from_slice(&12i32.to_le_bytes());
+        assert!(provider.handle_acc_packet(&realtime).unwrap().is_none());
+        assert_eq!(provider.focused_car, Some(12));
+
+        let frame = provider.handle_acc_packet(&car_update).unwrap().expect("focused car frame");
+        assert_eq!(frame.source, TelemetrySource::AssettoCorsaCompetizione);
+        assert_eq!((frame.gear, frame.speed_kmh, frame.max_rpm), (3, 212.0, Some(8000.0)));
+        assert!(provider.handle_acc_packet(&car_update[..20]).unwrap().is_none());
+        assert!(provider.handle_acc_packet(&[]).unwrap().is_none());
+    }
+}
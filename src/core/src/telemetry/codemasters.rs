@@ -0,0 +1,343 @@
+//! Codemasters / EA UDP telemetry (F1 20xx and Dirt Rally 2.0)
+//!
+//! Both games push telemetry to a configured UDP port, so unlike Assetto Corsa
+//! there is no handshake: providers bind a socket and decode whatever arrives.
+//! F1 packets carry a `packetFormat` year in their header which selects the
+//! header and per-car struct layouts. Dirt Rally 2.0 uses the legacy
+//! Codemasters float array (`extradata="3"` in hardware_settings_config.xml).
+
+use crate::config::UdpListenerConfig;
+use crate::error::Result;
+use crate::telemetry::{RaceFlag, TelemetryBus, TelemetryFrame, TelemetrySource};
+use tokio::net::UdpSocket;
+
+const F1_PACKET_CAR_TELEMETRY: u8 = 6;
+const F1_PACKET_CAR_STATUS: u8 = 7;
+const F1_MAX_CARS: usize = 22;
+
+/// Packet layout differences between F1 game years
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum F1PacketFormat {
+    Season2020,
+    Season2021,
+    Season2022,
+    Season2023,
+    Season2024,
+}
+
+impl F1PacketFormat {
+    pub fn from_header(packet_format: u16) -> Option<Self> {
+        match packet_format {
+            2020 => Some(Self::Season2020),
+            2021 => Some(Self::Season2021),
+            2022 => Some(Self::Season2022),
+            2023 => Some(Self::Season2023),
+            // Newer titles keep the 2024 layout until proven otherwise
+            year if year >= 2024 => Some(Self::Season2024),
+            _ => None,
+        }
+    }
+
+    fn header_size(self) -> usize {
+        match self {
+            Self::Season2020 | Self::Season2021 | Self::Season2022 => 24,
+            Self::Season2023 | Self::Season2024 => 29, // gameYear and overallFrameIdentifier added
+        }
+    }
+
+    fn packet_id_offset(self) -> usize {
+        match self {
+            Self::Season2020 | Self::Season2021 | Self::Season2022 => 5,
+            Self::Season2023 | Self::Season2024 => 6,
+        }
+    }
+
+    fn player_index_offset(self) -> usize {
+        self.header_size() - 2
+    }
+
+    fn car_telemetry_size(self) -> usize {
+        match self {
+            Self::Season2020 => 58,
+            _ => 60, // revLightsBitValue added in 2021
+        }
+    }
+
+    fn car_status_size(self) -> usize {
+        match self {
+            Self::Season2020 => 60,
+            Self::Season2021 | Self::Season2022 => 47,
+            Self::Season2023 | Self::Season2024 => 55,
+        }
+    }
+
+    fn fia_flags_offset(self) -> usize {
+        match self {
+            Self::Season2020 => 42,
+            _ => 28,
+        }
+    }
+}
+
+pub struct F1Provider {
+    config: UdpListenerConfig,
+    max_rpm: Option<f32>,
+    flag: RaceFlag,
+}
+
+impl F1Provider {
+    pub fn new(config: &UdpListenerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            max_rpm: None,
+            flag: RaceFlag::None,
+        }
+    }
+
+    /// Listen for F1 packets and publish frames until the socket fails
+    pub async fn run(&mut self, bus: &TelemetryBus) -> Result<()> {
+        let socket = UdpSocket::bind((self.config.bind_address.as_str(), self.config.port)).await?;
+        tracing::info!("Listening for F1 telemetry on {}:{}", self.config.bind_address, self.config.port);
+
+        let mut buf = [0u8; 2048];
+        let mut unknown_format_logged = false;
+
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            if len < 2 {
+                continue;
+            }
+
+            let packet_format = u16::from_le_bytes([buf[0], buf[1]]);
+            let Some(format) = F1PacketFormat::from_header(packet_format) else {
+                if !unknown_format_logged {
+                    tracing::warn!("Unsupported F1 packet format {}, ignoring", packet_format);
+                    unknown_format_logged = true;
+                }
+                continue;
+            };
+
+            if let Some(frame) = self.handle_packet(format, &buf[..len]) {
+                bus.publish(frame);
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, format: F1PacketFormat, data: &[u8]) -> Option<TelemetryFrame> {
+        let header_size = format.header_size();
+        if data.len() < header_size {
+            return None;
+        }
+
+        let packet_id = data[format.packet_id_offset()];
+        let player = data[format.player_index_offset()] as usize;
+        if player >= F1_MAX_CARS {
+            return None; // 255 while spectating
+        }
+
+        match packet_id {
+            F1_PACKET_CAR_STATUS => {
+                let car = car_slice(data, header_size, format.car_status_size(), player)?;
+                let max_rpm = u16::from_le_bytes([car[17], car[18]]);
+                self.max_rpm = (max_rpm > 0).then_some(max_rpm as f32);
+                self.flag = match car[format.fia_flags_offset()] as i8 {
+                    1 => RaceFlag::Green,
+                    2 => RaceFlag::Blue,
+                    3 => RaceFlag::Yellow,
+                    4 => RaceFlag::Red,
+                    _ => RaceFlag::None,
+                };
+                None
+            }
+            F1_PACKET_CAR_TELEMETRY => {
+                let car = car_slice(data, header_size, format.car_telemetry_size(), player)?;
+                let f32_at = |offset: usize| {
+                    f32::from_le_bytes([car[offset], car[offset + 1], car[offset + 2], car[offset + 3]])
+                };
+                let rpm = u16::from_le_bytes([car[16], car[17]]) as f32;
+
+                Some(TelemetryFrame {
+                    source: TelemetrySource::F1,
+                    rpm,
+                    max_rpm: self.max_rpm,
+                    gear: car[15] as i8,
+                    speed_kmh: u16::from_le_bytes([car[0], car[1]]) as f32,
+                    throttle: f32_at(2),
+                    brake: f32_at(10),
+                    limiter_active: self.max_rpm.map_or(false, |max| rpm >= max * 0.99),
+                    flag: self.flag,
+                    ..TelemetryFrame::default()
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn car_slice(data: &[u8], header_size: usize, car_size: usize, index: usize) -> Option<&[u8]> {
+    let start = header_size + index * car_size;
+    data.get(start..start + car_size)
+}
+
+// Dirt Rally 2.0 float indices (extradata="3")
+const DR2_SPEED: usize = 7;          // m/s
+const DR2_WHEEL_SPEED: usize = 25;   // 4 floats, RL RR FL FR
+const DR2_THROTTLE: usize = 29;
+const DR2_BRAKE: usize = 31;
+const DR2_GEAR: usize = 33;          // 10 = reverse
+const DR2_RPM: usize = 37;           // rpm / 10
+const DR2_MAX_RPM: usize = 63;       // rpm / 10
+const DR2_PACKET_FLOATS: usize = 66;
+
+pub struct DirtRallyProvider {
+    config: UdpListenerConfig,
+}
+
+impl DirtRallyProvider {
+    pub fn new(config: &UdpListenerConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// Listen for Dirt Rally 2.0 packets and publish frames until the socket fails
+    pub async fn run(&mut self, bus: &TelemetryBus) -> Result<()> {
+        let socket = UdpSocket::bind((self.config.bind_address.as_str(), self.config.port)).await?;
+        tracing::info!("Listening for Dirt Rally 2.0 telemetry on {}:{}", self.config.bind_address, self.config.port);
+
+        let mut buf = [0u8; 512];
+        let mut short_packet_logged = false;
+
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            match Self::parse_packet(&buf[..len]) {
+                Some(frame) => bus.publish(frame),
+                None if !short_packet_logged => {
+                    tracing::warn!(
+                        "Dirt Rally packet of {} bytes ignored, set extradata=\"3\" in hardware_settings_config.xml",
+                        len
+                    );
+                    short_packet_logged = true;
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn parse_packet(data: &[u8]) -> Option<TelemetryFrame> {
+        if data.len() < DR2_PACKET_FLOATS * 4 {
+            return None;
+        }
+
+        let float = |index: usize| {
+            let offset = index * 4;
+            f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+        };
+
+        let speed = float(DR2_SPEED);
+        let mut wheel_slip = [0.0; 4];
+        if speed > 1.0 {
+            // Reorder RL RR FL FR into FL FR RL RR
+            for (slot, index) in [2, 3, 0, 1].into_iter().enumerate() {
+                wheel_slip[slot] = (float(DR2_WHEEL_SPEED + index) - speed) / speed;
+            }
+        }
+
+        let gear = match float(DR2_GEAR) as i32 {
+            10 => -1,
+            gear => gear.clamp(0, i8::MAX as i32) as i8,
+        };
+        let max_rpm = float(DR2_MAX_RPM) * 10.0;
+
+        Some(TelemetryFrame {
+            source: TelemetrySource::DirtRally2,
+            rpm: float(DR2_RPM) * 10.0,
+            max_rpm: (max_rpm > 0.0).then_some(max_rpm),
+            gear,
+            speed_kmh: speed * 3.6,
+            throttle: float(DR2_THROTTLE),
+            brake: float(DR2_BRAKE),
+            wheel_slip,
+            ..TelemetryFrame::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// F1 packet of `format` with every car zeroed but the player's, filled by `car`
+    fn f1_packet(format: F1PacketFormat, year: u16, packet_id: u8, car_size: usize, car: impl FnOnce(&mut [u8])) -> Vec<u8> {
+        let player = 3;
+        let header_size = format.header_size();
+        let mut packet = vec![0u8; header_size + F1_MAX_CARS * car_size];
+        packet[0..2].copy_from_slice(&year.to_le_bytes());
+        packet[format.packet_id_offset()] = packet_id;
+        packet[format.player_index_offset()] = player as u8;
+        car(&mut packet[header_size + player * car_size..][..car_size]);
+        packet
+    }
+
+    #[test]
+    fn test_f1_status_and_telemetry_fixtures_parse() {
+        assert_eq!(F1PacketFormat::from_header(2019), None);
+        assert_eq!(F1PacketFormat::from_header(2025), Some(F1PacketFormat::Season2024));
+
+        for (year, format) in [(2020, F1PacketFormat::Season2020), (2023, F1PacketFormat::Season2023)] {
+            let mut provider = F1Provider::new(&UdpListenerConfig::f1_default());
+            let status = f1_packet(format, year, F1_PACKET_CAR_STATUS, format.car_status_size(), |car| {
+                car[17..19].copy_from_slice(&8000u16.to_le_bytes());
+                car[format.fia_flags_offset()] = 2;
+            });
+            assert!(provider.handle_packet(format, &status).is_none());
+            assert_eq!((provider.max_rpm, provider.flag), (Some(8000.0), RaceFlag::Blue), "{}", year);
+
+            let telemetry = f1_packet(format, year, F1_PACKET_CAR_TELEMETRY, format.car_telemetry_size(), |car| {
+                car[0..2].copy_from_slice(&250u16.to_le_bytes());
+                car[2..6].copy_from_slice(&0.9f32.to_le_bytes());
+                car[10..14].copy_from_slice(&0.25f32.to_le_bytes());
+                car[15] = 7;
+                car[16..18].copy_from_slice(&7950u16.to_le_bytes());
+            });
+            let frame = provider.handle_packet(format, &telemetry).expect("telemetry frame");
+            assert_eq!(frame.source, TelemetrySource::F1);
+            assert_eq!((frame.speed_kmh, frame.throttle, frame.brake, frame.gear), (250.0, 0.9, 0.25, 7), "{}", year);
+            assert_eq!((frame.rpm, frame.max_rpm, frame.flag), (7950.0, Some(8000.0), RaceFlag::Blue));
+            assert!(frame.limiter_active);
+
+            // Spectating reports no player car
+            let mut spectating = telemetry.clone();
+            spectating[format.player_index_offset()] = 255;
+            assert!(provider.handle_packet(format, &spectating).is_none());
+            let cut = format.header_size() + 3 * format.car_telemetry_size() + 10;
+            assert!(provider.handle_packet(format, &telemetry[..cut]).is_none());
+        }
+    }
+
+    #[test]
+    fn test_dirt_rally_fixture_parses() {
+        let mut packet = vec![0u8; DR2_PACKET_FLOATS * 4];
+        let mut put = |index: usize, value: f32| packet[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        put(DR2_SPEED, 20.0);
+        // Rear wheels RL RR spin 10% faster than the car moves
+        for (offset, wheel_speed) in [22.0, 22.0, 20.0, 20.0].into_iter().enumerate() {
+            put(DR2_WHEEL_SPEED + offset, wheel_speed);
+        }
+        put(DR2_THROTTLE, 1.0);
+        put(DR2_BRAKE, 0.5);
+        put(DR2_GEAR, 10.0);
+        put(DR2_RPM, 650.0);
+        put(DR2_MAX_RPM, 780.0);
+
+        let frame = DirtRallyProvider::parse_packet(&packet).expect("Dirt Rally frame");
+        assert_eq!(frame.source, TelemetrySource::DirtRally2);
+        assert_eq!((frame.rpm, frame.max_rpm, frame.gear), (6500.0, Some(7800.0), -1));
+        assert_eq!((frame.throttle, frame.brake), (1.0, 0.5));
+        assert!((frame.speed_kmh - 72.0).abs() < 0.001);
+        assert_eq!(frame.wheel_slip, [0.0, 0.0, 0.1, 0.1]);
+
+        // extradata="0" packets are too short to carry the floats
+        assert!(DirtRallyProvider::parse_packet(&packet[..DR2_PACKET_FLOATS * 4 - 1]).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Game telemetry ingestion
+//!
+//! Telemetry providers listen to the UDP streams published by racing games and
+//! normalize them into `TelemetryFrame`s on a shared `TelemetryBus`, which is
+//! consumed by the LED and haptic subsystems.
+
+pub mod assetto_corsa;
+pub mod codemasters;
+
+pub use assetto_corsa::AssettoCorsaProvider;
+pub use codemasters::{DirtRallyProvider, F1Provider};
+
+use crate::config::TelemetryConfig;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Race flag currently shown to the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaceFlag {
+    None,
+    Green,
+    Yellow,
+    Blue,
+    Red,
+    White,
+    Checkered,
+    Black,
+}
+
+impl Default for RaceFlag {
+    fn default() -> Self {
+        RaceFlag::None
+    }
+}
+
+/// Game that produced a telemetry frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TelemetrySource {
+    Unknown,
+    AssettoCorsa,
+    AssettoCorsaCompetizione,
+    F1,
+    DirtRally2,
+}
+
+impl Default for TelemetrySource {
+    fn default() -> Self {
+        TelemetrySource::Unknown
+    }
+}
+
+/// Game-independent snapshot of the player's car state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TelemetryFrame {
+    pub source: TelemetrySource,
+    pub rpm: f32,
+    pub max_rpm: Option<f32>,     // Not every game reports the rev limit
+    pub gear: i8,                 // -1 = reverse, 0 = neutral
+    pub speed_kmh: f32,
+    pub throttle: f32,            // 0.0 - 1.0
+    pub brake: f32,               // 0.0 - 1.0
+    pub abs_active: bool,
+    pub tc_active: bool,
+    pub limiter_active: bool,
+    pub wheel_slip: [f32; 4],     // FL, FR, RL, RR slip ratio
+    pub flag: RaceFlag,
+}
+
+impl TelemetryFrame {
+    /// RPM as a fraction of the rev limit, if the limit is known
+    pub fn rpm_fraction(&self) -> Option<f32> {
+        self.max_rpm
+            .filter(|max| *max > 0.0)
+            .map(|max| (self.rpm / max).clamp(0.0, 1.0))
+    }
+}
+
+/// Broadcast channel carrying telemetry frames from providers to consumers
+#[derive(Clone)]
+pub struct TelemetryBus {
+    sender: broadcast::Sender<TelemetryFrame>,
+}
+
+impl TelemetryBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish a frame to all current subscribers
+    pub fn publish(&self, frame: TelemetryFrame) {
+        // No subscribers is not an error, the frame is simply dropped
+        let _ = self.sender.send(frame);
+    }
+
+    /// Subscribe to frames published after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<TelemetryFrame> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for TelemetryBus {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+/// Spawn every provider enabled in the configuration onto the tokio runtime
+///
+/// A provider that stops with an error, e.g. because its UDP port is taken,
+/// logs it. The tasks run until they are aborted.
+pub fn spawn_providers(config: &TelemetryConfig, bus: &TelemetryBus) -> Vec<JoinHandle<Result<()>>> {
+    let mut handles = Vec::new();
+
+    if !config.enabled {
+        return handles;
+    }
+
+    if config.assetto_corsa.enabled {
+        let mut provider = AssettoCorsaProvider::new(&config.assetto_corsa);
+        let bus = bus.clone();
+        handles.push(spawn_provider("Assetto Corsa", async move { provider.run(&bus).await }));
+    }
+
+    if config.f1.enabled {
+        let mut provider = F1Provider::new(&config.f1);
+        let bus = bus.clone();
+        handles.push(spawn_provider("F1", async move { provider.run(&bus).await }));
+    }
+
+    if config.dirt_rally.enabled {
+        let mut provider = DirtRallyProvider::new(&config.dirt_rally);
+        let bus = bus.clone();
+        handles.push(spawn_provider("Dirt Rally 2.0", async move { provider.run(&bus).await }));
+    }
+
+    handles
+}
+
+fn spawn_provider<F>(name: &'static str, provider: F) -> JoinHandle<Result<()>>
+where
+    F: std::future::Future<Output = Result<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let result = provider.await;
+        if let Err(e) = &result {
+            tracing::warn!("{} telemetry stopped: {}", name, e);
+        }
+        result
+    })
+}
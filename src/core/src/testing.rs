@@ -0,0 +1,231 @@
+//! Invariant checks for translation configurations
+//!
+//! These run a configuration through the real `InputTranslator` and verify
+//! properties every sane setup should keep. CI uses them on the shipped
+//! defaults, and forks can call `check_all` on their own configs. With the
+//! `proptest` feature, `strategies` provides generators for reports and
+//! configs to drive the same checks from property-based tests.
+
+use crate::config::InputConfig;
+use crate::convert;
+use crate::device::{HatDirection, ThrustmasterInputReport, SOURCE_BUTTON_COUNT};
+use crate::protocol::InputTranslator;
+use std::collections::HashSet;
+use std::fmt;
+
+/// G29 steering value for a centered wheel
+pub const G29_STEERING_CENTER: u16 = convert::OFFSET_CENTER;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantViolation {
+    pub invariant: &'static str,
+    pub detail: String,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.invariant, self.detail)
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+/// Report with a centered wheel, released pedals and no buttons
+pub fn neutral_report() -> ThrustmasterInputReport {
+    ThrustmasterInputReport {
+        steering: 0,
+        throttle: 0,
+        brake: 0,
+        clutch: 0,
+        buttons: 0,
+        dpad: HatDirection::Centered,
+        base_range: None,
+        rim: None,
+    }
+}
+
+/// Each pedal output must never decrease as the pedal is pressed further
+pub fn check_pedal_curves_monotonic(config: &InputConfig) -> Result<(), InvariantViolation> {
+    let mut translator = InputTranslator::new(config);
+
+    for pedal in ["throttle", "brake", "clutch"] {
+        let mut previous = 0u16;
+        for raw in 0..=u8::MAX {
+            let mut report = neutral_report();
+            match pedal {
+                "throttle" => report.throttle = raw,
+                "brake" => report.brake = raw,
+                _ => report.clutch = raw,
+            }
+
+            let translated = translator.translate(report);
+            let value = match pedal {
+                "throttle" => translated.throttle,
+                "brake" => translated.brake,
+                _ => translated.clutch,
+            };
+
+            if raw > 0 && value < previous {
+                return Err(InvariantViolation {
+                    invariant: "monotonic pedal curve",
+                    detail: format!("{} output drops from {} to {} at raw input {}", pedal, previous, value, raw),
+                });
+            }
+            previous = value;
+        }
+    }
+
+    Ok(())
+}
+
+/// A centered physical wheel must produce the G29 center value
+pub fn check_steering_center(config: &InputConfig) -> Result<(), InvariantViolation> {
+    let mut translator = InputTranslator::new(config);
+    let steering = translator.translate(neutral_report()).steering;
+
+    if steering != G29_STEERING_CENTER {
+        return Err(InvariantViolation {
+            invariant: "steering center",
+            detail: format!("centered wheel maps to {:#06x}, expected {:#06x}", steering, G29_STEERING_CENTER),
+        });
+    }
+
+    Ok(())
+}
+
+/// A 1:1 button map must send each source button to exactly one distinct G29 button
+///
+/// Maps where several sources intentionally share a target are not 1:1 and
+/// are skipped.
+pub fn check_button_bijection(config: &InputConfig) -> Result<(), InvariantViolation> {
+    let targets: HashSet<_> = config.button_mapping.values().collect();
+    if targets.len() != config.button_mapping.len() {
+        return Ok(());
+    }
+
+    let mut translator = InputTranslator::new(config);
+    let mut seen = HashSet::new();
+
+    for &source in config.button_mapping.keys() {
+        if source >= SOURCE_BUTTON_COUNT {
+            continue;
+        }
+        let mut report = neutral_report();
+        report.buttons = 1 << source;
+
+        let pressed = translator.translate(report).buttons & 0x00FF_FFFF;
+        if pressed.count_ones() != 1 {
+            return Err(InvariantViolation {
+                invariant: "button bijection",
+                detail: format!("source button {} produces G29 buttons {:#08x}", source, pressed),
+            });
+        }
+        if !seen.insert(pressed) {
+            return Err(InvariantViolation {
+                invariant: "button bijection",
+                detail: format!("source button {} collides on G29 buttons {:#08x}", source, pressed),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Run every invariant check and collect the violations
+pub fn check_all(config: &InputConfig) -> Vec<InvariantViolation> {
+    [
+        check_pedal_curves_monotonic(config),
+        check_steering_center(config),
+        check_button_bijection(config),
+    ]
+    .into_iter()
+    .filter_map(|result| result.err())
+    .collect()
+}
+
+/// proptest generators for reports and configurations
+#[cfg(feature = "proptest")]
+pub mod strategies {
+    use crate::config::{CurveType, InputConfig};
+    use crate::device::{HatDirection, ThrustmasterInputReport};
+    use proptest::prelude::*;
+
+    pub fn input_report() -> impl Strategy<Value = ThrustmasterInputReport> {
+        (any::<i16>(), any::<u8>(), any::<u8>(), any::<u8>(), any::<u32>(), 0u8..=8).prop_map(
+            |(steering, throttle, brake, clutch, buttons, dpad)| ThrustmasterInputReport {
+                steering,
+                throttle,
+                brake,
+                clutch,
+                buttons,
+                dpad: HatDirection::from_raw(dpad).unwrap_or_default(),
+                base_range: None,
+                rim: None,
+            },
+        )
+    }
+
+    /// Curves that are monotonic by construction
+    pub fn monotonic_curve() -> impl Strategy<Value = CurveType> {
+        prop_oneof![
+            Just(CurveType::Linear),
+            Just(CurveType::Squared),
+            Just(CurveType::Cubed),
+            prop::collection::vec(0.0f32..=1.0, 2..16).prop_map(|mut table| {
+                table.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                CurveType::Custom(table)
+            }),
+        ]
+    }
+
+    pub fn input_config() -> impl Strategy<Value = InputConfig> {
+        (0.0f32..0.2, monotonic_curve(), monotonic_curve(), monotonic_curve()).prop_map(
+            |(deadzone, throttle, brake, clutch)| {
+                let mut config = InputConfig::default();
+                config.axis_tuning.steering.deadzone = deadzone;
+                config.pedal_curves.throttle_curve = throttle;
+                config.pedal_curves.brake_curve = brake;
+                config.pedal_curves.clutch_curve = clutch;
+                config
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CurveType;
+
+    #[test]
+    fn test_default_config_holds_invariants() {
+        assert_eq!(check_all(&InputConfig::default()), vec![]);
+    }
+
+    #[test]
+    fn test_decreasing_custom_curve_is_rejected() {
+        let mut config = InputConfig::default();
+        config.pedal_curves.brake_curve = CurveType::Custom(vec![0.0, 0.8, 0.4, 1.0]);
+        assert!(check_pedal_curves_monotonic(&config).is_err());
+    }
+
+    #[test]
+    fn test_colliding_buttons_skip_bijection_check() {
+        let mut config = InputConfig::default();
+        config.button_mapping.insert(0, 1);
+        assert!(check_button_bijection(&config).is_ok());
+    }
+}
+
+#[cfg(all(test, feature = "proptest"))]
+mod properties {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn prop_generated_configs_hold_invariants(config in strategies::input_config()) {
+            prop_assert_eq!(check_all(&config), vec![]);
+        }
+    }
+}
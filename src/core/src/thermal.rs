@@ -0,0 +1,93 @@
+//! Thermal-style FFB limiter for small wheel motors
+//!
+//! Entry-level bases are sized for short bursts of force, not for an hour of
+//! a heavy car at full steering load. `ThermalLimiter` keeps an exponential
+//! average of the squared output force, which tracks motor heating since it
+//! grows with the square of the current. Past `duty_threshold` the gain
+//! drops linearly, reaching `min_gain` when the average says full force has
+//! been held for the whole window. The gain recovers on its own as the
+//! average cools down.
+
+use crate::config::ThermalLimiterConfig;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct ThermalLimiter {
+    config: ThermalLimiterConfig,
+    /// Averaged squared load, 1.0 = full force held continuously
+    duty: f32,
+    gain: f32,
+}
+
+impl ThermalLimiter {
+    pub fn new(config: &ThermalLimiterConfig) -> Self {
+        Self { config: *config, duty: 0.0, gain: 1.0 }
+    }
+
+    /// Averaged squared load, 1.0 = full force held continuously
+    pub fn duty(&self) -> f32 {
+        self.duty
+    }
+
+    /// Gain to apply on top of the configured ones, 1.0 while below the threshold
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Account `load` (0.0 - 1.0 of full scale) having been output for `elapsed`
+    pub fn accumulate(&mut self, load: f32, elapsed: Duration) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let window = self.config.window_secs.max(0.001);
+        let alpha = 1.0 - (-elapsed.as_secs_f32() / window).exp();
+        let load = load.clamp(0.0, 1.0);
+        self.duty += (load * load - self.duty) * alpha;
+
+        let threshold = self.config.duty_threshold.clamp(0.0, 0.999);
+        let min_gain = self.config.min_gain.clamp(0.0, 1.0);
+        let excess = ((self.duty - threshold) / (1.0 - threshold)).clamp(0.0, 1.0);
+        let gain = 1.0 - (1.0 - min_gain) * excess;
+
+        if gain < 1.0 && self.gain >= 1.0 {
+            tracing::warn!("FFB duty {:.0}% over the thermal limit, reducing force", self.duty * 100.0);
+        } else if gain >= 1.0 && self.gain < 1.0 {
+            tracing::info!("FFB duty back under the thermal limit, full force restored");
+        }
+        self.gain = gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sustained_full_force_reduces_gain_then_recovers() {
+        let config = ThermalLimiterConfig { enabled: true, window_secs: 10.0, duty_threshold: 0.5, min_gain: 0.4 };
+        let mut limiter = ThermalLimiter::new(&config);
+        let tick = Duration::from_millis(100);
+
+        // Short bursts stay under the threshold
+        for _ in 0..20 {
+            limiter.accumulate(1.0, tick);
+        }
+        assert_eq!(limiter.gain(), 1.0);
+
+        for _ in 0..1000 {
+            limiter.accumulate(1.0, tick);
+        }
+        assert!(limiter.duty() > 0.99);
+        assert!((limiter.gain() - 0.4).abs() < 0.01, "gain {}", limiter.gain());
+
+        for _ in 0..1000 {
+            limiter.accumulate(0.0, tick);
+        }
+        assert_eq!(limiter.gain(), 1.0);
+
+        let mut disabled = ThermalLimiter::new(&ThermalLimiterConfig::default());
+        disabled.accumulate(1.0, Duration::from_secs(3600));
+        assert_eq!(disabled.gain(), 1.0);
+    }
+}
@@ -0,0 +1,213 @@
+//! Time budget monitoring for the input translation loop
+//!
+//! Each tick of the input loop is split into stages and timed. When ticks
+//! repeatedly take longer than the configured budget, the monitor produces a
+//! `BudgetReport` with the average and worst stage breakdown of the slow
+//! ticks, so an overloaded host (a Raspberry Pi running a recorder and
+//! telemetry, say) shows which stage is eating the time.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Ticks over which overruns are counted
+pub const BUDGET_WINDOW_TICKS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading the wheel and auxiliary devices
+    Read,
+    /// Runtime actions, button mapping, curves
+    Translate,
+    /// State store, session recorder
+    Publish,
+    /// Writing to the virtual device
+    Send,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [Stage::Read, Stage::Translate, Stage::Publish, Stage::Send];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Read => "read",
+            Stage::Translate => "translate",
+            Stage::Publish => "publish",
+            Stage::Send => "send",
+        }
+    }
+}
+
+/// Time spent in each stage of one tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TickBreakdown {
+    stages: [Duration; 4],
+}
+
+impl TickBreakdown {
+    pub fn stage(&self, stage: Stage) -> Duration {
+        self.stages[stage as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().sum()
+    }
+
+    fn add(&mut self, other: &TickBreakdown) {
+        for (sum, stage) in self.stages.iter_mut().zip(other.stages) {
+            *sum += stage;
+        }
+    }
+
+    fn divided(&self, count: u32) -> TickBreakdown {
+        TickBreakdown { stages: self.stages.map(|stage| stage / count.max(1)) }
+    }
+}
+
+impl fmt::Display for TickBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, stage) in Stage::ALL.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}={}us", stage.name(), self.stage(stage).as_micros())?;
+        }
+        Ok(())
+    }
+}
+
+/// Times the stages of one tick as it progresses
+pub struct TickTimer {
+    last: Instant,
+    breakdown: TickBreakdown,
+}
+
+impl TickTimer {
+    pub fn start() -> Self {
+        Self { last: Instant::now(), breakdown: TickBreakdown::default() }
+    }
+
+    /// Attribute the time since the previous mark to `stage`
+    pub fn mark(&mut self, stage: Stage) {
+        let now = Instant::now();
+        self.breakdown.stages[stage as usize] += now - self.last;
+        self.last = now;
+    }
+
+    /// Discard the time since the previous mark, e.g. a blocking wait for input
+    pub fn skip(&mut self) {
+        self.last = Instant::now();
+    }
+
+    pub fn finish(self) -> TickBreakdown {
+        self.breakdown
+    }
+}
+
+/// Repeated budget overruns within one window
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetReport {
+    pub budget: Duration,
+    pub overruns: u32,
+    pub ticks: u32,
+    /// Average breakdown of the ticks over budget
+    pub mean: TickBreakdown,
+    pub worst: TickBreakdown,
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} ticks exceeded the {}us budget; mean {} ({}us), worst {} ({}us)",
+            self.overruns,
+            self.ticks,
+            self.budget.as_micros(),
+            self.mean,
+            self.mean.total().as_micros(),
+            self.worst,
+            self.worst.total().as_micros()
+        )
+    }
+}
+
+pub struct TickBudgetMonitor {
+    budget: Duration,
+    threshold: u32,
+    ticks: u32,
+    overruns: u32,
+    overrun_sum: TickBreakdown,
+    worst: TickBreakdown,
+}
+
+impl TickBudgetMonitor {
+    /// Report once `threshold` ticks in a window of `BUDGET_WINDOW_TICKS` exceed `budget`
+    pub fn new(budget: Duration, threshold: u32) -> Self {
+        Self {
+            budget,
+            threshold: threshold.max(1),
+            ticks: 0,
+            overruns: 0,
+            overrun_sum: TickBreakdown::default(),
+            worst: TickBreakdown::default(),
+        }
+    }
+
+    /// Account one tick, returning a report when the window closes over the threshold
+    pub fn observe(&mut self, tick: TickBreakdown) -> Option<BudgetReport> {
+        self.ticks += 1;
+        if tick.total() > self.budget {
+            self.overruns += 1;
+            self.overrun_sum.add(&tick);
+            if tick.total() > self.worst.total() {
+                self.worst = tick;
+            }
+        }
+
+        if self.ticks < BUDGET_WINDOW_TICKS {
+            return None;
+        }
+        let report = (self.overruns >= self.threshold).then(|| BudgetReport {
+            budget: self.budget,
+            overruns: self.overruns,
+            ticks: self.ticks,
+            mean: self.overrun_sum.divided(self.overruns),
+            worst: self.worst,
+        });
+        *self = Self::new(self.budget, self.threshold);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(read_us: u64, send_us: u64) -> TickBreakdown {
+        let mut breakdown = TickBreakdown::default();
+        breakdown.stages[Stage::Read as usize] = Duration::from_micros(read_us);
+        breakdown.stages[Stage::Send as usize] = Duration::from_micros(send_us);
+        breakdown
+    }
+
+    #[test]
+    fn test_report_breaks_down_slow_ticks() {
+        let mut monitor = TickBudgetMonitor::new(Duration::from_micros(500), 10);
+
+        let mut report = None;
+        for i in 0..BUDGET_WINDOW_TICKS {
+            let slow = i % 50 == 0;
+            let sample = if slow { tick(100, if i == 0 { 1900 } else { 900 }) } else { tick(50, 100) };
+            report = monitor.observe(sample).or(report);
+        }
+
+        let report = report.expect("20 overruns exceed the threshold");
+        assert_eq!(report.overruns, 20);
+        assert_eq!(report.worst.stage(Stage::Send), Duration::from_micros(1900));
+        assert_eq!(report.mean.stage(Stage::Send), Duration::from_micros(950));
+        assert_eq!(report.mean.stage(Stage::Read), Duration::from_micros(100));
+
+        for _ in 0..BUDGET_WINDOW_TICKS {
+            assert!(monitor.observe(tick(50, 100)).is_none());
+        }
+    }
+}
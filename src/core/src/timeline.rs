@@ -0,0 +1,205 @@
+//! Effect lifetimes for FFB timeline viewers
+//!
+//! Games often misbehave in ways only visible over time: an effect restarted
+//! every frame, a spring never stopped, a rumble that expires before it is
+//! felt. The FFB engine logs every change of an effect's lifetime as an
+//! `EffectEvent`; the pipeline hands them to `TranslatorControl`, which
+//! numbers them and keeps the most recent ones for the `timeline` control
+//! command.
+//!
+//! On the control channel each event is one line, stable for external tools:
+//!
+//! ```text
+//! effect <seq> <time_ms> <id> <phase> <kind> [key=value ...]
+//! ```
+//!
+//! `seq` increases by one per event, so a gap means events were dropped;
+//! `time_ms` counts from the translator's start. Created and updated events
+//! carry the effect parameters as `key=value` fields.
+
+use crate::error::{Result, TranslatorError};
+use crate::ffb::{EffectKind, EffectType, FfbEffect};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Events the FFB engine holds until they are collected
+pub const ENGINE_TIMELINE_LEN: usize = 256;
+
+/// Point in an effect's lifetime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectPhase {
+    Created,   // First parameters for the block
+    Updated,   // New parameters for a block that already had some
+    Started,
+    Stopped,   // By the game
+    Expired,   // Its duration ran out
+}
+
+impl fmt::Display for EffectPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Started => "started",
+            Self::Stopped => "stopped",
+            Self::Expired => "expired",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for EffectPhase {
+    type Err = TranslatorError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "created" => Ok(Self::Created),
+            "updated" => Ok(Self::Updated),
+            "started" => Ok(Self::Started),
+            "stopped" => Ok(Self::Stopped),
+            "expired" => Ok(Self::Expired),
+            _ => Err(TranslatorError::protocol_error(format!("Unknown effect phase: {}", s))),
+        }
+    }
+}
+
+/// A lifetime change as seen by the FFB engine
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectEvent {
+    pub at: Instant,
+    pub id: u8,
+    pub kind: EffectKind,
+    pub phase: EffectPhase,
+    /// `key=value` fields of the effect, empty but for created and updated
+    pub parameters: String,
+}
+
+impl EffectEvent {
+    pub fn new(at: Instant, effect: &FfbEffect, phase: EffectPhase) -> Self {
+        let parameters = match phase {
+            EffectPhase::Created | EffectPhase::Updated => parameters(effect),
+            _ => String::new(),
+        };
+        Self { at, id: effect.id, kind: EffectKind::of(&effect.effect_type), phase, parameters }
+    }
+}
+
+/// An event as published by `TranslatorControl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineEntry {
+    pub seq: u64,
+    /// Time since the translator started
+    pub time: Duration,
+    pub id: u8,
+    pub kind: EffectKind,
+    pub phase: EffectPhase,
+    pub parameters: String,
+}
+
+impl TimelineEntry {
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "effect {} {} {} {} {:?}",
+            self.seq,
+            self.time.as_millis(),
+            self.id,
+            self.phase,
+            self.kind
+        );
+        if !self.parameters.is_empty() {
+            line.push(' ');
+            line.push_str(&self.parameters);
+        }
+        line
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let malformed = || TranslatorError::protocol_error(format!("Malformed effect event: {}", line.trim_end()));
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 || fields[0] != "effect" {
+            return Err(malformed());
+        }
+        let kind = EffectKind::ALL
+            .into_iter()
+            .find(|kind| format!("{:?}", kind) == fields[5])
+            .ok_or_else(malformed)?;
+
+        Ok(Self {
+            seq: fields[1].parse().map_err(|_| malformed())?,
+            time: Duration::from_millis(fields[2].parse().map_err(|_| malformed())?),
+            id: fields[3].parse().map_err(|_| malformed())?,
+            phase: fields[4].parse()?,
+            kind,
+            parameters: fields[6..].join(" "),
+        })
+    }
+}
+
+/// `key=value` fields describing `effect`
+fn parameters(effect: &FfbEffect) -> String {
+    let mut fields = match &effect.effect_type {
+        EffectType::Constant(constant) => {
+            format!("magnitude={} duration={}", constant.magnitude, constant.duration.to_report())
+        }
+        EffectType::Periodic(periodic) => format!(
+            "waveform={:?} magnitude={} period={} phase={}",
+            periodic.waveform, periodic.magnitude, periodic.period, periodic.phase
+        ),
+        EffectType::Condition(condition) => format!(
+            "positive={} negative={}",
+            condition.positive_coefficient, condition.negative_coefficient
+        ),
+        EffectType::Ramp(ramp) => format!(
+            "start={} end={} duration={}",
+            ramp.start_magnitude, ramp.end_magnitude, ramp.duration.to_report()
+        ),
+    };
+    let envelope = match &effect.effect_type {
+        EffectType::Constant(constant) => constant.envelope,
+        EffectType::Periodic(periodic) => periodic.envelope,
+        EffectType::Ramp(ramp) => ramp.envelope,
+        EffectType::Condition(_) => None,
+    };
+    if let Some(envelope) = envelope {
+        fields.push_str(&format!(
+            " attack={}/{} fade={}/{}",
+            envelope.attack_level, envelope.attack_time, envelope.fade_level, envelope.fade_time
+        ));
+    }
+    fields.push_str(&format!(" gain={}", effect.gain));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffb::ConstantEffect;
+    use crate::units::Milliseconds;
+
+    #[test]
+    fn test_entry_lines_round_trip() {
+        let effect = FfbEffect {
+            id: 3,
+            effect_type: EffectType::Constant(ConstantEffect { magnitude: -1200, duration: Milliseconds::new(500), envelope: None }),
+            gain: 255,
+        };
+        let event = EffectEvent::new(Instant::now(), &effect, EffectPhase::Created);
+        assert_eq!(event.parameters, "magnitude=-1200 duration=500 gain=255");
+
+        let entry = TimelineEntry {
+            seq: 7,
+            time: Duration::from_millis(1234),
+            id: event.id,
+            kind: event.kind,
+            phase: event.phase,
+            parameters: event.parameters,
+        };
+        assert_eq!(entry.to_line(), "effect 7 1234 3 created Constant magnitude=-1200 duration=500 gain=255");
+        assert_eq!(TimelineEntry::parse(&entry.to_line()).unwrap(), entry);
+
+        let stopped = TimelineEntry { phase: EffectPhase::Stopped, parameters: String::new(), ..entry };
+        assert_eq!(TimelineEntry::parse(&stopped.to_line()).unwrap(), stopped);
+        assert!(TimelineEntry::parse("effect 1 2 3 exploded Constant").is_err());
+    }
+}
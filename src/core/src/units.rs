@@ -0,0 +1,263 @@
+//! Unit-safe value types used across the translation pipeline
+//!
+//! Raw HID values come in many widths and signednesses (8-bit pedals, signed
+//! 16-bit steering, offset-centered 16-bit G29 steering, 10-bit G29 pedals).
+//! These newtypes make the unit explicit and keep every conversion in one
+//! checked place instead of ad-hoc casts at each call site.
+
+use crate::convert;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Axis position normalized to -1.0..=1.0 (bipolar axes) or 0.0..=1.0 (pedals)
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NormalizedAxis(f32);
+
+impl NormalizedAxis {
+    pub const CENTER: Self = Self(0.0);
+    pub const MIN: Self = Self(-1.0);
+    pub const MAX: Self = Self(1.0);
+
+    /// Checked constructor, `None` if the value is outside -1.0..=1.0 or NaN
+    pub fn new(value: f32) -> Option<Self> {
+        (-1.0..=1.0).contains(&value).then_some(Self(value))
+    }
+
+    /// Clamp into -1.0..=1.0, mapping NaN to center
+    pub fn saturating(value: f32) -> Self {
+        if value.is_nan() {
+            Self::CENTER
+        } else {
+            Self(value.clamp(-1.0, 1.0))
+        }
+    }
+
+    pub fn value(self) -> f32 {
+        self.0
+    }
+
+    /// Signed 16-bit steering, 0 = center
+    pub fn from_signed_steering(raw: i16) -> Self {
+        // i16::MIN is one step further than i16::MAX, saturate it to full lock
+        Self::saturating(raw as f32 / i16::MAX as f32)
+    }
+
+    /// Unsigned 8-bit pedal, 0 = released
+    pub fn from_pedal_u8(raw: u8) -> Self {
+        Self(raw as f32 / u8::MAX as f32)
+    }
+
+    /// G29 steering, 16-bit with center at 0x8000
+    ///
+    /// The left half spans one step more than the right, full left lock is 0
+    /// and full right lock 0xFFFF.
+    pub fn to_g29_steering(self) -> u16 {
+        let offset = (self.0 * Self::g29_half_span(self.0)).round() as i32 + 0x8000;
+        offset.clamp(0, u16::MAX as i32) as u16
+    }
+
+    /// G29 pedal, 10-bit (0-1023); negative values read as released
+    pub fn to_g29_pedal(self) -> u16 {
+        (self.0.max(0.0) * 1023.0).round() as u16
+    }
+
+    /// Inverse of `to_g29_steering`
+    pub fn from_g29_steering(raw: u16) -> Self {
+        let offset = (raw as i32 - 0x8000) as f32;
+        Self::saturating(offset / Self::g29_half_span(offset))
+    }
+
+    /// Inverse of `to_g29_pedal`
+    pub fn from_g29_pedal(raw: u16) -> Self {
+        Self::saturating(raw as f32 / 1023.0)
+    }
+
+    /// Steps from the G29 steering center to the lock on the side of `value`
+    fn g29_half_span(value: f32) -> f32 {
+        if value < 0.0 {
+            32768.0
+        } else {
+            32767.0
+        }
+    }
+}
+
+/// Physical steering angle in degrees, negative = left of center
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SteeringAngle(f32);
+
+impl SteeringAngle {
+    pub fn from_degrees(degrees: f32) -> Self {
+        Self(degrees)
+    }
+
+    pub fn degrees(self) -> f32 {
+        self.0
+    }
+
+    /// Angle corresponding to an axis position on a wheel with `range_degrees` lock to lock
+    pub fn from_axis(axis: NormalizedAxis, range_degrees: u16) -> Self {
+        Self(convert::normalized_to_degrees(axis.value(), range_degrees))
+    }
+
+    /// Axis position of this angle on a wheel with `range_degrees` lock to lock
+    pub fn to_axis(self, range_degrees: u16) -> NormalizedAxis {
+        NormalizedAxis::saturating(convert::degrees_to_normalized(self.0, range_degrees))
+    }
+}
+
+/// Force in Newtons
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ForceNewton(f32);
+
+impl ForceNewton {
+    pub const fn new(newtons: f32) -> Self {
+        Self(newtons)
+    }
+
+    pub fn newtons(self) -> f32 {
+        self.0
+    }
+
+    /// Ratio of this force to `reference`, 0.0 if the reference is not positive
+    pub fn ratio_to(self, reference: ForceNewton) -> f32 {
+        if reference.0 > 0.0 {
+            self.0 / reference.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Effect duration in milliseconds, or infinite
+///
+/// PID reports carry infinite as 0, which leaves them no way to say 0 ms.
+/// Here 0 is a real 0 ms and infinite is `u16::MAX`, so the longest finite
+/// duration is 65534 ms; `from_report` and `to_report` convert at the edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Milliseconds(u16);
+
+impl Milliseconds {
+    pub const INFINITE: Self = Self(u16::MAX);
+
+    /// Finite duration
+    ///
+    /// 65535 ms is the infinite marker, so it is clamped to the longest finite
+    /// 65534 ms; `try_from` a `Duration` refuses it instead.
+    pub const fn new(millis: u16) -> Self {
+        if millis == u16::MAX {
+            Self(u16::MAX - 1)
+        } else {
+            Self(millis)
+        }
+    }
+
+    /// Duration field of a PID report, 0 = infinite
+    pub const fn from_report(raw: u16) -> Self {
+        if raw == 0 {
+            Self::INFINITE
+        } else {
+            Self::new(raw)
+        }
+    }
+
+    /// Milliseconds of a finite value, `u16::MAX` for infinite
+    pub fn get(self) -> u16 {
+        self.0
+    }
+
+    pub fn is_infinite(self) -> bool {
+        self == Self::INFINITE
+    }
+
+    /// Duration of a finite value, `None` for infinite
+    pub fn as_duration(self) -> Option<Duration> {
+        (!self.is_infinite()).then(|| Duration::from_millis(self.0 as u64))
+    }
+
+    /// Duration field for a report, 0 = infinite
+    ///
+    /// A report cannot carry 0 ms, it is sent as the shortest finite 1 ms.
+    pub fn to_report(self) -> u16 {
+        match self.0 {
+            u16::MAX => 0,
+            millis => millis.max(1),
+        }
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 2] {
+        self.to_report().to_le_bytes()
+    }
+}
+
+impl Default for Milliseconds {
+    fn default() -> Self {
+        Self::INFINITE
+    }
+}
+
+impl TryFrom<Duration> for Milliseconds {
+    type Error = std::num::TryFromIntError;
+
+    /// Finite value of `duration`, an error from 65535 ms on
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        // Offset by one so the infinite marker overflows along with everything above it
+        u16::try_from(duration.as_millis() + 1).map(|millis| Self(millis - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis_constructors_check_or_saturate() {
+        assert_eq!(NormalizedAxis::new(0.5).map(NormalizedAxis::value), Some(0.5));
+        assert_eq!(NormalizedAxis::new(1.0), Some(NormalizedAxis::MAX));
+        assert_eq!(NormalizedAxis::new(1.01), None);
+        assert_eq!(NormalizedAxis::new(f32::NAN), None);
+
+        assert_eq!(NormalizedAxis::saturating(-3.0), NormalizedAxis::MIN);
+        assert_eq!(NormalizedAxis::saturating(f32::INFINITY), NormalizedAxis::MAX);
+        assert_eq!(NormalizedAxis::saturating(f32::NAN), NormalizedAxis::CENTER);
+        assert_eq!(NormalizedAxis::from_signed_steering(i16::MIN), NormalizedAxis::MIN);
+
+        // Full lock reaches both ends of the G29 range and parses back
+        assert_eq!(NormalizedAxis::MIN.to_g29_steering(), 0);
+        assert_eq!(NormalizedAxis::MAX.to_g29_steering(), u16::MAX);
+        assert_eq!(NormalizedAxis::CENTER.to_g29_steering(), 0x8000);
+        assert_eq!(NormalizedAxis::from_g29_steering(0), NormalizedAxis::MIN);
+        assert_eq!(NormalizedAxis::saturating(-0.5).to_g29_pedal(), 0);
+        assert_eq!(NormalizedAxis::from_g29_pedal(2000), NormalizedAxis::MAX);
+        assert_eq!(ForceNewton::new(3.0).ratio_to(ForceNewton::new(0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_zero_milliseconds_is_not_infinite() {
+        let zero = Milliseconds::try_from(Duration::ZERO).unwrap();
+        assert_eq!(zero.get(), 0);
+        assert!(!zero.is_infinite());
+        assert_eq!(zero.as_duration(), Some(Duration::ZERO));
+        // Reports have no 0 ms, the shortest finite duration is sent instead
+        assert_eq!(zero.to_report(), 1);
+
+        assert_eq!(Milliseconds::from_report(0), Milliseconds::INFINITE);
+        assert_eq!(Milliseconds::INFINITE.to_report(), 0);
+        assert_eq!(Milliseconds::INFINITE.as_duration(), None);
+        assert_eq!(Milliseconds::from_report(250).as_duration(), Some(Duration::from_millis(250)));
+        assert_eq!(Milliseconds::new(250).to_le_bytes(), [250, 0]);
+
+        // The checked conversion refuses the infinite marker and beyond, `new` clamps it
+        assert!(Milliseconds::try_from(Duration::from_millis(65_536)).is_err());
+        assert!(Milliseconds::try_from(Duration::from_millis(65_535)).is_err());
+        let longest = Milliseconds::try_from(Duration::from_millis(65_534)).unwrap();
+        assert!(!longest.is_infinite());
+        assert_eq!(longest.to_report(), 65_534);
+        assert_eq!(Milliseconds::new(u16::MAX), longest);
+        assert_eq!(Milliseconds::from_report(u16::MAX), longest);
+    }
+}
@@ -0,0 +1,407 @@
+//! Validation of configuration against the source wheel and the G29 target
+//!
+//! Produces a report of problems found in a loaded configuration. Hard errors
+//! make a configuration unusable and should stop the translator; warnings
+//! describe setups that work but are probably not what the user intended.
+
+use crate::config::{Config, CurveType};
+use crate::device::models::ThrustmasterModel;
+use crate::device::SOURCE_BUTTON_COUNT;
+use crate::ffb::EffectKind;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Number of buttons on the G29
+pub const G29_BUTTON_COUNT: u8 = 24;
+
+/// G29 buttons most games need to be reachable, with their names
+pub const IMPORTANT_G29_BUTTONS: &[(u8, &str)] = &[
+    (0, "Cross"),
+    (2, "Circle"),
+    (4, "right paddle"),
+    (5, "left paddle"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn warn(&mut self, message: String) {
+        self.issues.push(ValidationIssue { severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, message: String) {
+        self.issues.push(ValidationIssue { severity: Severity::Error, message });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            let label = match issue.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            writeln!(f, "  {}: {}", label, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the button mapping of `config` for a source wheel with `source_button_count` buttons
+///
+/// Entries left as the default mapping has them are not checked against the
+/// wheel, the default maps more buttons than the smaller bases have.
+pub fn validate_button_mapping(config: &Config, source_button_count: u8) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mapping = &config.input_config.button_mapping;
+    let defaults = crate::config::InputConfig::default().button_mapping;
+
+    let mut sources: Vec<_> = mapping.keys().copied().collect();
+    sources.sort_unstable();
+
+    for &source in &sources {
+        if source >= source_button_count && defaults.get(&source) != Some(&mapping[&source]) {
+            report.error(format!(
+                "source button {} does not exist, the wheel has {} buttons (0-{})",
+                source,
+                source_button_count,
+                source_button_count.saturating_sub(1)
+            ));
+        }
+
+        let target = mapping[&source];
+        if target >= G29_BUTTON_COUNT {
+            report.error(format!(
+                "source button {} maps to G29 button {}, the G29 has {} buttons (0-{})",
+                source,
+                target,
+                G29_BUTTON_COUNT,
+                G29_BUTTON_COUNT - 1
+            ));
+        }
+    }
+
+    let mut by_target: HashMap<u8, Vec<u8>> = HashMap::new();
+    for &source in &sources {
+        by_target.entry(mapping[&source]).or_default().push(source);
+    }
+    let mut duplicates: Vec<_> = by_target.iter().filter(|(_, sources)| sources.len() > 1).collect();
+    duplicates.sort_unstable_by_key(|(target, _)| **target);
+    for (target, sources) in duplicates {
+        report.warn(format!("G29 button {} is driven by several source buttons {:?}", target, sources));
+    }
+
+    if let Some(toggle) = config.input_config.menu_nudge.toggle_button {
+        if mapping.contains_key(&toggle) {
+            report.warn(format!(
+                "source button {} toggles menu navigation mode and is never sent to the game",
+                toggle
+            ));
+        }
+    }
+
+    for (rim, mapping) in &config.input_config.rim_button_mappings {
+        let mut entries: Vec<_> = mapping.iter().collect();
+        entries.sort_unstable();
+        for (&source, &target) in entries {
+            if source >= source_button_count || target >= G29_BUTTON_COUNT {
+                report.error(format!(
+                    "rim {} maps source button {} to G29 button {}, outside the wheel's 0-{} or the G29's 0-{}",
+                    rim,
+                    source,
+                    target,
+                    source_button_count.saturating_sub(1),
+                    G29_BUTTON_COUNT - 1
+                ));
+            }
+        }
+    }
+
+    if config.shifter_config.enabled {
+        for &button in &config.shifter_config.gear_buttons {
+            if let Some(sources) = by_target.get(&button) {
+                report.warn(format!("G29 button {} is pressed by both a shifter gear and source buttons {:?}", button, sources));
+            }
+        }
+    }
+
+    let mut key_bindings: Vec<_> = config.keyboard_config.bindings.iter().collect();
+    key_bindings.sort_unstable();
+    for (&source, key) in key_bindings {
+        if source >= source_button_count {
+            report.error(format!("source button {} is bound to key {}, the wheel has no such button", source, key));
+        }
+        if let Err(e) = key.parse::<crate::keys::Key>() {
+            report.error(format!("source button {} is bound to an invalid key: {}", source, e));
+        }
+    }
+
+    for mapping in &config.input_config.button_axes {
+        if mapping.button >= source_button_count {
+            report.error(format!("source button {} drives the {:?} axis, the wheel has no such button", mapping.button, mapping.axis));
+        }
+        if !(-1.0..=1.0).contains(&mapping.value) {
+            report.warn(format!("source button {} drives the {:?} axis to {}, clamped to the axis range", mapping.button, mapping.axis, mapping.value));
+        }
+    }
+    for mapping in &config.input_config.axis_buttons {
+        if mapping.g29_button >= G29_BUTTON_COUNT {
+            report.error(format!(
+                "the {:?} axis presses G29 button {}, the G29 has {} buttons (0-{})",
+                mapping.axis,
+                mapping.g29_button,
+                G29_BUTTON_COUNT,
+                G29_BUTTON_COUNT - 1
+            ));
+        }
+        if mapping.threshold.abs() > 1.0 || mapping.threshold == 0.0 {
+            report.warn(format!("the {:?} axis threshold {} for G29 button {} is never crossed or always", mapping.axis, mapping.threshold, mapping.g29_button));
+        }
+    }
+
+    let combos = &config.input_config.combos;
+    for chord in &combos.chords {
+        if chord.buttons.len() < 2 {
+            report.error(format!("chord {:?} needs at least two source buttons", chord.buttons));
+        }
+        if chord.buttons.iter().any(|&source| source >= source_button_count) || chord.g29_button >= G29_BUTTON_COUNT {
+            report.error(format!(
+                "chord {:?} presses G29 button {}, outside the wheel's 0-{} or the G29's 0-{}",
+                chord.buttons,
+                chord.g29_button,
+                source_button_count.saturating_sub(1),
+                G29_BUTTON_COUNT - 1
+            ));
+        }
+    }
+    for long in &combos.long_presses {
+        if long.button >= source_button_count || long.g29_button >= G29_BUTTON_COUNT {
+            report.error(format!(
+                "long press of source button {} presses G29 button {}, outside the wheel's 0-{} or the G29's 0-{}",
+                long.button,
+                long.g29_button,
+                source_button_count.saturating_sub(1),
+                G29_BUTTON_COUNT - 1
+            ));
+        }
+        if !mapping.contains_key(&long.button) {
+            report.warn(format!("source button {} has a long press but no mapping, short presses send nothing", long.button));
+        }
+    }
+
+    let curves = &config.input_config.pedal_curves;
+    for (pedal, curve) in [("throttle", &curves.throttle_curve), ("brake", &curves.brake_curve), ("clutch", &curves.clutch_curve)] {
+        if let CurveType::Spline(points) = curve {
+            if let Some(problem) = crate::curve::spline_problem(points) {
+                report.error(format!("{} curve: {}", pedal, problem));
+            }
+        }
+    }
+
+    for &(button, name) in IMPORTANT_G29_BUTTONS {
+        if !by_target.contains_key(&button) {
+            report.warn(format!("G29 button {} ({}) is not mapped from any source button", button, name));
+        }
+    }
+
+    report
+}
+
+/// Source buttons a report of `model` carries
+pub fn model_button_count(model: ThrustmasterModel) -> u8 {
+    model
+        .layout()
+        .buttons
+        .iter()
+        .map(|field| field.first.saturating_add(field.count))
+        .max()
+        .unwrap_or(0)
+        .min(SOURCE_BUTTON_COUNT)
+}
+
+/// Source buttons of the wheel `config` reads: its pinned model's, else those of the model its PID names
+pub fn source_button_count(config: &Config) -> u8 {
+    let thrustmaster = &config.thrustmaster_config;
+    model_button_count(thrustmaster.model.unwrap_or_else(|| ThrustmasterModel::from_pid(thrustmaster.pid)))
+}
+
+/// Check the button mapping of `config` and of each of its profiles
+///
+/// Each is checked against its own wheel, see `source_button_count`, so a
+/// profile pinning another model is held to that model's buttons. Issues a
+/// profile only inherits from the configuration are reported once.
+pub fn validate_profiles(config: &Config) -> ValidationReport {
+    let mut report = validate_button_mapping(config, source_button_count(config));
+    let profiles = match crate::profile::list(config) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            report.error(format!("profiles: {}", e));
+            return report;
+        }
+    };
+    let inherited = report.issues.clone();
+    for profile in &profiles {
+        let layered = match crate::profile::apply(config, profile) {
+            Ok(layered) => layered,
+            Err(e) => {
+                report.error(e.to_string());
+                continue;
+            }
+        };
+        for issue in validate_button_mapping(&layered, source_button_count(&layered)).issues {
+            if !inherited.contains(&issue) {
+                let message = format!("profile {}: {}", profile.name, issue.message);
+                report.issues.push(ValidationIssue { message, ..issue });
+            }
+        }
+    }
+    report
+}
+
+/// Check what `config` assumes about the wheel against the `model` attached with `pid`
+///
+/// Everything found is a warning: the translator runs, but buttons, pedal
+/// scaling or effects will not behave as the configuration expects.
+pub fn validate_model(config: &Config, model: ThrustmasterModel, pid: u16) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let input = &config.input_config;
+
+    // Buttons, entries left as the default mapping has them are no assumption
+    let buttons = model_button_count(model);
+    let defaults = crate::config::InputConfig::default().button_mapping;
+    let mut used: Vec<u8> = input
+        .button_mapping
+        .iter()
+        .filter(|&(source, target)| defaults.get(source) != Some(target))
+        .map(|(source, _)| source)
+        .chain(input.rim_button_mappings.values().flat_map(|mapping| mapping.keys()))
+        .chain(config.keyboard_config.bindings.keys())
+        .chain(config.ffb_config.panic_button.as_ref())
+        .copied()
+        .filter(|&source| source >= buttons)
+        .collect();
+    used.sort_unstable();
+    used.dedup();
+    if !used.is_empty() {
+        report.warn(format!("source buttons {:?} are mapped but a {} has {} buttons (0-{})", used, model, buttons, buttons.saturating_sub(1)));
+    }
+    if !input.rim_button_mappings.is_empty() && model.layout().rim.is_none() {
+        report.warn(format!("rim_button_mappings never apply, a {} does not report its rim", model));
+    }
+
+    // Pedal resolution follows the report layout the configuration pins
+    if let Some(pinned) = config.thrustmaster_config.model.filter(|&pinned| pinned != model) {
+        let (assumed, actual) = (pinned.layout().pedal_bits, model.layout().pedal_bits);
+        let pedals = if assumed == actual {
+            String::new()
+        } else {
+            format!(", pedals read as {}-bit instead of {}-bit", assumed, actual)
+        };
+        report.warn(format!("model is pinned to {} but a {} is attached{}", pinned, model, pedals));
+    }
+
+    // Rotation range
+    if let Some(applied) = model.clamp_range(input.steering_range).filter(|&applied| applied != input.steering_range) {
+        report.warn(format!("steering_range {}° is outside what a {} accepts, {}° is set", input.steering_range, model, applied));
+    }
+
+    // Effects, only known up front for bases whose descriptor does not list them
+    if let Some(capabilities) = crate::device::models::model_capabilities(pid) {
+        let dropped: Vec<EffectKind> = EffectKind::ALL
+            .into_iter()
+            .filter(|&kind| config.ffb_config.effects.is_enabled(kind) && !capabilities.supports(kind))
+            .collect();
+        if !dropped.is_empty() {
+            report.warn(format!("{:?} effects are enabled but the wheel cannot play them", dropped));
+        }
+    }
+
+    report
+}
+
+/// First profile of `config` without model mismatches, when the configuration itself has some
+pub fn suggest_profile(config: &Config, model: ThrustmasterModel, pid: u16) -> Option<String> {
+    let profiles = crate::profile::list(config).ok()?;
+    profiles
+        .iter()
+        .filter_map(|profile| crate::profile::apply(config, profile).ok().map(|layered| (profile, layered)))
+        .find(|(_, layered)| validate_model(layered, model, pid).is_empty())
+        .map(|(profile, _)| profile.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_t300_profile_on_a_t150_reports_mismatches() {
+        let mut config = Config::default();
+        config.thrustmaster_config.model = Some(ThrustmasterModel::T300rs);
+        config.profiles.insert("t150".to_string(), toml::from_str("[thrustmaster_config]\nmodel = \"T150\"\n").unwrap());
+
+        let report = validate_model(&config, ThrustmasterModel::T150, 0xB677);
+        let messages: Vec<_> = report.warnings().map(|issue| issue.message.as_str()).collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("16-bit instead of 10-bit"));
+        assert_eq!(suggest_profile(&config, ThrustmasterModel::T150, 0xB677).as_deref(), Some("t150"));
+
+        config.input_config.button_mapping.insert(20, 12);
+        let report = validate_model(&config, ThrustmasterModel::T150, 0xB677);
+        assert!(report.issues[0].message.starts_with("source buttons [20]"));
+        assert!(validate_model(&config, ThrustmasterModel::T300rs, 0xB66E).is_empty());
+    }
+
+    #[test]
+    fn test_profiles_are_checked_against_their_own_wheel() {
+        let mut config = Config {
+            source: Some(std::env::temp_dir().join("tm-g29-validation-profiles/config.toml")),
+            ..Config::default()
+        };
+        config.thrustmaster_config.pid = 0xB66E;
+        config.input_config.button_mapping.insert(20, 12);
+        assert_eq!(source_button_count(&config), 26);
+        assert!(!validate_profiles(&config).has_errors());
+
+        // The default mapping's button 13 is no error on a 13-button T150
+        config.profiles.insert("t150".to_string(), toml::from_str("[thrustmaster_config]\nmodel = \"T150\"\n").unwrap());
+        let report = validate_profiles(&config);
+        let errors: Vec<_> = report.errors().map(|issue| issue.message.as_str()).collect();
+        assert_eq!(errors, ["profile t150: source button 20 does not exist, the wheel has 13 buttons (0-12)"]);
+
+        config.thrustmaster_config.pid = 0xB677;
+        let report = validate_profiles(&config);
+        assert_eq!(report.errors().count(), 1);
+        assert!(report.issues[0].message.starts_with("source button 20"));
+    }
+}
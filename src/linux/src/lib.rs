@@ -5,134 +5,379 @@
 #![cfg(target_os = "linux")]
 
 use thrustmaster_core::{
-    device::{G29InputReport, G29OutputReport, descriptors::G29_HID_DESCRIPTOR},
+    device::{
+        virtual_g29::{BoxFuture, OutputSender, PlatformBackend},
+        G29InputReport, G29OutputReport,
+    },
     config::G29Config,
+    ffb::{ConditionType, Waveform},
     error::{TranslatorError, Result},
+    evdev::{
+        self, EV_ABS, EV_FF, EV_KEY, EV_SYN, SYN_REPORT, FF_CONSTANT, FF_DAMPER, FF_FRICTION, FF_INERTIA, FF_PERIODIC, FF_RAMP, FF_SAW_DOWN,
+        FF_SAW_UP, FF_SPRING, FF_SQUARE, FF_TRIANGLE, G29_ABS_AXES, G29_FF_BITS, G29_FF_EFFECTS_MAX,
+    },
 };
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
 use std::os::unix::io::AsRawFd;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::{info, warn, error, debug};
 
+// uinput ioctl requests from <linux/uinput.h>
+const UINPUT_IOCTL_BASE: u32 = b'U' as u32;
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+const fn ioc(dir: u32, nr: u32, size: usize) -> libc::c_ulong {
+    ((dir << 30) | ((size as u32) << 16) | (UINPUT_IOCTL_BASE << 8) | nr) as libc::c_ulong
+}
+
+const UI_DEV_CREATE: libc::c_ulong = ioc(0, 1, 0);
+const UI_DEV_DESTROY: libc::c_ulong = ioc(0, 2, 0);
+const UI_DEV_SETUP: libc::c_ulong = ioc(IOC_WRITE, 3, std::mem::size_of::<libc::uinput_setup>());
+const UI_ABS_SETUP: libc::c_ulong = ioc(IOC_WRITE, 4, std::mem::size_of::<libc::uinput_abs_setup>());
+const UI_SET_EVBIT: libc::c_ulong = ioc(IOC_WRITE, 100, std::mem::size_of::<libc::c_int>());
+const UI_SET_KEYBIT: libc::c_ulong = ioc(IOC_WRITE, 101, std::mem::size_of::<libc::c_int>());
+const UI_SET_ABSBIT: libc::c_ulong = ioc(IOC_WRITE, 103, std::mem::size_of::<libc::c_int>());
+const UI_SET_FFBIT: libc::c_ulong = ioc(IOC_WRITE, 107, std::mem::size_of::<libc::c_int>());
+const UI_BEGIN_FF_UPLOAD: libc::c_ulong = ioc(IOC_READ | IOC_WRITE, 200, std::mem::size_of::<libc::uinput_ff_upload>());
+const UI_END_FF_UPLOAD: libc::c_ulong = ioc(IOC_WRITE, 201, std::mem::size_of::<libc::uinput_ff_upload>());
+const UI_BEGIN_FF_ERASE: libc::c_ulong = ioc(IOC_READ | IOC_WRITE, 202, std::mem::size_of::<libc::uinput_ff_erase>());
+const UI_END_FF_ERASE: libc::c_ulong = ioc(IOC_WRITE, 203, std::mem::size_of::<libc::uinput_ff_erase>());
+
+const fn ui_get_sysname(len: usize) -> libc::c_ulong {
+    ioc(IOC_READ, 44, len)
+}
+
+// Requests the kernel queues on the uinput fd for force feedback
+const EV_UINPUT: u16 = 0x0101;
+const UI_FF_UPLOAD: u16 = 1;
+const UI_FF_ERASE: u16 = 2;
+
+const BUS_USB: u16 = 0x03;
+
+/// EVIOCSCLOCKID from <linux/input.h>, selects the clock of event timestamps
+const EVIOCSCLOCKID: libc::c_ulong = ((IOC_WRITE << 30) | (4 << 16) | ((b'E' as u32) << 8) | 0xA0) as libc::c_ulong;
+
+/// One report injected into the virtual device and read back from its event node
+#[derive(Debug, Clone, Copy)]
+pub struct LoopbackSample {
+    /// From before the write to uinput until the kernel stamped the events
+    pub injection: Duration,
+    /// From the kernel's stamp until a reader of the event node had the events
+    pub delivery: Duration,
+}
+
+impl LoopbackSample {
+    pub fn total(&self) -> Duration {
+        self.injection + self.delivery
+    }
+}
+
 /// Linux-specific virtual G29 device using uinput
 pub struct LinuxVirtualG29Device {
     config: G29Config,
-    // TODO: Add uinput device handle when uinput crate is available
-    _uinput_fd: Option<i32>,
+    uinput: File,
     device_node: Option<String>,
+    /// Last report written, so only changed axes and buttons are sent
+    last_report: Mutex<Option<G29InputReport>>,
+    ff: Mutex<FfState>,
+}
+
+/// Force feedback effects the game uploaded to the device
+#[derive(Default)]
+struct FfState {
+    effects: HashMap<i16, G29OutputReport>,
+    playing: HashSet<i16>,
+    output: Option<OutputSender>,
+}
+
+impl FfState {
+    /// Send the stored effect `id` to the output channel
+    fn forward(&self, id: i16) {
+        if let (Some(output), Some(report)) = (&self.output, self.effects.get(&id)) {
+            let _ = output.send(report.clone());
+        }
+    }
+
+    /// Stop effect `id` if it is playing, as a zero constant force in its slot
+    fn stop(&mut self, id: i16) {
+        if self.playing.remove(&id) {
+            if let Some(output) = &self.output {
+                let _ = output.send(G29OutputReport::constant_effect(effect_block(id), 0, 0));
+            }
+        }
+    }
 }
 
 impl LinuxVirtualG29Device {
     /// Create a new Linux virtual G29 device
     pub async fn new(config: &G29Config) -> Result<Self> {
         info!("Creating Linux virtual G29 device using uinput");
-        
-        // TODO: Open /dev/uinput
-        // let uinput_file = OpenOptions::new()
-        //     .write(true)
-        //     .open("/dev/uinput")
-        //     .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot open /dev/uinput: {}", e)))?;
-        // 
-        // let fd = uinput_file.as_raw_fd();
-        
-        // TODO: Set up device capabilities
-        // unsafe {
-        //     // Enable event types
-        //     ioctl(fd, libc::UI_SET_EVBIT, libc::EV_KEY);
-        //     ioctl(fd, libc::UI_SET_EVBIT, libc::EV_ABS);
-        //     ioctl(fd, libc::UI_SET_EVBIT, libc::EV_FF);
-        //     
-        //     // Set up absolute axes (steering wheel)
-        //     ioctl(fd, libc::UI_SET_ABSBIT, libc::ABS_X);  // Steering
-        //     ioctl(fd, libc::UI_SET_ABSBIT, libc::ABS_Y);  // Throttle  
-        //     ioctl(fd, libc::UI_SET_ABSBIT, libc::ABS_Z);  // Brake
-        //     ioctl(fd, libc::UI_SET_ABSBIT, libc::ABS_RZ); // Clutch
-        //     
-        //     // Set up buttons
-        //     for i in libc::BTN_JOYSTICK..libc::BTN_JOYSTICK + 24 {
-        //         ioctl(fd, libc::UI_SET_KEYBIT, i);
-        //     }
-        //     
-        //     // Set up force feedback
-        //     ioctl(fd, libc::UI_SET_FFBIT, libc::FF_CONSTANT);
-        //     ioctl(fd, libc::UI_SET_FFBIT, libc::FF_SPRING);
-        //     ioctl(fd, libc::UI_SET_FFBIT, libc::FF_DAMPER);
-        //     ioctl(fd, libc::UI_SET_FFBIT, libc::FF_PERIODIC);
-        // }
-        
-        // TODO: Configure device information
-        // let mut usetup = libc::uinput_setup {
-        //     id: libc::input_id {
-        //         bustype: libc::BUS_USB,
-        //         vendor: config.vid,
-        //         product: config.pid,
-        //         version: 0x0100,
-        //     },
-        //     name: [0; libc::UINPUT_MAX_NAME_SIZE],
-        //     ff_effects_max: 40, // G29 supports up to 40 effects
-        // };
-        // 
-        // // Copy device name
-        // let name_bytes = config.product_string.as_bytes();
-        // let copy_len = std::cmp::min(name_bytes.len(), libc::UINPUT_MAX_NAME_SIZE - 1);
-        // usetup.name[..copy_len].copy_from_slice(&name_bytes[..copy_len]);
-        
-        // TODO: Create the device
-        // unsafe {
-        //     ioctl(fd, libc::UI_DEV_SETUP, &usetup);
-        //     ioctl(fd, libc::UI_DEV_CREATE);
-        // }
-        
-        warn!("uinput integration not yet implemented - using stub");
-        
+
+        let uinput = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot open /dev/uinput: {}", e)))?;
+        let fd = uinput.as_raw_fd();
+
+        // Event types and codes must be enabled before UI_DEV_SETUP
+        for event_type in [EV_KEY, EV_ABS, EV_FF] {
+            ioctl_int(fd, UI_SET_EVBIT, event_type, "UI_SET_EVBIT")?;
+        }
+        // Buttons the emulated wheel lacks are never reported
+        for index in 0..config.target.button_count() {
+            ioctl_int(fd, UI_SET_KEYBIT, evdev::button_code(index), "UI_SET_KEYBIT")?;
+        }
+        for effect in G29_FF_BITS {
+            ioctl_int(fd, UI_SET_FFBIT, effect, "UI_SET_FFBIT")?;
+        }
+        for axis in G29_ABS_AXES {
+            ioctl_int(fd, UI_SET_ABSBIT, axis.code, "UI_SET_ABSBIT")?;
+        }
+
+        // SAFETY: all-zero is a valid uinput_setup
+        let mut setup: libc::uinput_setup = unsafe { std::mem::zeroed() };
+        setup.id.bustype = BUS_USB;
+        (setup.id.vendor, setup.id.product) = config.usb_ids();
+        setup.id.version = 0x0111;
+        setup.ff_effects_max = G29_FF_EFFECTS_MAX;
+        let name = config.product_name().as_bytes();
+        let copy_len = name.len().min(libc::UINPUT_MAX_NAME_SIZE - 1);
+        for (dst, &src) in setup.name.iter_mut().zip(&name[..copy_len]) {
+            *dst = src as libc::c_char;
+        }
+        ioctl_ptr(fd, UI_DEV_SETUP, &setup, "UI_DEV_SETUP")?;
+
+        for axis in G29_ABS_AXES {
+            // SAFETY: all-zero is a valid uinput_abs_setup
+            let mut abs: libc::uinput_abs_setup = unsafe { std::mem::zeroed() };
+            abs.code = axis.code;
+            abs.absinfo.minimum = axis.minimum;
+            abs.absinfo.maximum = axis.maximum;
+            abs.absinfo.fuzz = axis.fuzz;
+            abs.absinfo.flat = axis.flat;
+            ioctl_ptr(fd, UI_ABS_SETUP, &abs, "UI_ABS_SETUP")?;
+        }
+
+        // SAFETY: UI_DEV_CREATE takes no argument
+        if unsafe { libc::ioctl(fd, UI_DEV_CREATE as _) } < 0 {
+            return Err(TranslatorError::virtual_device_error(format!(
+                "UI_DEV_CREATE failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        let device_node = event_node(fd);
+        match &device_node {
+            Some(node) => info!("Virtual {} created at {}", config.product_name(), node),
+            None => warn!("Virtual G29 created but its event node could not be determined"),
+        }
+
         Ok(Self {
             config: config.clone(),
-            _uinput_fd: None,
-            device_node: Some("/dev/input/js0".to_string()), // Stub
+            uinput,
+            device_node,
+            last_report: Mutex::new(None),
+            ff: Mutex::new(FfState::default()),
         })
     }
 
     /// Send input report to the virtual G29 device
     pub async fn send_input(&self, report: G29InputReport) -> Result<()> {
         debug!("Sending input to Linux virtual G29: {:?}", report);
-        
-        // TODO: Convert G29InputReport to Linux input events
-        // let events = vec![
-        //     libc::input_event {
-        //         time: libc::timeval { tv_sec: 0, tv_usec: 0 },
-        //         type_: libc::EV_ABS as u16,
-        //         code: libc::ABS_X as u16,
-        //         value: report.steering as i32 - 32768, // Convert to signed
-        //     },
-        //     libc::input_event {
-        //         time: libc::timeval { tv_sec: 0, tv_usec: 0 },
-        //         type_: libc::EV_ABS as u16,
-        //         code: libc::ABS_Y as u16,
-        //         value: report.throttle as i32,
-        //     },
-        //     // ... more events for brake, clutch, buttons
-        //     libc::input_event {
-        //         time: libc::timeval { tv_sec: 0, tv_usec: 0 },
-        //         type_: libc::EV_SYN as u16,
-        //         code: libc::SYN_REPORT as u16,
-        //         value: 0,
-        //     },
-        // ];
-        
-        // TODO: Write events to uinput device
-        // for event in events {
-        //     unsafe {
-        //         libc::write(fd, &event as *const _ as *const libc::c_void, 
-        //                    std::mem::size_of::<libc::input_event>());
-        //     }
-        // }
-        
-        // For now, just log the report
-        debug!("Would send to uinput: steering={}, throttle={}, brake={}, buttons={:08x}", 
-               report.steering, report.throttle, report.brake, report.buttons);
-        
+
+        let mut last_report = self.last_report.lock().unwrap_or_else(|e| e.into_inner());
+        let events: Vec<libc::input_event> = evdev::report_events(last_report.as_ref(), &report)
+            .into_iter()
+            .map(|event| libc::input_event {
+                // The kernel stamps events written to uinput
+                time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+                type_: event.event_type,
+                code: event.code,
+                value: event.value,
+            })
+            .collect();
+
+        // SAFETY: input_event is plain old data, the slice covers exactly `events`
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                events.as_ptr() as *const u8,
+                events.len() * std::mem::size_of::<libc::input_event>(),
+            )
+        };
+        (&self.uinput)
+            .write_all(bytes)
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot write to uinput: {}", e)))?;
+        *last_report = Some(report);
         Ok(())
     }
 
+    /// Forward force feedback the game plays on the device to `sender`
+    pub fn set_output_sender(&self, sender: OutputSender) {
+        self.ff.lock().unwrap_or_else(|e| e.into_inner()).output = Some(sender);
+    }
+
+    /// Answer force feedback requests as the kernel queues them, until the task is aborted
+    ///
+    /// A game's EVIOCSFF blocks until the uinput owner acknowledges the
+    /// upload, so the fd is watched on its own instead of waiting for the
+    /// next input report, which may be long in coming while the wheel is idle.
+    pub fn spawn_ff_service(self: &Arc<Self>) -> Result<tokio::task::JoinHandle<()>> {
+        let fd = tokio::io::unix::AsyncFd::with_interest(self.uinput.as_raw_fd(), tokio::io::Interest::READABLE)
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot watch uinput for FFB requests: {}", e)))?;
+        let device = Arc::clone(self);
+        Ok(tokio::spawn(async move {
+            loop {
+                let mut ready = match fd.readable().await {
+                    Ok(ready) => ready,
+                    Err(e) => {
+                        error!("Watching uinput for FFB requests failed: {}", e);
+                        return;
+                    }
+                };
+                if let Err(e) = device.service_ff_requests() {
+                    warn!("FFB request not answered: {}", e);
+                }
+                // Drained until it would block, the next request wakes the task again
+                ready.clear_ready();
+            }
+        }))
+    }
+
+    /// Answer force feedback requests queued by the kernel and forward playback
+    ///
+    /// Uploaded effects are kept and sent to the output channel when the game
+    /// plays them, or right away when a playing effect is updated.
+    pub fn service_ff_requests(&self) -> Result<()> {
+        let fd = self.uinput.as_raw_fd();
+        let mut ff = self.ff.lock().unwrap_or_else(|e| e.into_inner());
+        let mut buffer = [0u8; std::mem::size_of::<libc::input_event>()];
+        loop {
+            match (&self.uinput).read(&mut buffer) {
+                Ok(n) if n == buffer.len() => {}
+                Ok(_) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => {
+                    return Err(TranslatorError::virtual_device_error(format!("Cannot read from uinput: {}", e)));
+                }
+            }
+            // SAFETY: buffer holds one complete input_event
+            let event: libc::input_event = unsafe { std::ptr::read_unaligned(buffer.as_ptr() as *const _) };
+
+            match (event.type_, event.code) {
+                (EV_UINPUT, UI_FF_UPLOAD) => {
+                    // SAFETY: all-zero is a valid uinput_ff_upload
+                    let mut upload: libc::uinput_ff_upload = unsafe { std::mem::zeroed() };
+                    upload.request_id = event.value as u32;
+                    ioctl_mut(fd, UI_BEGIN_FF_UPLOAD, &mut upload, "UI_BEGIN_FF_UPLOAD")?;
+                    debug!("FF upload: type {:#x}, id {}", upload.effect.type_, upload.effect.id);
+                    let id = upload.effect.id;
+                    upload.retval = match effect_report(&upload.effect) {
+                        Some(report) => {
+                            ff.effects.insert(id, report);
+                            if ff.playing.contains(&id) {
+                                ff.forward(id);
+                            }
+                            0
+                        }
+                        None => -libc::EINVAL,
+                    };
+                    ioctl_ptr(fd, UI_END_FF_UPLOAD, &upload, "UI_END_FF_UPLOAD")?;
+                }
+                (EV_UINPUT, UI_FF_ERASE) => {
+                    // SAFETY: all-zero is a valid uinput_ff_erase
+                    let mut erase: libc::uinput_ff_erase = unsafe { std::mem::zeroed() };
+                    erase.request_id = event.value as u32;
+                    ioctl_mut(fd, UI_BEGIN_FF_ERASE, &mut erase, "UI_BEGIN_FF_ERASE")?;
+                    debug!("FF erase: id {}", erase.effect_id);
+                    let id = erase.effect_id as i16;
+                    ff.stop(id);
+                    ff.effects.remove(&id);
+                    erase.retval = 0;
+                    ioctl_ptr(fd, UI_END_FF_ERASE, &erase, "UI_END_FF_ERASE")?;
+                }
+                // Playback control, the code is the effect id and the value the repeat count
+                (EV_FF, code) if (code as u32) < G29_FF_EFFECTS_MAX => {
+                    let id = code as i16;
+                    if event.value > 0 {
+                        ff.playing.insert(id);
+                        ff.forward(id);
+                    } else {
+                        ff.stop(id);
+                    }
+                }
+                (EV_FF, code) => debug!("FF setting {:#x} = {} ignored", code, event.value),
+                _ => {}
+            }
+        }
+    }
+
+    /// Inject `samples` reports and time each until it can be read from our own event node
+    ///
+    /// Steering alternates between two positions so every report produces
+    /// events. Timestamps use CLOCK_MONOTONIC on both sides, so the split
+    /// between injection and delivery is exact. Other readers of the node
+    /// (a running game) see the reports too.
+    pub async fn measure_loopback(&self, samples: usize, timeout: Duration) -> Result<Vec<LoopbackSample>> {
+        let node = self
+            .device_node
+            .as_deref()
+            .ok_or_else(|| TranslatorError::virtual_device_error("Event node of the virtual G29 unknown"))?;
+        let reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(node)
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot open {} for reading: {}", node, e)))?;
+        let clock: libc::c_int = libc::CLOCK_MONOTONIC;
+        ioctl_ptr(reader.as_raw_fd(), EVIOCSCLOCKID, &clock, "EVIOCSCLOCKID")?;
+        while read_event(&reader)?.is_some() {}
+
+        let mut report = G29InputReport {
+            report_id: 0x01,
+            steering: 0x8000,
+            throttle: 0,
+            brake: 0,
+            clutch: 0,
+            buttons: 0x0800_0000,
+            unused: [0; 4],
+        };
+        let mut results = Vec::with_capacity(samples);
+        for i in 0..samples {
+            report.steering = if i % 2 == 0 { 0x4000 } else { 0xC000 };
+            let injected = monotonic_now();
+            self.send_input(report).await?;
+
+            let stamped = loop {
+                let Some(event) = read_event(&reader)? else {
+                    let mut poll = libc::pollfd { fd: reader.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+                    // SAFETY: one valid pollfd for the duration of the call
+                    if unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) } <= 0 {
+                        return Err(TranslatorError::virtual_device_error(format!(
+                            "Report {} not read back from {} within {:?}",
+                            i, node, timeout
+                        )));
+                    }
+                    continue;
+                };
+                if event.type_ == EV_SYN && event.code == SYN_REPORT {
+                    break Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1_000);
+                }
+            };
+            let read = monotonic_now();
+            results.push(LoopbackSample {
+                injection: stamped.saturating_sub(injected),
+                delivery: read.saturating_sub(stamped),
+            });
+        }
+        Ok(results)
+    }
+
     /// Get the device node path
     pub fn device_node(&self) -> Option<&str> {
         self.device_node.as_deref()
@@ -140,24 +385,194 @@ impl LinuxVirtualG29Device {
 
     /// Check if the virtual device is available
     pub fn is_available(&self) -> bool {
-        // TODO: Check if device node exists and is accessible
-        self.device_node.is_some()
+        self.device_node
+            .as_deref()
+            .map_or(false, |node| std::path::Path::new(node).exists())
+    }
+
+    /// Configuration the device was created with
+    pub fn config(&self) -> &G29Config {
+        &self.config
+    }
+}
+
+/// uinput virtual G29 with the task answering its FFB requests
+struct UinputBackend {
+    device: Arc<LinuxVirtualG29Device>,
+    ff_service: tokio::task::JoinHandle<()>,
+}
+
+impl PlatformBackend for UinputBackend {
+    fn send_input(&self, report: G29InputReport) -> BoxFuture<'_, Result<()>> {
+        Box::pin(self.device.send_input(report))
+    }
+
+    fn is_connected(&self) -> bool {
+        self.device.is_available()
     }
 }
 
+impl Drop for UinputBackend {
+    fn drop(&mut self) {
+        // Releases the task's handle on the device, which is destroyed with the last one
+        self.ff_service.abort();
+    }
+}
+
+/// `BackendFactory` of the uinput virtual G29, forwarding the FFB games play on it to `output`
+pub fn virtual_backend(config: G29Config, output: OutputSender) -> BoxFuture<'static, Result<Box<dyn PlatformBackend>>> {
+    Box::pin(async move {
+        let device = Arc::new(LinuxVirtualG29Device::new(&config).await?);
+        device.set_output_sender(output);
+        let ff_service = device.spawn_ff_service()?;
+        Ok(Box::new(UinputBackend { device, ff_service }) as Box<dyn PlatformBackend>)
+    })
+}
+
 impl Drop for LinuxVirtualG29Device {
     fn drop(&mut self) {
-        // TODO: Clean up uinput device
-        // if let Some(fd) = self.uinput_fd {
-        //     unsafe {
-        //         ioctl(fd, libc::UI_DEV_DESTROY);
-        //         libc::close(fd);
-        //     }
-        // }
+        // SAFETY: UI_DEV_DESTROY takes no argument; the fd is closed with `uinput`
+        unsafe {
+            libc::ioctl(self.uinput.as_raw_fd(), UI_DEV_DESTROY as _);
+        }
         info!("Linux virtual G29 device dropped");
     }
 }
 
+fn ioctl_int(fd: libc::c_int, request: libc::c_ulong, value: u16, name: &str) -> Result<()> {
+    // SAFETY: the UI_SET_*BIT requests take an int by value
+    if unsafe { libc::ioctl(fd, request as _, value as libc::c_int) } < 0 {
+        return Err(TranslatorError::virtual_device_error(format!(
+            "{}({:#x}) failed: {}",
+            name,
+            value,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+fn ioctl_ptr<T>(fd: libc::c_int, request: libc::c_ulong, value: &T, name: &str) -> Result<()> {
+    // SAFETY: `request` encodes size_of::<T>() and only reads from the pointer
+    if unsafe { libc::ioctl(fd, request as _, value as *const T) } < 0 {
+        return Err(TranslatorError::virtual_device_error(format!(
+            "{} failed: {}",
+            name,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+fn ioctl_mut<T>(fd: libc::c_int, request: libc::c_ulong, value: &mut T, name: &str) -> Result<()> {
+    // SAFETY: `request` encodes size_of::<T>()
+    if unsafe { libc::ioctl(fd, request as _, value as *mut T) } < 0 {
+        return Err(TranslatorError::virtual_device_error(format!(
+            "{} failed: {}",
+            name,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// Effect block index (1-based) of a kernel effect id (0-based)
+fn effect_block(id: i16) -> u8 {
+    (id + 1).clamp(1, G29_FF_EFFECTS_MAX as i16) as u8
+}
+
+/// Output report describing a kernel `ff_effect`, `None` for unsupported types
+///
+/// The union after the common header sits at offset 16 on every ABI, its
+/// members are read from there as laid out in <linux/input.h>.
+fn effect_report(effect: &libc::ff_effect) -> Option<G29OutputReport> {
+    // SAFETY: ff_effect is plain old data
+    let bytes = unsafe {
+        std::slice::from_raw_parts(effect as *const _ as *const u8, std::mem::size_of::<libc::ff_effect>())
+    };
+    let u16_at = |offset: usize| u16::from_ne_bytes([bytes[offset], bytes[offset + 1]]);
+    let i16_at = |offset: usize| i16::from_ne_bytes([bytes[offset], bytes[offset + 1]]);
+    const UNION: usize = 16;
+
+    // Wheels take the sideways share of the force, as hid-lg4ff does
+    let angle = effect.direction as f32 / 65536.0 * std::f32::consts::TAU;
+    let directed = |level: i16| (level as f32 * angle.sin()).round() as i16;
+    let id = effect_block(effect.id);
+    let length_ms = effect.replay.length;
+    match effect.type_ {
+        FF_CONSTANT => Some(G29OutputReport::constant_effect(id, directed(i16_at(UNION)), length_ms)),
+        // Ramps are approximated by their average level
+        FF_RAMP => {
+            let average = (i16_at(UNION) as i32 + i16_at(UNION + 2) as i32) / 2;
+            Some(G29OutputReport::constant_effect(id, directed(average as i16), length_ms))
+        }
+        FF_PERIODIC => {
+            let waveform = match u16_at(UNION) {
+                FF_SQUARE => Waveform::Square,
+                FF_TRIANGLE => Waveform::Triangle,
+                FF_SAW_UP => Waveform::SawtoothUp,
+                FF_SAW_DOWN => Waveform::SawtoothDown,
+                _ => Waveform::Sine,
+            };
+            let period = u16_at(UNION + 2);
+            let magnitude = i16_at(UNION + 4).unsigned_abs();
+            // Kernel phase is a fraction of the period, reports carry degrees
+            let phase = (u16_at(UNION + 8) as u32 * 360 / period.max(1) as u32 % 360) as u16;
+            Some(G29OutputReport::periodic_effect(id, waveform, magnitude, period, phase))
+        }
+        FF_SPRING | FF_DAMPER | FF_INERTIA | FF_FRICTION => {
+            let condition = match effect.type_ {
+                FF_SPRING => ConditionType::Spring,
+                FF_DAMPER => ConditionType::Damper,
+                FF_INERTIA => ConditionType::Inertia,
+                _ => ConditionType::Friction,
+            };
+            // First axis: right/left saturation, then right/left coefficient
+            Some(G29OutputReport::condition_effect(id, condition, i16_at(UNION + 4), i16_at(UNION + 6)))
+        }
+        _ => None,
+    }
+}
+
+/// Next queued event on a non-blocking event node, `None` when there is none
+fn read_event(mut node: &File) -> Result<Option<libc::input_event>> {
+    let mut buffer = [0u8; std::mem::size_of::<libc::input_event>()];
+    match node.read(&mut buffer) {
+        // SAFETY: buffer holds one complete input_event
+        Ok(n) if n == buffer.len() => Ok(Some(unsafe { std::ptr::read_unaligned(buffer.as_ptr() as *const _) })),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(TranslatorError::virtual_device_error(format!("Cannot read event: {}", e))),
+    }
+}
+
+/// CLOCK_MONOTONIC now, comparable with event timestamps after EVIOCSCLOCKID
+fn monotonic_now() -> Duration {
+    // SAFETY: all-zero is a valid timespec
+    let mut now: libc::timespec = unsafe { std::mem::zeroed() };
+    // SAFETY: `now` is valid for writing
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+/// `/dev/input/eventN` node of the device created on `fd`
+fn event_node(fd: libc::c_int) -> Option<String> {
+    let mut sysname = [0u8; 64];
+    // SAFETY: the request length matches the buffer
+    if unsafe { libc::ioctl(fd, ui_get_sysname(sysname.len()) as _, sysname.as_mut_ptr()) } < 0 {
+        return None;
+    }
+    let len = sysname.iter().position(|&b| b == 0).unwrap_or(sysname.len());
+    let sysname = std::str::from_utf8(&sysname[..len]).ok()?;
+
+    std::fs::read_dir(format!("/sys/devices/virtual/input/{}", sysname))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .find(|name| name.starts_with("event"))
+        .map(|name| format!("/dev/input/{}", name))
+}
+
 /// Check if uinput is available and accessible
 pub fn check_uinput_availability() -> Result<bool> {
     info!("Checking uinput availability");
@@ -260,10 +675,21 @@ mod tests {
     #[tokio::test]
     async fn test_virtual_device_creation() {
         let config = G29Config::default();
-        let result = LinuxVirtualG29Device::new(&config).await;
-        
-        // Should succeed with stub implementation
-        assert!(result.is_ok());
+        if std::fs::OpenOptions::new().write(true).open("/dev/uinput").is_err() {
+            // No uinput access in this environment
+            return;
+        }
+        let device = LinuxVirtualG29Device::new(&config).await.unwrap();
+        let report = G29InputReport {
+            report_id: 0x01,
+            steering: 0x8000,
+            throttle: 0,
+            brake: 0,
+            clutch: 0,
+            buttons: 0x0800_0000,
+            unused: [0; 4],
+        };
+        device.send_input(report).await.unwrap();
     }
 
     #[test]
@@ -5,124 +5,266 @@
 #![cfg(target_os = "macos")]
 
 use thrustmaster_core::{
-    device::G29InputReport,
+    device::{
+        encoding::descriptor_for,
+        virtual_g29::{BoxFuture, OutputSender, PlatformBackend},
+        G29InputReport, G29OutputReport, G29ReportEncoder,
+    },
     config::G29Config,
     error::{TranslatorError, Result},
 };
+use core_foundation::base::{CFType, TCFType};
+use core_foundation::data::CFData;
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::number::CFNumber;
+use core_foundation::string::CFString;
+use core_foundation_sys::base::{kCFAllocatorDefault, CFAllocatorRef, CFIndex, CFRelease};
+use core_foundation_sys::dictionary::CFDictionaryRef;
+use core_foundation_sys::runloop::{
+    kCFRunLoopDefaultMode, CFRunLoopGetCurrent, CFRunLoopRef, CFRunLoopRunInMode, CFRunLoopStop,
+};
+use core_foundation_sys::string::CFStringRef;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
 use tracing::{info, warn, error, debug};
 
-/// macOS-specific virtual G29 device using VirtualHIDDevice
+type IOHIDUserDeviceRef = *mut c_void;
+type IOReturn = i32;
+
+const K_IO_RETURN_SUCCESS: IOReturn = 0;
+const K_IOHID_REPORT_TYPE_OUTPUT: u32 = 1;
+
+type IOHIDUserDeviceSetReportCallback = extern "C" fn(
+    refcon: *mut c_void,
+    report_type: u32,
+    report_id: u32,
+    report: *mut u8,
+    report_length: CFIndex,
+) -> IOReturn;
+
+/// Where the set-report callback forwards output reports, empty until a sender is set
+type OutputSlot = Mutex<Option<OutputSender>>;
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOHIDUserDeviceCreate(allocator: CFAllocatorRef, properties: CFDictionaryRef) -> IOHIDUserDeviceRef;
+    fn IOHIDUserDeviceScheduleWithRunLoop(device: IOHIDUserDeviceRef, run_loop: CFRunLoopRef, mode: CFStringRef);
+    fn IOHIDUserDeviceUnscheduleFromRunLoop(device: IOHIDUserDeviceRef, run_loop: CFRunLoopRef, mode: CFStringRef);
+    fn IOHIDUserDeviceRegisterSetReportCallback(
+        device: IOHIDUserDeviceRef,
+        callback: IOHIDUserDeviceSetReportCallback,
+        refcon: *mut c_void,
+    );
+    fn IOHIDUserDeviceHandleReport(device: IOHIDUserDeviceRef, report: *const u8, report_length: CFIndex) -> IOReturn;
+}
+
+/// IOHIDUserDevice handle that may cross threads
+///
+/// IOHIDUserDevice is thread safe for report submission; scheduling and
+/// unscheduling only happen on the run loop thread.
+#[derive(Clone, Copy)]
+struct DeviceHandle(IOHIDUserDeviceRef);
+
+unsafe impl Send for DeviceHandle {}
+unsafe impl Sync for DeviceHandle {}
+
+/// Run loop of the thread servicing the device
+#[derive(Clone, Copy)]
+struct RunLoopHandle(CFRunLoopRef);
+
+unsafe impl Send for RunLoopHandle {}
+unsafe impl Sync for RunLoopHandle {}
+
+/// macOS-specific virtual G29 device using IOHIDUserDevice
 pub struct MacOSVirtualG29Device {
     config: G29Config,
-    // TODO: Add IOHIDUserDevice handle when IOKit bindings are available
-    _virtual_device: Option<()>,
-    device_service: Option<u32>,
+    device: DeviceHandle,
+    encoder: G29ReportEncoder,
+    run_loop: RunLoopHandle,
+    /// Target of the set-report callback, outlives the device's scheduling
+    output: Arc<OutputSlot>,
+    stop: Arc<AtomicBool>,
+    run_loop_thread: Option<JoinHandle<()>>,
 }
 
 impl MacOSVirtualG29Device {
     /// Create a new macOS virtual G29 device
     pub async fn new(config: &G29Config) -> Result<Self> {
-        info!("Creating macOS virtual G29 device using VirtualHIDDevice");
-        
-        // TODO: Create IOHIDUserDevice
-        // let device_properties = CFDictionary::from_CFType_pairs(&[
-        //     (CFString::new("Transport"), CFString::new("USB")),
-        //     (CFString::new("VendorID"), CFNumber::from(config.vid as i32)),
-        //     (CFString::new("ProductID"), CFNumber::from(config.pid as i32)),
-        //     (CFString::new("Product"), CFString::new(&config.product_string)),
-        //     (CFString::new("Manufacturer"), CFString::new(&config.manufacturer_string)),
-        //     (CFString::new("SerialNumber"), CFString::new(&config.serial_number)),
-        //     (CFString::new("ReportDescriptor"), CFData::from_buffer(&G29_HID_DESCRIPTOR)),
-        // ]);
-        
-        // TODO: Create the virtual HID device
-        // let virtual_device = IOHIDUserDeviceCreate(
-        //     kCFAllocatorDefault,
-        //     device_properties.as_concrete_TypeRef(),
-        // );
-        
-        // if virtual_device.is_null() {
-        //     return Err(TranslatorError::virtual_device_error("Failed to create IOHIDUserDevice"));
-        // }
-        
-        // TODO: Schedule with run loop
-        // IOHIDUserDeviceScheduleWithRunLoop(
-        //     virtual_device,
-        //     CFRunLoopGetCurrent(),
-        //     kCFRunLoopDefaultMode,
-        // );
-        
-        warn!("VirtualHIDDevice integration not yet implemented - using stub");
-        
+        info!("Creating macOS virtual G29 device using IOHIDUserDevice");
+
+        let encoder = G29ReportEncoder::for_config(config)?;
+        let (vid, pid) = config.usb_ids();
+        let properties = CFDictionary::<CFString, CFType>::from_CFType_pairs(&[
+            (CFString::new("Transport"), CFString::new("USB").as_CFType()),
+            (CFString::new("VendorID"), CFNumber::from(vid as i32).as_CFType()),
+            (CFString::new("ProductID"), CFNumber::from(pid as i32).as_CFType()),
+            (CFString::new("Product"), CFString::new(config.product_name()).as_CFType()),
+            (CFString::new("Manufacturer"), CFString::new(&config.manufacturer_string).as_CFType()),
+            (CFString::new("SerialNumber"), CFString::new(&config.serial_number).as_CFType()),
+            (CFString::new("ReportDescriptor"), CFData::from_buffer(descriptor_for(config.target, config.mode)).as_CFType()),
+        ]);
+
+        // SAFETY: the dictionary outlives the call, which copies what it keeps
+        let device = unsafe { IOHIDUserDeviceCreate(kCFAllocatorDefault, properties.as_concrete_TypeRef()) };
+        if device.is_null() {
+            return Err(TranslatorError::virtual_device_error(
+                "IOHIDUserDeviceCreate failed. The binary needs the com.apple.developer.hid.virtual.device \
+                 entitlement, or must run as root with SIP allowing virtual HID devices",
+            ));
+        }
+        let device = DeviceHandle(device);
+
+        // Callbacks must be registered before the device is scheduled
+        let output: Arc<OutputSlot> = Arc::new(Mutex::new(None));
+        // SAFETY: `output` is kept alive in self until after the device is released
+        unsafe {
+            IOHIDUserDeviceRegisterSetReportCallback(device.0, set_report, Arc::as_ptr(&output) as *mut c_void);
+        }
+
+        // Host requests (feature reports, FFB output) are delivered on the
+        // run loop the device is scheduled with, so it gets a thread of its own
+        let (run_loop_tx, run_loop_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let run_loop_thread = std::thread::Builder::new()
+            .name("tm-g29-hid".to_string())
+            .spawn(move || {
+                let device = device;
+                // SAFETY: the device stays valid until the owner joins this thread
+                unsafe {
+                    let run_loop = CFRunLoopGetCurrent();
+                    IOHIDUserDeviceScheduleWithRunLoop(device.0, run_loop, kCFRunLoopDefaultMode);
+                    let _ = run_loop_tx.send(RunLoopHandle(run_loop));
+                    // Bounded runs so a stop requested before the loop started is still seen
+                    while !thread_stop.load(Ordering::Relaxed) {
+                        CFRunLoopRunInMode(kCFRunLoopDefaultMode, 0.25, 0);
+                    }
+                    IOHIDUserDeviceUnscheduleFromRunLoop(device.0, run_loop, kCFRunLoopDefaultMode);
+                }
+            })
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot start HID run loop thread: {}", e)))?;
+
+        let run_loop = match run_loop_rx.recv() {
+            Ok(run_loop) => run_loop,
+            Err(_) => {
+                let _ = run_loop_thread.join();
+                // SAFETY: created above and no longer scheduled
+                unsafe { CFRelease(device.0 as *const c_void) };
+                return Err(TranslatorError::virtual_device_error("HID run loop thread exited during setup"));
+            }
+        };
+
+        info!("Virtual G29 created: {:?} mode, {}-byte reports", config.mode, encoder.report_len());
         Ok(Self {
             config: config.clone(),
-            _virtual_device: None,
-            device_service: Some(12345), // Stub service ID
+            device,
+            encoder,
+            run_loop,
+            output,
+            stop,
+            run_loop_thread: Some(run_loop_thread),
         })
     }
 
     /// Send input report to the virtual G29 device
     pub async fn send_input(&self, report: G29InputReport) -> Result<()> {
         debug!("Sending input to macOS virtual G29: {:?}", report);
-        
-        // TODO: Convert G29InputReport to HID report format
-        // let mut hid_report = [0u8; 28]; // G29 input report size
-        // hid_report[0] = report.report_id;
-        // hid_report[1] = (report.steering & 0xFF) as u8;
-        // hid_report[2] = (report.steering >> 8) as u8;
-        // hid_report[3] = (report.throttle & 0xFF) as u8;
-        // hid_report[4] = (report.throttle >> 8) as u8;
-        // hid_report[5] = (report.brake & 0xFF) as u8;
-        // hid_report[6] = (report.brake >> 8) as u8;
-        // hid_report[7] = (report.clutch & 0xFF) as u8;
-        // hid_report[8] = (report.clutch >> 8) as u8;
-        // // ... encode buttons and remaining fields
-        
-        // TODO: Send to virtual device
-        // let report_data = CFData::from_buffer(&hid_report);
-        // let result = IOHIDUserDeviceHandleReport(
-        //     virtual_device,
-        //     report_data.as_concrete_TypeRef(),
-        // );
-        
-        // if result != kIOReturnSuccess {
-        //     return Err(TranslatorError::virtual_device_error("Failed to send HID report"));
-        // }
-        
-        // For now, just log the report
-        debug!("Would send to VirtualHIDDevice: steering={}, throttle={}, brake={}, buttons={:08x}", 
-               report.steering, report.throttle, report.brake, report.buttons);
-        
+
+        let hid_report = self.encoder.encode(&report);
+        // SAFETY: the device is valid for the lifetime of self and the buffer for the call
+        let result = unsafe {
+            IOHIDUserDeviceHandleReport(self.device.0, hid_report.as_ptr(), hid_report.len() as CFIndex)
+        };
+        if result != K_IO_RETURN_SUCCESS {
+            return Err(TranslatorError::virtual_device_error(format!(
+                "IOHIDUserDeviceHandleReport failed: {:#010x}",
+                result
+            )));
+        }
+
         Ok(())
     }
 
-    /// Get the device service ID
-    pub fn service_id(&self) -> Option<u32> {
-        self.device_service
+    /// Forward output reports the host writes to the device (FFB) to `sender`
+    pub fn set_output_sender(&self, sender: OutputSender) {
+        *self.output.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
     }
 
     /// Check if the virtual device is active
     pub fn is_active(&self) -> bool {
-        // TODO: Check IOHIDUserDevice status
-        self.device_service.is_some()
+        self.run_loop_thread.as_ref().map_or(false, |thread| !thread.is_finished())
+    }
+
+    /// Configuration the device was created with
+    pub fn config(&self) -> &G29Config {
+        &self.config
     }
 }
 
+impl PlatformBackend for MacOSVirtualG29Device {
+    fn send_input(&self, report: G29InputReport) -> BoxFuture<'_, Result<()>> {
+        Box::pin(MacOSVirtualG29Device::send_input(self, report))
+    }
+
+    fn is_connected(&self) -> bool {
+        self.is_active()
+    }
+}
+
+/// `BackendFactory` of the IOHIDUserDevice virtual G29, forwarding the output reports games write to `output`
+pub fn virtual_backend(config: G29Config, output: OutputSender) -> BoxFuture<'static, Result<Box<dyn PlatformBackend>>> {
+    Box::pin(async move {
+        let device = MacOSVirtualG29Device::new(&config).await?;
+        device.set_output_sender(output);
+        Ok(Box::new(device) as Box<dyn PlatformBackend>)
+    })
+}
+
 impl Drop for MacOSVirtualG29Device {
     fn drop(&mut self) {
-        // TODO: Clean up IOHIDUserDevice
-        // if let Some(device) = self.virtual_device {
-        //     IOHIDUserDeviceUnscheduleFromRunLoop(
-        //         device,
-        //         CFRunLoopGetCurrent(),
-        //         kCFRunLoopDefaultMode,
-        //     );
-        //     CFRelease(device as *const c_void);
-        // }
+        // SAFETY: stopping a run loop from another thread is allowed; the
+        // thread unschedules the device before exiting, then it is released
+        self.stop.store(true, Ordering::Relaxed);
+        unsafe { CFRunLoopStop(self.run_loop.0) };
+        if let Some(thread) = self.run_loop_thread.take() {
+            if thread.join().is_err() {
+                warn!("HID run loop thread panicked");
+            }
+        }
+        unsafe { CFRelease(self.device.0 as *const c_void) };
         info!("macOS virtual G29 device dropped");
     }
 }
 
+/// Set-report callback, runs on the HID run loop thread
+extern "C" fn set_report(
+    refcon: *mut c_void,
+    report_type: u32,
+    report_id: u32,
+    report: *mut u8,
+    report_length: CFIndex,
+) -> IOReturn {
+    if report_type != K_IOHID_REPORT_TYPE_OUTPUT || report.is_null() {
+        return K_IO_RETURN_SUCCESS;
+    }
+    // SAFETY: refcon is the device's OutputSlot and `report` holds `report_length` bytes for this call
+    let (slot, bytes) = unsafe {
+        (&*(refcon as *const OutputSlot), std::slice::from_raw_parts(report, report_length.max(0) as usize))
+    };
+
+    // Numbered reports carry their ID as the first byte
+    let data = match bytes.split_first() {
+        Some((&first, rest)) if report_id != 0 && first as u32 == report_id => rest,
+        _ => bytes,
+    };
+    if let Some(sender) = slot.lock().ok().and_then(|sender| sender.clone()) {
+        let _ = sender.send(G29OutputReport { report_id: report_id as u8, data: data.to_vec() });
+    }
+    K_IO_RETURN_SUCCESS
+}
+
 /// Check if VirtualHIDDevice framework is available
 pub fn check_virtual_hid_availability() -> Result<bool> {
     info!("Checking VirtualHIDDevice framework availability");
@@ -250,9 +392,11 @@ mod tests {
     async fn test_virtual_device_creation() {
         let config = G29Config::default();
         let result = MacOSVirtualG29Device::new(&config).await;
-        
-        // Should succeed with stub implementation
-        assert!(result.is_ok());
+
+        // Creation needs the virtual HID entitlement, which test binaries lack
+        if let Ok(device) = result {
+            assert!(device.is_active());
+        }
     }
 
     #[test]
@@ -5,79 +5,124 @@
 #![cfg(windows)]
 
 use thrustmaster_core::{
-    device::{G29InputReport, G29OutputReport},
+    convert::{offset_to_signed, u10_to_u16, u10_to_u8},
+    device::{
+        virtual_g29::{BoxFuture, OutputSender, PlatformBackend},
+        G29InputReport, G29OutputReport, HatDirection,
+    },
+    ffb::Waveform,
     config::G29Config,
     error::{TranslatorError, Result},
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
 use tracing::{info, warn, error, debug};
+use vigem_client::{Client, TargetId, XButtons, XGamepad, Xbox360Wired};
+
+/// Xbox button for each of the first G29 buttons, `0` where the G29 button
+/// has no counterpart (the pedal triggers carry LT/RT)
+const XBOX_BUTTONS: [u16; 13] = [
+    XButtons::X,      // 0 X button
+    XButtons::A,      // 1 A button
+    XButtons::B,      // 2 B button
+    XButtons::Y,      // 3 Y button
+    XButtons::LB,     // 4 left bumper
+    XButtons::RB,     // 5 right bumper
+    0,                // 6 LT, left trigger is the brake
+    0,                // 7 RT, right trigger is the throttle
+    XButtons::BACK,   // 8 View/Back
+    XButtons::START,  // 9 Menu/Start
+    XButtons::LTHUMB, // 10 left stick click
+    XButtons::RTHUMB, // 11 right stick click
+    XButtons::GUIDE,  // 12
+];
+
+/// Effect block the Xbox rumble motors are forwarded in
+const RUMBLE_EFFECT: u8 = 1;
 
 /// Windows-specific virtual G29 device using ViGEm Bus
+///
+/// ViGEm only emulates Xbox 360 and DualShock 4 pads, so the wheel is an
+/// Xbox 360 target carrying the G29 VID/PID: steering on the left stick X,
+/// throttle and brake on the triggers, clutch on the right stick Y.
 pub struct WindowsVirtualG29Device {
     config: G29Config,
-    // TODO: Add ViGEm client and target device fields when vigem-client crate is available
-    _vigem_client: Option<()>,
-    _vigem_target: Option<()>,
+    target: Mutex<Xbox360Wired<Client>>,
+    /// Receives rumble requests from the bus, ends when the target is removed
+    notification_thread: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl WindowsVirtualG29Device {
     /// Create a new Windows virtual G29 device
     pub async fn new(config: &G29Config) -> Result<Self> {
         info!("Creating Windows virtual G29 device");
-        
-        // TODO: Initialize ViGEm Bus client
-        // let vigem_client = vigem_alloc();
-        // if vigem_client.is_null() {
-        //     return Err(TranslatorError::virtual_device_error("Failed to allocate ViGEm client"));
-        // }
-        
-        // TODO: Connect to ViGEm Bus
-        // let result = vigem_connect(vigem_client);
-        // if !VIGEM_SUCCESS(result) {
-        //     return Err(TranslatorError::virtual_device_error("Failed to connect to ViGEm Bus"));
-        // }
-        
-        // TODO: Create G29 target device
-        // let target = vigem_target_x360_alloc(); // Will need custom G29 target type
-        // vigem_target_set_vid(target, config.vid);
-        // vigem_target_set_pid(target, config.pid);
-        
-        // TODO: Add target to ViGEm Bus
-        // let result = vigem_target_add(vigem_client, target);
-        // if !VIGEM_SUCCESS(result) {
-        //     return Err(TranslatorError::virtual_device_error("Failed to add G29 target"));
-        // }
-        
-        warn!("ViGEm integration not yet implemented - using stub");
-        
+
+        let client = Client::connect().map_err(|e| {
+            TranslatorError::virtual_device_error(format!(
+                "Cannot connect to the ViGEm Bus driver ({}). Install it from https://github.com/ViGEm/ViGEmBus/releases",
+                e
+            ))
+        })?;
+
+        let (vendor_id, product_id) = config.usb_ids();
+        let id = TargetId { vendor_id, product_id };
+        let mut target = Xbox360Wired::new(client, id);
+        target
+            .plugin()
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Failed to add G29 target: {}", e)))?;
+        target
+            .wait_ready()
+            .map_err(|e| TranslatorError::virtual_device_error(format!("G29 target did not become ready: {}", e)))?;
+
+        info!("Virtual G29 attached to ViGEm Bus as {:04X}:{:04X}", vendor_id, product_id);
         Ok(Self {
             config: config.clone(),
-            _vigem_client: None,
-            _vigem_target: None,
+            target: Mutex::new(target),
+            notification_thread: Mutex::new(None),
         })
     }
 
     /// Send input report to the virtual G29 device
     pub async fn send_input(&self, report: G29InputReport) -> Result<()> {
         debug!("Sending input to Windows virtual G29: {:?}", report);
-        
-        // TODO: Convert G29InputReport to ViGEm format and send
-        // let vigem_report = convert_g29_to_vigem(report);
-        // vigem_target_x360_update(vigem_client, vigem_target, vigem_report);
-        
-        // For now, just log the report
-        debug!("Would send to ViGEm: steering={}, throttle={}, brake={}, buttons={:08x}", 
-               report.steering, report.throttle, report.brake, report.buttons);
-        
+
+        let gamepad = to_gamepad(&report);
+        let mut target = self.target.lock().unwrap_or_else(|e| e.into_inner());
+        target
+            .update(&gamepad)
+            .map_err(|e| TranslatorError::virtual_device_error(format!("ViGEm update failed: {}", e)))
+    }
+
+    /// Forward the rumble games request from the pad to `sender`
+    ///
+    /// XInput has no force feedback beyond the two rumble motors, so their
+    /// strength is passed on as a sine vibration: slow for the large motor,
+    /// fast for the small one. Uses vigem-client's target notifications
+    /// (`unstable_xtarget_notification` feature).
+    pub fn set_output_sender(&self, sender: OutputSender) -> Result<()> {
+        let mut target = self.target.lock().unwrap_or_else(|e| e.into_inner());
+        let notification = target
+            .request_notification()
+            .map_err(|e| TranslatorError::virtual_device_error(format!("Cannot subscribe to rumble requests: {}", e)))?;
+
+        let thread = notification.spawn_thread(move |_, data| {
+            debug!("Rumble request: large {} small {}", data.large_motor, data.small_motor);
+            let strength = data.large_motor.max(data.small_motor);
+            let report = if strength == 0 {
+                G29OutputReport::constant_effect(RUMBLE_EFFECT, 0, 0)
+            } else {
+                let period_ms = if data.large_motor >= data.small_motor { 40 } else { 10 };
+                G29OutputReport::periodic_effect(RUMBLE_EFFECT, Waveform::Sine, strength as u16 * 257, period_ms, 0)
+            };
+            let _ = sender.send(report);
+        });
+        *self.notification_thread.lock().unwrap_or_else(|e| e.into_inner()) = Some(thread);
         Ok(())
     }
 
     /// Check if the virtual device is connected
     pub fn is_connected(&self) -> bool {
-        // TODO: Check ViGEm target status
-        // vigem_target_is_attached(vigem_target)
-        true // Stub implementation
+        self.target.lock().map_or(false, |target| target.is_attached())
     }
 
     /// Get the virtual device path (for debugging)
@@ -86,21 +131,75 @@ impl WindowsVirtualG29Device {
     }
 }
 
+impl PlatformBackend for WindowsVirtualG29Device {
+    fn send_input(&self, report: G29InputReport) -> BoxFuture<'_, Result<()>> {
+        Box::pin(WindowsVirtualG29Device::send_input(self, report))
+    }
+
+    fn is_connected(&self) -> bool {
+        WindowsVirtualG29Device::is_connected(self)
+    }
+}
+
+/// `BackendFactory` of the ViGEm virtual G29, forwarding the rumble games request to `output`
+///
+/// Without rumble notifications the pad still works, only the vibration is lost.
+pub fn virtual_backend(config: G29Config, output: OutputSender) -> BoxFuture<'static, Result<Box<dyn PlatformBackend>>> {
+    Box::pin(async move {
+        let device = WindowsVirtualG29Device::new(&config).await?;
+        if let Err(e) = device.set_output_sender(output) {
+            warn!("Rumble from games is not forwarded: {}", e);
+        }
+        Ok(Box::new(device) as Box<dyn PlatformBackend>)
+    })
+}
+
 impl Drop for WindowsVirtualG29Device {
     fn drop(&mut self) {
-        // TODO: Clean up ViGEm resources
-        // if let Some(target) = self.vigem_target {
-        //     vigem_target_remove(vigem_client, target);
-        //     vigem_target_free(target);
-        // }
-        // if let Some(client) = self.vigem_client {
-        //     vigem_disconnect(client);
-        //     vigem_free(client);
-        // }
+        if let Ok(target) = self.target.get_mut() {
+            if target.is_attached() {
+                if let Err(e) = target.unplug() {
+                    warn!("Failed to remove G29 target: {}", e);
+                }
+            }
+        }
+        // The notification thread fails its next wait and exits on its own
+        // once the target is gone, it is not joined to keep drop from blocking
         info!("Windows virtual G29 device dropped");
     }
 }
 
+/// Xbox 360 pad state for a G29 report
+fn to_gamepad(report: &G29InputReport) -> XGamepad {
+    let mut buttons = match report.hat() {
+        HatDirection::North => XButtons::UP,
+        HatDirection::NorthEast => XButtons::UP | XButtons::RIGHT,
+        HatDirection::East => XButtons::RIGHT,
+        HatDirection::SouthEast => XButtons::DOWN | XButtons::RIGHT,
+        HatDirection::South => XButtons::DOWN,
+        HatDirection::SouthWest => XButtons::DOWN | XButtons::LEFT,
+        HatDirection::West => XButtons::LEFT,
+        HatDirection::NorthWest => XButtons::UP | XButtons::LEFT,
+        HatDirection::Centered => 0,
+    };
+    for (index, &xbox) in XBOX_BUTTONS.iter().enumerate() {
+        if report.buttons & (1 << index) != 0 {
+            buttons |= xbox;
+        }
+    }
+
+    // 10-bit pedals onto 8-bit triggers and a signed stick axis
+    XGamepad {
+        buttons: XButtons { raw: buttons },
+        left_trigger: u10_to_u8(report.brake),
+        right_trigger: u10_to_u8(report.throttle),
+        thumb_lx: offset_to_signed(report.steering),
+        thumb_ly: 0,
+        thumb_rx: 0,
+        thumb_ry: offset_to_signed(u10_to_u16(report.clutch)),
+    }
+}
+
 /// Windows-specific device enumeration
 pub fn enumerate_thrustmaster_devices() -> Result<Vec<WindowsThrustmasterDevice>> {
     info!("Enumerating Thrustmaster devices on Windows");
@@ -126,12 +225,14 @@ pub struct WindowsThrustmasterDevice {
 /// Check if ViGEm Bus driver is installed and accessible
 pub fn check_vigem_availability() -> Result<bool> {
     info!("Checking ViGEm Bus driver availability");
-    
-    // TODO: Check if ViGEm Bus driver is installed
-    // This would involve checking the Windows service or driver registry entries
-    
-    warn!("ViGEm availability check not yet implemented");
-    Ok(false) // Conservative default
+
+    match Client::connect() {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            warn!("ViGEm Bus driver not reachable: {}", e);
+            Ok(false)
+        }
+    }
 }
 
 /// Install or prompt for ViGEm Bus driver installation
@@ -158,16 +259,36 @@ mod tests {
     async fn test_virtual_device_creation() {
         let config = G29Config::default();
         let result = WindowsVirtualG29Device::new(&config).await;
-        
-        // Should succeed with stub implementation
-        assert!(result.is_ok());
+
+        // Only machines with the ViGEm Bus driver can create the target
+        assert_eq!(result.is_ok(), check_vigem_availability().unwrap());
+        if let Ok(device) = result {
+            assert!(device.is_connected());
+        }
+    }
+
+    #[test]
+    fn test_gamepad_conversion() {
+        let report = G29InputReport {
+            report_id: 0x01,
+            steering: 0xFFFF,
+            throttle: 1023,
+            brake: 0,
+            clutch: 0,
+            buttons: (2 << 24) | 0b1,
+            unused: [0; 4],
+        };
+        let gamepad = to_gamepad(&report);
+        assert_eq!(gamepad.thumb_lx, i16::MAX);
+        assert_eq!(gamepad.right_trigger, 255);
+        assert_eq!(gamepad.left_trigger, 0);
+        assert_eq!(gamepad.thumb_ry, i16::MIN);
+        assert_eq!(gamepad.buttons.raw, XButtons::X | XButtons::RIGHT);
     }
 
     #[test]
     fn test_vigem_availability_check() {
         let result = check_vigem_availability();
         assert!(result.is_ok());
-        // Returns false in stub implementation
-        assert_eq!(result.unwrap(), false);
     }
 } 
\ No newline at end of file